@@ -1,27 +1,61 @@
 use std::path::PathBuf;
 
-use clap::{Args, Parser};
+use clap::{Args, Parser, ValueEnum};
+use lazuli::system::BootMode;
 
+use crate::config::{self, AudioBackend};
+
+/// JIT flags. These override the config files when present.
 #[derive(Args, Debug)]
 pub struct PpcjitConfig {
-    /// Maximum number of instructions per block
-    #[arg(visible_alias("ipb"), long, default_value_t = 128)]
-    pub instr_per_block: u32,
+    /// Maximum number of instructions per block [default: 128]
+    #[arg(visible_alias("ipb"), long)]
+    pub instr_per_block: Option<u32>,
     /// Whether to treat syscalls as no-ops
-    #[arg(long, default_value_t = false)]
+    #[arg(long)]
     pub nop_syscalls: bool,
     /// Whether to ignore the FPU enabled bit in MSR
-    #[arg(long, default_value_t = false)]
+    #[arg(long)]
     pub force_fpu: bool,
     /// Whether to ignore unimplemented instructions
-    #[arg(long, default_value_t = false)]
+    #[arg(long)]
     pub ignore_unimplemented_inst: bool,
     /// Whether to clear the JIT block cache
     #[arg(long, default_value_t = false)]
     pub clear_cache: bool,
+    /// Whether to disable the JIT block cache, compiling every block from scratch
+    #[arg(long)]
+    pub no_block_cache: bool,
     /// Whether to perform round-to-single operations
-    #[arg(long, default_value_t = false)]
+    #[arg(long)]
     pub round_to_single: bool,
+    /// Whether to update FPSCR exception bits on floating point arithmetic
+    #[arg(long)]
+    pub float_exceptions: bool,
+    /// Whether to run memset and memcpy-like loops in bulk
+    #[arg(long)]
+    pub bulk_memory_idioms: bool,
+}
+
+/// How to boot the system.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Boot {
+    /// Boot through the IPL ROM
+    Ipl,
+    /// Boot the sideloaded executable directly
+    Dol,
+    /// Boot the disc through the IPL HLE
+    Disc,
+}
+
+impl From<Boot> for BootMode {
+    fn from(value: Boot) -> Self {
+        match value {
+            Boot::Ipl => Self::Ipl,
+            Boot::Dol => Self::DirectDol,
+            Boot::Disc => Self::DiscApploader,
+        }
+    }
 }
 
 /// Lazuli: GameCube emulator
@@ -33,6 +67,12 @@ pub struct Config {
     /// Path to the IPL ROM
     #[arg(long)]
     pub ipl: Option<PathBuf>,
+    /// Path to a dump of the DSP instruction ROM (dsp_rom.bin)
+    #[arg(long)]
+    pub dsp_rom: Option<PathBuf>,
+    /// Path to a dump of the DSP coefficient ROM (dsp_coef.bin)
+    #[arg(long)]
+    pub dsp_coef: Option<PathBuf>,
     /// Path to the ROM to load and execute
     ///
     /// Supported formats are .iso and .rvz. To sideload executables, use the `exec` argument.
@@ -40,7 +80,7 @@ pub struct Config {
     pub rom: Option<PathBuf>,
     /// Path to the executable to sideload and execute
     ///
-    /// Supported format is .dol.
+    /// Supported formats are .dol and .elf.
     #[arg(long)]
     pub exec: Option<PathBuf>,
     /// Path to a file to use as a debug info provider
@@ -48,10 +88,66 @@ pub struct Config {
     /// Supported formats are .elf and .map.
     #[arg(long)]
     pub debug: Option<PathBuf>,
-    /// Whether to LLE the IPL instead of HLEing it for loading games
-    #[arg(long, default_value_t = false)]
-    pub ipl_lle: bool,
+    /// How to boot the system
+    ///
+    /// Defaults to `dol` if an executable is given, `disc` if a ROM is given and `ipl` otherwise.
+    #[arg(long)]
+    pub boot: Option<Boot>,
     /// Whether to start running the emulator right away
     #[arg(short, long, default_value_t = false)]
     pub run: bool,
+    /// Whether to run the DSP on a dedicated thread
+    #[arg(long)]
+    pub dsp_thread: bool,
+    /// Whether to make runs independent of the host, so that they can be reproduced
+    #[arg(long)]
+    pub deterministic: bool,
+    /// Whether to disable EFB multisampling
+    #[arg(long)]
+    pub no_msaa: bool,
+    /// Whether to disable audio output
+    #[arg(long)]
+    pub no_audio: bool,
+    /// Path to a file to capture the render actions into, for offline replaying
+    #[arg(long)]
+    pub capture_actions: Option<PathBuf>,
+    /// Whether to print the effective configuration, along with the source of each setting
+    #[arg(long, default_value_t = false)]
+    pub print_config: bool,
+}
+
+impl Config {
+    /// Returns the boot mode selected by the CLI flags.
+    pub fn boot_mode(&self) -> BootMode {
+        match self.boot {
+            Some(boot) => boot.into(),
+            None if self.exec.is_some() => BootMode::DirectDol,
+            None if self.rom.is_some() => BootMode::DiscApploader,
+            None => BootMode::Ipl,
+        }
+    }
+
+    /// Returns the configuration layer defined by the CLI flags.
+    pub fn layer(&self) -> config::Layer {
+        let flag = |set: bool, value| set.then_some(value);
+
+        let mut layer = config::Layer::default();
+        layer.ppcjit.instr_per_block = self.ppcjit.instr_per_block;
+        layer.ppcjit.nop_syscalls = flag(self.ppcjit.nop_syscalls, true);
+        layer.ppcjit.force_fpu = flag(self.ppcjit.force_fpu, true);
+        layer.ppcjit.ignore_unimplemented_inst = flag(self.ppcjit.ignore_unimplemented_inst, true);
+        layer.ppcjit.round_to_single = flag(self.ppcjit.round_to_single, true);
+        layer.ppcjit.float_exceptions = flag(self.ppcjit.float_exceptions, true);
+        layer.ppcjit.bulk_memory_idioms = flag(self.ppcjit.bulk_memory_idioms, true);
+        layer.ppcjit.block_cache = flag(self.ppcjit.no_block_cache, false);
+        layer.dsp.threaded = flag(self.dsp_thread, true);
+        layer.system.deterministic = flag(self.deterministic, true);
+        layer.renderer.msaa = flag(self.no_msaa, false);
+        layer.audio.backend = flag(self.no_audio, AudioBackend::None);
+        layer.paths.ipl = self.ipl.clone().map(Some);
+        layer.paths.dsp_rom = self.dsp_rom.clone().map(Some);
+        layer.paths.dsp_coef = self.dsp_coef.clone().map(Some);
+
+        layer
+    }
 }
@@ -0,0 +1,461 @@
+//! Layered emulator configuration.
+//!
+//! Settings are resolved from the following layers, in increasing order of precedence: defaults,
+//! the user config file, settings detected from the game (see [`crate::heuristics`]), the per-game
+//! config file and CLI flags. Each layer is a [`Layer`], where every setting is optional, and the
+//! result of resolving them is a [`Config`].
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use eyre_pretty::eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    User,
+    Detected,
+    Game,
+    Cli,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Default => "default",
+            Self::User => "user",
+            Self::Detected => "detected",
+            Self::Game => "game",
+            Self::Cli => "cli",
+        })
+    }
+}
+
+/// Audio backend to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackend {
+    Cpal,
+    None,
+}
+
+/// When to skip frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameSkipMode {
+    /// Every frame is presented.
+    Off,
+    /// Frames are skipped while emulation consistently falls behind real time.
+    Auto,
+    /// A fixed amount of frames is always skipped.
+    Fixed,
+}
+
+/// A memory card slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardSlot {
+    A,
+    B,
+}
+
+macro_rules! settings {
+    ($(
+        $(#[doc = $section_doc:literal])*
+        $section:ident: $Section:ident, $SectionLayer:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $field:ident: $ty:ty = $default:expr,
+            )*
+        }
+    )*) => {
+        $(
+            $(#[doc = $section_doc])*
+            #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+            pub struct $Section {
+                $(
+                    $(#[doc = $doc])*
+                    pub $field: $ty,
+                )*
+            }
+
+            impl Default for $Section {
+                fn default() -> Self {
+                    Self {
+                        $($field: $default,)*
+                    }
+                }
+            }
+
+            $(#[doc = $section_doc])*
+            #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+            #[serde(default, deny_unknown_fields)]
+            pub struct $SectionLayer {
+                $(
+                    $(#[doc = $doc])*
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    pub $field: Option<$ty>,
+                )*
+            }
+        )*
+
+        /// Resolved settings.
+        #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+        pub struct Settings {
+            $(pub $section: $Section,)*
+        }
+
+        /// A configuration layer, as stored in a config file.
+        #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        pub struct Layer {
+            $(pub $section: $SectionLayer,)*
+        }
+
+        impl Config {
+            fn apply(&mut self, source: Source, layer: &Layer) {
+                $($(
+                    if let Some(value) = &layer.$section.$field {
+                        self.settings.$section.$field = value.clone();
+                        self.sources.insert(
+                            concat!(stringify!($section), ".", stringify!($field)),
+                            source,
+                        );
+                    }
+                )*)*
+            }
+
+            /// Returns a human readable dump of every setting, along with its source.
+            pub fn effective(&self) -> String {
+                let mut out = String::new();
+                $($(
+                    let key = concat!(stringify!($section), ".", stringify!($field));
+                    _ = writeln!(
+                        out,
+                        "{key} = {:?} ({})",
+                        self.settings.$section.$field,
+                        self.source(key),
+                    );
+                )*)*
+
+                out
+            }
+        }
+    };
+}
+
+settings! {
+    /// PowerPC JIT settings.
+    ppcjit: Ppcjit, PpcjitLayer {
+        /// Maximum number of instructions per block.
+        instr_per_block: u32 = 128,
+        /// Whether to treat syscalls as no-ops.
+        nop_syscalls: bool = false,
+        /// Whether to ignore the FPU enabled bit in MSR.
+        force_fpu: bool = false,
+        /// Whether to ignore unimplemented instructions.
+        ignore_unimplemented_inst: bool = false,
+        /// Whether to perform round-to-single operations.
+        round_to_single: bool = false,
+        /// Whether to update FPSCR exception bits on floating point arithmetic.
+        float_exceptions: bool = false,
+        /// Whether memset and memcpy-like loops are run in bulk by the host.
+        bulk_memory_idioms: bool = false,
+        /// Whether compiled blocks are kept in a cache across runs, so that later runs of the
+        /// same code start faster.
+        block_cache: bool = true,
+    }
+
+    /// DSP settings.
+    dsp: Dsp, DspLayer {
+        /// Whether to run the DSP on a dedicated thread.
+        threaded: bool = false,
+        /// How many instructions the DSP thread is allowed to run ahead of the CPU.
+        slack: u32 = cores::dsp::threaded::DEFAULT_SLACK,
+    }
+
+    /// Emulated system settings.
+    system: System, SystemLayer {
+        /// Seed of the pattern memory is filled with at power-on, useful to shake out reads of
+        /// uninitialized memory. Memory is zeroed if unset.
+        fill_seed: Option<u64> = None,
+        /// Whether runs must not depend on the host, e.g. for reproducible tests. The RTC starts
+        /// at `rtc_epoch` instead of the host's clock and emulation advances in fixed steps.
+        deterministic: bool = false,
+        /// Value of the RTC at power-on in deterministic mode, in seconds since 2000-01-01.
+        rtc_epoch: u32 = 0,
+        /// Memory card slot a USB Gecko is plugged into, if any. Its output goes to the guest
+        /// console.
+        usb_gecko: Option<CardSlot> = None,
+        /// Whether vertex array reads are checked against the bounds of RAM, reading zeroes when
+        /// out of bounds. Costs under 1% of vertex parsing time.
+        validate_vertex_indices: bool = cfg!(debug_assertions),
+    }
+
+    /// Renderer settings.
+    renderer: Renderer, RendererLayer {
+        /// Whether to use 4x MSAA on the EFB.
+        msaa: bool = true,
+        /// When to skip presenting frames. Skipped frames are still fully emulated.
+        frame_skip: FrameSkipMode = FrameSkipMode::Off,
+        /// How many frames are skipped for each presented one, in fixed mode.
+        frame_skip_fixed: u8 = 1,
+        /// Whether skipped frames also drop their draws. Saves GPU work, but EFB copies made
+        /// during skipped frames end up stale.
+        frame_skip_draws: bool = false,
+    }
+
+    /// Emulation speed settings.
+    speed: Speed, SpeedLayer {
+        /// Emulation speed relative to real time, e.g. 0.5 to run at half speed.
+        normal: f64 = 1.0,
+        /// Emulation speed while the turbo key is held. Emulation may not keep up with it.
+        turbo: f64 = 4.0,
+        /// Emulation speed while slow motion is toggled on.
+        slow_motion: f64 = 0.25,
+        /// Key to hold for turbo, by its name (e.g. `Tab`).
+        turbo_key: String = "Tab".to_owned(),
+        /// Key which toggles slow motion, by its name.
+        slow_motion_key: String = "Backslash".to_owned(),
+    }
+
+    /// Audio settings.
+    audio: Audio, AudioLayer {
+        /// Audio backend to use.
+        backend: AudioBackend = AudioBackend::Cpal,
+    }
+
+    /// Input settings.
+    input: Input, InputLayer {
+        /// Whether to use gamepads as controllers.
+        gamepad: bool = true,
+        /// Whether to plug newly connected gamepads into the first free controller port.
+        auto_assign: bool = true,
+    }
+
+    /// Paths.
+    paths: Paths, PathsLayer {
+        /// Path to the IPL ROM.
+        ipl: Option<PathBuf> = None,
+        /// Path to a dump of the DSP instruction ROM. Defaults to the bundled free replacement.
+        dsp_rom: Option<PathBuf> = None,
+        /// Path to a dump of the DSP coefficient ROM. Defaults to the bundled free replacement.
+        dsp_coef: Option<PathBuf> = None,
+        /// Path to the JIT block cache directory. Defaults to the platform cache directory.
+        jit_cache: Option<PathBuf> = None,
+    }
+}
+
+impl Layer {
+    /// Loads a layer from the given TOML file. Returns `None` if the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+
+        let layer = toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Some(layer))
+    }
+
+    /// Saves this layer to the given TOML file, creating parent directories if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let text = toml::to_string_pretty(self)?;
+        std::fs::write(path, text).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+/// The effective configuration, resolved from all layers.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub settings: Settings,
+    sources: BTreeMap<&'static str, Source>,
+}
+
+impl Config {
+    /// Resolves the configuration from the given layers, in increasing order of precedence.
+    pub fn resolve<'a>(layers: impl IntoIterator<Item = (Source, &'a Layer)>) -> Self {
+        let mut config = Self::default();
+        for (source, layer) in layers {
+            config.apply(source, layer);
+        }
+
+        config
+    }
+
+    /// Returns the source of the setting with the given key (i.e. `section.field`).
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).copied().unwrap_or(Source::Default)
+    }
+}
+
+/// The user config layer, along with the file it is stored in.
+pub struct UserLayer {
+    pub path: PathBuf,
+    pub layer: Layer,
+}
+
+impl UserLayer {
+    pub fn save(&self) -> Result<()> {
+        self.layer.save(&self.path)
+    }
+}
+
+/// Paths of the config files.
+pub struct Files {
+    /// The user config file.
+    pub user: PathBuf,
+    /// The directory of per-game config files.
+    pub games: PathBuf,
+}
+
+impl Files {
+    pub fn new(config_dir: &Path) -> Self {
+        Self {
+            user: config_dir.join("config.toml"),
+            games: config_dir.join("games"),
+        }
+    }
+
+    /// Path of the config file for the game in the given ROM.
+    pub fn game(&self, rom: &Path) -> Option<PathBuf> {
+        let stem = rom.file_stem()?;
+        Some(self.games.join(stem).with_extension("toml"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layer_precedence() {
+        let mut user = Layer::default();
+        user.ppcjit.instr_per_block = Some(64);
+        user.ppcjit.nop_syscalls = Some(true);
+        user.renderer.msaa = Some(false);
+
+        let mut game = Layer::default();
+        game.ppcjit.instr_per_block = Some(32);
+        game.ppcjit.round_to_single = Some(true);
+
+        let mut cli = Layer::default();
+        cli.ppcjit.instr_per_block = Some(16);
+
+        let config = Config::resolve([
+            (Source::User, &user),
+            (Source::Game, &game),
+            (Source::Cli, &cli),
+        ]);
+
+        let settings = &config.settings;
+        assert_eq!(settings.ppcjit.instr_per_block, 16);
+        assert!(settings.ppcjit.nop_syscalls);
+        assert!(settings.ppcjit.round_to_single);
+        assert!(!settings.renderer.msaa);
+        assert!(!settings.ppcjit.force_fpu);
+
+        assert_eq!(config.source("ppcjit.instr_per_block"), Source::Cli);
+        assert_eq!(config.source("ppcjit.round_to_single"), Source::Game);
+        assert_eq!(config.source("ppcjit.nop_syscalls"), Source::User);
+        assert_eq!(config.source("ppcjit.force_fpu"), Source::Default);
+    }
+
+    #[test]
+    fn effective_lists_sources() {
+        let mut cli = Layer::default();
+        cli.ppcjit.force_fpu = Some(true);
+
+        let config = Config::resolve([(Source::Cli, &cli)]);
+        let effective = config.effective();
+
+        assert!(effective.contains("ppcjit.force_fpu = true (cli)"));
+        assert!(effective.contains("ppcjit.instr_per_block = 128 (default)"));
+    }
+
+    fn full_layer() -> Layer {
+        Layer {
+            ppcjit: PpcjitLayer {
+                instr_per_block: Some(256),
+                nop_syscalls: Some(true),
+                force_fpu: Some(false),
+                ignore_unimplemented_inst: Some(true),
+                round_to_single: Some(true),
+                float_exceptions: Some(false),
+                bulk_memory_idioms: Some(true),
+                block_cache: Some(false),
+            },
+            dsp: DspLayer {
+                threaded: Some(true),
+                slack: Some(512),
+            },
+            system: SystemLayer {
+                fill_seed: Some(Some(42)),
+                deterministic: Some(true),
+                rtc_epoch: Some(86400),
+                usb_gecko: Some(Some(CardSlot::B)),
+                validate_vertex_indices: Some(true),
+            },
+            renderer: RendererLayer {
+                msaa: Some(false),
+                frame_skip: Some(FrameSkipMode::Fixed),
+                frame_skip_fixed: Some(2),
+                frame_skip_draws: Some(true),
+            },
+            speed: SpeedLayer {
+                normal: Some(0.5),
+                turbo: Some(8.0),
+                slow_motion: Some(0.125),
+                turbo_key: Some("Space".to_owned()),
+                slow_motion_key: Some("F4".to_owned()),
+            },
+            audio: AudioLayer {
+                backend: Some(AudioBackend::None),
+            },
+            input: InputLayer {
+                gamepad: Some(false),
+                auto_assign: Some(false),
+            },
+            paths: PathsLayer {
+                ipl: Some(Some(PathBuf::from("ipl.bin"))),
+                dsp_rom: Some(Some(PathBuf::from("dsp_rom.bin"))),
+                dsp_coef: Some(Some(PathBuf::from("dsp_coef.bin"))),
+                jit_cache: Some(Some(PathBuf::from("cache"))),
+            },
+        }
+    }
+
+    #[test]
+    fn layer_round_trip() {
+        for layer in [Layer::default(), full_layer()] {
+            let text = toml::to_string_pretty(&layer).unwrap();
+            let parsed: Layer = toml::from_str(&text).unwrap();
+            assert_eq!(layer, parsed);
+        }
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = Config::resolve([(Source::User, &full_layer())]).settings;
+        let text = toml::to_string_pretty(&settings).unwrap();
+        let parsed: Settings = toml::from_str(&text).unwrap();
+        assert_eq!(settings, parsed);
+
+        let settings = Settings::default();
+        let text = toml::to_string_pretty(&settings).unwrap();
+        let parsed: Settings = toml::from_str(&text).unwrap();
+        assert_eq!(settings, parsed);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(toml::from_str::<Layer>("[ppcjit]\nunknown = 1").is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Per-title settings detection.
+//!
+//! Titles built with different SDK releases need different quirks. Two indicators of the SDK
+//! release a title was built with are used:
+//!
+//! - The date in the version string of the disc's apploader (e.g. `2003/04/17`), which is known
+//!   before booting. Settings derived from it are applied as the [`Source::Detected`] layer, so the
+//!   per-game config and CLI flags override them.
+//! - The build date in the banner of the OS library (e.g. `<< Dolphin SDK - OS release build: Apr
+//!   17 2003 ... >>`), which is only known once the game has been loaded into RAM. It is searched
+//!   for in the data sections of the disc's boot file by the runner, and only reported, since most
+//!   settings can't be changed after booting.
+//!
+//! [`Source::Detected`]: crate::config::Source::Detected
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use lazuli::disks::apploader;
+use lazuli::disks::binrw::BinRead;
+use lazuli::disks::iso::Iso;
+use lazuli::modules::disk::DiskModule;
+
+use crate::config::Layer;
+
+/// Offset of the apploader in a disc.
+const APPLOADER_OFFSET: u64 = 0x2440;
+
+/// Start of the OS library banner.
+const SDK_BANNER: &[u8] = b"<< Dolphin SDK - OS";
+
+/// How often to scan RAM for the OS library banner.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many scans for the OS library banner are done before giving up.
+const MAX_SCANS: u32 = 30;
+
+/// Longest OS library banner that is read.
+const MAX_BANNER_LEN: usize = 0x80;
+
+/// Mask of the physical address of RAM in a logical address.
+const RAM_MASK: u32 = 0x01FF_FFFF;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date identifying an SDK release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SdkDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl SdkDate {
+    pub const fn new(year: u16, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    /// Parses the date out of an apploader version string, i.e. the first `YYYY/MM/DD` in it.
+    pub fn from_apploader_version(version: &str) -> Option<Self> {
+        version.split_whitespace().find_map(|word| {
+            let mut parts = word.split('/');
+            let year = parts.next()?;
+            let month = parts.next()?;
+            let day = parts.next()?;
+            if parts.next().is_some() || year.len() != 4 {
+                return None;
+            }
+
+            Self::checked(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+        })
+    }
+
+    /// Parses the build date out of an OS library banner, i.e. the `Mmm DD YYYY` following
+    /// `build:`.
+    pub fn from_sdk_banner(banner: &str) -> Option<Self> {
+        let (_, rest) = banner.split_once("build:")?;
+        let mut words = rest.split_whitespace();
+        let month = words.next()?;
+        let month = MONTHS.iter().position(|m| *m == month)? as u8 + 1;
+        let day = words.next()?.parse().ok()?;
+        let year = words.next()?.parse().ok()?;
+
+        Self::checked(year, month, day)
+    }
+
+    fn checked(year: u16, month: u8, day: u8) -> Option<Self> {
+        ((1999..2100).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day))
+            .then_some(Self::new(year, month, day))
+    }
+}
+
+impl std::fmt::Display for SdkDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}/{:02}/{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// A range of SDK releases which share the same recommended settings.
+pub struct Era {
+    /// Date of the first SDK release of this era.
+    pub since: SdkDate,
+    pub name: &'static str,
+    /// Sets the recommended settings of this era.
+    apply: fn(&mut Layer),
+}
+
+/// SDK eras, sorted by date.
+const ERAS: &[Era] = &[
+    Era {
+        since: SdkDate::new(1999, 1, 1),
+        name: "launch",
+        // early OS libraries don't rely on syscalls for anything other than timing
+        apply: |layer| layer.ppcjit.nop_syscalls = Some(true),
+    },
+    Era {
+        since: SdkDate::new(2002, 1, 1),
+        name: "middle",
+        apply: |_| (),
+    },
+    Era {
+        since: SdkDate::new(2003, 1, 1),
+        name: "late",
+        // later AX ucodes have tight CPU/DSP handshakes, which the threaded DSP can miss
+        apply: |layer| layer.dsp.threaded = Some(false),
+    },
+];
+
+impl Era {
+    /// Returns the era of the given SDK release.
+    pub fn of(date: SdkDate) -> &'static Self {
+        ERAS.iter()
+            .rev()
+            .find(|era| era.since <= date)
+            .unwrap_or(&ERAS[0])
+    }
+
+    /// Returns a layer with the recommended settings of this era.
+    pub fn layer(&self) -> Layer {
+        let mut layer = Layer::default();
+        (self.apply)(&mut layer);
+        layer
+    }
+}
+
+/// What was detected about the running title.
+#[derive(Debug, Default)]
+pub struct Detection {
+    /// Date of the apploader of the disc.
+    pub apploader: Option<SdkDate>,
+    /// Build date of the OS library, once found in RAM.
+    pub sdk: Option<SdkDate>,
+    /// RAM ranges the OS library banner is searched in, i.e. the data sections of the boot file
+    /// of the disc. If empty, the whole RAM is searched.
+    banner_sections: Vec<Range<usize>>,
+    last_scan: Option<Instant>,
+    scans: u32,
+    /// Whether scanning for the OS library banner is over, either because it was found or
+    /// because it was given up on.
+    scan_done: bool,
+}
+
+/// Reads the data sections of the boot file of a disc, as RAM ranges.
+fn boot_file_sections(disk: &mut dyn DiskModule) -> Option<Vec<Range<usize>>> {
+    disk.seek(SeekFrom::Start(0)).ok()?;
+    let header = Iso::new(disk).ok()?.bootfile_header().ok()?;
+    let sections = header
+        .data_sections()
+        .map(|section| {
+            let start = (section.target & RAM_MASK) as usize;
+            start..start + section.size as usize
+        })
+        .collect();
+
+    Some(sections)
+}
+
+/// Finds the OS library banner in the given memory.
+fn find_banner(memory: &[u8]) -> Option<String> {
+    let start = memory
+        .windows(SDK_BANNER.len())
+        .position(|w| w == SDK_BANNER)?;
+
+    let banner = &memory[start..memory.len().min(start + MAX_BANNER_LEN)];
+    let end = banner.iter().position(|&b| b == 0).unwrap_or(banner.len());
+    Some(String::from_utf8_lossy(&banner[..end]).into_owned())
+}
+
+impl Detection {
+    /// Detects what is possible from the disc, before booting. The disc is left at its start.
+    pub fn from_disk(mut disk: &mut dyn DiskModule) -> Self {
+        let mut detection = Self::default();
+        if !disk.has_disk() {
+            return detection;
+        }
+
+        detection.banner_sections = boot_file_sections(disk).unwrap_or_default();
+        let header = disk
+            .seek(SeekFrom::Start(APPLOADER_OFFSET))
+            .ok()
+            .and_then(|_| apploader::Header::read(&mut disk).ok());
+        _ = disk.seek(SeekFrom::Start(0));
+
+        if let Some(header) = header {
+            let version = header.version.to_string();
+            detection.apploader = SdkDate::from_apploader_version(&version);
+            match detection.apploader {
+                Some(date) => tracing::info!(
+                    "apploader {version:?} is from {date}, in the {} SDK era",
+                    Era::of(date).name
+                ),
+                None => tracing::warn!("unrecognized apploader version {version:?}"),
+            }
+        }
+
+        detection
+    }
+
+    /// The settings recommended for the title, as known before booting.
+    pub fn layer(&self) -> Layer {
+        self.apploader
+            .map(|date| Era::of(date).layer())
+            .unwrap_or_default()
+    }
+
+    /// The era of the title, preferring the OS library build date when known.
+    pub fn era(&self) -> Option<&'static Era> {
+        self.sdk.or(self.apploader).map(Era::of)
+    }
+
+    /// Scans RAM for the OS library banner, until it is found or [`MAX_SCANS`] scans failed.
+    /// Scans are rate limited, so this is cheap to call often.
+    pub fn poll(&mut self, ram: &[u8]) {
+        if self.scan_done || self.last_scan.is_some_and(|t| t.elapsed() < SCAN_INTERVAL) {
+            return;
+        }
+
+        self.last_scan = Some(Instant::now());
+        self.scans += 1;
+
+        let banner = if self.banner_sections.is_empty() {
+            find_banner(ram)
+        } else {
+            self.banner_sections
+                .iter()
+                .filter_map(|section| ram.get(section.start..section.end.min(ram.len())))
+                .find_map(find_banner)
+        };
+
+        let Some(banner) = banner else {
+            if self.scans == MAX_SCANS {
+                tracing::debug!("OS library banner not found after {MAX_SCANS} scans");
+                self.scan_done = true;
+            }
+
+            return;
+        };
+
+        self.scan_done = true;
+        self.sdk = SdkDate::from_sdk_banner(&banner);
+        let Some(date) = self.sdk else {
+            tracing::warn!("unrecognized OS library banner {banner:?}");
+            return;
+        };
+
+        let era = Era::of(date);
+        tracing::info!(
+            "OS library was built on {date}, in the {} SDK era",
+            era.name
+        );
+
+        if era.layer() != self.layer() {
+            tracing::warn!(
+                "the OS library is from the {} SDK era, but the settings applied at boot were \
+                 derived from the apploader",
+                era.name,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apploader_versions() {
+        assert_eq!(
+            SdkDate::from_apploader_version("2003/04/17"),
+            Some(SdkDate::new(2003, 4, 17))
+        );
+        assert_eq!(
+            SdkDate::from_apploader_version("APPLOADER 2001/08/14"),
+            Some(SdkDate::new(2001, 8, 14))
+        );
+        assert_eq!(SdkDate::from_apploader_version("APPLOADER"), None);
+        assert_eq!(SdkDate::from_apploader_version("2003/13/01"), None);
+    }
+
+    #[test]
+    fn sdk_banners() {
+        let banner = "<< Dolphin SDK - OS\trelease build: Apr  5 2004 04:14:51 (0x2301) >>";
+        assert_eq!(
+            SdkDate::from_sdk_banner(banner),
+            Some(SdkDate::new(2004, 4, 5))
+        );
+        assert_eq!(SdkDate::from_sdk_banner("<< Dolphin SDK - OS >>"), None);
+    }
+
+    #[test]
+    fn derived_defaults() {
+        let layer = |version| {
+            let date = SdkDate::from_apploader_version(version).unwrap();
+            Detection {
+                apploader: Some(date),
+                ..Default::default()
+            }
+            .layer()
+        };
+
+        let launch = layer("APPLOADER 2001/08/14");
+        assert_eq!(launch.ppcjit.nop_syscalls, Some(true));
+        assert_eq!(launch.dsp.threaded, None);
+
+        let middle = layer("2002/06/10");
+        assert_eq!(middle, Layer::default());
+
+        let late = layer("APPLOADER 2003/04/17");
+        assert_eq!(late.ppcjit.nop_syscalls, None);
+        assert_eq!(late.dsp.threaded, Some(false));
+
+        assert_eq!(Detection::default().layer(), Layer::default());
+    }
+
+    #[test]
+    fn banner_found_in_ram() {
+        let mut ram = vec![0; 0x1000];
+        let banner = b"<< Dolphin SDK - OS\trelease build: Nov 10 2002 06:26:41 (0x2301) >>";
+        ram[0x800..][..banner.len()].copy_from_slice(banner);
+
+        let mut detection = Detection::default();
+        detection.poll(&ram);
+        assert_eq!(detection.sdk, Some(SdkDate::new(2002, 11, 10)));
+        assert_eq!(detection.era().unwrap().name, "middle");
+    }
+
+    #[test]
+    fn banner_is_searched_in_sections() {
+        let mut ram = vec![0; 0x1000];
+        let banner = b"<< Dolphin SDK - OS\trelease build: Nov 10 2002 06:26:41 (0x2301) >>";
+        ram[0x800..][..banner.len()].copy_from_slice(banner);
+
+        let mut detection = Detection {
+            banner_sections: vec![0x100..0x200, 0xF00..0x2000],
+            ..Default::default()
+        };
+        detection.poll(&ram);
+        assert_eq!(detection.sdk, None);
+        assert!(!detection.scan_done);
+
+        let mut detection = Detection {
+            banner_sections: vec![0x100..0x200, 0x7F0..0x2000],
+            ..Default::default()
+        };
+        detection.poll(&ram);
+        assert_eq!(detection.sdk, Some(SdkDate::new(2002, 11, 10)));
+        assert!(detection.scan_done);
+    }
+
+    #[test]
+    fn unrecognized_banner_ends_the_scan() {
+        let mut ram = vec![0; 0x1000];
+        let banner = b"<< Dolphin SDK - OS\trelease build: ??? >>";
+        ram[0x800..][..banner.len()].copy_from_slice(banner);
+
+        let mut detection = Detection::default();
+        detection.poll(&ram);
+        assert_eq!(detection.sdk, None);
+        assert!(detection.scan_done);
+    }
+}
@@ -1,104 +1,393 @@
 #![feature(trim_prefix_suffix)]
 
 mod cli;
+mod config;
+mod heuristics;
+mod osd;
 mod runner;
 mod windows;
 
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use clap::Parser;
 use eframe::egui;
 use eframe::egui_wgpu::{WgpuConfiguration, WgpuSetup, WgpuSetupCreateNew};
-use eyre_pretty::eyre::Result;
-use lazuli::Lazuli;
-use lazuli::cores::Cores;
+use eyre_pretty::eyre::{Result, WrapErr, bail};
+use lazuli::cores::{Cores, DspCore};
+use lazuli::disks::format::Format;
+use lazuli::disks::iso::Iso;
+use lazuli::disks::iso::mmap::IsoMmap;
 use lazuli::disks::rvz::Rvz;
+use lazuli::disks::{Console, DiscImage};
+use lazuli::modules::audio::{AudioModule, NopAudioModule};
 use lazuli::modules::debug::{DebugModule, NopDebugModule};
 use lazuli::modules::disk::{DiskModule, NopDiskModule};
+use lazuli::modules::input::{Device, InputModule, NopInputModule};
+use lazuli::modules::render::{Action, RenderModule};
 use lazuli::system::executable::Executable;
-use lazuli::system::{self, Modules};
+use lazuli::system::exi::gecko::UsbGecko;
+use lazuli::system::vi::FrameSkipRate;
+use lazuli::system::{self, BootMode, Modules, exi};
+use lazuli::{Lazuli, ResetKind};
 use modules::audio::CpalModule;
 use modules::debug::{Addr2LineModule, MapFileModule};
-use modules::disk::{IsoModule, RvzModule};
+use modules::disk::{DiscModule, MappedDiscModule, Prefetcher};
 use modules::input::GilrsModule;
 use nanorand::Rng;
 use renderer::Renderer;
 use runner::State;
 use vtxjit::JitVertexModule;
 
-use crate::runner::Runner;
+use crate::config::{AudioBackend, CardSlot, Config, Settings, Source};
+use crate::heuristics::Detection;
+use crate::osd::{Osd, Severity};
+use crate::runner::{Event, Runner};
 use crate::windows::{AppWindow, AppWindowState};
 
+/// Maximum number of entries in the recent files list.
+const MAX_RECENT_FILES: usize = 10;
+
+/// File extensions that can be opened at runtime.
+const OPEN_EXTENSIONS: &[&str] = &["iso", "rvz", "dol", "elf"];
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+/// Detects the format of a file from its contents, falling back to its extension. Wii files are
+/// rejected.
+fn file_format(path: &Path) -> Result<Format> {
+    let file = std::fs::File::open(path)?;
+    let format = Format::sniff(BufReader::new(file))?
+        .or_else(|| extension(path).as_deref().and_then(Format::from_extension));
+
+    let Some(format) = format else {
+        bail!("unsupported file format: {}", path.display());
+    };
+
+    if format.console() == Some(Console::Wii) {
+        bail!("{} is a Wii disc, which is not supported", path.display());
+    }
+
+    Ok(format)
+}
+
+fn open_disk(rom: Option<&Path>) -> Result<Box<dyn DiskModule>> {
+    let Some(path) = rom else {
+        return Ok(Box::new(NopDiskModule));
+    };
+
+    let image: Box<dyn DiscImage + Send> = match file_format(path)? {
+        Format::Iso(_) => {
+            match IsoMmap::open(path) {
+                Ok(iso) => return Ok(Box::new(MappedDiscModule::new(iso))),
+                Err(e) => {
+                    tracing::warn!("failed to map {}, reading it instead: {e}", path.display())
+                }
+            }
+
+            let file = std::fs::File::open(path)?;
+            let reader = Prefetcher::new(file)?;
+            Box::new(Iso::new(reader)?)
+        }
+        Format::Rvz(_) => {
+            let file = std::fs::File::open(path)?;
+            let reader = BufReader::new(file);
+            Box::new(Rvz::new(reader)?.into_iso()?)
+        }
+        _ => bail!("unsupported disc format: {}", path.display()),
+    };
+
+    Ok(Box::new(DiscModule::new(image)))
+}
+
+/// What to boot.
+struct BootSource {
+    /// Disc to insert.
+    rom: Option<PathBuf>,
+    /// Executable to sideload.
+    exec: Option<PathBuf>,
+    /// File to use as a debug info provider.
+    debug: Option<PathBuf>,
+    mode: BootMode,
+}
+
+impl BootSource {
+    fn from_cli(cfg: &cli::Config) -> Self {
+        Self {
+            rom: cfg.rom.clone(),
+            exec: cfg.exec.clone(),
+            debug: cfg.debug.clone(),
+            mode: cfg.boot_mode(),
+        }
+    }
+
+    /// The boot source for a file opened at runtime. Discs are booted through the IPL HLE and
+    /// executables are booted directly, with `.elf` executables also providing debug info.
+    fn from_file(path: &Path) -> Result<Self> {
+        let format = file_format(path)?;
+        let path = path.to_path_buf();
+        Ok(match format {
+            Format::Iso(_) | Format::Rvz(_) => Self {
+                rom: Some(path),
+                exec: None,
+                debug: None,
+                mode: BootMode::DiscApploader,
+            },
+            Format::Dol => Self {
+                rom: None,
+                exec: Some(path),
+                debug: None,
+                mode: BootMode::DirectDol,
+            },
+            Format::Elf => Self {
+                rom: None,
+                exec: Some(path.clone()),
+                debug: Some(path),
+                mode: BootMode::DirectDol,
+            },
+        })
+    }
+
+    /// The file being booted, if any.
+    fn file(&self) -> Option<&Path> {
+        self.exec.as_deref().or(self.rom.as_deref())
+    }
+}
+
+/// A boot source, along with its disc and the configuration resolved for it.
+struct Boot {
+    source: BootSource,
+    disk: Box<dyn DiskModule>,
+    config: Config,
+    detection: Detection,
+}
+
+impl Boot {
+    /// Opens the disc of the given boot source and resolves the configuration for it.
+    fn load(
+        source: BootSource,
+        files: &config::Files,
+        user: &config::Layer,
+        cli: &config::Layer,
+    ) -> Result<Self> {
+        let game = match source.rom.as_deref().and_then(|rom| files.game(rom)) {
+            Some(path) => config::Layer::load(&path)?.unwrap_or_default(),
+            None => config::Layer::default(),
+        };
+
+        let mut disk = open_disk(source.rom.as_deref())?;
+        let detection = Detection::from_disk(disk.as_mut());
+        let detected = detection.layer();
+
+        let config = Config::resolve([
+            (Source::User, user),
+            (Source::Detected, &detected),
+            (Source::Game, &game),
+            (Source::Cli, cli),
+        ]);
+
+        Ok(Self {
+            source,
+            disk,
+            config,
+            detection,
+        })
+    }
+}
+
+fn open_debug(path: Option<&Path>) -> Box<dyn DebugModule> {
+    let Some(path) = path else {
+        return Box::new(NopDebugModule);
+    };
+
+    match extension(path).as_deref() {
+        Some("elf") => {
+            let debug = Addr2LineModule::new(path);
+            debug.map_or_else(
+                || Box::new(NopDebugModule) as Box<dyn DebugModule>,
+                |d| Box::new(d) as Box<dyn DebugModule>,
+            )
+        }
+        Some("map") => Box::new(MapFileModule::new(path)),
+        _ => Box::new(NopDebugModule),
+    }
+}
+
+fn jit_cache_path(settings: &Settings, dirs: &directories::ProjectDirs) -> PathBuf {
+    settings
+        .paths
+        .jit_cache
+        .clone()
+        .unwrap_or_else(|| dirs.cache_dir().join("ppcjit"))
+}
+
+/// Creates the emulator for the given boot source, with the given settings.
+#[allow(clippy::default_constructed_unit_structs)]
+fn create_lazuli(
+    settings: &Settings,
+    dirs: &directories::ProjectDirs,
+    renderer: &Renderer,
+    source: &BootSource,
+    disk: Box<dyn DiskModule>,
+) -> Result<Lazuli> {
+    let ipl = if let Some(path) = &settings.paths.ipl {
+        Some(std::fs::read(path)?)
+    } else {
+        None
+    };
+
+    let executable = if let Some(path) = &source.exec {
+        Some(Executable::open(path)?)
+    } else {
+        None
+    };
+
+    let mut dsp_rom = cores::dsp::Rom::default();
+    if let Some(path) = &settings.paths.dsp_rom {
+        let dump = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        dsp_rom
+            .load_irom(&dump)
+            .with_context(|| format!("loading {}", path.display()))?;
+    }
+
+    if let Some(path) = &settings.paths.dsp_coef {
+        let dump = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        dsp_rom
+            .load_coef(&dump)
+            .with_context(|| format!("loading {}", path.display()))?;
+    }
+
+    let dsp: Box<dyn DspCore> = if settings.dsp.threaded {
+        Box::new(cores::dsp::threaded::Core::new(dsp_rom, settings.dsp.slack))
+    } else {
+        Box::new(cores::dsp::interpreter::Core::new(dsp_rom))
+    };
+
+    // the frontend keeps the CPU core boxed, so that it can be replaced at runtime
+    let cores: Cores = Cores {
+        dsp,
+        cpu: Box::new(cores::cpu::jit::Core::new(cores::cpu::jit::Config {
+            instr_per_block: settings.ppcjit.instr_per_block,
+            jit_settings: cores::cpu::jit::ppcjit::Settings {
+                compiler: cores::cpu::jit::ppcjit::CompilerSettings {
+                    nop_syscalls: settings.ppcjit.nop_syscalls,
+                    force_fpu: settings.ppcjit.force_fpu,
+                    ignore_unimplemented: settings.ppcjit.ignore_unimplemented_inst,
+                    round_to_single: settings.ppcjit.round_to_single,
+                    float_exceptions: settings.ppcjit.float_exceptions,
+                    bulk_memory_idioms: settings.ppcjit.bulk_memory_idioms,
+                },
+                cache_path: settings
+                    .ppcjit
+                    .block_cache
+                    .then(|| jit_cache_path(settings, dirs)),
+            },
+        })),
+    };
+
+    let audio: Box<dyn AudioModule> = match settings.audio.backend {
+        AudioBackend::Cpal => Box::new(CpalModule::new()),
+        AudioBackend::None => Box::new(NopAudioModule),
+    };
+
+    let input: Box<dyn InputModule> = if settings.input.gamepad {
+        let mut gilrs = GilrsModule::new();
+        gilrs.set_auto_assign(settings.input.auto_assign);
+        Box::new(gilrs)
+    } else {
+        Box::new(NopInputModule)
+    };
+
+    let modules = Modules {
+        audio,
+        debug: open_debug(source.debug.as_deref()),
+        disk,
+        input,
+        render: Box::new(renderer.clone()),
+        vertex: Box::new(JitVertexModule::new(vtxjit::Settings {
+            validate_indices: settings.system.validate_vertex_indices,
+        })),
+    };
+
+    let mut lazuli = Lazuli::new(
+        cores,
+        modules,
+        system::Config {
+            boot: source.mode,
+            ipl,
+            sideload: executable,
+            fill_seed: settings.system.fill_seed,
+            deterministic: settings.system.deterministic,
+            rtc_epoch: settings.system.rtc_epoch,
+        },
+    )?;
+
+    if let Some(slot) = settings.system.usb_gecko {
+        let slot = match slot {
+            CardSlot::A => exi::Slot::MEMORY_CARD_A,
+            CardSlot::B => exi::Slot::MEMORY_CARD_B,
+        };
+
+        let gecko = UsbGecko::new(lazuli.sys.guest_console.clone());
+        lazuli.sys.external.plug(slot, Box::new(gecko));
+    }
+
+    Ok(lazuli)
+}
+
+/// The frame skip policy described by the given settings.
+fn frame_skip_policy(settings: &Settings) -> runner::frame_skip::Policy {
+    runner::frame_skip::Policy {
+        mode: settings.renderer.frame_skip,
+        fixed: settings.renderer.frame_skip_fixed,
+        draws: settings.renderer.frame_skip_draws,
+    }
+}
+
 struct App {
     last_update: Instant,
     renderer: Renderer,
     windows: Vec<AppWindowState>,
     runner: Runner,
     cps: u64,
+    /// Rate at which frames are currently being skipped.
+    frame_skip: FrameSkipRate,
     organize: bool,
+    config: Config,
+    user_config: config::UserLayer,
+    dirs: directories::ProjectDirs,
+    files: config::Files,
+    cli: config::Layer,
+    recent_files: Vec<PathBuf>,
+    error: Option<String>,
+    /// File dropped while emulation was running, waiting for confirmation to be opened.
+    dropped: Option<PathBuf>,
+    osd: Osd,
+    /// Input devices as of the last update, to notice (dis)connections.
+    devices: Vec<Device>,
+    /// Emulation speed last requested from the runner.
+    speed: f64,
+    /// Whether slow motion is toggled on.
+    slow_motion: bool,
 }
 
 impl App {
-    #[allow(clippy::default_constructed_unit_structs)]
-    fn new(cc: &eframe::CreationContext<'_>, cfg: &cli::Config) -> Result<Self> {
+    fn new(
+        cc: &eframe::CreationContext<'_>,
+        cfg: &cli::Config,
+        dirs: directories::ProjectDirs,
+        files: config::Files,
+        user_config: config::UserLayer,
+        boot: Boot,
+    ) -> Result<Self> {
         tracing::info!("starting app setup");
-
-        let ipl = if let Some(path) = &cfg.ipl {
-            Some(std::fs::read(path)?)
-        } else {
-            None
-        };
-
-        let disk: Box<dyn DiskModule> = if let Some(path) = &cfg.rom {
-            let extension = path.extension().and_then(|ext| ext.to_str()).unwrap();
-            match extension {
-                "iso" => {
-                    let file = std::fs::File::open(path)?;
-                    let reader = BufReader::new(file);
-                    Box::new(IsoModule(Some(reader)))
-                }
-                "rvz" => {
-                    let file = std::fs::File::open(path)?;
-                    let reader = BufReader::new(file);
-                    let rvz = Rvz::new(reader).unwrap();
-                    let rvz = RvzModule::new(rvz);
-                    Box::new(rvz)
-                }
-                _ => todo!(),
-            }
-        } else {
-            Box::new(NopDiskModule)
-        };
-
-        let executable = if let Some(path) = &cfg.exec {
-            Some(Executable::open(path)?)
-        } else {
-            None
-        };
-
-        // this is a mess lol
-        let debug_module = if let Some(path) = cfg.debug.as_deref() {
-            match path
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|s| s.to_ascii_lowercase())
-                .as_deref()
-            {
-                Some("elf") => {
-                    let debug = Addr2LineModule::new(path);
-                    debug.map_or_else(
-                        || Box::new(NopDebugModule) as Box<dyn DebugModule>,
-                        |d| Box::new(d) as Box<dyn DebugModule>,
-                    )
-                }
-                Some("map") => Box::new(MapFileModule::new(path)) as Box<dyn DebugModule>,
-                _ => Box::new(NopDebugModule),
-            }
-        } else {
-            Box::new(NopDebugModule)
-        };
+        let settings = &boot.config.settings;
 
         let wgpu_state = cc.wgpu_render_state.as_ref().unwrap();
         tracing::info!("wgpu device limits: {:?}", wgpu_state.device.limits());
@@ -108,51 +397,22 @@ impl App {
             wgpu_state.queue.clone(),
             wgpu_state.target_format,
         );
-
-        let dirs = directories::ProjectDirs::from("", "", "lazuli").unwrap();
-        let cache_dir = dirs.cache_dir();
-        let jit_cache_path = cache_dir.join("ppcjit");
+        renderer.set_msaa(if settings.renderer.msaa { 4 } else { 1 });
+        if let Some(path) = cfg.capture_actions.as_deref() {
+            renderer.start_capture(path)?;
+        }
 
         if cfg.ppcjit.clear_cache {
-            _ = std::fs::remove_dir_all(&jit_cache_path);
+            let path = jit_cache_path(settings, &dirs);
+            if let Err(e) = cores::cpu::jit::ppcjit::invalidate_cache(&path) {
+                tracing::warn!("failed to clear the JIT block cache: {e}");
+            }
         }
 
-        let cores = Cores {
-            dsp: Box::new(cores::dsp::interpreter::Core::default()),
-            cpu: Box::new(cores::cpu::jit::Core::new(cores::cpu::jit::Config {
-                instr_per_block: cfg.ppcjit.instr_per_block,
-                jit_settings: cores::cpu::jit::ppcjit::Settings {
-                    compiler: cores::cpu::jit::ppcjit::CompilerSettings {
-                        nop_syscalls: cfg.ppcjit.nop_syscalls,
-                        force_fpu: cfg.ppcjit.force_fpu,
-                        ignore_unimplemented: cfg.ppcjit.ignore_unimplemented_inst,
-                        round_to_single: cfg.ppcjit.round_to_single,
-                    },
-                    cache_path: jit_cache_path,
-                },
-            })),
-        };
-
-        let modules = Modules {
-            audio: Box::new(CpalModule::new()),
-            debug: debug_module,
-            disk,
-            input: Box::new(GilrsModule::new()),
-            render: Box::new(renderer.clone()),
-            vertex: Box::new(JitVertexModule::new()),
-        };
-
-        let lazuli = Lazuli::new(
-            cores,
-            modules,
-            system::Config {
-                ipl_lle: cfg.ipl_lle,
-                ipl,
-                sideload: executable,
-            },
-        );
-
-        let mut runner = runner::Runner::new(lazuli);
+        let lazuli = create_lazuli(settings, &dirs, &renderer, &boot.source, boot.disk)?;
+        let devices = lazuli.sys.modules.input.devices();
+        let mut runner = runner::Runner::new(lazuli, boot.detection);
+        runner.set_frame_skip(frame_skip_policy(settings));
         if cfg.run {
             runner.start();
         }
@@ -163,6 +423,13 @@ impl App {
             .and_then(|s| s.get_string("windows"))
             .and_then(|s| ron::from_str(&s).ok());
 
+        let recent_files: Vec<PathBuf> = cc
+            .storage
+            .as_ref()
+            .and_then(|s| s.get_string("recent_files"))
+            .and_then(|s| ron::from_str(&s).ok())
+            .unwrap_or_default();
+
         let (windows, create_default) = if let Some(windows) = windows {
             (windows, false)
         } else {
@@ -175,9 +442,26 @@ impl App {
             windows,
             runner,
             cps: 0,
+            frame_skip: FrameSkipRate::default(),
             organize: false,
+            config: boot.config,
+            user_config,
+            dirs,
+            files,
+            cli: cfg.layer(),
+            recent_files,
+            error: None,
+            dropped: None,
+            osd: Osd::default(),
+            devices,
+            speed: 1.0,
+            slow_motion: false,
         };
 
+        if let Some(file) = boot.source.file() {
+            app.add_recent_file(file);
+        }
+
         if create_default {
             app.create_window(windows::disasm());
             app.create_window(windows::control());
@@ -195,6 +479,51 @@ impl App {
         Ok(app)
     }
 
+    /// Boots the given file, replacing the running emulator.
+    fn open(&mut self, path: &Path) -> Result<()> {
+        let source = BootSource::from_file(path)?;
+        let boot = Boot::load(source, &self.files, &self.user_config.layer, &self.cli)?;
+        let lazuli = create_lazuli(
+            &boot.config.settings,
+            &self.dirs,
+            &self.renderer,
+            &boot.source,
+            boot.disk,
+        )?;
+
+        tracing::info!("booting {}", path.display());
+        tracing::info!("effective configuration:\n{}", boot.config.effective());
+
+        self.devices = lazuli.sys.modules.input.devices();
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        self.osd
+            .push(Severity::Info, format!("Booted {}", name.to_string_lossy()));
+
+        let msaa = if boot.config.settings.renderer.msaa {
+            4
+        } else {
+            1
+        };
+        self.renderer.exec(Action::Reset);
+        self.renderer.set_msaa(msaa);
+        self.runner.replace(lazuli, boot.detection);
+        self.runner
+            .set_frame_skip(frame_skip_policy(&boot.config.settings));
+        self.runner.start();
+
+        self.config = boot.config;
+        self.add_recent_file(path);
+
+        Ok(())
+    }
+
+    fn add_recent_file(&mut self, path: &Path) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.recent_files.retain(|p| *p != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
     fn create_window(&mut self, window: impl AppWindow) {
         let mut rng = nanorand::tls_rng();
         let id = rng.generate::<u64>();
@@ -206,13 +535,144 @@ impl App {
     }
 }
 
+/// Shows OSD messages for input devices which were connected or disconnected since the last
+/// call, given the devices now known.
+fn report_devices(osd: &mut Osd, known: &mut Vec<Device>, devices: Vec<Device>) {
+    for device in &devices {
+        let was_connected = known
+            .iter()
+            .find(|d| d.id == device.id)
+            .is_some_and(|d| d.connected);
+
+        match (was_connected, device.connected) {
+            (false, true) => osd.push(Severity::Info, format!("{} connected", device.name)),
+            (true, false) => osd.push(Severity::Warning, format!("{} disconnected", device.name)),
+            _ => (),
+        }
+    }
+
+    *known = devices;
+}
+
+/// Shows an OSD message for an event of the runner.
+fn report_event(osd: &mut Osd, event: Event) {
+    match event {
+        Event::FrameSkipChanged(rate) if rate.skipped() == 0 => {
+            osd.push(Severity::Info, "Frame skipping disengaged");
+        }
+        Event::FrameSkipChanged(rate) => osd.push(
+            Severity::Warning,
+            format!("Skipping {}/{} frames", rate.skipped(), rate.cycle),
+        ),
+        Event::BreakpointHit { dsp: false } => osd.push(Severity::Info, "Breakpoint hit"),
+        Event::BreakpointHit { dsp: true } => osd.push(Severity::Info, "DSP breakpoint hit"),
+    }
+}
+
+/// The emulation speed asked for by the speed settings and the keys held. Toggles slow motion if
+/// its key was pressed.
+fn requested_speed(ctx: &egui::Context, settings: &config::Speed, slow_motion: &mut bool) -> f64 {
+    // keys typed into text fields aren't hotkeys
+    let typing = ctx.wants_keyboard_input();
+    let key = |name: &str| egui::Key::from_name(name).filter(|_| !typing);
+    let (turbo, toggle) = ctx.input(|i| {
+        (
+            key(&settings.turbo_key).is_some_and(|k| i.key_down(k)),
+            key(&settings.slow_motion_key).is_some_and(|k| i.key_pressed(k)),
+        )
+    });
+
+    if toggle {
+        *slow_motion = !*slow_motion;
+    }
+
+    if turbo {
+        settings.turbo
+    } else if *slow_motion {
+        settings.slow_motion
+    } else {
+        settings.normal
+    }
+}
+
+/// Dims the window and shows a hint while files are being dragged over it.
+fn paint_drop_overlay(ctx: &egui::Context) {
+    let hovering = ctx.input(|i| !i.raw.hovered_files.is_empty());
+    if !hovering {
+        return;
+    }
+
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("drop_overlay"),
+    ));
+
+    let rect = ctx.content_rect();
+    painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(192));
+    painter.text(
+        rect.center(),
+        egui::Align2::CENTER_CENTER,
+        "Drop a disc or executable to boot it",
+        egui::FontId::proportional(24.0),
+        egui::Color32::WHITE,
+    );
+}
+
 const FRAMETIME: Duration = Duration::new(0, (1_000_000_000.0 / 60.0) as u32);
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut open = None;
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.label("Lazuli");
+                ui.menu_button("🗁 File", |ui| {
+                    if ui.button("Open...").clicked() {
+                        open = rfd::FileDialog::new()
+                            .add_filter("GameCube discs and executables", OPEN_EXTENSIONS)
+                            .pick_file();
+                    }
+
+                    ui.menu_button("Recent files", |ui| {
+                        if self.recent_files.is_empty() {
+                            ui.label("No recent files");
+                            return;
+                        }
+
+                        for path in &self.recent_files {
+                            let name = path.file_name().unwrap_or(path.as_os_str());
+                            if ui
+                                .button(name.to_string_lossy())
+                                .on_hover_text(path.display().to_string())
+                                .clicked()
+                            {
+                                open = Some(path.clone());
+                            }
+                        }
+
+                        ui.separator();
+                        if ui.button("Clear").clicked() {
+                            self.recent_files.clear();
+                        }
+                    });
+                });
+
+                ui.menu_button("⟲ Reset", |ui| {
+                    if ui.button("Hard reset").clicked() {
+                        self.runner.reset(ResetKind::Hard);
+                        self.osd.push(Severity::Info, "Hard reset");
+                    }
+
+                    if ui
+                        .button("Soft reset")
+                        .on_hover_text("Presses the console's reset button")
+                        .clicked()
+                    {
+                        self.runner.reset(ResetKind::Soft);
+                        self.osd.push(Severity::Info, "Soft reset");
+                    }
+                });
+
                 ui.menu_button("🗖 View", |ui| {
                     if ui.button("Control").clicked() {
                         self.create_window(windows::control());
@@ -238,6 +698,10 @@ impl eframe::App for App {
                         self.create_window(windows::variables());
                     }
 
+                    if ui.button("Memory").clicked() {
+                        self.create_window(windows::memory());
+                    }
+
                     if ui.button("XFB").clicked() {
                         self.create_window(windows::xfb());
                     }
@@ -250,6 +714,30 @@ impl eframe::App for App {
                         self.create_window(windows::renderer());
                     }
 
+                    if ui.button("Controllers").clicked() {
+                        self.create_window(windows::controllers());
+                    }
+
+                    if ui.button("Memory Card Manager").clicked() {
+                        self.create_window(windows::memcard());
+                    }
+
+                    if ui.button("Disk Activity").clicked() {
+                        self.create_window(windows::disk_activity());
+                    }
+
+                    if ui.button("Guest Console").clicked() {
+                        self.create_window(windows::guest_console());
+                    }
+
+                    if ui.button("Debug Output").clicked() {
+                        self.create_window(windows::debug_output());
+                    }
+
+                    if ui.button("Settings").clicked() {
+                        self.create_window(windows::settings());
+                    }
+
                     ui.menu_button("Subsystems", |ui| {
                         if ui.button("Command Processor").clicked() {
                             self.create_window(windows::subsystem_cp());
@@ -265,9 +753,76 @@ impl eframe::App for App {
                     "Speed: {}%",
                     ((self.cps as f64 / lazuli::gekko::FREQUENCY as f64) * 100.0).round()
                 ));
+
+                if self.frame_skip.skipped() != 0 {
+                    ui.label(format!(
+                        "Skipping {}/{} frames",
+                        self.frame_skip.skipped(),
+                        self.frame_skip.cycle
+                    ));
+                }
             });
         });
 
+        paint_drop_overlay(ctx);
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|f| f.path.clone()));
+        if let Some(path) = dropped {
+            if self.runner.running() {
+                self.dropped = Some(path);
+            } else {
+                open = Some(path);
+            }
+        }
+
+        if let Some(path) = &self.dropped {
+            let name = path.file_name().unwrap_or(path.as_os_str());
+            let modal = egui::Modal::new(egui::Id::new("confirm_open")).show(ctx, |ui| {
+                ui.heading("Open file");
+                ui.label(format!(
+                    "Stop the running emulation and boot {}?",
+                    name.to_string_lossy()
+                ));
+                ui.horizontal(|ui| (ui.button("Open").clicked(), ui.button("Cancel").clicked()))
+                    .inner
+            });
+
+            let (confirm, cancel) = modal.inner;
+            if confirm {
+                open = self.dropped.take();
+            } else if cancel || modal.should_close() {
+                self.dropped = None;
+            }
+        }
+
+        if let Some(path) = open
+            && let Err(e) = self.open(&path)
+        {
+            tracing::error!("failed to open {}: {e:?}", path.display());
+            self.error = Some(format!("Failed to open {}:\n{e:#}", path.display()));
+        }
+
+        if let Some(error) = &self.error {
+            let modal = egui::Modal::new(egui::Id::new("error")).show(ctx, |ui| {
+                ui.heading("Error");
+                ui.label(error);
+                ui.button("Ok").clicked()
+            });
+
+            if modal.inner || modal.should_close() {
+                self.error = None;
+            }
+        }
+
+        let speed = requested_speed(ctx, &self.config.settings.speed, &mut self.slow_motion);
+        if speed.to_bits() != self.speed.to_bits() {
+            self.speed = speed;
+            self.runner.set_speed(speed);
+            self.osd.push(
+                Severity::Info,
+                format!("Emulation speed: {}%", (speed * 100.0).round()),
+            );
+        }
+
         let running = self.runner.running();
         self.runner.stop();
 
@@ -283,19 +838,34 @@ impl eframe::App for App {
                 .map(|c| c.0.value())
                 .sum::<u64>()
                 * 2;
+
+            self.frame_skip = state.lazuli.sys.frame_skip.rate();
+
+            for event in state.take_events() {
+                report_event(&mut self.osd, event);
+            }
+
+            let devices = state.lazuli.sys.modules.input.devices();
+            report_devices(&mut self.osd, &mut self.devices, devices);
         }
 
         if running {
             self.runner.start();
         }
 
+        let jit_cache = jit_cache_path(&self.config.settings, &self.dirs);
         let mut context = windows::Ctx {
             step: false,
             running,
             renderer: &mut self.renderer,
+            config: &self.config,
+            user_config: &mut self.user_config,
+            jit_cache: &jit_cache,
         };
 
-        egui::CentralPanel::default().show(ctx, |_| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.osd.paint(ctx, ui.max_rect());
+
             let mut close = None;
             for (index, window_state) in self.windows.iter_mut().enumerate() {
                 let mut open = true;
@@ -346,9 +916,28 @@ impl eframe::App for App {
         }
     }
 
+    fn on_exit(&mut self) {
+        self.runner.stop();
+
+        let state = self.runner.get();
+        let skipped = state.lazuli.skipped_instructions();
+        if skipped.is_empty() {
+            return;
+        }
+
+        println!("unimplemented instructions skipped:");
+        for skipped in skipped {
+            println!(
+                "  {:<10} {:>10}x, last at {} (0x{:08X})",
+                skipped.mnemonic, skipped.count, skipped.pc, skipped.code
+            );
+        }
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         let windows = self.windows.iter().collect::<Vec<_>>();
         storage.set_string("windows", ron::to_string(&windows).unwrap());
+        storage.set_string("recent_files", ron::to_string(&self.recent_files).unwrap());
     }
 }
 
@@ -384,23 +973,24 @@ fn main() -> Result<()> {
     let _tracing_guard = setup_tracing();
     let cfg = cli::Config::parse();
 
-    let device_descriptor = Arc::new(|_: &wgpu::Adapter| {
-        let mut required_features = wgpu::Features::empty();
-        required_features |= wgpu::Features::DUAL_SOURCE_BLENDING;
-        required_features |= wgpu::Features::FLOAT32_FILTERABLE;
-        required_features |= wgpu::Features::PUSH_CONSTANTS;
+    let dirs = directories::ProjectDirs::from("", "", "lazuli").unwrap();
+    let files = config::Files::new(dirs.config_dir());
 
-        let mut required_limits = wgpu::Limits::defaults();
-        required_limits.max_texture_dimension_2d = 8192;
-        required_limits.max_push_constant_size = 64 + 32;
+    let user = config::Layer::load(&files.user)?.unwrap_or_default();
+    let boot = Boot::load(BootSource::from_cli(&cfg), &files, &user, &cfg.layer())?;
 
-        wgpu::DeviceDescriptor {
-            label: Some("lazuli wgpu device"),
-            required_features,
-            required_limits,
-            ..Default::default()
-        }
-    });
+    let effective = boot.config.effective();
+    tracing::info!("effective configuration:\n{effective}");
+    if cfg.print_config {
+        print!("{effective}");
+    }
+
+    let user_config = config::UserLayer {
+        path: files.user.clone(),
+        layer: user,
+    };
+
+    let device_descriptor = Arc::new(renderer::device_descriptor);
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_maximized(true),
@@ -424,7 +1014,7 @@ fn main() -> Result<()> {
         "Lazuli",
         options,
         Box::new(|cc| {
-            let app = App::new(cc, &cfg)?;
+            let app = App::new(cc, &cfg, dirs, files, user_config, boot)?;
             Ok(Box::new(app))
         }),
     )?;
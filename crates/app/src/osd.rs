@@ -0,0 +1,168 @@
+//! The on-screen display: short messages painted over the emulator view, e.g. when a controller
+//! is connected, which fade out after a while.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+/// How many messages are shown at once, after which the oldest ones are dropped.
+pub const MAX_MESSAGES: usize = 6;
+
+/// How long messages take to fade out before expiring.
+const FADE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long messages of this severity stay on screen, including fading out.
+    fn lifetime(self) -> Duration {
+        match self {
+            Self::Info => Duration::from_secs(3),
+            Self::Warning => Duration::from_secs(5),
+            Self::Error => Duration::from_secs(8),
+        }
+    }
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            Self::Info => egui::Color32::WHITE,
+            Self::Warning => egui::Color32::from_rgb(255, 210, 80),
+            Self::Error => egui::Color32::from_rgb(255, 100, 100),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Message {
+    text: String,
+    severity: Severity,
+    expiry: Instant,
+}
+
+impl Message {
+    /// Opacity of the message at the given instant, going from 1 to 0 as it fades out.
+    fn opacity(&self, now: Instant) -> f32 {
+        let left = self.expiry.saturating_duration_since(now);
+        (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0)
+    }
+}
+
+/// Queue of the messages on screen, oldest first.
+#[derive(Debug, Default)]
+pub struct Osd {
+    messages: VecDeque<Message>,
+}
+
+impl Osd {
+    /// Shows a message, starting at the given instant.
+    pub fn push_at(&mut self, now: Instant, severity: Severity, text: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back(Message {
+            text: text.into(),
+            severity,
+            expiry: now + severity.lifetime(),
+        });
+    }
+
+    /// Shows a message, starting now.
+    pub fn push(&mut self, severity: Severity, text: impl Into<String>) {
+        self.push_at(Instant::now(), severity, text);
+    }
+
+    /// Drops expired messages and returns the remaining ones, oldest first, along with their
+    /// opacity.
+    pub fn visible(&mut self, now: Instant) -> impl Iterator<Item = (&str, Severity, f32)> {
+        self.messages.retain(|m| m.expiry > now);
+        self.messages
+            .iter()
+            .map(move |m| (m.text.as_str(), m.severity, m.opacity(now)))
+    }
+
+    /// Paints the messages in the top left corner of `rect`, on top of everything else.
+    pub fn paint(&mut self, ctx: &egui::Context, rect: egui::Rect) {
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("osd"),
+        ));
+
+        let margin = egui::vec2(8.0, 4.0);
+        let mut pos = rect.min + egui::vec2(12.0, 12.0);
+        for (text, severity, opacity) in self.visible(Instant::now()) {
+            let galley = painter.layout_no_wrap(
+                text.to_owned(),
+                egui::FontId::proportional(16.0),
+                severity.color().gamma_multiply(opacity),
+            );
+
+            let background = egui::Rect::from_min_size(pos, galley.size() + 2.0 * margin);
+            painter.rect_filled(
+                background,
+                4.0,
+                egui::Color32::from_black_alpha((160.0 * opacity) as u8),
+            );
+            painter.galley(pos + margin, galley, egui::Color32::WHITE);
+
+            pos.y += background.height() + 4.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn texts(osd: &mut Osd, now: Instant) -> Vec<String> {
+        osd.visible(now).map(|(text, ..)| text.to_owned()).collect()
+    }
+
+    #[test]
+    fn messages_expire() {
+        let start = Instant::now();
+        let mut osd = Osd::default();
+        osd.push_at(start, Severity::Info, "info");
+        osd.push_at(start, Severity::Error, "error");
+
+        let info = Severity::Info.lifetime();
+        assert_eq!(texts(&mut osd, start), ["info", "error"]);
+        assert_eq!(texts(&mut osd, start + info - FADE / 2), ["info", "error"]);
+        assert_eq!(texts(&mut osd, start + info), ["error"]);
+        assert!(texts(&mut osd, start + Severity::Error.lifetime()).is_empty());
+        assert!(osd.messages.is_empty());
+    }
+
+    #[test]
+    fn messages_fade_out() {
+        let start = Instant::now();
+        let mut osd = Osd::default();
+        osd.push_at(start, Severity::Info, "info");
+
+        let opacity = |osd: &mut Osd, now| osd.visible(now).next().map(|(_, _, o)| o);
+        let lifetime = Severity::Info.lifetime();
+        assert_eq!(opacity(&mut osd, start), Some(1.0));
+        assert_eq!(opacity(&mut osd, start + lifetime - FADE), Some(1.0));
+        assert_eq!(opacity(&mut osd, start + lifetime - FADE / 2), Some(0.5));
+        assert_eq!(opacity(&mut osd, start + lifetime), None);
+    }
+
+    #[test]
+    fn oldest_messages_are_dropped() {
+        let start = Instant::now();
+        let mut osd = Osd::default();
+        for i in 0..MAX_MESSAGES + 2 {
+            osd.push_at(start, Severity::Info, i.to_string());
+        }
+
+        let texts = texts(&mut osd, start);
+        assert_eq!(texts.len(), MAX_MESSAGES);
+        assert_eq!(texts[0], "2");
+        assert_eq!(texts[MAX_MESSAGES - 1], (MAX_MESSAGES + 1).to_string());
+    }
+}
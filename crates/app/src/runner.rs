@@ -1,19 +1,39 @@
+pub mod frame_skip;
 mod timer;
 
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
-use lazuli::{Address, Cycles, Lazuli};
+use lazuli::system::vi::FrameSkipRate;
+use lazuli::{Address, Cycles, Lazuli, ResetKind};
 use spin_sleep::SpinSleeper;
 
+use crate::heuristics::Detection;
+use crate::runner::frame_skip::{FrameSkip, FrameTiming};
 use crate::runner::timer::Timer;
 
+/// Something that happened while running which is worth telling the user about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Automatic frame skipping changed the rate at which frames are skipped.
+    FrameSkipChanged(FrameSkipRate),
+    /// Emulation stopped at a breakpoint.
+    BreakpointHit { dsp: bool },
+}
+
+/// How many events are kept until they're taken, after which the oldest ones are dropped.
+const MAX_EVENTS: usize = 64;
+
 pub struct State {
     pub lazuli: Lazuli,
     pub breakpoints: Vec<Address>,
     pub cycles_history: VecDeque<(Cycles, Duration)>,
+    pub frame_skip: FrameSkip,
+    /// What is known about the SDK the running title was built with.
+    pub detection: Detection,
+    events: VecDeque<Event>,
 }
 
 impl State {
@@ -26,22 +46,55 @@ impl State {
     pub fn remove_breakpoint(&mut self, breakpoint: Address) {
         self.breakpoints.retain(|b| *b != breakpoint);
     }
+
+    fn push_event(&mut self, event: Event) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(event);
+    }
+
+    /// Takes the events since the last call, oldest first.
+    pub fn take_events(&mut self) -> impl Iterator<Item = Event> {
+        self.events.drain(..)
+    }
 }
 
 struct Shared {
     state: Mutex<State>,
     advance: AtomicBool,
+    /// Bits of the emulation speed, relative to real time.
+    speed: AtomicU64,
 }
 
 const STEP: Duration = Duration::from_millis(1);
 
+/// Lowest emulation speed, relative to real time.
+pub const MIN_SPEED: f64 = 0.01;
+
+/// Whether audio plays at the given speed. Audio isn't resampled to follow the speed, so it's
+/// muted unless emulation runs at (about) real time.
+fn plays_audio(speed: f64) -> bool {
+    (speed - 1.0).abs() < 0.01
+}
+
 fn worker(runner_state: Arc<Shared>) {
+    profiling::register_thread!();
+
     let sleeper = SpinSleeper::default();
 
     let mut timer = Timer::new();
     let mut emulated = Duration::ZERO;
+    let mut last = Duration::ZERO;
 
     loop {
+        // the timer runs at the emulation speed, so everything below is in emulated time
+        let speed = f64::from_bits(runner_state.speed.load(Ordering::Relaxed));
+        if speed.to_bits() != timer.scale().to_bits() {
+            timer.set_scale(speed);
+        }
+
         if runner_state.advance.load(Ordering::Relaxed) {
             timer.resume();
         } else {
@@ -58,10 +111,12 @@ fn worker(runner_state: Arc<Shared>) {
         // wait until delta >= STEP
         let to_sleep = STEP.saturating_sub(delta);
         if !to_sleep.is_zero() {
-            sleeper.sleep(to_sleep);
+            sleeper.sleep(to_sleep.div_f64(speed));
         }
 
         let now = timer.elapsed();
+        let real = now.saturating_sub(last);
+        last = now;
 
         // ignore slowdowns that are too large (~1 frame at 60fps)
         let delta = if delta > Duration::from_millis(16) {
@@ -74,14 +129,38 @@ fn worker(runner_state: Arc<Shared>) {
         let mut lock = runner_state.state.lock().unwrap();
         let state = &mut *lock;
 
+        // how far the CPU gets in a single exec call affects when events and the DSP run, so
+        // deterministic runs always take the same step and catch up over the next iterations
+        let delta = if state.lazuli.sys.config.deterministic {
+            STEP
+        } else {
+            delta
+        };
+
         let executed = state
             .lazuli
             .exec(Cycles::from_duration(delta), &state.breakpoints);
 
         emulated += delta;
+        state.detection.poll(state.lazuli.sys.mem.ram());
 
-        if executed.hit_breakpoint {
+        let timing = FrameTiming {
+            real,
+            emulated: executed.cycles.to_duration(),
+            idle: to_sleep,
+        };
+
+        if let Some(rate) = state.frame_skip.record(timing) {
+            tracing::info!("frame skip rate changed to {}/{}", rate.skip, rate.cycle);
+            state.lazuli.sys.frame_skip.set_rate(rate);
+            state.push_event(Event::FrameSkipChanged(rate));
+        }
+
+        if executed.hit_breakpoint || executed.hit_dsp_breakpoint {
             runner_state.advance.store(false, Ordering::SeqCst);
+            state.push_event(Event::BreakpointHit {
+                dsp: executed.hit_dsp_breakpoint,
+            });
         }
 
         while let Some(front) = state.cycles_history.front()
@@ -98,14 +177,18 @@ pub struct Runner {
 }
 
 impl Runner {
-    pub fn new(lazuli: Lazuli) -> Self {
+    pub fn new(lazuli: Lazuli, detection: Detection) -> Self {
         let state = Shared {
             state: Mutex::new(State {
                 lazuli,
                 breakpoints: vec![],
                 cycles_history: VecDeque::new(),
+                frame_skip: FrameSkip::default(),
+                detection,
+                events: VecDeque::new(),
             }),
             advance: AtomicBool::new(false),
+            speed: AtomicU64::new(1.0f64.to_bits()),
         };
 
         let state = Arc::new(state);
@@ -135,6 +218,58 @@ impl Runner {
         }
     }
 
+    pub fn reset(&mut self, kind: ResetKind) {
+        let mut lock = self.shared.state.lock().unwrap();
+        lock.lazuli.reset(kind);
+        lock.cycles_history.clear();
+    }
+
+    /// Replaces the emulator, e.g. to boot something else. Breakpoints are cleared, since they
+    /// refer to the code of the old one.
+    pub fn replace(&mut self, lazuli: Lazuli, detection: Detection) {
+        self.stop();
+
+        let mut lock = self.shared.state.lock().unwrap();
+        lock.lazuli = lazuli;
+        lock.detection = detection;
+        lock.breakpoints.clear();
+        lock.cycles_history.clear();
+        lock.events.clear();
+
+        let rate = lock.frame_skip.rate();
+        lock.lazuli.sys.frame_skip.set_rate(rate);
+
+        let muted = !plays_audio(self.speed());
+        lock.lazuli.sys.modules.audio.set_muted(muted);
+    }
+
+    /// The emulation speed, relative to real time.
+    pub fn speed(&self) -> f64 {
+        f64::from_bits(self.shared.speed.load(Ordering::Relaxed))
+    }
+
+    /// Sets the emulation speed, relative to real time, which is at least [`MIN_SPEED`]. Audio is
+    /// muted unless the speed is about 1.
+    pub fn set_speed(&mut self, speed: f64) {
+        let speed = speed.max(MIN_SPEED);
+        self.shared.speed.store(speed.to_bits(), Ordering::Relaxed);
+
+        let mut lock = self.shared.state.lock().unwrap();
+        lock.lazuli.sys.modules.audio.set_muted(!plays_audio(speed));
+    }
+
+    /// Sets how frames are skipped. See [`frame_skip::Policy`].
+    pub fn set_frame_skip(&mut self, policy: frame_skip::Policy) {
+        let mut lock = self.shared.state.lock().unwrap();
+        let state = &mut *lock;
+        state.frame_skip.set_policy(policy);
+        state
+            .lazuli
+            .sys
+            .frame_skip
+            .set_rate(state.frame_skip.rate());
+    }
+
     pub fn running(&mut self) -> bool {
         self.shared.advance.load(Ordering::Relaxed)
     }
@@ -0,0 +1,219 @@
+//! Frame skip policy, deciding how many frames to skip from how well emulation keeps up.
+use std::time::Duration;
+
+use lazuli::system::vi::FrameSkipRate;
+
+use crate::config::FrameSkipMode;
+
+/// Length of the windows timing is measured over.
+const WINDOW: Duration = Duration::from_millis(250);
+/// For how many consecutive windows emulation has to be slow (or fast) before the amount of
+/// skipped frames changes.
+const SUSTAIN: u32 = 4;
+/// Below which speed a window is considered slow.
+const SLOW_SPEED: f64 = 0.97;
+/// Above which fraction of idle time a window is considered fast.
+const FAST_IDLE: f64 = 0.3;
+/// Maximum amount of frames skipped per presented frame in auto mode.
+const MAX_AUTO_SKIP: u8 = 3;
+
+/// How frames should be skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    pub mode: FrameSkipMode,
+    /// Frames skipped per presented frame, in fixed mode.
+    pub fixed: u8,
+    /// Whether skipped frames also skip their draws.
+    pub draws: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            mode: FrameSkipMode::Off,
+            fixed: 1,
+            draws: false,
+        }
+    }
+}
+
+/// Timing of the emulation thread over a window of real time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTiming {
+    /// Real time elapsed.
+    pub real: Duration,
+    /// Emulated time.
+    pub emulated: Duration,
+    /// Real time spent waiting for real time to catch up with emulation.
+    pub idle: Duration,
+}
+
+impl FrameTiming {
+    /// Ratio of emulated time to real time.
+    pub fn speed(&self) -> f64 {
+        self.emulated.div_duration_f64(self.real)
+    }
+
+    /// Fraction of real time spent idle.
+    pub fn idle(&self) -> f64 {
+        self.idle.div_duration_f64(self.real)
+    }
+}
+
+/// Applies a [`Policy`], adjusting the amount of skipped frames from timing measurements in
+/// auto mode.
+#[derive(Debug, Default)]
+pub struct FrameSkip {
+    policy: Policy,
+    timing: FrameTiming,
+    /// Frames currently skipped per presented frame, in auto mode.
+    auto_skip: u8,
+    /// Consecutive slow windows.
+    slow: u32,
+    /// Consecutive fast windows.
+    fast: u32,
+}
+
+impl FrameSkip {
+    pub fn set_policy(&mut self, policy: Policy) {
+        *self = Self {
+            policy,
+            ..Default::default()
+        };
+    }
+
+    /// The rate at which frames should currently be skipped.
+    pub fn rate(&self) -> FrameSkipRate {
+        let skip = match self.policy.mode {
+            FrameSkipMode::Off => return FrameSkipRate::default(),
+            FrameSkipMode::Auto => self.auto_skip,
+            FrameSkipMode::Fixed => self.policy.fixed,
+        };
+
+        FrameSkipRate {
+            skip,
+            cycle: skip.saturating_add(1),
+            draws: self.policy.draws,
+        }
+    }
+
+    /// Records an iteration of the emulation thread. Returns the new rate if it changed.
+    pub fn record(&mut self, timing: FrameTiming) -> Option<FrameSkipRate> {
+        if self.policy.mode != FrameSkipMode::Auto {
+            return None;
+        }
+
+        self.timing.real += timing.real;
+        self.timing.emulated += timing.emulated;
+        self.timing.idle += timing.idle;
+        if self.timing.real < WINDOW {
+            return None;
+        }
+
+        let window = std::mem::take(&mut self.timing);
+        if window.speed() < SLOW_SPEED {
+            self.slow += 1;
+            self.fast = 0;
+        } else if window.idle() > FAST_IDLE {
+            self.fast += 1;
+            self.slow = 0;
+        } else {
+            self.slow = 0;
+            self.fast = 0;
+        }
+
+        let skip = if self.slow >= SUSTAIN {
+            self.auto_skip.saturating_add(1).min(MAX_AUTO_SKIP)
+        } else if self.fast >= SUSTAIN {
+            self.auto_skip.saturating_sub(1)
+        } else {
+            return None;
+        };
+
+        self.slow = 0;
+        self.fast = 0;
+        if skip == self.auto_skip {
+            return None;
+        }
+
+        self.auto_skip = skip;
+        Some(self.rate())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    fn auto() -> FrameSkip {
+        let mut skip = FrameSkip::default();
+        skip.set_policy(Policy {
+            mode: FrameSkipMode::Auto,
+            ..Default::default()
+        });
+
+        skip
+    }
+
+    /// Records `duration` worth of iterations running at `speed` and idle for `idle` of the time,
+    /// returning the rates reported.
+    fn run(skip: &mut FrameSkip, duration: Duration, speed: f64, idle: f64) -> Vec<u8> {
+        let timing = FrameTiming {
+            real: TICK,
+            emulated: TICK.mul_f64(speed),
+            idle: TICK.mul_f64(idle),
+        };
+
+        (0..duration.div_duration_f64(TICK) as u32)
+            .filter_map(|_| skip.record(timing))
+            .map(|rate| rate.skip)
+            .collect()
+    }
+
+    #[test]
+    fn off_and_fixed_ignore_timing() {
+        let mut skip = FrameSkip::default();
+        assert!(run(&mut skip, Duration::from_secs(5), 0.5, 0.0).is_empty());
+        assert_eq!(skip.rate(), FrameSkipRate::default());
+
+        skip.set_policy(Policy {
+            mode: FrameSkipMode::Fixed,
+            fixed: 2,
+            draws: true,
+        });
+
+        assert!(run(&mut skip, Duration::from_secs(5), 0.5, 0.0).is_empty());
+        assert_eq!(
+            skip.rate(),
+            FrameSkipRate {
+                skip: 2,
+                cycle: 3,
+                draws: true,
+            }
+        );
+    }
+
+    #[test]
+    fn auto_reacts_to_sustained_slowdowns() {
+        let mut skip = auto();
+
+        // a short hiccup is ignored
+        assert!(run(&mut skip, WINDOW * 2, 0.5, 0.0).is_empty());
+        assert!(run(&mut skip, WINDOW, 1.0, 0.1).is_empty());
+
+        // a sustained slowdown increases skipping up to the maximum
+        let rates = run(&mut skip, Duration::from_secs(10), 0.5, 0.0);
+        assert_eq!(rates, [1, 2, 3]);
+        assert_eq!(skip.rate().cycle, 4);
+
+        // running at full speed without headroom keeps the current rate
+        assert!(run(&mut skip, Duration::from_secs(5), 1.0, 0.1).is_empty());
+
+        // headroom brings it back down
+        let rates = run(&mut skip, Duration::from_secs(10), 1.0, 0.5);
+        assert_eq!(rates, [2, 1, 0]);
+        assert_eq!(skip.rate().skipped(), 0);
+    }
+}
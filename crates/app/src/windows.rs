@@ -1,24 +1,40 @@
 mod call_stack;
 mod control;
+mod controllers;
+mod debug_output;
 mod disasm;
+mod disk_activity;
 mod efb;
+mod export;
+mod guest_console;
+mod image_view;
+mod memcard;
+mod memory;
 mod registers;
 mod renderer_info;
+mod settings;
 mod subsystem;
 mod threads;
 mod variables;
 mod xfb;
 
+use std::path::Path;
+
 use eframe::egui::{self, Vec2};
 use renderer::Renderer;
 use serde::{Deserialize, Serialize};
 
+use crate::config::{Config, UserLayer};
 use crate::runner::State;
 
 pub struct Ctx<'a> {
     pub step: bool,
     pub running: bool,
     pub renderer: &'a mut Renderer,
+    pub config: &'a Config,
+    pub user_config: &'a mut UserLayer,
+    /// Path to the JIT block cache directory.
+    pub jit_cache: &'a Path,
 }
 
 #[typetag::serde]
@@ -62,6 +78,10 @@ pub fn variables() -> variables::Window {
     Default::default()
 }
 
+pub fn controllers() -> controllers::Window {
+    Default::default()
+}
+
 pub fn xfb() -> xfb::Window {
     Default::default()
 }
@@ -74,6 +94,30 @@ pub fn renderer() -> renderer_info::Window {
     Default::default()
 }
 
+pub fn guest_console() -> guest_console::Window {
+    Default::default()
+}
+
+pub fn debug_output() -> debug_output::Window {
+    Default::default()
+}
+
+pub fn memory() -> memory::Window {
+    Default::default()
+}
+
+pub fn disk_activity() -> disk_activity::Window {
+    Default::default()
+}
+
+pub fn memcard() -> memcard::Window {
+    Default::default()
+}
+
+pub fn settings() -> settings::Window {
+    Default::default()
+}
+
 pub fn subsystem_cp() -> subsystem::cp::Window {
     Default::default()
 }
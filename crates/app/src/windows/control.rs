@@ -2,9 +2,11 @@ use std::collections::HashMap;
 
 use eframe::egui::{self, RichText};
 use lazuli::Address;
+use lazuli::cores::{BlockStats, SkippedInstruction};
 use serde::{Deserialize, Serialize};
 
 use crate::State;
+use crate::heuristics::{Era, SdkDate};
 use crate::windows::{AppWindow, Ctx};
 
 #[derive(Default, Serialize, Deserialize)]
@@ -21,6 +23,16 @@ pub struct Window {
     breakpoint_text: String,
     #[serde(default)]
     labels: HashMap<u32, String>,
+    #[serde(skip)]
+    skipped: Vec<SkippedInstruction>,
+    #[serde(skip)]
+    block_stats: Option<BlockStats>,
+    #[serde(skip)]
+    apploader: Option<SdkDate>,
+    #[serde(skip)]
+    sdk: Option<SdkDate>,
+    #[serde(skip)]
+    era: Option<&'static Era>,
 }
 
 impl Window {}
@@ -50,6 +62,16 @@ impl AppWindow for Window {
         self.labels.retain(|b, _| self.breakpoints.contains(b));
 
         self.current_pc = state.lazuli.sys.cpu.pc.value();
+
+        self.skipped.clear();
+        self.skipped
+            .extend_from_slice(state.lazuli.skipped_instructions());
+
+        self.block_stats = state.lazuli.block_stats();
+
+        self.apploader = state.detection.apploader;
+        self.sdk = state.detection.sdk;
+        self.era = state.detection.era();
     }
 
     fn show(&mut self, ui: &mut egui::Ui, ctx: &mut Ctx) {
@@ -63,6 +85,49 @@ impl AppWindow for Window {
             }
         });
 
+        ui.separator();
+        ui.label("Detected");
+
+        let date = |date: Option<SdkDate>| date.map_or("unknown".to_owned(), |d| d.to_string());
+        ui.label(format!("Apploader: {}", date(self.apploader)));
+        ui.label(format!("OS library: {}", date(self.sdk)));
+        ui.label(format!(
+            "SDK era: {}",
+            self.era.map_or("unknown", |era| era.name)
+        ));
+
+        if !self.skipped.is_empty() {
+            ui.separator();
+            egui::CollapsingHeader::new("Unimplemented instructions")
+                .default_open(true)
+                .show(ui, |ui| {
+                    for skipped in &self.skipped {
+                        ui.label(format!("{} x{}", skipped.mnemonic, skipped.count))
+                            .on_hover_text(format!(
+                                "last skipped at {}: 0x{:08X}",
+                                skipped.pc, skipped.code
+                            ));
+                    }
+                });
+        }
+
+        if let Some(stats) = &self.block_stats {
+            ui.separator();
+            egui::CollapsingHeader::new("JIT blocks").show(ui, |ui| {
+                ui.label(format!(
+                    "Lookups: {} hits, {} misses ({:.1}%)",
+                    stats.lookup_hits,
+                    stats.lookup_misses,
+                    stats.hit_rate() * 100.0
+                ));
+                ui.label(format!(
+                    "Links: {} ({} misses)",
+                    stats.links, stats.link_misses
+                ));
+                ui.label(format!("Unlinks: {}", stats.unlinks));
+            });
+        }
+
         ui.separator();
         ui.label("Breakpoints");
 
@@ -0,0 +1,76 @@
+use eframe::egui;
+use lazuli::modules::input::{Device, DeviceId, PORT_COUNT};
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::{AppWindow, Ctx};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    #[serde(skip)]
+    devices: Vec<Device>,
+    #[serde(skip)]
+    ports: [Option<DeviceId>; PORT_COUNT],
+    #[serde(skip)]
+    assignments: Vec<(usize, Option<DeviceId>)>,
+}
+
+impl Window {
+    fn device_label(&self, id: Option<DeviceId>) -> String {
+        let Some(id) = id else {
+            return "None".to_owned();
+        };
+
+        match self.devices.iter().find(|d| d.id == id) {
+            Some(device) if device.connected => format!("{} (#{})", device.name, id.0),
+            Some(device) => format!("{} (#{}, disconnected)", device.name, id.0),
+            None => format!("#{}", id.0),
+        }
+    }
+}
+
+#[typetag::serde(name = "controllers")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Controllers"
+    }
+
+    fn prepare(&mut self, state: &mut State) {
+        let input = &mut state.lazuli.sys.modules.input;
+        for (port, device) in self.assignments.drain(..) {
+            input.assign(port, device);
+        }
+
+        self.devices = input.devices();
+        self.ports = input.ports();
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        if self.devices.is_empty() {
+            ui.label("No devices");
+        }
+
+        egui::Grid::new("controller_ports").show(ui, |ui| {
+            for port in 0..PORT_COUNT {
+                ui.label(format!("Port {}", port + 1));
+
+                let mut selected = self.ports[port];
+                egui::ComboBox::from_id_salt(("controller_port", port))
+                    .selected_text(self.device_label(selected))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selected, None, "None");
+                        for device in &self.devices {
+                            let label = self.device_label(Some(device.id));
+                            ui.selectable_value(&mut selected, Some(device.id), label);
+                        }
+                    });
+
+                if selected != self.ports[port] {
+                    self.assignments.push((port, selected));
+                }
+
+                ui.end_row();
+            }
+        });
+    }
+}
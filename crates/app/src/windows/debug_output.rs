@@ -0,0 +1,65 @@
+//! The debug output: lines the guest writes to the debug output port, e.g. through OSReport.
+use eframe::egui;
+use lazuli::system::debug_output::MAX_LINES;
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::{AppWindow, Ctx};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    #[serde(skip)]
+    lines: Vec<String>,
+    /// The line being written.
+    #[serde(skip)]
+    pending: String,
+    /// How many lines of the output were already copied.
+    #[serde(skip)]
+    seen: u64,
+}
+
+#[typetag::serde(name = "debug_output")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Debug Output"
+    }
+
+    fn default_size(&self) -> Option<egui::Vec2> {
+        Some(egui::Vec2::new(600.0, 400.0))
+    }
+
+    fn prepare(&mut self, state: &mut State) {
+        let output = state.lazuli.sys.debug_output();
+
+        self.lines
+            .extend(output.lines_since(self.seen).map(str::to_owned));
+        self.seen = output.total();
+
+        let excess = self.lines.len().saturating_sub(MAX_LINES);
+        self.lines.drain(..excess);
+
+        self.pending.clear();
+        self.pending
+            .push_str(&String::from_utf8_lossy(output.pending()));
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        if ui.button("Clear").clicked() {
+            self.lines.clear();
+        }
+
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = self.lines.len() + 1;
+        egui::ScrollArea::both()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, rows, |ui, range| {
+                for row in range {
+                    let line = self.lines.get(row).unwrap_or(&self.pending);
+                    ui.add(egui::Label::new(egui::RichText::new(line).monospace()).extend());
+                }
+            });
+    }
+}
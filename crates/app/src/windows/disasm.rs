@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use eframe::egui;
 use egui_extras::{Column, TableBuilder};
 use lazuli::Address;
@@ -14,6 +16,8 @@ pub struct Window {
     target_text: String,
     follow_pc: bool,
     simplified: bool,
+    #[serde(default)]
+    heatmap: bool,
 
     #[serde(skip)]
     pc: u32,
@@ -25,6 +29,11 @@ pub struct Window {
     breakpoints: Vec<u32>,
     #[serde(skip)]
     breakpoint_to_toggle: Option<u32>,
+    /// How many times each instruction has been executed since the heatmap was enabled.
+    #[serde(skip)]
+    heat: HashMap<u32, u64>,
+    #[serde(skip)]
+    max_heat: u64,
 }
 
 impl Default for Window {
@@ -34,17 +43,47 @@ impl Default for Window {
             target_text: String::new(),
             follow_pc: true,
             simplified: true,
+            heatmap: false,
             instructions: Vec::new(),
 
             pc: 0,
             rows: 0,
             breakpoints: Vec::new(),
             breakpoint_to_toggle: None,
+            heat: HashMap::new(),
+            max_heat: 0,
         }
     }
 }
 
-impl Window {}
+impl Window {
+    fn update_heat(&mut self, state: &mut State) {
+        state.lazuli.set_block_tracing(self.heatmap);
+        if !self.heatmap {
+            self.heat.clear();
+            self.max_heat = 0;
+            return;
+        }
+
+        for (addr, block) in state.lazuli.take_block_trace().blocks() {
+            for i in 0..block.instructions {
+                let heat = self.heat.entry(addr.value() + 4 * i).or_default();
+                *heat += block.entries;
+                self.max_heat = self.max_heat.max(*heat);
+            }
+        }
+    }
+
+    /// Background color of the instruction at `addr` in the heatmap.
+    fn heat_color(&self, addr: u32) -> Option<egui::Color32> {
+        let heat = *self.heat.get(&addr)?;
+
+        // logarithmic, otherwise only the hottest loop stands out
+        let intensity = (heat as f32).ln_1p() / (self.max_heat as f32).ln_1p();
+        let alpha = (16.0 + 96.0 * intensity) as u8;
+        Some(egui::Color32::from_rgba_unmultiplied(255, 96, 0, alpha))
+    }
+}
 
 #[typetag::serde(name = "disasm")]
 impl AppWindow for Window {
@@ -53,6 +92,8 @@ impl AppWindow for Window {
     }
 
     fn prepare(&mut self, state: &mut State) {
+        self.update_heat(state);
+
         self.breakpoints.clear();
         self.breakpoints
             .extend(state.breakpoints.iter().map(|b| b.value()));
@@ -92,6 +133,8 @@ impl AppWindow for Window {
         ui.horizontal(|ui| {
             ui.checkbox(&mut self.follow_pc, "Follow PC");
             ui.checkbox(&mut self.simplified, "Simplified");
+            ui.checkbox(&mut self.heatmap, "Heatmap")
+                .on_hover_text("Shade instructions by how often they are executed");
         });
 
         if !self.follow_pc {
@@ -170,6 +213,10 @@ impl AppWindow for Window {
                         });
 
                         row.col(|ui| {
+                            if let Some(color) = self.heat_color(current) {
+                                ui.painter().rect_filled(ui.max_rect(), 0.0, color);
+                            }
+
                             let mut parsed = ParsedIns::new();
                             if self.simplified {
                                 ins.parse_simplified(&mut parsed);
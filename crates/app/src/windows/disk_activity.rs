@@ -0,0 +1,172 @@
+//! Disk activity: recent reads of the disk plotted by offset over time, along with the
+//! throughput.
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+use eframe::egui::{self, Align2, Color32, FontId, Pos2};
+use lazuli::modules::disk::DiskRead;
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::{AppWindow, Ctx};
+
+/// Size of a GameCube disc, the minimum range of the offset axis.
+const DISC_SIZE: u64 = 1_459_978_240;
+
+/// How far back the plot goes.
+const HISTORY: Duration = Duration::from_secs(10);
+
+/// Reads whose point is closer than this to the pointer are described on hover.
+const HOVER_DISTANCE: f32 = 4.0;
+
+const COMPLETED_COLOR: Color32 = Color32::LIGHT_BLUE;
+const IN_FLIGHT_COLOR: Color32 = Color32::ORANGE;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    #[serde(skip)]
+    reads: Vec<DiskRead>,
+    #[serde(skip)]
+    now: Option<Instant>,
+}
+
+impl Window {
+    /// Bytes read per second, over the reads which completed in the last second.
+    fn throughput(&self, now: Instant) -> u64 {
+        let since = now.checked_sub(Duration::from_secs(1));
+        self.reads
+            .iter()
+            .filter(|read| read.duration.is_some())
+            .filter(|read| since.is_none_or(|since| read.start >= since))
+            .map(|read| read.length)
+            .sum()
+    }
+}
+
+fn describe(read: &DiskRead) -> String {
+    let duration = match read.duration {
+        Some(duration) => format!("{:.3} ms", duration.as_secs_f64() * 1000.0),
+        None => "in flight".to_owned(),
+    };
+
+    format!(
+        "Offset: 0x{:09X}\nLength: {}\nDuration: {duration}\nQueue depth: {}",
+        read.offset,
+        ByteSize(read.length),
+        read.queue_depth
+    )
+}
+
+#[typetag::serde(name = "disk_activity")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Disk Activity"
+    }
+
+    fn default_size(&self) -> Option<egui::Vec2> {
+        Some(egui::Vec2::new(500.0, 300.0))
+    }
+
+    fn prepare(&mut self, state: &mut State) {
+        self.reads = state.lazuli.sys.modules.disk.recent_activity();
+        self.now = Some(Instant::now());
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        let Some(now) = self.now else {
+            return;
+        };
+
+        let in_flight = self
+            .reads
+            .iter()
+            .filter(|read| read.duration.is_none())
+            .count();
+
+        ui.horizontal(|ui| {
+            let throughput = self.throughput(now) as f64 / 1_000_000.0;
+            ui.label(format!("Throughput: {throughput:.2} MB/s"));
+            ui.separator();
+            ui.label(format!("In flight: {in_flight}"));
+        });
+
+        ui.separator();
+
+        let size = ui.available_size().max(egui::Vec2::splat(64.0));
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        let disc_size = self
+            .reads
+            .iter()
+            .map(|read| read.offset + read.length)
+            .fold(DISC_SIZE, u64::max);
+
+        let points = self.reads.iter().filter_map(|read| {
+            let age = now.saturating_duration_since(read.start);
+            if age > HISTORY {
+                return None;
+            }
+
+            let x = egui::remap(
+                age.as_secs_f32(),
+                0.0..=HISTORY.as_secs_f32(),
+                rect.right()..=rect.left(),
+            );
+            let y = egui::remap(
+                read.offset as f32,
+                0.0..=disc_size as f32,
+                rect.top()..=rect.bottom(),
+            );
+
+            Some((Pos2::new(x, y), read))
+        });
+
+        let pointer = response.hover_pos();
+        let mut hovered = None;
+        for (pos, read) in points {
+            let color = if read.duration.is_some() {
+                COMPLETED_COLOR
+            } else {
+                IN_FLIGHT_COLOR
+            };
+            painter.circle_filled(pos, 2.0, color);
+
+            if pointer.is_some_and(|pointer| pointer.distance(pos) < HOVER_DISTANCE) {
+                hovered = Some(read);
+            }
+        }
+
+        let font = FontId::monospace(10.0);
+        let text_color = ui.visuals().weak_text_color();
+        painter.text(
+            rect.left_top(),
+            Align2::LEFT_TOP,
+            "0x0",
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            rect.left_bottom(),
+            Align2::LEFT_BOTTOM,
+            ByteSize(disc_size).to_string(),
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            rect.right_bottom(),
+            Align2::RIGHT_BOTTOM,
+            format!("-{} s .. now", HISTORY.as_secs()),
+            font,
+            text_color,
+        );
+
+        if let Some(read) = hovered {
+            response.on_hover_text_at_pointer(describe(read));
+        }
+
+        // keep the plot scrolling
+        ui.ctx().request_repaint();
+    }
+}
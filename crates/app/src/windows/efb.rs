@@ -1,29 +1,143 @@
-use eframe::egui::{self, Vec2};
-use eframe::egui_wgpu::{self, CallbackTrait};
-use lazuli::system::gx::{EFB_HEIGHT, EFB_WIDTH};
-use renderer::Renderer;
+use eframe::egui;
+use lazuli::modules::render::{Action, RenderModule, oneshot};
+use lazuli::system::gx::color::Rgba8;
+use lazuli::system::gx::{DEPTH_24_BIT_MAX, EFB_HEIGHT, EFB_WIDTH};
 use serde::{Deserialize, Serialize};
 
 use crate::State;
+use crate::windows::image_view::{Image, ImageView};
 use crate::windows::{AppWindow, Ctx};
 
-pub struct RendererCallback {
-    renderer: Renderer,
+/// What to show of the EFB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum Channel {
+    #[default]
+    Color,
+    /// Alpha, as grayscale.
+    Alpha,
+    /// Depth, as grayscale normalized to the range of depth values in the EFB.
+    Depth,
 }
 
-impl CallbackTrait for RendererCallback {
-    fn paint(
-        &self,
-        _info: egui::PaintCallbackInfo,
-        render_pass: &mut eframe::wgpu::RenderPass<'static>,
-        _callback_resources: &egui_wgpu::CallbackResources,
-    ) {
-        self.renderer.render(render_pass);
+/// Polls a pending readback, storing its data once it arrives.
+fn poll<T>(pending: &mut Option<oneshot::Receiver<Vec<T>>>, data: &mut Vec<T>) -> bool {
+    let Some(receiver) = pending else {
+        return false;
+    };
+
+    match receiver.try_recv() {
+        Ok(received) => {
+            *data = received;
+            *pending = None;
+            true
+        }
+        Err(oneshot::TryRecvError::Empty) => false,
+        Err(oneshot::TryRecvError::Disconnected) => {
+            *pending = None;
+            false
+        }
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
-pub struct Window;
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Window {
+    channel: Channel,
+    /// Whether to read the EFB back continuously, instead of on request.
+    live: bool,
+    #[serde(skip)]
+    pending_color: Option<oneshot::Receiver<Vec<Rgba8>>>,
+    #[serde(skip)]
+    pending_depth: Option<oneshot::Receiver<Vec<u32>>>,
+    #[serde(skip)]
+    color: Vec<Rgba8>,
+    #[serde(skip)]
+    depth: Vec<u32>,
+    #[serde(skip)]
+    image: Image,
+    #[serde(skip, default = "view")]
+    view: ImageView,
+}
+
+fn view() -> ImageView {
+    ImageView::new("efb")
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            channel: Channel::Color,
+            live: true,
+            pending_color: None,
+            pending_depth: None,
+            color: Vec::new(),
+            depth: Vec::new(),
+            image: Image::default(),
+            view: view(),
+        }
+    }
+}
+
+impl Window {
+    /// Requests a readback of the whole EFB. Readbacks are only ever requested while the window
+    /// is shown, so they cost nothing during normal play.
+    fn request(&mut self, ctx: &mut Ctx) {
+        let (color, color_receiver) = oneshot::channel();
+        let (depth, depth_receiver) = oneshot::channel();
+
+        ctx.renderer.exec(Action::ColorCopy {
+            x: 0,
+            y: 0,
+            width: EFB_WIDTH as u16,
+            height: EFB_HEIGHT as u16,
+            half: false,
+            clear: false,
+            response: color,
+        });
+        ctx.renderer.exec(Action::DepthCopy {
+            x: 0,
+            y: 0,
+            width: EFB_WIDTH as u16,
+            height: EFB_HEIGHT as u16,
+            half: false,
+            clear: false,
+            response: depth,
+        });
+
+        self.pending_color = Some(color_receiver);
+        self.pending_depth = Some(depth_receiver);
+    }
+
+    /// Rebuilds the shown image from the last readback, according to the selected channel.
+    fn update_image(&mut self) {
+        let gray = |value: u8| Rgba8 {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        };
+
+        let pixels = match self.channel {
+            Channel::Color => self.color.iter().map(|p| Rgba8 { a: 255, ..*p }).collect(),
+            Channel::Alpha => self.color.iter().map(|p| gray(p.a)).collect(),
+            Channel::Depth => {
+                let min = self.depth.iter().copied().min().unwrap_or(0);
+                let max = self.depth.iter().copied().max().unwrap_or(0);
+                let range = (max - min).max(1) as u64;
+                self.depth
+                    .iter()
+                    .map(|&z| gray(((z - min) as u64 * 255 / range) as u8))
+                    .collect()
+            }
+        };
+
+        self.image = Image {
+            width: EFB_WIDTH as usize,
+            height: EFB_HEIGHT as usize,
+            pixels,
+        };
+    }
+}
 
 #[typetag::serde(name = "efb")]
 impl AppWindow for Window {
@@ -38,30 +152,49 @@ impl AppWindow for Window {
     fn prepare(&mut self, _: &mut State) {}
 
     fn show(&mut self, ui: &mut egui::Ui, ctx: &mut Ctx) {
-        egui::Frame::canvas(ui.style()).show(ui, |ui| {
-            let aspect_ratio = 4.0 / 3.0;
-            let available_height = (ui.available_height() - 20.0).max(0.0);
-
-            let rect = if ui.available_width() < available_height {
-                ui.allocate_exact_size(
-                    Vec2::new(ui.available_width(), ui.available_width() / aspect_ratio),
-                    egui::Sense::click(),
-                )
-                .0
-            } else {
-                ui.allocate_exact_size(
-                    Vec2::new(available_height * aspect_ratio, available_height),
-                    egui::Sense::click(),
-                )
-                .0
+        let mut changed = poll(&mut self.pending_color, &mut self.color);
+        changed |= poll(&mut self.pending_depth, &mut self.depth);
+
+        let mut refresh = false;
+        ui.horizontal(|ui| {
+            for (channel, name) in [
+                (Channel::Color, "Color"),
+                (Channel::Alpha, "Alpha"),
+                (Channel::Depth, "Depth"),
+            ] {
+                changed |= ui
+                    .selectable_value(&mut self.channel, channel, name)
+                    .changed();
+            }
+
+            ui.separator();
+            ui.checkbox(&mut self.live, "Live");
+            refresh = ui.button("Refresh").clicked();
+        });
+
+        let pending = self.pending_color.is_some() || self.pending_depth.is_some();
+        let empty = self.color.is_empty() && self.depth.is_empty();
+        if !pending && (self.live || refresh || empty) {
+            self.request(ctx);
+        }
+
+        if self.pending_color.is_some() || self.pending_depth.is_some() {
+            ui.ctx().request_repaint();
+        }
+
+        let expected = EFB_WIDTH as usize * EFB_HEIGHT as usize;
+        if changed && self.color.len() == expected && self.depth.len() == expected {
+            self.update_image();
+            self.view.set(ui.ctx(), &self.image);
+        }
+
+        let depth = &self.depth;
+        self.view.show(ui, &self.image, |x, y| {
+            let Some(&z) = depth.get(y * EFB_WIDTH as usize + x) else {
+                return String::new();
             };
 
-            ui.painter().add(egui_wgpu::Callback::new_paint_callback(
-                rect,
-                RendererCallback {
-                    renderer: ctx.renderer.clone(),
-                },
-            ));
+            format!("Z 0x{z:06X} ({:.6})", z as f32 / DEPTH_24_BIT_MAX as f32)
         });
     }
 }
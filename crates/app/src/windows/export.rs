@@ -0,0 +1,161 @@
+//! Exporting raw bytes from debug windows: to a binary file, or to the clipboard as a C array or a
+//! hex string.
+use std::fmt::Write;
+use std::path::Path;
+
+use eframe::egui;
+
+/// How many elements are written per line of a C array.
+pub const C_ARRAY_WIDTH: usize = 12;
+
+/// Ways of exporting a range of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Export {
+    /// Write the bytes to a file chosen by the user.
+    Binary,
+    /// Copy the bytes to the clipboard as a C array.
+    CArray,
+    /// Copy the bytes to the clipboard as a hex string.
+    Hex,
+}
+
+impl Export {
+    pub const ALL: [Self; 3] = [Self::Binary, Self::CArray, Self::Hex];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Binary => "Save as binary...",
+            Self::CArray => "Copy as C array",
+            Self::Hex => "Copy as hex string",
+        }
+    }
+}
+
+/// Formats `bytes` as a C array definition named `name`, with `width` elements per line.
+///
+/// Every element is followed by a comma except for the last one, and lines never end with
+/// trailing whitespace. An empty array is written as `{}`.
+pub fn c_array(name: &str, bytes: &[u8], width: usize) -> String {
+    let mut out = format!("const unsigned char {name}[{}] = {{", bytes.len());
+    if bytes.is_empty() {
+        out.push_str("};\n");
+        return out;
+    }
+
+    out.push('\n');
+    let lines = bytes.chunks(width.max(1)).collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+        let last_line = i + 1 == lines.len();
+
+        out.push_str("   ");
+        for (j, byte) in line.iter().enumerate() {
+            let last = last_line && j + 1 == line.len();
+            write!(out, " 0x{byte:02X}{}", if last { "" } else { "," }).unwrap();
+        }
+
+        out.push('\n');
+    }
+
+    out.push_str("};\n");
+    out
+}
+
+/// Formats `bytes` as an uppercase hex string, without separators.
+pub fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        write!(out, "{byte:02X}").unwrap();
+        out
+    })
+}
+
+/// Places `text` in the system clipboard, through egui so that it works regardless of the
+/// windowing backend.
+pub fn copy_to_clipboard(ctx: &egui::Context, text: String) {
+    ctx.copy_text(text);
+}
+
+/// Exports `bytes` in the given way. `name` is used as the name of the C array and as the default
+/// file name. Returns a status message describing the result, if any.
+pub fn export(ctx: &egui::Context, export: Export, name: &str, bytes: &[u8]) -> Option<String> {
+    match export {
+        Export::Binary => {
+            let path = rfd::FileDialog::new()
+                .set_file_name(format!("{name}.bin"))
+                .save_file()?;
+
+            Some(save_binary(&path, bytes))
+        }
+        Export::CArray => {
+            copy_to_clipboard(ctx, c_array(name, bytes, C_ARRAY_WIDTH));
+            Some(format!("Copied {} bytes as a C array", bytes.len()))
+        }
+        Export::Hex => {
+            copy_to_clipboard(ctx, hex_string(bytes));
+            Some(format!("Copied {} bytes as hex", bytes.len()))
+        }
+    }
+}
+
+fn save_binary(path: &Path, bytes: &[u8]) -> String {
+    match std::fs::write(path, bytes) {
+        Ok(()) => format!("Saved {}", path.display()),
+        Err(e) => format!("Failed to write {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn c_array_wraps_lines() {
+        let bytes = (0..10).collect::<Vec<u8>>();
+        let expected = "\
+const unsigned char data[10] = {
+    0x00, 0x01, 0x02, 0x03,
+    0x04, 0x05, 0x06, 0x07,
+    0x08, 0x09
+};
+";
+        assert_eq!(c_array("data", &bytes, 4), expected);
+    }
+
+    #[test]
+    fn c_array_last_full_line_has_no_trailing_comma() {
+        let expected = "\
+const unsigned char buf[4] = {
+    0xDE, 0xAD,
+    0xBE, 0xEF
+};
+";
+        assert_eq!(c_array("buf", &[0xDE, 0xAD, 0xBE, 0xEF], 2), expected);
+    }
+
+    #[test]
+    fn c_array_edge_cases() {
+        assert_eq!(
+            c_array("empty", &[], 4),
+            "const unsigned char empty[0] = {};\n"
+        );
+        assert_eq!(
+            c_array("one", &[0x7F], 4),
+            "const unsigned char one[1] = {\n    0x7F\n};\n"
+        );
+
+        // a width of zero is treated as one element per line
+        assert_eq!(
+            c_array("narrow", &[1, 2], 0),
+            "const unsigned char narrow[2] = {\n    0x01,\n    0x02\n};\n"
+        );
+
+        let line = c_array("wide", &[0xAB; 24], C_ARRAY_WIDTH);
+        assert_eq!(line.lines().count(), 4);
+        assert!(line.lines().all(|l| !l.ends_with(' ')));
+    }
+
+    #[test]
+    fn hex_strings() {
+        assert_eq!(hex_string(&[]), "");
+        assert_eq!(hex_string(&[0x00, 0x1F, 0xA0, 0xFF]), "001FA0FF");
+    }
+}
@@ -0,0 +1,100 @@
+//! The guest console: what the guest writes through debug devices (e.g. a USB Gecko), with an input
+//! line whose text is sent back to the guest.
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::{AppWindow, Ctx};
+
+/// How much output is kept, after which the oldest lines are dropped.
+const MAX_TEXT_LEN: usize = 256 * 1024;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    #[serde(skip)]
+    text: String,
+    #[serde(skip)]
+    input: String,
+    /// Input typed by the user, sent to the guest on the next prepare.
+    #[serde(skip)]
+    pending_input: Vec<u8>,
+}
+
+impl Window {
+    fn append(&mut self, output: &[u8]) {
+        let output = String::from_utf8_lossy(output);
+        self.text
+            .push_str(&output.replace("\r\n", "\n").replace('\r', "\n"));
+
+        if self.text.len() > MAX_TEXT_LEN {
+            let excess = self.text.len() - MAX_TEXT_LEN;
+            // a newline byte is always a char boundary
+            let cut = self.text.as_bytes()[excess..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(self.text.len(), |i| excess + i + 1);
+
+            self.text.drain(..cut);
+        }
+    }
+}
+
+#[typetag::serde(name = "guest_console")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Guest Console"
+    }
+
+    fn default_size(&self) -> Option<egui::Vec2> {
+        Some(egui::Vec2::new(600.0, 400.0))
+    }
+
+    fn prepare(&mut self, state: &mut State) {
+        let console = &state.lazuli.sys.guest_console;
+
+        let output = console.take_output();
+        if !output.is_empty() {
+            self.append(&output);
+        }
+
+        if !self.pending_input.is_empty() {
+            console.send_input(&std::mem::take(&mut self.pending_input));
+        }
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        let mut send = false;
+        egui::TopBottomPanel::bottom("guest_console_input").show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.input)
+                        .hint_text("Input to the guest")
+                        .desired_width(ui.available_width() - 100.0),
+                );
+
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    send = true;
+                    response.request_focus();
+                }
+
+                send |= ui.button("Send").clicked();
+                if ui.button("Clear").clicked() {
+                    self.text.clear();
+                }
+            });
+        });
+
+        if send {
+            self.pending_input.extend(self.input.as_bytes());
+            self.pending_input.push(b'\n');
+            self.input.clear();
+        }
+
+        egui::ScrollArea::both()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.add(egui::Label::new(egui::RichText::new(&self.text).monospace()).extend());
+            });
+    }
+}
@@ -0,0 +1,235 @@
+//! A zoomable view of a framebuffer, shared by the XFB and EFB windows.
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eframe::egui::{self, Color32, Pos2, Rect, Stroke, Vec2};
+use lazuli::system::gx::color::Rgba8;
+
+use crate::windows::export::{self, Export};
+
+/// Minimum size of a framebuffer pixel on screen for the grid to be drawn.
+const GRID_MIN_PIXEL_SIZE: f32 = 8.0;
+const MAX_ZOOM: f32 = 64.0;
+
+/// An image in native framebuffer space.
+#[derive(Default)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgba8>,
+}
+
+impl Image {
+    pub fn get(&self, x: usize, y: usize) -> Rgba8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// The pixels of this image as tightly packed RGBA8 bytes.
+    pub fn rgba_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|p| [p.r, p.g, p.b, p.a])
+            .collect()
+    }
+
+    /// Writes this image to a PNG file.
+    pub fn save_png(&self, path: &std::path::Path) -> Result<(), png::EncodingError> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba_bytes())?;
+        writer.finish()
+    }
+}
+
+/// Zoom, pan, pixel picking, grid overlay and PNG export for an [`Image`].
+pub struct ImageView {
+    name: &'static str,
+    zoom: f32,
+    pan: Vec2,
+    grid: bool,
+    texture: Option<egui::TextureHandle>,
+    export_status: Option<String>,
+}
+
+impl ImageView {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            grid: true,
+            texture: None,
+            export_status: None,
+        }
+    }
+
+    /// Uploads a new image to be shown.
+    pub fn set(&mut self, ctx: &egui::Context, image: &Image) {
+        let color_image = egui::ColorImage {
+            size: [image.width, image.height],
+            source_size: Vec2::new(image.width as f32, image.height as f32),
+            pixels: image
+                .pixels
+                .iter()
+                .map(|p| Color32::from_rgba_unmultiplied(p.r, p.g, p.b, p.a))
+                .collect(),
+        };
+
+        match &mut self.texture {
+            Some(texture) => texture.set(color_image, egui::TextureOptions::NEAREST),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture(self.name, color_image, egui::TextureOptions::NEAREST));
+            }
+        }
+    }
+
+    /// Shows the toolbar and the view. `inspect` returns extra information about the pixel at
+    /// the given coordinates, shown by the pixel picker.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        image: &Image,
+        inspect: impl Fn(usize, usize) -> String,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{:.0}%", self.zoom * 100.0));
+            if ui.button("Reset view").clicked() {
+                self.zoom = 1.0;
+                self.pan = Vec2::ZERO;
+            }
+
+            ui.checkbox(&mut self.grid, "Grid");
+            ui.menu_button("Export", |ui| {
+                if ui.button("Save PNG").clicked() {
+                    self.export(image);
+                }
+
+                // the decoded pixels, as RGBA8
+                for export in Export::ALL {
+                    if ui.button(export.label()).clicked() {
+                        let name = format!("{}_{}x{}", self.name, image.width, image.height);
+                        let status = export::export(ui.ctx(), export, &name, &image.rgba_bytes());
+                        if status.is_some() {
+                            self.export_status = status;
+                        }
+                    }
+                }
+            });
+
+            if let Some(status) = &self.export_status {
+                ui.label(status);
+            }
+        });
+
+        let Some(texture) = &self.texture else {
+            ui.label("No image yet");
+            return;
+        };
+
+        if image.width == 0 || image.height == 0 {
+            ui.label("Empty image");
+            return;
+        }
+
+        let picker_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        let size = ui.available_size() - Vec2::new(0.0, picker_height);
+        let (rect, response) = ui.allocate_exact_size(size.max(Vec2::ZERO), egui::Sense::drag());
+
+        // zoom around the cursor
+        if let Some(hover) = response.hover_pos() {
+            let factor = ui.input(|i| i.zoom_delta() * (i.smooth_scroll_delta.y / 200.0).exp());
+            let zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+            if (zoom - self.zoom).abs() > f32::EPSILON {
+                let anchor = hover - rect.center() - self.pan;
+                self.pan -= anchor * (zoom / self.zoom - 1.0);
+                self.zoom = zoom;
+            }
+        }
+
+        self.pan += response.drag_delta();
+
+        // fit the image to the view, then apply zoom and pan
+        let image_size = Vec2::new(image.width as f32, image.height as f32);
+        let fit = (rect.width() / image_size.x).min(rect.height() / image_size.y);
+        let scale = fit * self.zoom;
+        let image_rect = Rect::from_center_size(rect.center() + self.pan, image_size * scale);
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::BLACK);
+        painter.image(
+            texture.id(),
+            image_rect,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+
+        if self.grid && scale >= GRID_MIN_PIXEL_SIZE {
+            let visible = rect.intersect(image_rect);
+            let stroke = Stroke::new(1.0, Color32::from_black_alpha(96));
+
+            let first_x = ((visible.left() - image_rect.left()) / scale).floor() as usize;
+            let last_x = ((visible.right() - image_rect.left()) / scale).ceil() as usize;
+            for x in first_x..=last_x.min(image.width) {
+                let screen_x = image_rect.left() + x as f32 * scale;
+                painter.vline(screen_x, visible.y_range(), stroke);
+            }
+
+            let first_y = ((visible.top() - image_rect.top()) / scale).floor() as usize;
+            let last_y = ((visible.bottom() - image_rect.top()) / scale).ceil() as usize;
+            for y in first_y..=last_y.min(image.height) {
+                let screen_y = image_rect.top() + y as f32 * scale;
+                painter.hline(visible.x_range(), screen_y, stroke);
+            }
+        }
+
+        // pixel picker
+        let picked = response
+            .hover_pos()
+            .filter(|pos| image_rect.contains(*pos))
+            .map(|pos| {
+                let pixel = (pos - image_rect.min) / scale;
+                let x = (pixel.x as usize).min(image.width - 1);
+                let y = (pixel.y as usize).min(image.height - 1);
+                (x, y)
+            });
+
+        let text = match picked {
+            Some((x, y)) => {
+                let p = image.get(x, y);
+                let mut text = format!(
+                    "({x}, {y}): R {} G {} B {} A {} (#{:02X}{:02X}{:02X}{:02X})",
+                    p.r, p.g, p.b, p.a, p.r, p.g, p.b, p.a,
+                );
+
+                let extra = inspect(x, y);
+                if !extra.is_empty() {
+                    text.push_str(", ");
+                    text.push_str(&extra);
+                }
+
+                text
+            }
+            None => format!("{}x{}", image.width, image.height),
+        };
+
+        ui.label(text);
+    }
+
+    fn export(&mut self, image: &Image) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let path = PathBuf::from(format!("lazuli-{}-{time}.png", self.name));
+        self.export_status = Some(match image.save_png(&path) {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => format!("Export failed: {e}"),
+        });
+    }
+}
@@ -0,0 +1,301 @@
+//! A manager for the saves in a memory card image. It works on the image file directly, so it can
+//! be used whether emulation is running or not.
+use std::path::PathBuf;
+
+use eframe::egui::{self, Color32, Vec2};
+use gxtex::Pixel;
+use lazuli::disks::memcard::{Card, DirEntry, ICON_SIZE, Icon};
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::{AppWindow, Ctx};
+
+/// A save in the card, as shown in the list.
+struct File {
+    index: usize,
+    entry: DirEntry,
+    comment: String,
+    icon: Option<egui::TextureHandle>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    card: Option<Card>,
+    #[serde(skip)]
+    files: Vec<File>,
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+fn decode_icon(icon: Icon) -> Vec<Pixel> {
+    match icon {
+        Icon::Rgb5a3(data) => gxtex::decode::<gxtex::Rgb5A3>(ICON_SIZE, ICON_SIZE, data),
+        Icon::Ci8 { indices, palette } => {
+            gxtex::decode::<gxtex::CI8>(ICON_SIZE, ICON_SIZE, indices)
+                .into_iter()
+                .map(|index| {
+                    let entry = &palette[2 * index as usize..][..2];
+                    Pixel::from_rgb5a3(u16::from_be_bytes([entry[0], entry[1]]))
+                })
+                .collect()
+        }
+    }
+}
+
+impl Window {
+    /// Reads the card image at `path`. Cards whose checksums don't match are not opened.
+    fn open(&mut self, ctx: &egui::Context) {
+        self.card = None;
+        self.files.clear();
+
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let card = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| Card::new(data).map_err(|e| e.to_string()));
+
+        match card {
+            Ok(card) => {
+                self.card = Some(card);
+                self.status = None;
+                self.refresh(ctx);
+            }
+            Err(e) => self.status = Some(format!("Failed to open {}: {e}", path.display())),
+        }
+    }
+
+    /// Rebuilds the list of files from the card.
+    fn refresh(&mut self, ctx: &egui::Context) {
+        let Some(card) = &self.card else {
+            return;
+        };
+
+        self.files = card
+            .entries()
+            .map(|(index, entry)| {
+                let data = card.read_file(index).unwrap_or_default();
+                let comment = entry
+                    .comments(&data)
+                    .map(|[game, save]| format!("{game} - {save}"))
+                    .unwrap_or_default();
+
+                let icon = entry.icon(&data).map(|icon| {
+                    let image = egui::ColorImage {
+                        size: [ICON_SIZE, ICON_SIZE],
+                        source_size: Vec2::splat(ICON_SIZE as f32),
+                        pixels: decode_icon(icon)
+                            .into_iter()
+                            .map(|p| Color32::from_rgba_unmultiplied(p.r, p.g, p.b, p.a))
+                            .collect(),
+                    };
+
+                    let name = format!("memcard_icon_{index}");
+                    ctx.load_texture(name, image, egui::TextureOptions::NEAREST)
+                });
+
+                File {
+                    index,
+                    entry,
+                    comment,
+                    icon,
+                }
+            })
+            .collect();
+    }
+
+    /// Writes the card back to its file and refreshes the list.
+    fn save(&mut self, ctx: &egui::Context) {
+        let (Some(path), Some(card)) = (&self.path, &self.card) else {
+            return;
+        };
+
+        if let Err(e) = std::fs::write(path, card.data()) {
+            self.status = Some(format!("Failed to write {}: {e}", path.display()));
+        }
+
+        self.refresh(ctx);
+    }
+
+    fn import(&mut self, ctx: &egui::Context) {
+        let Some(card) = &mut self.card else {
+            return;
+        };
+
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("GameCube saves", &["gci"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let result = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|gci| card.import(&gci).map_err(|e| e.to_string()));
+
+        match result {
+            Ok(_) => {
+                self.status = Some(format!("Imported {}", path.display()));
+                self.save(ctx);
+            }
+            Err(e) => self.status = Some(format!("Failed to import {}: {e}", path.display())),
+        }
+    }
+
+    fn export(&mut self, index: usize) {
+        let Some(card) = &self.card else {
+            return;
+        };
+
+        let entry = card.entry(index);
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("GameCube saves", &["gci"])
+            .set_file_name(format!("{}-{}.gci", entry.game_id(), entry.filename()))
+            .save_file()
+        else {
+            return;
+        };
+
+        let result = card
+            .export(index)
+            .map_err(|e| e.to_string())
+            .and_then(|gci| std::fs::write(&path, gci).map_err(|e| e.to_string()));
+
+        self.status = Some(match result {
+            Ok(()) => format!("Exported {}", path.display()),
+            Err(e) => format!("Failed to export {}: {e}", path.display()),
+        });
+    }
+
+    fn delete(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(card) = &mut self.card else {
+            return;
+        };
+
+        match card.delete(index) {
+            Ok(()) => {
+                self.status = None;
+                self.save(ctx);
+            }
+            Err(e) => self.status = Some(format!("Failed to delete: {e}")),
+        }
+    }
+}
+
+#[typetag::serde(name = "memcard")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Memory Card Manager"
+    }
+
+    fn default_size(&self) -> Option<Vec2> {
+        Some(Vec2::new(560.0, 400.0))
+    }
+
+    fn prepare(&mut self, _: &mut State) {}
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        let ctx = ui.ctx().clone();
+        if self.card.is_none() && self.status.is_none() && self.path.is_some() {
+            self.open(&ctx);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Open card...").clicked() {
+                let path = rfd::FileDialog::new()
+                    .add_filter("Memory card images", &["raw", "gcp"])
+                    .pick_file();
+
+                if path.is_some() {
+                    self.path = path;
+                    self.open(&ctx);
+                }
+            }
+
+            if ui
+                .add_enabled(self.card.is_some(), egui::Button::new("Reload"))
+                .clicked()
+            {
+                self.open(&ctx);
+            }
+
+            if ui
+                .add_enabled(self.card.is_some(), egui::Button::new("Import .gci..."))
+                .clicked()
+            {
+                self.import(&ctx);
+            }
+        });
+
+        if let Some(path) = &self.path {
+            ui.label(path.display().to_string());
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        let Some(card) = &self.card else {
+            return;
+        };
+
+        ui.label(format!(
+            "{} files, {} free blocks",
+            self.files.len(),
+            card.free_blocks()
+        ));
+        ui.separator();
+
+        let mut export = None;
+        let mut delete = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("memcard_files")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Name");
+                    ui.strong("Game");
+                    ui.strong("Blocks");
+                    ui.label("");
+                    ui.end_row();
+
+                    for file in &self.files {
+                        match &file.icon {
+                            Some(icon) => {
+                                ui.image((icon.id(), Vec2::splat(ICON_SIZE as f32)));
+                            }
+                            None => {
+                                ui.label("");
+                            }
+                        }
+
+                        ui.label(file.entry.filename()).on_hover_text(&file.comment);
+                        ui.label(file.entry.game_id());
+                        ui.label(file.entry.block_count().to_string());
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Export...").clicked() {
+                                export = Some(file.index);
+                            }
+
+                            if ui.button("Delete").clicked() {
+                                delete = Some(file.index);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if let Some(index) = export {
+            self.export(index);
+        }
+
+        if let Some(index) = delete {
+            self.delete(&ctx, index);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! A hex view of memory, whose selected ranges can be exported.
+use eframe::egui;
+use lazuli::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::windows::export::{self, Export};
+use crate::windows::{AppWindow, Ctx};
+
+const BYTES_PER_ROW: u32 = 16;
+const ROW_HEIGHT: f32 = 18.0;
+/// Largest range which can be exported at once.
+const MAX_EXPORT_LEN: u32 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub struct Window {
+    /// Address of the first row.
+    target: u32,
+    #[serde(skip)]
+    target_text: String,
+
+    #[serde(skip)]
+    rows: u32,
+    #[serde(skip)]
+    data: Vec<u8>,
+
+    /// First and last selected addresses, in the order they were picked.
+    #[serde(skip)]
+    selection: Option<(u32, u32)>,
+    /// Export requested by the user, which is performed once the data has been read.
+    #[serde(skip)]
+    requested_export: Option<Export>,
+    #[serde(skip)]
+    pending_export: Option<(Export, Vec<u8>)>,
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            target: 0x8000_0000,
+            target_text: String::new(),
+            rows: 0,
+            data: Vec::new(),
+            selection: None,
+            requested_export: None,
+            pending_export: None,
+            status: None,
+        }
+    }
+}
+
+impl Window {
+    /// The selected range as a start address and a length.
+    fn selected_range(&self) -> Option<(u32, u32)> {
+        let (a, b) = self.selection?;
+        let (start, end) = (a.min(b), a.max(b));
+        Some((start, end - start + 1))
+    }
+
+    fn is_selected(&self, addr: u32) -> bool {
+        self.selected_range()
+            .is_some_and(|(start, len)| addr.wrapping_sub(start) < len)
+    }
+
+    fn select(&mut self, addr: u32, extend: bool) {
+        self.selection = match self.selection {
+            Some((anchor, _)) if extend => Some((anchor, addr)),
+            _ => Some((addr, addr)),
+        };
+    }
+
+    fn context_menu(&mut self, ui: &mut egui::Ui) {
+        let Some((start, len)) = self.selected_range() else {
+            ui.label("Nothing selected");
+            return;
+        };
+
+        ui.label(format!("{len} bytes at {}", Address(start)));
+        for export in Export::ALL {
+            if ui
+                .add_enabled(len <= MAX_EXPORT_LEN, egui::Button::new(export.label()))
+                .clicked()
+            {
+                self.requested_export = Some(export);
+                ui.close();
+            }
+        }
+    }
+}
+
+#[typetag::serde(name = "memory")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Memory"
+    }
+
+    fn default_size(&self) -> Option<egui::Vec2> {
+        Some(egui::Vec2::new(620.0, 400.0))
+    }
+
+    fn prepare(&mut self, state: &mut State) {
+        let sys = &state.lazuli.sys;
+
+        self.data.resize((self.rows * BYTES_PER_ROW) as usize, 0);
+        sys.copy_to_host(Address(self.target), &mut self.data);
+
+        if let Some(export) = self.requested_export.take()
+            && let Some((start, len)) = self.selected_range()
+        {
+            let mut bytes = vec![0; len as usize];
+            sys.copy_to_host(Address(start), &mut bytes);
+            self.pending_export = Some((export, bytes));
+        }
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
+        if let Some((kind, bytes)) = self.pending_export.take() {
+            let start = self
+                .selected_range()
+                .map_or(self.target, |(start, _)| start);
+            let name = format!("mem_{start:08X}");
+            if let Some(status) = export::export(ui.ctx(), kind, &name, &bytes) {
+                self.status = Some(status);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Address: ");
+            if ui.text_edit_singleline(&mut self.target_text).lost_focus() {
+                let clean = self.target_text.trim_prefix("0x").replace("_", "");
+                if let Ok(addr) = u32::from_str_radix(&clean, 16) {
+                    self.target = addr - addr % BYTES_PER_ROW;
+                    self.target_text = format!("{addr:08X}");
+                }
+            }
+
+            if let Some((start, len)) = self.selected_range() {
+                ui.label(format!("Selected: {len} bytes at {}", Address(start)));
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        let mut clicked = None;
+        let response = ui.scope(|ui| {
+            ui.spacing_mut().item_spacing = egui::Vec2::new(4.0, 0.0);
+            self.rows = (ui.available_height() / ROW_HEIGHT).max(1.0) as u32;
+
+            let data = std::mem::take(&mut self.data);
+            for (row, bytes) in data.chunks(BYTES_PER_ROW as usize).enumerate() {
+                let row_addr = self.target.wrapping_add(row as u32 * BYTES_PER_ROW);
+                ui.horizontal(|ui| {
+                    ui.set_height(ROW_HEIGHT);
+                    ui.monospace(Address(row_addr).to_string());
+                    ui.add_space(8.0);
+
+                    for (i, byte) in bytes.iter().enumerate() {
+                        let addr = row_addr.wrapping_add(i as u32);
+                        let mut text = egui::RichText::new(format!("{byte:02X}")).monospace();
+                        if self.is_selected(addr) {
+                            text = text.background_color(ui.visuals().selection.bg_fill);
+                        }
+
+                        let response = ui.add(
+                            egui::Label::new(text)
+                                .selectable(false)
+                                .sense(egui::Sense::click()),
+                        );
+
+                        if response.clicked() || response.secondary_clicked() {
+                            clicked = Some((addr, response.clicked()));
+                        }
+
+                        response.context_menu(|ui| self.context_menu(ui));
+                    }
+
+                    ui.add_space(8.0);
+                    let ascii = bytes
+                        .iter()
+                        .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                        .collect::<String>();
+                    ui.monospace(ascii);
+                });
+            }
+
+            self.data = data;
+        });
+
+        // right clicking outside of the selection selects the clicked byte instead
+        if let Some((addr, primary)) = clicked
+            && (primary || !self.is_selected(addr))
+        {
+            let extend = primary && ui.input(|i| i.modifiers.shift);
+            self.select(addr, extend);
+        }
+
+        let rect = response.response.rect;
+        let response = ui.interact(rect, egui::Id::new("memory_scroll"), egui::Sense::hover());
+        if response.hovered() {
+            let delta = ui.input(|i| i.smooth_scroll_delta);
+            let rows = -((delta.y / 10.0) as i32);
+            self.target = self.target.wrapping_add_signed(rows * BYTES_PER_ROW as i32);
+        }
+    }
+}
@@ -15,6 +15,8 @@ pub struct Window {
     capture: bool,
     #[serde(skip)]
     is_capturing: bool,
+    #[serde(skip)]
+    msaa: Option<bool>,
 }
 
 impl Default for Window {
@@ -23,6 +25,7 @@ impl Default for Window {
             renderdoc: RenderDoc::new().ok(),
             capture: false,
             is_capturing: false,
+            msaa: None,
         }
     }
 }
@@ -37,8 +40,17 @@ impl AppWindow for Window {
 
     fn show(&mut self, ui: &mut egui::Ui, ctx: &mut Ctx) {
         let stats = ctx.renderer.stats();
+        let frame = ctx.renderer.frame_stats();
 
         ui.vertical(|ui| {
+            ui.heading("Last Frame");
+            ui.label(format!("Draw Calls: {}", frame.draw_calls));
+            ui.label(format!("Triangles: {}", frame.triangles));
+            match frame.gpu_time {
+                Some(time) => ui.label(format!("GPU Time: {:.2} ms", time.as_secs_f64() * 1000.0)),
+                None => ui.label("GPU Time: unavailable"),
+            };
+
             ui.heading("Allocator Report");
             if let Some(alloc) = &stats.alloc {
                 ui.label(format!(
@@ -79,6 +91,13 @@ impl AppWindow for Window {
                 counters.memory_allocations.read(),
             ));
 
+            ui.heading("Settings");
+            let msaa = self.msaa.get_or_insert(ctx.config.settings.renderer.msaa);
+
+            if ui.checkbox(msaa, "Anti-aliasing (4x MSAA)").changed() {
+                ctx.renderer.set_msaa(if *msaa { 4 } else { 1 });
+            }
+
             ui.heading("Renderdoc");
             if let Some(renderdoc) = &mut self.renderdoc {
                 ui.horizontal(|ui| {
@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::State;
+use crate::config::{AudioBackend, CardSlot, FrameSkipMode};
+use crate::windows::{AppWindow, Ctx};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Window {
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+/// Shows a combo box for an optional setting, where `None` means the setting is inherited from
+/// the defaults.
+fn optional<T: Clone + PartialEq>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<T>,
+    options: &[(T, &str)],
+) -> bool {
+    let selected = match value.as_ref() {
+        Some(value) => options
+            .iter()
+            .find(|(option, _)| option == value)
+            .map_or("?", |(_, name)| *name),
+        None => "Default",
+    };
+
+    let mut changed = false;
+    egui::ComboBox::from_label(label)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            changed |= ui.selectable_value(value, None, "Default").changed();
+            for (option, name) in options {
+                changed |= ui
+                    .selectable_value(value, Some(option.clone()), *name)
+                    .changed();
+            }
+        });
+
+    changed
+}
+
+fn flag(ui: &mut egui::Ui, label: &str, value: &mut Option<bool>) -> bool {
+    optional(ui, label, value, &[(true, "Enabled"), (false, "Disabled")])
+}
+
+fn path(ui: &mut egui::Ui, label: &str, value: &mut Option<Option<PathBuf>>) -> bool {
+    let mut text = value
+        .clone()
+        .flatten()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+
+    let changed = ui
+        .horizontal(|ui| {
+            ui.label(label);
+            ui.text_edit_singleline(&mut text).changed()
+        })
+        .inner;
+
+    if changed {
+        *value = (!text.is_empty()).then(|| Some(PathBuf::from(text)));
+    }
+
+    changed
+}
+
+#[typetag::serde(name = "settings")]
+impl AppWindow for Window {
+    fn title(&self) -> &str {
+        "Settings"
+    }
+
+    fn prepare(&mut self, _: &mut State) {}
+
+    fn show(&mut self, ui: &mut egui::Ui, ctx: &mut Ctx) {
+        let layer = &mut ctx.user_config.layer;
+        let mut changed = false;
+
+        ui.label("Changes are saved to the user config and take effect on restart.");
+
+        ui.heading("JIT");
+        let mut ipb = layer.ppcjit.instr_per_block.is_some();
+        if ui
+            .checkbox(&mut ipb, "Override instructions per block")
+            .changed()
+        {
+            layer.ppcjit.instr_per_block = ipb.then_some(128);
+            changed = true;
+        }
+
+        if let Some(ipb) = &mut layer.ppcjit.instr_per_block {
+            changed |= ui.add(egui::DragValue::new(ipb).range(1..=4096)).changed();
+        }
+
+        changed |= flag(ui, "Syscalls as no-ops", &mut layer.ppcjit.nop_syscalls);
+        changed |= flag(ui, "Force FPU", &mut layer.ppcjit.force_fpu);
+        changed |= flag(
+            ui,
+            "Ignore unimplemented instructions",
+            &mut layer.ppcjit.ignore_unimplemented_inst,
+        );
+        changed |= flag(ui, "Round to single", &mut layer.ppcjit.round_to_single);
+        changed |= flag(ui, "Float exceptions", &mut layer.ppcjit.float_exceptions);
+        changed |= flag(
+            ui,
+            "Bulk memory idioms",
+            &mut layer.ppcjit.bulk_memory_idioms,
+        );
+        changed |= flag(ui, "Persistent block cache", &mut layer.ppcjit.block_cache);
+        if ui.button("Clear block cache").clicked() {
+            self.status = Some(
+                match cores::cpu::jit::ppcjit::invalidate_cache(ctx.jit_cache) {
+                    Ok(()) => "Block cache will be cleared on restart".to_owned(),
+                    Err(e) => format!("Failed to clear block cache: {e}"),
+                },
+            );
+        }
+
+        ui.heading("DSP");
+        changed |= flag(ui, "Dedicated thread", &mut layer.dsp.threaded);
+
+        ui.heading("System");
+        changed |= optional(
+            ui,
+            "USB Gecko",
+            &mut layer.system.usb_gecko,
+            &[
+                (None, "Unplugged"),
+                (Some(CardSlot::A), "Slot A"),
+                (Some(CardSlot::B), "Slot B"),
+            ],
+        );
+        changed |= flag(
+            ui,
+            "Validate vertex indices",
+            &mut layer.system.validate_vertex_indices,
+        );
+
+        ui.heading("Renderer");
+        changed |= flag(ui, "Anti-aliasing (4x MSAA)", &mut layer.renderer.msaa);
+        changed |= optional(
+            ui,
+            "Frame skip",
+            &mut layer.renderer.frame_skip,
+            &[
+                (FrameSkipMode::Off, "Off"),
+                (FrameSkipMode::Auto, "Auto"),
+                (FrameSkipMode::Fixed, "Fixed"),
+            ],
+        );
+
+        if layer.renderer.frame_skip == Some(FrameSkipMode::Fixed) {
+            let fixed = layer.renderer.frame_skip_fixed.get_or_insert(1);
+            changed |= ui
+                .horizontal(|ui| {
+                    ui.label("Frames skipped per presented frame");
+                    ui.add(egui::DragValue::new(fixed).range(1..=9)).changed()
+                })
+                .inner;
+        }
+
+        changed |= flag(
+            ui,
+            "Skip draws of skipped frames",
+            &mut layer.renderer.frame_skip_draws,
+        );
+
+        ui.heading("Audio");
+        changed |= optional(
+            ui,
+            "Backend",
+            &mut layer.audio.backend,
+            &[(AudioBackend::Cpal, "Cpal"), (AudioBackend::None, "None")],
+        );
+
+        ui.heading("Input");
+        changed |= flag(ui, "Gamepad", &mut layer.input.gamepad);
+        changed |= flag(ui, "Auto-assign ports", &mut layer.input.auto_assign);
+
+        ui.heading("Paths");
+        changed |= path(ui, "IPL", &mut layer.paths.ipl);
+        changed |= path(ui, "DSP ROM", &mut layer.paths.dsp_rom);
+        changed |= path(ui, "DSP coefficients", &mut layer.paths.dsp_coef);
+        changed |= path(ui, "JIT cache", &mut layer.paths.jit_cache);
+
+        if changed {
+            self.status = Some(match ctx.user_config.save() {
+                Ok(()) => format!("Saved to {}", ctx.user_config.path.display()),
+                Err(e) => format!("Failed to save: {e}"),
+            });
+        }
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.collapsing("Effective configuration", |ui| {
+            ui.monospace(ctx.config.effective());
+        });
+    }
+}
@@ -14,6 +14,8 @@ pub struct Window {
     control: Control,
     #[serde(skip)]
     fifo: Fifo,
+    #[serde(skip)]
+    array_violations: ArrayViolations,
 }
 
 #[typetag::serde(name = "subsystem-cp")]
@@ -29,6 +31,7 @@ impl AppWindow for Window {
         self.status = cp.status;
         self.control = cp.control;
         self.fifo = cp.fifo.clone();
+        self.array_violations = cp.array_violations;
     }
 
     fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
@@ -45,6 +48,13 @@ impl AppWindow for Window {
             mmio_dbg(ui, "FIFO count", &self.fifo.count());
             mmio_dbg(ui, "FIFO write ptr", &self.fifo.write_ptr);
             mmio_dbg(ui, "FIFO read ptr", &self.fifo.read_ptr);
+            ui.separator();
+
+            let violations = &self.array_violations;
+            ui.label("Out of bounds vertex array reads");
+            ui.label(format!("Last draw: {}", violations.last_draw));
+            ui.label(format!("Draws with violations: {}", violations.draws));
+            ui.label(format!("Total: {}", violations.total));
         });
     }
 }
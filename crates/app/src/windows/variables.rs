@@ -1,10 +1,23 @@
+use std::collections::HashSet;
+
 use eframe::egui;
-use lazuli::Address;
+use lazuli::modules::debug::{ScalarKind, TypeId, TypeKind};
+use lazuli::system::System;
+use lazuli::{Address, Primitive};
 use serde::{Deserialize, Serialize};
 
 use crate::State;
 use crate::windows::{AppWindow, Ctx};
 
+/// What is shown in place of values which can't be read.
+const INVALID: &str = "<invalid>";
+
+/// How deep symbol trees go, so that following pointers can't go on forever.
+const MAX_DEPTH: usize = 16;
+
+/// How many elements of an array are shown.
+const MAX_ELEMENTS: u32 = 256;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 enum VarKind {
     #[default]
@@ -22,9 +35,301 @@ struct Variable {
     value: u32,
 }
 
+fn sign_extend(bits: u64, size: u32) -> i64 {
+    let shift = 64 - size * 8;
+    ((bits << shift) as i64) >> shift
+}
+
+fn mask(size: u32) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1 << (size * 8)) - 1
+    }
+}
+
+fn format_scalar(kind: ScalarKind, size: u32, bits: u64) -> String {
+    match kind {
+        ScalarKind::Unsigned => bits.to_string(),
+        ScalarKind::Signed => sign_extend(bits, size).to_string(),
+        ScalarKind::Float if size == 4 => f32::from_bits(bits as u32).to_string(),
+        ScalarKind::Float if size == 8 => f64::from_bits(bits).to_string(),
+        ScalarKind::Float => format!("0x{bits:X}"),
+        ScalarKind::Bool => (bits != 0).to_string(),
+    }
+}
+
+/// Parses a scalar of `size` bytes, returning its bits. Integers can also be written in hex.
+fn parse_scalar(kind: ScalarKind, size: u32, text: &str) -> Option<u64> {
+    let text = text.trim();
+    let integer = matches!(kind, ScalarKind::Unsigned | ScalarKind::Signed);
+    if integer && let Some(hex) = text.strip_prefix("0x") {
+        let bits = u64::from_str_radix(&hex.replace("_", ""), 16).ok()?;
+        return (bits & !mask(size) == 0).then_some(bits);
+    }
+
+    let bits = match kind {
+        ScalarKind::Unsigned => text.parse::<u64>().ok()?,
+        ScalarKind::Signed => {
+            let value = text.parse::<i64>().ok()?;
+            let bits = value as u64 & mask(size);
+            return (sign_extend(bits, size) == value).then_some(bits);
+        }
+        ScalarKind::Float if size == 4 => text.parse::<f32>().ok()?.to_bits() as u64,
+        ScalarKind::Float if size == 8 => text.parse::<f64>().ok()?.to_bits(),
+        ScalarKind::Float => return None,
+        ScalarKind::Bool => match text {
+            "true" | "1" => 1,
+            "false" | "0" => 0,
+            _ => return None,
+        },
+    };
+
+    (bits & !mask(size) == 0).then_some(bits)
+}
+
+/// Reads a big-endian value of `size` bytes, if all of them are backed by memory.
+fn read_bits(sys: &System, address: Address, size: u32) -> Option<u64> {
+    if size == 0 || size > 8 {
+        return None;
+    }
+
+    let mut buf = [0; 8];
+    let buf = &mut buf[..size as usize];
+    (sys.copy_to_host(address, buf) == buf.len())
+        .then(|| buf.iter().fold(0, |acc, &byte| (acc << 8) | byte as u64))
+}
+
+/// Writes a value, but only to memory: writing to e.g. MMIO could have side effects.
+fn write_primitive<P: Primitive>(sys: &mut System, address: Address, value: P) -> bool {
+    sys.read_pure::<P>(address).is_some() && sys.write(address, value)
+}
+
+/// An editable value in a symbol tree.
+#[derive(Clone)]
+struct Leaf {
+    address: Address,
+    size: u32,
+    kind: ScalarKind,
+    /// Named values, for enums.
+    enumerators: Vec<(String, i64)>,
+    /// The current value, as it should be edited.
+    text: String,
+}
+
+impl Leaf {
+    fn parse(&self, text: &str) -> Option<u64> {
+        let text = text.trim();
+        match self.enumerators.iter().find(|(name, _)| name == text) {
+            Some(&(_, value)) => Some(value as u64 & mask(self.size)),
+            None => parse_scalar(self.kind, self.size, text),
+        }
+    }
+
+    fn write(&self, sys: &mut System, bits: u64) -> bool {
+        match self.size {
+            1 => write_primitive(sys, self.address, bits as u8),
+            2 => write_primitive(sys, self.address, bits as u16),
+            4 => write_primitive(sys, self.address, bits as u32),
+            8 => write_primitive(sys, self.address, bits),
+            _ => false,
+        }
+    }
+}
+
+/// A node of a symbol tree, as shown.
+struct Row {
+    /// Index of the symbol the node belongs to.
+    symbol: usize,
+    /// Identifies the node within the trees, e.g. `player.inventory[2].*`.
+    path: String,
+    depth: usize,
+    label: String,
+    address: Address,
+    type_name: String,
+    value: String,
+    expandable: bool,
+    leaf: Option<Leaf>,
+}
+
+/// Builds the rows of the symbol trees, only descending into expanded nodes.
+struct Tree<'a> {
+    sys: &'a System,
+    expanded: &'a HashSet<String>,
+    rows: Vec<Row>,
+    /// Nodes on the way to the current one, to tell when dereferencing a pointer loops back.
+    ancestors: Vec<(Address, TypeId)>,
+}
+
+impl Tree<'_> {
+    fn node(&mut self, symbol: usize, path: String, label: String, address: Address, id: TypeId) {
+        let sys = self.sys;
+        let depth = self.ancestors.len();
+        let mut row = Row {
+            symbol,
+            path,
+            depth,
+            label,
+            address,
+            type_name: String::new(),
+            value: String::new(),
+            expandable: false,
+            leaf: None,
+        };
+
+        let Some(ty) = sys.modules.debug.find_type(id) else {
+            row.value = "<unknown type>".to_owned();
+            self.rows.push(row);
+            return;
+        };
+
+        row.type_name.clone_from(&ty.name);
+        let readable = sys.read_pure::<u8>(address).is_some();
+        let bits = read_bits(sys, address, ty.size);
+        match (&ty.kind, bits) {
+            (TypeKind::Scalar(kind), Some(bits)) => {
+                row.value = format_scalar(*kind, ty.size, bits);
+                row.leaf = Some(Leaf {
+                    address,
+                    size: ty.size,
+                    kind: *kind,
+                    enumerators: Vec::new(),
+                    text: row.value.clone(),
+                });
+            }
+            (TypeKind::Enum(enumerators), Some(bits)) => {
+                let signed = enumerators.iter().any(|&(_, value)| value < 0);
+                let kind = if signed {
+                    ScalarKind::Signed
+                } else {
+                    ScalarKind::Unsigned
+                };
+
+                let value = format_scalar(kind, ty.size, bits);
+                let name = enumerators
+                    .iter()
+                    .find(|&&(_, v)| v as u64 & mask(ty.size) == bits)
+                    .map(|(name, _)| name.clone());
+
+                row.value = name
+                    .as_ref()
+                    .map_or_else(|| value.clone(), |name| format!("{name} ({value})"));
+                row.leaf = Some(Leaf {
+                    address,
+                    size: ty.size,
+                    kind,
+                    enumerators: enumerators.clone(),
+                    text: name.unwrap_or(value),
+                });
+            }
+            (TypeKind::Pointer(pointee), Some(bits)) => {
+                row.value = format!("0x{bits:08X}");
+                row.expandable = bits != 0 && pointee.is_some();
+                row.leaf = Some(Leaf {
+                    address,
+                    size: ty.size,
+                    kind: ScalarKind::Unsigned,
+                    enumerators: Vec::new(),
+                    text: row.value.clone(),
+                });
+            }
+            (TypeKind::Array { count, .. }, _) if readable => {
+                row.value = format!("[{count}]");
+                row.expandable = *count > 0;
+            }
+            (TypeKind::Struct(members), _) if readable => {
+                row.value = "{…}".to_owned();
+                row.expandable = !members.is_empty();
+            }
+            (TypeKind::Opaque, _) => (),
+            _ => row.value = INVALID.to_owned(),
+        }
+
+        row.expandable &= depth < MAX_DEPTH;
+        let expand = row.expandable && self.expanded.contains(&row.path);
+        let path = row.path.clone();
+        self.rows.push(row);
+        if !expand {
+            return;
+        }
+
+        self.ancestors.push((address, id));
+        match &ty.kind {
+            TypeKind::Struct(members) => {
+                for member in members {
+                    self.node(
+                        symbol,
+                        format!("{path}.{}", member.name),
+                        member.name.clone(),
+                        address + member.offset,
+                        member.ty,
+                    );
+                }
+            }
+            TypeKind::Array { element, count } => {
+                let size = sys
+                    .modules
+                    .debug
+                    .find_type(*element)
+                    .map_or(0, |ty| ty.size);
+                for i in 0..(*count).min(MAX_ELEMENTS) {
+                    self.node(
+                        symbol,
+                        format!("{path}[{i}]"),
+                        format!("[{i}]"),
+                        address + i.wrapping_mul(size),
+                        *element,
+                    );
+                }
+
+                if *count > MAX_ELEMENTS {
+                    self.rows.push(Row {
+                        symbol,
+                        path: format!("{path}[…]"),
+                        depth: depth + 1,
+                        label: "…".to_owned(),
+                        address: address + MAX_ELEMENTS.wrapping_mul(size),
+                        type_name: String::new(),
+                        value: format!("{} more", count - MAX_ELEMENTS),
+                        expandable: false,
+                        leaf: None,
+                    });
+                }
+            }
+            &TypeKind::Pointer(Some(pointee)) => {
+                let target = Address(bits.unwrap_or(0) as u32);
+                let path = format!("{path}.*");
+                if self.ancestors.contains(&(target, pointee)) {
+                    self.rows.push(Row {
+                        symbol,
+                        path,
+                        depth: depth + 1,
+                        label: "*".to_owned(),
+                        address: target,
+                        type_name: String::new(),
+                        value: "<cycle>".to_owned(),
+                        expandable: false,
+                        leaf: None,
+                    });
+                } else {
+                    self.node(symbol, path, "*".to_owned(), target, pointee);
+                }
+            }
+            _ => (),
+        }
+        self.ancestors.pop();
+    }
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Window {
     variables: Vec<Variable>,
+    /// Names of the watched variables described by debug info.
+    #[serde(default)]
+    symbols: Vec<String>,
+    /// Paths of the expanded nodes of the symbol trees.
+    #[serde(default)]
+    expanded: HashSet<String>,
 
     #[serde(skip)]
     variable_address: String,
@@ -32,6 +337,18 @@ pub struct Window {
     variable_label: String,
     #[serde(skip)]
     variable_kind: VarKind,
+    #[serde(skip)]
+    symbol_name: String,
+
+    #[serde(skip)]
+    rows: Vec<Row>,
+    /// Path of the node being edited, along with the text being edited.
+    #[serde(skip)]
+    editing: Option<(String, String)>,
+    #[serde(skip)]
+    pending_write: Option<(Leaf, String)>,
+    #[serde(skip)]
+    status: Option<String>,
 }
 
 #[typetag::serde(name = "variables")]
@@ -41,16 +358,47 @@ impl AppWindow for Window {
     }
 
     fn prepare(&mut self, state: &mut State) {
-        let emulator = &state.lazuli;
+        let sys = &mut state.lazuli.sys;
+        if let Some((leaf, text)) = self.pending_write.take() {
+            self.status = match leaf.parse(&text) {
+                Some(bits) if leaf.write(sys, bits) => None,
+                Some(_) => Some(format!("Failed to write to {}", leaf.address)),
+                None => Some(format!("Invalid value: {text}")),
+            };
+        }
+
+        let sys = &*sys;
         for variable in self.variables.iter_mut() {
-            let physical = emulator
-                .sys
-                .mem
-                .translate_data_addr(variable.address)
-                .unwrap_or(0);
+            variable.value = sys.read_pure(Address(variable.address)).unwrap_or(0);
+        }
 
-            variable.value = emulator.sys.read_phys_pure(Address(physical)).unwrap_or(0);
+        let mut tree = Tree {
+            sys,
+            expanded: &self.expanded,
+            rows: Vec::new(),
+            ancestors: Vec::new(),
+        };
+
+        for (i, name) in self.symbols.iter().enumerate() {
+            match sys.modules.debug.find_variable(name) {
+                Some(variable) => {
+                    tree.node(i, name.clone(), name.clone(), variable.address, variable.ty)
+                }
+                None => tree.rows.push(Row {
+                    symbol: i,
+                    path: name.clone(),
+                    depth: 0,
+                    label: name.clone(),
+                    address: Address(0),
+                    type_name: String::new(),
+                    value: "<not found>".to_owned(),
+                    expandable: false,
+                    leaf: None,
+                }),
+            }
         }
+
+        self.rows = tree.rows;
     }
 
     fn show(&mut self, ui: &mut egui::Ui, _: &mut Ctx) {
@@ -113,6 +461,144 @@ impl AppWindow for Window {
             if let Some(i) = remove {
                 self.variables.remove(i);
             }
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Symbol: ");
+                let response = ui.text_edit_singleline(&mut self.symbol_name);
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                let name = self.symbol_name.trim();
+                if (ui.button("Watch").clicked() || submitted)
+                    && !name.is_empty()
+                    && !self.symbols.iter().any(|s| s == name)
+                {
+                    self.symbols.push(name.to_owned());
+                    self.symbol_name.clear();
+                }
+            });
+
+            let mut remove = None;
+            for row in &self.rows {
+                ui.horizontal(|ui| {
+                    if row.depth == 0 {
+                        if ui.button("🗑").clicked() {
+                            remove = Some(row.symbol);
+                        }
+                    } else {
+                        ui.add_space(row.depth as f32 * 16.0);
+                    }
+
+                    if row.expandable {
+                        let expanded = self.expanded.contains(&row.path);
+                        let icon = if expanded { "⏷" } else { "⏵" };
+                        if ui.small_button(icon).clicked() && !self.expanded.remove(&row.path) {
+                            self.expanded.insert(row.path.clone());
+                        }
+                    }
+
+                    ui.label(format!("{}:", row.label))
+                        .on_hover_text(format!("{} at {}", row.type_name, row.address));
+
+                    let editing = self.editing.as_mut().filter(|(path, _)| *path == row.path);
+
+                    match (editing, &row.leaf) {
+                        (Some((_, text)), Some(leaf)) => {
+                            let response = ui.text_edit_singleline(text);
+                            if response.lost_focus() {
+                                if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                    self.pending_write = Some((leaf.clone(), text.clone()));
+                                }
+
+                                self.editing = None;
+                            } else {
+                                response.request_focus();
+                            }
+                        }
+                        (_, Some(leaf)) => {
+                            let label =
+                                egui::Label::new(row.value.as_str()).sense(egui::Sense::click());
+                            if ui
+                                .add(label)
+                                .on_hover_text("Double click to edit")
+                                .double_clicked()
+                            {
+                                self.editing = Some((row.path.clone(), leaf.text.clone()));
+                            }
+                        }
+                        (_, None) => {
+                            ui.label(row.value.as_str());
+                        }
+                    }
+                });
+            }
+
+            if let Some(i) = remove {
+                let name = self.symbols.remove(i);
+                self.expanded.retain(|path| {
+                    let rest = path.strip_prefix(name.as_str());
+                    !rest.is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+                });
+            }
+
+            if let Some(status) = &self.status {
+                ui.separator();
+                ui.colored_label(egui::Color32::LIGHT_RED, status);
+            }
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scalars_round_trip() {
+        let cases = [
+            (ScalarKind::Unsigned, 2, "4660", 0x1234),
+            (ScalarKind::Signed, 1, "-2", 0xFE),
+            (ScalarKind::Signed, 4, "-1", 0xFFFF_FFFF),
+            (ScalarKind::Float, 4, "2.5", 0x4020_0000),
+            (ScalarKind::Float, 8, "-1", 0xBFF0_0000_0000_0000),
+            (ScalarKind::Bool, 1, "true", 1),
+        ];
+
+        for (kind, size, text, bits) in cases {
+            assert_eq!(format_scalar(kind, size, bits), text);
+            assert_eq!(parse_scalar(kind, size, text), Some(bits), "{text}");
+        }
+    }
+
+    #[test]
+    fn parse_rejects_out_of_range() {
+        assert_eq!(parse_scalar(ScalarKind::Unsigned, 1, "256"), None);
+        assert_eq!(parse_scalar(ScalarKind::Unsigned, 1, "0x100"), None);
+        assert_eq!(parse_scalar(ScalarKind::Signed, 1, "-129"), None);
+        assert_eq!(parse_scalar(ScalarKind::Signed, 1, "128"), None);
+        assert_eq!(parse_scalar(ScalarKind::Signed, 2, "0xFFFF"), Some(0xFFFF));
+        assert_eq!(
+            parse_scalar(ScalarKind::Unsigned, 4, "0x8000_0000"),
+            Some(0x8000_0000)
+        );
+        assert_eq!(parse_scalar(ScalarKind::Bool, 1, "maybe"), None);
+    }
+
+    #[test]
+    fn enumerators_parse_by_name() {
+        let leaf = Leaf {
+            address: Address(0),
+            size: 1,
+            kind: ScalarKind::Signed,
+            enumerators: vec![("NONE".to_owned(), -1), ("SOME".to_owned(), 1)],
+            text: String::new(),
+        };
+
+        assert_eq!(leaf.parse("NONE"), Some(0xFF));
+        assert_eq!(leaf.parse(" SOME "), Some(1));
+        assert_eq!(leaf.parse("3"), Some(3));
+        assert_eq!(leaf.parse("OTHER"), None);
+    }
+}
@@ -15,6 +15,16 @@ pub fn convert_range<const OLD_MAX: u32, const NEW_MAX: u32>(value: u8) -> u8 {
     ((value * NEW_MAX + OLD_MAX / 2) / OLD_MAX) as u8
 }
 
+/// Alpha from which RGB5A3 uses its opaque encoding. The 3-bit alpha encoding only goes up to
+/// 224 (7 * 32), so this is the midpoint between it and 255.
+pub const RGB5A3_OPAQUE_ALPHA: u8 = 240;
+
+/// Quantizes an alpha below [`RGB5A3_OPAQUE_ALPHA`] to the 3 bits of RGB5A3, rounding to nearest.
+#[inline(always)]
+fn rgb5a3_alpha(a: u8) -> u8 {
+    ((a as u16 + 16) / 32) as u8
+}
+
 #[inline(always)]
 fn fast_range_conv_31_to_255(value: u8) -> u8 {
     // 255 / 31 is approx 8.25, so multiply value by 8 and divide by 4 then add them
@@ -39,6 +49,22 @@ fn fast_range_conv_255_to_63(value: u8) -> u8 {
     value >> 2
 }
 
+/// 4x4 Bayer matrix, used for ordered dithering.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Converts a value in range `0..=255` to a value in the range `0..=NEW_MAX`, using the given
+/// threshold (in range `0..16`) instead of rounding to nearest.
+#[inline(always)]
+fn dither_range<const NEW_MAX: u32>(value: u8, threshold: u8) -> u8 {
+    const {
+        assert!(NEW_MAX <= 255);
+    };
+
+    // value * NEW_MAX / 255 + (threshold + 0.5) / 16, floored
+    let value = value as u32 * NEW_MAX * 32 + 255 * (2 * threshold as u32 + 1);
+    (value / (255 * 32)).min(NEW_MAX) as u8
+}
+
 /// A single RGBA8 pixel.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Immutable, IntoBytes, FromBytes, Default)]
 #[repr(C)]
@@ -101,6 +127,19 @@ impl Rgba8 {
             .with_bits(11, 16, r as u16)
     }
 
+    /// Like [`Self::to_rgb565`], but applies ordered dithering according to the position of the
+    /// pixel in the image.
+    #[inline(always)]
+    pub fn to_rgb565_dithered(self, x: usize, y: usize) -> u16 {
+        let threshold = BAYER_4X4[y % 4][x % 4];
+        let r = dither_range::<31>(self.r, threshold);
+        let g = dither_range::<63>(self.g, threshold);
+        let b = dither_range::<31>(self.b, threshold);
+        0u16.with_bits(0, 5, b as u16)
+            .with_bits(5, 11, g as u16)
+            .with_bits(11, 16, r as u16)
+    }
+
     #[inline(always)]
     pub fn from_rgb5a3(value: u16) -> Self {
         if value.bit(15) {
@@ -120,9 +159,12 @@ impl Rgba8 {
         }
     }
 
+    /// Encodes to RGB5A3, picking whichever encoding represents the alpha best: the opaque one
+    /// (15-bit color) from [`RGB5A3_OPAQUE_ALPHA`] on, and the 3-bit alpha one (12-bit color)
+    /// below it. Decoding with [`Self::from_rgb5a3`] and encoding again gives back the same value.
     #[inline(always)]
     pub fn to_rgb5a3(self) -> u16 {
-        if self.a == 255 {
+        if self.a >= RGB5A3_OPAQUE_ALPHA {
             let r = convert_range::<255, 31>(self.r);
             let g = convert_range::<255, 31>(self.g);
             let b = convert_range::<255, 31>(self.b);
@@ -134,7 +176,34 @@ impl Rgba8 {
             let r = convert_range::<255, 15>(self.r);
             let g = convert_range::<255, 15>(self.g);
             let b = convert_range::<255, 15>(self.b);
-            let a = self.a / 32;
+            let a = rgb5a3_alpha(self.a);
+
+            0u16.with_bits(0, 4, b as u16)
+                .with_bits(4, 8, g as u16)
+                .with_bits(8, 12, r as u16)
+                .with_bits(12, 15, a as u16)
+                .with_bit(15, false)
+        }
+    }
+
+    /// Like [`Self::to_rgb5a3`], but applies ordered dithering to the color channels according
+    /// to the position of the pixel in the image. Alpha is not dithered.
+    #[inline(always)]
+    pub fn to_rgb5a3_dithered(self, x: usize, y: usize) -> u16 {
+        let threshold = BAYER_4X4[y % 4][x % 4];
+        if self.a >= RGB5A3_OPAQUE_ALPHA {
+            let r = dither_range::<31>(self.r, threshold);
+            let g = dither_range::<31>(self.g, threshold);
+            let b = dither_range::<31>(self.b, threshold);
+            0u16.with_bits(0, 5, b as u16)
+                .with_bits(5, 10, g as u16)
+                .with_bits(10, 15, r as u16)
+                .with_bit(15, true)
+        } else {
+            let r = dither_range::<15>(self.r, threshold);
+            let g = dither_range::<15>(self.g, threshold);
+            let b = dither_range::<15>(self.b, threshold);
+            let a = rgb5a3_alpha(self.a);
 
             0u16.with_bits(0, 4, b as u16)
                 .with_bits(4, 8, g as u16)
@@ -155,6 +224,49 @@ impl Rgba8 {
         }
     }
 
+    /// Composites `self` over `dst` using straight (non-premultiplied) alpha.
+    #[inline(always)]
+    pub fn blend_over(self, dst: Self) -> Self {
+        // computes value / 255, rounded to nearest. exact for value <= 255 * 255
+        #[inline(always)]
+        fn div_255(value: u32) -> u32 {
+            let value = value + 128;
+            (value + (value >> 8)) >> 8
+        }
+
+        match self.a {
+            0 => return dst,
+            255 => return self,
+            _ => (),
+        }
+
+        let src_a = self.a as u32;
+        let dst_a = div_255(dst.a as u32 * (255 - src_a));
+        let out_a = src_a + dst_a;
+
+        let channel = |src: u8, dst: u8| {
+            ((src as u32 * src_a + dst as u32 * dst_a + out_a / 2) / out_a) as u8
+        };
+
+        Self {
+            r: channel(self.r, dst.r),
+            g: channel(self.g, dst.g),
+            b: channel(self.b, dst.b),
+            a: out_a as u8,
+        }
+    }
+
+    /// Maps the RGB channels through the given gamma table, leaving alpha untouched.
+    #[inline(always)]
+    pub fn with_gamma(self, table: &GammaTable) -> Self {
+        Self {
+            r: table.apply(self.r),
+            g: table.apply(self.g),
+            b: table.apply(self.b),
+            a: self.a,
+        }
+    }
+
     #[inline(always)]
     pub fn y(self) -> u8 {
         let (r, g, b) = (self.r as f32, self.g as f32, self.b as f32);
@@ -168,7 +280,50 @@ impl Rgba8 {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default, FromBytes, Immutable)]
+/// Lookup table encoding 8 bit channel values with a gamma, i.e. mapping `x` to `x^(1 / gamma)`
+/// in the `0.0..=1.0` range, rounded to nearest.
+#[derive(Debug, Clone)]
+pub struct GammaTable([u8; 256]);
+
+impl GammaTable {
+    pub fn new(gamma: f32) -> Self {
+        let exponent = gamma.recip();
+        Self(std::array::from_fn(|i| {
+            ((i as f32 / 255.0).powf(exponent) * 255.0).round() as u8
+        }))
+    }
+
+    /// Whether the table maps every value to itself, as it does for a gamma of 1.0.
+    pub fn is_identity(&self) -> bool {
+        self.0.iter().enumerate().all(|(i, &v)| i == v as usize)
+    }
+
+    #[inline(always)]
+    pub fn apply(&self, value: u8) -> u8 {
+        self.0[value as usize]
+    }
+}
+
+/// Converts an image with the given width to RGB565, using ordered dithering to reduce banding.
+/// Use [`Rgba8::to_rgb565`] instead when an exact round-trip is needed.
+pub fn dither_to_rgb565(pixels: &[Rgba8], width: usize, out: &mut [u16]) {
+    assert_eq!(pixels.len(), out.len());
+    for (i, (pixel, out)) in pixels.iter().zip(out).enumerate() {
+        *out = pixel.to_rgb565_dithered(i % width, i / width);
+    }
+}
+
+/// Converts an image with the given width to RGB5A3, using ordered dithering to reduce banding.
+/// Use [`Rgba8::to_rgb5a3`] instead when an exact round-trip is needed.
+pub fn dither_to_rgb5a3(pixels: &[Rgba8], width: usize, out: &mut [u16]) {
+    assert_eq!(pixels.len(), out.len());
+    for (i, (pixel, out)) in pixels.iter().zip(out).enumerate() {
+        *out = pixel.to_rgb5a3_dithered(i % width, i / width);
+    }
+}
+
+/// A single ABGR8 pixel, i.e. [`Rgba8`] with the channels in reverse order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Immutable, IntoBytes, FromBytes, Default)]
 #[repr(C)]
 pub struct Abgr8 {
     pub a: u8,
@@ -177,6 +332,50 @@ pub struct Abgr8 {
     pub r: u8,
 }
 
+impl Abgr8 {
+    #[inline(always)]
+    pub fn from_rgba8(value: Rgba8) -> Self {
+        Self {
+            a: value.a,
+            b: value.b,
+            g: value.g,
+            r: value.r,
+        }
+    }
+
+    #[inline(always)]
+    pub fn to_rgba8(self) -> Rgba8 {
+        Rgba8 {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+            a: self.a,
+        }
+    }
+
+    #[inline(always)]
+    pub fn from_rgb565(value: u16) -> Self {
+        Self::from_rgba8(Rgba8::from_rgb565(value))
+    }
+
+    #[inline(always)]
+    pub fn to_rgb565(self) -> u16 {
+        self.to_rgba8().to_rgb565()
+    }
+}
+
+impl From<Rgba8> for Abgr8 {
+    fn from(value: Rgba8) -> Self {
+        Self::from_rgba8(value)
+    }
+}
+
+impl From<Abgr8> for Rgba8 {
+    fn from(value: Abgr8) -> Self {
+        value.to_rgba8()
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Immutable, IntoBytes, Default)]
 #[repr(C)]
 pub struct Rgba16 {
@@ -216,6 +415,25 @@ impl Rgba {
             a: 1.0,
         }
     }
+
+    /// Composites `self` over `dst` using straight (non-premultiplied) alpha.
+    #[inline(always)]
+    pub fn blend_over(self, dst: Self) -> Self {
+        let src_a = self.a.clamp(0.0, 1.0);
+        let dst_a = dst.a.clamp(0.0, 1.0) * (1.0 - src_a);
+        let out_a = src_a + dst_a;
+        if out_a <= 0.0 {
+            return dst;
+        }
+
+        let channel = |src: f32, dst: f32| (src * src_a + dst * dst_a) / out_a;
+        Self {
+            r: channel(self.r, dst.r),
+            g: channel(self.g, dst.g),
+            b: channel(self.b, dst.b),
+            a: out_a,
+        }
+    }
 }
 
 impl std::fmt::Debug for Rgba {
@@ -277,3 +495,224 @@ impl From<Rgba16> for Rgba {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blend_over_transparent() {
+        let src = Rgba8 {
+            r: 10,
+            g: 20,
+            b: 30,
+            a: 0,
+        };
+
+        let dst = Rgba8 {
+            r: 200,
+            g: 100,
+            b: 50,
+            a: 128,
+        };
+
+        assert_eq!(src.blend_over(dst), dst);
+        assert_eq!(Rgba::from(src).blend_over(dst.into()), Rgba::from(dst));
+    }
+
+    #[test]
+    fn blend_over_opaque() {
+        let src = Rgba8 {
+            r: 10,
+            g: 20,
+            b: 30,
+            a: 255,
+        };
+
+        let dst = Rgba8 {
+            r: 200,
+            g: 100,
+            b: 50,
+            a: 128,
+        };
+
+        assert_eq!(src.blend_over(dst), src);
+        assert_eq!(Rgba::from(src).blend_over(dst.into()), Rgba::from(src));
+    }
+
+    #[test]
+    fn blend_over_half() {
+        let src = Rgba8 {
+            r: 255,
+            g: 0,
+            b: 100,
+            a: 128,
+        };
+
+        let dst = Rgba8 {
+            r: 0,
+            g: 255,
+            b: 100,
+            a: 255,
+        };
+
+        assert_eq!(
+            src.blend_over(dst),
+            Rgba8 {
+                r: 128,
+                g: 127,
+                b: 100,
+                a: 255,
+            }
+        );
+    }
+
+    #[test]
+    fn abgr8_round_trip() {
+        let rgba = Rgba8 {
+            r: 1,
+            g: 2,
+            b: 3,
+            a: 4,
+        };
+
+        let abgr = Abgr8::from_rgba8(rgba);
+        assert_eq!(abgr.as_bytes(), [4, 3, 2, 1]);
+        assert_eq!(abgr.to_rgba8(), rgba);
+        assert_eq!(Rgba::from(abgr), Rgba::from(rgba));
+
+        for value in [0x0000, 0xFFFF, 0xF800, 0x07E0, 0x001F, 0x1234] {
+            let abgr = Abgr8::from_rgb565(value);
+            assert_eq!(abgr.to_rgba8(), Rgba8::from_rgb565(value));
+            assert_eq!(abgr.to_rgb565(), value);
+        }
+    }
+
+    /// PSNR of a gradient encoded to RGB565 and decoded back, after averaging 4x4 blocks (i.e.
+    /// roughly as perceived from a distance).
+    fn gradient_psnr(encode: fn(&[Rgba8], usize, &mut [u16])) -> f64 {
+        const WIDTH: usize = 256;
+        const HEIGHT: usize = 4;
+
+        let pixels = (0..WIDTH * HEIGHT)
+            .map(|i| {
+                let v = (i % WIDTH) as u8;
+                Rgba8 {
+                    r: v,
+                    g: v,
+                    b: v,
+                    a: 255,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = vec![0; pixels.len()];
+        encode(&pixels, WIDTH, &mut encoded);
+
+        let mut squared_error = 0.0;
+        for block in 0..WIDTH / 4 {
+            let mut expected = 0.0;
+            let mut actual = 0.0;
+            for y in 0..HEIGHT {
+                for x in block * 4..block * 4 + 4 {
+                    let i = y * WIDTH + x;
+                    expected += pixels[i].r as f64;
+                    actual += Rgba8::from_rgb565(encoded[i]).r as f64;
+                }
+            }
+
+            let error = (expected - actual) / 16.0;
+            squared_error += error * error;
+        }
+
+        let mse = squared_error / (WIDTH / 4) as f64;
+        10.0 * (255.0f64 * 255.0 / mse).log10()
+    }
+
+    #[test]
+    fn dithering_reduces_banding() {
+        let truncated = gradient_psnr(|pixels, _, out| {
+            for (pixel, out) in pixels.iter().zip(out) {
+                *out = pixel.to_rgb565();
+            }
+        });
+        let dithered = gradient_psnr(dither_to_rgb565);
+
+        assert!(dithered > truncated + 6.0, "{dithered} vs {truncated}");
+    }
+
+    #[test]
+    fn dithering_keeps_extremes() {
+        let black = Rgba8::default();
+        let white = Rgba8 {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+
+        for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+            assert_eq!(black.to_rgb565_dithered(x, y), 0);
+            assert_eq!(white.to_rgb565_dithered(x, y), 0xFFFF);
+            assert_eq!(white.to_rgb5a3_dithered(x, y), 0xFFFF);
+        }
+    }
+
+    #[test]
+    fn rgb5a3_alpha_threshold() {
+        let pixel = |a| Rgba8 {
+            r: 255,
+            g: 0,
+            b: 136,
+            a,
+        };
+
+        // opaque: 1 | r5 | g5 | b5
+        assert_eq!(pixel(255).to_rgb5a3(), 0xFC11);
+        assert_eq!(pixel(254).to_rgb5a3(), 0xFC11);
+        assert_eq!(pixel(RGB5A3_OPAQUE_ALPHA).to_rgb5a3(), 0xFC11);
+
+        // translucent: 0 | a3 | r4 | g4 | b4
+        assert_eq!(pixel(RGB5A3_OPAQUE_ALPHA - 1).to_rgb5a3(), 0x7F08);
+        assert_eq!(pixel(224).to_rgb5a3(), 0x7F08);
+        assert_eq!(pixel(15).to_rgb5a3(), 0x0F08);
+        assert_eq!(pixel(0).to_rgb5a3(), 0x0F08);
+
+        for a in [0, 15, 224, 239, 240, 254, 255] {
+            assert_eq!(
+                pixel(a).to_rgb5a3_dithered(0, 0) & 0xF000,
+                pixel(a).to_rgb5a3() & 0xF000
+            );
+        }
+    }
+
+    #[test]
+    fn rgb5a3_round_trip() {
+        for value in 0..=u16::MAX {
+            assert_eq!(Rgba8::from_rgb5a3(value).to_rgb5a3(), value, "{value:04X}");
+        }
+    }
+
+    #[test]
+    fn gamma_table() {
+        assert!(GammaTable::new(1.0).is_identity());
+
+        let table = GammaTable::new(2.2);
+        assert!(!table.is_identity());
+        assert_eq!(table.apply(0), 0);
+        assert_eq!(table.apply(255), 255);
+        assert_eq!(table.apply(128), 186);
+
+        let pixel = Rgba8 {
+            r: 64,
+            g: 128,
+            b: 192,
+            a: 128,
+        };
+        let encoded = pixel.with_gamma(&table);
+        assert_eq!(
+            (encoded.r, encoded.g, encoded.b, encoded.a),
+            (136, 186, 224, 128)
+        );
+    }
+}
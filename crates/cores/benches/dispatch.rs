@@ -0,0 +1,116 @@
+//! Compares running the JIT through a boxed [`CpuCore`] against running it monomorphized.
+use std::hint::black_box;
+use std::path::Path;
+
+use cores::cpu::jit;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use lazuli::cores::{Cores, CpuCore};
+use lazuli::disks::dol::{Dol, Header};
+use lazuli::modules::audio::NopAudioModule;
+use lazuli::modules::debug::NopDebugModule;
+use lazuli::modules::disk::NopDiskModule;
+use lazuli::modules::input::NopInputModule;
+use lazuli::modules::render::NopRenderModule;
+use lazuli::modules::vertex::NopVertexModule;
+use lazuli::system::executable::Executable;
+use lazuli::system::{self, Modules};
+use lazuli::{Cycles, Lazuli};
+
+const ENTRY: u32 = 0x8000_3100;
+/// How many cycles are emulated per iteration.
+const CYCLES: u64 = 1_000_000;
+
+/// Increments a counter forever.
+const PROGRAM: [u32; 2] = [
+    0x3884_0001, // loop: addi r4, r4, 0x1
+    0x4BFF_FFFC, // b loop
+];
+
+fn executable() -> Executable {
+    let mut header = Header::default();
+    header.text_offsets[0] = 0x100;
+    header.text_targets[0] = ENTRY;
+    header.text_sizes[0] = 4 * PROGRAM.len() as u32;
+    header.entry = ENTRY;
+
+    Executable::Dol(Dol {
+        header,
+        body: PROGRAM.iter().flat_map(|code| code.to_be_bytes()).collect(),
+    })
+}
+
+fn lazuli<C: CpuCore + ?Sized>(cpu: Box<C>) -> Lazuli<C> {
+    let cores = Cores {
+        cpu,
+        dsp: Box::new(cores::dsp::interpreter::Core::default()),
+    };
+
+    let modules = Modules {
+        audio: Box::new(NopAudioModule),
+        debug: Box::new(NopDebugModule),
+        disk: Box::new(NopDiskModule),
+        input: Box::new(NopInputModule),
+        render: Box::new(NopRenderModule),
+        vertex: Box::new(NopVertexModule),
+    };
+
+    let config = system::Config {
+        boot: system::BootMode::DirectDol,
+        ipl: None,
+        sideload: Some(executable()),
+        fill_seed: Some(0x5EED),
+        deterministic: true,
+        rtc_epoch: 0,
+    };
+
+    Lazuli::new(cores, modules, config).unwrap()
+}
+
+fn jit(cache_path: &Path) -> jit::Core {
+    jit::Core::new(jit::Config {
+        instr_per_block: 64,
+        jit_settings: jit::ppcjit::Settings {
+            compiler: Default::default(),
+            cache_path: Some(cache_path.to_path_buf()),
+        },
+    })
+}
+
+/// Emulates [`CYCLES`] cycles, `slice` cycles at a time.
+fn run<C: CpuCore + ?Sized>(lazuli: &mut Lazuli<C>, slice: u64) {
+    let mut elapsed = Cycles(0);
+    while elapsed < Cycles(CYCLES) {
+        elapsed += lazuli.exec(black_box(Cycles(slice)), &[]).cycles;
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let cache_path = std::env::temp_dir().join(format!("dispatch-bench-{}", std::process::id()));
+    let mut boxed = lazuli::<dyn CpuCore>(Box::new(jit(&cache_path.join("boxed"))));
+    let mut direct = lazuli(Box::new(jit(&cache_path.join("direct"))));
+
+    let mut group = c.benchmark_group("CPU Dispatch");
+    group.throughput(Throughput::Elements(CYCLES));
+
+    // small slices are what the frontend uses while single stepping or near breakpoints, and
+    // are where the cost of each call matters the most
+    for slice in [100, 10_000, CYCLES] {
+        group.bench_with_input(BenchmarkId::new("Boxed", slice), &slice, |b, &slice| {
+            b.iter(|| run(&mut boxed, slice))
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("Monomorphized", slice),
+            &slice,
+            |b, &slice| b.iter(|| run(&mut direct, slice)),
+        );
+    }
+
+    group.finish();
+
+    drop((boxed, direct));
+    _ = std::fs::remove_dir_all(cache_path);
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
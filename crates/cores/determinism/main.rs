@@ -0,0 +1,145 @@
+//! Runs the same executable twice in deterministic mode and checks that both runs end up in the
+//! same state.
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use cores::cpu::jit;
+use lazuli::cores::Cores;
+use lazuli::disks::dol::{Dol, Header};
+use lazuli::modules::audio::NopAudioModule;
+use lazuli::modules::debug::NopDebugModule;
+use lazuli::modules::disk::NopDiskModule;
+use lazuli::modules::input::NopInputModule;
+use lazuli::modules::render::NopRenderModule;
+use lazuli::modules::vertex::NopVertexModule;
+use lazuli::system::executable::Executable;
+use lazuli::system::{self, Modules};
+use lazuli::{Cycles, Lazuli};
+
+const ENTRY: u32 = 0x8000_3100;
+const RTC_EPOCH: u32 = 0x1234_5678;
+const CYCLES: Cycles = Cycles(10_000_000);
+const STEP: Cycles = Cycles(1_000_000);
+/// Where the program stores the RTC, as an offset into RAM.
+const RTC_OUT: usize = 0x0020_0000;
+
+/// Reads the RTC through EXI into `0x8020_0000`, then keeps storing the time base and a counter
+/// into a ring buffer at `0x8010_0000`.
+const PROGRAM: [u32; 25] = [
+    0x3CC0_CC00, // lis r6, 0xCC00
+    0x60C6_6800, // ori r6, r6, 0x6800
+    0x38E0_0100, // li r7, 0x100 (select the RTC)
+    0x90E6_0000, // stw r7, 0x0(r6)
+    0x3CE0_2000, // lis r7, 0x2000 (RTC read command)
+    0x90E6_0010, // stw r7, 0x10(r6)
+    0x38E0_0035, // li r7, 0x35 (immediate write of 4 bytes)
+    0x90E6_000C, // stw r7, 0xC(r6)
+    0x38E0_0031, // li r7, 0x31 (immediate read of 4 bytes)
+    0x90E6_000C, // stw r7, 0xC(r6)
+    0x80E6_0010, // lwz r7, 0x10(r6)
+    0x38A0_0000, // li r5, 0x0
+    0x90A6_0000, // stw r5, 0x0(r6) (deselect)
+    0x3C60_8020, // lis r3, 0x8020
+    0x90E3_0000, // stw r7, 0x0(r3)
+    0x3C60_8010, // lis r3, 0x8010
+    0x3880_0000, // li r4, 0x0
+    0x7CAC_42E6, // loop: mftb r5
+    0x90A3_0000, // stw r5, 0x0(r3)
+    0x9083_0004, // stw r4, 0x4(r3)
+    0x3884_0001, // addi r4, r4, 0x1
+    0x3863_0008, // addi r3, r3, 0x8
+    0x5463_043E, // rlwinm r3, r3, 0, 16, 31
+    0x6463_8010, // oris r3, r3, 0x8010
+    0x4BFF_FFE4, // b loop
+];
+
+fn executable() -> Executable {
+    let mut header = Header::default();
+    header.text_offsets[0] = 0x100;
+    header.text_targets[0] = ENTRY;
+    header.text_sizes[0] = 4 * PROGRAM.len() as u32;
+    header.entry = ENTRY;
+
+    Executable::Dol(Dol {
+        header,
+        body: PROGRAM.iter().flat_map(|code| code.to_be_bytes()).collect(),
+    })
+}
+
+/// The state a run ends up in.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    ram: u64,
+    registers: u64,
+    rtc: u32,
+    iterations: u32,
+}
+
+fn run(index: usize) -> Outcome {
+    let cache_path =
+        std::env::temp_dir().join(format!("determinism-{}-{index}", std::process::id()));
+
+    let cores = Cores {
+        cpu: Box::new(jit::Core::new(jit::Config {
+            instr_per_block: 64,
+            jit_settings: jit::ppcjit::Settings {
+                compiler: Default::default(),
+                cache_path: Some(cache_path.clone()),
+            },
+        })),
+        dsp: Box::new(cores::dsp::interpreter::Core::default()),
+    };
+
+    let modules = Modules {
+        audio: Box::new(NopAudioModule),
+        debug: Box::new(NopDebugModule),
+        disk: Box::new(NopDiskModule),
+        input: Box::new(NopInputModule),
+        render: Box::new(NopRenderModule),
+        vertex: Box::new(NopVertexModule),
+    };
+
+    let config = system::Config {
+        boot: system::BootMode::DirectDol,
+        ipl: None,
+        sideload: Some(executable()),
+        fill_seed: Some(0x5EED),
+        deterministic: true,
+        rtc_epoch: RTC_EPOCH,
+    };
+
+    let mut lazuli = Lazuli::new(cores, modules, config).unwrap();
+    let mut elapsed = Cycles(0);
+    while elapsed < CYCLES {
+        elapsed += lazuli.exec(STEP, &[]).cycles;
+    }
+
+    let sys = &lazuli.sys;
+    let mut ram = DefaultHasher::new();
+    sys.mem.ram().hash(&mut ram);
+
+    let mut registers = DefaultHasher::new();
+    sys.cpu.pc.hash(&mut registers);
+    sys.cpu.user.gpr.hash(&mut registers);
+
+    let outcome = Outcome {
+        ram: ram.finish(),
+        registers: registers.finish(),
+        rtc: u32::from_be_bytes(sys.mem.ram()[RTC_OUT..][..4].try_into().unwrap()),
+        iterations: sys.cpu.user.gpr[4],
+    };
+
+    drop(lazuli);
+    _ = std::fs::remove_dir_all(cache_path);
+
+    outcome
+}
+
+#[test]
+fn runs_are_identical() {
+    let first = run(0);
+    let second = run(1);
+
+    assert_eq!(first.rtc, RTC_EPOCH);
+    assert!(first.iterations > 0);
+    assert_eq!(first, second);
+}
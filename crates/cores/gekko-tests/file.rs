@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use binrw::helpers::until_eof;
+use binrw::io::BufReader;
+use binrw::{BinRead, binread};
+use lazuli::gekko::{CondReg, FloatControlReg, FloatPair, User, XerReg};
+
+/// The user level state of the CPU the cases deal with.
+#[derive(BinRead, Debug, Clone, PartialEq)]
+#[br(little)]
+pub struct State {
+    pub gpr: [u32; 32],
+    pub fpr: [[u64; 2]; 32],
+    pub cr: u32,
+    pub xer: u32,
+    pub fpscr: u32,
+}
+
+impl State {
+    pub fn from_user(user: &User) -> Self {
+        Self {
+            gpr: user.gpr,
+            fpr: user.fpr.map(|pair| pair.0.map(f64::to_bits)),
+            cr: user.cr.to_bits(),
+            xer: user.xer.to_bits(),
+            fpscr: user.fpscr.to_bits(),
+        }
+    }
+
+    /// Writes this state into the user level registers, leaving the others untouched.
+    pub fn apply(&self, user: &mut User) {
+        user.gpr = self.gpr;
+        user.fpr = self.fpr.map(|pair| FloatPair(pair.map(f64::from_bits)));
+        user.cr = CondReg::from_bits(self.cr);
+        user.xer = XerReg::from_bits(self.xer);
+        user.fpscr = FloatControlReg::from_bits(self.fpscr);
+    }
+}
+
+#[derive(BinRead)]
+#[br(import(instructions: u16), little)]
+pub struct TestCase {
+    #[br(count = instructions)]
+    pub instructions: Vec<u32>,
+    pub initial: State,
+    pub expected: State,
+}
+
+#[binread]
+#[br(little)]
+pub struct TestFile {
+    #[br(temp)]
+    instructions: u16,
+    #[br(parse_with = until_eof, args(instructions))]
+    pub cases: Vec<TestCase>,
+}
+
+impl TestFile {
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let mut buffer = BufReader::new(std::fs::File::open(path).unwrap());
+        Self::read(&mut buffer).unwrap()
+    }
+}
@@ -0,0 +1,811 @@
+#!/usr/bin/env python3
+"""Generates the gekko-tests corpus from a reference implementation of the covered instructions.
+
+Every file in `tests/` holds the cases of a single instruction (or, for `mixed.bin`, of short
+sequences of integer instructions). The format is little endian:
+
+    instructions per case: u16
+    cases, until the end of the file:
+        instructions: [u32; instructions per case]
+        initial: State
+        expected: State
+
+    State:
+        gpr: [u32; 32]
+        fpr: [[u64; 2]; 32]  (ps0 and ps1, as double bits)
+        cr: u32
+        xer: u32
+        fpscr: u32
+
+Usage: generate.py [output directory]. The generator is deterministic, so regenerating the corpus
+only changes it if the reference does.
+"""
+
+import os
+import random
+import struct
+import sys
+from dataclasses import dataclass, field
+
+CASES_PER_FILE = 48
+MIXED_CASES = 256
+MIXED_LENGTH = 4
+SEED = 0x6E6B6F
+
+MASK32 = 0xFFFF_FFFF
+
+XER_SO = 1 << 31
+XER_OV = 1 << 30
+XER_CA = 1 << 29
+
+FPSCR_FX = 1 << 31
+FPSCR_OX = 1 << 28
+FPSCR_FPRF = 0x1F << 12
+FPSCR_FPCC = 0xF << 12
+
+# FPRF classes of the values paired single cases produce
+FPRF_POS_NORMAL = 0b00100
+FPRF_NEG_NORMAL = 0b01000
+FPRF_POS_ZERO = 0b00010
+FPRF_NEG_ZERO = 0b10010
+
+EDGE_VALUES = [0, 1, 2, 0x7FFF_FFFF, 0x8000_0000, 0x8000_0001, 0xFFFF_FFFF, 0xFFFF_FFFE, 0xFFFF]
+
+
+@dataclass
+class State:
+    gpr: list = field(default_factory=lambda: [0] * 32)
+    fpr: list = field(default_factory=lambda: [[0.0, 0.0] for _ in range(32)])
+    cr: int = 0
+    xer: int = 0
+    fpscr: int = 0
+
+    def copy(self):
+        return State(
+            list(self.gpr), [list(p) for p in self.fpr], self.cr, self.xer, self.fpscr
+        )
+
+    def pack(self):
+        out = struct.pack("<32I", *self.gpr)
+        for ps0, ps1 in self.fpr:
+            out += struct.pack("<dd", ps0, ps1)
+        return out + struct.pack("<III", self.cr, self.xer, self.fpscr)
+
+    def cr_field(self, index):
+        return (self.cr >> (28 - 4 * index)) & 0xF
+
+    def set_cr_field(self, index, value):
+        shift = 28 - 4 * index
+        self.cr = (self.cr & ~(0xF << shift) & MASK32) | (value << shift)
+
+    def cr_bit(self, index):
+        return (self.cr >> (31 - index)) & 1
+
+    def set_cr_bit(self, index, value):
+        bit = 1 << (31 - index)
+        self.cr = (self.cr | bit) if value else (self.cr & ~bit & MASK32)
+
+
+def signed(value):
+    value &= MASK32
+    return value - (1 << 32) if value & 0x8000_0000 else value
+
+
+def sext16(value):
+    return value - 0x1_0000 if value & 0x8000 else value
+
+
+def rotl(value, amount):
+    amount &= 31
+    return ((value << amount) | (value >> (32 - amount))) & MASK32
+
+
+def mask(mb, me):
+    """The rotate mask from bit `mb` to bit `me`, in PowerPC bit order."""
+    begin = MASK32 >> mb
+    end = (MASK32 << (31 - me)) & MASK32
+    return (begin & end) if mb <= me else (begin | end)
+
+
+def compare(a, b):
+    """CR field bits of a comparison, without SO."""
+    return 0b1000 if a < b else 0b0100 if a > b else 0b0010
+
+
+def update_cr0(state, value):
+    so = 1 if state.xer & XER_SO else 0
+    state.set_cr_field(0, compare(signed(value), 0) | so)
+
+
+def update_cr1(state):
+    state.set_cr_field(1, state.fpscr >> 28)
+
+
+# ------------------------------------------------------------------------------------------------
+# random values
+# ------------------------------------------------------------------------------------------------
+
+
+def rand_u32(rng):
+    kind = rng.random()
+    if kind < 0.2:
+        return rng.choice(EDGE_VALUES)
+    if kind < 0.35:
+        return rng.randrange(0, 0x100)
+    if kind < 0.5:
+        return (-rng.randrange(1, 0x100)) & MASK32
+    return rng.getrandbits(32)
+
+
+def rand_simm(rng):
+    kind = rng.random()
+    if kind < 0.2:
+        return rng.choice([0, 1, 0x7FFF, 0x8000, 0xFFFF])
+    return rng.getrandbits(16)
+
+
+def rand_single(rng):
+    """A single precision value with few mantissa bits and a small exponent, such that sums and
+    products of two of them are exact in single precision."""
+    if rng.random() < 0.05:
+        return rng.choice([0.0, -0.0])
+
+    value = rng.randrange(1, 1 << 11) * 2.0 ** rng.randrange(-8, 5)
+    return -value if rng.random() < 0.5 else value
+
+
+def rand_state(rng, floats):
+    state = State()
+    state.gpr = [rand_u32(rng) for _ in range(32)]
+    if floats:
+        state.fpr = [[rand_single(rng), rand_single(rng)] for _ in range(32)]
+
+        # occasionally make both operands of an op cancel each other out
+        for _ in range(4):
+            a, b = rng.randrange(32), rng.randrange(32)
+            state.fpr[b][rng.randrange(2)] = -state.fpr[a][rng.randrange(2)]
+
+        fpscr_bits = FPSCR_FPRF | FPSCR_FX | FPSCR_OX
+        state.fpscr = rng.getrandbits(32) & fpscr_bits
+
+    state.cr = rng.getrandbits(32)
+    state.xer = rng.getrandbits(3) << 29
+    return state
+
+
+def reg(rng):
+    return rng.randrange(32)
+
+
+# ------------------------------------------------------------------------------------------------
+# integer arithmetic
+# ------------------------------------------------------------------------------------------------
+
+
+def xo_form(xo, d, a, b, oe, rc):
+    return (31 << 26) | (d << 21) | (a << 16) | (b << 11) | (oe << 10) | (xo << 1) | rc
+
+
+def d_form(opcode, d, a, imm):
+    return (opcode << 26) | (d << 21) | (a << 16) | (imm & 0xFFFF)
+
+
+def carrying(lhs, rhs, carry_in):
+    """Returns the result, carry and signed overflow of `lhs + rhs + carry_in`."""
+    total = lhs + rhs + carry_in
+    result = total & MASK32
+    overflow = not -(1 << 31) <= signed(lhs) + signed(rhs) + carry_in < (1 << 31)
+    return result, total >> 32, overflow
+
+
+def finish_arith(state, d, result, oe, overflow, rc, carry=None):
+    state.gpr[d] = result
+    if carry is not None:
+        state.xer = (state.xer | XER_CA) if carry else (state.xer & ~XER_CA & MASK32)
+    if oe:
+        state.xer = (state.xer | XER_OV | XER_SO) if overflow else (state.xer & ~XER_OV)
+    if rc:
+        update_cr0(state, result)
+
+
+def add_family(xo, operands, sets_carry):
+    """Arithmetic of the `add`/`subf` families: `operands(ra, rb, ca)` returns the addends."""
+
+    def make(rng, oe):
+        d, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+        def run(state):
+            ca = 1 if state.xer & XER_CA else 0
+            lhs, rhs, carry_in = operands(state.gpr[a], state.gpr[b], ca)
+            result, carry, overflow = carrying(lhs, rhs, carry_in)
+            finish_arith(state, d, result, oe, overflow, rc, carry if sets_carry else None)
+
+        # single operand instructions have a zero rB field
+        uses_b = xo not in (104, 200, 202, 232, 234)
+        return xo_form(xo, d, a, b if uses_b else 0, oe, rc), run
+
+    return make
+
+
+def not32(value):
+    return ~value & MASK32
+
+
+ADD_FAMILY = {
+    "add": (266, lambda a, b, ca: (a, b, 0), False),
+    "addc": (10, lambda a, b, ca: (a, b, 0), True),
+    "adde": (138, lambda a, b, ca: (a, b, ca), True),
+    "addze": (202, lambda a, b, ca: (a, 0, ca), True),
+    "addme": (234, lambda a, b, ca: (a, MASK32, ca), True),
+    "subf": (40, lambda a, b, ca: (not32(a), b, 1), False),
+    "subfc": (8, lambda a, b, ca: (not32(a), b, 1), True),
+    "subfe": (136, lambda a, b, ca: (not32(a), b, ca), True),
+    "subfze": (200, lambda a, b, ca: (not32(a), 0, ca), True),
+    "subfme": (232, lambda a, b, ca: (not32(a), MASK32, ca), True),
+    "neg": (104, lambda a, b, ca: (not32(a), 0, 1), False),
+}
+
+
+def mul_div(xo, op):
+    """`op(ra, rb)` returns the result and whether it overflowed."""
+
+    def make(rng, oe):
+        d, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+        def run(state):
+            result, overflow = op(state.gpr[a], state.gpr[b])
+            finish_arith(state, d, result & MASK32, oe, overflow, rc)
+
+        return xo_form(xo, d, a, b, oe, rc), run
+
+    return make
+
+
+def mullw(a, b):
+    product = signed(a) * signed(b)
+    return product, not -(1 << 31) <= product < (1 << 31)
+
+
+def mulhw(a, b):
+    return (signed(a) * signed(b)) >> 32, False
+
+
+def mulhwu(a, b):
+    return (a * b) >> 32, False
+
+
+def divw(a, b):
+    # matches the hardware on the undefined cases
+    if b == 0 or (a == 0x8000_0000 and b == MASK32):
+        return (MASK32 if a & 0x8000_0000 else 0), True
+
+    quotient = abs(signed(a)) // abs(signed(b))
+    return (-quotient if (signed(a) < 0) != (signed(b) < 0) else quotient), False
+
+
+def divwu(a, b):
+    if b == 0:
+        return 0, True
+    return a // b, False
+
+
+MUL_DIV = {
+    "mullw": (235, mullw, True),
+    "mulhw": (75, mulhw, False),
+    "mulhwu": (11, mulhwu, False),
+    "divw": (491, divw, True),
+    "divwu": (459, divwu, True),
+}
+
+
+def addi(opcode, shifted):
+    def make(rng):
+        d, a, imm = reg(rng), reg(rng), rand_simm(rng)
+
+        def run(state):
+            base = state.gpr[a] if a != 0 else 0
+            value = sext16(imm) << 16 if shifted else sext16(imm)
+            state.gpr[d] = (base + value) & MASK32
+
+        return d_form(opcode, d, a, imm), run
+
+    return make
+
+
+def addic(rc):
+    def make(rng):
+        d, a, imm = reg(rng), reg(rng), rand_simm(rng)
+
+        def run(state):
+            result, carry, _ = carrying(state.gpr[a], sext16(imm) & MASK32, 0)
+            finish_arith(state, d, result, 0, False, rc, carry)
+
+        return d_form(13 if rc else 12, d, a, imm), run
+
+    return make
+
+
+def subfic(rng):
+    d, a, imm = reg(rng), reg(rng), rand_simm(rng)
+
+    def run(state):
+        result, carry, _ = carrying(not32(state.gpr[a]), sext16(imm) & MASK32, 1)
+        finish_arith(state, d, result, 0, False, 0, carry)
+
+    return d_form(8, d, a, imm), run
+
+
+def mulli(rng):
+    d, a, imm = reg(rng), reg(rng), rand_simm(rng)
+
+    def run(state):
+        state.gpr[d] = (signed(state.gpr[a]) * sext16(imm)) & MASK32
+
+    return d_form(7, d, a, imm), run
+
+
+# ------------------------------------------------------------------------------------------------
+# rotates, shifts and masks
+# ------------------------------------------------------------------------------------------------
+
+
+def m_form(opcode, s, a, sh, mb, me, rc):
+    return (opcode << 26) | (s << 21) | (a << 16) | (sh << 11) | (mb << 6) | (me << 1) | rc
+
+
+def x_form(xo, s, a, b, rc):
+    return (31 << 26) | (s << 21) | (a << 16) | (b << 11) | (xo << 1) | rc
+
+
+def rand_mask_bounds(rng):
+    kind = rng.random()
+    if kind < 0.2:
+        # common masks: clrlwi, clrrwi, slwi, srwi
+        return rng.choice([(rng.randrange(32), 31), (0, rng.randrange(32))])
+    return rng.randrange(32), rng.randrange(32)
+
+
+def rotate(opcode):
+    def make(rng):
+        s, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+        sh = rng.randrange(32)
+        mb, me = rand_mask_bounds(rng)
+
+        def run(state):
+            amount = state.gpr[b] & 31 if opcode == 23 else sh
+            m = mask(mb, me)
+            rotated = rotl(state.gpr[s], amount) & m
+            if opcode == 20:
+                rotated |= state.gpr[a] & ~m & MASK32
+
+            state.gpr[a] = rotated
+            if rc:
+                update_cr0(state, rotated)
+
+        return m_form(opcode, s, a, b if opcode == 23 else sh, mb, me, rc), run
+
+    return make
+
+
+def andi(shifted):
+    def make(rng):
+        s, a, imm = reg(rng), reg(rng), rng.getrandbits(16)
+
+        def run(state):
+            result = state.gpr[s] & (imm << 16 if shifted else imm)
+            state.gpr[a] = result
+            update_cr0(state, result)
+
+        return d_form(29 if shifted else 28, s, a, imm), run
+
+    return make
+
+
+def logical(xo, op):
+    def make(rng):
+        s, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+        def run(state):
+            result = op(state.gpr[s], state.gpr[b]) & MASK32
+            state.gpr[a] = result
+            if rc:
+                update_cr0(state, result)
+
+        return x_form(xo, s, a, b, rc), run
+
+    return make
+
+
+def rand_shift_amount(rng, state, b):
+    """Makes shift amounts in range (or just out of it) more likely."""
+    if rng.random() < 0.5:
+        state.gpr[b] = rng.randrange(0x40)
+
+
+def shift(xo):
+    def make(rng):
+        s, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+        def run(state):
+            value, amount = state.gpr[s], state.gpr[b] & 0x3F
+            if xo == 24:
+                result = (value << amount) & MASK32 if amount < 32 else 0
+            elif xo == 536:
+                result = value >> amount if amount < 32 else 0
+            else:
+                result, carry = sraw(value, amount)
+                state.xer = (state.xer | XER_CA) if carry else (state.xer & ~XER_CA & MASK32)
+
+            state.gpr[a] = result
+            if rc:
+                update_cr0(state, result)
+
+        return x_form(xo, s, a, b, rc), run
+
+    make.shift_operand = True
+    return make
+
+
+def sraw(value, amount):
+    negative = value & 0x8000_0000 != 0
+    if amount >= 32:
+        return (MASK32 if negative else 0), negative
+
+    result = (signed(value) >> amount) & MASK32
+    shifted_out = value & ((1 << amount) - 1)
+    return result, negative and shifted_out != 0
+
+
+def srawi(rng):
+    s, a, sh, rc = reg(rng), reg(rng), rng.randrange(32), rng.randrange(2)
+
+    def run(state):
+        result, carry = sraw(state.gpr[s], sh)
+        state.xer = (state.xer | XER_CA) if carry else (state.xer & ~XER_CA & MASK32)
+        state.gpr[a] = result
+        if rc:
+            update_cr0(state, result)
+
+    return x_form(824, s, a, sh, rc), run
+
+
+# ------------------------------------------------------------------------------------------------
+# condition register
+# ------------------------------------------------------------------------------------------------
+
+
+def xl_form(xo, d, a, b):
+    return (19 << 26) | (d << 21) | (a << 16) | (b << 11) | (xo << 1)
+
+
+def cr_logic(xo, op):
+    def make(rng):
+        d, a, b = rng.randrange(32), rng.randrange(32), rng.randrange(32)
+
+        def run(state):
+            state.set_cr_bit(d, op(state.cr_bit(a), state.cr_bit(b)) & 1)
+
+        return xl_form(xo, d, a, b), run
+
+    return make
+
+
+def mcrf(rng):
+    d, s = rng.randrange(8), rng.randrange(8)
+
+    def run(state):
+        state.set_cr_field(d, state.cr_field(s))
+
+    return xl_form(0, d << 2, s << 2, 0), run
+
+
+def mcrxr(rng):
+    d = rng.randrange(8)
+
+    def run(state):
+        state.set_cr_field(d, state.xer >> 28)
+        state.xer &= ~(XER_SO | XER_OV | XER_CA) & MASK32
+
+    return x_form(512, d << 2, 0, 0, 0), run
+
+
+def mfcr(rng):
+    d = reg(rng)
+
+    def run(state):
+        state.gpr[d] = state.cr
+
+    return x_form(19, d, 0, 0, 0), run
+
+
+def mtcrf(rng):
+    s, crm = reg(rng), rng.getrandbits(8)
+
+    def run(state):
+        m = 0
+        for i in range(8):
+            if crm & (0x80 >> i):
+                m |= 0xF << (28 - 4 * i)
+        state.cr = (state.gpr[s] & m) | (state.cr & ~m & MASK32)
+
+    return (31 << 26) | (s << 21) | (crm << 12) | (144 << 1), run
+
+
+def cmp_reg(xo, unsigned):
+    def make(rng):
+        d, a, b = rng.randrange(8), reg(rng), reg(rng)
+
+        def run(state):
+            lhs, rhs = state.gpr[a], state.gpr[b]
+            if not unsigned:
+                lhs, rhs = signed(lhs), signed(rhs)
+            so = 1 if state.xer & XER_SO else 0
+            state.set_cr_field(d, compare(lhs, rhs) | so)
+
+        return x_form(xo, d << 2, a, b, 0), run
+
+    return make
+
+
+def cmp_imm(opcode, unsigned):
+    def make(rng):
+        d, a, imm = rng.randrange(8), reg(rng), rand_simm(rng)
+
+        def run(state):
+            lhs, rhs = state.gpr[a], imm
+            if not unsigned:
+                lhs, rhs = signed(lhs), sext16(imm)
+            so = 1 if state.xer & XER_SO else 0
+            state.set_cr_field(d, compare(lhs, rhs) | so)
+
+        return d_form(opcode, d << 2, a, imm), run
+
+    return make
+
+
+CR_LOGIC = {
+    "crand": (257, lambda a, b: a & b),
+    "crandc": (129, lambda a, b: a & ~b),
+    "creqv": (289, lambda a, b: ~(a ^ b)),
+    "crnand": (225, lambda a, b: ~(a & b)),
+    "crnor": (33, lambda a, b: ~(a | b)),
+    "cror": (449, lambda a, b: a | b),
+    "crorc": (417, lambda a, b: a | ~b),
+    "crxor": (193, lambda a, b: a ^ b),
+}
+
+
+# ------------------------------------------------------------------------------------------------
+# paired singles
+# ------------------------------------------------------------------------------------------------
+
+
+def fprf(value):
+    if value == 0.0:
+        return FPRF_NEG_ZERO if struct.pack("<d", value)[7] & 0x80 else FPRF_POS_ZERO
+    return FPRF_NEG_NORMAL if value < 0 else FPRF_POS_NORMAL
+
+
+def set_fprf(state, value):
+    state.fpscr = (state.fpscr & ~FPSCR_FPRF & MASK32) | (fprf(value) << 12)
+
+
+def ps_a_form(xo, d, a, b, c, rc):
+    return (4 << 26) | (d << 21) | (a << 16) | (b << 11) | (c << 6) | (xo << 1) | rc
+
+
+def ps_x_form(xo, d, a, b, rc):
+    return (4 << 26) | (d << 21) | (a << 16) | (b << 11) | (xo << 1) | rc
+
+
+def ps_arith(xo, op, fprf_slot=0):
+    """`op(a, b, c)` returns both slots of the result from the operand pairs."""
+
+    def make(rng):
+        d, a, b, c, rc = reg(rng), reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+        def run(state):
+            result = op(state.fpr[a], state.fpr[b], state.fpr[c])
+            state.fpr[d] = list(result)
+            set_fprf(state, result[fprf_slot])
+            if rc:
+                update_cr1(state)
+
+        # unused operand fields are zero
+        uses_b = xo in (10, 11, 20, 21, 23)
+        uses_c = xo in (10, 11, 12, 13, 23, 25)
+        return ps_a_form(xo, d, a, b if uses_b else 0, c if uses_c else 0, rc), run
+
+    return make
+
+
+def ps_move(xo, op):
+    """Moves which don't touch FPSCR. `op(a, b)` returns both slots of the result."""
+
+    def make(rng):
+        d, a, b, rc = reg(rng), reg(rng), reg(rng), rng.randrange(2)
+        uses_a = xo in (528, 560, 592, 624)
+
+        def run(state):
+            state.fpr[d] = list(op(state.fpr[a], state.fpr[b]))
+            if rc:
+                update_cr1(state)
+
+        return ps_x_form(xo, d, a if uses_a else 0, b, rc), run
+
+    return make
+
+
+def ps_sel(rng):
+    d, a, b, c, rc = reg(rng), reg(rng), reg(rng), reg(rng), rng.randrange(2)
+
+    def run(state):
+        pa, pb, pc = state.fpr[a], state.fpr[b], state.fpr[c]
+        state.fpr[d] = [pc[i] if pa[i] >= 0.0 else pb[i] for i in range(2)]
+        if rc:
+            update_cr1(state)
+
+    return ps_a_form(23, d, a, b, c, rc), run
+
+
+def ps_cmpu(xo, slot):
+    def make(rng):
+        d, a, b = rng.randrange(8), reg(rng), reg(rng)
+
+        def run(state):
+            cc = compare(state.fpr[a][slot], state.fpr[b][slot])
+            state.set_cr_field(d, cc)
+            state.fpscr = (state.fpscr & ~FPSCR_FPCC & MASK32) | (cc << 12)
+
+        return ps_x_form(xo, d << 2, a, b, 0), run
+
+    return make
+
+
+def neg(x):
+    return -x
+
+
+PS_ARITH = {
+    "ps_add": (21, lambda a, b, c: (a[0] + b[0], a[1] + b[1]), 0),
+    "ps_sub": (20, lambda a, b, c: (a[0] - b[0], a[1] - b[1]), 0),
+    "ps_mul": (25, lambda a, b, c: (a[0] * c[0], a[1] * c[1]), 0),
+    "ps_muls0": (12, lambda a, b, c: (a[0] * c[0], a[1] * c[0]), 0),
+    "ps_muls1": (13, lambda a, b, c: (a[0] * c[1], a[1] * c[1]), 0),
+    "ps_sum0": (10, lambda a, b, c: (a[0] + b[1], c[1]), 0),
+    "ps_sum1": (11, lambda a, b, c: (c[0], a[0] + b[1]), 1),
+}
+
+PS_MOVE = {
+    "ps_mr": (72, lambda a, b: (b[0], b[1])),
+    "ps_neg": (40, lambda a, b: (-b[0], -b[1])),
+    "ps_abs": (264, lambda a, b: (abs(b[0]), abs(b[1]))),
+    "ps_nabs": (136, lambda a, b: (-abs(b[0]), -abs(b[1]))),
+    "ps_merge00": (528, lambda a, b: (a[0], b[0])),
+    "ps_merge01": (560, lambda a, b: (a[0], b[1])),
+    "ps_merge10": (592, lambda a, b: (a[1], b[0])),
+    "ps_merge11": (624, lambda a, b: (a[1], b[1])),
+}
+
+
+# ------------------------------------------------------------------------------------------------
+# corpus
+# ------------------------------------------------------------------------------------------------
+
+
+def suites():
+    """Returns (name, generator, uses floats) for every file of the corpus."""
+    out = []
+    for name, (xo, operands, sets_carry) in ADD_FAMILY.items():
+        make = add_family(xo, operands, sets_carry)
+        out.append((name, lambda rng, make=make: make(rng, 0), False))
+        out.append((name + "o", lambda rng, make=make: make(rng, 1), False))
+
+    for name, (xo, op, has_oe) in MUL_DIV.items():
+        make = mul_div(xo, op)
+        out.append((name, lambda rng, make=make: make(rng, 0), False))
+        if has_oe:
+            out.append((name + "o", lambda rng, make=make: make(rng, 1), False))
+
+    out += [
+        ("addi", addi(14, False), False),
+        ("addis", addi(15, True), False),
+        ("addic", addic(0), False),
+        ("addic_rc", addic(1), False),
+        ("subfic", subfic, False),
+        ("mulli", mulli, False),
+        ("rlwinm", rotate(21), False),
+        ("rlwimi", rotate(20), False),
+        ("rlwnm", rotate(23), False),
+        ("andi_rc", andi(False), False),
+        ("andis_rc", andi(True), False),
+        ("and", logical(28, lambda s, b: s & b), False),
+        ("andc", logical(60, lambda s, b: s & ~b), False),
+        ("slw", shift(24), False),
+        ("srw", shift(536), False),
+        ("sraw", shift(792), False),
+        ("srawi", srawi, False),
+    ]
+
+    for name, (xo, op) in CR_LOGIC.items():
+        out.append((name, cr_logic(xo, op), False))
+
+    out += [
+        ("mcrf", mcrf, False),
+        ("mcrxr", mcrxr, False),
+        ("mfcr", mfcr, False),
+        ("mtcrf", mtcrf, False),
+        ("cmp", cmp_reg(0, False), False),
+        ("cmpl", cmp_reg(32, True), False),
+        ("cmpi", cmp_imm(11, False), False),
+        ("cmpli", cmp_imm(10, True), False),
+    ]
+
+    for name, (xo, op, slot) in PS_ARITH.items():
+        out.append((name, ps_arith(xo, op, slot), True))
+
+    for name, (xo, op) in PS_MOVE.items():
+        out.append((name, ps_move(xo, op), True))
+
+    out += [
+        ("ps_sel", ps_sel, True),
+        ("ps_cmpu0", ps_cmpu(0, 0), True),
+        ("ps_cmpu1", ps_cmpu(64, 1), True),
+    ]
+
+    return out
+
+
+def write_file(path, length, cases):
+    with open(path, "wb") as file:
+        file.write(struct.pack("<H", length))
+        for words, initial, expected in cases:
+            file.write(struct.pack(f"<{length}I", *words))
+            file.write(initial.pack())
+            file.write(expected.pack())
+
+
+def generate_case(rng, generators, floats):
+    initial = rand_state(rng, floats)
+    words, runs = [], []
+    for generator in generators:
+        word, run = generator(rng)
+        words.append(word)
+        runs.append(run)
+
+        # shifts are more interesting with small amounts
+        if getattr(generator, "shift_operand", False):
+            rand_shift_amount(rng, initial, (word >> 11) & 0x1F)
+
+    expected = initial.copy()
+    for run in runs:
+        run(expected)
+
+    return words, initial, expected
+
+
+def main():
+    out_dir = sys.argv[1] if len(sys.argv) > 1 else os.path.join(os.path.dirname(__file__), "tests")
+    os.makedirs(out_dir, exist_ok=True)
+
+    rng = random.Random(SEED)
+    all_suites = suites()
+    total = 0
+    for name, generator, floats in all_suites:
+        cases = [generate_case(rng, [generator], floats) for _ in range(CASES_PER_FILE)]
+        write_file(os.path.join(out_dir, f"{name}.bin"), 1, cases)
+        total += len(cases)
+
+    integer = [generator for _, generator, floats in all_suites if not floats]
+    cases = []
+    for _ in range(MIXED_CASES):
+        generators = [rng.choice(integer) for _ in range(MIXED_LENGTH)]
+        cases.append(generate_case(rng, generators, False))
+    write_file(os.path.join(out_dir, "mixed.bin"), MIXED_LENGTH, cases)
+    total += len(cases)
+
+    print(f"generated {total} cases in {len(all_suites) + 1} files")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,272 @@
+//! CPU conformance tests. Every case sets up the user level registers, runs a few instructions
+//! through a CPU core and compares the resulting registers with the expected ones. The corpus in
+//! `tests` is generated by `generate.py`, which also describes the file format.
+#![feature(trim_prefix_suffix)]
+
+mod file;
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use cores::cpu::jit;
+use lazuli::cores::CpuCore;
+use lazuli::gekko::MachineState;
+use lazuli::gekko::disasm::{Extensions, Ins, ParsedIns};
+use lazuli::modules::audio::NopAudioModule;
+use lazuli::modules::debug::NopDebugModule;
+use lazuli::modules::disk::NopDiskModule;
+use lazuli::modules::input::NopInputModule;
+use lazuli::modules::render::NopRenderModule;
+use lazuli::modules::vertex::NopVertexModule;
+use lazuli::system::{self, Modules, System};
+use lazuli::{Address, Cycles};
+use libtest_mimic::{Arguments, Failed, Trial};
+
+use crate::file::State;
+
+/// Physical address the code of a case is placed at. Address translation is disabled.
+const CODE_START: Address = Address(0x0000_3000);
+/// Cycles a case may take. Cases which don't reach the end of their code by then (e.g. because
+/// they raised an exception) fail.
+const MAX_CYCLES: Cycles = Cycles(1024);
+
+/// Creates the CPU core to test, as selected by the `CORE` environment variable (`jit` by
+/// default).
+fn core(cache_path: PathBuf) -> Box<dyn CpuCore> {
+    match std::env::var("CORE").as_deref() {
+        Ok("jit") | Err(_) => Box::new(jit::Core::new(jit::Config {
+            instr_per_block: 64,
+            jit_settings: jit::ppcjit::Settings {
+                compiler: jit::ppcjit::CompilerSettings {
+                    ignore_unimplemented: true,
+                    round_to_single: true,
+                    float_exceptions: true,
+                    ..Default::default()
+                },
+                cache_path: Some(cache_path),
+            },
+        })),
+        Ok(other) => panic!("unknown core {other}"),
+    }
+}
+
+fn disasm(code: &[u32]) -> String {
+    let mut disasm = String::new();
+    for (i, &code) in code.iter().enumerate() {
+        let mut parsed = ParsedIns::new();
+        Ins::new(code, Extensions::gekko_broadway()).parse_basic(&mut parsed);
+        writeln!(
+            &mut disasm,
+            "{} {code:08X} {parsed}",
+            CODE_START + 4 * i as u32
+        )
+        .unwrap();
+    }
+
+    disasm
+}
+
+fn divergences(value: &State, expected: &State) -> Vec<String> {
+    let mut divergences = vec![];
+    for (i, (v, e)) in value.gpr.iter().zip(expected.gpr).enumerate() {
+        if *v != e {
+            divergences.push(format!("r{i}(v={v:08X}, e={e:08X})"));
+        }
+    }
+
+    for (i, (v, e)) in value.fpr.iter().zip(expected.fpr).enumerate() {
+        for (slot, (v, e)) in v.iter().zip(e).enumerate() {
+            if *v != e {
+                let (v, e) = (f64::from_bits(*v), f64::from_bits(e));
+                divergences.push(format!("f{i}.ps{slot}(v={v:?}, e={e:?})"));
+            }
+        }
+    }
+
+    for (name, v, e) in [
+        ("CR", value.cr, expected.cr),
+        ("XER", value.xer, expected.xer),
+        ("FPSCR", value.fpscr, expected.fpscr),
+    ] {
+        if v != e {
+            divergences.push(format!("{name}(v={v:08X}, e={e:08X})"));
+        }
+    }
+
+    divergences
+}
+
+struct FailedCase {
+    code: Vec<u32>,
+    initial: State,
+    expected: State,
+    divergences: Vec<String>,
+}
+
+fn run_case(
+    sys: &mut System,
+    core: &mut dyn CpuCore,
+    case: file::TestCase,
+) -> Result<(), FailedCase> {
+    // setup
+    core.reset();
+    sys.cpu.supervisor.config.msr = MachineState::default()
+        .with_instr_addr_translation(false)
+        .with_data_addr_translation(false)
+        .with_float_available(true);
+
+    case.initial.apply(&mut sys.cpu.user);
+    for (i, &ins) in case.instructions.iter().enumerate() {
+        sys.write_phys_slow(CODE_START + 4 * i as u32, ins);
+    }
+
+    // run until the end of the code
+    let end = CODE_START + 4 * case.instructions.len() as u32;
+    sys.cpu.pc = CODE_START;
+    core.exec(sys, MAX_CYCLES, &[end]);
+
+    // check
+    let state = State::from_user(&sys.cpu.user);
+    let mut divergences = divergences(&state, &case.expected);
+    if sys.cpu.pc != end {
+        divergences.insert(0, format!("PC(v={}, e={end})", sys.cpu.pc));
+    }
+
+    if !divergences.is_empty() {
+        return Err(FailedCase {
+            code: case.instructions,
+            initial: case.initial,
+            expected: case.expected,
+            divergences,
+        });
+    }
+
+    Ok(())
+}
+
+fn run_test(name: &str, file: file::TestFile, quiet: bool) -> Result<(), Failed> {
+    let early_exit = std::env::var("EARLY_EXIT").is_ok();
+    let total = file.cases.len();
+    let mut failures = vec![];
+
+    let modules = Modules {
+        audio: Box::new(NopAudioModule),
+        debug: Box::new(NopDebugModule),
+        disk: Box::new(NopDiskModule),
+        input: Box::new(NopInputModule),
+        render: Box::new(NopRenderModule),
+        vertex: Box::new(NopVertexModule),
+    };
+
+    let mut system = System::new(
+        modules,
+        system::Config {
+            ipl: None,
+            sideload: None,
+            boot: system::BootMode::Ipl,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        },
+    )
+    .unwrap();
+
+    let cache_path =
+        std::env::temp_dir().join(format!("gekko-tests-{}-{name}", std::process::id()));
+    let mut core = core(cache_path.clone());
+
+    for (i, case) in file.cases.into_iter().enumerate() {
+        let Err(failure) = run_case(&mut system, core.as_mut(), case) else {
+            continue;
+        };
+
+        let disasm = disasm(&failure.code);
+        let divergences = failure.divergences.join(", ");
+        if early_exit {
+            failures.push(format!(
+                "Case {i} failed:\r\nINITIAL: {:08X?}\r\nEXPECTED: {:08X?}\r\nDIVERGENCES: {}\r\nCODE:\r\n{disasm}",
+                failure.initial, failure.expected, divergences,
+            ));
+            break;
+        } else {
+            failures.push(format!("Case {i} failed: {divergences}\r\n{disasm}"));
+        }
+    }
+
+    drop(core);
+    _ = std::fs::remove_dir_all(cache_path);
+
+    if !failures.is_empty() {
+        if quiet {
+            return Err(Failed::from(format!(
+                "Failed a total of {} cases (out of {})",
+                failures.len(),
+                total
+            )));
+        }
+
+        let mut msg = format!(
+            "Failed a total of {} cases (out of {})\r\n\r\n",
+            failures.len(),
+            total
+        );
+        let tests_to_show = 8;
+
+        let show = failures.iter().take(tests_to_show);
+        for failure in show {
+            writeln!(&mut msg, "{}", failure).unwrap();
+        }
+
+        if failures.len() > tests_to_show {
+            writeln!(
+                &mut msg,
+                "... and {} others",
+                failures.len() - tests_to_show
+            )
+            .unwrap();
+        }
+
+        return Err(Failed::from(msg));
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let manifest = env!("CARGO_MANIFEST_DIR");
+    let tests_dir = std::fs::read_dir(format!("{manifest}/gekko-tests/tests")).unwrap();
+    let args = Arguments::from_args();
+    let env_quiet = std::env::var("QUIET").is_ok();
+
+    let mut tests = vec![];
+    for test in tests_dir {
+        let test = test.unwrap();
+        if test.file_type().unwrap().is_file() {
+            let file = file::TestFile::open(test.path());
+            let name = test.file_name().to_string_lossy().into_owned();
+            let stem = name.trim_suffix(".bin").to_owned();
+            tests.push(Trial::test(name, move || {
+                let result = std::panic::catch_unwind(move || {
+                    run_test(&stem, file, args.quiet || env_quiet)
+                });
+
+                match result {
+                    Ok(r) => r,
+                    Err(e) => {
+                        let mut msg = "<unknown panic>".to_owned();
+                        if let Some(s) = e.downcast_ref::<String>() {
+                            msg = s.clone();
+                        } else if let Some(s) = e.downcast_ref::<&'static str>() {
+                            msg = (*s).to_owned();
+                        }
+
+                        Err(Failed::from(msg))
+                    }
+                }
+            }));
+        }
+    }
+
+    std::panic::set_hook(Box::new(move |_| ()));
+    libtest_mimic::run(&args, tests).exit();
+}
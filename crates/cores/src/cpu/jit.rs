@@ -1,14 +1,20 @@
 mod table;
 
 use indexmap::IndexSet;
-use lazuli::cores::{CpuCore, Executed};
+use lazuli::cores::{
+    BlockStats, BlockTrace, CpuCore, Executed, SkippedInstruction, SkippedInstructions,
+};
 use lazuli::gekko::disasm::{Extensions, Ins};
-use lazuli::gekko::{self, Cpu, DEQUANTIZATION_LUT, QUANTIZATION_LUT, QuantReg, QuantizedType};
+use lazuli::gekko::{
+    self, Cpu, DEQUANTIZATION_LUT, Exception, QUANTIZATION_LUT, QuantReg, QuantizedType,
+};
+use lazuli::system::mem::FastmemLuts;
+use lazuli::system::mmu::Fault;
 use lazuli::system::{self, System};
 use lazuli::{Address, Cycles, Primitive};
+use ppcjit::Block;
 use ppcjit::block::{BlockFn, Info, LinkData, Pattern};
 use ppcjit::hooks::*;
-use ppcjit::{Block, FastmemLut};
 use table::Table;
 
 #[rustfmt::skip]
@@ -85,6 +91,7 @@ pub struct Blocks {
     logical_deps: DepsTable,
     physical_deps: DepsTable,
     temp_deps: IndexSet<Address>,
+    stats: BlockStats,
 }
 
 impl Default for Blocks {
@@ -96,6 +103,7 @@ impl Default for Blocks {
             logical_deps: Default::default(),
             physical_deps: Default::default(),
             temp_deps: IndexSet::new(),
+            stats: BlockStats::default(),
         }
     }
 }
@@ -200,6 +208,24 @@ impl Blocks {
         self.storage.get(self.get_mapping(logical, addr)?.id.0)
     }
 
+    /// Links `link_data` to the block mapped to `addr`, if any. The link is undone once that block
+    /// is invalidated.
+    pub fn link(&mut self, logical: bool, addr: Address, link_data: &mut Option<LinkData>) {
+        let Some(mapping) = self.get_mapping(logical, addr) else {
+            self.stats.link_misses += 1;
+            return;
+        };
+
+        let stored = &mut self.storage[mapping.id.0];
+        *link_data = Some(LinkData {
+            block: stored.inner.as_ptr(),
+            pattern: stored.inner.meta().pattern,
+        });
+
+        stored.links.push(&raw mut *link_data);
+        self.stats.links += 1;
+    }
+
     /// Invalidate mappings that contain `addr`.
     pub fn invalidate(&mut self, logical: bool, target: Address) {
         let deps = if logical {
@@ -239,6 +265,7 @@ impl Blocks {
             };
 
             let block = &mut self.storage[mapping.id.0];
+            self.stats.unlinks += block.links.len() as u64;
             for link in block.links.drain(..) {
                 let link = unsafe { link.as_mut().unwrap() };
                 *link = None;
@@ -249,6 +276,11 @@ impl Blocks {
         self.temp_deps = temp_deps;
     }
 
+    /// Lookup and linking statistics.
+    pub fn stats(&self) -> BlockStats {
+        self.stats
+    }
+
     /// Clears all mappings.
     pub fn clear(&mut self) {
         self.logical_mappings = Table::new();
@@ -270,6 +302,8 @@ struct Context<'a> {
     sys: &'a mut System,
     /// The block mapping, so that write operations can invalidate blocks.
     blocks: &'a mut Blocks,
+    /// Unimplemented instructions skipped so far.
+    skipped: &'a mut SkippedInstructions,
     /// Amount of cycles we are trying to execute.
     target_cycles: u32,
     /// Maximum instructions we should execute.
@@ -287,7 +321,7 @@ const CTX_HOOKS: Hooks = {
         &mut ctx.sys.cpu
     }
 
-    extern "sysv64-unwind" fn get_fastmem<'a>(ctx: &'a mut Context) -> &'a FastmemLut {
+    extern "sysv64-unwind" fn get_fastmem<'a>(ctx: &'a mut Context) -> &'a FastmemLuts {
         if ctx.sys.cpu.supervisor.config.msr.data_addr_translation() {
             ctx.sys.mem.data_fastmem_lut_logical()
         } else {
@@ -300,8 +334,10 @@ const CTX_HOOKS: Hooks = {
         ctx: &mut Context,
         link_data: &mut Option<LinkData>,
     ) -> bool {
-        // if we have reached cycle or instruction limit, don't follow links, just exit.
+        // if we have reached cycle or instruction limit, or the DSP has been kicked, don't follow
+        // links, just exit.
         if ctx.force_no_link
+            || ctx.sys.dsp.kick
             || info.cycles >= ctx.target_cycles
             || info.instructions >= ctx.max_instructions
         {
@@ -338,14 +374,27 @@ const CTX_HOOKS: Hooks = {
     ) {
         debug_assert!(link_data.is_none());
         let logical = ctx.sys.cpu.supervisor.config.msr.instr_addr_translation();
-        if let Some(mapping) = ctx.blocks.get_mapping(logical, addr) {
-            let stored = ctx.blocks.storage.get_mut(mapping.id.0).unwrap();
-            *link_data = Some(LinkData {
-                block: stored.inner.as_ptr(),
-                pattern: stored.inner.meta().pattern,
-            });
-
-            stored.links.push(&raw mut *link_data);
+        ctx.blocks.link(logical, addr, link_data);
+    }
+
+    /// Records a data access fault in DSISR. The DSI exception itself is raised by the block.
+    fn data_fault(ctx: &mut Context, addr: Address, fault: Fault, write: bool) {
+        tracing::debug!(pc = ?ctx.sys.cpu.pc, ?fault, "failed to translate address {addr}");
+        ctx.sys.cpu.supervisor.exception.dsisr = fault.dsisr(write);
+    }
+
+    fn read_any<P: Primitive>(sys: &mut System, addr: Address) -> Result<P, Fault> {
+        match sys.read_fast(addr) {
+            Some(value) => Ok(value),
+            None => sys.read_slow(addr),
+        }
+    }
+
+    fn write_any<P: Primitive>(sys: &mut System, addr: Address, value: P) -> Result<(), Fault> {
+        if sys.write_fast(addr, value) {
+            Ok(())
+        } else {
+            sys.write_slow(addr, value)
         }
     }
 
@@ -354,13 +403,16 @@ const CTX_HOOKS: Hooks = {
         addr: Address,
         value: &mut P,
     ) -> bool {
-        if let Some(read) = ctx.sys.read_slow(addr) {
-            *value = read;
-            true
-        } else {
-            std::hint::cold_path();
-            tracing::error!(pc = ?ctx.sys.cpu.pc, "failed to translate address {addr}");
-            false
+        match ctx.sys.read_slow(addr) {
+            Ok(read) => {
+                *value = read;
+                true
+            }
+            Err(fault) => {
+                std::hint::cold_path();
+                data_fault(ctx, addr, fault, false);
+                false
+            }
         }
     }
 
@@ -369,12 +421,13 @@ const CTX_HOOKS: Hooks = {
         addr: Address,
         value: P,
     ) -> bool {
-        if ctx.sys.write_slow(addr, value) {
-            true
-        } else {
-            std::hint::cold_path();
-            tracing::error!(pc = ?ctx.sys.cpu.pc, "failed to translate address {addr}");
-            false
+        match ctx.sys.write_slow(addr, value) {
+            Ok(()) => true,
+            Err(fault) => {
+                std::hint::cold_path();
+                data_fault(ctx, addr, fault, true);
+                false
+            }
         }
     }
 
@@ -392,17 +445,20 @@ const CTX_HOOKS: Hooks = {
         };
 
         let read = match ty {
-            QuantizedType::U8 => ctx.sys.read::<u8>(addr).map(|x| x as f64),
-            QuantizedType::U16 => ctx.sys.read::<u16>(addr).map(|x| x as f64),
-            QuantizedType::I8 => ctx.sys.read::<i8>(addr).map(|x| x as f64),
-            QuantizedType::I16 => ctx.sys.read::<i16>(addr).map(|x| x as f64),
-            _ => ctx.sys.read::<u32>(addr).map(|x| f32::from_bits(x) as f64),
+            QuantizedType::U8 => read_any::<u8>(ctx.sys, addr).map(|x| x as f64),
+            QuantizedType::U16 => read_any::<u16>(ctx.sys, addr).map(|x| x as f64),
+            QuantizedType::I8 => read_any::<i8>(ctx.sys, addr).map(|x| x as f64),
+            QuantizedType::I16 => read_any::<i16>(ctx.sys, addr).map(|x| x as f64),
+            _ => read_any::<u32>(ctx.sys, addr).map(|x| f32::from_bits(x) as f64),
         };
 
-        let Some(read) = read else {
-            std::hint::cold_path();
-            tracing::error!("failed to translate address {addr}");
-            return 0;
+        let read = match read {
+            Ok(read) => read,
+            Err(fault) => {
+                std::hint::cold_path();
+                data_fault(ctx, addr, fault, false);
+                return 0;
+            }
         };
 
         let scaled = read * DEQUANTIZATION_LUT[(scale as usize) & 0b0011_1111];
@@ -425,17 +481,17 @@ const CTX_HOOKS: Hooks = {
         };
 
         let scaled = value * QUANTIZATION_LUT[(scale as usize) & 0b0011_1111];
-        let success = match ty {
-            QuantizedType::U8 => ctx.sys.write(addr, scaled as u8),
-            QuantizedType::U16 => ctx.sys.write(addr, scaled as u16),
-            QuantizedType::I8 => ctx.sys.write(addr, scaled as i8),
-            QuantizedType::I16 => ctx.sys.write(addr, scaled as i16),
-            _ => ctx.sys.write(addr, (scaled as f32).to_bits()),
+        let result = match ty {
+            QuantizedType::U8 => write_any(ctx.sys, addr, scaled as u8),
+            QuantizedType::U16 => write_any(ctx.sys, addr, scaled as u16),
+            QuantizedType::I8 => write_any(ctx.sys, addr, scaled as i8),
+            QuantizedType::I16 => write_any(ctx.sys, addr, scaled as i16),
+            _ => write_any(ctx.sys, addr, (scaled as f32).to_bits()),
         };
 
-        if !success {
+        if let Err(fault) = result {
             std::hint::cold_path();
-            tracing::error!("failed to translate address {addr}");
+            data_fault(ctx, addr, fault, true);
             return 0;
         }
 
@@ -464,20 +520,18 @@ const CTX_HOOKS: Hooks = {
         let dma = ctx.sys.cpu.supervisor.config.dma.clone();
 
         if dma.lower.trigger() {
-            let regions = ctx.sys.mem.regions();
-            let ram =
-                &mut regions.ram[dma.mem_address().value() as usize..][..dma.length() as usize];
-            let l2c = &mut regions.l2c[dma.cache_address().value() as usize - 0xE000_0000..]
-                [..dma.length() as usize];
+            let ram = dma.mem_address().value() as usize;
+            let l2c = dma.cache_address().value() as usize - 0xE000_0000;
+            let len = dma.length() as usize;
 
             debug_assert!(dma.length() <= 4096);
 
             match dma.lower.direction() {
                 gekko::DmaDirection::FromCacheToRam => {
-                    ram.copy_from_slice(l2c);
+                    ctx.sys.mem.copy_l2c_to_ram(l2c, ram, len);
                 }
                 gekko::DmaDirection::FromRamToCache => {
-                    l2c.copy_from_slice(ram);
+                    ctx.sys.mem.copy_ram_to_l2c(ram, l2c, len);
                 }
             }
         }
@@ -505,6 +559,22 @@ const CTX_HOOKS: Hooks = {
             .build_data_bat_lut(&ctx.sys.cpu.supervisor.memory.dbat);
     }
 
+    extern "sysv64-unwind" fn invalidate_tlb(ctx: &mut Context, addr: Address) {
+        ctx.sys.tlb.invalidate(addr);
+
+        // blocks in the page might now be mapped elsewhere
+        let page = Address(addr.value() & !0xFFF);
+        for offset in (0..4096).step_by(4) {
+            ctx.blocks.invalidate(true, page + offset);
+        }
+    }
+
+    extern "sysv64-unwind" fn page_table_changed(ctx: &mut Context) {
+        tracing::info!("page table changed - clearing blocks mapping and tlb");
+        ctx.blocks.clear();
+        ctx.sys.tlb.invalidate_all();
+    }
+
     extern "sysv64-unwind" fn dec_read(ctx: &mut Context) {
         ctx.sys.update_decrementer();
     }
@@ -521,6 +591,10 @@ const CTX_HOOKS: Hooks = {
             .schedule(dec as u64, System::decrementer_overflow);
     }
 
+    extern "sysv64-unwind" fn unimplemented(ctx: &mut Context, code: u32, pc: Address) {
+        ctx.skipped.record(code, pc);
+    }
+
     extern "sysv64-unwind" fn tb_read(ctx: &mut Context) {
         ctx.sys.update_time_base();
     }
@@ -580,12 +654,20 @@ const CTX_HOOKS: Hooks = {
         let dbat_changed =
             transmute::<_, GenericHook>(dbat_changed as extern "sysv64-unwind" fn(_));
 
+        let invalidate_tlb =
+            transmute::<_, InvalidateTlb>(invalidate_tlb as extern "sysv64-unwind" fn(_, _));
+        let page_table_changed =
+            transmute::<_, GenericHook>(page_table_changed as extern "sysv64-unwind" fn(_));
+
         let tb_read = transmute::<_, GenericHook>(tb_read as extern "sysv64-unwind" fn(_));
         let tb_changed = transmute::<_, GenericHook>(tb_changed as extern "sysv64-unwind" fn(_));
 
         let dec_read = transmute::<_, GenericHook>(dec_read as extern "sysv64-unwind" fn(_));
         let dec_changed = transmute::<_, GenericHook>(dec_changed as extern "sysv64-unwind" fn(_));
 
+        let unimplemented =
+            transmute::<_, UnimplementedHook>(unimplemented as extern "sysv64-unwind" fn(_, _, _));
+
         Hooks {
             get_registers,
             get_fastmem,
@@ -612,11 +694,16 @@ const CTX_HOOKS: Hooks = {
             ibat_changed,
             dbat_changed,
 
+            invalidate_tlb,
+            page_table_changed,
+
             tb_read,
             tb_changed,
 
             dec_read,
             dec_changed,
+
+            unimplemented,
         }
     }
 };
@@ -633,6 +720,9 @@ pub struct Core {
     pub config: Config,
     pub compiler: ppcjit::Jit,
     pub blocks: Blocks,
+    pub skipped: SkippedInstructions,
+    /// Blocks executed so far, if tracing is enabled.
+    pub trace: Option<BlockTrace>,
 }
 
 fn closest_breakpoint(pc: Address, breakpoints: &[Address]) -> Address {
@@ -652,6 +742,21 @@ fn closest_breakpoint(pc: Address, breakpoints: &[Address]) -> Address {
     closest_breakpoint
 }
 
+/// Checks whether the instruction at the current PC can be fetched and, if it can't, raises an ISI
+/// exception.
+fn check_fetch(sys: &mut System) -> bool {
+    match sys.translate_instr_access(sys.cpu.pc) {
+        Ok(_) => true,
+        Err(fault) => {
+            std::hint::cold_path();
+            tracing::debug!(pc = ?sys.cpu.pc, ?fault, "failed to translate instruction address");
+            sys.cpu.raise_exception(Exception::ISI);
+            sys.cpu.supervisor.exception.srr[1] |= fault.isi_srr1();
+            false
+        }
+    }
+}
+
 impl Core {
     pub fn new(config: Config) -> Self {
         let compiler = ppcjit::Jit::new(config.jit_settings.clone(), CTX_HOOKS);
@@ -660,12 +765,15 @@ impl Core {
             config,
             compiler,
             blocks: Blocks::default(),
+            skipped: SkippedInstructions::default(),
+            trace: None,
         }
     }
 
     /// Compiles a sequence of at most `limit` instructions starting at `addr` into a JIT block.
     fn compile(&mut self, sys: &mut System, addr: Address, limit: u32) -> ppcjit::Block {
         let _span = tracing::trace_span!("compiling new block", addr = ?sys.cpu.pc).entered();
+        profiling::scope!("compile block");
 
         let mut count = 0;
         let instructions = std::iter::from_fn(|| {
@@ -713,11 +821,18 @@ impl Core {
             .get(logical, sys.cpu.pc)
             .filter(|b| b.inner.meta().seq.len() <= max_instructions as usize);
 
+        // while tracing, every block goes through here so that its address can be recorded
+        let force_no_link = force_no_link || self.trace.is_some();
+        let start = sys.cpu.pc;
+
         let compiled: ppcjit::Block;
         let block = match stored {
             Some(stored) => stored.inner.as_ptr(),
             None => {
                 std::hint::cold_path();
+                if !check_fetch(sys) {
+                    return Executed::default();
+                }
 
                 compiled = self.compile(sys, sys.cpu.pc, max_instructions);
                 compiled.as_ptr()
@@ -727,6 +842,7 @@ impl Core {
         let mut ctx = Context {
             sys,
             blocks: &mut self.blocks,
+            skipped: &mut self.skipped,
             target_cycles,
             max_instructions,
             force_no_link,
@@ -740,17 +856,23 @@ impl Core {
                 .call(&raw mut ctx as *mut ppcjit::hooks::Context, block)
         };
 
-        let cycles = if ctx.exit_reason == ExitReason::IdleLooping {
+        let idle = ctx.exit_reason == ExitReason::IdleLooping;
+        let cycles = if idle {
             std::hint::cold_path();
             Cycles(target_cycles as u64)
         } else {
             Cycles(info.cycles as u64)
         };
 
+        if let Some(trace) = &mut self.trace {
+            trace.record(start, info.instructions);
+        }
+
         Executed {
             instructions: info.instructions,
             cycles,
-            hit_breakpoint: false,
+            idle,
+            ..Default::default()
         }
     }
 
@@ -767,7 +889,14 @@ impl Core {
             .get(logical, sys.cpu.pc)
             .filter(|b| b.inner.meta().seq.len() <= max_instructions as usize);
 
-        if block.is_none() {
+        if block.is_some() {
+            self.blocks.stats.lookup_hits += 1;
+        } else {
+            self.blocks.stats.lookup_misses += 1;
+            if !check_fetch(sys) {
+                return Executed::default();
+            }
+
             // avoid trying to compile unimplemented instructions in debug mode
             let instructions = if cfg!(debug_assertions) {
                 self.config.instr_per_block.min(max_instructions)
@@ -788,6 +917,7 @@ impl Core {
         cycles: Cycles,
         breakpoints: &[Address],
     ) -> Executed {
+        profiling::scope!("execute blocks");
         let mut executed = Executed::default();
         while executed.cycles < cycles {
             // detect mailbox idle loop
@@ -805,6 +935,7 @@ impl Core {
                     std::hint::cold_path();
                     executed.cycles = cycles;
                     executed.instructions = 1;
+                    executed.idle = true;
                     break;
                 }
             }
@@ -821,11 +952,17 @@ impl Core {
             let e = self.cached_exec(sys, target_cycles.0 as u32, max_instructions, BREAKPOINTS);
             executed.instructions += e.instructions;
             executed.cycles += e.cycles;
+            executed.idle = e.idle;
 
             if BREAKPOINTS && breakpoints.contains(&sys.cpu.pc) {
                 executed.hit_breakpoint = true;
                 break;
             }
+
+            // let the DSP see what was written to it
+            if sys.dsp.kick {
+                break;
+            }
         }
 
         executed
@@ -844,4 +981,103 @@ impl CpuCore for Core {
     fn step(&mut self, sys: &mut System) -> Executed {
         self.uncached_exec(sys, u32::MAX, 1, true)
     }
+
+    fn reset(&mut self) {
+        tracing::info!("dropping all compiled blocks");
+        self.blocks = Blocks::default();
+    }
+
+    fn skipped_instructions(&self) -> &[SkippedInstruction] {
+        self.skipped.entries()
+    }
+
+    fn set_block_tracing(&mut self, enabled: bool) {
+        if !enabled {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(BlockTrace::default());
+        }
+    }
+
+    fn take_block_trace(&mut self) -> BlockTrace {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn block_stats(&self) -> Option<BlockStats> {
+        Some(self.blocks.stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Compiles a block of `len` nops.
+    fn block(jit: &mut ppcjit::Jit, len: usize) -> Block {
+        let nop = Ins::new(0x6000_0000, Extensions::gekko_broadway());
+        jit.build(std::iter::repeat_n(nop, len)).unwrap()
+    }
+
+    #[test]
+    fn invalidation_removes_containing_blocks() {
+        let mut jit = ppcjit::Jit::new(ppcjit::Settings::default(), CTX_HOOKS);
+        let mut blocks = Blocks::default();
+
+        let first = Address(0x8000_3000);
+        let second = Address(0x8000_5000);
+        blocks.insert(true, first, block(&mut jit, 4));
+        blocks.insert(true, second, block(&mut jit, 4));
+
+        // a different address space is untouched
+        blocks.invalidate(false, first + 8u32);
+        assert!(blocks.get_mapping(true, first).is_some());
+
+        blocks.invalidate(true, first + 8u32);
+        assert!(blocks.get_mapping(true, first).is_none());
+        assert!(blocks.get_mapping(true, second).is_some());
+
+        // invalidating again is a no-op
+        blocks.invalidate(true, first + 8u32);
+        assert!(blocks.get_mapping(true, second).is_some());
+    }
+
+    #[test]
+    fn invalidation_unlinks() {
+        let mut jit = ppcjit::Jit::new(ppcjit::Settings::default(), CTX_HOOKS);
+        let mut blocks = Blocks::default();
+
+        let target = Address(0x8000_3000);
+        let other = Address(0x8000_5000);
+        blocks.insert(true, target, block(&mut jit, 4));
+        blocks.insert(true, other, block(&mut jit, 4));
+
+        // link slots live in the code of the linking blocks, so they must not move
+        let mut slots: Vec<Box<Option<LinkData>>> = (0..4).map(|_| Box::new(None)).collect();
+        blocks.link(true, target, &mut slots[0]);
+        blocks.link(true, target, &mut slots[1]);
+        blocks.link(true, other, &mut slots[2]);
+        blocks.link(true, Address(0x8000_7000), &mut slots[3]);
+
+        let expected = blocks.get(true, target).unwrap().inner.as_ptr();
+        assert_eq!((*slots[0]).as_ref().map(|link| link.block), Some(expected));
+        assert!(slots[3].is_none());
+
+        let stats = blocks.stats();
+        assert_eq!((stats.links, stats.link_misses, stats.unlinks), (3, 1, 0));
+
+        blocks.invalidate(true, target);
+        assert!(slots[0].is_none() && slots[1].is_none());
+        assert!(slots[2].is_some());
+        assert_eq!(blocks.stats().unlinks, 2);
+
+        // recompiling the target allows linking to it again
+        blocks.insert(true, target, block(&mut jit, 2));
+        blocks.link(true, target, &mut slots[0]);
+        assert!(slots[0].is_some());
+
+        blocks.invalidate(true, other + 4u32);
+        assert!(slots[2].is_none());
+        assert!(slots[0].is_some());
+        assert_eq!(blocks.stats().unlinks, 3);
+    }
 }
@@ -1,4 +1,30 @@
 pub mod interpreter;
+pub mod threaded;
+
+use dspint::Interpreter;
+use easyerr::Error;
+use lazuli::cores::DspMemory;
+
+/// Length of the instruction ROM, in words.
+const IROM_WORDS: usize = 4096;
+/// Length of the coefficient ROM, in words.
+const COEF_WORDS: usize = 2048;
+
+/// Adler-32 checksums of known dumps of the instruction ROM.
+const KNOWN_IROMS: [u32; 2] = [
+    // official
+    0x66F3_34FE,
+    // free replacement, bundled
+    0xB649_B5A5,
+];
+
+/// Adler-32 checksums of known dumps of the coefficient ROM.
+const KNOWN_COEFS: [u32; 2] = [
+    // official
+    0xF3B9_3527,
+    // free replacement, bundled
+    0xFDE3_75F5,
+];
 
 const fn convert_to_dsp_words<const N: usize>(bytes: &[u8]) -> [u16; N] {
     assert!(bytes.len() / 2 == N);
@@ -17,12 +43,185 @@ const fn convert_to_dsp_words<const N: usize>(bytes: &[u8]) -> [u16; N] {
     result
 }
 
-pub static DSP_ROM: [u16; 4096] = convert_to_dsp_words(include_bytes!(concat!(
+pub static DSP_ROM: [u16; IROM_WORDS] = convert_to_dsp_words(include_bytes!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/../../resources/dsp_rom.bin"
 )));
 
-pub static DSP_COEF: [u16; 2048] = convert_to_dsp_words(include_bytes!(concat!(
+pub static DSP_COEF: [u16; COEF_WORDS] = convert_to_dsp_words(include_bytes!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/../../resources/dsp_coef.bin"
 )));
+
+#[derive(Debug, Error)]
+pub enum RomError {
+    #[error("{what} dump is {found} bytes long, but should be {expected} bytes long")]
+    Size {
+        what: &'static str,
+        found: usize,
+        expected: usize,
+    },
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+
+    let (mut a, mut b) = (1, 0);
+    for &byte in data {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+
+    b << 16 | a
+}
+
+/// Converts a dump of a ROM into words, after checking its length. Dumps which aren't known are
+/// still used, but warned about.
+fn load_dump<const N: usize>(
+    what: &'static str,
+    known: &[u32],
+    dump: &[u8],
+    words: &mut [u16; N],
+) -> Result<(), RomError> {
+    if dump.len() != 2 * N {
+        return Err(RomError::Size {
+            what,
+            found: dump.len(),
+            expected: 2 * N,
+        });
+    }
+
+    let checksum = adler32(dump);
+    if known.contains(&checksum) {
+        tracing::info!("loaded {what} dump with checksum 0x{checksum:08X}");
+    } else {
+        tracing::warn!("{what} dump has unknown checksum 0x{checksum:08X}, ucodes might misbehave");
+    }
+
+    for (word, bytes) in words.iter_mut().zip(dump.chunks_exact(2)) {
+        *word = u16::from_be_bytes([bytes[0], bytes[1]]);
+    }
+
+    Ok(())
+}
+
+/// Contents of the DSP instruction ROM (mapped at `0x8000` in instruction memory) and coefficient
+/// ROM (mapped at `0x1000` in data memory).
+#[derive(Clone)]
+pub struct Rom {
+    pub irom: Box<[u16; IROM_WORDS]>,
+    pub coef: Box<[u16; COEF_WORDS]>,
+}
+
+impl Default for Rom {
+    /// The free replacement ROMs bundled with the emulator. They implement the bootstrap mail
+    /// handshake and the helper functions ucodes call at the same entry points as the official
+    /// ones, so they stand in for them when no dumps are given.
+    fn default() -> Self {
+        Self {
+            irom: Box::new(DSP_ROM),
+            coef: Box::new(DSP_COEF),
+        }
+    }
+}
+
+impl Rom {
+    /// Replaces the instruction ROM with the given dump (usually `dsp_rom.bin`), which must be
+    /// 8 KiB long.
+    pub fn load_irom(&mut self, dump: &[u8]) -> Result<(), RomError> {
+        load_dump("DSP instruction ROM", &KNOWN_IROMS, dump, &mut self.irom)
+    }
+
+    /// Replaces the coefficient ROM with the given dump (usually `dsp_coef.bin`), which must be
+    /// 4 KiB long.
+    pub fn load_coef(&mut self, dump: &[u8]) -> Result<(), RomError> {
+        load_dump("DSP coefficient ROM", &KNOWN_COEFS, dump, &mut self.coef)
+    }
+}
+
+/// Creates an interpreter with the given ROMs loaded.
+fn interpreter_with_rom(rom: &Rom) -> Interpreter {
+    let mut interpreter = Interpreter::default();
+    interpreter.mem.irom.copy_from_slice(&rom.irom[..]);
+    interpreter.mem.coef.copy_from_slice(&rom.coef[..]);
+
+    interpreter
+}
+
+/// A view of the memory of an interpreter.
+fn memory_of(interpreter: &Interpreter) -> DspMemory<'_> {
+    let mem = &interpreter.mem;
+    DspMemory {
+        iram: &mem.iram[..],
+        irom: &mem.irom[..],
+        dram: &mem.dram[..],
+        coef: &mem.coef[..],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lazuli::system::dspi::{DspIo, Mailbox};
+
+    use super::*;
+
+    #[test]
+    fn bundled_rom_is_known() {
+        assert!(KNOWN_IROMS.contains(&adler32(DSP_ROM.map(u16::to_be_bytes).as_flattened())));
+        assert!(KNOWN_COEFS.contains(&adler32(DSP_COEF.map(u16::to_be_bytes).as_flattened())));
+    }
+
+    #[test]
+    fn load_dumps() {
+        let dump: Vec<u8> = (0..2 * IROM_WORDS).map(|i| i as u8).collect();
+
+        let mut rom = Rom::default();
+        rom.load_irom(&dump).unwrap();
+        assert_eq!(rom.irom[..3], [0x0001, 0x0203, 0x0405]);
+        assert_eq!(rom.coef, Rom::default().coef);
+
+        rom.load_coef(&dump[..2 * COEF_WORDS]).unwrap();
+        assert_eq!(rom.coef[COEF_WORDS - 1], 0xFEFF);
+
+        assert!(matches!(
+            rom.load_irom(&dump[..100]),
+            Err(RomError::Size {
+                found: 100,
+                expected: 8192,
+                ..
+            })
+        ));
+        assert!(rom.load_coef(&dump).is_err());
+
+        // a failed load leaves the ROM untouched
+        assert_eq!(rom.irom[..3], [0x0001, 0x0203, 0x0405]);
+    }
+
+    #[test]
+    fn bootstrap_handshake() {
+        let mut io = DspIo::new();
+        io.control.set_halt(false);
+        io.control.set_reset_high(true);
+
+        let mut dsp = interpreter_with_rom(&Rom::default());
+        dsp.reset(&mut io);
+        assert_eq!(dsp.pc, 0x8000);
+
+        // the ROM announces itself, then waits for the CPU to tell it what to boot
+        for _ in 0..64 {
+            if dsp.is_blocked(&io) {
+                break;
+            }
+
+            dsp.step(&mut io);
+        }
+
+        assert!(dsp.is_blocked(&io));
+        assert!(io.dsp_mailbox.status());
+        assert_eq!(io.dsp_mailbox.high_and_status(), 0x8071);
+        assert_eq!(io.dsp_mailbox.low(), 0xFEED);
+
+        io.cpu_mailbox = Mailbox::from_bits(0x80F3_A001);
+        assert!(!dsp.is_blocked(&io));
+    }
+}
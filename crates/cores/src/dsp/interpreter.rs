@@ -1,38 +1,71 @@
 use dspint::Interpreter;
-use lazuli::cores::DspCore;
+use lazuli::cores::{DspCore, DspExecuted, DspMemory};
 use lazuli::system::System;
 
-use super::{DSP_COEF, DSP_ROM};
+use super::Rom;
 
 pub struct Core {
     interpreter: Interpreter,
+    rom: Rom,
+}
+
+impl Core {
+    pub fn new(rom: Rom) -> Self {
+        Self {
+            interpreter: super::interpreter_with_rom(&rom),
+            rom,
+        }
+    }
 }
 
 impl Default for Core {
     fn default() -> Self {
-        let mut interpreter = Interpreter::default();
-        interpreter.mem.irom.copy_from_slice(&DSP_ROM[..]);
-        interpreter.mem.coef.copy_from_slice(&DSP_COEF[..]);
-
-        Self { interpreter }
+        Self::new(Rom::default())
     }
 }
 
 impl DspCore for Core {
-    fn exec(&mut self, sys: &mut System, instructions: u32) -> u32 {
-        self.interpreter.do_dma(sys);
-        self.interpreter.check_reset(sys);
-
-        if sys.dsp.control.halt()
-            || !sys.dsp.cpu_mailbox.status() && self.interpreter.is_waiting_for_cpu_mail()
-            || sys.dsp.dsp_mailbox.status() && self.interpreter.is_waiting_for_dsp_mail()
-        {
+    fn exec(&mut self, sys: &mut System, instructions: u32) -> DspExecuted {
+        profiling::scope!("DSP slice");
+        sys.mem
+            .write_ram(|ram| self.interpreter.do_dma(&mut sys.dsp, ram));
+        self.interpreter.check_reset(&mut sys.dsp, sys.mem.ram());
+
+        let mut hit_breakpoint = false;
+        if self.interpreter.is_blocked(&sys.dsp) {
             std::hint::cold_path();
-            self.interpreter.check_interrupts(sys);
+            self.interpreter.check_interrupts(&mut sys.dsp);
         } else {
-            self.interpreter.exec(sys, instructions);
+            hit_breakpoint = self.interpreter.exec(&mut sys.dsp, instructions);
+        }
+
+        DspExecuted {
+            instructions,
+            hit_breakpoint,
         }
+    }
+
+    fn is_idle(&mut self, sys: &System) -> bool {
+        // anything the next exec would act on besides running code
+        let pending = sys.dsp.control.interrupt()
+            || sys.dsp.control.reset()
+            || sys.dsp.control.reset_high() != self.interpreter.old_reset_high
+            || sys.dsp.dsp_dma.control.transfer_ongoing();
+
+        !pending && self.interpreter.is_blocked(&sys.dsp)
+    }
+
+    fn reset(&mut self) {
+        let breakpoints = std::mem::take(&mut self.interpreter.breakpoints);
+        self.interpreter = super::interpreter_with_rom(&self.rom);
+        self.interpreter.breakpoints = breakpoints;
+    }
+
+    fn set_breakpoints(&mut self, breakpoints: &[u16]) {
+        self.interpreter.breakpoints = breakpoints.to_vec();
+    }
 
-        instructions
+    fn memory(&self) -> Option<DspMemory<'_>> {
+        Some(super::memory_of(&self.interpreter))
     }
 }
@@ -0,0 +1,81 @@
+use dspint::threaded::Threaded;
+use lazuli::cores::{DspCore, DspExecuted, DspMemory};
+use lazuli::system::{System, dspi};
+
+use super::Rom;
+
+/// Default amount of instructions the DSP thread is allowed to run ahead of the CPU.
+pub const DEFAULT_SLACK: u32 = 4096;
+
+/// A DSP core which runs the interpreter on a dedicated thread.
+pub struct Core {
+    threaded: Threaded,
+    rom: Rom,
+    slack: u32,
+    breakpoints: Vec<u16>,
+    /// Whether `breakpoints` changed since they were last given to the interpreter, which only
+    /// happens at sync points.
+    breakpoints_changed: bool,
+}
+
+impl Core {
+    pub fn new(rom: Rom, slack: u32) -> Self {
+        Self {
+            threaded: Threaded::new(super::interpreter_with_rom(&rom), slack),
+            rom,
+            slack,
+            breakpoints: Vec::new(),
+            breakpoints_changed: false,
+        }
+    }
+}
+
+impl DspCore for Core {
+    fn exec(&mut self, sys: &mut System, instructions: u32) -> DspExecuted {
+        let mut executed = DspExecuted {
+            instructions,
+            hit_breakpoint: false,
+        };
+
+        if !self.threaded.advance(instructions) {
+            return executed;
+        }
+
+        profiling::scope!("DSP sync");
+        let interpreter = self.threaded.sync(&mut sys.dsp);
+        if std::mem::take(&mut self.breakpoints_changed) {
+            interpreter.breakpoints.clone_from(&self.breakpoints);
+        }
+
+        // ARAM is back, perform any deferred ARAM DMA
+        dspi::deferred_aram_dma(sys);
+
+        sys.mem
+            .write_ram(|ram| interpreter.do_dma(&mut sys.dsp, ram));
+        interpreter.check_reset(&mut sys.dsp, sys.mem.ram());
+
+        executed.hit_breakpoint = self.threaded.hit_breakpoint();
+        self.threaded.dispatch(&mut sys.dsp);
+        executed
+    }
+
+    fn reset(&mut self) {
+        // dropping the old core stops its thread, discarding any batch in flight
+        self.threaded = Threaded::new(super::interpreter_with_rom(&self.rom), self.slack);
+        self.breakpoints_changed = true;
+    }
+
+    fn set_breakpoints(&mut self, breakpoints: &[u16]) {
+        self.breakpoints = breakpoints.to_vec();
+        self.breakpoints_changed = true;
+    }
+
+    fn sync(&mut self, sys: &mut System) {
+        // the next exec dispatches the worker again, since the batch is now home
+        self.threaded.sync(&mut sys.dsp);
+    }
+
+    fn memory(&self) -> Option<DspMemory<'_>> {
+        self.threaded.interpreter().map(super::memory_of)
+    }
+}
@@ -0,0 +1,310 @@
+//! Disassembly of ranges of code in executables.
+
+use std::io::Write;
+use std::num::ParseIntError;
+use std::path::Path;
+
+use disks::binrw::BinRead;
+use disks::binrw::io::BufReader;
+use disks::{dol, iso};
+use eyre_pretty::{Context, ContextCompat, Result, bail};
+use mapfile_parser::MapFile;
+use powerpc::{Extensions, Ins, Opcode, ParsedIns};
+use serde::Serialize;
+
+/// Parses an integer given either in decimal, in hexadecimal (`0x` prefix) or in binary (`0b`
+/// prefix). Underscores are ignored.
+pub fn parse_u32(value: &str) -> std::result::Result<u32, ParseIntError> {
+    let value = value.replace('_', "");
+    if let Some(value) = value.strip_prefix("0x") {
+        u32::from_str_radix(value, 16)
+    } else if let Some(value) = value.strip_prefix("0b") {
+        u32::from_str_radix(value, 2)
+    } else {
+        value.parse()
+    }
+}
+
+/// Disassembles a single instruction code.
+pub fn disassemble_code(code: u32) -> String {
+    let ins = Ins::new(code, Extensions::gekko_broadway());
+    let mut parsed = ParsedIns::new();
+    ins.parse_basic(&mut parsed);
+
+    parsed.to_string()
+}
+
+/// Symbols of a `.map` file, parsed with the same parser as the debug module of the app.
+pub struct Symbols(MapFile);
+
+impl Symbols {
+    pub fn open(path: &Path) -> Self {
+        Self(MapFile::new_from_map_file(path))
+    }
+
+    /// Name of the symbol which starts at `addr`, if any.
+    fn label(&self, addr: u32) -> Option<String> {
+        self.0
+            .find_symbol_by_vram(addr as u64)
+            .0
+            .filter(|s| s.offset == 0)
+            .map(|s| s.symbol.name.clone())
+    }
+
+    /// Name of the symbol containing `addr`, along with the offset into it if it's not zero.
+    fn name(&self, addr: u32) -> Option<String> {
+        self.0.find_symbol_by_vram(addr as u64).0.map(|s| {
+            if s.offset == 0 {
+                s.symbol.name.clone()
+            } else {
+                format!("{}+{:#x}", s.symbol.name, s.offset)
+            }
+        })
+    }
+}
+
+/// A disassembled instruction.
+#[derive(Debug, Serialize)]
+pub struct Line {
+    pub addr: u32,
+    pub code: u32,
+    pub text: String,
+    /// Name of the symbol starting at this instruction.
+    pub label: Option<String>,
+    /// Destination of this instruction, if it is a direct branch.
+    pub target: Option<u32>,
+    /// Name of the symbol containing the destination of this instruction.
+    pub target_symbol: Option<String>,
+}
+
+/// Destination of the instruction at `addr`, if it is a direct branch.
+fn branch_target(ins: Ins, addr: u32) -> Option<u32> {
+    let offset = match ins.op {
+        Opcode::B => ins.field_li(),
+        Opcode::Bc => ins.field_bd() as i32,
+        _ => return None,
+    };
+
+    Some(if ins.field_aa() {
+        offset as u32
+    } else {
+        addr.wrapping_add_signed(offset)
+    })
+}
+
+/// Disassembles the code in `bytes`, which starts at address `base`.
+pub fn disassemble(base: u32, bytes: &[u8], symbols: Option<&Symbols>) -> Vec<Line> {
+    bytes
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, code)| {
+            let addr = base + 4 * i as u32;
+            let code = u32::from_be_bytes(code.try_into().unwrap());
+            let ins = Ins::new(code, Extensions::gekko_broadway());
+            let target = branch_target(ins, addr);
+
+            Line {
+                addr,
+                code,
+                text: disassemble_code(code),
+                label: symbols.and_then(|s| s.label(addr)),
+                target,
+                target_symbol: symbols.zip(target).and_then(|(s, t)| s.name(t)),
+            }
+        })
+        .collect()
+}
+
+/// Writes the given lines as plain text, one instruction per line: address, code, mnemonic and,
+/// for branches, their destination. Symbols are written as labels before their first instruction.
+pub fn write_text(out: &mut impl Write, lines: &[Line]) -> std::io::Result<()> {
+    for line in lines {
+        if let Some(label) = &line.label {
+            writeln!(out, "{label}:")?;
+        }
+
+        write!(out, "{:08X}  {:08X}  {}", line.addr, line.code, line.text)?;
+        if let Some(target) = line.target {
+            write!(out, "  -> {target:08X}")?;
+        }
+
+        if let Some(symbol) = &line.target_symbol {
+            write!(out, " <{symbol}>")?;
+        }
+
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the given lines as JSON, one object per line.
+pub fn write_json(out: &mut impl Write, lines: &[Line]) -> Result<()> {
+    for line in lines {
+        serde_json::to_writer(&mut *out, line)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Which code to disassemble.
+pub enum Range {
+    /// A whole section, by name (e.g. `.text0`).
+    Section(String),
+    /// A given amount of instructions starting at an address.
+    Addr { addr: u32, count: u32 },
+}
+
+/// Reads the executable in `input`: a .dol, or the bootfile of an .iso.
+fn read_dol(input: &Path) -> Result<dol::Dol> {
+    let extension = input
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .context("unknown or missing file extension")?;
+
+    let file = std::fs::File::open(input).context("opening input file")?;
+    let mut reader = BufReader::new(file);
+    match extension {
+        "dol" => dol::Dol::read(&mut reader).context("parsing .dol"),
+        "iso" => {
+            let mut iso = iso::Iso::new(reader)?;
+            iso.bootfile().context("reading bootfile")
+        }
+        _ => bail!("unknown or missing file extension"),
+    }
+}
+
+/// The sections of `dol`, named like `cubetool inspect` does.
+fn sections(dol: &dol::Dol) -> impl Iterator<Item = (String, dol::Section<'_>)> {
+    let text = dol
+        .text_sections()
+        .enumerate()
+        .map(|(i, section)| (format!(".text{i}"), section));
+    let data = dol
+        .data_sections()
+        .enumerate()
+        .map(|(i, section)| (format!(".data{i}"), section));
+
+    text.chain(data)
+}
+
+/// Finds the code selected by `range` in `dol`. Returns its address and its bytes.
+fn select<'dol>(dol: &'dol dol::Dol, range: &Range) -> Result<(u32, &'dol [u8])> {
+    match range {
+        Range::Section(name) => {
+            let (_, section) = sections(dol)
+                .find(|(n, _)| n == name)
+                .with_context(|| format!("no section named {name}"))?;
+
+            Ok((section.target, section.content))
+        }
+        &Range::Addr { addr, count } => {
+            let (name, section) = sections(dol)
+                .find(|(_, s)| (addr.wrapping_sub(s.target) as usize) < s.content.len())
+                .with_context(|| format!("address 0x{addr:08X} is not in any section"))?;
+
+            let start = (addr - section.target) as usize;
+            let len = 4 * count as usize;
+            let bytes = section
+                .content
+                .get(start..start + len)
+                .with_context(|| format!("range goes past the end of {name}"))?;
+
+            Ok((addr, bytes))
+        }
+    }
+}
+
+/// Disassembles the code selected by `range` in the executable at `input` and writes it to
+/// stdout, either as plain text or as JSON.
+pub fn disassemble_file(
+    input: &Path,
+    range: Range,
+    symbols: Option<&Path>,
+    json: bool,
+) -> Result<()> {
+    let dol = read_dol(input)?;
+    let symbols = symbols.map(Symbols::open);
+    let (base, bytes) = select(&dol, &range)?;
+    let lines = disassemble(base, bytes, symbols.as_ref());
+
+    let mut out = std::io::stdout().lock();
+    if json {
+        write_json(&mut out, &lines)
+    } else {
+        write_text(&mut out, &lines).context("writing disassembly")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BASE: u32 = 0x8000_3100;
+    const CODE: [u32; 7] = [
+        0x3860_0001, // addi r3, r0, 0x1
+        0x8083_0004, // lwz r4, 0x4(r3)
+        0x7C63_2214, // add r3, r3, r4
+        0x9064_0008, // stw r3, 0x8(r4)
+        0x4BFF_FFF1, // bl -0x10
+        0x4800_0008, // b 0x8
+        0x6000_0000, // ori r0, r0, 0x0
+    ];
+
+    fn fixture() -> dol::Dol {
+        let mut header = dol::Header::default();
+        header.text_offsets[0] = 0x100;
+        header.text_targets[0] = BASE;
+        header.text_sizes[0] = 4 * CODE.len() as u32;
+        header.entry = BASE;
+
+        dol::Dol {
+            header,
+            body: CODE.iter().flat_map(|code| code.to_be_bytes()).collect(),
+        }
+    }
+
+    fn text(dol: &dol::Dol, range: Range) -> String {
+        let (base, bytes) = select(dol, &range).unwrap();
+        let mut out = Vec::new();
+        write_text(&mut out, &disassemble(base, bytes, None)).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn section_matches_golden() {
+        let dol = fixture();
+        let golden = include_str!("../fixtures/disasm_text0.txt");
+        assert_eq!(text(&dol, Range::Section(".text0".to_owned())), golden);
+    }
+
+    #[test]
+    fn address_ranges() {
+        let dol = fixture();
+        let golden = include_str!("../fixtures/disasm_text0.txt");
+        let range = Range::Addr {
+            addr: BASE + 4,
+            count: 2,
+        };
+
+        let expected = golden.lines().skip(1).take(2).collect::<Vec<_>>();
+        assert_eq!(text(&dol, range).lines().collect::<Vec<_>>(), expected);
+
+        let past_end = Range::Addr {
+            addr: BASE + 4,
+            count: CODE.len() as u32,
+        };
+        assert!(select(&dol, &past_end).is_err());
+        assert!(select(&dol, &Range::Section(".text1".to_owned())).is_err());
+    }
+
+    #[test]
+    fn parse_integers() {
+        assert_eq!(parse_u32("0x8000_3100"), Ok(0x8000_3100));
+        assert_eq!(parse_u32("0b101"), Ok(5));
+        assert_eq!(parse_u32("64"), Ok(64));
+        assert!(parse_u32("0xZZ").is_err());
+    }
+}
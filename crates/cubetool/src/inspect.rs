@@ -8,8 +8,7 @@ use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
 use disks::binrw::BinRead;
 use disks::binrw::io::BufReader;
 use disks::iso::{self, Meta};
-use disks::rvz::{self, RvzReader};
-use disks::{Console, apploader, dol};
+use disks::{Console, apploader, dol, rvz};
 use eyre_pretty::{Context, Result};
 
 use crate::vfs::{self, VfsEntryId, VfsGraph, VirtualEntry};
@@ -384,12 +383,19 @@ pub fn inspect_iso(input: PathBuf, filesystem: bool) -> Result<()> {
         apploader_table(&apploader);
     }
 
-    if let Ok(bootfile) = iso.bootfile_header() {
-        label([
-            "> Bootfile (.dol)".to_string(),
-            format!("Entry: 0x{:08X}", bootfile.entry),
-        ]);
-        dol_table(&bootfile);
+    match iso.bootfile_location() {
+        Ok(location) => {
+            if let Ok(bootfile) = iso.bootfile_header() {
+                label([
+                    "> Bootfile (.dol)".to_string(),
+                    format!("Entry: 0x{:08X}", bootfile.entry),
+                    format!("Offset: 0x{:08X}", location.offset),
+                    format!("Source: {}", location.source),
+                ]);
+                dol_table(&bootfile);
+            }
+        }
+        Err(e) => label(["> Bootfile (.dol)".to_string(), format!("Not found: {e}")]),
     }
 
     Ok(())
@@ -400,7 +406,9 @@ pub fn inspect_rvz(input: PathBuf) -> Result<()> {
     let meta = file.metadata()?;
 
     let rvz = rvz::Rvz::new(BufReader::new(&mut file)).context("parsing .rvz file")?;
-    let mut rvz = RvzReader::new(rvz);
+    let rvz_header = rvz.rvz_header().clone();
+    let rvz_disk_header = rvz.disk_header().clone();
+    let mut iso = rvz.into_iso().context("parsing .iso header")?;
 
     label([format!(
         "{} ({})",
@@ -408,11 +416,7 @@ pub fn inspect_rvz(input: PathBuf) -> Result<()> {
         ByteSize(meta.len()).display()
     )]);
 
-    let disk_header = rvz.iso_header().unwrap();
-    let rvz_header = rvz.inner().rvz_header();
-    let rvz_disk_header = rvz.inner().disk_header();
-
-    let disk_properties = disk_properties_table(&disk_header);
+    let disk_properties = disk_properties_table(iso.header());
     let disk_meta = disk_meta_table(&rvz_disk_header.disk_meta);
 
     let mut rvz_properties = Table::new();
@@ -517,12 +521,12 @@ pub fn inspect_rvz(input: PathBuf) -> Result<()> {
     label(["> Disk Meta".into()]);
     println!("{disk_meta}");
 
-    if let Ok(apploader) = rvz.apploader_header() {
+    if let Ok(apploader) = iso.apploader_header() {
         label(["> Apploader".into()]);
         apploader_table(&apploader);
     }
 
-    if let Ok(bootfile) = rvz.bootfile_header() {
+    if let Ok(bootfile) = iso.bootfile_header() {
         label([
             "> Bootfile (.dol)".to_string(),
             format!("Entry: 0x{:08X}", bootfile.entry),
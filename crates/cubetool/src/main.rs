@@ -1,4 +1,6 @@
+mod disasm;
 mod inspect;
+mod textures;
 mod vfs;
 
 use std::io::{BufWriter, Read, Seek, SeekFrom};
@@ -12,8 +14,33 @@ use eyre_pretty::{Context, ContextCompat, Result, bail, eyre};
 
 #[derive(Debug, Subcommand)]
 enum Command {
-    /// Disassemble a PowerPC instruction.
-    Disassemble { code: String },
+    /// Disassemble PowerPC instructions
+    ///
+    /// Either disassembles a single instruction code or a range of code from an executable
+    /// (a .dol, or the bootfile of an .iso).
+    Disassemble {
+        /// Instruction code to disassemble
+        #[arg(required_unless_present = "input", conflicts_with = "input")]
+        code: Option<String>,
+        /// Path to the executable to disassemble code from
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Section to disassemble (e.g. .text0)
+        #[arg(long, requires = "input", conflicts_with = "addr")]
+        section: Option<String>,
+        /// Address to start disassembling at
+        #[arg(long, requires = "input", value_parser = disasm::parse_u32)]
+        addr: Option<u32>,
+        /// Amount of instructions to disassemble, starting at the address
+        #[arg(long, default_value_t = 64)]
+        count: u32,
+        /// Path to a .map file whose symbols label the disassembly
+        #[arg(long, requires = "input")]
+        symbols: Option<PathBuf>,
+        /// Whether to output JSON, one object per instruction, instead of plain text
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
     /// Inspect a file
     ///
     /// Supported formats: .dol, .iso
@@ -50,6 +77,24 @@ enum Command {
         #[arg(short, long)]
         output: PathBuf,
     },
+    /// Encode every image in a directory as a GX texture
+    ///
+    /// For each image, writes the encoded texture (.tex), its palette (.tlut, only for indexed
+    /// formats) and a sidecar describing them (.txt) to the output directory.
+    EncodeTextures {
+        /// Path to the input directory
+        #[arg(short, long)]
+        input: PathBuf,
+        /// Format to encode the textures with
+        #[arg(short, long)]
+        format: textures::TextureFormat,
+        /// Format of the palette of indexed formats
+        #[arg(long, default_value = "rgb5a3")]
+        palette_format: textures::PaletteFormat,
+        /// Path to the output directory
+        #[arg(short, long)]
+        output: PathBuf,
+    },
 }
 
 /// A CLI to inspect and manipulate files related to the GameCube.
@@ -113,22 +158,29 @@ fn main() -> Result<()> {
 
     let config = Args::parse();
     match config.command {
-        Command::Disassemble { code } => {
-            let code = code.replace("_", "");
-            let code = if let Some(code) = code.strip_prefix("0x") {
-                u32::from_str_radix(code, 16).context("parsing instruction code")?
-            } else if let Some(code) = code.strip_prefix("0b") {
-                u32::from_str_radix(code, 2).context("parsing instruction code")?
-            } else {
-                code.parse::<u32>().context("parsing instruction code")?
-            };
+        Command::Disassemble {
+            code,
+            input,
+            section,
+            addr,
+            count,
+            symbols,
+            json,
+        } => {
+            if let Some(code) = code {
+                let code = disasm::parse_u32(&code).context("parsing instruction code")?;
+                println!("{}", disasm::disassemble_code(code));
+                return Ok(());
+            }
 
-            let ins = powerpc::Ins::new(code, powerpc::Extensions::gekko_broadway());
-            let mut parsed = powerpc::ParsedIns::new();
-            ins.parse_basic(&mut parsed);
-            println!("{parsed}");
+            let input = input.context("missing input file")?;
+            let range = match (section, addr) {
+                (Some(section), _) => disasm::Range::Section(section),
+                (None, Some(addr)) => disasm::Range::Addr { addr, count },
+                (None, None) => bail!("either a section or an address must be given"),
+            };
 
-            Ok(())
+            disasm::disassemble_file(&input, range, symbols.as_deref(), json)
         }
         Command::Inspect { input, filesystem } => {
             let extension = input
@@ -170,5 +222,11 @@ fn main() -> Result<()> {
                 _ => bail!("unsupported extension/target combination"),
             }
         }
+        Command::EncodeTextures {
+            input,
+            format,
+            palette_format,
+            output,
+        } => textures::encode_textures(input, output, format, palette_format),
     }
 }
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use bytesize::ByteSize;
+use clap::ValueEnum;
+use eyre_pretty::{Context, Result};
+use gxtex::{PaletteIndex, Pixel};
+
+/// A GX texture format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TextureFormat {
+    I4,
+    Ia4,
+    I8,
+    Ia8,
+    Rgb565,
+    Rgb5a3,
+    Rgba8,
+    Cmpr,
+    Ci4,
+    Ci8,
+    Ci14x2,
+}
+
+impl TextureFormat {
+    /// Maximum amount of palette entries, for indexed formats.
+    fn palette_len(self) -> Option<usize> {
+        match self {
+            Self::Ci4 => Some(1 << 4),
+            Self::Ci8 => Some(1 << 8),
+            Self::Ci14x2 => Some(1 << 14),
+            _ => None,
+        }
+    }
+}
+
+/// The format of the entries of a palette (TLUT).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaletteFormat {
+    Ia8,
+    Rgb565,
+    Rgb5a3,
+}
+
+impl PaletteFormat {
+    fn encode(self, pixel: Pixel) -> u16 {
+        match self {
+            Self::Ia8 => u16::from_le_bytes([pixel.a, pixel.y()]),
+            Self::Rgb565 => pixel.to_rgb565(),
+            Self::Rgb5a3 => pixel.to_rgb5a3(),
+        }
+    }
+
+    fn decode(self, value: u16) -> Pixel {
+        match self {
+            Self::Ia8 => Pixel::from_ia8(value),
+            Self::Rgb565 => Pixel::from_rgb565(value),
+            Self::Rgb5a3 => Pixel::from_rgb5a3(value),
+        }
+    }
+}
+
+/// An encoded texture.
+struct Encoded {
+    data: Vec<u8>,
+    /// Palette of indexed formats, as big endian entries.
+    palette: Option<Vec<u16>>,
+}
+
+fn encode<F: gxtex::Format>(width: usize, height: usize, texels: &[F::Texel]) -> Vec<u8> {
+    let mut data = vec![0; gxtex::compute_size::<F>(width, height)];
+    let stride = width.div_ceil(F::TILE_WIDTH) * F::BYTES_PER_TILE / 32;
+    gxtex::encode::<F>(stride, width, height, texels, &mut data);
+
+    data
+}
+
+/// Builds a palette of at most `max` colors for the given pixels through median cut. Returns the
+/// palette and the index of the entry of each pixel.
+fn quantize(pixels: &[Pixel], max: usize) -> (Vec<Pixel>, Vec<PaletteIndex>) {
+    let mut counts = HashMap::<Pixel, usize>::new();
+    for &pixel in pixels {
+        *counts.entry(pixel).or_default() += 1;
+    }
+
+    let channels = |p: Pixel| [p.r, p.g, p.b, p.a];
+    let range = |colors: &[(Pixel, usize)], channel: usize| {
+        let values = colors.iter().map(|(p, _)| channels(*p)[channel]);
+        let min = values.clone().min().unwrap_or_default();
+        let max = values.max().unwrap_or_default();
+        max - min
+    };
+
+    // split the box with the widest channel at its median until there are enough boxes
+    let mut boxes = vec![counts.into_iter().collect::<Vec<_>>()];
+    while boxes.len() < max {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .flat_map(|(i, colors)| (0..4).map(move |c| (i, c, range(colors, c))))
+            .max_by_key(|(_, _, range)| *range);
+
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|(p, _)| channels(*p)[channel]);
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    // each entry is the average of its box, weighted by how often each color appears
+    let mut palette = Vec::with_capacity(boxes.len());
+    let mut lookup = HashMap::new();
+    for (index, colors) in boxes.iter().enumerate() {
+        let total = colors.iter().map(|(_, count)| count).sum::<usize>();
+        let mut sum = [0usize; 4];
+        for (pixel, count) in colors {
+            for (sum, value) in sum.iter_mut().zip(channels(*pixel)) {
+                *sum += value as usize * count;
+            }
+
+            lookup.insert(*pixel, index as PaletteIndex);
+        }
+
+        let [r, g, b, a] = sum.map(|sum| (sum as f64 / total as f64).round() as u8);
+        palette.push(Pixel { r, g, b, a });
+    }
+
+    let indices = pixels.iter().map(|pixel| lookup[pixel]).collect();
+    (palette, indices)
+}
+
+fn encode_indexed<F: gxtex::Format<Texel = PaletteIndex>>(
+    width: usize,
+    height: usize,
+    pixels: &[Pixel],
+    max: usize,
+    palette_format: PaletteFormat,
+) -> Encoded {
+    // reduce precision first, so colors which end up the same share an entry
+    let pixels = pixels
+        .iter()
+        .map(|&p| palette_format.decode(palette_format.encode(p)))
+        .collect::<Vec<_>>();
+
+    let (palette, indices) = quantize(&pixels, max);
+    let palette = palette.into_iter().map(|p| palette_format.encode(p));
+    Encoded {
+        data: encode::<F>(width, height, &indices),
+        palette: Some(palette.collect()),
+    }
+}
+
+fn encode_texture(
+    format: TextureFormat,
+    palette_format: PaletteFormat,
+    width: usize,
+    height: usize,
+    pixels: &[Pixel],
+) -> Encoded {
+    let direct = |data| Encoded {
+        data,
+        palette: None,
+    };
+
+    let max = format.palette_len().unwrap_or_default();
+    match format {
+        TextureFormat::I4 => direct(encode::<gxtex::I4>(width, height, pixels)),
+        TextureFormat::Ia4 => direct(encode::<gxtex::IA4>(width, height, pixels)),
+        TextureFormat::I8 => direct(encode::<gxtex::I8>(width, height, pixels)),
+        TextureFormat::Ia8 => direct(encode::<gxtex::IA8>(width, height, pixels)),
+        TextureFormat::Rgb565 => direct(encode::<gxtex::Rgb565>(width, height, pixels)),
+        TextureFormat::Rgb5a3 => direct(encode::<gxtex::Rgb5A3>(width, height, pixels)),
+        TextureFormat::Rgba8 => direct(encode::<gxtex::Rgba8>(width, height, pixels)),
+        TextureFormat::Cmpr => direct(encode::<gxtex::Cmpr>(width, height, pixels)),
+        TextureFormat::Ci4 => {
+            encode_indexed::<gxtex::CI4>(width, height, pixels, max, palette_format)
+        }
+        TextureFormat::Ci8 => {
+            encode_indexed::<gxtex::CI8>(width, height, pixels, max, palette_format)
+        }
+        TextureFormat::Ci14x2 => {
+            encode_indexed::<gxtex::CI14X2>(width, height, pixels, max, palette_format)
+        }
+    }
+}
+
+fn format_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+fn encode_file(
+    input: &Path,
+    output: &Path,
+    name: &str,
+    format: TextureFormat,
+    palette_format: PaletteFormat,
+) -> Result<()> {
+    let image = image::open(input)
+        .with_context(|| format!("opening {}", input.display()))?
+        .to_rgba8();
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let pixels = image
+        .pixels()
+        .map(|p| Pixel {
+            r: p.0[0],
+            g: p.0[1],
+            b: p.0[2],
+            a: p.0[3],
+        })
+        .collect::<Vec<_>>();
+
+    let encoded = encode_texture(format, palette_format, width, height, &pixels);
+    let texture_path = output.join(format!("{name}.tex"));
+    std::fs::write(texture_path, &encoded.data).context("writing texture")?;
+
+    let mut sidecar = format!(
+        "width = {width}\nheight = {height}\nformat = {}\n",
+        format_name(format)
+    );
+
+    if let Some(palette) = &encoded.palette {
+        let bytes = palette
+            .iter()
+            .flat_map(|entry| entry.to_be_bytes())
+            .collect::<Vec<_>>();
+        let palette_path = output.join(format!("{name}.tlut"));
+        std::fs::write(palette_path, bytes).context("writing palette")?;
+
+        writeln!(sidecar, "palette_format = {}", format_name(palette_format))?;
+        writeln!(sidecar, "palette_entries = {}", palette.len())?;
+    }
+
+    let sidecar_path = output.join(format!("{name}.txt"));
+    std::fs::write(sidecar_path, sidecar).context("writing sidecar")?;
+
+    println!(
+        "{}: {width}x{height} -> {}",
+        input.display(),
+        ByteSize(encoded.data.len() as u64).display()
+    );
+
+    Ok(())
+}
+
+/// Encodes every image in the `input` directory with the given format, writing the results to the
+/// `output` directory. Each image produces a `.tex` file with the encoded texture, a `.tlut` file
+/// with the palette (for indexed formats) and a `.txt` sidecar describing them.
+pub fn encode_textures(
+    input: PathBuf,
+    output: PathBuf,
+    format: TextureFormat,
+    palette_format: PaletteFormat,
+) -> Result<()> {
+    let mut paths = std::fs::read_dir(&input)
+        .context("reading input directory")?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    std::fs::create_dir_all(&output).context("creating output directory")?;
+    for path in paths {
+        let supported = image::ImageFormat::from_path(&path).is_ok_and(|f| f.reading_enabled());
+        if !path.is_file() || !supported {
+            continue;
+        }
+
+        let Some(name) = path.file_stem() else {
+            continue;
+        };
+
+        let name = name.to_string_lossy();
+        encode_file(&path, &output, &name, format, palette_format)?;
+    }
+
+    Ok(())
+}
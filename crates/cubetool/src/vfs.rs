@@ -53,9 +53,7 @@ impl VirtualFileSystem {
 
             match entry {
                 iso::filesystem::Entry::File(file) => {
-                    reader.seek(SeekFrom::Start(
-                        (filesystem.strings_offset + file.name_offset) as u64,
-                    ))?;
+                    reader.seek(SeekFrom::Start(filesystem.name_position(file.name_offset)))?;
                     let name = NullString::read(&mut reader)?.to_string();
                     let node = graph.add_node(VirtualEntry::File(VirtualFile {
                         name,
@@ -66,9 +64,7 @@ impl VirtualFileSystem {
                     graph.add_edge(*dir_stack.last().unwrap(), node, ());
                 }
                 iso::filesystem::Entry::Directory(dir) => {
-                    reader.seek(SeekFrom::Start(
-                        (filesystem.strings_offset + dir.name_offset) as u64,
-                    ))?;
+                    reader.seek(SeekFrom::Start(filesystem.name_position(dir.name_offset)))?;
                     let name = NullString::read(&mut reader)?.to_string();
                     let node = graph.add_node(VirtualEntry::Dir(VirtualDir { name }));
                     graph.add_edge(*dir_stack.last().unwrap(), node, ());
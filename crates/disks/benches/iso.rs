@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::hint::black_box;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use disks::iso::Iso;
+use disks::iso::mmap::IsoMmap;
+
+/// Size of the generated image.
+const IMAGE_SIZE: usize = 64 * 1024 * 1024;
+/// Size of a sector read by the drive.
+const SECTOR: usize = 0x800;
+
+/// Writes an image with a valid header and pseudo random contents to the temporary directory.
+fn image() -> PathBuf {
+    let mut data = (0..IMAGE_SIZE)
+        .map(|i| (i ^ (i >> 8) ^ (i >> 16)) as u8)
+        .collect::<Vec<_>>();
+
+    data[..0x440].fill(0);
+    data[..6].copy_from_slice(b"GTSE01");
+    data[0x1C..0x20].copy_from_slice(&0xC233_9F3D_u32.to_be_bytes());
+
+    let path = std::env::temp_dir().join(format!("disks-bench-{}.iso", std::process::id()));
+    std::fs::write(&path, data).unwrap();
+
+    path
+}
+
+/// Offsets of `count` sector sized reads scattered around the image.
+fn scattered(count: usize) -> Vec<u64> {
+    let mut state = 0x1234_5678_u64;
+    (0..count)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((state >> 33) as usize % (IMAGE_SIZE / SECTOR) * SECTOR) as u64
+        })
+        .collect()
+}
+
+fn bench(c: &mut Criterion) {
+    let path = image();
+    let mut buffered = Iso::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+    let mut mapped = IsoMmap::open(&path).unwrap();
+    let mut buf = vec![0; 32 * 1024];
+
+    let mut group = c.benchmark_group("Sequential Reads");
+    group.throughput(Throughput::Bytes(IMAGE_SIZE as u64));
+
+    group.bench_function("BufReader", |b| {
+        b.iter(|| {
+            for offset in (0..IMAGE_SIZE).step_by(buf.len()) {
+                buffered.read(offset as u64, black_box(&mut buf)).unwrap();
+            }
+        })
+    });
+
+    group.bench_function("Mmap", |b| {
+        b.iter(|| {
+            for offset in (0..IMAGE_SIZE).step_by(buf.len()) {
+                mapped.read(offset as u64, black_box(&mut buf)).unwrap();
+            }
+        })
+    });
+
+    group.bench_function("Mmap Slice", |b| {
+        b.iter(|| {
+            for offset in (0..IMAGE_SIZE).step_by(buf.len()) {
+                black_box(mapped.slice(offset as u64, buf.len()).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+
+    let offsets = scattered(4096);
+    let mut group = c.benchmark_group("Scattered Reads");
+    group.throughput(Throughput::Bytes((offsets.len() * SECTOR) as u64));
+
+    group.bench_function("BufReader", |b| {
+        b.iter(|| {
+            for &offset in &offsets {
+                buffered
+                    .read(offset, black_box(&mut buf[..SECTOR]))
+                    .unwrap();
+            }
+        })
+    });
+
+    group.bench_function("Mmap", |b| {
+        b.iter(|| {
+            for &offset in &offsets {
+                mapped.read(offset, black_box(&mut buf[..SECTOR])).unwrap();
+            }
+        })
+    });
+
+    group.finish();
+
+    _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Apploader headers and bodies.
+#![no_main]
+
+use disks::apploader::{Apploader, Header};
+use disks::binrw::BinRead;
+use disks::binrw::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    _ = Header::read(&mut Cursor::new(data)).map_err(|e| e.to_string());
+    if let Ok(apploader) = Apploader::read(&mut Cursor::new(data)) {
+        assert_eq!(apploader.body.len(), apploader.header.size as usize);
+    }
+});
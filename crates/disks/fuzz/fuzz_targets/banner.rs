@@ -0,0 +1,33 @@
+//! Disk banners and the decoding of their texts.
+#![no_main]
+
+use disks::banner::{self, Banner, Encoding};
+use disks::binrw::BinRead;
+use disks::binrw::io::Cursor;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let banner = match Banner::read(&mut Cursor::new(data)) {
+        Ok(banner) => banner,
+        Err(e) => {
+            _ = e.to_string();
+            return;
+        }
+    };
+
+    assert_eq!(banner.descriptions.len(), banner.kind.descriptions());
+    assert!(banner.primary().is_some());
+    for description in &banner.descriptions {
+        for encoding in [Encoding::ShiftJis, Encoding::Windows1252] {
+            for text in [
+                &description.short_title[..],
+                &description.short_maker,
+                &description.title,
+                &description.maker,
+                &description.description,
+            ] {
+                _ = banner::decode_text(text, encoding);
+            }
+        }
+    }
+});
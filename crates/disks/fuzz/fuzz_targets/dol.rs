@@ -0,0 +1,35 @@
+//! .dol headers and section slicing, both from a [`Dol`] and a [`DolRef`].
+#![no_main]
+
+use std::io::Cursor;
+
+use disks::binrw::BinRead;
+use disks::dol::{Dol, DolRef, Header};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = Header::read(&mut Cursor::new(data)) {
+        _ = header.size();
+        _ = header.validate().map_err(|e| e.to_string());
+    }
+
+    match Dol::read(&mut Cursor::new(data)) {
+        Ok(dol) => {
+            for section in dol.text_sections().chain(dol.data_sections()) {
+                _ = section.content.len();
+            }
+        }
+        Err(e) => _ = e.to_string(),
+    }
+
+    let Ok(mut dol) = DolRef::read(Cursor::new(data)) else {
+        return;
+    };
+
+    let sections = dol.header.text_sections().chain(dol.header.data_sections());
+    for info in sections.collect::<Vec<_>>() {
+        // sections are only as large as the input, which the fuzzer keeps small
+        let mut buf = vec![0; info.size.min(data.len() as u32) as usize];
+        _ = dol.read_section(info, &mut buf);
+    }
+});
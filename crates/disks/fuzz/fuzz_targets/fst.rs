@@ -0,0 +1,40 @@
+//! The filesystem table and its traversal helpers.
+#![no_main]
+
+use disks::binrw::BinRead;
+use disks::binrw::io::Cursor;
+use disks::iso::filesystem::FileSystem;
+use libfuzzer_sys::fuzz_target;
+
+/// How many entries [`FileSystem::parent`], which is linear on the entry count, is called for.
+const MAX_PARENT_LOOKUPS: usize = 4096;
+
+fuzz_target!(|data: &[u8]| {
+    let fs = match FileSystem::read(&mut Cursor::new(data)) {
+        Ok(fs) => fs,
+        Err(e) => {
+            _ = e.to_string();
+            return;
+        }
+    };
+
+    // walk the whole tree, visiting each entry once even if the directory ranges overlap
+    let count = fs.entries.len() + 1;
+    let mut visited = vec![false; count];
+    let mut pending = vec![0];
+    while let Some(dir) = pending.pop() {
+        for child in fs.children(dir) {
+            assert!(child > dir && child < count);
+            if !std::mem::replace(&mut visited[child], true) {
+                pending.push(child);
+            }
+        }
+    }
+
+    for index in (0..=count).take(MAX_PARENT_LOOKUPS) {
+        if let Some(parent) = fs.parent(index) {
+            assert!(parent < count);
+            _ = fs.entry(index);
+        }
+    }
+});
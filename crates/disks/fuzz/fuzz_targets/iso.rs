@@ -0,0 +1,22 @@
+//! The .iso header and bi2, along with everything [`Iso`] derives from them.
+#![no_main]
+
+use std::io::Cursor;
+
+use disks::iso::Iso;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut iso) = Iso::new(Cursor::new(data)) else {
+        return;
+    };
+
+    _ = iso.bi2().map_err(|e| e.to_string());
+    _ = iso.metadata().map_err(|e| e.to_string());
+    _ = iso.fingerprint().map_err(|e| e.to_string());
+    _ = iso.bootfile_location().map_err(|e| e.to_string());
+    _ = iso.bootfile().map_err(|e| e.to_string());
+    _ = iso.apploader_header().map_err(|e| e.to_string());
+    _ = iso.filesystem().map_err(|e| e.to_string());
+    _ = iso.banner().map_err(|e| e.to_string());
+});
@@ -1,6 +1,8 @@
 //! An apploader is a small wrapper around a `.dol` file that's present in `.iso`s to load games
 //! from disk into memory.
 
+use alloc::vec::Vec;
+
 use binrw::{BinRead, BinWrite, NullString};
 
 #[derive(Debug, BinRead, BinWrite)]
@@ -20,6 +22,36 @@ pub struct Header {
 pub struct Apploader {
     pub header: Header,
     #[brw(pad_before = 0x4)]
-    #[br(count = header.size)]
+    #[br(parse_with = crate::read_bytes, args(header.size as usize))]
     pub body: Vec<u8>,
 }
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use binrw::io::Cursor;
+
+    use super::*;
+
+    /// An apploader with the given body size in its header, followed by `body` bytes.
+    fn apploader(size: u32, body: usize) -> Vec<u8> {
+        let mut file = vec![0; 0x20 + body];
+        file[..8].copy_from_slice(b"2004/02/");
+        file[0x14..0x18].copy_from_slice(&size.to_be_bytes());
+        file
+    }
+
+    #[test]
+    fn body() {
+        let apploader = Apploader::read(&mut Cursor::new(apploader(0x10, 0x10))).unwrap();
+        assert_eq!(apploader.header.version.0, b"2004/02/");
+        assert_eq!(apploader.body.len(), 0x10);
+    }
+
+    #[test]
+    fn huge_body_in_small_file_is_an_error() {
+        let file = apploader(0xFFFF_FFF0, 0x10);
+        assert!(Apploader::read(&mut Cursor::new(file)).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! The banner of a GameCube disk (`opening.bnr`), holding the image and the texts the IPL shows
+//! for it.
+
+use binrw::{BinRead, BinWrite};
+
+/// Size of the banner image in bytes. The image is 96x32 pixels in the RGB5A3 format.
+pub const IMAGE_SIZE: usize = 96 * 32 * 2;
+
+/// Kind of a banner, which tells how many descriptions it has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead, BinWrite)]
+pub enum Kind {
+    /// A single description, in the language of the region (NTSC titles).
+    #[brw(magic = b"BNR1")]
+    Single,
+    /// Six descriptions: English, German, French, Spanish, Italian and Dutch (PAL titles).
+    #[brw(magic = b"BNR2")]
+    Multi,
+}
+
+impl Kind {
+    /// How many descriptions a banner of this kind has.
+    pub fn descriptions(self) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::Multi => 6,
+        }
+    }
+}
+
+/// Encoding of the texts in a banner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Used by Japanese titles.
+    ShiftJis,
+    /// Used by every other title.
+    Windows1252,
+}
+
+/// Texts describing a title in a single language. Texts are NUL padded.
+#[derive(Debug, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct Description {
+    pub short_title: [u8; 0x20],
+    pub short_maker: [u8; 0x20],
+    pub title: [u8; 0x40],
+    pub maker: [u8; 0x40],
+    pub description: [u8; 0x80],
+}
+
+/// Decodes a NUL padded text of a banner. Returns `None` if it is empty.
+pub fn decode_text(text: &[u8], encoding: Encoding) -> Option<String> {
+    let end = text.iter().position(|&b| b == 0).unwrap_or(text.len());
+    let encoding = match encoding {
+        Encoding::ShiftJis => encoding_rs::SHIFT_JIS,
+        Encoding::Windows1252 => encoding_rs::WINDOWS_1252,
+    };
+
+    let (text, _) = encoding.decode_without_bom_handling(&text[..end]);
+    let text = text.trim();
+
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+/// A disk banner.
+#[derive(Debug, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct Banner {
+    pub kind: Kind,
+    #[brw(pad_before = 0x1C)]
+    #[br(count = IMAGE_SIZE)]
+    pub image: Vec<u8>,
+    #[br(count = kind.descriptions())]
+    pub descriptions: Vec<Description>,
+}
+
+impl Banner {
+    /// The description in the primary language of the banner (i.e. English for PAL titles).
+    pub fn primary(&self) -> Option<&Description> {
+        self.descriptions.first()
+    }
+}
@@ -1,8 +1,12 @@
 //! A `.dol` file is a proprietary executable format used in the GameCube and the Wii.
 
-use std::io::{Read, Seek};
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom};
 
 use binrw::{BinRead, BinWrite};
+#[cfg(feature = "std")]
 use easyerr::{Error, ResultExt};
 
 const HEADER_SIZE: usize = 0x100;
@@ -80,12 +84,84 @@ impl Header {
     pub fn size(&self) -> u32 {
         self.text_sections()
             .chain(self.data_sections())
-            .map(|sec| sec.offset + sec.size)
+            .map(|sec| sec.offset.saturating_add(sec.size))
             .max()
             .unwrap_or_default()
     }
+
+    /// Checks that every section lies in the body of the .dol, i.e. that it starts at or after
+    /// the end of the header and that its end doesn't overflow.
+    pub fn validate_sections(&self) -> Result<(), HeaderError> {
+        for section in self.text_sections().chain(self.data_sections()) {
+            if section.offset < HEADER_SIZE as u32 {
+                return Err(HeaderError::SectionInHeader {
+                    offset: section.offset,
+                });
+            }
+
+            if section.offset.checked_add(section.size).is_none()
+                || section.target.checked_add(section.size).is_none()
+            {
+                return Err(HeaderError::SectionOverflow {
+                    offset: section.offset,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Performs basic sanity checks on this header, in order to tell whether it actually belongs
+    /// to a .dol file.
+    pub fn validate(&self) -> Result<(), HeaderError> {
+        let mut text = self.text_sections().peekable();
+        if text.peek().is_none() {
+            return Err(HeaderError::NoText);
+        }
+
+        self.validate_sections()?;
+
+        let entry_in_text =
+            text.any(|sec| (sec.target..sec.target + sec.size).contains(&self.entry));
+        if !entry_in_text {
+            return Err(HeaderError::EntryOutsideText { entry: self.entry });
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a .dol header is invalid. Implemented by hand, since easyerr needs `std`.
+#[derive(Debug)]
+pub enum HeaderError {
+    NoText,
+    SectionInHeader { offset: u32 },
+    SectionOverflow { offset: u32 },
+    EntryOutsideText { entry: u32 },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoText => f.write_str("dol has no .text sections"),
+            Self::SectionInHeader { offset } => {
+                write!(f, "section at 0x{offset:08X} overlaps the dol header")
+            }
+            Self::SectionOverflow { offset } => {
+                write!(f, "section at 0x{offset:08X} is too large")
+            }
+            Self::EntryOutsideText { entry } => {
+                write!(
+                    f,
+                    "entrypoint 0x{entry:08X} is outside of the .text sections"
+                )
+            }
+        }
+    }
 }
 
+impl core::error::Error for HeaderError {}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Section<'a> {
     pub target: u32,
@@ -96,17 +172,32 @@ pub struct Section<'a> {
 #[derive(Debug, BinRead, BinWrite)]
 #[brw(big)]
 pub struct Dol {
-    /// Header of the executable.
+    /// Header of the executable. Its sections are checked to lie in the body, so slicing them
+    /// out of it can't go out of bounds.
     #[brw(pad_size_to = HEADER_SIZE)]
+    #[br(assert(
+        header.validate_sections().is_ok(),
+        "dol sections overlap its header or overflow"
+    ))]
     pub header: Header,
-    /// Body of the executable.
-    #[br(count = header.size() - HEADER_SIZE as u32)]
+    /// Body of the executable, i.e. everything after the header. A section at offset
+    /// `HEADER_SIZE` starts at the beginning of the body.
+    #[br(
+        parse_with = crate::read_bytes,
+        args(header.size().saturating_sub(HEADER_SIZE as u32) as usize)
+    )]
     pub body: Vec<u8>,
 }
 
 impl Dol {
+    /// Returns the contents of a section. Sections of a [`Dol`] built by hand might not lie in its
+    /// body, so they are clamped to it.
     fn bytes(&self, offset: u32, size: u32) -> &[u8] {
-        &self.body[offset as usize - HEADER_SIZE..][..size as usize]
+        let start = (offset as usize)
+            .saturating_sub(HEADER_SIZE)
+            .min(self.body.len());
+        let end = start.saturating_add(size as usize).min(self.body.len());
+        &self.body[start..end]
     }
 
     pub fn text_sections(&self) -> impl Iterator<Item = Section<'_>> {
@@ -134,6 +225,66 @@ impl Dol {
     }
 }
 
+/// A .dol executable whose sections are read from the underlying reader on demand, instead of
+/// loading the whole body into memory like [`Dol`] does.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct DolRef<R> {
+    /// Header of the executable. Like with [`Dol`], its sections are checked to lie in the body.
+    pub header: Header,
+    reader: R,
+    /// Position of the start of the .dol in the reader.
+    start: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> DolRef<R> {
+    /// Parses the header of the .dol at the current position of `reader`. Fails if the reader
+    /// ends before the last section does, just like [`Dol::read`].
+    pub fn read(mut reader: R) -> binrw::BinResult<Self> {
+        let start = reader.stream_position()?;
+        let header = Header::read(&mut reader)?;
+        if header.validate_sections().is_err() {
+            return Err(binrw::Error::AssertFail {
+                pos: start,
+                message: "dol sections overlap its header or overflow".into(),
+            });
+        }
+
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < start + header.size() as u64 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(Self {
+            header,
+            reader,
+            start,
+        })
+    }
+
+    /// Reads the first `buf.len()` bytes of the given section into `buf`. Later parts of a
+    /// section can be read by offsetting it.
+    ///
+    /// # Panics
+    /// Panics if `buf` is larger than the section.
+    pub fn read_section(&mut self, info: SectionInfo, buf: &mut [u8]) -> std::io::Result<()> {
+        assert!(
+            buf.len() <= info.size as usize,
+            "buffer is larger than the section"
+        );
+
+        self.reader
+            .seek(SeekFrom::Start(self.start + info.offset as u64))?;
+        self.reader.read_exact(buf)
+    }
+
+    pub fn entrypoint(&self) -> u32 {
+        self.header.entry
+    }
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Error)]
 pub enum ElfToDolError {
     #[error(transparent)]
@@ -146,6 +297,7 @@ pub enum ElfToDolError {
     TooManyDataSections,
 }
 
+#[cfg(feature = "std")]
 pub fn elf_to_dol(reader: impl Read + Seek) -> Result<Dol, ElfToDolError> {
     let mut elf = elf::ElfStream::<elf::endian::AnyEndian, _>::open_stream(reader)
         .context(ElfToDolCtx::Elf)?;
@@ -226,3 +378,167 @@ pub fn elf_to_dol(reader: impl Read + Seek) -> Result<Dol, ElfToDolError> {
 
     Ok(Dol { header, body })
 }
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use binrw::io::Cursor;
+
+    use super::*;
+
+    const ENTRY: u32 = 0x8000_3100;
+
+    /// Builds a .dol with a .text section and a .data section at the given offsets.
+    fn dol(text_offset: u32, data_offset: u32) -> Vec<u8> {
+        let mut header = Header::default();
+        header.text_offsets[0] = text_offset;
+        header.text_targets[0] = ENTRY;
+        header.text_sizes[0] = 8;
+        header.data_offsets[0] = data_offset;
+        header.data_targets[0] = 0x8000_4000;
+        header.data_sizes[0] = 4;
+        header.entry = ENTRY;
+
+        let mut file = Cursor::new(vec![0; HEADER_SIZE]);
+        header.write(&mut file).unwrap();
+
+        let mut file = file.into_inner();
+        file.resize(header.size() as usize, 0);
+        file[text_offset as usize..][..8].copy_from_slice(&[0x11; 8]);
+        file[data_offset as usize..][..4].copy_from_slice(&[0x22; 4]);
+
+        file
+    }
+
+    #[test]
+    fn section_at_end_of_header() {
+        let file = dol(HEADER_SIZE as u32, HEADER_SIZE as u32 + 8);
+        let dol = Dol::read(&mut Cursor::new(file)).unwrap();
+        assert!(dol.header.validate().is_ok());
+        assert_eq!(dol.body.len(), 12);
+
+        let text = dol.text_sections().collect::<Vec<_>>();
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].target, ENTRY);
+        assert_eq!(text[0].content, [0x11; 8]);
+
+        let data = dol.data_sections().collect::<Vec<_>>();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].content, [0x22; 4]);
+    }
+
+    #[test]
+    fn section_after_end_of_header() {
+        let file = dol(HEADER_SIZE as u32 + 0x20, HEADER_SIZE as u32 + 0x40);
+        let dol = Dol::read(&mut Cursor::new(file)).unwrap();
+        assert_eq!(dol.body.len(), 0x44);
+        assert_eq!(dol.text_sections().next().unwrap().content, [0x11; 8]);
+        assert_eq!(dol.data_sections().next().unwrap().content, [0x22; 4]);
+    }
+
+    #[test]
+    fn huge_section_in_small_file_is_an_error() {
+        let mut file = dol(HEADER_SIZE as u32, HEADER_SIZE as u32 + 8);
+        file[0x90..0x94].copy_from_slice(&0xFFFF_0000_u32.to_be_bytes());
+        assert!(Dol::read(&mut Cursor::new(file)).is_err());
+    }
+
+    #[test]
+    fn sections_of_hand_built_dol_are_clamped() {
+        let mut header = Header::default();
+        header.text_offsets[0] = HEADER_SIZE as u32 + 4;
+        header.text_sizes[0] = 8;
+        header.data_offsets[0] = 0x10;
+        header.data_sizes[0] = 4;
+
+        let dol = Dol {
+            header,
+            body: vec![0x11; 8],
+        };
+
+        assert_eq!(dol.text_sections().next().unwrap().content, [0x11; 4]);
+        assert_eq!(dol.data_sections().next().unwrap().content, [0x11; 4]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dol_ref_matches_dol() {
+        for (text, data) in [(0x100, 0x108), (0x120, 0x140)] {
+            let file = dol(text, data);
+            let dol = Dol::read(&mut Cursor::new(&file)).unwrap();
+            let mut dol_ref = DolRef::read(Cursor::new(&file)).unwrap();
+            assert_eq!(dol_ref.entrypoint(), dol.entrypoint());
+
+            let owned = dol.text_sections().chain(dol.data_sections());
+            let infos = dol.header.text_sections().chain(dol.header.data_sections());
+            for (section, info) in owned.zip(infos) {
+                let mut buf = vec![0; info.size as usize];
+                dol_ref.read_section(info, &mut buf).unwrap();
+                assert_eq!(buf, section.content);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dol_ref_reads_only_the_header() {
+        /// Reader which counts how many bytes were read through it.
+        struct Counting<R> {
+            inner: R,
+            read: usize,
+        }
+
+        impl<R: Read> Read for Counting<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let read = self.inner.read(buf)?;
+                self.read += read;
+                Ok(read)
+            }
+        }
+
+        impl<R: Seek> Seek for Counting<R> {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let file = dol(0x120, 0x140);
+        let mut reader = Counting {
+            inner: Cursor::new(&file),
+            read: 0,
+        };
+
+        let mut dol_ref = DolRef::read(&mut reader).unwrap();
+        let data = dol_ref.header.data_sections().next().unwrap();
+        let mut buf = [0; 4];
+        dol_ref.read_section(data, &mut buf).unwrap();
+        assert_eq!(buf, [0x22; 4]);
+
+        // the header, then the section. nothing else of the body
+        assert!(reader.read <= HEADER_SIZE + 4, "read {} bytes", reader.read);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn dol_ref_rejects_truncated_body() {
+        let mut file = dol(0x100, 0x108);
+        file.pop();
+        assert!(Dol::read(&mut Cursor::new(&file)).is_err());
+        assert!(DolRef::read(Cursor::new(&file)).is_err());
+    }
+
+    #[test]
+    fn section_in_header_is_rejected() {
+        let mut file = dol(HEADER_SIZE as u32, HEADER_SIZE as u32 + 8);
+        // move the data section into the header
+        file[0x1C..][..4].copy_from_slice(&(HEADER_SIZE as u32 - 4).to_be_bytes());
+
+        let header = Header::read(&mut Cursor::new(&file)).unwrap();
+        assert!(matches!(
+            header.validate(),
+            Err(HeaderError::SectionInHeader { .. })
+        ));
+        assert!(Dol::read(&mut Cursor::new(file)).is_err());
+    }
+}
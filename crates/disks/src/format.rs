@@ -0,0 +1,137 @@
+//! Detection of the format of a file from its contents.
+
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use binrw::BinRead;
+
+use crate::{Console, dol};
+
+/// Amount of bytes at the start of a file which are looked at in order to detect its format.
+const SNIFF_LEN: usize = 0x100;
+
+const GAMECUBE_MAGIC: u32 = 0xC233_9F3D;
+const WII_MAGIC: u32 = 0x5D1C_9EA3;
+const RVZ_MAGIC: &[u8; 4] = b"RVZ\x01";
+const ELF_MAGIC: &[u8; 4] = b"\x7FELF";
+
+/// A file format supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A `.iso` disc image of the given console.
+    Iso(Console),
+    /// A `.rvz` disc image of the given console.
+    Rvz(Option<Console>),
+    /// A `.dol` executable.
+    Dol,
+    /// An `.elf` executable.
+    Elf,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+impl Format {
+    /// Detects the format of a file from its contents. Returns `None` if the format is not
+    /// recognized.
+    pub fn sniff(mut reader: impl Read + Seek) -> std::io::Result<Option<Self>> {
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut start = Vec::with_capacity(SNIFF_LEN);
+        (&mut reader)
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut start)?;
+
+        Ok(Self::sniff_bytes(&start))
+    }
+
+    /// Detects the format of a file from the bytes at its start.
+    fn sniff_bytes(start: &[u8]) -> Option<Self> {
+        if start.starts_with(RVZ_MAGIC) {
+            let console = match read_u32(start, 0x48) {
+                Some(1) => Some(Console::GameCube),
+                Some(2) => Some(Console::Wii),
+                _ => None,
+            };
+
+            return Some(Self::Rvz(console));
+        }
+
+        if start.starts_with(ELF_MAGIC) {
+            return Some(Self::Elf);
+        }
+
+        if read_u32(start, 0x1C) == Some(GAMECUBE_MAGIC) {
+            return Some(Self::Iso(Console::GameCube));
+        }
+
+        if read_u32(start, 0x18) == Some(WII_MAGIC) {
+            return Some(Self::Iso(Console::Wii));
+        }
+
+        // dols have no magic, so check whether the start is a sane dol header instead
+        let header = dol::Header::read(&mut Cursor::new(start)).ok()?;
+        header.validate().is_ok().then_some(Self::Dol)
+    }
+
+    /// Guesses the format of a file from its extension. Disc images are assumed to be of the
+    /// GameCube.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "iso" => Self::Iso(Console::GameCube),
+            "rvz" => Self::Rvz(None),
+            "dol" => Self::Dol,
+            "elf" => Self::Elf,
+            _ => return None,
+        })
+    }
+
+    /// The console this file belongs to, if known. Executables could belong to either.
+    pub fn console(&self) -> Option<Console> {
+        match self {
+            Self::Iso(console) => Some(*console),
+            Self::Rvz(console) => *console,
+            Self::Dol | Self::Elf => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iso::test::{DOL_OFFSET, image};
+
+    fn sniff(bytes: &[u8]) -> Option<Format> {
+        Format::sniff(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn sniff_discs() {
+        let iso = image(DOL_OFFSET as u32, "main.dol");
+        assert_eq!(sniff(&iso), Some(Format::Iso(Console::GameCube)));
+
+        let mut wii = vec![0; 0x100];
+        wii[0x18..0x1C].copy_from_slice(&WII_MAGIC.to_be_bytes());
+        assert_eq!(sniff(&wii), Some(Format::Iso(Console::Wii)));
+
+        let mut rvz = vec![0; 0x100];
+        rvz[..4].copy_from_slice(RVZ_MAGIC);
+        rvz[0x48..0x4C].copy_from_slice(&2u32.to_be_bytes());
+        assert_eq!(sniff(&rvz), Some(Format::Rvz(Some(Console::Wii))));
+    }
+
+    #[test]
+    fn sniff_executables() {
+        let iso = image(DOL_OFFSET as u32, "main.dol");
+        assert_eq!(sniff(&iso[DOL_OFFSET..]), Some(Format::Dol));
+        assert_eq!(sniff(b"\x7FELF\x01\x02\x01"), Some(Format::Elf));
+    }
+
+    #[test]
+    fn sniff_unknown() {
+        assert_eq!(sniff(&[]), None);
+        assert_eq!(sniff(&[0; 0x400]), None);
+        assert_eq!(sniff(b"not a game"), None);
+    }
+}
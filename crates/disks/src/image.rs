@@ -0,0 +1,191 @@
+//! A common interface for disc images, regardless of the container they are stored in.
+
+use std::io::{Read, Seek};
+
+use crate::iso::filesystem::FileSystem;
+use crate::iso::{self, BootfileError, Iso};
+use crate::{apploader, dol};
+
+/// A GameCube disc image.
+///
+/// Every container provides the raw disc data, from which [`Iso`] parses the actual structures -
+/// e.g. a .rvz is opened as an `Iso<RvzReader<R>>` through [`Rvz::into_iso`]. This trait is object
+/// safe, so images can be boxed regardless of their container.
+///
+/// [`Rvz::into_iso`]: crate::rvz::Rvz::into_iso
+pub trait DiscImage {
+    /// The header of the disc.
+    fn header(&self) -> &iso::Header;
+
+    /// Reads from the disc at the given offset into the buffer. Returns how many bytes were
+    /// actually read, which is less than the length of the buffer only if the end of the disc was
+    /// reached.
+    fn read(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize>;
+
+    /// Length of the disc, in bytes.
+    fn size(&self) -> u64;
+
+    fn filesystem(&mut self) -> Result<FileSystem, binrw::Error>;
+
+    fn bootfile(&mut self) -> Result<dol::Dol, BootfileError>;
+
+    fn apploader(&mut self) -> Result<apploader::Apploader, binrw::Error>;
+}
+
+impl<R> DiscImage for Iso<R>
+where
+    R: Read + Seek,
+{
+    fn header(&self) -> &iso::Header {
+        Iso::header(self)
+    }
+
+    fn read(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        Iso::read(self, offset, buf)
+    }
+
+    fn size(&self) -> u64 {
+        Iso::size(self)
+    }
+
+    fn filesystem(&mut self) -> Result<FileSystem, binrw::Error> {
+        Iso::filesystem(self)
+    }
+
+    fn bootfile(&mut self) -> Result<dol::Dol, BootfileError> {
+        Iso::bootfile(self)
+    }
+
+    fn apploader(&mut self) -> Result<apploader::Apploader, binrw::Error> {
+        Iso::apploader(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::iso::test::{DOL_OFFSET, ENTRY, image};
+    use crate::rvz::Rvz;
+
+    const CHUNK_LEN: usize = 0x2000;
+
+    /// Wraps a disc in a minimal .rvz: a single disk section, split into uncompressed chunks.
+    fn rvz(disc: &[u8]) -> Vec<u8> {
+        const HEADER_LEN: usize = 0x48;
+        const DISK_HEADER_LEN: usize = 0xDC;
+
+        let chunks = disc.len().div_ceil(CHUNK_LEN);
+        let compress = |data: &[u8]| zstd::bulk::compress(data, 0).unwrap();
+
+        let mut disk_sections = Vec::new();
+        disk_sections.extend(0u64.to_be_bytes());
+        disk_sections.extend((disc.len() as u64).to_be_bytes());
+        disk_sections.extend(0u32.to_be_bytes());
+        disk_sections.extend((chunks as u32).to_be_bytes());
+        let disk_sections = compress(&disk_sections);
+
+        let file_sections_offset = HEADER_LEN + DISK_HEADER_LEN + disk_sections.len();
+        let data_offset = (file_sections_offset + chunks * 0xC + 0x100).next_multiple_of(4);
+
+        let mut file_sections = Vec::new();
+        for (i, chunk) in disc.chunks(CHUNK_LEN).enumerate() {
+            let offset = data_offset + i * CHUNK_LEN;
+            file_sections.extend((offset as u32 / 4).to_be_bytes());
+            file_sections.extend((chunk.len() as u32).to_be_bytes());
+            file_sections.extend(0u32.to_be_bytes());
+        }
+        let file_sections = compress(&file_sections);
+        assert!(file_sections_offset + file_sections.len() <= data_offset);
+
+        let mut out = Vec::new();
+
+        // header
+        out.extend(b"RVZ\x01");
+        out.extend([1, 0, 0, 0xFF, 1, 0, 0, 0xFF]);
+        out.extend((DISK_HEADER_LEN as u32).to_be_bytes());
+        out.extend([0; 20]);
+        out.extend((disc.len() as u64).to_be_bytes());
+        out.extend(((data_offset + disc.len()) as u64).to_be_bytes());
+        out.extend([0; 20]);
+        assert_eq!(out.len(), HEADER_LEN);
+
+        // disk header: gamecube, zstd, level 0
+        out.extend(1u32.to_be_bytes());
+        out.extend(5u32.to_be_bytes());
+        out.extend(0u32.to_be_bytes());
+        out.extend((CHUNK_LEN as u32).to_be_bytes());
+        out.extend(&disc[..0x80]);
+        out.extend([0; 4 + 4 + 8 + 20]);
+        out.extend(1u32.to_be_bytes());
+        out.extend(((HEADER_LEN + DISK_HEADER_LEN) as u64).to_be_bytes());
+        out.extend((disk_sections.len() as u32).to_be_bytes());
+        out.extend((chunks as u32).to_be_bytes());
+        out.extend((file_sections_offset as u64).to_be_bytes());
+        out.extend((file_sections.len() as u32).to_be_bytes());
+        out.extend([0; 8]);
+        assert_eq!(out.len(), HEADER_LEN + DISK_HEADER_LEN);
+
+        out.extend(disk_sections);
+        out.extend(file_sections);
+        out.resize(data_offset, 0);
+        out.extend(disc);
+
+        out
+    }
+
+    /// The same disc, in every supported container.
+    fn images(disc: &[u8]) -> Vec<(&'static str, Box<dyn DiscImage>)> {
+        let iso = Iso::new(Cursor::new(disc.to_vec())).unwrap();
+        let rvz = Rvz::new(Cursor::new(rvz(disc)))
+            .unwrap()
+            .into_iso()
+            .unwrap();
+
+        vec![("iso", Box::new(iso)), ("rvz", Box::new(rvz))]
+    }
+
+    #[test]
+    fn containers_are_equivalent() {
+        let disc = image(DOL_OFFSET as u32, "main.dol");
+        for (name, mut image) in images(&disc) {
+            assert_eq!(image.size(), disc.len() as u64, "{name}");
+            assert_eq!(
+                format!("{:?}", image.header()),
+                format!("{:?}", Iso::new(Cursor::new(&disc)).unwrap().header()),
+                "{name}"
+            );
+
+            // reads across chunk boundaries and past the end of the disc
+            let mut buf = vec![0; 0x100];
+            for offset in [0, CHUNK_LEN as u64 - 0x80, disc.len() as u64 - 0x80] {
+                let read = image.read(offset, &mut buf).unwrap();
+                let expected = &disc[offset as usize..][..read];
+                assert_eq!(read, 0x100.min(disc.len() - offset as usize), "{name}");
+                assert_eq!(&buf[..read], expected, "{name}");
+            }
+
+            let filesystem = image.filesystem().unwrap();
+            assert_eq!(filesystem.entries.len(), 1, "{name}");
+            assert_eq!(image.bootfile().unwrap().entrypoint(), ENTRY, "{name}");
+            assert!(image.apploader().is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn containers_fall_back_to_the_same_bootfile() {
+        let disc = image(0, "boot.dol");
+        for (name, mut image) in images(&disc) {
+            assert_eq!(image.bootfile().unwrap().entrypoint(), ENTRY, "{name}");
+        }
+
+        let disc = image(0, "game.dol");
+        for (name, mut image) in images(&disc) {
+            assert!(
+                matches!(image.bootfile(), Err(BootfileError::NotFound { .. })),
+                "{name}"
+            );
+        }
+    }
+}
@@ -1,13 +1,26 @@
 //! A GameCube/Wii `.iso` file contains the entire image of a disk.
 
 pub mod filesystem;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 
-use std::io::{Read, Seek, SeekFrom};
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::hash::Hasher;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use binrw::{BinRead, BinWrite, NullString};
+#[cfg(feature = "std")]
+use easyerr::{Error, ResultExt};
+#[cfg(feature = "std")]
 use filesystem::FileSystem;
 
-use crate::{Console, apploader, dol};
+use crate::Console;
+#[cfg(feature = "std")]
+use crate::banner::{self, Banner};
+#[cfg(feature = "std")]
+use crate::{apploader, dol};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead, BinWrite)]
 #[brw(big, magic = 0xC233_9F3D_u32)]
@@ -90,51 +103,329 @@ pub enum Region {
     Usa,
 }
 
+/// Disk information (`bi2.bin`), right after the header. Read by the IPL and the OS.
+#[derive(Debug, Clone, BinRead, BinWrite)]
+#[brw(big)]
+pub struct Bi2 {
+    pub debug_monitor_size: u32,
+    pub simulated_memory_size: u32,
+    pub argument_offset: u32,
+    pub debug_flag: u32,
+    pub track_location: u32,
+    pub track_size: u32,
+    #[brw(pad_after = 0x1FE4)]
+    pub country_code: u32,
+}
+
+impl Bi2 {
+    /// The region of the disk, which the IPL checks against the console's.
+    pub fn region(&self) -> Option<Region> {
+        Some(match self.country_code {
+            0 => Region::Japan,
+            1 => Region::Usa,
+            2 => Region::Pal,
+            _ => return None,
+        })
+    }
+}
+
+/// Metadata of a title, consolidated from the header, bi2 and banner of its disk.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleMetadata {
+    /// Game code, e.g. `GALE`.
+    pub game_code: String,
+    /// Name of the maker from the banner, or the maker code (e.g. `01`) if there's no banner.
+    pub maker: String,
+    /// Full title from the banner, or the name in the header if there's no banner.
+    pub title: String,
+    /// Region from the country code of the header, or from bi2 if the country code does not
+    /// identify one (e.g. the language specific codes of PAL titles).
+    pub region: Option<Region>,
+    pub disk_id: u8,
+    pub version: u8,
+}
+
+/// Offset of bi2 in a .iso.
+#[cfg(feature = "std")]
+const BI2_OFFSET: u64 = 0x440;
+
+/// Name of the banner file in the root of the filesystem.
+#[cfg(feature = "std")]
+const BANNER_FILE: &str = "opening.bnr";
+
+/// Offset of the apploader in a .iso.
+#[cfg(feature = "std")]
+const APPLOADER_OFFSET: u64 = 0x2440;
+
+/// Size of the apploader header, including padding.
+#[cfg(feature = "std")]
+const APPLOADER_HEADER_SIZE: u64 = 0x20;
+
+/// Names of files in the filesystem which are used as the bootfile when the header does not point
+/// to a valid one, in order of preference.
+#[cfg(feature = "std")]
+const FALLBACK_BOOTFILES: [&str; 2] = ["main.dol", "boot.dol"];
+
+/// Where the bootfile of a .iso was found.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootfileSource {
+    /// The bootfile offset in the header.
+    Header,
+    /// A file with the given name in the filesystem.
+    Filesystem(String),
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for BootfileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Header => f.write_str("header"),
+            Self::Filesystem(name) => write!(f, "filesystem ({name})"),
+        }
+    }
+}
+
+/// The location of the bootfile in a .iso.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootfileLocation {
+    pub offset: u64,
+    pub source: BootfileSource,
+}
+
+/// Why the bootfile offset in the header was rejected.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum BootfileRejection {
+    #[error("bootfile offset is zero")]
+    ZeroOffset,
+    #[error(
+        "bootfile offset 0x{offset:08X} is inside the header or apploader (ending at 0x{end:08X})"
+    )]
+    Overlaps { offset: u32, end: u64 },
+    #[error("bootfile at 0x{offset:08X} is not a valid dol: {source}")]
+    Invalid {
+        offset: u32,
+        source: dol::HeaderError,
+    },
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum BootfileError {
+    #[error(transparent)]
+    Io { source: binrw::Error },
+    #[error("{reason}, and the filesystem contains no valid main.dol or boot.dol")]
+    NotFound { reason: BootfileRejection },
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum ReadError {
+    #[error(transparent)]
+    Io { source: std::io::Error },
+    #[error(transparent)]
+    Format { source: binrw::Error },
+}
+
+/// Adapts a [`Hasher`] so that it can be written to with [`std::io::copy`].
+#[cfg(feature = "std")]
+struct HashWriter<'a>(&'a mut twox_hash::XxHash3_64);
+
+#[cfg(feature = "std")]
+impl Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// A GameCube .iso file.
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub struct Iso<R> {
     /// Header of the ISO.
     header: Header,
+    /// Length of the ISO, in bytes.
+    size: u64,
     /// Reader of the contents.
     reader: R,
 }
 
+#[cfg(feature = "std")]
 impl<R> Iso<R>
 where
     R: Read + Seek,
 {
     pub fn new(mut reader: R) -> Result<Self, binrw::Error> {
         let header = Header::read(&mut reader)?;
-        Ok(Self { header, reader })
+        let size = reader.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            header,
+            size,
+            reader,
+        })
     }
 
     pub fn header(&self) -> &Header {
         &self.header
     }
 
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn into_reader(self) -> R {
+        self.reader
+    }
+
+    /// Reads from the ISO at the given offset into the buffer. Returns how many bytes were
+    /// actually read, which is less than the length of the buffer only if the end of the ISO was
+    /// reached.
+    pub fn read(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(read)
+    }
+
     pub fn reader(&mut self) -> &mut R {
         &mut self.reader
     }
 
-    pub fn bootfile(&mut self) -> Result<dol::Dol, binrw::Error> {
+    /// Reads a .dol header at the given offset and checks whether it is valid.
+    fn valid_dol_header_at(&mut self, offset: u64) -> Result<dol::Header, BootfileError> {
         self.reader
-            .seek(SeekFrom::Start(self.header.bootfile_offset as u64))?;
-        dol::Dol::read(&mut self.reader)
+            .seek(SeekFrom::Start(offset))
+            .map_err(binrw::Error::from)
+            .context(BootfileCtx::Io)?;
+
+        dol::Header::read(&mut self.reader).context(BootfileCtx::Io)
+    }
+
+    /// Checks whether the bootfile offset in the header points to a valid .dol.
+    fn check_header_bootfile(&mut self) -> Result<Option<BootfileRejection>, BootfileError> {
+        let offset = self.header.bootfile_offset;
+        if offset == 0 {
+            return Ok(Some(BootfileRejection::ZeroOffset));
+        }
+
+        // if the apploader header can't be read, at least keep clear of its start
+        let end = match self.apploader_header() {
+            Ok(header) => {
+                APPLOADER_OFFSET
+                    + APPLOADER_HEADER_SIZE
+                    + header.size as u64
+                    + header.trailer_size as u64
+            }
+            Err(_) => APPLOADER_OFFSET,
+        };
+
+        if (offset as u64) < end {
+            return Ok(Some(BootfileRejection::Overlaps { offset, end }));
+        }
+
+        let header = self.valid_dol_header_at(offset as u64)?;
+        Ok(header
+            .validate()
+            .err()
+            .map(|source| BootfileRejection::Invalid { offset, source }))
     }
 
-    pub fn bootfile_header(&mut self) -> Result<dol::Header, binrw::Error> {
+    /// Looks for a valid fallback bootfile in the filesystem.
+    fn find_fallback_bootfile(&mut self) -> Result<Option<BootfileLocation>, BootfileError> {
+        let filesystem = self.filesystem().context(BootfileCtx::Io)?;
+
+        let mut candidates = Vec::new();
+        for entry in &filesystem.entries {
+            let filesystem::Entry::File(file) = entry else {
+                continue;
+            };
+
+            self.reader
+                .seek(SeekFrom::Start(filesystem.name_position(file.name_offset)))
+                .map_err(binrw::Error::from)
+                .context(BootfileCtx::Io)?;
+
+            let name = NullString::read(&mut self.reader)
+                .context(BootfileCtx::Io)?
+                .to_string();
+
+            if let Some(preference) = FALLBACK_BOOTFILES
+                .iter()
+                .position(|fallback| name.eq_ignore_ascii_case(fallback))
+            {
+                candidates.push((preference, name, file.data_offset as u64));
+            }
+        }
+
+        candidates.sort_by_key(|(preference, ..)| *preference);
+        for (_, name, offset) in candidates {
+            if self.valid_dol_header_at(offset)?.validate().is_ok() {
+                return Ok(Some(BootfileLocation {
+                    offset,
+                    source: BootfileSource::Filesystem(name),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Finds the bootfile of this .iso.
+    ///
+    /// The bootfile offset in the header is used if it points to a valid .dol. Otherwise, a file
+    /// named `main.dol` or `boot.dol` in the filesystem is used instead.
+    pub fn bootfile_location(&mut self) -> Result<BootfileLocation, BootfileError> {
+        let Some(reason) = self.check_header_bootfile()? else {
+            return Ok(BootfileLocation {
+                offset: self.header.bootfile_offset as u64,
+                source: BootfileSource::Header,
+            });
+        };
+
+        self.find_fallback_bootfile()?
+            .ok_or(BootfileError::NotFound { reason })
+    }
+
+    pub fn bootfile(&mut self) -> Result<dol::Dol, BootfileError> {
+        let location = self.bootfile_location()?;
         self.reader
-            .seek(SeekFrom::Start(self.header.bootfile_offset as u64))?;
-        dol::Header::read(&mut self.reader)
+            .seek(SeekFrom::Start(location.offset))
+            .map_err(binrw::Error::from)
+            .context(BootfileCtx::Io)?;
+
+        dol::Dol::read(&mut self.reader).context(BootfileCtx::Io)
+    }
+
+    pub fn bootfile_header(&mut self) -> Result<dol::Header, BootfileError> {
+        let location = self.bootfile_location()?;
+        self.valid_dol_header_at(location.offset)
     }
 
     pub fn apploader(&mut self) -> Result<apploader::Apploader, binrw::Error> {
-        self.reader.seek(SeekFrom::Start(0x2440))?;
+        self.reader.seek(SeekFrom::Start(APPLOADER_OFFSET))?;
         apploader::Apploader::read(&mut self.reader)
     }
 
     pub fn apploader_header(&mut self) -> Result<apploader::Header, binrw::Error> {
-        self.reader.seek(SeekFrom::Start(0x2440))?;
+        self.reader.seek(SeekFrom::Start(APPLOADER_OFFSET))?;
         apploader::Header::read(&mut self.reader)
     }
 
@@ -144,4 +435,355 @@ where
 
         FileSystem::read(&mut self.reader)
     }
+
+    pub fn bi2(&mut self) -> Result<Bi2, binrw::Error> {
+        self.reader.seek(SeekFrom::Start(BI2_OFFSET))?;
+        Bi2::read(&mut self.reader)
+    }
+
+    /// Finds the file with the given name in the root directory of the filesystem, returning its
+    /// data offset.
+    fn root_file(&mut self, name: &str) -> Result<Option<u64>, binrw::Error> {
+        let filesystem = self.filesystem()?;
+
+        // entry indices start at 1, since the root is index 0
+        let mut index = 1;
+        while let Some(entry) = filesystem.entries.get(index - 1) {
+            let file = match entry {
+                filesystem::Entry::File(file) => file,
+                filesystem::Entry::Directory(dir) => {
+                    // skip the contents of subdirectories
+                    index = (dir.end_index as usize).max(index + 1);
+                    continue;
+                }
+            };
+
+            index += 1;
+            self.reader
+                .seek(SeekFrom::Start(filesystem.name_position(file.name_offset)))?;
+
+            if NullString::read(&mut self.reader)?.to_string() == name {
+                return Ok(Some(file.data_offset as u64));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the banner of this .iso, if it has one.
+    pub fn banner(&mut self) -> Result<Option<Banner>, binrw::Error> {
+        let Some(offset) = self.root_file(BANNER_FILE)? else {
+            return Ok(None);
+        };
+
+        self.reader.seek(SeekFrom::Start(offset))?;
+        Banner::read(&mut self.reader).map(Some)
+    }
+
+    /// Collects the metadata of the title in this .iso from its header, bi2 and banner.
+    pub fn metadata(&mut self) -> Result<TitleMetadata, binrw::Error> {
+        let bi2 = self.bi2()?;
+
+        // a broken banner only loses the names it holds, which the header also has
+        let banner = self.banner().ok().flatten();
+
+        let meta = &self.header.meta;
+        let region = meta.region().or_else(|| bi2.region());
+        let encoding = if region == Some(Region::Japan) {
+            banner::Encoding::ShiftJis
+        } else {
+            banner::Encoding::Windows1252
+        };
+
+        let description = banner.as_ref().and_then(Banner::primary);
+        let text = |f: fn(&banner::Description) -> &[u8]| {
+            description.and_then(|d| banner::decode_text(f(d), encoding))
+        };
+
+        let title = text(|d| &d.title)
+            .or_else(|| text(|d| &d.short_title))
+            .unwrap_or_else(|| banner::decode_text(&meta.game_name, encoding).unwrap_or_default());
+
+        let maker = text(|d| &d.maker)
+            .or_else(|| text(|d| &d.short_maker))
+            .unwrap_or_else(|| String::from_utf8_lossy(&meta.maker_code.to_be_bytes()).into());
+
+        Ok(TitleMetadata {
+            game_code: String::from_utf8_lossy(&meta.game_code().to_be_bytes()).into(),
+            maker,
+            title,
+            region,
+            disk_id: meta.disk_id,
+            version: meta.version,
+        })
+    }
+
+    /// Feeds `length` bytes starting at `offset` into the hasher.
+    fn hash_range(
+        &mut self,
+        hasher: &mut twox_hash::XxHash3_64,
+        offset: u64,
+        length: u64,
+    ) -> Result<(), ReadError> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .context(ReadCtx::Io)?;
+
+        let copied = std::io::copy(
+            &mut (&mut self.reader).take(length),
+            &mut HashWriter(hasher),
+        )
+        .context(ReadCtx::Io)?;
+
+        if copied != length {
+            return Err(ReadError::Io {
+                source: std::io::ErrorKind::UnexpectedEof.into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Computes a cheap fingerprint of this .iso, suitable as a key for per-title data (e.g.
+    /// settings or save states).
+    ///
+    /// Only the header, bi2, apploader and filesystem table are hashed, which amount to a few KiB
+    /// and are enough to tell title builds apart. This is _not_ a content hash of the whole disk:
+    /// images that differ only in file data will have the same fingerprint.
+    pub fn fingerprint(&mut self) -> Result<u64, ReadError> {
+        let apploader = self.apploader_header().context(ReadCtx::Format)?;
+        let apploader_end = APPLOADER_OFFSET
+            + APPLOADER_HEADER_SIZE
+            + apploader.size as u64
+            + apploader.trailer_size as u64;
+
+        let mut hasher = twox_hash::XxHash3_64::with_seed(0);
+        self.hash_range(&mut hasher, 0, apploader_end)?;
+        self.hash_range(
+            &mut hasher,
+            self.header.filesystem_offset as u64,
+            self.header.filesystem_size as u64,
+        )?;
+
+        Ok(hasher.finish())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+pub(crate) mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    pub(crate) const DOL_OFFSET: usize = 0x3000;
+    const FST_OFFSET: usize = 0x4000;
+    pub(crate) const ENTRY: u32 = 0x8000_3100;
+
+    fn write_u32(image: &mut [u8], offset: usize, value: u32) {
+        image[offset..][..4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// Builds a minimal image with a valid .dol at [`DOL_OFFSET`] and a filesystem containing a
+    /// single file, with the given name, pointing to it.
+    pub(crate) fn image(bootfile_offset: u32, fst_name: &str) -> Vec<u8> {
+        let mut image = vec![0; 0x5000];
+
+        // header
+        image[..6].copy_from_slice(b"GTSE01");
+        write_u32(&mut image, 0x1C, 0xC233_9F3D);
+        write_u32(&mut image, 0x420, bootfile_offset);
+        write_u32(&mut image, 0x424, FST_OFFSET as u32);
+        write_u32(&mut image, 0x428, 0x100);
+
+        // dol: a single text section containing the entrypoint
+        let dol = DOL_OFFSET;
+        write_u32(&mut image, dol, 0x100);
+        write_u32(&mut image, dol + 0x48, ENTRY);
+        write_u32(&mut image, dol + 0x90, 0x20);
+        write_u32(&mut image, dol + 0xE0, ENTRY);
+
+        // filesystem: root + one file
+        let fst = FST_OFFSET;
+        image[fst] = 1;
+        write_u32(&mut image, fst + 0x08, 2);
+        write_u32(&mut image, fst + 0x10, DOL_OFFSET as u32);
+        write_u32(&mut image, fst + 0x14, 0x120);
+        image[fst + 0x18..][..fst_name.len()].copy_from_slice(fst_name.as_bytes());
+
+        image
+    }
+
+    #[test]
+    fn header_bootfile() {
+        let mut iso = Iso::new(Cursor::new(image(DOL_OFFSET as u32, "main.dol"))).unwrap();
+        let location = iso.bootfile_location().unwrap();
+        assert_eq!(location.source, BootfileSource::Header);
+        assert_eq!(iso.bootfile().unwrap().entrypoint(), ENTRY);
+    }
+
+    #[test]
+    fn zero_offset_falls_back_to_filesystem() {
+        let mut iso = Iso::new(Cursor::new(image(0, "main.dol"))).unwrap();
+        let location = iso.bootfile_location().unwrap();
+        assert_eq!(location.offset, DOL_OFFSET as u64);
+        assert_eq!(
+            location.source,
+            BootfileSource::Filesystem("main.dol".into())
+        );
+        assert_eq!(iso.bootfile().unwrap().entrypoint(), ENTRY);
+    }
+
+    #[test]
+    fn overlapping_offset_falls_back_to_filesystem() {
+        let mut iso = Iso::new(Cursor::new(image(0x2440, "boot.dol"))).unwrap();
+        let location = iso.bootfile_location().unwrap();
+        assert_eq!(
+            location.source,
+            BootfileSource::Filesystem("boot.dol".into())
+        );
+    }
+
+    #[test]
+    fn invalid_dol_falls_back_to_filesystem() {
+        let mut iso = Iso::new(Cursor::new(image(0x3800, "main.dol"))).unwrap();
+        let location = iso.bootfile_location().unwrap();
+        assert_eq!(
+            location.source,
+            BootfileSource::Filesystem("main.dol".into())
+        );
+    }
+
+    #[test]
+    fn fingerprint_ignores_file_data() {
+        let fingerprint = |image: Vec<u8>| Iso::new(Cursor::new(image)).unwrap().fingerprint();
+
+        let base = fingerprint(image(DOL_OFFSET as u32, "main.dol")).unwrap();
+        assert_eq!(
+            base,
+            fingerprint(image(DOL_OFFSET as u32, "main.dol")).unwrap()
+        );
+
+        // file data is not part of the fingerprint...
+        let mut patched = image(DOL_OFFSET as u32, "main.dol");
+        patched[DOL_OFFSET + 0x100] = 0xFF;
+        assert_eq!(base, fingerprint(patched).unwrap());
+
+        // ...but the header and the filesystem table are
+        let mut patched = image(DOL_OFFSET as u32, "main.dol");
+        patched[0x20] = b'X';
+        assert_ne!(base, fingerprint(patched).unwrap());
+        assert_ne!(
+            base,
+            fingerprint(image(DOL_OFFSET as u32, "boot.dol")).unwrap()
+        );
+
+        // a truncated filesystem table is an error
+        let mut truncated = image(DOL_OFFSET as u32, "main.dol");
+        truncated.truncate(FST_OFFSET + 0x80);
+        assert!(matches!(fingerprint(truncated), Err(ReadError::Io { .. })));
+    }
+
+    const BANNER_OFFSET: usize = 0x5000;
+
+    /// Builds a banner of the given kind whose first description has the given title and maker.
+    fn banner_file(magic: &[u8; 4], descriptions: usize, title: &[u8], maker: &[u8]) -> Vec<u8> {
+        let mut banner = vec![0; 0x1820 + 0x140 * descriptions];
+        banner[..4].copy_from_slice(magic);
+        banner[0x1860..][..title.len()].copy_from_slice(title);
+        banner[0x18A0..][..maker.len()].copy_from_slice(maker);
+
+        banner
+    }
+
+    /// Builds an image with the given country codes and banner. The banner is in the root of the
+    /// filesystem, which also has a subdirectory holding a decoy banner file.
+    fn image_with_banner(country: u8, bi2_country: u32, banner: &[u8]) -> Vec<u8> {
+        let mut image = image(DOL_OFFSET as u32, "main.dol");
+        image[3] = country;
+        image[0x20..][..11].copy_from_slice(b"HEADER NAME");
+        write_u32(&mut image, 0x458, bi2_country);
+
+        // filesystem: root, a directory, the decoy in it and then the banner
+        let fst = FST_OFFSET;
+        image[fst..][..0x100].fill(0);
+        image[fst] = 1;
+        write_u32(&mut image, fst + 0x08, 4);
+
+        image[fst + 0x0C] = 1;
+        write_u32(&mut image, fst + 0x14, 3);
+
+        write_u32(&mut image, fst + 0x18, 4);
+        write_u32(&mut image, fst + 0x1C, DOL_OFFSET as u32);
+        write_u32(&mut image, fst + 0x20, 0x120);
+
+        write_u32(&mut image, fst + 0x24, 4);
+        write_u32(&mut image, fst + 0x28, BANNER_OFFSET as u32);
+        write_u32(&mut image, fst + 0x2C, banner.len() as u32);
+
+        image[fst + 0x30..][..16].copy_from_slice(b"sub\0opening.bnr\0");
+
+        image.resize(BANNER_OFFSET, 0);
+        image.extend_from_slice(banner);
+        image
+    }
+
+    #[test]
+    fn metadata_from_banner() {
+        let banner = banner_file(b"BNR1", 1, b"Test Title  ", b"Test Maker");
+        let image = image_with_banner(b'E', 1, &banner);
+        let metadata = Iso::new(Cursor::new(image)).unwrap().metadata().unwrap();
+
+        assert_eq!(
+            metadata,
+            TitleMetadata {
+                game_code: "GTSE".into(),
+                maker: "Test Maker".into(),
+                title: "Test Title".into(),
+                region: Some(Region::Usa),
+                disk_id: 0,
+                version: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn metadata_regions_and_encodings() {
+        // PAL titles have language specific country codes, so bi2 tells the region
+        let banner = banner_file(b"BNR2", 6, b"Caf\xE9", b"");
+        let disk = image_with_banner(b'D', 2, &banner);
+        let metadata = Iso::new(Cursor::new(disk)).unwrap().metadata().unwrap();
+        assert_eq!(metadata.region, Some(Region::Pal));
+        assert_eq!(metadata.title, "Café");
+        assert_eq!(metadata.maker, "01");
+
+        // japanese titles use Shift JIS
+        let banner = banner_file(b"BNR1", 1, b"\x83\x5B\x83\x8B\x83\x5F", b"");
+        let disk = image_with_banner(b'J', 0, &banner);
+        let metadata = Iso::new(Cursor::new(disk)).unwrap().metadata().unwrap();
+        assert_eq!(metadata.region, Some(Region::Japan));
+        assert_eq!(metadata.title, "ゼルダ");
+
+        // without a (valid) banner, names come from the header
+        let disk = image_with_banner(b'X', 7, b"junk");
+        let metadata = Iso::new(Cursor::new(disk)).unwrap().metadata().unwrap();
+        assert_eq!(metadata.region, None);
+        assert_eq!(metadata.title, "HEADER NAME");
+        assert_eq!(metadata.maker, "01");
+
+        let mut iso = Iso::new(Cursor::new(image(DOL_OFFSET as u32, "main.dol"))).unwrap();
+        assert!(iso.banner().unwrap().is_none());
+        assert_eq!(iso.metadata().unwrap().game_code, "GTSE");
+    }
+
+    #[test]
+    fn missing_fallback_is_an_error() {
+        let mut iso = Iso::new(Cursor::new(image(0, "game.dol"))).unwrap();
+        let err = iso.bootfile_location().unwrap_err();
+        assert!(matches!(
+            err,
+            BootfileError::NotFound {
+                reason: BootfileRejection::ZeroOffset
+            }
+        ));
+    }
 }
@@ -1,5 +1,7 @@
 #![allow(clippy::needless_raw_strings)]
 
+use alloc::vec::Vec;
+
 use binrw::{BinRead, BinWrite, binread};
 
 #[derive(Debug, BinRead, BinWrite)]
@@ -10,6 +12,7 @@ pub struct Root {
     pub name_offset: u32,
     /// Entry count in this filesystem, including root.
     #[brw(pad_before = 4)]
+    #[br(assert(entry_count >= 1, "filesystem has no root entry"))]
     pub entry_count: u32,
 }
 
@@ -59,8 +62,190 @@ pub enum Entry {
 #[brw(big, stream = s)]
 pub struct FileSystem {
     pub root: Root,
-    #[br(calc = s.stream_position().unwrap() as u32 + (root.entry_count - 1) * 0xC)]
+    #[br(calc = (s.stream_position().unwrap() as u32)
+        .saturating_add((root.entry_count - 1).saturating_mul(0xC)))]
     pub strings_offset: u32,
     #[br(count = root.entry_count - 1)]
     pub entries: Vec<Entry>,
 }
+
+impl FileSystem {
+    /// Returns the position of the name with the given offset relative to the strings table.
+    pub fn name_position(&self, name_offset: u32) -> u64 {
+        self.strings_offset as u64 + name_offset as u64
+    }
+
+    /// Returns the entry with the given index. The root directory, at index 0, has no entry.
+    pub fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index.checked_sub(1)?)
+    }
+
+    /// Returns the index one past the last entry inside the directory with the given index, or
+    /// `None` if it's not a directory.
+    fn end_index(&self, index: usize) -> Option<usize> {
+        let end = match index {
+            0 => self.root.entry_count,
+            _ => match self.entry(index)? {
+                Entry::File(_) => return None,
+                Entry::Directory(dir) => dir.end_index,
+            },
+        };
+
+        Some((end as usize).min(self.entries.len() + 1))
+    }
+
+    /// Returns an iterator over the indices of the immediate children of the directory with the
+    /// given index, in order. Empty if it's not a directory.
+    pub fn children(&self, dir_index: usize) -> impl Iterator<Item = usize> + '_ {
+        let end = self.end_index(dir_index).unwrap_or(0);
+        let mut next = dir_index.saturating_add(1);
+        core::iter::from_fn(move || {
+            if next >= end {
+                return None;
+            }
+
+            // skip over the contents of subdirectories
+            let current = next;
+            next = self
+                .end_index(current)
+                .map_or(current + 1, |end| end.max(current + 1));
+
+            Some(current)
+        })
+    }
+
+    /// Returns the index of the directory containing the entry with the given index, or `None`
+    /// for the root, indices out of range and directories whose parent does not precede them.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        match self.entry(index)? {
+            Entry::Directory(dir) => Some(dir.parent_index as usize).filter(|&p| p < index),
+            // the innermost directory before the file which still contains it
+            Entry::File(_) => Some(
+                (1..index)
+                    .rev()
+                    .find(|&i| self.end_index(i).is_some_and(|end| end > index))
+                    .unwrap_or(0),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use binrw::io::Cursor;
+
+    use super::*;
+
+    fn file() -> Entry {
+        Entry::File(FileEntry {
+            offset: 0,
+            name_offset: 0,
+            data_offset: 0,
+            data_length: 0,
+        })
+    }
+
+    fn dir(parent_index: u32, end_index: u32) -> Entry {
+        Entry::Directory(DirectoryEntry {
+            offset: 0,
+            name_offset: 0,
+            parent_index,
+            end_index,
+        })
+    }
+
+    /// ```text
+    /// 0 /
+    /// 1 ├ file
+    /// 2 ├ dir/
+    /// 3 │ ├ file
+    /// 4 │ ├ empty/
+    /// 5 │ ├ nested/
+    /// 6 │ │ └ file
+    /// 7 │ └ file
+    /// 8 └ file
+    /// ```
+    fn filesystem() -> FileSystem {
+        FileSystem {
+            root: Root {
+                name_offset: 0,
+                entry_count: 9,
+            },
+            strings_offset: 0,
+            entries: vec![
+                file(),
+                dir(0, 8),
+                file(),
+                dir(2, 5),
+                dir(2, 7),
+                file(),
+                file(),
+                file(),
+            ],
+        }
+    }
+
+    #[test]
+    fn children() {
+        let fs = filesystem();
+        let children = |index| fs.children(index).collect::<Vec<_>>();
+
+        assert_eq!(children(0), [1, 2, 8]);
+        assert_eq!(children(2), [3, 4, 5, 7]);
+        assert_eq!(children(4), []);
+        assert_eq!(children(5), [6]);
+        assert_eq!(children(1), []);
+        assert_eq!(children(9), []);
+    }
+
+    #[test]
+    fn parent() {
+        let fs = filesystem();
+        let parents = (0..10).map(|index| fs.parent(index)).collect::<Vec<_>>();
+
+        assert_eq!(
+            parents,
+            [
+                None,
+                Some(0),
+                Some(0),
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(5),
+                Some(2),
+                Some(0),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_indices() {
+        let fs = filesystem();
+        assert_eq!(fs.children(usize::MAX).count(), 0);
+        assert_eq!(fs.parent(usize::MAX), None);
+
+        let mut fs = filesystem();
+        fs.entries[1] = dir(0x1960, 8);
+        assert_eq!(fs.parent(2), None);
+    }
+
+    /// Root entry with the given entry count and no other entries.
+    fn root(entry_count: u32) -> [u8; 12] {
+        let mut root = [0; 12];
+        root[0] = 1;
+        root[8..].copy_from_slice(&entry_count.to_be_bytes());
+        root
+    }
+
+    #[test]
+    fn bogus_entry_counts_are_errors() {
+        // no root, and more entries than the strings table offset can fit
+        for count in [0, 0x2000_0000] {
+            assert!(FileSystem::read(&mut Cursor::new(root(count))).is_err());
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! A memory mapped backend for .iso files.
+//!
+//! Mapping the image lets reads copy straight from the page cache, instead of going through a
+//! [`BufReader`](std::io::BufReader) (or a prefetcher) and then being copied again.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use easyerr::{Error, ResultExt};
+use memmap2::Mmap;
+
+use super::Iso;
+
+#[derive(Debug, Error)]
+pub enum MmapError {
+    #[error(transparent)]
+    Open { source: std::io::Error },
+    #[error("failed to map the image into memory: {source}")]
+    Map { source: std::io::Error },
+    #[error(transparent)]
+    Header { source: binrw::Error },
+}
+
+/// A [`Read`] + [`Seek`] view of a memory mapped file.
+///
+/// The file is expected to keep its size while it is mapped. Since accessing pages past the end of
+/// a truncated file faults, every access first checks the current length of the file and fails
+/// with an error if it changed.
+#[derive(Debug)]
+pub struct MmapReader {
+    file: File,
+    map: Mmap,
+    position: u64,
+}
+
+impl MmapReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapError> {
+        let file = File::open(path).context(MmapCtx::Open)?;
+
+        // SAFETY: the mapping is read only and its length is checked against the file on every
+        // access, so a file which is modified concurrently is detected instead of faulting.
+        let map = unsafe { Mmap::map(&file) }.context(MmapCtx::Map)?;
+
+        Ok(Self {
+            file,
+            map,
+            position: 0,
+        })
+    }
+
+    /// Length of the mapping, in bytes.
+    pub fn len(&self) -> u64 {
+        self.map.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Checks that the file still has the length it had when it was mapped.
+    fn check(&self) -> std::io::Result<()> {
+        let len = self.file.metadata()?.len();
+        if len != self.len() {
+            return Err(std::io::Error::other(format!(
+                "image changed size while mapped (from {} to {len} bytes)",
+                self.len()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the data in the file starting at `offset` and with at most `len` bytes, without
+    /// copying it. The slice is shorter than `len` only if the end of the file was reached.
+    pub fn slice(&self, offset: u64, len: usize) -> std::io::Result<&[u8]> {
+        self.check()?;
+
+        let start = offset.min(self.len()) as usize;
+        let end = start.saturating_add(len).min(self.map.len());
+        Ok(&self.map[start..end])
+    }
+}
+
+impl Read for MmapReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let data = self.slice(self.position, buf.len())?;
+        let len = data.len();
+        buf[..len].copy_from_slice(data);
+
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for MmapReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        let Some(new) = new else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        };
+
+        self.position = new;
+        Ok(new)
+    }
+}
+
+/// A GameCube .iso file which is memory mapped instead of read through a buffered reader.
+pub type IsoMmap = Iso<MmapReader>;
+
+impl Iso<MmapReader> {
+    /// Maps the .iso at `path` into memory. Mapping might not be supported by every platform or
+    /// filesystem, in which case callers should fall back to a buffered reader.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapError> {
+        let reader = MmapReader::open(path)?;
+        Self::new(reader).context(MmapCtx::Header)
+    }
+
+    /// Returns the data in the ISO starting at `offset` and with at most `len` bytes, without
+    /// copying it. The slice is shorter than `len` only if the end of the ISO was reached.
+    pub fn slice(&self, offset: u64, len: usize) -> std::io::Result<&[u8]> {
+        self.reader.slice(offset, len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::iso::test::{DOL_OFFSET, ENTRY, image};
+
+    /// A file in the temporary directory which is removed once dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("disks-mmap-{}-{name}", std::process::id()));
+            std::fs::write(&path, data).unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn matches_buffered_reads() {
+        let data = image(DOL_OFFSET as u32, "main.dol");
+        let file = TempFile::new("reads", &data);
+
+        let mut mapped = IsoMmap::open(&file.0).unwrap();
+        let mut buffered = Iso::new(Cursor::new(data.clone())).unwrap();
+        assert_eq!(mapped.size(), buffered.size());
+        assert_eq!(mapped.bootfile().unwrap().entrypoint(), ENTRY);
+        assert_eq!(
+            mapped.fingerprint().unwrap(),
+            buffered.fingerprint().unwrap()
+        );
+
+        for (offset, len) in [(0, 0x20), (0x3000, 0x120), (0x4FF0, 0x20), (0x6000, 0x10)] {
+            let mut a = vec![0; len];
+            let mut b = vec![0; len];
+            let read = mapped.read(offset, &mut a).unwrap();
+            assert_eq!(read, buffered.read(offset, &mut b).unwrap());
+            assert_eq!(a, b);
+
+            let slice = mapped.slice(offset, len).unwrap();
+            assert_eq!(slice, &a[..read]);
+        }
+    }
+
+    #[test]
+    fn size_changes_are_errors() {
+        let data = image(DOL_OFFSET as u32, "main.dol");
+        let file = TempFile::new("resize", &data);
+        let iso = IsoMmap::open(&file.0).unwrap();
+
+        let handle = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&file.0)
+            .unwrap();
+        handle.set_len(data.len() as u64 / 2).unwrap();
+        assert!(iso.slice(0x4000, 0x100).is_err());
+
+        handle.set_len(data.len() as u64 * 2).unwrap();
+        assert!(iso.slice(0, 0x20).is_err());
+
+        handle.set_len(data.len() as u64).unwrap();
+        assert!(iso.slice(0, 0x20).is_ok());
+    }
+}
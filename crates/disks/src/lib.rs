@@ -1,11 +1,44 @@
 //! A collection of parsers for GameCube/Wii file formats.
+//!
+//! Without the default `std` feature, only the `.dol`, apploader and `.iso` header definitions are
+//! available, which only need `alloc`. This allows using them from guest code.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 pub mod apploader;
+#[cfg(feature = "std")]
+pub mod banner;
 pub mod dol;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "std")]
+pub mod image;
 pub mod iso;
+#[cfg(feature = "std")]
+pub mod memcard;
+#[cfg(feature = "std")]
 pub mod rvz;
 
 pub use binrw;
+#[cfg(feature = "std")]
+pub use image::DiscImage;
+
+/// Reads `len` bytes, growing the buffer as they're read instead of allocating all of it upfront,
+/// so that a bogus length in a small file fails with an error instead of exhausting memory.
+#[binrw::parser(reader)]
+pub(crate) fn read_bytes(len: usize) -> binrw::BinResult<alloc::vec::Vec<u8>> {
+    const CHUNK_LEN: usize = 1 << 16;
+
+    let mut bytes = alloc::vec::Vec::new();
+    while bytes.len() < len {
+        let start = bytes.len();
+        bytes.resize(start + (len - start).min(CHUNK_LEN), 0);
+        reader.read_exact(&mut bytes[start..])?;
+    }
+
+    Ok(bytes)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Console {
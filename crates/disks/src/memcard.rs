@@ -0,0 +1,671 @@
+//! Memory card images and `.gci` save files.
+//!
+//! A card is made of 8 KiB blocks. The first five are system blocks: the header, the directory, a
+//! backup of the directory, the block allocation table (BAT) and a backup of it. Both the
+//! directory and the BAT carry an update counter and a pair of checksums, and the valid copy with
+//! the highest counter is the current one. Saves occupy the remaining blocks, which are chained
+//! through the BAT.
+//!
+//! A `.gci` file is a single save: its directory entry followed by the contents of its blocks.
+
+use std::ops::Range;
+
+use easyerr::Error;
+
+pub const BLOCK_LEN: usize = 0x2000;
+pub const DIR_ENTRY_LEN: usize = 0x40;
+/// Amount of entries in the directory.
+pub const DIR_ENTRIES: usize = 127;
+/// Width and height of the icons of a save.
+pub const ICON_SIZE: usize = 32;
+
+/// Index of the first block that holds save data.
+const FIRST_DATA_BLOCK: u16 = 5;
+/// Maximum amount of blocks in a card (16 megabits).
+const MAX_BLOCKS: usize = 2048;
+
+const HEADER_BLOCK: usize = 0;
+const DIR_BLOCKS: [usize; 2] = [1, 2];
+const BAT_BLOCKS: [usize; 2] = [3, 4];
+
+const HEADER_SIZE_MBITS: usize = 0x22;
+const HEADER_CHECKED: Range<usize> = 0x000..0x1FC;
+const HEADER_CHECKSUMS: usize = 0x1FC;
+
+const DIR_COUNTER: usize = 0x1FFA;
+const DIR_CHECKED: Range<usize> = 0x0000..0x1FFC;
+const DIR_CHECKSUMS: usize = 0x1FFC;
+
+const BAT_CHECKSUMS: usize = 0x00;
+const BAT_CHECKED: Range<usize> = 0x0004..BLOCK_LEN;
+const BAT_COUNTER: usize = 0x04;
+const BAT_FREE_COUNT: usize = 0x06;
+const BAT_LAST_ALLOCATED: usize = 0x08;
+const BAT_MAP: usize = 0x0A;
+
+/// BAT entry of a free block.
+const BAT_FREE: u16 = 0x0000;
+/// BAT entry of the last block of a save.
+const BAT_LAST: u16 = 0xFFFF;
+
+const BANNER_PIXELS: usize = 96 * 32;
+const ICON_PIXELS: usize = ICON_SIZE * ICON_SIZE;
+const PALETTE_LEN: usize = 0x200;
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..][..2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..][..4].try_into().unwrap())
+}
+
+/// Reads a null terminated string of at most `len` bytes.
+fn read_str(data: &[u8], len: usize) -> String {
+    let data = &data[..len.min(data.len())];
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Computes the checksum and the inverse checksum of `data`, summed as big endian words.
+fn checksums(data: &[u8]) -> (u16, u16) {
+    let (sum, inverse) = data
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .fold((0u16, 0u16), |(sum, inverse), word| {
+            (sum.wrapping_add(word), inverse.wrapping_add(!word))
+        });
+
+    // 0xFFFF is reserved for erased data
+    let fix = |sum: u16| if sum == 0xFFFF { 0 } else { sum };
+    (fix(sum), fix(inverse))
+}
+
+fn checksums_valid(block: &[u8], checked: Range<usize>, at: usize) -> bool {
+    let (sum, inverse) = checksums(&block[checked]);
+    read_u16(block, at) == sum && read_u16(block, at + 2) == inverse
+}
+
+fn update_checksums(block: &mut [u8], checked: Range<usize>, at: usize) {
+    let (sum, inverse) = checksums(&block[checked]);
+    write_u16(block, at, sum);
+    write_u16(block, at + 2, inverse);
+}
+
+#[derive(Debug, Error)]
+pub enum CardError {
+    #[error("card length of {len} bytes is not a valid amount of blocks")]
+    InvalidLength { len: usize },
+    #[error("header checksums do not match (is the card formatted?)")]
+    CorruptHeader,
+    #[error("both copies of the directory have bad checksums")]
+    CorruptDirectory,
+    #[error("both copies of the block allocation table have bad checksums")]
+    CorruptBat,
+    #[error("block chain of the file at entry {index} is broken")]
+    BrokenChain { index: usize },
+    #[error("no file at entry {index}")]
+    NoFile { index: usize },
+    #[error("gci file has {len} bytes, but its entry needs {expected}")]
+    TruncatedGci { len: usize, expected: usize },
+    #[error("a file named {name} from {game} already exists")]
+    FileExists { name: String, game: String },
+    #[error("directory is full")]
+    DirectoryFull,
+    #[error("file needs {needed} blocks, but only {free} are free")]
+    NotEnoughSpace { needed: u16, free: u16 },
+}
+
+/// An icon of a save, still encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon<'a> {
+    /// An RGB5A3 texture.
+    Rgb5a3(&'a [u8]),
+    /// A C8 texture, with a palette of RGB5A3 entries.
+    Ci8 {
+        indices: &'a [u8],
+        palette: &'a [u8],
+    },
+}
+
+/// An entry of the directory of a card, describing a save.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry(pub [u8; DIR_ENTRY_LEN]);
+
+impl DirEntry {
+    /// An unused entry.
+    pub const EMPTY: Self = Self([0xFF; DIR_ENTRY_LEN]);
+
+    pub fn is_empty(&self) -> bool {
+        self.game_code() == [0xFF; 4]
+    }
+
+    pub fn game_code(&self) -> [u8; 4] {
+        self.0[0x00..0x04].try_into().unwrap()
+    }
+
+    pub fn maker_code(&self) -> [u8; 2] {
+        self.0[0x04..0x06].try_into().unwrap()
+    }
+
+    /// The game code followed by the maker code (e.g. `GALE01`).
+    pub fn game_id(&self) -> String {
+        String::from_utf8_lossy(&self.0[0x00..0x06]).into_owned()
+    }
+
+    pub fn filename(&self) -> String {
+        read_str(&self.0[0x08..], 32)
+    }
+
+    pub fn banner_format(&self) -> u8 {
+        self.0[0x07] & 0b11
+    }
+
+    /// Time of the last modification, in seconds since 2000-01-01.
+    pub fn modified(&self) -> u32 {
+        read_u32(&self.0, 0x28)
+    }
+
+    /// Offset of the banner and icons in the data of the save.
+    pub fn image_offset(&self) -> u32 {
+        read_u32(&self.0, 0x2C)
+    }
+
+    /// Format of each of the 8 icon frames, 2 bits each.
+    pub fn icon_formats(&self) -> u16 {
+        read_u16(&self.0, 0x30)
+    }
+
+    pub fn first_block(&self) -> u16 {
+        read_u16(&self.0, 0x36)
+    }
+
+    fn set_first_block(&mut self, block: u16) {
+        write_u16(&mut self.0, 0x36, block);
+    }
+
+    pub fn block_count(&self) -> u16 {
+        read_u16(&self.0, 0x38)
+    }
+
+    /// Offset of the two comment strings in the data of the save.
+    pub fn comments_offset(&self) -> u32 {
+        read_u32(&self.0, 0x3C)
+    }
+
+    /// Whether this entry describes the same file as `other`.
+    fn same_file(&self, other: &Self) -> bool {
+        self.0[0x00..0x06] == other.0[0x00..0x06] && self.0[0x08..0x28] == other.0[0x08..0x28]
+    }
+
+    /// Finds the first icon frame in `data`, the contents of the save.
+    pub fn icon<'a>(&self, data: &'a [u8]) -> Option<Icon<'a>> {
+        let mut offset = self.image_offset() as usize;
+        offset += match self.banner_format() {
+            1 => BANNER_PIXELS + PALETTE_LEN,
+            2 => BANNER_PIXELS * 2,
+            _ => 0,
+        };
+
+        // the shared palette comes after every frame
+        let formats = self.icon_formats();
+        let format = |frame: usize| (formats >> (2 * frame)) & 0b11;
+        let shared_palette = offset
+            + (0..8)
+                .map(|frame| match format(frame) {
+                    1 => ICON_PIXELS,
+                    2 => ICON_PIXELS * 2,
+                    3 => ICON_PIXELS + PALETTE_LEN,
+                    _ => 0,
+                })
+                .sum::<usize>();
+
+        let indices = data.get(offset..offset + ICON_PIXELS);
+        match format(0) {
+            1 => Some(Icon::Ci8 {
+                indices: indices?,
+                palette: data.get(shared_palette..shared_palette + PALETTE_LEN)?,
+            }),
+            2 => data.get(offset..offset + ICON_PIXELS * 2).map(Icon::Rgb5a3),
+            3 => Some(Icon::Ci8 {
+                indices: indices?,
+                palette: data.get(offset + ICON_PIXELS..offset + ICON_PIXELS + PALETTE_LEN)?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Reads the two comment strings (usually the name of the game and a description of the
+    /// save) from `data`, the contents of the save.
+    pub fn comments(&self, data: &[u8]) -> Option<[String; 2]> {
+        let offset = self.comments_offset() as usize;
+        let comments = data.get(offset..offset + 64)?;
+        Some([read_str(comments, 32), read_str(&comments[32..], 32)])
+    }
+}
+
+/// A memory card image.
+pub struct Card {
+    data: Vec<u8>,
+    /// Block of the current copy of the directory.
+    dir: usize,
+    /// Block of the current copy of the BAT.
+    bat: usize,
+}
+
+impl Card {
+    /// Opens a card image, checking its header and picking the current copies of the directory
+    /// and the BAT. A copy with bad checksums is never used.
+    pub fn new(data: Vec<u8>) -> Result<Self, CardError> {
+        let blocks = data.len() / BLOCK_LEN;
+        if !data.len().is_multiple_of(BLOCK_LEN)
+            || blocks <= FIRST_DATA_BLOCK as usize
+            || blocks > MAX_BLOCKS
+        {
+            return Err(CardError::InvalidLength { len: data.len() });
+        }
+
+        let block = |i: usize| &data[i * BLOCK_LEN..][..BLOCK_LEN];
+        if !checksums_valid(block(HEADER_BLOCK), HEADER_CHECKED, HEADER_CHECKSUMS) {
+            return Err(CardError::CorruptHeader);
+        }
+
+        let current = |copies: [usize; 2], checked: Range<usize>, checksums: usize, counter| {
+            copies
+                .into_iter()
+                .filter(|&i| checksums_valid(block(i), checked.clone(), checksums))
+                .max_by_key(|&i| read_u16(block(i), counter))
+        };
+
+        let dir = current(DIR_BLOCKS, DIR_CHECKED, DIR_CHECKSUMS, DIR_COUNTER)
+            .ok_or(CardError::CorruptDirectory)?;
+        let bat = current(BAT_BLOCKS, BAT_CHECKED, BAT_CHECKSUMS, BAT_COUNTER)
+            .ok_or(CardError::CorruptBat)?;
+
+        Ok(Self { data, dir, bat })
+    }
+
+    /// Creates a freshly formatted card with the given size in megabits (e.g. 4 for a 59 block
+    /// card).
+    pub fn format(size_mbits: u16) -> Self {
+        let len = (size_mbits as usize) << 17;
+        let blocks = (len / BLOCK_LEN) as u16;
+        assert!(blocks > FIRST_DATA_BLOCK && blocks as usize <= MAX_BLOCKS);
+
+        let mut data = vec![0xFF; len];
+        let (system, _) = data.split_at_mut(FIRST_DATA_BLOCK as usize * BLOCK_LEN);
+        let mut blocks_mut = system.chunks_exact_mut(BLOCK_LEN);
+
+        let header = blocks_mut.next().unwrap();
+        header[..0x26].fill(0);
+        write_u16(header, HEADER_SIZE_MBITS, size_mbits);
+        update_checksums(header, HEADER_CHECKED, HEADER_CHECKSUMS);
+
+        for dir in blocks_mut.by_ref().take(2) {
+            write_u16(dir, DIR_COUNTER, 0);
+            update_checksums(dir, DIR_CHECKED, DIR_CHECKSUMS);
+        }
+
+        for bat in blocks_mut {
+            bat.fill(0);
+            write_u16(bat, BAT_FREE_COUNT, blocks - FIRST_DATA_BLOCK);
+            write_u16(bat, BAT_LAST_ALLOCATED, FIRST_DATA_BLOCK - 1);
+            update_checksums(bat, BAT_CHECKED, BAT_CHECKSUMS);
+        }
+
+        Self {
+            data,
+            dir: DIR_BLOCKS[0],
+            bat: BAT_BLOCKS[0],
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn block(&self, index: usize) -> &[u8] {
+        &self.data[index * BLOCK_LEN..][..BLOCK_LEN]
+    }
+
+    fn block_mut(&mut self, index: usize) -> &mut [u8] {
+        &mut self.data[index * BLOCK_LEN..][..BLOCK_LEN]
+    }
+
+    fn block_count(&self) -> u16 {
+        (self.data.len() / BLOCK_LEN) as u16
+    }
+
+    /// Amount of blocks which are not used by any save.
+    pub fn free_blocks(&self) -> u16 {
+        read_u16(self.block(self.bat), BAT_FREE_COUNT)
+    }
+
+    /// The entry at the given index of the directory.
+    pub fn entry(&self, index: usize) -> DirEntry {
+        let dir = self.block(self.dir);
+        DirEntry(
+            dir[index * DIR_ENTRY_LEN..][..DIR_ENTRY_LEN]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// The used entries of the directory, along with their indices.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, DirEntry)> {
+        (0..DIR_ENTRIES)
+            .map(|i| (i, self.entry(i)))
+            .filter(|(_, entry)| !entry.is_empty())
+    }
+
+    /// Follows the BAT from the first block of the file at `index`, returning its blocks.
+    fn chain(&self, index: usize) -> Result<Vec<u16>, CardError> {
+        let entry = self.entry(index);
+        if entry.is_empty() {
+            return Err(CardError::NoFile { index });
+        }
+
+        let bat = self.block(self.bat);
+        let data_blocks = FIRST_DATA_BLOCK..self.block_count();
+        let broken = CardError::BrokenChain { index };
+
+        let mut blocks = Vec::with_capacity(entry.block_count() as usize);
+        let mut current = entry.first_block();
+        for i in 0..entry.block_count() {
+            if !data_blocks.contains(&current) || blocks.contains(&current) {
+                return Err(broken);
+            }
+
+            blocks.push(current);
+            let next = read_u16(bat, BAT_MAP + 2 * (current - FIRST_DATA_BLOCK) as usize);
+            let last = i + 1 == entry.block_count();
+            match next {
+                BAT_LAST if last => (),
+                BAT_LAST | BAT_FREE => return Err(broken),
+                _ if last => return Err(broken),
+                _ => current = next,
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Reads the contents of the file at `index`.
+    pub fn read_file(&self, index: usize) -> Result<Vec<u8>, CardError> {
+        Ok(self
+            .chain(index)?
+            .into_iter()
+            .flat_map(|block| self.block(block as usize))
+            .copied()
+            .collect())
+    }
+
+    /// Exports the file at `index` as a `.gci` file.
+    pub fn export(&self, index: usize) -> Result<Vec<u8>, CardError> {
+        let mut gci = self.entry(index).0.to_vec();
+        gci.extend(self.read_file(index)?);
+
+        Ok(gci)
+    }
+
+    /// Writes the given directory and BAT over their backup copies, which then become the
+    /// current ones. The previous copies are kept as backups.
+    fn commit(&mut self, mut dir: Vec<u8>, mut bat: Vec<u8>) {
+        let counter = read_u16(&dir, DIR_COUNTER).wrapping_add(1);
+        write_u16(&mut dir, DIR_COUNTER, counter);
+        update_checksums(&mut dir, DIR_CHECKED, DIR_CHECKSUMS);
+
+        let counter = read_u16(&bat, BAT_COUNTER).wrapping_add(1);
+        write_u16(&mut bat, BAT_COUNTER, counter);
+        update_checksums(&mut bat, BAT_CHECKED, BAT_CHECKSUMS);
+
+        self.dir = DIR_BLOCKS[0] + DIR_BLOCKS[1] - self.dir;
+        self.bat = BAT_BLOCKS[0] + BAT_BLOCKS[1] - self.bat;
+        self.block_mut(self.dir).copy_from_slice(&dir);
+        self.block_mut(self.bat).copy_from_slice(&bat);
+    }
+
+    /// Imports a `.gci` file into a free entry of the directory, returning its index.
+    pub fn import(&mut self, gci: &[u8]) -> Result<usize, CardError> {
+        let Some(entry) = gci.get(..DIR_ENTRY_LEN) else {
+            return Err(CardError::TruncatedGci {
+                len: gci.len(),
+                expected: DIR_ENTRY_LEN,
+            });
+        };
+
+        let mut entry = DirEntry(entry.try_into().unwrap());
+        let needed = entry.block_count();
+        let expected = DIR_ENTRY_LEN + needed as usize * BLOCK_LEN;
+        if gci.len() < expected {
+            return Err(CardError::TruncatedGci {
+                len: gci.len(),
+                expected,
+            });
+        }
+
+        if self.entries().any(|(_, e)| e.same_file(&entry)) {
+            return Err(CardError::FileExists {
+                name: entry.filename(),
+                game: entry.game_id(),
+            });
+        }
+
+        let index = (0..DIR_ENTRIES)
+            .find(|&i| self.entry(i).is_empty())
+            .ok_or(CardError::DirectoryFull)?;
+
+        let free = self.free_blocks();
+        if needed > free {
+            return Err(CardError::NotEnoughSpace { needed, free });
+        }
+
+        // allocate blocks after the last allocated one, wrapping around
+        let mut bat = self.block(self.bat).to_vec();
+        let map = |block: u16| BAT_MAP + 2 * (block - FIRST_DATA_BLOCK) as usize;
+        let data_blocks = self.block_count() - FIRST_DATA_BLOCK;
+        let start =
+            read_u16(&bat, BAT_LAST_ALLOCATED).wrapping_sub(FIRST_DATA_BLOCK - 1) % data_blocks;
+        let blocks = (0..data_blocks)
+            .map(|i| FIRST_DATA_BLOCK + (start + i) % data_blocks)
+            .filter(|&block| read_u16(&bat, map(block)) == BAT_FREE)
+            .take(needed as usize)
+            .collect::<Vec<_>>();
+
+        if blocks.len() < needed as usize {
+            return Err(CardError::CorruptBat);
+        }
+
+        let contents = gci[DIR_ENTRY_LEN..expected].chunks_exact(BLOCK_LEN);
+        for (i, (&block, contents)) in blocks.iter().zip(contents).enumerate() {
+            let next = blocks.get(i + 1).copied().unwrap_or(BAT_LAST);
+            write_u16(&mut bat, map(block), next);
+            self.block_mut(block as usize).copy_from_slice(contents);
+        }
+
+        write_u16(&mut bat, BAT_FREE_COUNT, free - needed);
+        if let Some(&last) = blocks.last() {
+            write_u16(&mut bat, BAT_LAST_ALLOCATED, last);
+        }
+
+        entry.set_first_block(blocks.first().copied().unwrap_or(BAT_LAST));
+        let mut dir = self.block(self.dir).to_vec();
+        dir[index * DIR_ENTRY_LEN..][..DIR_ENTRY_LEN].copy_from_slice(&entry.0);
+
+        self.commit(dir, bat);
+        Ok(index)
+    }
+
+    /// Deletes the file at `index`, freeing its blocks.
+    pub fn delete(&mut self, index: usize) -> Result<(), CardError> {
+        let blocks = self.chain(index)?;
+
+        let mut bat = self.block(self.bat).to_vec();
+        for &block in &blocks {
+            write_u16(
+                &mut bat,
+                BAT_MAP + 2 * (block - FIRST_DATA_BLOCK) as usize,
+                BAT_FREE,
+            );
+        }
+
+        let free = read_u16(&bat, BAT_FREE_COUNT) + blocks.len() as u16;
+        write_u16(&mut bat, BAT_FREE_COUNT, free);
+
+        let mut dir = self.block(self.dir).to_vec();
+        dir[index * DIR_ENTRY_LEN..][..DIR_ENTRY_LEN].copy_from_slice(&DirEntry::EMPTY.0);
+
+        self.commit(dir, bat);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A `.gci` file with the given name and amount of blocks, whose icon is a single RGB5A3
+    /// frame at the start of its data.
+    fn gci(name: &str, blocks: u16) -> Vec<u8> {
+        let mut entry = DirEntry::EMPTY;
+        entry.0[0x00..0x06].copy_from_slice(b"GALE01");
+        entry.0[0x07] = 0;
+        entry.0[0x08..0x28].fill(0);
+        entry.0[0x08..][..name.len()].copy_from_slice(name.as_bytes());
+        entry.0[0x2C..0x30].copy_from_slice(&0u32.to_be_bytes());
+        entry.0[0x30..0x32].copy_from_slice(&0b10u16.to_be_bytes());
+        entry.0[0x38..0x3A].copy_from_slice(&blocks.to_be_bytes());
+        entry.0[0x3C..0x40].copy_from_slice(&0x800u32.to_be_bytes());
+
+        let mut gci = entry.0.to_vec();
+        for block in 0..blocks {
+            gci.extend(std::iter::repeat_n(block as u8 + 1, BLOCK_LEN));
+        }
+
+        gci[DIR_ENTRY_LEN + 0x800..][..5].copy_from_slice(b"Game\0");
+        gci
+    }
+
+    #[test]
+    fn format_and_open() {
+        let card = Card::new(Card::format(4).into_data()).unwrap();
+        assert_eq!(card.free_blocks(), 59);
+        assert_eq!(card.entries().count(), 0);
+
+        let blank = vec![0xFF; 4 << 17];
+        assert!(matches!(Card::new(blank), Err(CardError::CorruptHeader)));
+        assert!(matches!(
+            Card::new(vec![0; 0x3000]),
+            Err(CardError::InvalidLength { len: 0x3000 })
+        ));
+    }
+
+    #[test]
+    fn import_export_delete() {
+        let mut card = Card::format(4);
+        let first = gci("first", 2);
+        let second = gci("second", 3);
+
+        assert_eq!(card.import(&first).unwrap(), 0);
+        assert_eq!(card.import(&second).unwrap(), 1);
+        assert!(matches!(
+            card.import(&first),
+            Err(CardError::FileExists { .. })
+        ));
+        assert_eq!(card.free_blocks(), 54);
+
+        // survives reopening
+        let card = Card::new(card.into_data()).unwrap();
+        let entries = card.entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].1.filename(), "second");
+        assert_eq!(entries[1].1.game_id(), "GALE01");
+        assert_eq!(entries[1].1.first_block(), 7);
+
+        // exports the same file, except for where it was placed
+        let exported = card.export(1).unwrap();
+        assert_eq!(exported[DIR_ENTRY_LEN..], second[DIR_ENTRY_LEN..]);
+        assert_eq!(exported[..0x36], second[..0x36]);
+
+        let data = card.read_file(1).unwrap();
+        let entry = &entries[1].1;
+        assert_eq!(
+            entry.icon(&data),
+            Some(Icon::Rgb5a3(&data[..ICON_PIXELS * 2]))
+        );
+        assert_eq!(entry.comments(&data).unwrap()[0], "Game");
+
+        // deleting frees the blocks for the next import
+        let mut card = card;
+        card.delete(0).unwrap();
+        assert_eq!(card.free_blocks(), 56);
+        assert!(card.entry(0).is_empty());
+        assert!(matches!(
+            card.delete(0),
+            Err(CardError::NoFile { index: 0 })
+        ));
+
+        let big = gci("big", 56);
+        assert_eq!(card.import(&big).unwrap(), 0);
+        assert_eq!(card.free_blocks(), 0);
+        assert_eq!(card.read_file(0).unwrap(), big[DIR_ENTRY_LEN..]);
+        assert!(matches!(
+            card.import(&gci("more", 1)),
+            Err(CardError::NotEnoughSpace { needed: 1, free: 0 })
+        ));
+    }
+
+    #[test]
+    fn truncated_gci() {
+        let mut card = Card::format(4);
+        let mut file = gci("file", 2);
+        file.truncate(BLOCK_LEN);
+
+        assert!(matches!(
+            card.import(&file),
+            Err(CardError::TruncatedGci { .. })
+        ));
+        assert_eq!(card.entries().count(), 0);
+    }
+
+    #[test]
+    fn corrupt_directory() {
+        let mut card = Card::format(4);
+        card.import(&gci("file", 1)).unwrap();
+
+        // the current copy is corrupt: the previous one (without the file) is used instead
+        let mut data = card.into_data();
+        data[DIR_BLOCKS[1] * BLOCK_LEN] ^= 1;
+        let card = Card::new(data).unwrap();
+        assert_eq!(card.entries().count(), 0);
+
+        // both are corrupt
+        let mut data = card.into_data();
+        data[DIR_BLOCKS[0] * BLOCK_LEN + 0x100] ^= 1;
+        assert!(matches!(Card::new(data), Err(CardError::CorruptDirectory)));
+    }
+
+    #[test]
+    fn broken_chain() {
+        let mut card = Card::format(4);
+        card.import(&gci("file", 2)).unwrap();
+
+        // mark the first block as the last one, even though the file has two
+        let mut bat = card.block(card.bat).to_vec();
+        write_u16(&mut bat, BAT_MAP, BAT_LAST);
+        card.commit(card.block(card.dir).to_vec(), bat);
+
+        assert!(matches!(
+            card.read_file(0),
+            Err(CardError::BrokenChain { index: 0 })
+        ));
+    }
+}
@@ -6,7 +6,7 @@ use std::io::{Cursor, Read, Seek, SeekFrom};
 use binrw::{BinRead, BinResult, binread};
 use easyerr::{Error, ResultExt};
 
-use crate::{Console, apploader, dol, iso};
+use crate::{Console, iso};
 
 /// A SHA1 hash.
 #[derive(Clone, BinRead)]
@@ -632,6 +632,11 @@ where
 
         Ok(out.len() as u64 - remaining)
     }
+
+    /// Opens the disc image contained in this RVZ.
+    pub fn into_iso(self) -> Result<iso::Iso<RvzReader<R>>, binrw::Error> {
+        iso::Iso::new(RvzReader::new(self))
+    }
 }
 
 /// A wrapper around [`Rvz`] providing an implementation of [`Read`] and [`Seek`].
@@ -698,41 +703,3 @@ where
         Ok(self.position)
     }
 }
-
-impl<R> RvzReader<R>
-where
-    R: Read + Seek,
-{
-    pub fn iso_header(&mut self) -> Result<iso::Header, binrw::Error> {
-        self.seek(SeekFrom::Start(0))?;
-        iso::Header::read_be(self)
-    }
-
-    pub fn bootfile(&mut self) -> Result<dol::Dol, binrw::Error> {
-        let header = self.iso_header()?;
-        self.seek(SeekFrom::Start(header.bootfile_offset as u64))?;
-        dol::Dol::read(self)
-    }
-
-    pub fn bootfile_header(&mut self) -> Result<dol::Header, binrw::Error> {
-        let header = self.iso_header()?;
-        self.seek(SeekFrom::Start(header.bootfile_offset as u64))?;
-        dol::Header::read(self)
-    }
-
-    pub fn apploader(&mut self) -> Result<apploader::Apploader, binrw::Error> {
-        self.seek(SeekFrom::Start(0x2440))?;
-        apploader::Apploader::read(self)
-    }
-
-    pub fn apploader_header(&mut self) -> Result<apploader::Header, binrw::Error> {
-        self.seek(SeekFrom::Start(0x2440))?;
-        apploader::Header::read(self)
-    }
-
-    pub fn filesystem(&mut self) -> Result<iso::filesystem::FileSystem, binrw::Error> {
-        let header = self.iso_header()?;
-        self.seek(SeekFrom::Start(header.filesystem_offset as u64))?;
-        iso::filesystem::FileSystem::read(self)
-    }
-}
@@ -0,0 +1,65 @@
+//! Parses headers from constant byte arrays in a `no_std` crate, as guest code would. Run with
+//! `--no-default-features` to check that they don't need `std`.
+#![no_std]
+
+use disks::binrw::BinRead;
+use disks::binrw::io::Cursor;
+use disks::iso::Region;
+use disks::{Console, dol, iso};
+
+const ENTRY: u32 = 0x8000_3100;
+
+/// Returns `buf` with `bytes` written at `offset`.
+const fn put<const N: usize>(mut buf: [u8; N], offset: usize, bytes: &[u8]) -> [u8; N] {
+    let mut i = 0;
+    while i < bytes.len() {
+        buf[offset + i] = bytes[i];
+        i += 1;
+    }
+
+    buf
+}
+
+const DOL_HEADER: [u8; 0x100] = {
+    let header = put([0; 0x100], 0x00, &0x100_u32.to_be_bytes());
+    let header = put(header, 0x48, &ENTRY.to_be_bytes());
+    let header = put(header, 0x90, &0x20_u32.to_be_bytes());
+    put(header, 0xE0, &ENTRY.to_be_bytes())
+};
+
+const ISO_HEADER: [u8; 0x440] = {
+    let header = put([0; 0x440], 0x00, b"GALE01\x00\x01");
+    let header = put(header, 0x1C, &0xC233_9F3D_u32.to_be_bytes());
+    let header = put(header, 0x20, b"Test Title\x00");
+    let header = put(header, 0x420, &0x1_E800_u32.to_be_bytes());
+    put(header, 0x424, &0x4_0000_u32.to_be_bytes())
+};
+
+#[test]
+fn dol_header() {
+    let header = dol::Header::read(&mut Cursor::new(&DOL_HEADER)).unwrap();
+    assert!(header.validate().is_ok());
+    assert_eq!(header.entry, ENTRY);
+    assert_eq!(header.text_sections().count(), 1);
+    assert_eq!(header.data_sections().count(), 0);
+    assert_eq!(header.size(), 0x120);
+}
+
+#[test]
+fn invalid_dol_header() {
+    let header = dol::Header::read(&mut Cursor::new(&[0; 0x100])).unwrap();
+    assert!(matches!(header.validate(), Err(dol::HeaderError::NoText)));
+}
+
+#[test]
+fn iso_header() {
+    let header = iso::Header::read(&mut Cursor::new(&ISO_HEADER)).unwrap();
+    assert_eq!(header.meta.console(), Some(Console::GameCube));
+    assert_eq!(header.meta.region(), Some(Region::Usa));
+    assert_eq!(header.meta.game_code_str().as_deref(), Some("GALE"));
+    assert_eq!(header.meta.maker_code, u16::from_be_bytes(*b"01"));
+    assert_eq!(header.meta.version, 1);
+    assert_eq!(header.meta.game_name.as_slice(), b"Test Title");
+    assert_eq!(header.bootfile_offset, 0x1_E800);
+    assert_eq!(header.filesystem_offset, 0x4_0000);
+}
@@ -1,8 +1,8 @@
 use bitos::BitUtils;
-use lazuli::system::System;
+use lazuli::system::dspi::DspIo;
 
 use crate::ins::CondCode;
-use crate::{Acc40, Ins, Interpreter, Reg, Registers, Status};
+use crate::{Acc40, Ins, Interpreter, MMIO_PAGE, Reg, Registers, Status, short_address};
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum MultiplyMode {
@@ -16,19 +16,21 @@ fn add_carried(lhs: i64, new: i64) -> bool {
     lhs as u64 > new as u64
 }
 
+/// Whether `lhs - rhs` did not borrow, given its result `new`. Both `lhs` and `new` are sign
+/// extended 40-bit values, which compare as 64-bit unsigned values in the same order as their
+/// unsigned 40-bit counterparts.
 #[inline(always)]
 fn sub_carried(lhs: i64, new: i64) -> bool {
     lhs as u64 >= new as u64
 }
 
-#[inline(always)]
-fn add_overflowed(lhs: i64, rhs: i64, new: i64) -> bool {
-    (lhs > 0 && rhs > 0 && new <= 0) || (lhs < 0 && rhs < 0 && new >= 0)
-}
-
+/// Whether `lhs - rhs` overflowed into the 40-bit result `new`, for comparisons which don't store
+/// it. The exact difference is compared instead of checking `lhs + (-rhs)`, since the negation of
+/// the smallest 40-bit value does not fit in 40 bits (e.g. `0 - MIN` overflows). Instructions
+/// storing their result get the same check from [`Acc40::set_checked`].
 #[inline(always)]
 fn sub_overflowed(lhs: i64, rhs: i64, new: i64) -> bool {
-    add_overflowed(lhs, -rhs, new)
+    lhs - rhs != new
 }
 
 /// Rounds a fixed point 24.16 number with ties to even.
@@ -129,31 +131,31 @@ impl Interpreter {
             .set_overflow_fused(self.regs.status.overflow() || self.regs.status.overflow_fused());
     }
 
-    pub fn abs(&mut self, _: &mut System, ins: Ins) {
+    pub fn abs(&mut self, _: &mut DspIo, ins: Ins) {
         let idx = ins.base.bit(11) as usize;
         let old = self.regs.acc40[idx].get();
-        let new = self.regs.acc40[idx].set(old.abs());
+        let (new, overflowed) = self.regs.acc40[idx].set_checked(old.abs());
 
         self.regs.status.set_carry(false);
-        self.regs.status.set_overflow(new == Acc40::MIN);
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn add(&mut self, _: &mut System, ins: Ins) {
+    pub fn add(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = self.regs.acc40[1 - d].get();
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn addarn(&mut self, _: &mut System, ins: Ins) {
+    pub fn addarn(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 2) as usize;
         let s = ins.base.bits(2, 4) as usize;
 
@@ -164,76 +166,74 @@ impl Interpreter {
         self.regs.addressing[d] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn addax(&mut self, _: &mut System, ins: Ins) {
+    pub fn addax(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = self.regs.acc32[s] as i64;
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn addaxl(&mut self, _: &mut System, ins: Ins) {
+    pub fn addaxl(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = self.regs.acc32[s].bits(0, 16) as u64 as i64;
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn addi(&mut self, _: &mut System, ins: Ins) {
+    pub fn addi(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = (ins.extra as i16 as i64) << 16;
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn addis(&mut self, _: &mut System, ins: Ins) {
+    pub fn addis(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = (ins.base.bits(0, 8) as i8 as i64) << 16;
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn addp(&mut self, _: &mut System, ins: Ins) {
+    pub fn addp(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let (carry, overflow, rhs) = self.regs.product.get();
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new) || carry);
-        self.regs
-            .status
-            .set_overflow(add_overflowed(lhs, rhs, new) ^ overflow);
+        self.regs.status.set_overflow(overflowed ^ overflow);
 
         self.base_flags(new);
     }
 
-    pub fn addpaxz(&mut self, _: &mut System, ins: Ins) {
+    pub fn addpaxz(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -241,31 +241,29 @@ impl Interpreter {
         let lhs = round_40_ties_to_even(lhs);
 
         let rhs = self.regs.acc32[s] as i64;
-        let new = self.regs.acc40[d].set((lhs + rhs) & !0xFFFF);
+        let (new, overflowed) = self.regs.acc40[d].set_checked((lhs + rhs) & !0xFFFF);
 
         self.regs.status.set_carry(add_carried(lhs, new) ^ carry);
-        self.regs
-            .status
-            .set_overflow(add_overflowed(lhs, rhs, new) ^ overflow);
+        self.regs.status.set_overflow(overflowed ^ overflow);
 
         self.base_flags(new);
     }
 
-    pub fn addr(&mut self, _: &mut System, ins: Ins) {
+    pub fn addr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bits(9, 11) as u8;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = (self.regs.get(Reg::new(s + 0x18)) as i16 as i64) << 16;
-        let new = self.regs.acc40[d].set(lhs + rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs + rhs);
 
         self.regs.status.set_carry(add_carried(lhs, new));
-        self.regs.status.set_overflow(add_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn andc(&mut self, _: &mut System, ins: Ins) {
+    pub fn andc(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid &= self.regs.acc40[1 - d].mid;
@@ -284,21 +282,21 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn andcf(&mut self, _: &mut System, ins: Ins) {
+    pub fn andcf(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let is_equal = self.regs.acc40[d].mid & ins.extra == ins.extra;
         self.regs.status.set_logic_zero(is_equal);
     }
 
-    pub fn andf(&mut self, _: &mut System, ins: Ins) {
+    pub fn andf(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let is_equal = self.regs.acc40[d].mid & ins.extra == 0;
         self.regs.status.set_logic_zero(is_equal);
     }
 
-    pub fn andi(&mut self, _: &mut System, ins: Ins) {
+    pub fn andi(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid &= ins.extra;
@@ -317,7 +315,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn andr(&mut self, _: &mut System, ins: Ins) {
+    pub fn andr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -337,7 +335,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn asl(&mut self, _: &mut System, ins: Ins) {
+    pub fn asl(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let imm = ins.base.bits(0, 6) as u8;
 
@@ -350,7 +348,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn asr(&mut self, _: &mut System, ins: Ins) {
+    pub fn asr(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let imm = ins.base.bits(0, 6);
 
@@ -364,7 +362,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn asrn(&mut self, _: &mut System, _: Ins) {
+    pub fn asrn(&mut self, _: &mut DspIo, _: Ins) {
         let lhs = self.regs.acc40[0].get();
         let signed_shift = self.regs.acc40[1].mid;
         let rhs = signed_shift.bits(0, 6);
@@ -382,7 +380,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn asrnr(&mut self, _: &mut System, ins: Ins) {
+    pub fn asrnr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
@@ -402,7 +400,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn asrnrx(&mut self, _: &mut System, ins: Ins) {
+    pub fn asrnrx(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -423,7 +421,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn asr16(&mut self, _: &mut System, ins: Ins) {
+    pub fn asr16(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(11) as usize;
 
         let old = self.regs.acc40[r].get();
@@ -435,11 +433,11 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn clr15(&mut self, _: &mut System, _: Ins) {
+    pub fn clr15(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_unsigned_mul(false);
     }
 
-    pub fn clr(&mut self, _: &mut System, ins: Ins) {
+    pub fn clr(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(11) as usize;
 
         let new = self.regs.acc40[r].set(0);
@@ -450,7 +448,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn clrl(&mut self, _: &mut System, ins: Ins) {
+    pub fn clrl(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[r].get();
@@ -462,14 +460,14 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn clrp(&mut self, _: &mut System, _: Ins) {
+    pub fn clrp(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.product.low = 0x0000;
         self.regs.product.mid1 = 0xFFF0;
         self.regs.product.mid2 = 0x0010;
         self.regs.product.high = 0x00FF;
     }
 
-    pub fn cmp(&mut self, _: &mut System, _: Ins) {
+    pub fn cmp(&mut self, _: &mut DspIo, _: Ins) {
         let lhs = self.regs.acc40[0].get();
         let rhs = self.regs.acc40[1].get();
         let diff = Acc40::from(lhs - rhs).get();
@@ -482,7 +480,7 @@ impl Interpreter {
         self.base_flags(diff);
     }
 
-    pub fn cmpaxh(&mut self, _: &mut System, ins: Ins) {
+    pub fn cmpaxh(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bit(11) as usize;
         let r = ins.base.bit(12) as usize;
 
@@ -498,7 +496,7 @@ impl Interpreter {
         self.base_flags(diff);
     }
 
-    pub fn cmpi(&mut self, _: &mut System, ins: Ins) {
+    pub fn cmpi(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
@@ -513,7 +511,7 @@ impl Interpreter {
         self.base_flags(diff);
     }
 
-    pub fn cmpis(&mut self, _: &mut System, ins: Ins) {
+    pub fn cmpis(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
@@ -528,7 +526,7 @@ impl Interpreter {
         self.base_flags(diff);
     }
 
-    pub fn dar(&mut self, _: &mut System, ins: Ins) {
+    pub fn dar(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 2) as usize;
 
         let ar = self.regs.addressing[d];
@@ -537,37 +535,35 @@ impl Interpreter {
         self.regs.addressing[d] = sub_from_addr_reg(ar, wr, 1i16);
     }
 
-    pub fn dec(&mut self, _: &mut System, ins: Ins) {
+    pub fn dec(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[d].get();
-        let new = self.regs.acc40[d].set(old.wrapping_sub(1));
+        let (new, overflowed) = self.regs.acc40[d].set_checked(old.wrapping_sub(1));
 
         self.regs.status.set_carry(sub_carried(old, new));
-        self.regs.status.set_overflow(add_overflowed(old, -1, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn decm(&mut self, _: &mut System, ins: Ins) {
+    pub fn decm(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[d].get();
-        let new = self.regs.acc40[d].set(old - (1 << 16));
+        let (new, overflowed) = self.regs.acc40[d].set_checked(old - (1 << 16));
 
         self.regs.status.set_carry(sub_carried(old, new));
-        self.regs
-            .status
-            .set_overflow(add_overflowed(old, -(1 << 16), new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn halt(&mut self, sys: &mut System, _: Ins) {
-        sys.dsp.control.set_halt(true);
+    pub fn halt(&mut self, io: &mut DspIo, _: Ins) {
+        io.control.set_halt(true);
     }
 
-    pub fn iar(&mut self, _: &mut System, ins: Ins) {
+    pub fn iar(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bits(0, 2) as usize;
 
         let ar = self.regs.addressing[r];
@@ -603,40 +599,38 @@ impl Interpreter {
         }
     }
 
-    pub fn ifcc(&mut self, _: &mut System, ins: Ins) {
+    pub fn ifcc(&mut self, _: &mut DspIo, ins: Ins) {
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
         if !self.condition(code) {
             self.pc += 1;
         }
     }
 
-    pub fn inc(&mut self, _: &mut System, ins: Ins) {
+    pub fn inc(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[d].get();
-        let new = self.regs.acc40[d].set(old.wrapping_add(1));
+        let (new, overflowed) = self.regs.acc40[d].set_checked(old.wrapping_add(1));
 
         self.regs.status.set_carry(add_carried(old, new));
-        self.regs.status.set_overflow(add_overflowed(old, 1, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn incm(&mut self, _: &mut System, ins: Ins) {
+    pub fn incm(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[d].get();
-        let new = self.regs.acc40[d].set(old + (1 << 16));
+        let (new, overflowed) = self.regs.acc40[d].set_checked(old + (1 << 16));
 
         self.regs.status.set_carry(add_carried(old, new));
-        self.regs
-            .status
-            .set_overflow(add_overflowed(old, 1 << 16, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn lsl(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsl(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let shift = ins.base.bits(0, 6);
 
@@ -649,7 +643,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsl16(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsl16(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[r].get();
@@ -661,7 +655,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsr(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsr(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let shift = ins.base.bits(0, 6);
 
@@ -675,7 +669,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsrn(&mut self, _: &mut System, _: Ins) {
+    pub fn lsrn(&mut self, _: &mut DspIo, _: Ins) {
         let lhs = (self.regs.acc40[0].get()) & ((1 << 40) - 1);
         let signed_shift = self.regs.acc40[1].mid;
         let rhs = signed_shift.bits(0, 6);
@@ -693,7 +687,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsrnr(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsrnr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = (self.regs.acc40[d].get()) & ((1 << 40) - 1);
@@ -713,7 +707,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsrnrx(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsrnrx(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -734,7 +728,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn lsr16(&mut self, _: &mut System, ins: Ins) {
+    pub fn lsr16(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
 
         let old = (self.regs.acc40[r].get() as u64) & ((1 << 40) - 1);
@@ -746,16 +740,16 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn m0(&mut self, _: &mut System, _: Ins) {
+    pub fn m0(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_dont_double_result(true);
     }
 
-    pub fn m2(&mut self, _: &mut System, _: Ins) {
+    pub fn m2(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_dont_double_result(false);
     }
 
     // NOTE: carry flag issue
-    pub fn madd(&mut self, _: &mut System, ins: Ins) {
+    pub fn madd(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bit(8) as usize;
 
         let acc = self.regs.acc32[s];
@@ -773,7 +767,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn maddc(&mut self, _: &mut System, ins: Ins) {
+    pub fn maddc(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -791,7 +785,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn maddx(&mut self, _: &mut System, ins: Ins) {
+    pub fn maddx(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(8) as u8;
         let s = ins.base.bit(9) as u8;
 
@@ -808,7 +802,7 @@ impl Interpreter {
         self.regs.product.set(prod + result);
     }
 
-    pub fn mov(&mut self, _: &mut System, ins: Ins) {
+    pub fn mov(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let new = self.regs.acc40[d].set(self.regs.acc40[1 - d].get());
@@ -819,7 +813,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn movax(&mut self, _: &mut System, ins: Ins) {
+    pub fn movax(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -831,7 +825,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn movnp(&mut self, _: &mut System, ins: Ins) {
+    pub fn movnp(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let (carry, overflow, prod) = self.regs.product.get();
@@ -843,7 +837,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn movp(&mut self, _: &mut System, ins: Ins) {
+    pub fn movp(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let (carry, overflow, prod) = self.regs.product.get();
@@ -856,7 +850,7 @@ impl Interpreter {
     }
 
     // TODO: carry flag
-    pub fn movpz(&mut self, _: &mut System, ins: Ins) {
+    pub fn movpz(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let (carry, overflow, prod) = self.regs.product.get();
@@ -868,7 +862,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn movr(&mut self, _: &mut System, ins: Ins) {
+    pub fn movr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bits(9, 11) as u8;
 
@@ -881,7 +875,7 @@ impl Interpreter {
         self.base_flags(new);
     }
 
-    pub fn mrr(&mut self, _: &mut System, ins: Ins) {
+    pub fn mrr(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let d = ins.base.bits(5, 10) as u8;
 
@@ -890,7 +884,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn msub(&mut self, _: &mut System, ins: Ins) {
+    pub fn msub(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bit(8) as usize;
 
         let acc = self.regs.acc32[s];
@@ -908,7 +902,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn msubc(&mut self, _: &mut System, ins: Ins) {
+    pub fn msubc(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -926,7 +920,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn msubx(&mut self, _: &mut System, ins: Ins) {
+    pub fn msubx(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(8) as u8;
         let s = ins.base.bit(9) as u8;
 
@@ -944,7 +938,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mul(&mut self, _: &mut System, ins: Ins) {
+    pub fn mul(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bit(11) as usize;
 
         let acc = self.regs.acc32[s];
@@ -961,7 +955,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulac(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulac(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let s = ins.base.bit(11) as usize;
 
@@ -985,7 +979,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulaxh(&mut self, _: &mut System, _: Ins) {
+    pub fn mulaxh(&mut self, _: &mut DspIo, _: Ins) {
         let val = (self.regs.acc32[0] >> 16) as i64;
         let mul = val * val;
         let result = if self.regs.status.dont_double_result() {
@@ -998,7 +992,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulc(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulc(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(11) as usize;
         let s = ins.base.bit(12) as usize;
 
@@ -1015,7 +1009,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulcac(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulcac(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11) as usize;
         let s = ins.base.bit(12) as usize;
@@ -1040,7 +1034,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulcmv(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulcmv(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11) as usize;
         let s = ins.base.bit(12) as usize;
@@ -1064,7 +1058,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulcmvz(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulcmvz(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11) as usize;
         let s = ins.base.bit(12) as usize;
@@ -1088,7 +1082,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulmv(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulmv(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let s = ins.base.bit(11) as usize;
 
@@ -1111,7 +1105,7 @@ impl Interpreter {
     }
 
     // NOTE: carry flag issue
-    pub fn mulmvz(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulmvz(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let s = ins.base.bit(11) as usize;
 
@@ -1154,7 +1148,7 @@ impl Interpreter {
         a * b * factor
     }
 
-    pub fn mulx(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulx(&mut self, _: &mut DspIo, ins: Ins) {
         let t = ins.base.bit(11);
         let s = ins.base.bit(12);
 
@@ -1181,7 +1175,7 @@ impl Interpreter {
         self.regs.product.set(result);
     }
 
-    pub fn mulxac(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulxac(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11);
         let s = ins.base.bit(12);
@@ -1213,7 +1207,7 @@ impl Interpreter {
         self.regs.product.set(result);
     }
 
-    pub fn mulxmv(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulxmv(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11);
         let s = ins.base.bit(12);
@@ -1244,7 +1238,7 @@ impl Interpreter {
         self.regs.product.set(result);
     }
 
-    pub fn mulxmvz(&mut self, _: &mut System, ins: Ins) {
+    pub fn mulxmvz(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
         let t = ins.base.bit(11);
         let s = ins.base.bit(12);
@@ -1275,19 +1269,19 @@ impl Interpreter {
         self.regs.product.set(result);
     }
 
-    pub fn neg(&mut self, _: &mut System, ins: Ins) {
+    pub fn neg(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let old = self.regs.acc40[d].get();
         let new = self.regs.acc40[d].set(-old);
 
         self.regs.status.set_carry(old == 0);
-        self.regs.status.set_overflow(old == (1 << 40));
+        self.regs.status.set_overflow(old == Acc40::MIN);
 
         self.base_flags(new);
     }
 
-    pub fn not(&mut self, _: &mut System, ins: Ins) {
+    pub fn not(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid ^= 0xFFFF;
@@ -1306,7 +1300,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn orc(&mut self, _: &mut System, ins: Ins) {
+    pub fn orc(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid |= self.regs.acc40[1 - d].mid;
@@ -1325,7 +1319,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn ori(&mut self, _: &mut System, ins: Ins) {
+    pub fn ori(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid |= ins.extra;
@@ -1344,7 +1338,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn orr(&mut self, _: &mut System, ins: Ins) {
+    pub fn orr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -1364,7 +1358,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn sbclr(&mut self, _: &mut System, ins: Ins) {
+    pub fn sbclr(&mut self, _: &mut DspIo, ins: Ins) {
         let i = ins.base.bits(0, 3) as u8;
 
         let idx = 6 + i;
@@ -1378,7 +1372,7 @@ impl Interpreter {
         self.regs.status = Status::from_bits(new);
     }
 
-    pub fn sbset(&mut self, _: &mut System, ins: Ins) {
+    pub fn sbset(&mut self, _: &mut DspIo, ins: Ins) {
         let i = ins.base.bits(0, 3) as u8;
 
         let idx = 6 + i;
@@ -1392,32 +1386,32 @@ impl Interpreter {
         self.regs.status = Status::from_bits(new);
     }
 
-    pub fn set15(&mut self, _: &mut System, _: Ins) {
+    pub fn set15(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_unsigned_mul(true);
     }
 
-    pub fn set16(&mut self, _: &mut System, _: Ins) {
+    pub fn set16(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_sign_extend_to_40(false);
     }
 
-    pub fn set40(&mut self, _: &mut System, _: Ins) {
+    pub fn set40(&mut self, _: &mut DspIo, _: Ins) {
         self.regs.status.set_sign_extend_to_40(true);
     }
 
-    pub fn sub(&mut self, _: &mut System, ins: Ins) {
+    pub fn sub(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = self.regs.acc40[1 - d].get();
-        let new = self.regs.acc40[d].set(lhs - rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs - rhs);
 
         self.regs.status.set_carry(sub_carried(lhs, new));
-        self.regs.status.set_overflow(sub_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn subarn(&mut self, _: &mut System, ins: Ins) {
+    pub fn subarn(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 2) as usize;
 
         let ix = self.regs.indexing[d];
@@ -1427,50 +1421,48 @@ impl Interpreter {
         self.regs.addressing[d] = sub_from_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn subax(&mut self, _: &mut System, ins: Ins) {
+    pub fn subax(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = self.regs.acc32[s] as i64;
-        let new = self.regs.acc40[d].set(lhs - rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs - rhs);
 
         self.regs.status.set_carry(sub_carried(lhs, new));
-        self.regs.status.set_overflow(sub_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn subp(&mut self, _: &mut System, ins: Ins) {
+    pub fn subp(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         let lhs = self.regs.acc40[d].get();
         let (carry, overflow, rhs) = self.regs.product.get();
-        let new = self.regs.acc40[d].set(lhs - rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs - rhs);
 
         self.regs.status.set_carry(sub_carried(lhs, new) ^ !carry);
-        self.regs
-            .status
-            .set_overflow(sub_overflowed(lhs, rhs, new) ^ overflow);
+        self.regs.status.set_overflow(overflowed ^ overflow);
 
         self.base_flags(new);
     }
 
-    pub fn subr(&mut self, _: &mut System, ins: Ins) {
+    pub fn subr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bits(9, 11) as u8;
 
         let lhs = self.regs.acc40[d].get();
         let rhs = (self.regs.get(Reg::new(s + 0x18)) as i16 as i64) << 16;
-        let new = self.regs.acc40[d].set(lhs - rhs);
+        let (new, overflowed) = self.regs.acc40[d].set_checked(lhs - rhs);
 
         self.regs.status.set_carry(sub_carried(lhs, new));
-        self.regs.status.set_overflow(sub_overflowed(lhs, rhs, new));
+        self.regs.status.set_overflow(overflowed);
 
         self.base_flags(new);
     }
 
-    pub fn tst(&mut self, _: &mut System, ins: Ins) {
+    pub fn tst(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(11) as usize;
 
         let acc = self.regs.acc40[r].get();
@@ -1481,7 +1473,7 @@ impl Interpreter {
         self.base_flags(acc);
     }
 
-    pub fn tstaxh(&mut self, _: &mut System, ins: Ins) {
+    pub fn tstaxh(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bit(8) as usize;
 
         let acc = self.regs.acc32[r] >> 16;
@@ -1496,7 +1488,7 @@ impl Interpreter {
             .set_top_two_bits_eq(acc.bit(15) == acc.bit(14));
     }
 
-    pub fn tstprod(&mut self, _: &mut System, _: Ins) {
+    pub fn tstprod(&mut self, _: &mut DspIo, _: Ins) {
         let (carry, overflow, prod) = self.regs.product.get();
 
         self.regs.status.set_carry(carry);
@@ -1505,7 +1497,7 @@ impl Interpreter {
         self.base_flags(prod);
     }
 
-    pub fn xorc(&mut self, _: &mut System, ins: Ins) {
+    pub fn xorc(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid ^= self.regs.acc40[1 - d].mid;
@@ -1524,7 +1516,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn xori(&mut self, _: &mut System, ins: Ins) {
+    pub fn xori(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
 
         self.regs.acc40[d].mid ^= ins.extra;
@@ -1543,7 +1535,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn xorr(&mut self, _: &mut System, ins: Ins) {
+    pub fn xorr(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bit(8) as usize;
         let s = ins.base.bit(9) as usize;
 
@@ -1563,7 +1555,7 @@ impl Interpreter {
             .set_sign((self.regs.acc40[d].mid as i16) < 0);
     }
 
-    pub fn bloop(&mut self, _: &mut System, ins: Ins) {
+    pub fn bloop(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bits(0, 5) as u8;
 
         let counter = self.regs.get(Reg::new(r));
@@ -1577,7 +1569,7 @@ impl Interpreter {
         }
     }
 
-    pub fn bloopi(&mut self, _: &mut System, ins: Ins) {
+    pub fn bloopi(&mut self, _: &mut DspIo, ins: Ins) {
         let counter = ins.base.bits(0, 8);
 
         if counter != 0 {
@@ -1589,7 +1581,7 @@ impl Interpreter {
         }
     }
 
-    pub fn call(&mut self, _: &mut System, ins: Ins) {
+    pub fn call(&mut self, _: &mut DspIo, ins: Ins) {
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
         if self.condition(code) {
             self.regs.call_stack.push(self.pc.wrapping_add(2));
@@ -1597,7 +1589,7 @@ impl Interpreter {
         }
     }
 
-    pub fn callr(&mut self, _: &mut System, ins: Ins) {
+    pub fn callr(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bits(5, 8) as u8;
 
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
@@ -1609,14 +1601,14 @@ impl Interpreter {
         }
     }
 
-    pub fn jmp(&mut self, _: &mut System, ins: Ins) {
+    pub fn jmp(&mut self, _: &mut DspIo, ins: Ins) {
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
         if self.condition(code) {
             self.pc = ins.extra - 2;
         }
     }
 
-    pub fn jmpr(&mut self, _: &mut System, ins: Ins) {
+    pub fn jmpr(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bits(5, 8) as u8;
 
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
@@ -1627,87 +1619,91 @@ impl Interpreter {
         }
     }
 
-    pub fn ret(&mut self, _: &mut System, ins: Ins) {
+    pub fn ret(&mut self, _: &mut DspIo, ins: Ins) {
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
         if self.condition(code) {
-            let addr = self.regs.call_stack.pop().unwrap();
-            self.pc = addr - 1;
+            let addr = self.regs.call_stack.pop().unwrap_or_else(|| {
+                tracing::warn!("DSP ret with an empty call stack");
+                0
+            });
+
+            self.pc = addr.wrapping_sub(1);
         }
     }
 
-    pub fn lr(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lr(&mut self, io: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
-        let data = self.read_dmem(sys, ins.extra);
+        let data = self.read_dmem(io, ins.extra);
         self.regs.set_saturate(Reg::new(d), data);
     }
 
-    pub fn lri(&mut self, _: &mut System, ins: Ins) {
+    pub fn lri(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
         self.regs.set_saturate(Reg::new(d), ins.extra);
     }
 
-    pub fn lris(&mut self, _: &mut System, ins: Ins) {
+    pub fn lris(&mut self, _: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(8, 11) as u8;
         let imm = ins.base.bits(0, 8) as i8 as i16;
         self.regs.set_saturate(Reg::new(0x18 + d), imm as u16);
     }
 
-    pub fn lrr(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lrr(&mut self, io: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
         let s = ins.base.bits(5, 7) as usize;
 
         let ar = self.regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(Reg::new(d), data);
     }
 
-    pub fn lrrd(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lrrd(&mut self, io: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
         let s = ins.base.bits(5, 7) as usize;
 
         let ar = self.regs.addressing[s];
         let wr = self.regs.wrapping[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.addressing[s] = sub_from_addr_reg(ar, wr, 1);
 
         self.regs.set_saturate(Reg::new(d), data);
     }
 
-    pub fn lrri(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lrri(&mut self, io: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
         let s = ins.base.bits(5, 7) as usize;
 
         let ar = self.regs.addressing[s];
         let wr = self.regs.wrapping[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, 1);
 
         self.regs.set_saturate(Reg::new(d), data);
     }
 
-    pub fn lrrn(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lrrn(&mut self, io: &mut DspIo, ins: Ins) {
         let d = ins.base.bits(0, 5) as u8;
         let s = ins.base.bits(5, 7) as usize;
 
         let ar = self.regs.addressing[s];
         let wr = self.regs.wrapping[s];
         let ix = self.regs.indexing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, ix as i16);
 
         self.regs.set_saturate(Reg::new(d), data);
     }
 
-    pub fn lrs(&mut self, sys: &mut System, ins: Ins) {
+    pub fn lrs(&mut self, io: &mut DspIo, ins: Ins) {
         let imm = ins.base.bits(0, 8) as u8;
         let d = ins.base.bits(8, 11) as u8;
 
-        let addr = u16::from_le_bytes([imm, self.regs.config]);
-        let data = self.read_dmem(sys, addr);
+        let addr = self.regs.config.short_address(imm);
+        let data = self.read_dmem(io, addr);
         self.regs.set_saturate(Reg::new(0x18 + d), data);
     }
 
-    pub fn ilrr(&mut self, _: &mut System, ins: Ins) {
+    pub fn ilrr(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bit(8);
 
@@ -1718,7 +1714,7 @@ impl Interpreter {
         self.regs.set_saturate(reg, data);
     }
 
-    pub fn ilrrd(&mut self, _: &mut System, ins: Ins) {
+    pub fn ilrrd(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bit(8);
 
@@ -1732,7 +1728,7 @@ impl Interpreter {
         self.regs.addressing[s] = sub_from_addr_reg(ar, wr, 1);
     }
 
-    pub fn ilrri(&mut self, _: &mut System, ins: Ins) {
+    pub fn ilrri(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bit(8);
 
@@ -1746,7 +1742,7 @@ impl Interpreter {
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ilrrn(&mut self, _: &mut System, ins: Ins) {
+    pub fn ilrrn(&mut self, _: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bit(8);
 
@@ -1761,60 +1757,60 @@ impl Interpreter {
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn si(&mut self, sys: &mut System, ins: Ins) {
-        let offset = ins.base.bits(0, 8) as u8;
-        let addr = u16::from_le_bytes([offset, 0xFF]);
-        self.write_dmem(sys, addr, ins.extra);
+    pub fn si(&mut self, io: &mut DspIo, ins: Ins) {
+        let imm = ins.base.bits(0, 8) as u8;
+        let addr = short_address(MMIO_PAGE, imm);
+        self.write_dmem(io, addr, ins.extra);
     }
 
-    pub fn sr(&mut self, sys: &mut System, ins: Ins) {
+    pub fn sr(&mut self, io: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let data = self.regs.get(Reg::new(s));
-        self.write_dmem(sys, ins.extra, data);
+        self.write_dmem(io, ins.extra, data);
     }
 
-    pub fn srr(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srr(&mut self, io: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let d = ins.base.bits(5, 7) as usize;
 
         let data = self.regs.get(Reg::new(s));
         let addr = self.regs.addressing[d];
-        self.write_dmem(sys, addr, data);
+        self.write_dmem(io, addr, data);
     }
 
-    pub fn srrd(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srrd(&mut self, io: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let d = ins.base.bits(5, 7) as usize;
 
         let data = self.regs.get(Reg::new(s));
         let ar = self.regs.addressing[d];
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = self.regs.addressing[d];
         let wr = self.regs.wrapping[d];
         self.regs.addressing[d] = sub_from_addr_reg(ar, wr, 1);
     }
 
-    pub fn srri(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srri(&mut self, io: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let d = ins.base.bits(5, 7) as usize;
 
         let data = self.regs.get(Reg::new(s));
         let ar = self.regs.addressing[d];
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = self.regs.addressing[d];
         let wr = self.regs.wrapping[d];
         self.regs.addressing[d] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn srrn(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srrn(&mut self, io: &mut DspIo, ins: Ins) {
         let s = ins.base.bits(0, 5) as u8;
         let d = ins.base.bits(5, 7) as usize;
 
         let data = self.regs.get(Reg::new(s));
         let ar = self.regs.addressing[d];
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = self.regs.addressing[d];
         let wr = self.regs.wrapping[d];
@@ -1822,51 +1818,57 @@ impl Interpreter {
         self.regs.addressing[d] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn srs(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srs(&mut self, io: &mut DspIo, ins: Ins) {
         let imm = ins.base.bits(0, 8) as u8;
         let s = ins.base.bits(8, 10) as u8;
 
-        let addr = u16::from_le_bytes([imm, self.regs.config]);
+        let addr = self.regs.config.short_address(imm);
         let data = self.regs.get(Reg::new(0x1C + s));
-        self.write_dmem(sys, addr, data);
+        self.write_dmem(io, addr, data);
     }
 
-    pub fn srsh(&mut self, sys: &mut System, ins: Ins) {
+    pub fn srsh(&mut self, io: &mut DspIo, ins: Ins) {
         let imm = ins.base.bits(0, 8) as u8;
         let s = ins.base.bit(8) as usize;
 
-        let addr = u16::from_le_bytes([imm, self.regs.config]);
+        let addr = self.regs.config.short_address(imm);
         let data = self.regs.acc40[s].high as i8 as i16 as u16;
-        self.write_dmem(sys, addr, data);
+        self.write_dmem(io, addr, data);
     }
 
-    pub fn loop_(&mut self, _: &mut System, ins: Ins) {
+    pub fn loop_(&mut self, _: &mut DspIo, ins: Ins) {
         let r = ins.base.bits(0, 5) as u8;
 
         let counter = self.regs.get(Reg::new(r));
-        self.loop_counter = Some(counter);
-        self.pc += 1;
+        self.repeat_next(counter);
     }
 
-    pub fn loopi(&mut self, _: &mut System, ins: Ins) {
+    pub fn loopi(&mut self, _: &mut DspIo, ins: Ins) {
         let imm = ins.base.bits(0, 8) as u8;
-        self.loop_counter = Some(imm as u16);
-        self.pc += 1;
+        self.repeat_next(imm as u16);
     }
 
-    pub fn rti(&mut self, _: &mut System, ins: Ins) {
+    pub fn rti(&mut self, _: &mut DspIo, ins: Ins) {
         let code = CondCode::new(ins.base.bits(0, 4) as u8);
         if self.condition(code) {
-            let sr = self.regs.data_stack.pop().unwrap();
-            let pc = self.regs.call_stack.pop().unwrap();
+            // like reading an empty stack register, popping an empty stack yields zero
+            let sr = self.regs.data_stack.pop().unwrap_or_else(|| {
+                tracing::warn!("DSP rti with an empty data stack");
+                0
+            });
+            let pc = self.regs.call_stack.pop().unwrap_or_else(|| {
+                tracing::warn!("DSP rti with an empty call stack");
+                0
+            });
+
             self.regs.set(Reg::Status, sr);
-            self.pc = pc - 1;
+            self.pc = pc.wrapping_sub(1);
         }
     }
 }
 
 impl Interpreter {
-    pub fn ext_dr(&mut self, _: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_dr(&mut self, _: &mut DspIo, ins: Ins, regs: &Registers) {
         let r = ins.base.bits(0, 2) as usize;
 
         let ar = regs.addressing[r];
@@ -1875,7 +1877,7 @@ impl Interpreter {
         self.regs.addressing[r] = sub_from_addr_reg(ar, wr, 1i16);
     }
 
-    pub fn ext_ir(&mut self, _: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ir(&mut self, _: &mut DspIo, ins: Ins, regs: &Registers) {
         let r = ins.base.bits(0, 2) as usize;
 
         let ar = regs.addressing[r];
@@ -1884,7 +1886,7 @@ impl Interpreter {
         self.regs.addressing[r] = add_to_addr_reg(ar, wr, 1i16);
     }
 
-    pub fn ext_nr(&mut self, _: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_nr(&mut self, _: &mut DspIo, ins: Ins, regs: &Registers) {
         let r = ins.base.bits(0, 2) as usize;
 
         let ar = regs.addressing[r];
@@ -1894,7 +1896,7 @@ impl Interpreter {
         self.regs.addressing[r] = add_to_addr_reg(ar, wr, ir as i16);
     }
 
-    pub fn ext_mv(&mut self, _: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_mv(&mut self, _: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as u8;
         let d = ins.base.bits(2, 4) as u8;
 
@@ -1902,12 +1904,12 @@ impl Interpreter {
             .set(Reg::new(0x18 + d), regs.get(Reg::new(0x1C + s)));
     }
 
-    pub fn ext_l(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_l(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bits(3, 6) as u8;
 
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[s];
@@ -1915,12 +1917,12 @@ impl Interpreter {
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_ln(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ln(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         let d = ins.base.bits(3, 6) as u8;
 
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[s];
@@ -1929,10 +1931,10 @@ impl Interpreter {
         self.regs.addressing[s] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ld(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ld(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         if s == 3 {
-            self.ext_ldax(sys, ins, regs);
+            self.ext_ldax(io, ins, regs);
             return;
         }
 
@@ -1941,7 +1943,7 @@ impl Interpreter {
 
         let d = if d { Reg::Acc32High0 } else { Reg::Acc32Low0 };
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(d, data);
 
         let ar = if (regs.addressing[3] >> 10) == (regs.addressing[s] >> 10) {
@@ -1950,7 +1952,7 @@ impl Interpreter {
             regs.addressing[3]
         };
         let r = if r { Reg::Acc32High1 } else { Reg::Acc32Low1 };
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(r, data);
 
         let ar = regs.addressing[s];
@@ -1962,18 +1964,18 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_ldax(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldax(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(5) as usize;
         let r = ins.base.bit(4) as usize;
 
         let ar = regs.addressing[s];
-        let high = self.read_dmem(sys, ar);
+        let high = self.read_dmem(io, ar);
         let ar = if (regs.addressing[3] >> 10) == (regs.addressing[s] >> 10) {
             regs.addressing[s]
         } else {
             regs.addressing[3]
         };
-        let low = self.read_dmem(sys, ar);
+        let low = self.read_dmem(io, ar);
 
         self.regs.acc32[r] = (((high as u32) << 16) | low as u32) as i32;
 
@@ -1986,10 +1988,10 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_ldm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         if s == 3 {
-            self.ext_ldaxm(sys, ins, regs);
+            self.ext_ldaxm(io, ins, regs);
             return;
         }
 
@@ -1998,7 +2000,7 @@ impl Interpreter {
 
         let d = if d { Reg::Acc32High0 } else { Reg::Acc32Low0 };
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(d, data);
 
         let r = if r { Reg::Acc32High1 } else { Reg::Acc32Low1 };
@@ -2007,7 +2009,7 @@ impl Interpreter {
         } else {
             regs.addressing[3]
         };
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(r, data);
 
         let ar = regs.addressing[s];
@@ -2020,18 +2022,18 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ldaxm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldaxm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(5) as usize;
         let r = ins.base.bit(4) as usize;
 
         let ar = regs.addressing[s];
-        let high = self.read_dmem(sys, ar);
+        let high = self.read_dmem(io, ar);
         let ar = if (regs.addressing[3] >> 10) == (regs.addressing[s] >> 10) {
             regs.addressing[s]
         } else {
             regs.addressing[3]
         };
-        let low = self.read_dmem(sys, ar);
+        let low = self.read_dmem(io, ar);
 
         self.regs.acc32[r] = (((high as u32) << 16) | low as u32) as i32;
 
@@ -2045,10 +2047,10 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ldnm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldnm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         if s == 3 {
-            self.ext_ldaxnm(sys, ins, regs);
+            self.ext_ldaxnm(io, ins, regs);
             return;
         }
 
@@ -2057,7 +2059,7 @@ impl Interpreter {
 
         let d = if d { Reg::Acc32High0 } else { Reg::Acc32Low0 };
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(d, data);
 
         let r = if r { Reg::Acc32High1 } else { Reg::Acc32Low1 };
@@ -2066,7 +2068,7 @@ impl Interpreter {
         } else {
             regs.addressing[3]
         };
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(r, data);
 
         let ar = regs.addressing[s];
@@ -2080,18 +2082,18 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ldaxnm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldaxnm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(5) as usize;
         let r = ins.base.bit(4) as usize;
 
         let ar = regs.addressing[s];
-        let high = self.read_dmem(sys, ar);
+        let high = self.read_dmem(io, ar);
         let ar = if (regs.addressing[3] >> 10) == (regs.addressing[s] >> 10) {
             regs.addressing[s]
         } else {
             regs.addressing[3]
         };
-        let low = self.read_dmem(sys, ar);
+        let low = self.read_dmem(io, ar);
 
         self.regs.acc32[r] = (((high as u32) << 16) | low as u32) as i32;
 
@@ -2106,10 +2108,10 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ldn(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldn(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bits(0, 2) as usize;
         if s == 3 {
-            self.ext_ldaxn(sys, ins, regs);
+            self.ext_ldaxn(io, ins, regs);
             return;
         }
 
@@ -2118,7 +2120,7 @@ impl Interpreter {
 
         let d = if d { Reg::Acc32High0 } else { Reg::Acc32Low0 };
         let ar = regs.addressing[s];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(d, data);
 
         let r = if r { Reg::Acc32High1 } else { Reg::Acc32Low1 };
@@ -2127,7 +2129,7 @@ impl Interpreter {
         } else {
             regs.addressing[3]
         };
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set_saturate(r, data);
 
         let ar = regs.addressing[s];
@@ -2140,18 +2142,18 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_ldaxn(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ldaxn(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(5) as usize;
         let r = ins.base.bit(4) as usize;
 
         let ar = regs.addressing[s];
-        let high = self.read_dmem(sys, ar);
+        let high = self.read_dmem(io, ar);
         let ar = if (regs.addressing[3] >> 10) == (regs.addressing[s] >> 10) {
             regs.addressing[s]
         } else {
             regs.addressing[3]
         };
-        let low = self.read_dmem(sys, ar);
+        let low = self.read_dmem(io, ar);
 
         self.regs.acc32[r] = (((high as u32) << 16) | low as u32) as i32;
 
@@ -2165,26 +2167,26 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_s(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_s(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let d = ins.base.bits(0, 2) as usize;
         let s = ins.base.bits(3, 5) as u8;
 
         let ar = regs.addressing[d];
         let data = regs.get(Reg::new(0x1C + s));
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[d];
         let wr = regs.wrapping[d];
         self.regs.addressing[d] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_sn(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_sn(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let d = ins.base.bits(0, 2) as usize;
         let s = ins.base.bits(3, 5) as u8;
 
         let ar = regs.addressing[d];
         let data = regs.get(Reg::new(0x1C + s));
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[d];
         let wr = regs.wrapping[d];
@@ -2192,17 +2194,17 @@ impl Interpreter {
         self.regs.addressing[d] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_ls(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_ls(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[3];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[0];
         let wr = regs.wrapping[0];
@@ -2213,17 +2215,17 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_lsm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_lsm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[3];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[0];
         let wr = regs.wrapping[0];
@@ -2235,17 +2237,17 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_lsnm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_lsnm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[3];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[0];
         let wr = regs.wrapping[0];
@@ -2258,17 +2260,17 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_lsn(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_lsn(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[3];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[0];
         let wr = regs.wrapping[0];
@@ -2280,16 +2282,16 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_sl(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_sl(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[3];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[0];
@@ -2301,16 +2303,16 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, 1);
     }
 
-    pub fn ext_slm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_slm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[3];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[0];
@@ -2323,16 +2325,16 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_slnm(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_slnm(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[3];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[0];
@@ -2346,16 +2348,16 @@ impl Interpreter {
         self.regs.addressing[3] = add_to_addr_reg(ar, wr, ix as i16);
     }
 
-    pub fn ext_sln(&mut self, sys: &mut System, ins: Ins, regs: &Registers) {
+    pub fn ext_sln(&mut self, io: &mut DspIo, ins: Ins, regs: &Registers) {
         let s = ins.base.bit(0) as usize;
         let d = ins.base.bits(4, 6) as u8;
 
         let ar = regs.addressing[0];
         let data = regs.acc40[s].mid;
-        self.write_dmem(sys, ar, data);
+        self.write_dmem(io, ar, data);
 
         let ar = regs.addressing[3];
-        let data = self.read_dmem(sys, ar);
+        let data = self.read_dmem(io, ar);
         self.regs.set(Reg::new(0x18 + d), data);
 
         let ar = regs.addressing[0];
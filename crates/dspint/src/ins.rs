@@ -29,6 +29,46 @@ impl std::fmt::Debug for Ins {
     }
 }
 
+/// Formats the mnemonic of the instruction (and of its extension, if any), followed by its
+/// extra word for two-word instructions. Operands encoded in the base word are not decoded, except
+/// for the condition of conditional instructions, which is part of their mnemonic (e.g. `jlnz`).
+impl std::fmt::Display for Ins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let decoded = self.decoded();
+        let mnemonic = format!("{:?}", decoded.opcode).to_ascii_lowercase();
+        match decoded.opcode {
+            Opcode::Jmp
+            | Opcode::Jr
+            | Opcode::Call
+            | Opcode::Callr
+            | Opcode::If
+            | Opcode::Ret
+            | Opcode::Rti => {
+                let code = CondCode::new(self.base as u8);
+                match (decoded.opcode, code) {
+                    (_, CondCode::Always) => f.write_str(&mnemonic)?,
+                    (Opcode::Jmp, _) => write!(f, "j{}", code.suffix())?,
+                    _ => write!(f, "{mnemonic}{}", code.suffix())?,
+                }
+            }
+            _ => f.write_str(&mnemonic)?,
+        }
+
+        if let Some(extension) = decoded.extension
+            && extension != ExtensionOpcode::Nop
+        {
+            let mnemonic = format!("{extension:?}").to_ascii_lowercase();
+            write!(f, "'{mnemonic}")?;
+        }
+
+        if decoded.needs_extra {
+            write!(f, " #0x{:04X}", self.extra)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Decoded {
     pub opcode: Opcode,
@@ -28,6 +28,28 @@ impl CondCode {
     pub fn new(value: u8) -> Self {
         unsafe { std::mem::transmute(value & 0xF) }
     }
+
+    /// The suffix of conditional instructions with this condition, e.g. `lnz` in `jlnz`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::GreaterOrEqual => "ge",
+            Self::Less => "l",
+            Self::Greater => "g",
+            Self::LessOrEqual => "le",
+            Self::NotZero => "nz",
+            Self::Zero => "z",
+            Self::NotCarry => "nc",
+            Self::Carry => "c",
+            Self::BelowS32 => "x8",
+            Self::AboveS32 => "x9",
+            Self::WeirdA => "xa",
+            Self::WeirdB => "xb",
+            Self::NotLogicZero => "lnz",
+            Self::LogicZero => "lz",
+            Self::Overflow => "o",
+            Self::Always => "",
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -3,12 +3,12 @@
 mod exec;
 
 pub mod ins;
+pub mod threaded;
 
 use bitos::integer::{u3, u4, u15};
 use bitos::{BitUtils, bitos};
 use lazuli::Primitive;
-use lazuli::system::System;
-use lazuli::system::dspi::{DspDmaControl, DspDmaDirection, DspDmaTarget, Mailbox};
+use lazuli::system::dspi::{DspDmaControl, DspDmaDirection, DspDmaTarget, DspIo, Mailbox};
 use strum::FromRepr;
 use tinyvec::ArrayVec;
 use util::boxed_array;
@@ -28,6 +28,9 @@ pub struct Memory {
     pub iram: Box<[u16; IRAM_LEN]>,
     pub irom: Box<[u16; IROM_LEN]>,
     pub dram: Box<[u16; DRAM_LEN]>,
+    /// The coefficient ROM, mapped at `0x1000` in data memory. It is read-only and no register
+    /// loads it, so it must be filled from a dump by whoever creates the interpreter. It is
+    /// unrelated to the accelerator coefficients at `0xFFA0..=0xFFAF`.
     pub coef: Box<[u16; COEF_LEN]>,
 }
 
@@ -83,11 +86,20 @@ impl Acc40 {
         (bits << 24) >> 24
     }
 
+    /// Stores the given value, wrapping it to 40 bits, and returns the stored value.
     #[inline(always)]
     pub fn set(&mut self, value: i64) -> i64 {
         *self = Self::from(value);
         self.get()
     }
+
+    /// Like [`Self::set`], but also returns whether wrapping changed the value, i.e. whether it
+    /// did not fit in 40 bits.
+    #[inline(always)]
+    pub fn set_checked(&mut self, value: i64) -> (i64, bool) {
+        let new = self.set(value);
+        (new, new != value)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -161,6 +173,43 @@ impl Default for Status {
     }
 }
 
+/// The config register (`$cr`).
+///
+/// Its only known effect is selecting the page of data memory accessed by the short addressing
+/// instructions (`lrs`, `srs` and `srsh`), which combine it with their 8 bit immediate. Ucode
+/// usually sets it to `0xFF`, so that these instructions access the MMIO registers.
+///
+/// It has no effect on memory mapping: IRAM and IROM are always mapped at `0x0000` and `0x8000` of
+/// instruction memory, and DRAM and COEF at `0x0000` and `0x1000` of data memory. Likewise, the
+/// accelerator is configured entirely through its MMIO registers. The register is 8 bits wide, so
+/// the upper bits of writes are dropped and read back as zero.
+#[bitos(8)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Config {
+    /// Upper byte of the data memory address used by the short addressing instructions.
+    #[bits(0..8)]
+    pub page: u8,
+}
+
+impl Config {
+    /// Returns the data memory address accessed by `lrs`, `srs` or `srsh` with the given
+    /// immediate.
+    pub fn short_address(&self, imm: u8) -> u16 {
+        short_address(self.page(), imm)
+    }
+}
+
+/// Page of data memory containing the MMIO registers, which is the one always accessed by `si`.
+pub const MMIO_PAGE: u8 = 0xFF;
+
+/// Forms the data memory address accessed by a short addressing instruction: `page` is the upper
+/// byte and the 8 bit immediate the lower one, which is never sign extended.
+///
+/// `lrs`, `srs` and `srsh` take the page from [`Config`], while `si` always uses [`MMIO_PAGE`].
+pub fn short_address(page: u8, imm: u8) -> u16 {
+    ((page as u16) << 8) | imm as u16
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 #[repr(u8)]
 pub enum Reg {
@@ -216,7 +265,7 @@ pub struct Registers {
     pub product: Product,
     pub acc40: [Acc40; 2],
     pub acc32: [i32; 2],
-    pub config: u8,
+    pub config: Config,
     pub status: Status,
 }
 
@@ -271,7 +320,7 @@ impl Registers {
             Reg::LoopCount => self.loop_count.last().copied().unwrap_or_default(),
             Reg::Acc40High0 => self.acc40[0].high as i8 as i16 as u16,
             Reg::Acc40High1 => self.acc40[1].high as i8 as i16 as u16,
-            Reg::Config => self.config as u16,
+            Reg::Config => self.config.to_bits() as u16,
             Reg::Status => self.status.to_bits(),
             Reg::ProdLow => self.product.low,
             Reg::ProdMid1 => self.product.mid1,
@@ -308,7 +357,7 @@ impl Registers {
             Reg::LoopCount => self.loop_count.push(value),
             Reg::Acc40High0 => self.acc40[0].high = value as u8,
             Reg::Acc40High1 => self.acc40[1].high = value as u8,
-            Reg::Config => self.config = value as u8,
+            Reg::Config => self.config = Config::from_bits(value as u8),
             Reg::Status => self.status = Status::from_bits(value.with_bit(8, false)),
             Reg::ProdLow => self.product.low = value,
             Reg::ProdMid1 => self.product.mid1 = value,
@@ -450,9 +499,15 @@ pub struct AccelPredictor {
     pub coefficients: u3,
 }
 
+/// A pair of prediction coefficients of the accelerator, as signed 5.11 fixed point numbers. Pair
+/// `n` is at registers `0xFFA0 + 2n` (`a`) and `0xFFA1 + 2n` (`b`). Ucodes copy them there from
+/// the header of the ADPCM sound they're about to play, and the accelerator picks the pair given by
+/// the predictor when decoding.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct AccelCoefficients {
+    /// Coefficient of the previous sample.
     pub a: i16,
+    /// Coefficient of the sample before the previous one.
     pub b: i16,
 }
 
@@ -484,8 +539,9 @@ pub struct Interpreter {
     pub regs: Registers,
     pub mem: Memory,
     pub accel: Accelerator,
-    pub loop_counter: Option<u16>,
     pub old_reset_high: bool,
+    /// Addresses at which [`Interpreter::exec`] stops.
+    pub breakpoints: Vec<u16>,
 
     cached: Box<[Option<CachedIns>; 1 << 16]>,
 }
@@ -497,17 +553,17 @@ impl Default for Interpreter {
             regs: Default::default(),
             mem: Default::default(),
             accel: Default::default(),
-            loop_counter: Default::default(),
             old_reset_high: Default::default(),
+            breakpoints: Vec::new(),
             cached: util::boxed_array(None),
         }
     }
 }
 
-type OpcodeFn = for<'a, 'b> fn(&'a mut Interpreter, &'b mut System, Ins);
+type OpcodeFn = for<'a, 'b> fn(&'a mut Interpreter, &'b mut DspIo, Ins);
 
 static OPCODE_EXEC_LUT: [OpcodeFn; 1 << 8] = {
-    fn nop(_: &mut Interpreter, _: &mut System, _: Ins) {}
+    fn nop(_: &mut Interpreter, _: &mut DspIo, _: Ins) {}
     let mut lut = [nop as OpcodeFn; 1 << 8];
 
     lut[Opcode::Abs as usize] = Interpreter::abs as OpcodeFn;
@@ -637,10 +693,10 @@ static OPCODE_EXEC_LUT: [OpcodeFn; 1 << 8] = {
     lut
 };
 
-type ExtensionFn = for<'a, 'b, 'c> fn(&'a mut Interpreter, &'b mut System, Ins, &'c Registers);
+type ExtensionFn = for<'a, 'b, 'c> fn(&'a mut Interpreter, &'b mut DspIo, Ins, &'c Registers);
 
 static EXTENSION_EXEC_LUT: [ExtensionFn; 1 << 8] = {
-    fn nop(_: &mut Interpreter, _: &mut System, _: Ins, _: &Registers) {}
+    fn nop(_: &mut Interpreter, _: &mut DspIo, _: Ins, _: &Registers) {}
     let mut lut = [nop as ExtensionFn; 1 << 8];
 
     lut[ExtensionOpcode::Dr as usize] = Interpreter::ext_dr as ExtensionFn;
@@ -680,11 +736,7 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    pub fn check_interrupts(&mut self, sys: &mut System) {
-        if self.loop_counter.is_some() {
-            return;
-        }
-
+    pub fn check_interrupts(&mut self, io: &mut DspIo) {
         if self.regs.status.interrupt_enable()
             && let Some(wrap) = self.accel.wrapped.take()
         {
@@ -694,10 +746,10 @@ impl Interpreter {
         }
 
         // external interrupt does not care about status interrupt enable
-        if self.regs.status.external_interrupt_enable() && sys.dsp.control.interrupt() {
+        if self.regs.status.external_interrupt_enable() && io.control.interrupt() {
             std::hint::cold_path();
             tracing::warn!("DSP external interrupt raised");
-            sys.dsp.control.set_interrupt(false);
+            io.control.set_interrupt(false);
             self.raise_interrupt(Interrupt::External);
         }
     }
@@ -722,16 +774,36 @@ impl Interpreter {
         }
     }
 
-    /// Soft resets the DSP.
-    pub fn reset(&mut self, sys: &mut System) {
-        self.loop_counter = None;
+    /// Sets up a hardware loop which repeats the instruction after the current one `counter` times.
+    ///
+    /// Just like `bloop`, this goes through the loop stacks, so interrupts can still be serviced
+    /// between iterations.
+    fn repeat_next(&mut self, counter: u16) {
+        let start = self.pc.wrapping_add(1);
+        let len = if Ins::new(self.read_imem(start)).decoded().needs_extra {
+            2
+        } else {
+            1
+        };
 
+        if counter != 0 {
+            self.regs.call_stack.push(start);
+            self.regs.loop_stack.push(start.wrapping_add(len));
+            self.regs.loop_count.push(counter);
+        } else {
+            // skip the repeated instruction
+            self.pc = start.wrapping_add(len - 1);
+        }
+    }
+
+    /// Soft resets the DSP.
+    pub fn reset(&mut self, io: &mut DspIo) {
         self.regs = Default::default();
-        sys.dsp.dsp_mailbox = Mailbox::from_bits(0);
-        sys.dsp.cpu_mailbox = Mailbox::from_bits(0);
+        io.dsp_mailbox = Mailbox::from_bits(0);
+        io.cpu_mailbox = Mailbox::from_bits(0);
 
         self.cached.fill(None);
-        self.pc = if sys.dsp.control.reset_high() {
+        self.pc = if io.control.reset_high() {
             tracing::debug!("resetting at IROM (0x8000)");
             0x8000
         } else {
@@ -741,14 +813,14 @@ impl Interpreter {
     }
 
     /// Checks for reset.
-    pub fn check_reset(&mut self, sys: &mut System) {
-        if sys.dsp.control.reset() || (sys.dsp.control.reset_high() != self.old_reset_high) {
+    pub fn check_reset(&mut self, io: &mut DspIo, ram: &[u8]) {
+        if io.control.reset() || (io.control.reset_high() != self.old_reset_high) {
             std::hint::cold_path();
 
             // DMA from main memory if resetting at low
-            if !sys.dsp.control.reset_high() {
+            if !io.control.reset_high() {
                 tracing::debug!("DSP DMA stub from main memory");
-                let data = sys.mem.ram()[0x0100_0000..][..1024]
+                let data = ram[0x0100_0000..][..1024]
                     .chunks_exact(2)
                     .map(|c| u16::from_be_bytes([c[0], c[1]]));
 
@@ -758,39 +830,61 @@ impl Interpreter {
             }
 
             tracing::debug!("DSP reset");
-            self.reset(sys);
+            self.reset(io);
         }
 
-        sys.dsp.control.set_reset(false);
-        self.old_reset_high = sys.dsp.control.reset_high();
+        io.control.set_reset(false);
+        self.old_reset_high = io.control.reset_high();
+    }
+
+    /// Returns the range of RAM accessed by a DSP DMA of `length` bytes at `ram_base`, clamped to
+    /// the end of RAM.
+    fn dma_ram_range(ram: &[u8], ram_base: u32, length: u16) -> std::ops::Range<usize> {
+        let start = (ram_base as usize).min(ram.len());
+        let end = start + length as usize;
+        if end > ram.len() {
+            tracing::warn!(
+                "DSP DMA of {length:04X} bytes at RAM {ram_base:08X} goes past the end of RAM, \
+                 clamping it"
+            );
+        }
+
+        start..end.min(ram.len())
     }
 
     /// Performs the DSP DMA if the transfer is ongoing.
-    pub fn do_dma(&mut self, sys: &mut System) {
-        if sys.dsp.dsp_dma.control.transfer_ongoing() {
+    ///
+    /// Lengths are in bytes, but DSP memories are addressed in words: an odd length transfers
+    /// the high byte of the last word only.
+    ///
+    /// Returns the range of RAM written to, if any.
+    pub fn do_dma(&mut self, io: &mut DspIo, ram: &mut [u8]) -> Option<std::ops::Range<usize>> {
+        let mut written = None;
+        if io.dsp_dma.control.transfer_ongoing() {
             std::hint::cold_path();
 
-            let ram_base = sys.dsp.dsp_dma.ram_base.with_bits(26, 32, 0);
-            let dsp_base = sys.dsp.dsp_dma.dsp_base;
-            let length = sys.dsp.dsp_dma.length;
+            let ram_base = io.dsp_dma.ram_base.with_bits(26, 32, 0);
+            let dsp_base = io.dsp_dma.dsp_base;
+            let length = io.dsp_dma.length;
+            let range = Self::dma_ram_range(ram, ram_base, length);
 
             let (target, direction) = (
-                sys.dsp.dsp_dma.control.dsp_target(),
-                sys.dsp.dsp_dma.control.direction(),
+                io.dsp_dma.control.dsp_target(),
+                io.dsp_dma.control.direction(),
             );
 
+            // the last word of an odd length transfer only has its high byte
+            let word =
+                |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes.get(1).copied().unwrap_or(0)]);
+
             match (target, direction) {
                 (DspDmaTarget::Dmem, DspDmaDirection::FromRamToDsp) => {
                     tracing::debug!(
                         "DSP DMA {length:04X} bytes from RAM {ram_base:08X} to DMEM {dsp_base:04X}",
                     );
 
-                    for word in 0..(length / 2) {
-                        let data = u16::read_be_bytes(
-                            &sys.mem.ram()[(ram_base + 2 * word as u32) as usize..],
-                        );
-
-                        self.write_dmem(sys, dsp_base + word, data);
+                    for (i, bytes) in ram[range].chunks(2).enumerate() {
+                        self.write_dmem(io, dsp_base.wrapping_add(i as u16), word(bytes));
                     }
                 }
                 (DspDmaTarget::Dmem, DspDmaDirection::FromDspToRam) => {
@@ -798,12 +892,12 @@ impl Interpreter {
                         "DSP DMA {length:04X} bytes from DMEM {dsp_base:04X} to RAM {ram_base:08X}"
                     );
 
-                    for word in 0..(length / 2) {
-                        let data = self.read_dmem(sys, dsp_base + word);
-                        data.write_be_bytes(
-                            &mut sys.mem.ram_mut()[(ram_base + 2 * word as u32) as usize..],
-                        );
+                    for (i, bytes) in ram[range.clone()].chunks_mut(2).enumerate() {
+                        let data = self.read_dmem(io, dsp_base.wrapping_add(i as u16));
+                        bytes.copy_from_slice(&data.to_be_bytes()[..bytes.len()]);
                     }
+
+                    written = Some(range);
                 }
                 (DspDmaTarget::Imem, DspDmaDirection::FromRamToDsp) => {
                     std::hint::cold_path();
@@ -812,12 +906,8 @@ impl Interpreter {
                         "DSP DMA {length:04X} bytes from RAM {ram_base:08X} to IMEM {dsp_base:04X} (ucode)"
                     );
 
-                    for word in 0..(length / 2) {
-                        let data = u16::read_be_bytes(
-                            &sys.mem.ram()[(ram_base + 2 * word as u32) as usize..],
-                        );
-
-                        self.write_imem(dsp_base + word, data);
+                    for (i, bytes) in ram[range].chunks(2).enumerate() {
+                        self.write_imem(dsp_base.wrapping_add(i as u16), word(bytes));
                     }
 
                     // clear cache
@@ -826,10 +916,12 @@ impl Interpreter {
                 (DspDmaTarget::Imem, DspDmaDirection::FromDspToRam) => unimplemented!(),
             };
 
-            sys.dsp.dsp_dma.length = 0;
-            sys.dsp.dsp_dma.control.set_transfer_ongoing(false);
-            sys.dsp.control.set_dsp_dma_ongoing(false);
+            io.dsp_dma.length = 0;
+            io.dsp_dma.control.set_transfer_ongoing(false);
+            io.control.set_dma_ongoing(false);
         }
+
+        written
     }
 
     fn increment_aram_curr(&mut self, _wrap: Option<AccelWrap>) {
@@ -842,23 +934,23 @@ impl Interpreter {
         }
     }
 
-    fn read_aram_raw(&mut self, sys: &mut System, wrap: Option<AccelWrap>) -> u16 {
+    fn read_aram_raw(&mut self, io: &mut DspIo, wrap: Option<AccelWrap>) -> u16 {
         let format = self.accel.format;
         let index = self.accel.aram_curr.with_bit(31, false);
         let value = match format.sample() {
             SampleSize::Nibble => {
                 let address = index / 2;
-                let byte = u8::read_be_bytes(&sys.dsp.aram[address as usize..]) as u16;
+                let byte = u8::read_be_bytes(&io.aram[address as usize..]) as u16;
                 if index.is_multiple_of(2) {
                     byte >> 4
                 } else {
                     byte & 0xF
                 }
             }
-            SampleSize::Byte => u8::read_be_bytes(&sys.dsp.aram[index as usize..]) as u16,
+            SampleSize::Byte => u8::read_be_bytes(&io.aram[index as usize..]) as u16,
             SampleSize::Word => {
                 let address = index * 2;
-                u16::read_be_bytes(&sys.dsp.aram[address as usize..])
+                u16::read_be_bytes(&io.aram[address as usize..])
             }
             _ => panic!("reserved format"),
         };
@@ -873,8 +965,8 @@ impl Interpreter {
         value
     }
 
-    fn read_accelerator_raw(&mut self, sys: &mut System) -> u16 {
-        self.read_aram_raw(sys, Some(AccelWrap::RawRead))
+    fn read_accelerator_raw(&mut self, io: &mut DspIo) -> u16 {
+        self.read_aram_raw(io, Some(AccelWrap::RawRead))
     }
 
     fn pcm_gain(&self, value: i32) -> i32 {
@@ -893,12 +985,12 @@ impl Interpreter {
         self.accel.format.divisor().apply(acc) as i16
     }
 
-    fn adpcm_decode(&mut self, sys: &mut System) -> i16 {
+    fn adpcm_decode(&mut self, io: &mut DspIo) -> i16 {
         assert_eq!(self.accel.format.sample(), SampleSize::Nibble);
 
         if self.accel.aram_curr.is_multiple_of(16) {
-            let coeff_idx = self.read_aram_raw(sys, None) as u8;
-            let scale = self.read_aram_raw(sys, None) as u8;
+            let coeff_idx = self.read_aram_raw(io, None) as u8;
+            let scale = self.read_aram_raw(io, None) as u8;
             self.accel.predictor.set_coefficients(u3::new(coeff_idx));
             self.accel.predictor.set_scale_log2(u4::new(scale));
         }
@@ -909,7 +1001,7 @@ impl Interpreter {
         let coeffs = self.accel.coefficients[coeff_idx as usize];
         let scale = 1 << predictor.scale_log2().value();
 
-        let data = ((self.read_aram_raw(sys, None) as i8) << 4) >> 4;
+        let data = ((self.read_aram_raw(io, None) as i8) << 4) >> 4;
         let value = scale * data as i32;
 
         let prediction = coeffs.a as i32 * self.accel.previous_samples[0] as i32
@@ -919,16 +1011,16 @@ impl Interpreter {
         result.clamp(i16::MIN as i32, i16::MAX as i32) as i16
     }
 
-    fn read_accelerator_sample(&mut self, sys: &mut System) -> i16 {
+    fn read_accelerator_sample(&mut self, io: &mut DspIo) -> i16 {
         if !self.accel.has_data {
             return 0;
         }
 
         let value = match self.accel.format.decoding() {
-            SampleDecoding::AramAdpcm => self.adpcm_decode(sys),
+            SampleDecoding::AramAdpcm => self.adpcm_decode(io),
             SampleDecoding::AcinPcm => self.pcm_decode(self.accel.input as i32),
             SampleDecoding::AramPcm => {
-                let value = self.read_aram_raw(sys, Some(AccelWrap::SampleRead)) as i16;
+                let value = self.read_aram_raw(io, Some(AccelWrap::SampleRead)) as i16;
                 self.pcm_decode(value as i32)
             }
             SampleDecoding::AcinPcmInc => {
@@ -943,9 +1035,10 @@ impl Interpreter {
         value
     }
 
-    pub fn read_mmio(&mut self, sys: &mut System, offset: u8) -> u16 {
+    pub fn read_mmio(&mut self, io: &mut DspIo, offset: u8) -> u16 {
         match offset {
-            // Coefficients
+            // Coefficients: 0xA0 + 2n is `a` of pair n, 0xA1 + 2n is its `b`. Plain storage, read
+            // by the accelerator when decoding
             0xA0..=0xAF => {
                 let index = (offset as usize - 0xA0) / 2;
                 if offset.is_multiple_of(2) {
@@ -956,14 +1049,14 @@ impl Interpreter {
             }
 
             // DMA
-            0xC9 => sys.dsp.dsp_dma.control.to_bits(),
-            0xCB => sys.dsp.dsp_dma.length,
-            0xCD => sys.dsp.dsp_dma.dsp_base,
-            0xCE => (sys.dsp.dsp_dma.ram_base >> 16) as u16,
-            0xCF => sys.dsp.dsp_dma.ram_base as u16,
+            0xC9 => io.dsp_dma.control.to_bits(),
+            0xCB => io.dsp_dma.length,
+            0xCD => io.dsp_dma.dsp_base,
+            0xCE => (io.dsp_dma.ram_base >> 16) as u16,
+            0xCF => io.dsp_dma.ram_base as u16,
 
             // Accelerator
-            0xD3 => self.read_accelerator_raw(sys),
+            0xD3 => self.read_accelerator_raw(io),
             0xD4 => self.accel.aram_start.bits(16, 32) as u16,
             0xD5 => self.accel.aram_start.bits(0, 16) as u16,
             0xD6 => self.accel.aram_end.bits(16, 32) as u16,
@@ -973,32 +1066,32 @@ impl Interpreter {
             0xDA => self.accel.predictor.to_bits(),
             0xDB => self.accel.previous_samples[0] as u16,
             0xDC => self.accel.previous_samples[1] as u16,
-            0xDD => self.read_accelerator_sample(sys) as u16,
+            0xDD => self.read_accelerator_sample(io) as u16,
             0xDE => self.accel.gain as u16,
             0xDF => self.accel.input as u16,
 
             // Mailboxes
-            0xFC => sys.dsp.dsp_mailbox.high_and_status(),
-            0xFD => sys.dsp.dsp_mailbox.low(),
-            0xFE => sys.dsp.cpu_mailbox.high_and_status(),
+            0xFC => io.dsp_mailbox.high_and_status(),
+            0xFD => io.dsp_mailbox.low(),
+            0xFE => io.cpu_mailbox.high_and_status(),
             0xFF => {
-                if sys.dsp.cpu_mailbox.status() {
+                if io.cpu_mailbox.status() {
                     tracing::trace!(
                         "received from CPU mailbox: 0x{:08X}",
-                        sys.dsp.cpu_mailbox.data().value()
+                        io.cpu_mailbox.data().value()
                     );
-                    sys.dsp.cpu_mailbox.set_status(false);
+                    io.cpu_mailbox.set_status(false);
                 }
 
-                sys.dsp.cpu_mailbox.low()
+                io.cpu_mailbox.low()
             }
             _ => unimplemented!("read from {offset:02X}"),
         }
     }
 
-    pub fn write_mmio(&mut self, sys: &mut System, offset: u8, value: u16) {
+    pub fn write_mmio(&mut self, io: &mut DspIo, offset: u8, value: u16) {
         match offset {
-            // Coefficients
+            // Coefficients, see the read side
             0xA0..=0xAF => {
                 let index = (offset as usize - 0xA0) / 2;
                 if offset.is_multiple_of(2) {
@@ -1009,24 +1102,20 @@ impl Interpreter {
             }
 
             // DMA
-            0xC9 => sys.dsp.dsp_dma.control = DspDmaControl::from_bits(value),
+            0xC9 => io.dsp_dma.control = DspDmaControl::from_bits(value),
             0xCB => {
-                sys.dsp.dsp_dma.length = value;
-                sys.dsp.dsp_dma.control.set_transfer_ongoing(true);
-                sys.dsp.control.set_dsp_dma_ongoing(true);
-            }
-            0xCD => sys.dsp.dsp_dma.dsp_base = value,
-            0xCE => {
-                sys.dsp.dsp_dma.ram_base = sys.dsp.dsp_dma.ram_base.with_bits(16, 32, value as u32)
-            }
-            0xCF => {
-                sys.dsp.dsp_dma.ram_base = sys.dsp.dsp_dma.ram_base.with_bits(0, 16, value as u32)
+                io.dsp_dma.length = value;
+                io.dsp_dma.control.set_transfer_ongoing(true);
+                io.control.set_dma_ongoing(true);
             }
+            0xCD => io.dsp_dma.dsp_base = value,
+            0xCE => io.dsp_dma.ram_base = io.dsp_dma.ram_base.with_bits(16, 32, value as u32),
+            0xCF => io.dsp_dma.ram_base = io.dsp_dma.ram_base.with_bits(0, 16, value as u32),
 
             // Interrupt
             0xFB => {
                 if value > 0 {
-                    sys.dsp.control.set_dsp_interrupt(true);
+                    io.control.set_dsp_interrupt(true);
                 }
             }
 
@@ -1040,8 +1129,7 @@ impl Interpreter {
                 );
 
                 value.write_be_bytes(
-                    sys.dsp.aram[self.accel.aram_curr.with_bit(31, false) as usize..]
-                        .as_mut_bytes(),
+                    io.aram[self.accel.aram_curr.with_bit(31, false) as usize..].as_mut_bytes(),
                 );
 
                 self.accel.aram_curr += 1;
@@ -1068,22 +1156,23 @@ impl Interpreter {
 
             // Mailboxes
             0xFC => {
-                sys.dsp.dsp_mailbox.set_high(u15::new(value));
+                // the top bit is the status, which is only set by writing the low half
+                io.dsp_mailbox.set_high(u15::new(value & 0x7FFF));
             }
             0xFD => {
-                sys.dsp.dsp_mailbox.set_low(value);
-                sys.dsp.dsp_mailbox.set_status(true);
+                io.dsp_mailbox.set_low(value);
+                io.dsp_mailbox.set_status(true);
             }
             _ => unimplemented!("write to {offset:02X}"),
         }
     }
 
     /// Reads from data memory.
-    pub fn read_dmem(&mut self, sys: &mut System, addr: u16) -> u16 {
+    pub fn read_dmem(&mut self, io: &mut DspIo, addr: u16) -> u16 {
         match addr {
             0x0000..0x1000 => self.mem.dram[addr as usize],
             0x1000..0x1800 => self.mem.coef[addr as usize - 0x1000],
-            0xFF00.. => self.read_mmio(sys, addr as u8),
+            0xFF00.. => self.read_mmio(io, addr as u8),
             _ => {
                 std::hint::cold_path();
                 0
@@ -1092,21 +1181,21 @@ impl Interpreter {
     }
 
     /// Writes to data memory.
-    pub fn write_dmem(&mut self, sys: &mut System, addr: u16, value: u16) {
+    pub fn write_dmem(&mut self, io: &mut DspIo, addr: u16, value: u16) {
         match addr {
             0x0000..0x1000 => self.mem.dram[addr as usize] = value,
             0x1000..0x1800 => {
                 std::hint::cold_path();
                 tracing::warn!("writing to coefficient data");
             }
-            0xFF00.. => self.write_mmio(sys, addr as u8, value),
+            0xFF00.. => self.write_mmio(io, addr as u8, value),
             _ => (),
         }
     }
 
     /// Reads from instruction memory.
     #[inline(always)]
-    pub fn read_imem(&mut self, addr: u16) -> u16 {
+    pub fn read_imem(&self, addr: u16) -> u16 {
         match addr {
             0x0000..0x1000 => self.mem.iram[addr as usize],
             0x8000..0x9000 => {
@@ -1212,23 +1301,47 @@ impl Interpreter {
             || self.is_waiting_for_dsp_mail_inner(-3)
     }
 
+    /// Whether the DSP can't make progress without the CPU, i.e. it is halted or spinning on a
+    /// mailbox.
+    #[inline(always)]
+    pub fn is_blocked(&mut self, io: &DspIo) -> bool {
+        io.control.halt()
+            || !io.cpu_mailbox.status() && self.is_waiting_for_cpu_mail()
+            || io.dsp_mailbox.status() && self.is_waiting_for_dsp_mail()
+    }
+
+    /// Fetches the instruction at the given address in instruction memory, along with its length
+    /// in words.
+    fn fetch(&self, addr: u16) -> (Ins, u16) {
+        let mut ins = Ins::new(self.read_imem(addr));
+        if ins.decoded().needs_extra {
+            ins.extra = self.read_imem(addr.wrapping_add(1));
+            (ins, 2)
+        } else {
+            (ins, 1)
+        }
+    }
+
+    /// Decodes `count` instructions from instruction memory, starting at `start`. Returns the
+    /// address of each instruction, along with the instruction itself and its textual form.
+    pub fn disassemble(&self, start: u16, count: usize) -> Vec<(u16, Ins, String)> {
+        let mut listing = Vec::with_capacity(count);
+        let mut addr = start;
+        for _ in 0..count {
+            let (ins, len) = self.fetch(addr);
+            listing.push((addr, ins, ins.to_string()));
+            addr = addr.wrapping_add(len);
+        }
+
+        listing
+    }
+
     fn fetch_decode_and_cache(&mut self) -> CachedIns {
         // fetch
-        let mut ins = Ins::new(self.read_imem(self.pc));
+        let (ins, len) = self.fetch(self.pc);
 
         // decode
         let decoded = ins.decoded();
-        let extra = decoded
-            .needs_extra
-            .then_some(self.read_imem(self.pc.wrapping_add(1)));
-
-        let len = if let Some(extra) = extra {
-            ins.extra = extra;
-            2
-        } else {
-            1
-        };
-
         let main = OPCODE_EXEC_LUT[decoded.opcode as usize];
         let extension = decoded
             .extension
@@ -1246,15 +1359,18 @@ impl Interpreter {
         cached
     }
 
-    pub fn exec(&mut self, sys: &mut System, instructions: u32) {
+    /// Executes up to `instructions` instructions. Returns whether a breakpoint was hit, in which
+    /// case execution stops right before the instruction at the breakpoint. The first instruction
+    /// is always executed, so that execution can be resumed from a breakpoint.
+    pub fn exec(&mut self, io: &mut DspIo, instructions: u32) -> bool {
         let mut i = 0;
         while i < instructions {
-            if sys.dsp.control.halt() {
+            if io.control.halt() {
                 std::hint::cold_path();
                 break;
             }
 
-            self.check_interrupts(sys);
+            self.check_interrupts(io);
             self.check_stacks();
 
             // have we cached this instruction already?
@@ -1268,29 +1384,467 @@ impl Interpreter {
             // execute
             if let Some(extension) = ins.extension {
                 let regs_previous = self.regs.clone();
-                (ins.main)(self, sys, ins.ins);
-                (extension)(self, sys, ins.ins, &regs_previous);
+                (ins.main)(self, io, ins.ins);
+                (extension)(self, io, ins.ins, &regs_previous);
             } else {
-                (ins.main)(self, sys, ins.ins);
+                (ins.main)(self, io, ins.ins);
             }
 
-            if let Some(loop_counter) = &mut self.loop_counter {
-                if *loop_counter == 0 {
-                    std::hint::cold_path();
-                    self.loop_counter = None;
-                    self.pc += 1;
-                } else {
-                    *loop_counter -= 1;
-                }
-            } else {
-                self.pc = self.pc.wrapping_add(ins.len);
+            self.pc = self.pc.wrapping_add(ins.len);
+            i += 1;
+
+            if !self.breakpoints.is_empty() && self.breakpoints.contains(&self.pc) {
+                std::hint::cold_path();
+                return true;
             }
+        }
 
-            i += 1;
+        false
+    }
+
+    /// Executes a single instruction. Returns whether it stopped at a breakpoint.
+    pub fn step(&mut self, io: &mut DspIo) -> bool {
+        self.exec(io, 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn io() -> DspIo {
+        let mut io = DspIo::new();
+        io.control.set_halt(false);
+        io
+    }
+
+    #[test]
+    fn interrupt_during_loop() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // external interrupt handler: iar $ar1; rti
+        dsp.mem.iram[Interrupt::External as usize * 2] = 0x0009;
+        dsp.mem.iram[Interrupt::External as usize * 2 + 1] = 0x02FF;
+
+        // loopi #4; iar $ar0; halt
+        dsp.mem.iram[0x20] = 0x1004;
+        dsp.mem.iram[0x21] = 0x0008;
+        dsp.mem.iram[0x22] = 0x0021;
+        dsp.pc = 0x20;
+
+        // loopi + first iteration
+        dsp.step(&mut io);
+        dsp.step(&mut io);
+        assert_eq!(dsp.regs.addressing[0], 1);
+
+        // interrupt must be serviced before the loop finishes
+        io.control.set_interrupt(true);
+        dsp.step(&mut io);
+        assert_eq!(dsp.regs.addressing[1], 1);
+        assert_eq!(dsp.regs.addressing[0], 1);
+
+        while !io.control.halt() {
+            dsp.step(&mut io);
+        }
+
+        assert_eq!(dsp.regs.addressing[0], 4);
+        assert_eq!(dsp.regs.addressing[1], 1);
+        assert!(dsp.regs.loop_stack.is_empty());
+        assert!(dsp.regs.call_stack.is_empty());
+    }
+
+    #[test]
+    fn loop_zero_skips() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // loopi #0; iar $ar0; halt
+        dsp.mem.iram[0x20] = 0x1000;
+        dsp.mem.iram[0x21] = 0x0008;
+        dsp.mem.iram[0x22] = 0x0021;
+        dsp.pc = 0x20;
+
+        while !io.control.halt() {
+            dsp.step(&mut io);
+        }
+
+        assert_eq!(dsp.regs.addressing[0], 0);
+    }
+
+    #[test]
+    fn dma_odd_length_and_past_ram() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        let mut ram = vec![0; 0x100];
+        ram[0x10..0x15].copy_from_slice(&[0x12, 0x34, 0x56, 0x78, 0x9A]);
+
+        // the last word of an odd length transfer only gets its high byte
+        io.dsp_dma.ram_base = 0x10;
+        io.dsp_dma.dsp_base = 0x40;
+        io.dsp_dma.length = 5;
+        io.dsp_dma.control = DspDmaControl::default().with_transfer_ongoing(true);
+        assert_eq!(dsp.do_dma(&mut io, &mut ram), None);
+
+        assert_eq!(dsp.mem.dram[0x40..0x43], [0x1234, 0x5678, 0x9A00]);
+        assert!(!io.dsp_dma.control.transfer_ongoing());
+
+        // transfers going past the end of RAM are clamped
+        io.dsp_dma.ram_base = 0xFD;
+        io.dsp_dma.length = 8;
+        io.dsp_dma.control = DspDmaControl::default()
+            .with_direction(DspDmaDirection::FromDspToRam)
+            .with_transfer_ongoing(true);
+        assert_eq!(dsp.do_dma(&mut io, &mut ram), Some(0xFD..0x100));
+
+        assert_eq!(ram[0xFD..], [0x12, 0x34, 0x56]);
+        assert_eq!(io.dsp_dma.length, 0);
+    }
+
+    #[test]
+    fn adpcm_uses_coefficient_registers() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // every register holds its index, except for pair 3, which is a = 1.0 and b = -0.5
+        for (offset, value) in (0xA0..=0xAF).zip(0u16..) {
+            dsp.write_mmio(&mut io, offset, value);
+        }
+        dsp.write_mmio(&mut io, 0xA6, 0x0800);
+        dsp.write_mmio(&mut io, 0xA7, (-0x0400_i16) as u16);
+
+        assert_eq!(dsp.read_mmio(&mut io, 0xA0), 0);
+        assert_eq!(dsp.read_mmio(&mut io, 0xA5), 5);
+        assert_eq!(dsp.read_mmio(&mut io, 0xA6), 0x0800);
+        assert_eq!(dsp.read_mmio(&mut io, 0xA7), 0xFC00);
+        assert_eq!(dsp.accel.coefficients[3].b, -0x0400);
+
+        // a frame using pair 3 with a scale of 1, whose first sample is 5
+        io.aram[0] = 0x30;
+        io.aram[1] = 0x50;
+        dsp.write_mmio(&mut io, 0xD1, AccelFormat::default().to_bits());
+        dsp.write_mmio(&mut io, 0xD7, 0x0F);
+        dsp.write_mmio(&mut io, 0xDB, 100);
+        dsp.write_mmio(&mut io, 0xDC, 50);
+
+        // 1.0 * 100 - 0.5 * 50 + 5
+        assert_eq!(dsp.read_mmio(&mut io, 0xDD), 80);
+        assert_eq!(dsp.read_mmio(&mut io, 0xDA), 0x0030);
+    }
+
+    /// Executes the given code (which must end with a `halt`) from address 0x20.
+    fn run(io: &mut DspIo, dsp: &mut Interpreter, code: &[u16]) {
+        dsp.mem.iram[0x20..][..code.len()].copy_from_slice(code);
+        dsp.pc = 0x20;
+
+        while !io.control.halt() {
+            dsp.step(io);
+        }
+    }
+
+    #[test]
+    fn return_with_empty_stacks() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        dsp.mem.iram[0] = 0x0021;
+
+        // ret
+        run(&mut io, &mut dsp, &[0x02DF]);
+        assert!(dsp.regs.call_stack.is_empty());
+
+        // rti
+        io.control.set_halt(false);
+        dsp.regs.set(Reg::Status, 0x0010);
+        run(&mut io, &mut dsp, &[0x02FF]);
+        assert_eq!(dsp.regs.status.to_bits(), 0);
+    }
+
+    #[test]
+    fn config_selects_short_address_page() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        io.dsp_dma.ram_base = 0x1234_0000;
+        dsp.regs.acc40[0].low = 0xABCD;
+
+        // only the low byte is kept
+        dsp.regs.set(Reg::Config, 0x1102);
+        assert_eq!(dsp.regs.get(Reg::Config), 0x02);
+
+        // srs @0x10, $ac0.l; halt
+        run(&mut io, &mut dsp, &[0x2C10, 0x0021]);
+        assert_eq!(dsp.mem.dram[0x0210], 0xABCD);
+
+        // srsh @0x42, $ac1.h; halt
+        io.control.set_halt(false);
+        dsp.regs.acc40[1].high = 0xFE;
+        run(&mut io, &mut dsp, &[0x2942, 0x0021]);
+        assert_eq!(dsp.mem.dram[0x0242], 0xFFFE);
+
+        // lrs $ax0.l, @0xCE; halt
+        io.control.set_halt(false);
+        dsp.regs.set(Reg::Config, 0xFF);
+        run(&mut io, &mut dsp, &[0x20CE, 0x0021]);
+        assert_eq!(dsp.regs.get(Reg::Acc32Low0), 0x1234);
+    }
+
+    #[test]
+    fn short_address_formation() {
+        // (page, imm, address)
+        let reference = [
+            (0x00, 0x00, 0x0000),
+            (0x00, 0x80, 0x0080),
+            (0x02, 0x10, 0x0210),
+            (0x7F, 0xFF, 0x7FFF),
+            (0x80, 0x7F, 0x807F),
+            (0xFF, 0x00, 0xFF00),
+            (0xFF, 0xCE, 0xFFCE),
+        ];
+
+        for (page, imm, addr) in reference {
+            assert_eq!(short_address(page, imm), addr);
+        }
+
+        for page in 0..=0xFF {
+            let config = Config::from_bits(page);
+            for imm in 0..=0xFF {
+                let addr = short_address(page, imm);
+                assert_eq!(addr.to_be_bytes(), [page, imm]);
+                assert_eq!(config.short_address(imm), addr);
+            }
+        }
+    }
+
+    #[test]
+    fn neg_most_negative_overflows() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        dsp.regs.acc40[1].set(Acc40::MIN);
+
+        // neg $acc1; halt
+        run(&mut io, &mut dsp, &[0x7D00, 0x0021]);
+
+        // -MIN doesn't fit in 40 bits and wraps back to MIN
+        assert_eq!(dsp.regs.acc40[1].get(), Acc40::MIN);
+        assert!(dsp.regs.status.overflow());
+        assert!(dsp.regs.status.sign());
+
+        // neg $acc1; halt
+        io.control.set_halt(false);
+        dsp.regs.acc40[1].set((1 << 39) - 1);
+        run(&mut io, &mut dsp, &[0x7D00, 0x0021]);
+
+        assert_eq!(dsp.regs.acc40[1].get(), -((1 << 39) - 1));
+        assert!(!dsp.regs.status.overflow());
+    }
+
+    #[test]
+    fn set_checked_reports_wrapping() {
+        const MAX: i64 = (1 << 39) - 1;
+
+        let mut acc = Acc40::default();
+        assert_eq!(acc.set_checked(MAX), (MAX, false));
+        assert_eq!(acc.set_checked(Acc40::MIN), (Acc40::MIN, false));
+        assert_eq!(acc.set_checked(MAX + 1), (Acc40::MIN, true));
+        assert_eq!(acc.set_checked(Acc40::MIN - 1), (MAX, true));
+        assert_eq!(acc.get(), MAX);
+    }
+
+    #[test]
+    fn addpaxz_rounding_overflows() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // rounding the largest product goes past 40 bits, even though $ax0 is zero
+        dsp.regs.product.set((1 << 39) - 1);
+
+        // addpaxz $acc0, $ax0; halt
+        run(&mut io, &mut dsp, &[0xF800, 0x0021]);
+
+        assert_eq!(dsp.regs.acc40[0].get(), Acc40::MIN);
+        assert!(dsp.regs.status.overflow());
+        assert!(dsp.regs.status.sign());
+    }
+
+    #[test]
+    fn cmpis_acc1_negative() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        dsp.regs.acc40[1].set(-128 << 16);
+
+        // cmpis $acc1, #-128; halt
+        run(&mut io, &mut dsp, &[0x0780, 0x0021]);
+
+        assert!(dsp.regs.status.arithmetic_zero());
+        assert!(!dsp.regs.status.sign());
+    }
+
+    #[test]
+    fn cmpis_acc0_minus_one() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // cmpis $acc0, #-1; halt
+        run(&mut io, &mut dsp, &[0x06FF, 0x0021]);
+
+        // 0 - (-1) = 1
+        assert!(!dsp.regs.status.arithmetic_zero());
+        assert!(!dsp.regs.status.sign());
+        assert_eq!(dsp.regs.acc40[0].get(), 0);
+    }
+
+    #[test]
+    fn cmp_40_bit_boundary() {
+        const MAX: i64 = (1 << 39) - 1;
+
+        // (acc0, acc1, carry, overflow, sign, zero)
+        let cases = [
+            // 0 - MIN = 2^39, wraps to MIN
+            (0, Acc40::MIN, false, true, true, false),
+            // MAX - (-1) = 2^39, wraps to MIN
+            (MAX, -1, false, true, true, false),
+            // MIN - 1 wraps to MAX
+            (Acc40::MIN, 1, true, true, false, false),
+            // MIN - MAX = 1 - 2^40, wraps to 1
+            (Acc40::MIN, MAX, true, true, false, false),
+            // MAX - MIN = 2^40 - 1, wraps to -1
+            (MAX, Acc40::MIN, false, true, true, false),
+            // no overflow right at the boundaries
+            (Acc40::MIN, Acc40::MIN, true, false, false, true),
+            (-1, MAX, true, false, true, false),
+            (MAX, 0, true, false, false, false),
+            (0, MAX, false, false, true, false),
+        ];
+
+        for (acc0, acc1, carry, overflow, sign, zero) in cases {
+            let mut io = io();
+            let mut dsp = Interpreter::default();
+            dsp.regs.acc40[0].set(acc0);
+            dsp.regs.acc40[1].set(acc1);
+
+            // cmp; halt
+            run(&mut io, &mut dsp, &[0x8200, 0x0021]);
+
+            let status = dsp.regs.status;
+            let case = format!("{acc0:#X} - {acc1:#X}");
+            assert_eq!(status.carry(), carry, "carry of {case}");
+            assert_eq!(status.overflow(), overflow, "overflow of {case}");
+            assert_eq!(status.sign(), sign, "sign of {case}");
+            assert_eq!(status.arithmetic_zero(), zero, "zero of {case}");
+
+            // comparisons leave the accumulators untouched
+            assert_eq!(dsp.regs.acc40[0].get(), acc0);
+            assert_eq!(dsp.regs.acc40[1].get(), acc1);
         }
     }
 
-    pub fn step(&mut self, sys: &mut System) {
-        self.exec(sys, 1);
+    #[test]
+    fn cmpi_40_bit_boundary() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        dsp.regs.acc40[1].set(Acc40::MIN);
+
+        // cmpi $acc1, #0x7FFF; halt
+        run(&mut io, &mut dsp, &[0x0380, 0x7FFF, 0x0021]);
+
+        // MIN - 0x7FFF_0000 wraps to a positive value
+        assert!(dsp.regs.status.carry());
+        assert!(dsp.regs.status.overflow());
+        assert!(!dsp.regs.status.sign());
+
+        // cmpis $acc0, #-128; halt
+        io.control.set_halt(false);
+        dsp.regs.acc40[0].set((1 << 39) - 1);
+        run(&mut io, &mut dsp, &[0x0680, 0x0021]);
+
+        // MAX + 0x80_0000 wraps to a negative value, borrowing since -128 is a large unsigned value
+        assert!(!dsp.regs.status.carry());
+        assert!(dsp.regs.status.overflow());
+        assert!(dsp.regs.status.sign());
+    }
+
+    #[test]
+    fn addis_acc1_negative() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // addis $acc1, #-2; halt
+        run(&mut io, &mut dsp, &[0x05FE, 0x0021]);
+
+        assert_eq!(dsp.regs.acc40[1].get(), -2 << 16);
+        assert_eq!(dsp.regs.acc40[0].get(), 0);
+        assert!(dsp.regs.status.sign());
+    }
+
+    #[test]
+    fn andf_andcf_acc1() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+        dsp.regs.acc40[1].mid = 0x00F0;
+
+        // andf $acc1, #0x0F00; halt
+        run(&mut io, &mut dsp, &[0x03A0, 0x0F00, 0x0021]);
+        assert!(dsp.regs.status.logic_zero());
+
+        // andcf $acc1, #0x00F0; halt
+        io.control.set_halt(false);
+        run(&mut io, &mut dsp, &[0x03C0, 0x00F0, 0x0021]);
+        assert!(dsp.regs.status.logic_zero());
+
+        // andcf $acc0, #0x00F0; halt
+        io.control.set_halt(false);
+        run(&mut io, &mut dsp, &[0x02C0, 0x00F0, 0x0021]);
+        assert!(!dsp.regs.status.logic_zero());
+    }
+
+    #[test]
+    fn disassemble_listing() {
+        let mut dsp = Interpreter::default();
+
+        // lrs $ACM0, @cmbh; andcf $ACM0, #0x8000; jlnz 0x0010; halt
+        dsp.mem.iram[0x10..][..6]
+            .copy_from_slice(&[0x26FE, 0x02C0, 0x8000, 0x029C, 0x0010, 0x0021]);
+
+        let listing = dsp.disassemble(0x10, 4);
+        let addrs = listing.iter().map(|(addr, ..)| *addr).collect::<Vec<_>>();
+        let text = listing
+            .iter()
+            .map(|(.., text)| text.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(addrs, [0x10, 0x11, 0x13, 0x15]);
+        assert_eq!(text, ["lrs", "andcf #0x8000", "jlnz #0x0010", "halt"]);
+        assert_eq!(listing[1].1.extra, 0x8000);
+
+        // unconditional forms keep their plain mnemonic
+        assert_eq!(Ins::with_extra(0x029F, 0x0010).to_string(), "jmp #0x0010");
+        assert_eq!(Ins::new(0x02DF).to_string(), "ret");
+        assert_eq!(Ins::new(0x02DD).to_string(), "retlz");
+        assert_eq!(Ins::new(0x1705).to_string(), "jrz");
+
+        // IROM is read transparently
+        dsp.mem.irom[0] = 0x0021;
+        let listing = dsp.disassemble(0x8000, 1);
+        assert_eq!(listing[0].0, 0x8000);
+        assert_eq!(listing[0].2, "halt");
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut io = io();
+        let mut dsp = Interpreter::default();
+
+        // iar $ar0; iar $ar0; iar $ar0; halt
+        dsp.mem.iram[0x20..][..4].copy_from_slice(&[0x0008, 0x0008, 0x0008, 0x0021]);
+        dsp.pc = 0x20;
+        dsp.breakpoints.push(0x22);
+
+        assert!(dsp.exec(&mut io, 16));
+        assert_eq!(dsp.pc, 0x22);
+        assert_eq!(dsp.regs.addressing[0], 2);
+
+        // resuming executes the instruction at the breakpoint
+        assert!(!dsp.exec(&mut io, 16));
+        assert!(io.control.halt());
+        assert_eq!(dsp.regs.addressing[0], 3);
     }
 }
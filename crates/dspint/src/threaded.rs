@@ -0,0 +1,336 @@
+//! Running the interpreter on a dedicated thread.
+//!
+//! The worker thread owns the interpreter along with a replica of the [`DspIo`] registers and is
+//! allowed to run ahead of the CPU by up to `slack` instructions. Both sides only synchronize at
+//! sync points, where:
+//!
+//! - the registers changed by each side are merged back into the [`DspIo`] of the system
+//! - ARAM is given back to the system, and lent again when the worker is resumed
+//! - the owner of the interpreter can perform work that needs main memory (e.g. DSP DMA)
+//!
+//! While lent, ARAM DMAs issued by the CPU are deferred until the next sync point.
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::JoinHandle;
+
+use lazuli::system::dspi::{Control, DspDma, DspIo, Mailbox};
+
+use crate::Interpreter;
+
+/// How many instructions to execute between checks for whether the DSP is blocked on the CPU.
+const CHUNK: u32 = 512;
+
+/// The [`DspIo`] registers replicated in the worker.
+#[derive(Clone)]
+struct IoRegisters {
+    control: Control,
+    dsp_mailbox: Mailbox,
+    cpu_mailbox: Mailbox,
+    dsp_dma: DspDma,
+}
+
+impl IoRegisters {
+    fn of(io: &DspIo) -> Self {
+        Self {
+            control: io.control,
+            dsp_mailbox: io.dsp_mailbox,
+            cpu_mailbox: io.cpu_mailbox,
+            dsp_dma: io.dsp_dma.clone(),
+        }
+    }
+
+    fn store(self, io: &mut DspIo) {
+        io.control = self.control;
+        io.dsp_mailbox = self.dsp_mailbox;
+        io.cpu_mailbox = self.cpu_mailbox;
+        io.dsp_dma = self.dsp_dma;
+    }
+
+    /// Merges the changes made by the CPU (`cpu`) and by the DSP (`dsp`) since `base`.
+    ///
+    /// Control bits changed by the DSP take precedence. A mailbox changed by its writer takes
+    /// precedence over the reader clearing its status, since the writer only writes to an empty
+    /// mailbox - this way, no mail is ever lost.
+    fn merge(base: &Self, cpu: Self, dsp: Self) -> Self {
+        let changed = base.control.to_bits() ^ dsp.control.to_bits();
+        let control = (cpu.control.to_bits() & !changed) | (dsp.control.to_bits() & changed);
+
+        let cpu_mailbox = if cpu.cpu_mailbox.to_bits() != base.cpu_mailbox.to_bits() {
+            cpu.cpu_mailbox
+        } else {
+            dsp.cpu_mailbox
+        };
+
+        let dsp_mailbox = if dsp.dsp_mailbox.to_bits() != base.dsp_mailbox.to_bits() {
+            dsp.dsp_mailbox
+        } else {
+            cpu.dsp_mailbox
+        };
+
+        Self {
+            control: Control::from_bits(control),
+            dsp_mailbox,
+            cpu_mailbox,
+            // only ever written by the DSP
+            dsp_dma: dsp.dsp_dma,
+        }
+    }
+}
+
+/// Work sent to the worker thread.
+struct Batch {
+    interpreter: Interpreter,
+    io: DspIo,
+    instructions: u32,
+    /// Whether the batch stopped at a breakpoint.
+    hit_breakpoint: bool,
+}
+
+impl Batch {
+    fn run(&mut self) {
+        profiling::scope!("DSP slice");
+        let mut remaining = self.instructions;
+        while remaining > 0 {
+            if self.interpreter.is_blocked(&self.io) {
+                // nothing to do until the CPU catches up
+                self.interpreter.check_interrupts(&mut self.io);
+                break;
+            }
+
+            let chunk = remaining.min(CHUNK);
+            if self.interpreter.exec(&mut self.io, chunk) {
+                self.hit_breakpoint = true;
+                break;
+            }
+
+            remaining -= chunk;
+        }
+    }
+}
+
+fn worker(batches: Receiver<Batch>, done: Sender<Batch>) {
+    profiling::register_thread!();
+
+    while let Ok(mut batch) = batches.recv() {
+        batch.run();
+        if done.send(batch).is_err() {
+            break;
+        }
+    }
+}
+
+/// An [`Interpreter`] running on a dedicated thread.
+pub struct Threaded {
+    /// The current batch, if it is not in flight.
+    home: Option<Batch>,
+    /// The registers as they were when the batch in flight was dispatched.
+    base: IoRegisters,
+    /// How many instructions the DSP is allowed to run ahead of the CPU.
+    slack: u32,
+    /// How many instructions the CPU side has advanced.
+    cpu_time: u64,
+    /// How many instructions the DSP side has been allowed to advance.
+    dsp_time: u64,
+
+    batches: Option<Sender<Batch>>,
+    done: Receiver<Batch>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Threaded {
+    pub fn new(interpreter: Interpreter, slack: u32) -> Self {
+        let (batches, worker_batches) = std::sync::mpsc::channel();
+        let (worker_done, done) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("dsp".into())
+            .spawn(move || worker(worker_batches, worker_done))
+            .expect("spawning DSP thread");
+
+        let io = DspIo::new();
+        Self {
+            base: IoRegisters::of(&io),
+            home: Some(Batch {
+                interpreter,
+                io,
+                instructions: 0,
+                hit_breakpoint: false,
+            }),
+            slack,
+            cpu_time: 0,
+            dsp_time: 0,
+
+            batches: Some(batches),
+            done,
+            thread: Some(thread),
+        }
+    }
+
+    /// Advances the CPU side by the given amount of instructions. Returns whether a sync point
+    /// has been reached, in which case [`Threaded::sync`] and [`Threaded::dispatch`] should be
+    /// called.
+    pub fn advance(&mut self, instructions: u32) -> bool {
+        self.cpu_time += instructions as u64;
+        self.home.is_some() || self.dsp_time <= self.cpu_time
+    }
+
+    /// Waits for the worker to finish its batch and merges its state into `io`. Returns the
+    /// interpreter, which stays available until [`Threaded::dispatch`] is called.
+    pub fn sync(&mut self, io: &mut DspIo) -> &mut Interpreter {
+        if self.home.is_none() {
+            let mut batch = self.done.recv().expect("DSP thread is alive");
+
+            let merged =
+                IoRegisters::merge(&self.base, IoRegisters::of(io), IoRegisters::of(&batch.io));
+            merged.store(io);
+
+            std::mem::swap(&mut io.aram, &mut batch.io.aram);
+            io.aram_lent = false;
+
+            self.home = Some(batch);
+        }
+
+        &mut self.home.as_mut().unwrap().interpreter
+    }
+
+    /// The interpreter, if synchronized with (i.e. between [`Threaded::sync`] and
+    /// [`Threaded::dispatch`]).
+    pub fn interpreter(&self) -> Option<&Interpreter> {
+        self.home.as_ref().map(|batch| &batch.interpreter)
+    }
+
+    /// Whether the last batch stopped at a breakpoint. Only meaningful after [`Threaded::sync`],
+    /// and reset by [`Threaded::dispatch`].
+    pub fn hit_breakpoint(&self) -> bool {
+        self.home.as_ref().is_some_and(|batch| batch.hit_breakpoint)
+    }
+
+    /// Resumes the worker, allowing it to run up to `slack` instructions ahead of the CPU.
+    ///
+    /// # Panics
+    /// Panics if not called after [`Threaded::sync`].
+    pub fn dispatch(&mut self, io: &mut DspIo) {
+        let mut batch = self.home.take().expect("dispatch is preceded by sync");
+        batch.hit_breakpoint = false;
+
+        let end = self.cpu_time + self.slack as u64;
+        batch.instructions = (end - self.dsp_time) as u32;
+        self.dsp_time = end;
+
+        self.base = IoRegisters::of(io);
+        self.base.clone().store(&mut batch.io);
+
+        std::mem::swap(&mut io.aram, &mut batch.io.aram);
+        io.aram_lent = true;
+
+        self.batches
+            .as_ref()
+            .unwrap()
+            .send(batch)
+            .expect("DSP thread is alive");
+    }
+
+    /// Synchronizes with the worker and returns the interpreter, stopping the thread.
+    pub fn into_inner(mut self, io: &mut DspIo) -> Interpreter {
+        self.sync(io);
+        self.home.take().unwrap().interpreter
+    }
+}
+
+impl Drop for Threaded {
+    fn drop(&mut self) {
+        // closing the channel stops the worker
+        self.batches = None;
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Echoes every mail from the CPU back to it.
+    #[rustfmt::skip]
+    const ECHO: [u16; 15] = [
+        // wait: lrs $ACM0, @cmbh; andcf $ACM0, #0x8000; jlnz wait
+        0x26FE, 0x02C0, 0x8000, 0x029C, 0x0010,
+        // lrs $ACL0, @cmbl
+        0x24FF,
+        // full: lrs $ACM1, @dmbh; andcf $ACM1, #0x8000; jlz full
+        0x27FC, 0x03C0, 0x8000, 0x029D, 0x0016,
+        // srs @dmbh, $ACM1; srs @dmbl, $ACL0
+        0x2FFC, 0x2CFD,
+        // jmp wait
+        0x029F, 0x0010,
+    ];
+
+    fn hammer(slack: u32) {
+        const MAILS: u16 = 4096;
+        const STEP: u32 = 64;
+
+        let mut interpreter = Interpreter::default();
+        interpreter.mem.iram[0x10..][..ECHO.len()].copy_from_slice(&ECHO);
+        interpreter.pc = 0x10;
+
+        let mut io = DspIo::new();
+        io.control.set_halt(false);
+
+        let mut dsp = Threaded::new(interpreter, slack);
+        let mut sent = 0;
+        let mut received = 0;
+        let mut steps = 0;
+        while received < MAILS {
+            if sent < MAILS && !io.cpu_mailbox.status() {
+                io.cpu_mailbox.set_low(sent);
+                io.cpu_mailbox.set_status(true);
+                sent += 1;
+            }
+
+            if io.dsp_mailbox.status() {
+                assert_eq!(io.dsp_mailbox.low(), received, "mail was lost");
+                io.dsp_mailbox.set_status(false);
+                received += 1;
+            }
+
+            if dsp.advance(STEP) {
+                dsp.sync(&mut io);
+                dsp.dispatch(&mut io);
+            }
+
+            steps += 1;
+            assert!(steps < 1_000_000, "DSP stalled after {received} mails");
+        }
+
+        dsp.into_inner(&mut io);
+        assert!(!io.aram_lent);
+    }
+
+    #[test]
+    fn interpreter_is_reachable_when_synced() {
+        let mut interpreter = Interpreter::default();
+        interpreter.mem.dram[0x10] = 0x1234;
+
+        let mut io = DspIo::new();
+        let mut dsp = Threaded::new(interpreter, 64);
+        assert_eq!(dsp.interpreter().map(|i| i.mem.dram[0x10]), Some(0x1234));
+
+        dsp.sync(&mut io);
+        dsp.dispatch(&mut io);
+        assert!(dsp.interpreter().is_none());
+        assert!(io.aram_lent);
+
+        dsp.sync(&mut io);
+        assert_eq!(dsp.interpreter().map(|i| i.mem.dram[0x10]), Some(0x1234));
+        assert!(!io.aram_lent);
+    }
+
+    #[test]
+    fn mailbox_stress_lockstep() {
+        hammer(0);
+    }
+
+    #[test]
+    fn mailbox_stress_run_ahead() {
+        hammer(2048);
+    }
+}
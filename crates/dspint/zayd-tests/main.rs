@@ -4,6 +4,7 @@ mod file;
 
 use std::fmt::Write;
 
+use dspint::threaded::Threaded;
 use dspint::{Interpreter, Registers};
 use lazuli::modules::audio::NopAudioModule;
 use lazuli::modules::debug::NopDebugModule;
@@ -49,8 +50,23 @@ fn run_case(sys: &mut System, case: file::TestCase) -> Result<(), FailedCase> {
 
     // run until halt
     let code = parse_code(&case.instructions);
-    while !sys.dsp.control.halt() {
-        dsp.step(sys);
+    if std::env::var("THREADED").is_ok() {
+        let mut threaded = Threaded::new(dsp, 0);
+        loop {
+            threaded.advance(1);
+            threaded.sync(&mut sys.dsp);
+            if sys.dsp.control.halt() {
+                break;
+            }
+
+            threaded.dispatch(&mut sys.dsp);
+        }
+
+        dsp = threaded.into_inner(&mut sys.dsp);
+    } else {
+        while !sys.dsp.control.halt() {
+            dsp.step(&mut sys.dsp);
+        }
     }
 
     // check
@@ -106,9 +122,13 @@ fn run_test(file: file::TestFile, quiet: bool) -> Result<(), Failed> {
         system::Config {
             ipl: None,
             sideload: None,
-            ipl_lle: false,
+            boot: system::BootMode::Ipl,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
         },
-    );
+    )
+    .unwrap();
 
     for (i, case) in file.cases.into_iter().enumerate() {
         let Err(failure) = run_case(&mut system, case) else {
@@ -1042,6 +1042,9 @@ pub struct Miscellaneous {
     pub dec: u32,
     /// L2 Control
     pub l2cr: u32,
+    /// Reservation created by `lwarx`: the address it was created for, with bit 0 set while it is
+    /// valid.
+    pub reservation: u32,
 }
 
 /// Performance monitor registers.
@@ -1447,6 +1450,7 @@ pub enum Reg {
     SR15,
     TBL,
     TBU,
+    RESERVATION,
 }
 
 impl Reg {
@@ -1499,6 +1503,7 @@ impl Reg {
             Self::SR15 => offset_of!(Cpu, supervisor.memory.sr[15]),
             Self::TBL => offset_of!(Cpu, supervisor.misc.tb),
             Self::TBU => offset_of!(Cpu, supervisor.misc.tb) + 4,
+            Self::RESERVATION => offset_of!(Cpu, supervisor.misc.reservation),
         }
     }
 }
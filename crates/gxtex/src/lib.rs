@@ -1,6 +1,8 @@
 #![expect(clippy::identity_op, reason = "seq expanded code")]
 #![expect(clippy::erasing_op, reason = "seq expanded code")]
 
+pub mod sample;
+
 use std::marker::PhantomData;
 
 use bitut::BitUtils;
@@ -25,10 +27,85 @@ pub use color;
 pub type Pixel = color::Rgba8;
 pub type PaletteIndex = u16;
 
+/// What a single packed texel contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelKind {
+    /// An intensity value.
+    I,
+    /// Intensity in the low half and alpha in the high half.
+    IA,
+    /// Red in bits `11..16`, green in bits `5..11` and blue in bits `0..5`.
+    Rgb565,
+    /// If bit 15 is set, red in bits `10..15`, green in bits `5..10` and blue in bits `0..5`.
+    /// Otherwise, alpha in bits `12..15`, red in bits `8..12`, green in bits `4..8` and blue in
+    /// bits `0..4`.
+    Rgb5A3,
+    /// An index into a palette.
+    Index,
+}
+
+/// How texels are stored within a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelLayout {
+    /// Texels of `bits` bits each, in row-major order. Multi-byte texels are big endian and
+    /// sub-byte texels start at the most significant bits of a byte.
+    Packed { bits: usize, kind: TexelKind },
+    /// 32 bit texels split across two halves of the tile: the first contains alpha and red and
+    /// the second contains green and blue, each as pairs of bytes in row-major order.
+    SplitArGb,
+    /// Four 4x4 sub-blocks in row-major order, each made of two big endian RGB565 colors followed
+    /// by 2 bit indices into the palette derived from them, most significant first.
+    Cmpr,
+}
+
+/// Machine-readable description of how a format is tiled, for uploading encoded textures as-is and
+/// de-tiling them somewhere else (e.g. in a shader).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileLayout {
+    /// Width of a tile, in texels.
+    pub width: usize,
+    /// Height of a tile, in texels.
+    pub height: usize,
+    /// Length of a tile, in bytes.
+    pub bytes: usize,
+    pub texel: TexelLayout,
+}
+
+impl TileLayout {
+    /// Number of tiles in each dimension of a texture with the given size.
+    pub fn tiles(&self, width: usize, height: usize) -> (usize, usize) {
+        (width.div_ceil(self.width), height.div_ceil(self.height))
+    }
+
+    /// Offset, in bytes, of the tile containing the given texel in a texture with the given width.
+    pub fn tile_offset(&self, width: usize, x: usize, y: usize) -> usize {
+        let (width_in_tiles, _) = self.tiles(width, 0);
+        let tile_index = (y / self.height) * width_in_tiles + x / self.width;
+        tile_index * self.bytes
+    }
+
+    /// Offset, in bits, of the given texel within its tile, for [`TexelLayout::Packed`] layouts.
+    pub fn texel_bit_offset(&self, x: usize, y: usize) -> Option<usize> {
+        let TexelLayout::Packed { bits, .. } = self.texel else {
+            return None;
+        };
+
+        let index = (y % self.height) * self.width + x % self.width;
+        Some(index * bits)
+    }
+}
+
 pub trait Format {
     const TILE_WIDTH: usize;
     const TILE_HEIGHT: usize;
     const BYTES_PER_TILE: usize = 32;
+    const TEXEL_LAYOUT: TexelLayout;
+    const LAYOUT: TileLayout = TileLayout {
+        width: Self::TILE_WIDTH,
+        height: Self::TILE_HEIGHT,
+        bytes: Self::BYTES_PER_TILE,
+        texel: Self::TEXEL_LAYOUT,
+    };
 
     type Texel: Clone + Copy + Default;
 
@@ -81,9 +158,21 @@ pub fn encode<F: Format>(
     }
 }
 
-#[multiversion(targets = "simd")]
-pub fn decode<F: Format>(width: usize, height: usize, data: &[u8]) -> Vec<F::Texel> {
-    let mut texels = vec![F::Texel::default(); width * height];
+/// Maximum amount of texels in a tile, across all formats.
+pub const MAX_TILE_AREA: usize = 64;
+
+/// Decodes a texture one tile at a time, without allocating.
+///
+/// `f` is called with the coordinates of each tile (in tiles) and its texels, in row-major order.
+/// Tiles at the right and bottom edges may extend past `width` and `height`, in which case the
+/// texels outside the texture should be ignored.
+#[inline(always)]
+pub fn decode_tiles<F: Format, G>(width: usize, height: usize, data: &[u8], mut f: G)
+where
+    G: FnMut(usize, usize, &[F::Texel]),
+{
+    let tile_area = F::TILE_WIDTH * F::TILE_HEIGHT;
+    assert!(tile_area <= MAX_TILE_AREA);
 
     let width_in_tiles = width.div_ceil(F::TILE_WIDTH);
     let height_in_tiles = height.div_ceil(F::TILE_HEIGHT);
@@ -92,27 +181,39 @@ pub fn decode<F: Format>(width: usize, height: usize, data: &[u8]) -> Vec<F::Tex
     let full_height = height_in_tiles * F::TILE_HEIGHT;
     assert!(data.len() >= compute_size::<F>(full_width, full_height));
 
+    let mut tile = [F::Texel::default(); MAX_TILE_AREA];
     for tile_y in 0..height_in_tiles {
         for tile_x in 0..width_in_tiles {
             let tile_index = tile_y * width_in_tiles + tile_x;
             let tile_offset = tile_index * F::BYTES_PER_TILE;
             let tile_data = &data[tile_offset..][..F::BYTES_PER_TILE];
 
-            let base_x = tile_x * F::TILE_WIDTH;
-            let base_y = tile_y * F::TILE_HEIGHT;
             F::decode_tile(tile_data, |x, y, value| {
                 assert!(x <= F::TILE_WIDTH);
                 assert!(y <= F::TILE_HEIGHT);
-
-                let x = base_x + x;
-                let y = base_y + y;
-                if x < width && y < height {
-                    let image_index = y * width + x;
-                    texels[image_index] = value;
-                }
+                tile[y * F::TILE_WIDTH + x] = value;
             });
+
+            f(tile_x, tile_y, &tile[..tile_area]);
         }
     }
+}
+
+#[multiversion(targets = "simd")]
+pub fn decode<F: Format>(width: usize, height: usize, data: &[u8]) -> Vec<F::Texel> {
+    let mut texels = vec![F::Texel::default(); width * height];
+
+    decode_tiles::<F, _>(width, height, data, |tile_x, tile_y, tile| {
+        let base_x = tile_x * F::TILE_WIDTH;
+        let base_y = tile_y * F::TILE_HEIGHT;
+        let row_len = F::TILE_WIDTH.min(width - base_x);
+        let rows = F::TILE_HEIGHT.min(height - base_y);
+
+        for (y, row) in tile.chunks_exact(F::TILE_WIDTH).take(rows).enumerate() {
+            let image_index = (base_y + y) * width + base_x;
+            texels[image_index..][..row_len].copy_from_slice(&row[..row_len]);
+        }
+    });
 
     texels
 }
@@ -180,6 +281,10 @@ pub struct I4<Source = Luma>(PhantomData<Source>);
 impl<Source: ComponentSource> Format for I4<Source> {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 8;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 4,
+        kind: TexelKind::I,
+    };
 
     type Texel = Pixel;
 
@@ -238,6 +343,10 @@ impl<IntensitySource: ComponentSource, AlphaSource: ComponentSource> Format
 {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 8,
+        kind: TexelKind::IA,
+    };
 
     type Texel = Pixel;
 
@@ -282,6 +391,10 @@ pub struct I8<Source = Luma>(PhantomData<Source>);
 impl<Source: ComponentSource> Format for I8<Source> {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 8,
+        kind: TexelKind::I,
+    };
 
     type Texel = Pixel;
 
@@ -327,6 +440,10 @@ impl<IntensitySource: ComponentSource, AlphaSource: ComponentSource> Format
 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 16,
+        kind: TexelKind::IA,
+    };
 
     type Texel = Pixel;
 
@@ -373,6 +490,10 @@ pub struct Rgb565;
 impl Format for Rgb565 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 16,
+        kind: TexelKind::Rgb565,
+    };
 
     type Texel = Pixel;
 
@@ -416,6 +537,10 @@ pub struct FastRgb565;
 impl Format for FastRgb565 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 16,
+        kind: TexelKind::Rgb565,
+    };
 
     type Texel = Pixel;
 
@@ -459,6 +584,10 @@ pub struct Rgb5A3;
 impl Format for Rgb5A3 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 16,
+        kind: TexelKind::Rgb5A3,
+    };
 
     type Texel = Pixel;
 
@@ -492,6 +621,7 @@ impl Format for Rgba8 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
     const BYTES_PER_TILE: usize = 64;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::SplitArGb;
 
     type Texel = Pixel;
 
@@ -533,14 +663,94 @@ impl Format for Rgba8 {
 
 pub struct Cmpr;
 
+/// The fourth color of a CMPR sub-block in 3 color mode: transparent black.
+const CMPR_TRANSPARENT: Pixel = Pixel {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+
 impl Format for Cmpr {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 8;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Cmpr;
 
     type Texel = Pixel;
 
-    fn encode_tile(_: &mut [u8], _: impl Fn(usize, usize) -> Pixel) {
-        unimplemented!("cmpr encoding not implemented")
+    /// Encodes each sub-block using its two most distant colors as endpoints. Sub-blocks with
+    /// any texel with less than half alpha use the 3 color mode, where the fourth color is
+    /// transparent.
+    fn encode_tile(data: &mut [u8], get: impl Fn(usize, usize) -> Pixel) {
+        let distance = |a: Pixel, b: Pixel| {
+            let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            d(a.r, b.r) + d(a.g, b.g) + d(a.b, b.b)
+        };
+
+        for sub_y in 0..2 {
+            for sub_x in 0..2 {
+                let sub_offset = 8 * (sub_y * 2 + sub_x);
+                let mut pixels = [Pixel::default(); 16];
+                for (i, pixel) in pixels.iter_mut().enumerate() {
+                    *pixel = get(sub_x * 4 + i % 4, sub_y * 4 + i / 4);
+                }
+
+                let transparent = pixels.iter().any(|p| p.a < 128);
+                let opaque = || pixels.iter().copied().filter(|p| p.a >= 128);
+
+                // endpoints: the two most distant opaque colors
+                let mut endpoints = (Pixel::default(), Pixel::default());
+                let mut max_distance = -1;
+                for a in opaque() {
+                    for b in opaque() {
+                        let distance = distance(a, b);
+                        if distance > max_distance {
+                            max_distance = distance;
+                            endpoints = (a, b);
+                        }
+                    }
+                }
+
+                let (mut a, mut b) = (endpoints.0.to_rgb565(), endpoints.1.to_rgb565());
+                if transparent == (a > b) {
+                    std::mem::swap(&mut a, &mut b);
+                }
+
+                let mut palette = [Pixel::default(); 4];
+                palette[0] = Pixel::from_rgb565(a);
+                palette[1] = Pixel::from_rgb565(b);
+
+                let colors = if a > b {
+                    palette[2] = palette[0].lerp(palette[1], 1.0 / 3.0);
+                    palette[3] = palette[0].lerp(palette[1], 2.0 / 3.0);
+                    4
+                } else {
+                    palette[2] = palette[0].lerp(palette[1], 0.5);
+                    palette[3] = CMPR_TRANSPARENT;
+                    3
+                };
+
+                data[sub_offset..][..2].copy_from_slice(&a.to_be_bytes());
+                data[sub_offset + 2..][..2].copy_from_slice(&b.to_be_bytes());
+
+                for (row, pixels) in pixels.chunks_exact(4).enumerate() {
+                    let mut byte = 0u8;
+                    for (i, &pixel) in (0u8..).zip(pixels) {
+                        let index = if transparent && pixel.a < 128 {
+                            3
+                        } else {
+                            (0..colors)
+                                .min_by_key(|&index| distance(pixel, palette[index]))
+                                .unwrap()
+                        };
+
+                        byte = byte.with_bits(6 - 2 * i, 8 - 2 * i, index as u8);
+                    }
+
+                    data[sub_offset + 4 + row] = byte;
+                }
+            }
+        }
     }
 
     fn decode_tile(data: &[u8], mut set: impl FnMut(usize, usize, Pixel)) {
@@ -564,6 +774,7 @@ impl Format for Cmpr {
                     palette[3] = palette[0].lerp(palette[1], 2.0 / 3.0);
                 } else {
                     palette[2] = palette[0].lerp(palette[1], 0.5);
+                    palette[3] = CMPR_TRANSPARENT;
                 }
 
                 // read pixels (last 4 bytes)
@@ -592,6 +803,10 @@ pub struct CI4;
 impl Format for CI4 {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 8;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 4,
+        kind: TexelKind::Index,
+    };
 
     type Texel = PaletteIndex;
 
@@ -635,6 +850,10 @@ pub struct CI8;
 impl Format for CI8 {
     const TILE_WIDTH: usize = 8;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 8,
+        kind: TexelKind::Index,
+    };
 
     type Texel = PaletteIndex;
 
@@ -665,6 +884,10 @@ pub struct CI14X2;
 impl Format for CI14X2 {
     const TILE_WIDTH: usize = 4;
     const TILE_HEIGHT: usize = 4;
+    const TEXEL_LAYOUT: TexelLayout = TexelLayout::Packed {
+        bits: 16,
+        kind: TexelKind::Index,
+    };
 
     type Texel = PaletteIndex;
 
@@ -751,6 +974,154 @@ mod test {
         test_format::<Rgb565>("resources/waterfall.webp", "RGB565");
         test_format::<Rgb5A3>("resources/waterfall.webp", "RGB5A3");
         test_format::<Rgba8>("resources/waterfall.webp", "RGBA8");
+        test_format::<Cmpr>("resources/waterfall.webp", "CMPR");
+    }
+
+    /// A 4 bit value for the texel at the given position, which differs between horizontally
+    /// adjacent texels so that swapped nibbles are noticed.
+    fn nibble_pattern(x: usize, y: usize) -> u8 {
+        ((x * 3 + y * 5) % 16) as u8
+    }
+
+    #[test]
+    fn test_i4_nibble_order() {
+        type F = I4<RedChannel>;
+
+        let (width, height) = (16, 8);
+        let texels = (0..width * height)
+            .map(|i| Pixel {
+                r: nibble_pattern(i % width, i / width) * 17,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = vec![0; compute_size::<F>(width, height)];
+        encode::<F>(width / F::TILE_WIDTH, width, height, &texels, &mut encoded);
+
+        // even texels in the high nibble, odd texels in the low one
+        assert_eq!(encoded[0], nibble_pattern(0, 0) << 4 | nibble_pattern(1, 0),);
+        assert_eq!(
+            encoded[32 + 5],
+            nibble_pattern(8 + 2, 1) << 4 | nibble_pattern(8 + 3, 1),
+        );
+
+        let decoded = decode::<F>(width, height, &encoded);
+        for (i, pixel) in decoded.into_iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let intensity = nibble_pattern(x, y) * 17;
+            let expected = Pixel {
+                r: intensity,
+                g: intensity,
+                b: intensity,
+                a: intensity,
+            };
+
+            assert_eq!(pixel, expected, "texel ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn test_ia4_nibble_order() {
+        type F = IA4<RedChannel, AlphaChannel>;
+
+        // alpha is the intensity of the next texel, so that it always differs from intensity
+        let (width, height) = (16, 4);
+        let texels = (0..width * height)
+            .map(|i| Pixel {
+                r: nibble_pattern(i % width, i / width) * 17,
+                a: nibble_pattern(i % width + 1, i / width) * 17,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = vec![0; compute_size::<F>(width, height)];
+        encode::<F>(width / F::TILE_WIDTH, width, height, &texels, &mut encoded);
+
+        // alpha in the high nibble, intensity in the low one
+        assert_eq!(encoded[0], nibble_pattern(1, 0) << 4 | nibble_pattern(0, 0),);
+        assert_eq!(
+            encoded[32 + 8 + 3],
+            nibble_pattern(8 + 4, 1) << 4 | nibble_pattern(8 + 3, 1),
+        );
+
+        let decoded = decode::<F>(width, height, &encoded);
+        for (i, pixel) in decoded.into_iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let intensity = nibble_pattern(x, y) * 17;
+            let expected = Pixel {
+                r: intensity,
+                g: intensity,
+                b: intensity,
+                a: nibble_pattern(x + 1, y) * 17,
+            };
+
+            assert_eq!(pixel, expected, "texel ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn test_cmpr_encode() {
+        let red = Pixel {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let blue = Pixel {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 255,
+        };
+        let clear = Pixel::default();
+
+        // left half red, right half blue, with a transparent texel in the last sub-block
+        let texel = |x: usize, y: usize| match (x, y) {
+            (7, 7) => clear,
+            (0..4, _) => red,
+            _ => blue,
+        };
+
+        let mut data = [0; 32];
+        Cmpr::encode_tile(&mut data, texel);
+
+        Cmpr::decode_tile(&data, |x, y, pixel| {
+            assert_eq!(pixel, texel(x, y), "texel ({x}, {y})");
+        });
+    }
+
+    #[test]
+    fn test_cmpr_transparent() {
+        // every sub-block: endpoints blue and red, so a <= b selects the 3 color mode, and each
+        // row goes through indices 0, 1, 2 and 3
+        let sub_block = [0x00, 0x1F, 0xF8, 0x00, 0x1B, 0x1B, 0x1B, 0x1B];
+        let data = sub_block.repeat(4);
+
+        let expected = [
+            Pixel {
+                r: 0,
+                g: 0,
+                b: 255,
+                a: 255,
+            },
+            Pixel {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255,
+            },
+            Pixel {
+                r: 128,
+                g: 0,
+                b: 128,
+                a: 255,
+            },
+            CMPR_TRANSPARENT,
+        ];
+
+        Cmpr::decode_tile(&data, |x, y, pixel| {
+            assert_eq!(pixel, expected[x % 4], "texel ({x}, {y})");
+        });
     }
 
     #[test]
@@ -759,6 +1130,90 @@ mod test {
         test_format::<IA8<FastLuma, AlphaChannel>>("resources/waterfall.webp", "FAST_IA8");
     }
 
+    #[test]
+    fn test_decode_tiles() {
+        let width = 13;
+        let height = 7;
+        let mut data = vec![0; compute_size::<Rgba8>(width, height)];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let decoded = decode::<Rgba8>(width, height, &data);
+
+        let mut tiles = 0;
+        decode_tiles::<Rgba8, _>(width, height, &data, |tile_x, tile_y, tile| {
+            assert_eq!(tile.len(), Rgba8::TILE_WIDTH * Rgba8::TILE_HEIGHT);
+            tiles += 1;
+
+            for (i, texel) in tile.iter().enumerate() {
+                let x = tile_x * Rgba8::TILE_WIDTH + i % Rgba8::TILE_WIDTH;
+                let y = tile_y * Rgba8::TILE_HEIGHT + i / Rgba8::TILE_WIDTH;
+                if x < width && y < height {
+                    assert_eq!(*texel, decoded[y * width + x]);
+                }
+            }
+        });
+
+        assert_eq!(tiles, 4 * 2);
+    }
+
+    /// Checks that setting the bits of each texel, as described by the layout, changes only that
+    /// texel when decoding.
+    fn test_packed_layout<F: Format<Texel: PartialEq + std::fmt::Debug>>() {
+        let layout = F::LAYOUT;
+        let TexelLayout::Packed { bits, .. } = layout.texel else {
+            panic!("layout is not packed");
+        };
+
+        let decode_tile = |data: &[u8]| {
+            let mut texels = vec![F::Texel::default(); layout.width * layout.height];
+            F::decode_tile(data, |x, y, texel| texels[y * layout.width + x] = texel);
+            texels
+        };
+
+        let zero = decode_tile(&[0; 32]);
+        for y in 0..layout.height {
+            for x in 0..layout.width {
+                let offset = layout.texel_bit_offset(x, y).unwrap();
+                let mut data = [0u8; 32];
+                for bit in offset..offset + bits {
+                    data[bit / 8] |= 0x80 >> (bit % 8);
+                }
+
+                let texels = decode_tile(&data);
+                for (i, (texel, zero)) in texels.iter().zip(&zero).enumerate() {
+                    let this = i == y * layout.width + x;
+                    assert_eq!(texel != zero, this, "texel {i}, setting ({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_layouts() {
+        test_packed_layout::<I4>();
+        test_packed_layout::<IA4>();
+        test_packed_layout::<I8>();
+        test_packed_layout::<IA8>();
+        test_packed_layout::<Rgb565>();
+        test_packed_layout::<Rgb5A3>();
+        test_packed_layout::<CI4>();
+        test_packed_layout::<CI8>();
+        test_packed_layout::<CI14X2>();
+
+        assert_eq!(Rgba8::LAYOUT.texel, TexelLayout::SplitArGb);
+        assert_eq!(Rgba8::LAYOUT.bytes, 64);
+        assert_eq!(Cmpr::LAYOUT.texel, TexelLayout::Cmpr);
+        assert_eq!(Rgba8::LAYOUT.texel_bit_offset(0, 0), None);
+
+        // 13x7 I8 texture: 2x2 tiles of 8x4
+        let layout = I8::<Luma>::LAYOUT;
+        assert_eq!(layout.tiles(13, 7), (2, 2));
+        assert_eq!(layout.tile_offset(13, 12, 6), 3 * 32);
+        assert_eq!(layout.texel_bit_offset(12, 6), Some((2 * 8 + 4) * 8));
+    }
+
     #[test]
     fn test_bad() {
         test_format::<Rgba8>("resources/bad.png", "bad");
@@ -0,0 +1,264 @@
+//! CPU-side texture sampling, following the rules of the GX texture unit.
+//!
+//! Coordinates are normalized and converted to texels with 7 bits of subtexel precision, like the
+//! texture unit does. Nearest filtering takes the texel containing the coordinate, while bilinear
+//! filtering first moves the coordinate by half a texel (so that texel centers are sampled
+//! exactly) and then blends the four surrounding texels with 7 bit weights. The blended value is
+//! truncated, not rounded.
+use crate::Pixel;
+
+/// Subtexel precision, in bits.
+const FRACTION_BITS: u32 = 7;
+const ONE: i32 = 1 << FRACTION_BITS;
+
+/// How coordinates outside of the texture are brought back into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// Coordinates are clamped to the edge texels.
+    #[default]
+    Clamp,
+    /// The texture is tiled.
+    Repeat,
+    /// The texture is tiled, flipping every other tile. Texel `-1` maps to texel `0` and texel
+    /// `size` maps to texel `size - 1`, i.e. edge texels are repeated at the boundary.
+    Mirror,
+}
+
+impl Wrap {
+    /// Wraps a texel coordinate into `0..size`.
+    pub fn apply(self, coord: i32, size: usize) -> usize {
+        let size = size as i32;
+        let coord = match self {
+            Self::Clamp => coord.clamp(0, size - 1),
+            Self::Repeat => coord.rem_euclid(size),
+            Self::Mirror => {
+                let coord = coord.rem_euclid(2 * size);
+                if coord < size {
+                    coord
+                } else {
+                    2 * size - 1 - coord
+                }
+            }
+        };
+
+        coord as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+/// Sampling parameters of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sampler {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub filter: Filter,
+}
+
+impl Sampler {
+    /// Samples a decoded texture with the given dimensions at normalized coordinates `(u, v)`.
+    pub fn sample(&self, texels: &[Pixel], width: usize, height: usize, u: f32, v: f32) -> Pixel {
+        assert!(width > 0 && height > 0);
+        assert!(texels.len() >= width * height);
+
+        let fetch = |s: i32, t: i32| {
+            let x = self.wrap_s.apply(s, width);
+            let y = self.wrap_t.apply(t, height);
+            texels[y * width + x]
+        };
+
+        // texel coordinates, in fixed point
+        let s = (u * (width as f32) * ONE as f32).floor() as i32;
+        let t = (v * (height as f32) * ONE as f32).floor() as i32;
+
+        match self.filter {
+            Filter::Nearest => fetch(s >> FRACTION_BITS, t >> FRACTION_BITS),
+            Filter::Linear => {
+                let s = s - ONE / 2;
+                let t = t - ONE / 2;
+                let (x, fx) = (s >> FRACTION_BITS, s & (ONE - 1));
+                let (y, fy) = (t >> FRACTION_BITS, t & (ONE - 1));
+
+                let texels = [
+                    (fetch(x, y), (ONE - fx) * (ONE - fy)),
+                    (fetch(x + 1, y), fx * (ONE - fy)),
+                    (fetch(x, y + 1), (ONE - fx) * fy),
+                    (fetch(x + 1, y + 1), fx * fy),
+                ];
+
+                let channel = |get: fn(&Pixel) -> u8| {
+                    let sum = texels
+                        .iter()
+                        .map(|(texel, weight)| get(texel) as i32 * weight)
+                        .sum::<i32>();
+
+                    (sum >> (2 * FRACTION_BITS)) as u8
+                };
+
+                Pixel {
+                    r: channel(|p| p.r),
+                    g: channel(|p| p.g),
+                    b: channel(|p| p.b),
+                    a: channel(|p| p.a),
+                }
+            }
+        }
+    }
+}
+
+/// Scales an image down by half in each dimension, averaging each 2x2 block like the EFB copy does
+/// when its half scale option is set. Averages are rounded to nearest, with ties rounding up. If a
+/// dimension is odd, the last row or column is dropped.
+pub fn scale_half(texels: &[Pixel], width: usize, height: usize) -> Vec<Pixel> {
+    assert!(texels.len() >= width * height);
+
+    let (half_width, half_height) = (width / 2, height / 2);
+    let mut out = Vec::with_capacity(half_width * half_height);
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let top = 2 * y * width + 2 * x;
+            let block = [
+                texels[top],
+                texels[top + 1],
+                texels[top + width],
+                texels[top + width + 1],
+            ];
+
+            let channel = |get: fn(&Pixel) -> u8| {
+                let sum = block.iter().map(|p| get(p) as u32).sum::<u32>();
+                ((sum + 2) / 4) as u8
+            };
+
+            out.push(Pixel {
+                r: channel(|p| p.r),
+                g: channel(|p| p.g),
+                b: channel(|p| p.b),
+                a: channel(|p| p.a),
+            });
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray(value: u8) -> Pixel {
+        Pixel {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        }
+    }
+
+    /// A 4x1 texture with texels 0, 40, 80, 120.
+    fn row() -> Vec<Pixel> {
+        (0..4).map(|i| gray(i * 40)).collect()
+    }
+
+    fn sampler(wrap: Wrap, filter: Filter) -> Sampler {
+        Sampler {
+            wrap_s: wrap,
+            wrap_t: Wrap::Clamp,
+            filter,
+        }
+    }
+
+    #[test]
+    fn wrap_boundaries() {
+        assert_eq!(Wrap::Clamp.apply(-1, 4), 0);
+        assert_eq!(Wrap::Clamp.apply(4, 4), 3);
+        assert_eq!(Wrap::Repeat.apply(-1, 4), 3);
+        assert_eq!(Wrap::Repeat.apply(4, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(-1, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(-2, 4), 1);
+        assert_eq!(Wrap::Mirror.apply(4, 4), 3);
+        assert_eq!(Wrap::Mirror.apply(7, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(8, 4), 0);
+    }
+
+    #[test]
+    fn nearest() {
+        let texels = row();
+        let sample = |wrap, u| {
+            sampler(wrap, Filter::Nearest)
+                .sample(&texels, 4, 1, u, 0.5)
+                .r
+        };
+
+        assert_eq!(sample(Wrap::Clamp, 0.0), 0);
+        assert_eq!(sample(Wrap::Clamp, 0.249), 0);
+        assert_eq!(sample(Wrap::Clamp, 0.25), 40);
+        assert_eq!(sample(Wrap::Clamp, 1.0), 120);
+        assert_eq!(sample(Wrap::Repeat, 1.0), 0);
+        assert_eq!(sample(Wrap::Repeat, -0.1), 120);
+        assert_eq!(sample(Wrap::Mirror, 1.0), 120);
+        assert_eq!(sample(Wrap::Mirror, -0.1), 0);
+    }
+
+    #[test]
+    fn linear() {
+        let texels = row();
+        let sample = |wrap, u| {
+            sampler(wrap, Filter::Linear)
+                .sample(&texels, 4, 1, u, 0.5)
+                .r
+        };
+
+        // texel centers are exact
+        assert_eq!(sample(Wrap::Clamp, 0.125), 0);
+        assert_eq!(sample(Wrap::Clamp, 0.375), 40);
+
+        // halfway between texels 1 and 2
+        assert_eq!(sample(Wrap::Clamp, 0.5), 60);
+
+        // a quarter of the way from texel 1 to 2: (40 * 96 + 80 * 32) * 128 >> 14 = 50
+        assert_eq!(sample(Wrap::Clamp, 0.4375), 50);
+
+        // left edge: blends texel 0 with the texel to its left
+        assert_eq!(sample(Wrap::Clamp, 0.0), 0);
+        assert_eq!(sample(Wrap::Repeat, 0.0), 60);
+        assert_eq!(sample(Wrap::Mirror, 0.0), 0);
+
+        // right edge: blends texel 3 with the texel to its right
+        assert_eq!(sample(Wrap::Clamp, 1.0), 120);
+        assert_eq!(sample(Wrap::Repeat, 1.0), 60);
+        assert_eq!(sample(Wrap::Mirror, 1.0), 120);
+    }
+
+    #[test]
+    fn linear_truncates() {
+        let texels = [gray(0), gray(1)];
+        let sampler = sampler(Wrap::Clamp, Filter::Linear);
+
+        // 3/4 of the way from 0 to 1 is 0.75, which truncates to 0
+        assert_eq!(sampler.sample(&texels, 2, 1, 0.625, 0.5).r, 0);
+        assert_eq!(sampler.sample(&texels, 2, 1, 0.75, 0.5).r, 1);
+    }
+
+    #[test]
+    fn half_scale() {
+        let texels = [
+            gray(0),
+            gray(1),
+            gray(2),
+            gray(4),
+            gray(1),
+            gray(1),
+            gray(4),
+            gray(4),
+        ];
+        let half = scale_half(&texels, 4, 2);
+
+        // (0 + 1 + 1 + 1) / 4 = 0.75 rounds to 1, (2 + 4 + 4 + 4) / 4 = 3.5 rounds to 4
+        assert_eq!(half, [gray(1), gray(4)]);
+    }
+}
@@ -7,7 +7,9 @@ use lazuli::modules::debug::NopDebugModule;
 use lazuli::modules::disk::NopDiskModule;
 use lazuli::modules::input::NopInputModule;
 use lazuli::modules::render::NopRenderModule;
+use lazuli::modules::vertex::NopVertexModule;
 use lazuli::system::mem::{RAM_END, RAM_LEN, RAM_START};
+use lazuli::system::mmu::Fault;
 use lazuli::system::{self, Modules, System};
 
 fn test_inner(sys: &mut System, range: RangeInclusive<u32>) {
@@ -68,6 +70,132 @@ fn test_logical(sys: &mut System) {
     test_inner(sys, 0xC000_0000 + RAM_START..=0xC000_0000 + RAM_END);
 }
 
+/// Physical address of the page table used by the paged test. With a HTABMASK of zero, the table
+/// is 64 KiB long.
+const HTABORG: u32 = 0x0100_0000;
+
+/// VSID of the segment used by the paged test.
+const VSID: u32 = 0x123;
+
+/// Adds an entry to the primary PTEG of the page of `logical`, returning the physical address of
+/// the PTE.
+fn map_page(sys: &mut System, logical: u32, physical: u32, protection: u32) -> u32 {
+    let page_index = (logical >> 12) & 0xFFFF;
+    let hash = (VSID & 0x7_FFFF) ^ page_index;
+    let pteg = HTABORG | ((hash & 0x3FF) << 6);
+
+    let upper = (1 << 31) | (VSID << 7) | (page_index >> 10);
+    let lower = physical | protection;
+    for i in 0..8 {
+        let pte = pteg + 8 * i;
+        if sys.read_phys_slow::<u32>(Address(pte)) & (1 << 31) == 0 {
+            sys.write_phys_slow(Address(pte), upper);
+            sys.write_phys_slow(Address(pte + 4), lower);
+            return pte;
+        }
+    }
+
+    panic!("PTEG for {logical:08X} is full");
+}
+
+/// Tests memory mapped through the page table.
+fn test_paged(sys: &mut System) {
+    println!("=> testing paged");
+
+    for offset in (0..0x1_0000).step_by(4) {
+        sys.write_phys_slow(Address(HTABORG + offset), 0u32);
+    }
+
+    let mut mman = MemoryManagement::default();
+    mman.setup_default_bats();
+    mman.sdr1 = HTABORG;
+    mman.sr[1] = VSID;
+    mman.sr[2] = (1 << 28) | (VSID + 1);
+    sys.mem.build_bat_lut(&mman);
+    sys.cpu.supervisor.memory = mman;
+    sys.tlb.invalidate_all();
+    sys.cpu
+        .supervisor
+        .config
+        .msr
+        .set_data_addr_translation(true);
+    sys.cpu
+        .supervisor
+        .config
+        .msr
+        .set_instr_addr_translation(true);
+
+    println!("read/write");
+    let mut ptes = Vec::new();
+    for page in 0..16 {
+        let logical = 0x1000_0000 + page * 0x1000;
+        let physical = 0x0080_0000 + page * 0x1000;
+        ptes.push(map_page(sys, logical, physical, 0b10));
+
+        for offset in (0..0x1000).step_by(4) {
+            let addr = Address(logical + offset);
+            assert!(sys.write(addr, 0xDEAD_BEEFu32 ^ offset));
+            assert_eq!(sys.read(addr), Some(0xDEAD_BEEFu32 ^ offset));
+            assert_eq!(
+                sys.read_phys_slow::<u32>(Address(physical + offset)),
+                0xDEAD_BEEF ^ offset
+            );
+        }
+    }
+
+    println!("referenced and changed bits");
+    for pte in ptes.iter().copied() {
+        let lower = sys.read_phys_slow::<u32>(Address(pte + 4));
+        assert_eq!(lower & 0x180, 0x180);
+    }
+
+    println!("read only page");
+    let pte = map_page(sys, 0x1001_0000, 0x0090_0000, 0b11);
+    sys.write_phys_slow(Address(0x0090_0000), 0xCAFE_BABEu32);
+    assert_eq!(sys.read_slow(Address(0x1001_0000)), Ok(0xCAFE_BABEu32));
+    assert_eq!(
+        sys.write_slow(Address(0x1001_0000), 0u32),
+        Err(Fault::Protection)
+    );
+    assert_eq!(Fault::Protection.dsisr(true), 0x0A00_0000);
+    assert_eq!(sys.read_phys_slow::<u32>(Address(pte + 4)) & 0x180, 0x100);
+
+    println!("faults");
+    assert_eq!(
+        sys.read_slow::<u32>(Address(0x1002_0000)),
+        Err(Fault::NoEntry)
+    );
+    assert_eq!(Fault::NoEntry.dsisr(false), 0x4000_0000);
+    assert_eq!(
+        sys.translate_instr_access(Address(0x1002_0000)),
+        Err(Fault::NoEntry)
+    );
+    assert_eq!(Fault::NoEntry.isi_srr1(), 0x4000_0000);
+    assert_eq!(
+        sys.translate_instr_access(Address(0x2000_0000)),
+        Err(Fault::Segment)
+    );
+    assert_eq!(
+        sys.translate_instr_access(Address(0x1000_0000)),
+        Ok(Address(0x0080_0000))
+    );
+
+    println!("tlb invalidation");
+    sys.write_phys_slow(Address(ptes[0]), 0u32);
+    assert!(sys.read_slow::<u32>(Address(0x1000_0000)).is_ok());
+    sys.tlb.invalidate(Address(0x1000_0000));
+    assert_eq!(
+        sys.read_slow::<u32>(Address(0x1000_0000)),
+        Err(Fault::NoEntry)
+    );
+
+    sys.cpu
+        .supervisor
+        .config
+        .msr
+        .set_instr_addr_translation(false);
+}
+
 fn main() {
     let modules = Modules {
         audio: Box::new(NopAudioModule),
@@ -75,6 +203,7 @@ fn main() {
         disk: Box::new(NopDiskModule),
         input: Box::new(NopInputModule),
         render: Box::new(NopRenderModule),
+        vertex: Box::new(NopVertexModule),
     };
 
     let mut system = System::new(
@@ -82,10 +211,15 @@ fn main() {
         system::Config {
             ipl: None,
             sideload: None,
-            ipl_lle: false,
+            boot: system::BootMode::Ipl,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
         },
-    );
+    )
+    .unwrap();
 
     test_physical(&mut system);
     test_logical(&mut system);
+    test_paged(&mut system);
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use gekko::disasm::{Extensions, Ins, ParsedIns};
 use gekko::{Address, Cycles};
 
 use crate::system::System;
@@ -10,26 +13,221 @@ pub struct Executed {
     pub cycles: Cycles,
     /// Whether a breakpoint was hit.
     pub hit_breakpoint: bool,
+    /// Whether a DSP breakpoint was hit.
+    pub hit_dsp_breakpoint: bool,
+    /// Whether the CPU stopped in a detected idle loop, where it would spin until an event
+    /// happens.
+    pub idle: bool,
+    /// How many of the executed cycles were fast-forwarded because both the CPU and the DSP were
+    /// idle.
+    pub skipped_cycles: Cycles,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct DspExecuted {
+    /// How many instructions have been executed.
+    pub instructions: u32,
+    /// Whether a breakpoint was hit.
+    pub hit_breakpoint: bool,
+}
+
+/// Maximum number of distinct instructions tracked by [`SkippedInstructions`].
+pub const MAX_SKIPPED_INSTRUCTIONS: usize = 256;
+
+/// An unimplemented instruction which a CPU core skipped.
+#[derive(Debug, Clone)]
+pub struct SkippedInstruction {
+    pub mnemonic: &'static str,
+    /// Encoding of the last skipped occurrence.
+    pub code: u32,
+    /// Address of the last skipped occurrence.
+    pub pc: Address,
+    /// How many times it has been skipped.
+    pub count: u64,
+}
+
+/// Unimplemented instructions skipped by a CPU core, with one entry per mnemonic.
+#[derive(Debug, Clone, Default)]
+pub struct SkippedInstructions(Vec<SkippedInstruction>);
+
+impl SkippedInstructions {
+    /// Records a skipped instruction. Warns the first time each mnemonic is skipped.
+    pub fn record(&mut self, code: u32, pc: Address) {
+        let ins = Ins::new(code, Extensions::gekko_broadway());
+        let mut parsed = ParsedIns::new();
+        ins.parse_basic(&mut parsed);
+
+        if let Some(entry) = self.0.iter_mut().find(|e| e.mnemonic == parsed.mnemonic) {
+            entry.code = code;
+            entry.pc = pc;
+            entry.count += 1;
+            return;
+        }
+
+        if self.0.len() < MAX_SKIPPED_INSTRUCTIONS {
+            tracing::warn!("skipping unimplemented instruction ({parsed}) at {pc}");
+            self.0.push(SkippedInstruction {
+                mnemonic: parsed.mnemonic,
+                code,
+                pc,
+                count: 1,
+            });
+        }
+    }
+
+    /// The skipped instructions, in the order they were first skipped.
+    pub fn entries(&self) -> &[SkippedInstruction] {
+        &self.0
+    }
+}
+
+/// How often a block was executed, as recorded in a [`BlockTrace`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracedBlock {
+    /// How many times the block has been entered.
+    pub entries: u64,
+    /// Length of the block, in instructions.
+    pub instructions: u32,
+}
+
+/// Start addresses of the blocks executed by a CPU core.
+#[derive(Debug, Clone, Default)]
+pub struct BlockTrace(HashMap<Address, TracedBlock>);
+
+impl BlockTrace {
+    /// Records an execution of the block at `addr`, in which `instructions` instructions ran.
+    pub fn record(&mut self, addr: Address, instructions: u32) {
+        let block = self.0.entry(addr).or_default();
+        block.entries += 1;
+        block.instructions = block.instructions.max(instructions);
+    }
+
+    /// The recorded blocks, in no particular order.
+    pub fn blocks(&self) -> impl Iterator<Item = (Address, TracedBlock)> {
+        self.0.iter().map(|(addr, block)| (*addr, *block))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Block lookup and linking statistics of a CPU core which compiles blocks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockStats {
+    /// How many dispatcher lookups found an existing block.
+    pub lookup_hits: u64,
+    /// How many dispatcher lookups had to compile a new block.
+    pub lookup_misses: u64,
+    /// How many block exits were successfully linked to another block.
+    pub links: u64,
+    /// How many link attempts failed because the target block did not exist yet.
+    pub link_misses: u64,
+    /// How many links were undone because their target block got invalidated.
+    pub unlinks: u64,
+}
+
+impl BlockStats {
+    /// Ratio of dispatcher lookups which found an existing block.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.lookup_hits + self.lookup_misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.lookup_hits as f64 / total as f64
+        }
+    }
 }
 
 /// Trait for CPU cores.
 pub trait CpuCore: Send {
     /// Drives the CPU core forward by approximatedly the given number of `cycles`, stopping at any
-    /// address in `breakpoints`.
+    /// address in `breakpoints`. Cores should return early once [`DspIo::kick`] is set.
+    ///
+    /// [`DspIo::kick`]: crate::system::dspi::DspIo::kick
     fn exec(&mut self, sys: &mut System, cycles: Cycles, breakpoints: &[Address]) -> Executed;
     /// Steps the CPU, i.e. runs exactly 1 instruction.
     fn step(&mut self, sys: &mut System) -> Executed;
+    /// Drops any state derived from the system (e.g. compiled code), as needed when it is reset.
+    fn reset(&mut self);
+    /// Unimplemented instructions skipped so far, for cores which are able to skip them.
+    fn skipped_instructions(&self) -> &[SkippedInstruction] {
+        &[]
+    }
+    /// Enables or disables recording which blocks get executed, for cores which are able to.
+    /// Tracing has a cost, so it is disabled by default.
+    fn set_block_tracing(&mut self, enabled: bool) {
+        _ = enabled;
+    }
+    /// Takes the blocks executed since tracing was enabled or since the last call.
+    fn take_block_trace(&mut self) -> BlockTrace {
+        BlockTrace::default()
+    }
+    /// Block lookup and linking statistics, for cores which compile blocks.
+    fn block_stats(&self) -> Option<BlockStats> {
+        None
+    }
+}
+
+/// A read-only view of the memory of the DSP, in 16 bit words.
+#[derive(Debug, Clone, Copy)]
+pub struct DspMemory<'a> {
+    pub iram: &'a [u16],
+    pub irom: &'a [u16],
+    pub dram: &'a [u16],
+    pub coef: &'a [u16],
 }
 
 /// Trait for DSP cores.
 pub trait DspCore: Send {
-    /// Drives the DSP core forward by _at most_ the specified amount of instructions. The actual
-    /// number of instructions executed is returned.
-    fn exec(&mut self, sys: &mut System, instructions: u32) -> u32;
+    /// Drives the DSP core forward by _at most_ the specified amount of instructions, stopping at
+    /// any of the breakpoints set with [`DspCore::set_breakpoints`].
+    fn exec(&mut self, sys: &mut System, instructions: u32) -> DspExecuted;
+    /// Brings the DSP core back to its power-on state, as needed when the system is reset.
+    fn reset(&mut self);
+    /// Whether the DSP can't make progress until the CPU or an event does something, e.g. because
+    /// it is waiting for mail. Cores which can't tell always return `false`.
+    fn is_idle(&mut self, sys: &System) -> bool {
+        _ = sys;
+        false
+    }
+    /// Sets the DSP instruction addresses at which to stop, for cores which are able to.
+    fn set_breakpoints(&mut self, breakpoints: &[u16]) {
+        _ = breakpoints;
+    }
+    /// Waits for any work the core is doing elsewhere (e.g. on another thread) and brings what it
+    /// borrowed from `sys`, such as ARAM, back. Both stay up to date until the next
+    /// [`DspCore::exec`].
+    fn sync(&mut self, sys: &mut System) {
+        _ = sys;
+    }
+    /// The memory of the DSP, as of the last [`DspCore::sync`]. Cores which don't emulate it (or
+    /// can't reach it without syncing) return `None`.
+    fn memory(&self) -> Option<DspMemory<'_>> {
+        None
+    }
 }
 
 /// Cores that emulate system components.
-pub struct Cores {
-    pub cpu: Box<dyn CpuCore>,
+///
+/// The CPU core is generic so that a concrete core (e.g. the JIT) can be called without going
+/// through a vtable, which lets its hot loop be inlined into [`Lazuli::exec`]. By default it is a
+/// trait object, which is what frontends that pick or swap the core at runtime should use. See
+/// [`Lazuli`] for when to use each.
+///
+/// [`Lazuli`]: crate::Lazuli
+/// [`Lazuli::exec`]: crate::Lazuli::exec
+pub struct Cores<C: CpuCore + ?Sized = dyn CpuCore> {
+    pub cpu: Box<C>,
     pub dsp: Box<dyn DspCore>,
 }
+
+impl<C: CpuCore + 'static> Cores<C> {
+    /// Erases the type of the CPU core.
+    pub fn into_dyn(self) -> Cores {
+        Cores {
+            cpu: self.cpu,
+            dsp: self.dsp,
+        }
+    }
+}
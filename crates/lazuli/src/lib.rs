@@ -14,43 +14,192 @@ pub use disks;
 pub use gekko::{self, Address, Cycles};
 pub use primitive::Primitive;
 
-use crate::cores::Cores;
+use crate::cores::{Cores, CpuCore};
 use crate::system::{Modules, System};
 
 /// How many DSP instructions to execute per cycle.
 const DSP_INST_PER_CYCLE: f64 = 1.0;
 /// How many DSP cycles to execute per step.
 const DSP_STEP: u32 = 512;
-/// How many DSP instructions to execute per step.
-const DSP_INST_PER_STEP: u32 = (DSP_STEP as f64 * DSP_INST_PER_CYCLE) as u32;
+/// How many DSP cycles to execute per step after a kick.
+const DSP_KICK_STEP: u32 = 32;
+/// For how many DSP cycles after a kick to keep using [`DSP_KICK_STEP`], so that the handshake
+/// which usually follows it doesn't wait for regular steps.
+const DSP_KICK_WINDOW: f64 = 4096.0;
+
+/// Kind of reset to perform on the emulated system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetKind {
+    /// Power cycles the console: every component is brought back to its power-on state and the
+    /// system boots again from its boot source.
+    Hard,
+    /// Presses the console's reset button. Titles detect it and decide what to do about it,
+    /// usually resetting the console themselves.
+    Soft,
+}
 
 /// The Lazuli emulator.
-pub struct Lazuli {
+///
+/// The emulator is generic over its CPU core `C`:
+/// - `Lazuli` (i.e. `Lazuli<dyn CpuCore>`) calls the core through a trait object. Use it when the
+///   core is chosen at runtime or has to be swapped, as the frontend does.
+/// - `Lazuli<C>` with a concrete core is monomorphized, so the core is called directly and can be
+///   inlined into [`Lazuli::exec`]. Use it when the core is known at compile time, e.g. in
+///   headless runners, tests and benchmarks.
+///
+/// A monomorphized emulator can be turned into a boxed one with [`Lazuli::into_dyn`].
+pub struct Lazuli<C: CpuCore + ?Sized = dyn CpuCore> {
     /// System state.
     pub sys: System,
     /// Cores of the emulator.
-    cores: Cores,
+    cores: Cores<C>,
     /// How many DSP cycles are pending.
     dsp_pending: f64,
+    /// For how many more DSP cycles to use [`DSP_KICK_STEP`] instead of [`DSP_STEP`].
+    dsp_kick_window: f64,
+}
+
+impl<C: CpuCore + 'static> Lazuli<C> {
+    /// Erases the type of the CPU core, keeping the state of the emulator.
+    pub fn into_dyn(self) -> Lazuli {
+        Lazuli {
+            sys: self.sys,
+            cores: self.cores.into_dyn(),
+            dsp_pending: self.dsp_pending,
+            dsp_kick_window: self.dsp_kick_window,
+        }
+    }
 }
 
-impl Lazuli {
-    pub fn new(cores: Cores, modules: Modules, config: system::Config) -> Self {
-        Self {
-            sys: System::new(modules, config),
+impl<C: CpuCore + ?Sized> Lazuli<C> {
+    pub fn new(
+        cores: Cores<C>,
+        modules: Modules,
+        config: system::Config,
+    ) -> Result<Self, system::BootError> {
+        Ok(Self {
+            sys: System::new(modules, config)?,
             cores,
             dsp_pending: 0.0,
+            dsp_kick_window: 0.0,
+        })
+    }
+
+    /// Resets the emulated system. See [`ResetKind`].
+    pub fn reset(&mut self, kind: ResetKind) {
+        match kind {
+            ResetKind::Hard => {
+                self.cores.cpu.reset();
+                self.cores.dsp.reset();
+                self.dsp_pending = 0.0;
+                self.dsp_kick_window = 0.0;
+                self.sys.reset();
+            }
+            ResetKind::Soft => system::pi::press_reset_switch(&mut self.sys),
+        }
+    }
+
+    /// Unimplemented instructions skipped by the CPU core so far.
+    pub fn skipped_instructions(&self) -> &[cores::SkippedInstruction] {
+        self.cores.cpu.skipped_instructions()
+    }
+
+    /// Enables or disables recording which blocks the CPU core executes. See
+    /// [`Lazuli::take_block_trace`].
+    pub fn set_block_tracing(&mut self, enabled: bool) {
+        self.cores.cpu.set_block_tracing(enabled);
+    }
+
+    /// Takes the blocks executed by the CPU core since tracing was enabled or since the last call.
+    /// Cores which can't trace blocks always return an empty trace.
+    pub fn take_block_trace(&mut self) -> cores::BlockTrace {
+        self.cores.cpu.take_block_trace()
+    }
+
+    /// Block lookup and linking statistics of the CPU core, if it compiles blocks.
+    pub fn block_stats(&self) -> Option<cores::BlockStats> {
+        self.cores.cpu.block_stats()
+    }
+
+    /// Size of the current DSP step, in DSP cycles.
+    fn dsp_step(&self) -> u32 {
+        if self.dsp_kick_window > 0.0 {
+            DSP_KICK_STEP
+        } else {
+            DSP_STEP
+        }
+    }
+
+    /// Sets the DSP instruction addresses at which to stop.
+    pub fn set_dsp_breakpoints(&mut self, breakpoints: &[u16]) {
+        self.cores.dsp.set_breakpoints(breakpoints);
+    }
+
+    /// The memory of the DSP, for debugging. `None` if the DSP core doesn't emulate it.
+    ///
+    /// A DSP core running on its own thread is synchronized with first, which waits for the
+    /// batch it is working on. That's fine once per frame, but not in a hot loop.
+    pub fn dsp_memory(&mut self) -> Option<cores::DspMemory<'_>> {
+        self.cores.dsp.sync(&mut self.sys);
+        self.cores.dsp.memory()
+    }
+
+    /// The contents of ARAM, for debugging. Like [`Lazuli::dsp_memory`], synchronizes with a DSP
+    /// core running on its own thread, which borrows ARAM while it runs.
+    pub fn aram(&mut self) -> &[u8] {
+        self.cores.dsp.sync(&mut self.sys);
+        &self.sys.dsp.aram[..]
+    }
+
+    /// Runs the DSP for every complete step pending. If the CPU kicked the DSP, it is also caught
+    /// up to the CPU right away, and smaller steps are used for a while.
+    ///
+    /// Returns whether the DSP hit a breakpoint, in which case it stops early.
+    fn exec_dsp(&mut self) -> bool {
+        loop {
+            let step = self.dsp_step();
+            if self.dsp_pending < step as f64 {
+                break;
+            }
+
+            let instructions = (step as f64 * DSP_INST_PER_CYCLE) as u32;
+            let executed = self.cores.dsp.exec(&mut self.sys, instructions);
+            self.dsp_pending -= step as f64;
+            self.dsp_kick_window -= step as f64;
+
+            if executed.hit_breakpoint {
+                std::hint::cold_path();
+                return true;
+            }
         }
+
+        let mut hit_breakpoint = false;
+        if std::mem::take(&mut self.sys.dsp.kick) {
+            // only run the cycles the DSP is behind by, so it never gets ahead of the CPU
+            let behind = self.dsp_pending.floor();
+            if behind >= 1.0 {
+                let instructions = (behind * DSP_INST_PER_CYCLE) as u32;
+                let executed = self.cores.dsp.exec(&mut self.sys, instructions);
+                self.dsp_pending -= behind;
+                hit_breakpoint = executed.hit_breakpoint;
+            }
+
+            self.dsp_kick_window = DSP_KICK_WINDOW;
+        }
+
+        hit_breakpoint
     }
 
-    /// Advances emulation by the specified number of CPU cycles.
+    /// Advances emulation by the specified number of CPU cycles. While both the CPU and the DSP
+    /// are idle, time is fast-forwarded to the next event instead, see
+    /// [`Executed::skipped_cycles`](cores::Executed::skipped_cycles).
     pub fn exec(&mut self, cycles: Cycles, breakpoints: &[Address]) -> cores::Executed {
         let mut total_executed = cores::Executed::default();
         while total_executed.cycles < cycles {
             // how many CPU cycles can we execute?
             let remaining = cycles - total_executed.cycles;
             let until_next_dsp_step =
-                Cycles((6.0 * ((DSP_STEP as f64) - self.dsp_pending)).ceil() as u64);
+                Cycles((6.0 * ((self.dsp_step() as f64) - self.dsp_pending)).ceil() as u64);
             let until_next_event = Cycles(self.sys.scheduler.until_next().unwrap_or(u64::MAX));
             let can_execute = until_next_dsp_step.min(until_next_event).min(remaining);
 
@@ -61,12 +210,19 @@ impl Lazuli {
 
             // execute DSP
             self.dsp_pending += executed.cycles.to_dsp_cycles();
-            while self.dsp_pending >= DSP_STEP as f64 {
-                self.cores.dsp.exec(&mut self.sys, DSP_INST_PER_STEP);
-                self.dsp_pending -= DSP_STEP as f64;
+            let hit_dsp_breakpoint = self.exec_dsp();
+
+            // if neither can make progress, nothing happens until the next event: skip to it. the
+            // DSP doesn't get the skipped time, it would only spin anyway
+            let mut elapsed = executed.cycles;
+            if executed.idle && !hit_dsp_breakpoint && self.cores.dsp.is_idle(&self.sys) {
+                let skip = until_next_event.min(remaining).0.saturating_sub(elapsed.0);
+                total_executed.cycles += Cycles(skip);
+                total_executed.skipped_cycles += Cycles(skip);
+                elapsed += Cycles(skip);
             }
 
-            self.sys.scheduler.advance(executed.cycles.0);
+            self.sys.scheduler.advance(elapsed.0);
             self.sys.process_events();
 
             if executed.hit_breakpoint || breakpoints.contains(&self.sys.cpu.pc) {
@@ -74,6 +230,12 @@ impl Lazuli {
                 total_executed.hit_breakpoint = true;
                 break;
             }
+
+            if hit_dsp_breakpoint {
+                std::hint::cold_path();
+                total_executed.hit_dsp_breakpoint = true;
+                break;
+            }
         }
 
         total_executed
@@ -81,14 +243,11 @@ impl Lazuli {
 
     pub fn step(&mut self) -> cores::Executed {
         // execute CPU
-        let executed = self.cores.cpu.step(&mut self.sys);
+        let mut executed = self.cores.cpu.step(&mut self.sys);
         self.dsp_pending += executed.cycles.to_dsp_cycles();
 
         // execute DSP
-        while self.dsp_pending >= DSP_STEP as f64 {
-            self.cores.dsp.exec(&mut self.sys, DSP_INST_PER_STEP);
-            self.dsp_pending -= DSP_STEP as f64;
-        }
+        executed.hit_dsp_breakpoint = self.exec_dsp();
 
         // process events
         self.sys.scheduler.advance(executed.cycles.0);
@@ -97,3 +256,247 @@ impl Lazuli {
         executed
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+    use crate::cores::{DspCore, DspExecuted, Executed};
+    use crate::system::BootMode;
+
+    /// CPU cycle at which the mock CPU sends its mail, right after a regular DSP step.
+    const SEND_AT: u64 = 6 * DSP_STEP as u64 + 30;
+    /// How many instructions the mock DSP takes to reply to a mail.
+    const REPLY_WORK: u32 = 100;
+
+    /// A CPU which sends a mail to the DSP and measures how long it takes to get a reply.
+    struct MailCpu {
+        now: u64,
+        sent_at: Option<u64>,
+        honor_kick: bool,
+        latency: Arc<AtomicU64>,
+    }
+
+    impl CpuCore for MailCpu {
+        fn exec(&mut self, sys: &mut System, cycles: Cycles, _: &[Address]) -> Executed {
+            let mut executed = Executed::default();
+            while executed.cycles < cycles {
+                executed.instructions += 1;
+                executed.cycles += Cycles(10);
+                self.now += 10;
+
+                match self.sent_at {
+                    None if self.now >= SEND_AT => {
+                        sys.write_phys_slow(Address(0x0C00_5000), 0x8000_CAFEu32);
+                        self.sent_at = Some(self.now);
+                        if !self.honor_kick {
+                            sys.dsp.kick = false;
+                        }
+                    }
+                    Some(sent_at) if sys.dsp.dsp_mailbox.status() => {
+                        _ = self.latency.compare_exchange(
+                            u64::MAX,
+                            self.now - sent_at,
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                        );
+                    }
+                    _ => (),
+                }
+
+                if sys.dsp.kick {
+                    break;
+                }
+            }
+
+            executed
+        }
+
+        fn step(&mut self, sys: &mut System) -> Executed {
+            self.exec(sys, Cycles(1), &[])
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    /// A DSP which replies to mails after [`REPLY_WORK`] instructions.
+    #[derive(Default)]
+    struct MailDsp {
+        work: Option<u32>,
+    }
+
+    impl DspCore for MailDsp {
+        fn exec(&mut self, sys: &mut System, instructions: u32) -> DspExecuted {
+            if sys.dsp.cpu_mailbox.status() {
+                sys.dsp.cpu_mailbox.set_status(false);
+                self.work = Some(REPLY_WORK);
+            }
+
+            if let Some(work) = self.work {
+                let work = work.saturating_sub(instructions);
+                self.work = Some(work);
+                if work == 0 {
+                    sys.dsp.dsp_mailbox.set_low(0xBEEF);
+                    sys.dsp.dsp_mailbox.set_status(true);
+                    self.work = None;
+                }
+            }
+
+            DspExecuted {
+                instructions,
+                hit_breakpoint: false,
+            }
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    fn mail_lazuli(honor_kick: bool, latency: &Arc<AtomicU64>) -> Lazuli<MailCpu> {
+        let cores = Cores {
+            cpu: Box::new(MailCpu {
+                now: 0,
+                sent_at: None,
+                honor_kick,
+                latency: Arc::clone(latency),
+            }),
+            dsp: Box::new(MailDsp::default()),
+        };
+
+        let config = system::Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        Lazuli::new(cores, Modules::nop(), config).unwrap()
+    }
+
+    fn mail_latency(honor_kick: bool) -> u64 {
+        let latency = Arc::new(AtomicU64::new(u64::MAX));
+        let mut lazuli = mail_lazuli(honor_kick, &latency);
+        lazuli.exec(Cycles(SEND_AT * 4), &[]);
+
+        latency.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn kick_reduces_mailbox_latency() {
+        let before = mail_latency(false);
+        let after = mail_latency(true);
+
+        // without a kick, the reply has to wait for the next regular step
+        assert!(before != u64::MAX && before >= 5 * DSP_STEP as u64);
+        // with it, the DSP is only ever a kick step behind while working on the reply
+        assert!(after <= 6 * (REPLY_WORK + 2 * DSP_KICK_STEP) as u64);
+    }
+
+    /// A CPU which is always idle looping, counting how often it gets to run.
+    struct IdleCpu {
+        calls: Arc<AtomicU64>,
+    }
+
+    impl CpuCore for IdleCpu {
+        fn exec(&mut self, _: &mut System, cycles: Cycles, _: &[Address]) -> Executed {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            Executed {
+                instructions: 1,
+                cycles,
+                idle: true,
+                ..Default::default()
+            }
+        }
+
+        fn step(&mut self, sys: &mut System) -> Executed {
+            self.exec(sys, Cycles(1), &[])
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    /// A DSP which does nothing, and may claim to be idle.
+    struct NopDsp {
+        idle: bool,
+    }
+
+    impl DspCore for NopDsp {
+        fn exec(&mut self, _: &mut System, instructions: u32) -> DspExecuted {
+            DspExecuted {
+                instructions,
+                hit_breakpoint: false,
+            }
+        }
+
+        fn reset(&mut self) {}
+
+        fn is_idle(&mut self, _: &System) -> bool {
+            self.idle
+        }
+    }
+
+    /// Runs an idle CPU for `cycles`, returning what was executed, the scheduler clock afterwards
+    /// and how many times the CPU core was called.
+    fn run_idle(dsp_idle: bool, cycles: Cycles) -> (Executed, u64, u64) {
+        let calls = Arc::new(AtomicU64::new(0));
+        let cores = Cores {
+            cpu: Box::new(IdleCpu {
+                calls: Arc::clone(&calls),
+            }),
+            dsp: Box::new(NopDsp { idle: dsp_idle }),
+        };
+
+        let config = system::Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut lazuli = Lazuli::new(cores, Modules::nop(), config).unwrap();
+        let executed = lazuli.exec(cycles, &[]);
+
+        (
+            executed,
+            lazuli.sys.scheduler.elapsed(),
+            calls.load(Ordering::Relaxed),
+        )
+    }
+
+    #[test]
+    fn idle_time_is_skipped() {
+        let cycles = Cycles(1_000_000);
+        let (busy, busy_elapsed, busy_calls) = run_idle(false, cycles);
+        let (idle, idle_elapsed, idle_calls) = run_idle(true, cycles);
+
+        // a busy DSP still needs its steps, so the CPU has to be run in between them
+        assert_eq!(busy.skipped_cycles, Cycles(0));
+        assert!(busy_calls >= cycles.0 / (6 * DSP_STEP as u64));
+
+        // otherwise, time goes straight to the next event
+        assert!(idle.skipped_cycles > Cycles(0));
+        assert!(idle_calls < busy_calls / 10);
+
+        // either way, the same amount of time passes
+        assert_eq!(idle.cycles, busy.cycles);
+        assert_eq!(idle_elapsed, busy_elapsed);
+    }
+
+    #[test]
+    fn erased_core_keeps_state() {
+        let latency = Arc::new(AtomicU64::new(u64::MAX));
+        let mut lazuli = mail_lazuli(true, &latency);
+        lazuli.exec(Cycles(SEND_AT + 10), &[]);
+
+        // switching to a boxed core halfway through must not disturb emulation
+        let mut lazuli = lazuli.into_dyn();
+        lazuli.exec(Cycles(SEND_AT * 3), &[]);
+
+        assert_eq!(latency.load(Ordering::Relaxed), mail_latency(true));
+    }
+}
@@ -6,6 +6,9 @@ use crate::system::ai::{Frame, SampleRate};
 pub trait AudioModule: Send {
     fn set_sample_rate(&mut self, sample_rate: SampleRate);
     fn play(&mut self, frame: Frame);
+    /// Sets whether played frames are discarded instead, e.g. while emulation doesn't run at
+    /// real-time speed.
+    fn set_muted(&mut self, muted: bool);
 }
 
 /// An implementation of [`AudioModule`] which does nothing.
@@ -15,4 +18,5 @@ pub struct NopAudioModule;
 impl AudioModule for NopAudioModule {
     fn set_sample_rate(&mut self, _: SampleRate) {}
     fn play(&mut self, _: Frame) {}
+    fn set_muted(&mut self, _: bool) {}
 }
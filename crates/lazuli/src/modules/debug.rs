@@ -32,10 +32,77 @@ impl<'a> Location<'a> {
     }
 }
 
+/// Identifies a type described by a [`DebugModule`]. Only meaningful to the module it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeId(pub u64);
+
+/// How a scalar is encoded. Scalars are stored big-endian in guest memory, like everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarKind {
+    Unsigned,
+    Signed,
+    Float,
+    Bool,
+}
+
+/// A member of a struct, class or union.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    /// Offset of the member from the start of its parent, in bytes.
+    pub offset: u32,
+    pub ty: TypeId,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeKind {
+    Scalar(ScalarKind),
+    /// An integer with named values.
+    Enum(Vec<(String, i64)>),
+    /// A pointer to a value of the given type, or to something undescribed (e.g. `void *`).
+    Pointer(Option<TypeId>),
+    Array {
+        element: TypeId,
+        count: u32,
+    },
+    /// A struct, class or union.
+    Struct(Vec<Member>),
+    /// Anything which can't be inspected, e.g. functions and incomplete types.
+    Opaque,
+}
+
+/// A type as described by a [`DebugModule`]. Typedefs and qualifiers are resolved, only keeping
+/// their name.
+#[derive(Debug, Clone)]
+pub struct Type {
+    pub name: String,
+    /// Size of the type, in bytes.
+    pub size: u32,
+    pub kind: TypeKind,
+}
+
+/// A variable with a fixed address.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub address: Address,
+    pub ty: TypeId,
+}
+
 /// Trait for debug info modules.
 pub trait DebugModule: Send {
     fn find_symbol(&self, addr: Address) -> Option<String>;
     fn find_location(&self, addr: Address) -> Option<Location<'_>>;
+
+    /// Finds a variable with a fixed address (i.e. a global or a static) by name.
+    fn find_variable(&self, _name: &str) -> Option<Variable> {
+        None
+    }
+
+    /// Describes a type referenced by this module.
+    fn find_type(&self, _ty: TypeId) -> Option<&Type> {
+        None
+    }
 }
 
 /// An implementation of [`DebugModule`] which does nothing.
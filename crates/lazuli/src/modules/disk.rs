@@ -1,6 +1,22 @@
 //! Disk module interface.
 
 use std::io::{Read, Seek};
+use std::time::{Duration, Instant};
+
+/// A read of the disk, as reported by [`DiskModule::recent_activity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskRead {
+    /// When the read started.
+    pub start: Instant,
+    /// Offset of the read in the disk, in bytes.
+    pub offset: u64,
+    /// Length of the read, in bytes.
+    pub length: u64,
+    /// How long the read took, or [`None`] if it's still in flight.
+    pub duration: Option<Duration>,
+    /// How many reads were in flight when this one started, itself included.
+    pub queue_depth: u32,
+}
 
 /// Trait for disk modules.
 ///
@@ -8,9 +24,20 @@ use std::io::{Read, Seek};
 /// read or seek the module should return an [`std::io::Error`] with [`std::io::ErrorKind::Other`].
 /// If the implementation doesn't support inserting a disk, it should return a
 /// [`std::io::ErrorKind::Unsupported`] instead.
+///
+/// Errors from reads of an inserted disk are reported to the game as drive errors: reads past the
+/// end of the disk ([`std::io::ErrorKind::UnexpectedEof`]) as out of range, and any other error
+/// as an unrecovered read error. Implementations can fail reads on purpose to simulate a damaged
+/// disk.
 pub trait DiskModule: Read + Seek + Send {
     /// Whether a disk is inserted.
     fn has_disk(&self) -> bool;
+
+    /// The most recent reads, oldest first, including those still in flight. Meant for loading
+    /// indicators and profiling, so implementations should only keep a small rolling window.
+    fn recent_activity(&self) -> Vec<DiskRead> {
+        Vec::new()
+    }
 }
 
 /// An implementation of [`DiskModule`] which never has a disk.
@@ -31,9 +31,50 @@ pub struct ControllerState {
     pub button_start: bool,
 }
 
+/// How many controller ports the console has.
+pub const PORT_COUNT: usize = 4;
+
+/// Identifies an input device. Ids are stable for as long as the module is alive, even if the
+/// device is disconnected and connected again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceId(pub u32);
+
+/// An input device known to a module.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub id: DeviceId,
+    pub name: String,
+    /// Whether the device is currently connected.
+    pub connected: bool,
+}
+
 /// Trait for controller modules.
 pub trait InputModule: Send {
+    /// Returns the state of the controller on the given port, or `None` if there's no controller
+    /// plugged into it.
     fn controller(&mut self, index: usize) -> Option<ControllerState>;
+
+    /// Whether there's a controller plugged into the given port.
+    fn is_connected(&mut self, index: usize) -> bool {
+        self.controller(index).is_some()
+    }
+
+    /// Devices known to the module, including disconnected ones, for modules which deal with
+    /// multiple devices.
+    fn devices(&self) -> Vec<Device> {
+        Vec::new()
+    }
+
+    /// The device assigned to each port.
+    fn ports(&self) -> [Option<DeviceId>; PORT_COUNT] {
+        [None; PORT_COUNT]
+    }
+
+    /// Assigns a device to the given port, removing it from any other port. `None` unplugs the
+    /// port.
+    fn assign(&mut self, port: usize, device: Option<DeviceId>) {
+        _ = (port, device);
+    }
 }
 
 /// An implementation of [`InputModule`] which does nothing: every controller is always
@@ -1,5 +1,7 @@
 //! Renderer module interface.
 
+pub mod capture;
+
 use color::{Abgr8, Rgba, Rgba8, Rgba16};
 use glam::Mat4;
 use oneshot::Sender;
@@ -7,10 +9,12 @@ use ordered_float::OrderedFloat;
 use static_assertions::const_assert;
 
 use crate::system::gx::pix::{BlendMode, BufferFormat, ConstantAlpha, DepthMode};
-use crate::system::gx::tev::{AlphaFunction, Constant, DepthTexture, StageOps, StageRefs};
+use crate::system::gx::tev::{AlphaFunction, Constant, DepthTexture, Fog, StageOps, StageRefs};
 use crate::system::gx::tex::{ClutFormat, Format, LodLimits, MipmapData, SamplerMode};
 use crate::system::gx::xform::{BaseTexGen, ChannelControl, Light, ProjectionMat};
-use crate::system::gx::{CullingMode, EFB_HEIGHT, EFB_WIDTH, Topology, VertexStream};
+use crate::system::gx::{
+    CullingMode, EFB_HEIGHT, EFB_WIDTH, LinePointSize, Topology, VertexStream,
+};
 
 #[rustfmt::skip]
 pub use oneshot;
@@ -70,6 +74,9 @@ pub struct TexEnvConfig {
 #[derive(Debug, Clone, Default)]
 pub struct TexGenStage {
     pub base: BaseTexGen,
+    /// Whether the dual texture transform is enabled, i.e. whether regular texgens are normalized
+    /// (if `normalize` is set) and transformed by `post_matrix`.
+    pub dual: bool,
     pub normalize: bool,
     pub post_matrix: Mat4,
 }
@@ -77,6 +84,7 @@ pub struct TexGenStage {
 impl PartialEq for TexGenStage {
     fn eq(&self, other: &Self) -> bool {
         self.base == other.base
+            && self.dual == other.dual
             && self.normalize == other.normalize
             && HashableMat4::from(self.post_matrix) == HashableMat4::from(other.post_matrix)
     }
@@ -87,6 +95,7 @@ impl Eq for TexGenStage {}
 impl std::hash::Hash for TexGenStage {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.base.hash(state);
+        self.dual.hash(state);
         self.normalize.hash(state);
         HashableMat4::from(self.post_matrix).hash(state);
     }
@@ -117,6 +126,25 @@ pub struct Scaling {
     pub v: f32,
 }
 
+/// Screen space extents of the pixels drawn since the bounding box was last set, in EFB pixels.
+/// Bounds are inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BoundingBox {
+    pub left: u16,
+    pub right: u16,
+    pub top: u16,
+    pub bottom: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LinePoint {
+    pub size: LinePointSize,
+    /// Bitmask of the texture coordinates which are offset in lines.
+    pub line_tex_offsets: u8,
+    /// Bitmask of the texture coordinates which are offset in points.
+    pub point_tex_offsets: u8,
+}
+
 #[derive(Debug, Clone)]
 pub struct Clut(pub Vec<u16>);
 
@@ -135,6 +163,19 @@ impl ClutAddress {
     }
 }
 
+/// What the renderer should skip of the current frame, see [`Action::SetFrameSkip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FrameSkip {
+    /// Nothing is skipped.
+    #[default]
+    None,
+    /// The frame is rendered as usual, but never presented.
+    Present,
+    /// Draws are dropped as well, so that the frame costs no GPU work. EFB copies and bounding
+    /// box reads still happen, but see whatever the EFB held before.
+    Draws,
+}
+
 pub enum Action {
     SetFramebufferFormat(BufferFormat),
     SetViewport(Viewport),
@@ -145,6 +186,13 @@ pub enum Action {
     SetBlendMode(BlendMode),
     SetConstantAlpha(ConstantAlpha),
     SetAlphaFunction(AlphaFunction),
+    /// Sets whether the depth test happens before texturing (i.e. the zcomploc bit of the pixel
+    /// engine control register).
+    SetEarlyDepth(bool),
+    /// Sets the width of lines, the size of points and how their texture coordinates are offset.
+    SetLinePoint(LinePoint),
+    /// Sets the fog mode, parameters and color.
+    SetFog(Fog),
     SetProjectionMatrix(ProjectionMat),
     SetTexEnvConfig(TexEnvConfig),
     SetTexGenConfig(TexGenConfig),
@@ -191,6 +239,31 @@ pub enum Action {
     XfbCopy {
         clear: bool,
     },
+    /// Sets the horizontal (or vertical, if `vertical` is set) extents of the bounding box, which
+    /// then grows as geometry is drawn.
+    SetBoundingBox {
+        vertical: bool,
+        min: u16,
+        max: u16,
+    },
+    GetBoundingBox {
+        response: Sender<BoundingBox>,
+    },
+    /// Sets the amount of samples per pixel of the EFB (i.e. MSAA). A value of 1 disables
+    /// multisampling. Changing it discards the contents of the EFB.
+    SetMsaa(u32),
+    /// Presents an XFB drawn by the CPU, bypassing the EFB.
+    PresentXfb {
+        width: u16,
+        height: u16,
+        data: Vec<Rgba8>,
+    },
+    /// Drops all GPU state and caches, clearing both the EFB and the XFB. Issued when the system
+    /// is reset.
+    Reset,
+    /// Sets what to skip of the frames that follow, until changed again. Issued at frame
+    /// boundaries while emulation can't keep up with real time.
+    SetFrameSkip(FrameSkip),
 }
 
 const_assert!(size_of::<Action>() <= 64);
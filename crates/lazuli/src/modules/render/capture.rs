@@ -0,0 +1,951 @@
+//! Serialization of [`Action`] streams, so that they can be captured and replayed offline.
+//!
+//! A capture starts with [`MAGIC`] and [`VERSION`], followed by the encoded actions. Everything is
+//! little endian and sequences are prefixed with their length as an `u32`. Captures are only
+//! readable by the version that wrote them.
+//!
+//! The responses of EFB copies and bounding box queries are not part of a capture: replayed
+//! requests have their results discarded.
+use std::io::{self, Read, Write};
+
+use bitos::integer::{u2, u3, u4, u5, u10};
+use color::{Abgr8, Rgba, Rgba8, Rgba16};
+use easyerr::{Error, ResultExt};
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::modules::render::{
+    Action, Clut, ClutAddress, FrameSkip, LinePoint, Sampler, Scaling, TexEnvConfig, TexEnvStage,
+    TexGenConfig, TexGenStage, Texture, TextureId, Viewport, oneshot,
+};
+use crate::system::gx::pix::{BlendMode, BufferFormat, ConstantAlpha, DepthMode};
+use crate::system::gx::tev::{
+    AlphaFunction, Constant, DepthTexMode, DepthTexture, Fog, FogColor, FogMode, FogRange,
+    FogRangeFactors, FogScale, StageAlpha, StageColor, StageOps, StageRefs,
+};
+use crate::system::gx::tex::{ClutFormat, Format, LodLimits, MipmapData, SamplerMode};
+use crate::system::gx::xform::{BaseTexGen, ChannelControl, Light, ProjectionMat};
+use crate::system::gx::{CullingMode, LinePointSize, MatrixId, Topology, Vertex, VertexStream};
+
+/// Magic bytes at the start of every capture.
+pub const MAGIC: [u8; 4] = *b"LZAC";
+
+/// Version of the capture format. Must be bumped whenever the encoding of actions changes.
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error(transparent)]
+    Io { source: io::Error },
+    #[error("not an action capture")]
+    Magic,
+    #[error("capture has version {found}, but only version {VERSION} is supported")]
+    Version { found: u32 },
+    #[error("invalid {what} value {value}")]
+    Invalid { what: &'static str, value: u32 },
+}
+
+type Result<T> = std::result::Result<T, CaptureError>;
+
+trait Encode {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()>;
+}
+
+trait Decode: Sized {
+    fn decode(r: &mut dyn Read) -> Result<Self>;
+}
+
+fn decode<T: Decode>(r: &mut dyn Read) -> Result<T> {
+    T::decode(r)
+}
+
+macro_rules! primitive {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    let mut bytes = [0; size_of::<$ty>()];
+                    r.read_exact(&mut bytes).context(CaptureCtx::Io)?;
+                    Ok(<$ty>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+primitive!(u8, u16, u32, i16, f32);
+
+impl Encode for bool {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        (*self as u8).encode(w)
+    }
+}
+
+impl Decode for bool {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        match decode::<u8>(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(CaptureError::Invalid {
+                what: "bool",
+                value: value as u32,
+            }),
+        }
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        (self.len() as u32).encode(w)?;
+        self.iter().try_for_each(|item| item.encode(w))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.as_slice().encode(w)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        let len = decode::<u32>(r)?;
+        (0..len).map(|_| T::decode(r)).collect()
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.iter().try_for_each(|item| item.encode(w))
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        let items = (0..N).map(|_| T::decode(r)).collect::<Result<Vec<_>>>()?;
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.0.encode(w)?;
+        self.1.encode(w)
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        Ok((decode(r)?, decode(r)?))
+    }
+}
+
+macro_rules! glam {
+    ($($ty:ty: $len:literal, $to:ident, $from:expr;)*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    self.$to().encode(w)
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    Ok($from(decode::<[f32; $len]>(r)?))
+                }
+            }
+        )*
+    };
+}
+
+glam! {
+    Vec2: 2, to_array, Vec2::from_array;
+    Vec3: 3, to_array, Vec3::from_array;
+    Mat4: 16, to_cols_array, |cols: [f32; 16]| Mat4::from_cols_array(&cols);
+}
+
+/// Implements encoding for structs by encoding each of the given fields in order.
+macro_rules! fields {
+    ($($ty:ident { $($field:ident),* })*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    $(self.$field.encode(w)?;)*
+                    Ok(())
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    Ok(Self {
+                        $($field: decode(r)?,)*
+                    })
+                }
+            }
+        )*
+    };
+}
+
+fields! {
+    Rgba8 { r, g, b, a }
+    Abgr8 { a, b, g, r }
+    Rgba16 { r, g, b, a }
+    Rgba { r, g, b, a }
+    Viewport { width, height, top_left_x, top_left_y, near_depth, far_depth }
+    StageOps { color, alpha }
+    TexEnvStage { ops, refs, color_const, alpha_const }
+    DepthTexture { mode, bias }
+    TexEnvConfig { stages, constants, depth_tex }
+    TexGenStage { base, dual, normalize, post_matrix }
+    TexGenConfig { stages }
+    Texture { width, height, format, data }
+    Sampler { mode, lods }
+    Scaling { u, v }
+    LinePoint { size, line_tex_offsets, point_tex_offsets }
+    Fog { scale, b_magnitude, b_shift, mode, color, range, range_factors }
+    ProjectionMat { params, orthographic }
+    Light { color, cos_attenuation, dist_attenuation, position, direction }
+    Vertex {
+        position, normal, binormal, tangent, pos_norm_matrix, chan0, chan1, tex_coords,
+        tex_coords_matrix
+    }
+}
+
+macro_rules! newtype {
+    ($($ty:ident($inner:ty)),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    self.0.encode(w)
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    Ok(Self(decode::<$inner>(r)?))
+                }
+            }
+        )*
+    };
+}
+
+newtype!(TextureId(u32), ClutAddress(u16), Clut(Vec<u16>));
+
+/// Implements encoding for 32-bit wide bitfields through their raw bits.
+macro_rules! bits32 {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    self.to_bits().encode(w)
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    Ok(<$ty>::from_bits(decode(r)?))
+                }
+            }
+        )*
+    };
+}
+
+bits32!(
+    DepthMode,
+    BlendMode,
+    ConstantAlpha,
+    AlphaFunction,
+    StageColor,
+    StageAlpha,
+    DepthTexMode,
+    SamplerMode,
+    LodLimits,
+    BaseTexGen,
+    ChannelControl,
+    LinePointSize,
+    FogScale,
+    FogMode,
+    FogColor,
+    FogRange,
+    FogRangeFactors
+);
+
+/// Implements encoding for narrow bitfields through their raw bits, which are validated on
+/// decoding.
+macro_rules! bits {
+    ($($ty:ty: $int:ident($prim:ty, $width:literal)),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+                    self.to_bits().value().encode(w)
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(r: &mut dyn Read) -> Result<Self> {
+                    let value = decode::<$prim>(r)?;
+                    if value >> $width != 0 {
+                        return Err(CaptureError::Invalid {
+                            what: stringify!($ty),
+                            value: u32::from(value),
+                        });
+                    }
+
+                    Ok(<$ty>::from_bits($int::new(value)))
+                }
+            }
+        )*
+    };
+}
+
+bits!(
+    BufferFormat: u3(u8, 3),
+    CullingMode: u2(u8, 2),
+    StageRefs: u10(u16, 10),
+    Constant: u5(u8, 5),
+    ClutFormat: u2(u8, 2),
+    Format: u4(u8, 4)
+);
+
+impl Encode for FrameSkip {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        let value: u8 = match self {
+            Self::None => 0,
+            Self::Present => 1,
+            Self::Draws => 2,
+        };
+
+        value.encode(w)
+    }
+}
+
+impl Decode for FrameSkip {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        match decode::<u8>(r)? {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Present),
+            2 => Ok(Self::Draws),
+            value => Err(CaptureError::Invalid {
+                what: "FrameSkip",
+                value: value as u32,
+            }),
+        }
+    }
+}
+
+impl Encode for MatrixId {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.get().encode(w)
+    }
+}
+
+impl Decode for MatrixId {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        // 64 position matrices followed by 32 normal matrices
+        match decode::<u8>(r)? {
+            value @ 0..96 => Ok(Self::from_raw(value)),
+            value => Err(CaptureError::Invalid {
+                what: "MatrixId",
+                value: value as u32,
+            }),
+        }
+    }
+}
+
+impl Encode for Topology {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        let value: u8 = match self {
+            Self::QuadList => 0,
+            Self::TriangleList => 1,
+            Self::TriangleStrip => 2,
+            Self::TriangleFan => 3,
+            Self::LineList => 4,
+            Self::LineStrip => 5,
+            Self::PointList => 6,
+        };
+
+        value.encode(w)
+    }
+}
+
+impl Decode for Topology {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        Ok(match decode::<u8>(r)? {
+            0 => Self::QuadList,
+            1 => Self::TriangleList,
+            2 => Self::TriangleStrip,
+            3 => Self::TriangleFan,
+            4 => Self::LineList,
+            5 => Self::LineStrip,
+            6 => Self::PointList,
+            value => {
+                return Err(CaptureError::Invalid {
+                    what: "Topology",
+                    value: value as u32,
+                });
+            }
+        })
+    }
+}
+
+impl Encode for MipmapData {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Self::Direct(lods) => {
+                0u8.encode(w)?;
+                lods.encode(w)
+            }
+            Self::Indirect(lods) => {
+                1u8.encode(w)?;
+                lods.encode(w)
+            }
+        }
+    }
+}
+
+impl Decode for MipmapData {
+    fn decode(r: &mut dyn Read) -> Result<Self> {
+        Ok(match decode::<u8>(r)? {
+            0 => Self::Direct(decode(r)?),
+            1 => Self::Indirect(decode(r)?),
+            value => {
+                return Err(CaptureError::Invalid {
+                    what: "MipmapData",
+                    value: value as u32,
+                });
+            }
+        })
+    }
+}
+
+impl Encode for Action {
+    fn encode(&self, w: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Self::SetFramebufferFormat(format) => {
+                0u8.encode(w)?;
+                format.encode(w)
+            }
+            Self::SetViewport(viewport) => {
+                1u8.encode(w)?;
+                viewport.encode(w)
+            }
+            Self::SetCullingMode(mode) => {
+                2u8.encode(w)?;
+                mode.encode(w)
+            }
+            Self::SetClearColor(color) => {
+                3u8.encode(w)?;
+                color.encode(w)
+            }
+            Self::SetClearDepth(depth) => {
+                4u8.encode(w)?;
+                depth.encode(w)
+            }
+            Self::SetDepthMode(mode) => {
+                5u8.encode(w)?;
+                mode.encode(w)
+            }
+            Self::SetBlendMode(mode) => {
+                6u8.encode(w)?;
+                mode.encode(w)
+            }
+            Self::SetConstantAlpha(alpha) => {
+                7u8.encode(w)?;
+                alpha.encode(w)
+            }
+            Self::SetAlphaFunction(func) => {
+                8u8.encode(w)?;
+                func.encode(w)
+            }
+            Self::SetProjectionMatrix(mat) => {
+                9u8.encode(w)?;
+                mat.encode(w)
+            }
+            Self::SetTexEnvConfig(config) => {
+                10u8.encode(w)?;
+                config.encode(w)
+            }
+            Self::SetTexGenConfig(config) => {
+                11u8.encode(w)?;
+                config.encode(w)
+            }
+            Self::SetAmbient(idx, color) => {
+                12u8.encode(w)?;
+                idx.encode(w)?;
+                color.encode(w)
+            }
+            Self::SetMaterial(idx, color) => {
+                13u8.encode(w)?;
+                idx.encode(w)?;
+                color.encode(w)
+            }
+            Self::SetColorChannel(idx, control) => {
+                14u8.encode(w)?;
+                idx.encode(w)?;
+                control.encode(w)
+            }
+            Self::SetAlphaChannel(idx, control) => {
+                15u8.encode(w)?;
+                idx.encode(w)?;
+                control.encode(w)
+            }
+            Self::SetLight(idx, light) => {
+                16u8.encode(w)?;
+                idx.encode(w)?;
+                light.encode(w)
+            }
+            Self::LoadTexture { texture, id } => {
+                17u8.encode(w)?;
+                texture.encode(w)?;
+                id.encode(w)
+            }
+            Self::LoadClut { addr, clut } => {
+                18u8.encode(w)?;
+                addr.encode(w)?;
+                clut.encode(w)
+            }
+            Self::SetTextureSlot {
+                slot,
+                texture_id,
+                sampler,
+                scaling,
+                clut_addr,
+                clut_fmt,
+            } => {
+                19u8.encode(w)?;
+                (*slot as u32).encode(w)?;
+                texture_id.encode(w)?;
+                sampler.encode(w)?;
+                scaling.encode(w)?;
+                clut_addr.encode(w)?;
+                clut_fmt.encode(w)
+            }
+            Self::Draw(topology, stream) => {
+                20u8.encode(w)?;
+                topology.encode(w)?;
+                stream.vertices().encode(w)?;
+                stream.matrices().encode(w)
+            }
+            Self::ColorCopy {
+                x,
+                y,
+                width,
+                height,
+                half,
+                clear,
+                response: _,
+            } => {
+                21u8.encode(w)?;
+                [*x, *y, *width, *height].encode(w)?;
+                [*half, *clear].encode(w)
+            }
+            Self::DepthCopy {
+                x,
+                y,
+                width,
+                height,
+                half,
+                clear,
+                response: _,
+            } => {
+                22u8.encode(w)?;
+                [*x, *y, *width, *height].encode(w)?;
+                [*half, *clear].encode(w)
+            }
+            Self::XfbCopy { clear } => {
+                23u8.encode(w)?;
+                clear.encode(w)
+            }
+            Self::SetMsaa(samples) => {
+                24u8.encode(w)?;
+                samples.encode(w)
+            }
+            Self::PresentXfb {
+                width,
+                height,
+                data,
+            } => {
+                25u8.encode(w)?;
+                width.encode(w)?;
+                height.encode(w)?;
+                data.encode(w)
+            }
+            Self::Reset => 26u8.encode(w),
+            Self::SetEarlyDepth(early) => {
+                27u8.encode(w)?;
+                early.encode(w)
+            }
+            Self::SetLinePoint(line_point) => {
+                28u8.encode(w)?;
+                line_point.encode(w)
+            }
+            Self::SetBoundingBox { vertical, min, max } => {
+                29u8.encode(w)?;
+                vertical.encode(w)?;
+                [*min, *max].encode(w)
+            }
+            Self::GetBoundingBox { .. } => 30u8.encode(w),
+            Self::SetFog(fog) => {
+                31u8.encode(w)?;
+                fog.encode(w)
+            }
+            Self::SetFrameSkip(skip) => {
+                32u8.encode(w)?;
+                skip.encode(w)
+            }
+        }
+    }
+}
+
+impl Action {
+    fn decode_with_tag(tag: u8, r: &mut dyn Read) -> Result<Self> {
+        Ok(match tag {
+            0 => Self::SetFramebufferFormat(decode(r)?),
+            1 => Self::SetViewport(decode(r)?),
+            2 => Self::SetCullingMode(decode(r)?),
+            3 => Self::SetClearColor(decode(r)?),
+            4 => Self::SetClearDepth(decode(r)?),
+            5 => Self::SetDepthMode(decode(r)?),
+            6 => Self::SetBlendMode(decode(r)?),
+            7 => Self::SetConstantAlpha(decode(r)?),
+            8 => Self::SetAlphaFunction(decode(r)?),
+            9 => Self::SetProjectionMatrix(decode(r)?),
+            10 => Self::SetTexEnvConfig(decode(r)?),
+            11 => Self::SetTexGenConfig(decode(r)?),
+            12 => Self::SetAmbient(decode(r)?, decode(r)?),
+            13 => Self::SetMaterial(decode(r)?, decode(r)?),
+            14 => Self::SetColorChannel(decode(r)?, decode(r)?),
+            15 => Self::SetAlphaChannel(decode(r)?, decode(r)?),
+            16 => Self::SetLight(decode(r)?, decode(r)?),
+            17 => Self::LoadTexture {
+                texture: decode(r)?,
+                id: decode(r)?,
+            },
+            18 => Self::LoadClut {
+                addr: decode(r)?,
+                clut: decode(r)?,
+            },
+            19 => Self::SetTextureSlot {
+                slot: decode::<u32>(r)? as usize,
+                texture_id: decode(r)?,
+                sampler: decode(r)?,
+                scaling: decode(r)?,
+                clut_addr: decode(r)?,
+                clut_fmt: decode(r)?,
+            },
+            20 => {
+                let topology = decode(r)?;
+                let vertices = decode(r)?;
+                let matrices = decode(r)?;
+                Self::Draw(topology, VertexStream::new(vertices, matrices))
+            }
+            21 => {
+                let [x, y, width, height] = decode::<[u16; 4]>(r)?;
+                let [half, clear] = decode::<[bool; 2]>(r)?;
+                Self::ColorCopy {
+                    x,
+                    y,
+                    width,
+                    height,
+                    half,
+                    clear,
+                    response: oneshot::channel().0,
+                }
+            }
+            22 => {
+                let [x, y, width, height] = decode::<[u16; 4]>(r)?;
+                let [half, clear] = decode::<[bool; 2]>(r)?;
+                Self::DepthCopy {
+                    x,
+                    y,
+                    width,
+                    height,
+                    half,
+                    clear,
+                    response: oneshot::channel().0,
+                }
+            }
+            23 => Self::XfbCopy { clear: decode(r)? },
+            24 => Self::SetMsaa(decode(r)?),
+            25 => Self::PresentXfb {
+                width: decode(r)?,
+                height: decode(r)?,
+                data: decode(r)?,
+            },
+            26 => Self::Reset,
+            27 => Self::SetEarlyDepth(decode(r)?),
+            28 => Self::SetLinePoint(decode(r)?),
+            29 => {
+                let vertical = decode(r)?;
+                let [min, max] = decode::<[u16; 2]>(r)?;
+                Self::SetBoundingBox { vertical, min, max }
+            }
+            30 => Self::GetBoundingBox {
+                response: oneshot::channel().0,
+            },
+            31 => Self::SetFog(decode(r)?),
+            32 => Self::SetFrameSkip(decode(r)?),
+            _ => {
+                return Err(CaptureError::Invalid {
+                    what: "Action",
+                    value: tag as u32,
+                });
+            }
+        })
+    }
+}
+
+/// Writes a stream of actions to a capture.
+pub struct Writer<W> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer, writing the capture header to `inner`.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(&MAGIC)?;
+        VERSION.encode(&mut inner)?;
+        Ok(Self { inner })
+    }
+
+    pub fn write(&mut self, action: &Action) -> io::Result<()> {
+        action.encode(&mut self.inner)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads a stream of actions from a capture.
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader, checking the capture header in `inner`.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        inner.read_exact(&mut magic).context(CaptureCtx::Io)?;
+        if magic != MAGIC {
+            return Err(CaptureError::Magic);
+        }
+
+        let found = decode::<u32>(&mut inner)?;
+        if found != VERSION {
+            return Err(CaptureError::Version { found });
+        }
+
+        Ok(Self { inner })
+    }
+
+    /// Reads the next action. Returns `None` at the end of the capture.
+    pub fn read(&mut self) -> Result<Option<Action>> {
+        let mut tag = [0; 1];
+        match self.inner.read_exact(&mut tag) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(source) => return Err(CaptureError::Io { source }),
+        }
+
+        Action::decode_with_tag(tag[0], &mut self.inner).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vertex(i: u8) -> Vertex {
+        let f = i as f32;
+        Vertex {
+            position: Vec3::new(f, f + 1.0, f + 2.0),
+            normal: Vec3::Z,
+            binormal: Vec3::X,
+            tangent: Vec3::Y,
+            pos_norm_matrix: MatrixId::from_position_idx(i),
+            chan0: Rgba::new(1.0, 0.5, 0.25, 1.0),
+            chan1: Rgba::new(0.0, 0.0, 0.0, 0.5),
+            tex_coords: [Vec2::new(f, -f); 8],
+            tex_coords_matrix: [MatrixId::from_normal_idx(i); 8],
+        }
+    }
+
+    fn actions() -> Vec<Action> {
+        let stream = VertexStream::new(
+            (0..6).map(vertex).collect(),
+            vec![
+                (MatrixId::from_position_idx(0), Mat4::IDENTITY),
+                (
+                    MatrixId::from_normal_idx(3),
+                    Mat4::from_scale(Vec3::splat(2.0)),
+                ),
+            ],
+        );
+
+        let texture = Texture {
+            width: 2,
+            height: 2,
+            format: Format::Rgba8,
+            data: MipmapData::Direct(vec![
+                vec![
+                    Rgba8 {
+                        r: 1,
+                        g: 2,
+                        b: 3,
+                        a: 4,
+                    };
+                    4
+                ],
+                vec![Rgba8::default()],
+            ]),
+        };
+
+        vec![
+            Action::SetFramebufferFormat(BufferFormat::RGBA6Z24),
+            Action::SetViewport(Viewport::default()),
+            Action::SetCullingMode(CullingMode::Back),
+            Action::SetClearColor(Rgba::new(0.1, 0.2, 0.3, 1.0)),
+            Action::SetClearDepth(0.75),
+            Action::SetDepthMode(DepthMode::from_bits(0x17)),
+            Action::SetBlendMode(BlendMode::from_bits(0x0000_08A5)),
+            Action::SetConstantAlpha(ConstantAlpha::from_bits(0x1FF)),
+            Action::SetAlphaFunction(AlphaFunction::from_bits(0x00C0_FF80)),
+            Action::SetEarlyDepth(true),
+            Action::SetLinePoint(LinePoint {
+                size: LinePointSize::from_bits(0x0052_3010),
+                line_tex_offsets: 0b0000_0001,
+                point_tex_offsets: 0b1000_0000,
+            }),
+            Action::SetFog(Fog {
+                scale: FogScale::from_bits(0x0004_0000),
+                b_magnitude: 0x00AB_CDEF,
+                b_shift: 9,
+                mode: FogMode::from_bits(0x00B3_F800),
+                color: FogColor::from_bits(0x0080_4020),
+                range: FogRange::from_bits(0x0000_0556),
+                range_factors: [FogRangeFactors::from_bits(0x0010_0200); 5],
+            }),
+            Action::SetProjectionMatrix(ProjectionMat {
+                params: [1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+                orthographic: true,
+            }),
+            Action::SetTexEnvConfig(TexEnvConfig {
+                stages: vec![TexEnvStage::default(); 3],
+                constants: [Rgba16 {
+                    r: -1,
+                    g: 255,
+                    b: 1023,
+                    a: 0,
+                }; 4],
+                depth_tex: DepthTexture::default(),
+            }),
+            Action::SetTexGenConfig(TexGenConfig {
+                stages: vec![TexGenStage {
+                    base: BaseTexGen::from_bits(0x0000_1234),
+                    dual: true,
+                    normalize: true,
+                    post_matrix: Mat4::from_translation(Vec3::X),
+                }],
+            }),
+            Action::SetAmbient(
+                1,
+                Abgr8 {
+                    a: 1,
+                    b: 2,
+                    g: 3,
+                    r: 4,
+                },
+            ),
+            Action::SetColorChannel(0, ChannelControl::from_bits(0x7FFF)),
+            Action::LoadTexture {
+                texture,
+                id: TextureId(0xDEAD),
+            },
+            Action::LoadClut {
+                addr: ClutAddress(3),
+                clut: Clut(vec![0x1234; 16]),
+            },
+            Action::SetTextureSlot {
+                slot: 2,
+                texture_id: TextureId(0xDEAD),
+                sampler: Sampler::default(),
+                scaling: Scaling { u: 2.0, v: 0.5 },
+                clut_addr: ClutAddress(3),
+                clut_fmt: ClutFormat::RGB5A3,
+            },
+            Action::Draw(Topology::TriangleStrip, stream),
+            Action::ColorCopy {
+                x: 0,
+                y: 0,
+                width: 640,
+                height: 528,
+                half: false,
+                clear: true,
+                response: oneshot::channel().0,
+            },
+            Action::XfbCopy { clear: true },
+            Action::SetBoundingBox {
+                vertical: true,
+                min: 16,
+                max: 527,
+            },
+            Action::GetBoundingBox {
+                response: oneshot::channel().0,
+            },
+            Action::SetMsaa(4),
+            Action::SetFrameSkip(FrameSkip::Draws),
+            Action::PresentXfb {
+                width: 2,
+                height: 1,
+                data: vec![Rgba8::default(); 2],
+            },
+            Action::Reset,
+        ]
+    }
+
+    fn encode_all<'a>(actions: impl IntoIterator<Item = &'a Action>) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        for action in actions {
+            writer.write(action).unwrap();
+        }
+
+        writer.into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        let actions = actions();
+        let bytes = encode_all(&actions);
+
+        let mut reader = Reader::new(bytes.as_slice()).unwrap();
+        let mut decoded = Vec::new();
+        while let Some(action) = reader.read().unwrap() {
+            decoded.push(action);
+        }
+
+        assert_eq!(decoded.len(), actions.len());
+        assert_eq!(encode_all(&decoded), bytes);
+    }
+
+    #[test]
+    fn version_is_checked() {
+        let mut bytes = encode_all(&[]);
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        let result = Reader::new(bytes.as_slice());
+        assert!(matches!(result, Err(CaptureError::Version { found }) if found == VERSION + 1));
+
+        let result = Reader::new(&b"nope\x01\x00\x00\x00"[..]);
+        assert!(matches!(result, Err(CaptureError::Magic)));
+    }
+}
@@ -15,6 +15,9 @@ pub struct Ctx<'ctx> {
 
 /// Trait for vertex parsing modules.
 pub trait VertexModule: Send {
+    /// Parses the vertices of the given stream. Returns how many array elements were out of bounds
+    /// of RAM and read as zeroes instead, which is always zero for modules that don't validate
+    /// array indices.
     fn parse(
         &mut self,
         ctx: Ctx,
@@ -23,7 +26,7 @@ pub trait VertexModule: Send {
         stream: &VertexAttributeStream,
         vertices: &mut [MaybeUninit<Vertex>],
         matrix_set: &mut MatrixSet,
-    );
+    ) -> u32;
 }
 
 /// An implementation of [`VertexModule`] that panics when used to parse a vertex stream.
@@ -38,7 +41,7 @@ impl VertexModule for NopVertexModule {
         _: &VertexAttributeStream,
         _: &mut [MaybeUninit<Vertex>],
         _: &mut MatrixSet,
-    ) {
+    ) -> u32 {
         unimplemented!()
     }
 }
@@ -1,8 +1,10 @@
 //! State of the system (i.e. GameCube and emulator).
 
 pub mod bus;
+pub mod debug_output;
 pub mod eabi;
 pub mod executable;
+pub mod guest_console;
 pub mod ipl;
 pub mod lazy;
 pub mod os;
@@ -14,13 +16,15 @@ pub mod dspi;
 pub mod exi;
 pub mod gx;
 pub mod mem;
+pub mod mmu;
 pub mod pi;
 pub mod si;
 pub mod vi;
 
-use std::io::{Cursor, SeekFrom};
+use std::io::{Cursor, Read, SeekFrom};
 
 use disks::binrw::BinRead;
+use disks::dol::SectionInfo;
 use disks::{apploader, dol, iso};
 use easyerr::{Error, ResultExt};
 use gekko::{Address, Cpu, Cycles};
@@ -29,21 +33,73 @@ use crate::modules::audio::AudioModule;
 use crate::modules::debug::DebugModule;
 use crate::modules::disk::DiskModule;
 use crate::modules::input::InputModule;
-use crate::modules::render::RenderModule;
+use crate::modules::render::{Action, RenderModule};
 use crate::modules::vertex::VertexModule;
-use crate::system::dspi::Dsp;
+use crate::system::debug_output::DebugOutput;
+use crate::system::dspi::DspIo;
 use crate::system::executable::Executable;
+use crate::system::guest_console::GuestConsole;
 use crate::system::gx::Gpu;
 use crate::system::ipl::Ipl;
 use crate::system::lazy::Lazy;
 use crate::system::mem::Memory;
+use crate::system::mmu::Tlb;
 use crate::system::scheduler::{HandlerCtx, Scheduler};
 
+/// How the system boots.
+///
+/// The boot mode is always selected explicitly. Each mode requires its own boot source to be
+/// present, and a sideloaded executable is only accepted by [`BootMode::DirectDol`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMode {
+    /// Boot through the IPL ROM, as the console does. If no IPL is given, the ROM is zeroed.
+    Ipl,
+    /// Load the sideloaded executable and jump straight to its entrypoint, skipping the IPL.
+    DirectDol,
+    /// Load the apploader of the disc and boot it through the IPL HLE, skipping the IPL.
+    DiscApploader,
+}
+
 /// System configuration.
 pub struct Config {
-    pub ipl_lle: bool,
+    pub boot: BootMode,
     pub ipl: Option<Vec<u8>>,
     pub sideload: Option<Executable>,
+    /// Seed of the pattern main RAM, the L2C and ARAM are filled with at power-on. If `None`,
+    /// they are zeroed. Nothing else depends on it: the IPL ROM, the SRAM and every register
+    /// always start out the same. See [`mem::power_on_fill`].
+    pub fill_seed: Option<u64>,
+    /// Whether the system must not depend on the host, so that runs with the same inputs end up
+    /// in the same state. The only guest visible host state is the time the RTC starts at, which
+    /// is then `rtc_epoch` instead of the host's clock. Emulated time never depends on the host.
+    pub deterministic: bool,
+    /// Value of the RTC at power-on when deterministic, in seconds since 2000-01-01.
+    pub rtc_epoch: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum BootError {
+    #[error("direct DOL boot requires an executable to sideload")]
+    MissingExecutable,
+    #[error("disc apploader boot requires a disc")]
+    MissingDisc,
+    #[error("an executable to sideload was given, but the boot mode is {mode:?}")]
+    UnexpectedExecutable { mode: BootMode },
+}
+
+impl Config {
+    /// Checks whether the boot sources required by the boot mode are present, and that there
+    /// are no boot sources which would be ignored.
+    pub fn validate(&self, has_disk: bool) -> Result<(), BootError> {
+        match self.boot {
+            BootMode::DirectDol if self.sideload.is_none() => Err(BootError::MissingExecutable),
+            BootMode::DiscApploader if !has_disk => Err(BootError::MissingDisc),
+            BootMode::Ipl | BootMode::DiscApploader if self.sideload.is_some() => {
+                Err(BootError::UnexpectedExecutable { mode: self.boot })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// System modules.
@@ -56,6 +112,44 @@ pub struct Modules {
     pub vertex: Box<dyn VertexModule>,
 }
 
+#[cfg(test)]
+impl Modules {
+    /// Modules which do nothing, for tests.
+    pub(crate) fn nop() -> Self {
+        use crate::modules::audio::NopAudioModule;
+        use crate::modules::debug::NopDebugModule;
+        use crate::modules::disk::NopDiskModule;
+        use crate::modules::input::NopInputModule;
+        use crate::modules::render::NopRenderModule;
+        use crate::modules::vertex::NopVertexModule;
+
+        Self {
+            audio: Box::new(NopAudioModule),
+            debug: Box::new(NopDebugModule),
+            disk: Box::new(NopDiskModule),
+            input: Box::new(NopInputModule),
+            render: Box::new(NopRenderModule),
+            vertex: Box::new(NopVertexModule),
+        }
+    }
+}
+
+/// Arena high bound given to sideloaded executables. The arena ends below the sideload stack.
+const SIDELOAD_ARENA_HIGH: u32 = 0x8170_0000;
+/// Initial stack pointer given to sideloaded executables, near the end of RAM.
+const SIDELOAD_STACK_TOP: u32 = 0x817F_FFF0;
+/// Maximum length of a debug monitor. MetroTRK builds are well below this.
+const MAX_DEBUG_MONITOR_LEN: u32 = 0x4_0000;
+
+/// A debug monitor (e.g. MetroTRK) copied into memory at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugMonitor {
+    /// Address the monitor was copied to.
+    pub target: Address,
+    /// Length of the monitor, in bytes.
+    pub len: u32,
+}
+
 /// System state.
 pub struct System {
     /// System configuration.
@@ -69,23 +163,34 @@ pub struct System {
     /// The GPU state.
     pub gpu: Gpu,
     /// The DSP state.
-    pub dsp: Dsp,
+    pub dsp: DspIo,
     /// System memory.
     pub mem: Memory,
+    /// Software TLB for page table translations.
+    pub tlb: Tlb,
     /// State of mechanisms that update lazily (e.g. time related registers).
     pub lazy: Lazy,
     /// The video interface.
     pub video: vi::Interface,
+    /// Which frames are skipped while emulation can't keep up.
+    pub frame_skip: vi::FrameSkipper,
     /// The processor interface.
     pub processor: pi::Interface,
     /// The external interface.
     pub external: exi::Interface,
+    /// The guest console, shared with the devices which write to it. Kept across resets.
+    pub guest_console: GuestConsole,
+    /// Text written to the debug output port. Kept across resets.
+    debug_output: DebugOutput,
     /// The audio interface.
     pub audio: ai::Interface,
     /// The disk interface.
     pub disk: di::Interface,
     /// The serial interface.
     pub serial: si::Interface,
+    /// The debug monitor resident in memory, if the disc booted from has one. Debuggers should
+    /// avoid conflicting with it, e.g. by not replacing the exception handlers it installs.
+    pub debug_monitor: Option<DebugMonitor>,
 }
 
 #[derive(Debug, Error)]
@@ -107,56 +212,166 @@ impl System {
             .context(LoadApploaderCtx::Apploader)?;
 
         let size = apploader.header.size;
-        self.mem.ram_mut()[0x0120_0000..][..size as usize].copy_from_slice(&apploader.body);
+        self.mem
+            .ram_range_mut(0x0120_0000..0x0120_0000 + size as usize)
+            .copy_from_slice(&apploader.body);
 
         Ok(Address(apploader.header.entrypoint))
     }
 
-    fn load_executable(&mut self) {
-        let Some(exec) = self.config.sideload.take() else {
+    /// Copies the debug monitor of the disc with the given header to its target, like the IPL
+    /// does for debug builds. The header doesn't store the length of the monitor, so it is
+    /// assumed to extend up to the next region of the disc after it.
+    fn load_debug_monitor(&mut self, header: &iso::Header) {
+        let offset = header.debug_monitor_offset;
+        if offset == 0 {
             return;
-        };
+        }
 
-        match &exec {
-            Executable::Dol(dol) => {
-                self.cpu.pc = Address(dol.entrypoint());
-                self.cpu.supervisor.memory.setup_default_bats();
-                self.mem.build_bat_lut(&self.cpu.supervisor.memory);
-
-                self.cpu
-                    .supervisor
-                    .config
-                    .msr
-                    .set_instr_addr_translation(true);
-                self.cpu
-                    .supervisor
-                    .config
-                    .msr
-                    .set_data_addr_translation(true);
-
-                // zero bss first, let other sections overwrite it if it occurs
-                for offset in 0..dol.header.bss_size {
-                    self.write(Address(dol.header.bss_target + offset), 0u8);
-                }
+        let end = [0x2440, header.bootfile_offset, header.filesystem_offset]
+            .into_iter()
+            .filter(|&region| region > offset)
+            .min()
+            .unwrap_or(u32::MAX);
+        let len = (end - offset).min(MAX_DEBUG_MONITOR_LEN);
 
-                for section in dol.text_sections() {
-                    for (offset, byte) in section.content.iter().copied().enumerate() {
-                        self.write(Address(section.target) + offset as u32, byte);
-                    }
-                }
+        let mut monitor = Vec::with_capacity(len as usize);
+        let read = self
+            .modules
+            .disk
+            .seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| {
+                (&mut self.modules.disk)
+                    .take(len as u64)
+                    .read_to_end(&mut monitor)
+            });
+
+        if let Err(e) = read {
+            tracing::warn!("failed to read debug monitor: {e}");
+            return;
+        }
+
+        let target = Address(header.debug_monitor_target);
+        for (i, byte) in monitor.iter().copied().enumerate() {
+            self.write(target + i as u32, byte);
+        }
+
+        tracing::info!(
+            "loaded debug monitor at {target} (0x{:X} bytes)",
+            monitor.len()
+        );
 
-                for section in dol.data_sections() {
-                    for (offset, byte) in section.content.iter().copied().enumerate() {
-                        self.write(Address(section.target) + offset as u32, byte);
+        self.debug_monitor = Some(DebugMonitor {
+            target,
+            len: monitor.len() as u32,
+        });
+    }
+
+    /// Writes the given bytes to guest memory, starting at `target`.
+    fn write_section(&mut self, target: u32, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().copied().enumerate() {
+            self.write(Address(target) + offset as u32, byte);
+        }
+    }
+
+    fn load_executable(&mut self, exec: &mut Executable) {
+        let header = exec.header();
+        self.cpu.pc = Address(header.entry);
+        self.cpu.supervisor.memory.setup_default_bats();
+        self.mem.build_bat_lut(&self.cpu.supervisor.memory);
+
+        self.cpu
+            .supervisor
+            .config
+            .msr
+            .set_instr_addr_translation(true);
+        self.cpu
+            .supervisor
+            .config
+            .msr
+            .set_data_addr_translation(true);
+
+        // zero bss first, let other sections overwrite it if it occurs
+        for offset in 0..header.bss_size {
+            self.write(Address(header.bss_target + offset), 0u8);
+        }
+
+        match exec {
+            Executable::Dol(dol) => {
+                for section in dol.text_sections().chain(dol.data_sections()) {
+                    self.write_section(section.target, section.content);
+                }
+            }
+            Executable::DolFile(dol) => {
+                // stream the sections a chunk at a time, instead of reading the whole file
+                let sections = dol.header.text_sections().chain(dol.header.data_sections());
+                let mut chunk = [0; 0x1000];
+                for section in sections.collect::<Vec<_>>() {
+                    for start in (0..section.size).step_by(chunk.len()) {
+                        let part = SectionInfo {
+                            offset: section.offset + start,
+                            target: section.target + start,
+                            size: (section.size - start).min(chunk.len() as u32),
+                        };
+
+                        let buf = &mut chunk[..part.size as usize];
+                        if let Err(e) = dol.read_section(part, buf) {
+                            tracing::error!("failed to read executable section: {e}");
+                            break;
+                        }
+
+                        self.write_section(part.target, buf);
                     }
                 }
             }
         }
 
-        self.config.sideload = Some(exec);
         tracing::debug!("finished loading executable");
     }
 
+    /// Writes the boot info block the OS library expects in low memory, with the given arena
+    /// bounds. Disc related fields are left alone.
+    fn write_boot_info(&mut self, arena_low: u32, arena_high: u32) {
+        self.write_phys_slow::<u32>(Address(0x20), 0x0D15_EA5E); // Boot kind
+        self.write_phys_slow::<u32>(Address(0x24), 0x0000_0001); // Version
+        self.write_phys_slow::<u32>(Address(0x28), 0x0180_0000); // Physical Memory Size
+        self.write_phys_slow::<u32>(Address(0x2C), 0x1000_0005); // Console Type
+        self.write_phys_slow::<u32>(Address(0x30), arena_low); // Arena Low
+        self.write_phys_slow::<u32>(Address(0x34), arena_high); // Arena High
+        // TODO: deal with TV mode, games hang if it is wrong...
+        self.write_phys_slow::<u32>(Address(0xCC), 0x0000_0000); // TV Mode
+        self.write_phys_slow::<u32>(Address(0xD0), 0x0100_0000); // ARAM size
+        self.write_phys_slow::<u32>(Address(0xF0), 0x0180_0000); // Simulated Memory Size
+        self.write_phys_slow::<u32>(Address(0xF8), 0x09A7_EC80); // Bus clock
+        self.write_phys_slow::<u32>(Address(0xFC), 0x1CF7_C580); // CPU clock
+    }
+
+    /// Loads the sideloaded executable and sets up what the apploader and IPL usually leave
+    /// behind for it: the boot info block and a stack.
+    ///
+    /// Arena low is left as zero, which makes `OSInit` use the `__ArenaLo` symbol of the
+    /// executable. The SDK linker script places the stack right after .bss, so the end of the
+    /// loaded sections is _not_ a safe arena start. Disc related fields (disc ID, FST) are left
+    /// as zero, so titles which read from the disc still need one to be inserted.
+    fn load_direct_dol(&mut self) {
+        // the executable is kept in the config, so that it is loaded again on every boot
+        let mut exec = self
+            .config
+            .sideload
+            .take()
+            .expect("validated by the config");
+        self.load_executable(&mut exec);
+        self.config.sideload = Some(exec);
+
+        self.write_boot_info(0, SIDELOAD_ARENA_HIGH);
+
+        // the SDK's __init_registers sets its own stack, but homebrew might not
+        self.cpu.user.gpr[1] = SIDELOAD_STACK_TOP;
+
+        // exceptions go to the handlers the OS installs in low memory, not to the IPL
+        self.cpu.supervisor.config.msr.set_exception_prefix(false);
+    }
+
     fn load_ipl_hle(&mut self) {
         self.cpu.supervisor.memory.setup_default_bats();
         self.mem.build_bat_lut(&self.cpu.supervisor.memory);
@@ -187,6 +402,9 @@ impl System {
                 .unwrap_or("<unknown>")
         );
 
+        // the IPL copies the debug monitor before running the apploader
+        self.load_debug_monitor(&header);
+
         // load apploader
         let entry = self.load_apploader().unwrap();
 
@@ -196,8 +414,7 @@ impl System {
             "/../../local/ipl-hle.dol"
         )));
         let ipl = dol::Dol::read(&mut cursor).unwrap();
-        self.config.sideload = Some(Executable::Dol(ipl));
-        self.load_executable();
+        self.load_executable(&mut Executable::Dol(ipl));
 
         // setup apploader entrypoint for ipl-hle
         self.cpu.user.gpr[3] = entry.value();
@@ -211,19 +428,9 @@ impl System {
         self.write_phys_slow::<u8>(Address(0x09), header.meta.stream_buffer_size);
 
         self.write_phys_slow::<u32>(Address(0x1C), 0xC233_9F3D); // DVD Magic Word
-        self.write_phys_slow::<u32>(Address(0x20), 0x0D15_EA5E); // Boot kind
-        self.write_phys_slow::<u32>(Address(0x24), 0x0000_0001); // Version
-        self.write_phys_slow::<u32>(Address(0x28), 0x0180_0000); // Physical Memory Size
-        self.write_phys_slow::<u32>(Address(0x2C), 0x1000_0005); // Console Type
-        self.write_phys_slow::<u32>(Address(0x30), 0x8042_E260); // Arena Low
-        self.write_phys_slow::<u32>(Address(0x34), 0x817F_E8C0); // Arena High
+        self.write_boot_info(0x8042_E260, 0x817F_E8C0);
         self.write_phys_slow::<u32>(Address(0x38), 0x817F_E8C0); // FST address
         self.write_phys_slow::<u32>(Address(0x3C), 0x0000_0024); // FST max length
-        // TODO: deal with TV mode, games hang if it is wrong...
-        self.write_phys_slow::<u32>(Address(0xCC), 0x0000_0000); // TV Mode
-        self.write_phys_slow::<u32>(Address(0xD0), 0x0100_0000); // ARAM size
-        self.write_phys_slow::<u32>(Address(0xF8), 0x09A7_EC80); // Bus clock
-        self.write_phys_slow::<u32>(Address(0xFC), 0x1CF7_C580); // CPU clock
 
         self.video
             .display_config
@@ -240,41 +447,92 @@ impl System {
         self.cpu.pc = Address(0xFFF0_0100);
     }
 
-    pub fn new(modules: Modules, mut config: Config) -> Self {
+    fn boot(&mut self) {
+        match self.config.boot {
+            BootMode::Ipl => self.load_ipl(),
+            BootMode::DirectDol => self.load_direct_dol(),
+            BootMode::DiscApploader => self.load_ipl_hle(),
+        }
+    }
+
+    fn initial_scheduler() -> Scheduler {
         let mut scheduler = Scheduler::default();
         scheduler.schedule(1 << 16, gx::cmd::process);
+        scheduler
+    }
+
+    pub fn new(modules: Modules, mut config: Config) -> Result<Self, BootError> {
+        config.validate(modules.disk.has_disk())?;
 
         let ipl = Ipl::new(config.ipl.take().unwrap_or_else(|| vec![0; mem::IPL_LEN]));
 
+        let fill_seed = config.fill_seed;
+        let rtc = if config.deterministic {
+            config.rtc_epoch
+        } else {
+            exi::rtc::host_rtc()
+        };
+
         let mut system = System {
-            scheduler,
+            scheduler: Self::initial_scheduler(),
             cpu: Cpu::default(),
             gpu: Gpu::default(),
-            dsp: Dsp::new(),
-            mem: Memory::new(&ipl),
+            dsp: DspIo::new(),
+            mem: Memory::new(&ipl, fill_seed),
+            tlb: Tlb::default(),
             lazy: Lazy::default(),
             video: vi::Interface::default(),
+            frame_skip: vi::FrameSkipper::default(),
             processor: pi::Interface::default(),
-            external: exi::Interface::new(),
+            external: exi::Interface::new(&ipl, rtc),
+            guest_console: GuestConsole::default(),
+            debug_output: DebugOutput::default(),
             audio: ai::Interface::default(),
             disk: di::Interface::default(),
             serial: si::Interface::default(),
+            debug_monitor: None,
 
             config,
             modules,
         };
 
-        if system.config.ipl_lle {
-            system.load_ipl();
-        } else if system.config.sideload.is_some() {
-            system.load_executable();
-        } else if system.modules.disk.has_disk() {
-            system.load_ipl_hle();
-        } else {
-            system.load_ipl();
-        }
+        mem::power_on_fill(&mut system.dsp.aram[..], fill_seed);
+        system.boot();
+        Ok(system)
+    }
+
+    /// Hard resets the system, i.e. brings every component back to its power-on state and boots
+    /// again from the configured boot source. Battery backed state (the SRAM) is kept, and the
+    /// render module is told to drop its state.
+    ///
+    /// For the console's reset button, see [`pi::press_reset_switch`].
+    pub fn reset(&mut self) {
+        tracing::info!("hard resetting system");
+
+        self.scheduler = Self::initial_scheduler();
+        self.cpu = Cpu::default();
+        self.gpu = Gpu::default();
+        self.dsp = DspIo::new();
+        mem::power_on_fill(&mut self.dsp.aram[..], self.config.fill_seed);
+        self.mem.reset(self.config.fill_seed);
+        self.tlb.invalidate_all();
+        self.lazy = Lazy::default();
+        self.video = vi::Interface::default();
+        self.processor = pi::Interface::default();
+        self.external.reset();
+        self.audio = ai::Interface::default();
+        self.disk = di::Interface::default();
+        self.serial = si::Interface::default();
+        self.debug_monitor = None;
+        self.frame_skip.reset();
+
+        self.modules.render.exec(Action::Reset);
+        self.boot();
+    }
 
-        system
+    /// Text written to the debug output port, e.g. by OSReport.
+    pub fn debug_output(&self) -> &DebugOutput {
+        &self.debug_output
     }
 
     /// Processes scheduled events.
@@ -286,7 +544,320 @@ impl System {
                 cycles_late: Cycles(cycles_late),
             };
 
+            profiling::scope!("scheduler event");
             event.handler.call(self, ctx);
         }
     }
+
+    /// Advances the scheduler clock by the given number of CPU cycles. See
+    /// [`System::advance_to`].
+    pub fn advance_by(&mut self, cycles: Cycles) {
+        self.advance_to(self.scheduler.elapsed() + cycles.0);
+    }
+
+    /// Advances the scheduler clock to the given cycle without running the CPU or the DSP,
+    /// processing every event due until then. The clock stops at each event's cycle before it is
+    /// processed, so handlers see the time they were scheduled for and events they schedule are
+    /// processed in order if they are also due.
+    ///
+    /// This is meant for tests of timed events (e.g. interrupts, DMA completion). Since the cores
+    /// don't run, time passes without any instruction being executed - the time base and the
+    /// decrementer still advance, as they derive from the clock. The clock can't go backwards, so
+    /// `cycle` must not be in the past.
+    pub fn advance_to(&mut self, cycle: u64) {
+        assert!(
+            cycle >= self.scheduler.elapsed(),
+            "can't advance the scheduler clock backwards"
+        );
+
+        while let Some(until_next) = self.scheduler.until_next()
+            && self.scheduler.elapsed() + until_next <= cycle
+        {
+            self.scheduler.advance(until_next);
+            self.process_events();
+        }
+
+        self.scheduler.advance(cycle - self.scheduler.elapsed());
+    }
+
+    /// Returns the physical memory map of the system.
+    pub fn memory_map(&self) -> &'static [mem::MemRegion] {
+        &mem::MEMORY_MAP
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use disks::dol::{Dol, Header};
+
+    use super::*;
+
+    /// A direct DOL boot of an executable with a single text section at 0x8000_3100, filled with
+    /// 0x60 bytes, followed by .bss.
+    fn direct_dol_config() -> Config {
+        let mut header = Header::default();
+        header.text_offsets[0] = 0x100;
+        header.text_targets[0] = 0x8000_3100;
+        header.text_sizes[0] = 0x20;
+        header.bss_target = 0x8000_3120;
+        header.bss_size = 0x40;
+        header.entry = 0x8000_3100;
+
+        let dol = Dol {
+            header,
+            body: vec![0x60; 0x20],
+        };
+
+        Config {
+            boot: BootMode::DirectDol,
+            ipl: None,
+            sideload: Some(Executable::Dol(dol)),
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn direct_dol_boot_info() {
+        let mut sys = System::new(Modules::nop(), direct_dol_config()).unwrap();
+        let mut word = |addr| sys.read_phys_slow::<u32>(Address(addr));
+
+        assert_eq!(word(0x20), 0x0D15_EA5E);
+        assert_eq!(word(0x28), 0x0180_0000);
+        assert_eq!(word(0x30), 0);
+        assert_eq!(word(0x34), SIDELOAD_ARENA_HIGH);
+        assert_eq!(word(0x38), 0);
+        assert_eq!(word(0xF0), 0x0180_0000);
+        assert_eq!(word(0xF8), 0x09A7_EC80);
+        assert_eq!(word(0xFC), 0x1CF7_C580);
+        assert_eq!(word(0x3100), 0x6060_6060);
+        assert_eq!(word(0x3120), 0);
+
+        assert_eq!(sys.cpu.pc, Address(0x8000_3100));
+        assert_eq!(sys.cpu.user.gpr[1], SIDELOAD_STACK_TOP);
+    }
+
+    #[test]
+    fn direct_dol_reloads_on_reset() {
+        let mut sys = System::new(Modules::nop(), direct_dol_config()).unwrap();
+        sys.write_phys_slow::<u32>(Address(0x3100), 0xDEAD_BEEF);
+        sys.cpu.pc = Address(0x8000_3110);
+
+        sys.reset();
+        assert_eq!(sys.read_phys_slow::<u32>(Address(0x3100)), 0x6060_6060);
+        assert_eq!(sys.cpu.pc, Address(0x8000_3100));
+        assert!(sys.config.sideload.is_some());
+    }
+
+    #[test]
+    fn dol_file_loads_like_dol() {
+        use disks::binrw::BinWrite;
+
+        // sections larger than the chunks a .dol file is streamed in
+        let mut header = Header::default();
+        header.text_offsets[0] = 0x100;
+        header.text_targets[0] = 0x8000_3100;
+        header.text_sizes[0] = 0x1804;
+        header.data_offsets[0] = 0x1904;
+        header.data_targets[0] = 0x8001_0000;
+        header.data_sizes[0] = 0x2000;
+        header.entry = 0x8000_3100;
+
+        let body = (0..0x3804u32).map(|i| (i * 7) as u8).collect();
+        let dol = Dol { header, body };
+
+        let mut file = Cursor::new(Vec::new());
+        dol.write(&mut file).unwrap();
+        let path = std::env::temp_dir().join(format!("lazuli-sideload-{}.dol", std::process::id()));
+        std::fs::write(&path, file.into_inner()).unwrap();
+        let dol_file = Executable::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let boot = |exec| {
+            let config = Config {
+                boot: BootMode::DirectDol,
+                ipl: None,
+                sideload: Some(exec),
+                fill_seed: None,
+                deterministic: true,
+                rtc_epoch: 0,
+            };
+
+            System::new(Modules::nop(), config).unwrap()
+        };
+
+        let from_file = boot(dol_file);
+        let from_memory = boot(Executable::Dol(dol));
+        assert!(from_file.mem.ram() == from_memory.mem.ram());
+        assert_eq!(from_file.mem.ram()[0x3100..][..4], [0, 7, 14, 21]);
+        assert_eq!(from_file.cpu.pc, from_memory.cpu.pc);
+    }
+
+    struct TestDisk(Cursor<Vec<u8>>);
+
+    impl Read for TestDisk {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl std::io::Seek for TestDisk {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    impl DiskModule for TestDisk {
+        fn has_disk(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn debug_monitor_is_copied_to_its_target() {
+        const MONITOR_OFFSET: u32 = 0x8000;
+        const MONITOR_LEN: u32 = 0x100;
+
+        let mut image = vec![0; 0x1_0000];
+        let marker = (0..MONITOR_LEN).map(|i| i as u8 ^ 0xA5).collect::<Vec<_>>();
+        image[MONITOR_OFFSET as usize..][..marker.len()].copy_from_slice(&marker);
+        // something right after the monitor, which must not be copied
+        image[(MONITOR_OFFSET + MONITOR_LEN) as usize..][..4].copy_from_slice(&[0xFF; 4]);
+
+        // an empty header, apart from the magic word
+        let mut raw_header = vec![0; 0x440];
+        raw_header[0x1C..0x20].copy_from_slice(&0xC233_9F3D_u32.to_be_bytes());
+        let mut header = iso::Header::read(&mut Cursor::new(raw_header)).unwrap();
+        header.debug_monitor_offset = MONITOR_OFFSET;
+        header.debug_monitor_target = 0x8170_0000;
+        header.bootfile_offset = MONITOR_OFFSET + MONITOR_LEN;
+
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut sys = System::new(Modules::nop(), config).unwrap();
+        sys.modules.disk = Box::new(TestDisk(Cursor::new(image)));
+        sys.cpu.supervisor.memory.setup_default_bats();
+        sys.mem.build_bat_lut(&sys.cpu.supervisor.memory);
+
+        sys.load_debug_monitor(&header);
+        assert_eq!(
+            sys.debug_monitor,
+            Some(DebugMonitor {
+                target: Address(0x8170_0000),
+                len: MONITOR_LEN,
+            })
+        );
+
+        let copied = (0..MONITOR_LEN + 4)
+            .map(|i| sys.read_phys_slow::<u8>(Address(0x0170_0000 + i)))
+            .collect::<Vec<_>>();
+        assert_eq!(copied[..MONITOR_LEN as usize], marker);
+        assert_eq!(copied[MONITOR_LEN as usize..], [0; 4]);
+
+        // no monitor, nothing copied
+        header.debug_monitor_offset = 0;
+        sys.debug_monitor = None;
+        sys.load_debug_monitor(&header);
+        assert_eq!(sys.debug_monitor, None);
+    }
+
+    #[test]
+    fn advance_to_processes_events_in_order() {
+        static FIRED: Mutex<Vec<(&str, u64, u64)>> = Mutex::new(Vec::new());
+
+        fn record(name: &'static str, sys: &mut System, ctx: HandlerCtx) {
+            let cycle = sys.scheduler.elapsed();
+            FIRED.lock().unwrap().push((name, cycle, ctx.cycles_late.0));
+        }
+
+        fn first(sys: &mut System, ctx: HandlerCtx) {
+            record("first", sys, ctx);
+            sys.scheduler.schedule_full(50, chained);
+        }
+
+        fn chained(sys: &mut System, ctx: HandlerCtx) {
+            record("chained", sys, ctx);
+        }
+
+        fn last(sys: &mut System, ctx: HandlerCtx) {
+            record("last", sys, ctx);
+        }
+
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut sys = System::new(Modules::nop(), config).unwrap();
+        let start = sys.scheduler.elapsed();
+        sys.scheduler.schedule_full(100, first);
+        sys.scheduler.schedule_full(200, last);
+
+        sys.advance_to(start + 99);
+        assert!(FIRED.lock().unwrap().is_empty());
+
+        sys.advance_to(start + 150);
+        assert_eq!(sys.scheduler.elapsed(), start + 150);
+        assert_eq!(*FIRED.lock().unwrap(), [("first", start + 100, 0)]);
+
+        sys.advance_by(Cycles(100));
+        assert_eq!(
+            *FIRED.lock().unwrap(),
+            [
+                ("first", start + 100, 0),
+                ("chained", start + 150, 0),
+                ("last", start + 200, 0),
+            ]
+        );
+        assert_eq!(sys.scheduler.elapsed(), start + 250);
+    }
+
+    #[test]
+    fn aram_dma_copies_once_done() {
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut sys = System::new(Modules::nop(), config).unwrap();
+        let data = (0..0x40).collect::<Vec<u8>>();
+        sys.mem.ram_mut()[0x1000..0x1040].copy_from_slice(&data);
+
+        sys.write_phys_slow::<u32>(Address(0x0C00_5020), 0x1000);
+        sys.write_phys_slow::<u32>(Address(0x0C00_5024), 0x20_0000);
+
+        // the length is written last, in halves
+        sys.write_phys_slow::<u16>(Address(0x0C00_5028), 0);
+        assert!(!sys.dsp.control.dma_ongoing());
+        sys.write_phys_slow::<u16>(Address(0x0C00_502A), 0x40);
+        assert!(sys.dsp.control.dma_ongoing());
+
+        sys.advance_by(Cycles(1));
+        assert_ne!(sys.dsp.aram[0x20_0000..][..0x40], data);
+        assert!(!sys.dsp.control.aram_interrupt());
+
+        sys.advance_by(Cycles(10_000));
+        assert_eq!(sys.dsp.aram[0x20_0000..][..0x40], data);
+        assert!(!sys.dsp.control.dma_ongoing());
+        assert!(sys.dsp.control.aram_interrupt());
+        assert_eq!(sys.read_phys_slow::<u32>(Address(0x0C00_5028)), 0);
+    }
 }
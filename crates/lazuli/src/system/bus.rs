@@ -8,6 +8,7 @@ use zerocopy::IntoBytes;
 
 use crate::Primitive;
 use crate::system::mem::{IPL_LEN, L2C_LEN, RAM_LEN};
+use crate::system::mmu::{Access, Fault};
 use crate::system::{System, ai, di, dspi, exi, gx, pi, si, vi};
 
 #[rustfmt::skip]
@@ -45,44 +46,109 @@ macro_rules! map {
 
 impl System {
     /// Translates a data logical address into a physical address.
+    ///
+    /// Addresses not covered by a BAT are translated through the page table, without updating
+    /// the TLB or the page table itself.
     #[inline(always)]
     pub fn translate_data_addr(&self, addr: Address) -> Option<Address> {
         if !self.cpu.supervisor.config.msr.data_addr_translation() {
             return Some(addr);
         }
 
-        self.mem.translate_data_addr(addr)
+        self.mem
+            .translate_data_addr(addr)
+            .or_else(|| self.page_translate(addr, Access::Read).ok())
     }
 
     /// Translates an instruction logical address into a physical address.
+    ///
+    /// Addresses not covered by a BAT are translated through the page table, without updating
+    /// the TLB or the page table itself.
     #[inline(always)]
     pub fn translate_instr_addr(&self, addr: Address) -> Option<Address> {
         if !self.cpu.supervisor.config.msr.instr_addr_translation() {
             return Some(addr);
         }
 
-        self.mem.translate_inst_addr(addr)
+        self.mem
+            .translate_inst_addr(addr)
+            .or_else(|| self.page_translate(addr, Access::Fetch).ok())
+    }
+
+    /// Translates a data logical address into a physical address for an actual access, i.e.
+    /// going through the TLB and updating the referenced and changed bits of the page table.
+    #[inline(always)]
+    pub fn translate_data_access(&mut self, addr: Address, write: bool) -> Result<Address, Fault> {
+        if !self.cpu.supervisor.config.msr.data_addr_translation() {
+            return Ok(addr);
+        }
+
+        match self.mem.translate_data_addr(addr) {
+            Some(addr) => Ok(addr),
+            None => {
+                let access = if write { Access::Write } else { Access::Read };
+                self.page_translate_mut(addr, access)
+            }
+        }
+    }
+
+    /// Translates an instruction logical address into a physical address for an actual fetch,
+    /// i.e. going through the TLB and updating the referenced bit of the page table.
+    #[inline(always)]
+    pub fn translate_instr_access(&mut self, addr: Address) -> Result<Address, Fault> {
+        if !self.cpu.supervisor.config.msr.instr_addr_translation() {
+            return Ok(addr);
+        }
+
+        match self.mem.translate_inst_addr(addr) {
+            Some(addr) => Ok(addr),
+            None => self.page_translate_mut(addr, Access::Fetch),
+        }
     }
 
     /// Reads a primitive from the given physical address, but only if it can't possibly have a
     /// side effect.
     pub fn read_phys_pure<P: Primitive>(&self, addr: Address) -> Option<P> {
-        let offset: usize;
-        map! {
-            offset, addr;
-            0x0000_0000, RAM_LEN => Some(P::read_be_bytes(&self.mem.ram()[offset..])),
-            0xFFF0_0000, IPL_LEN / 2 => Some(P::read_be_bytes(&self.mem.ipl()[offset..])),
-            @default => None
-        }
+        self.mem
+            .slice(addr, size_of::<P>() as u32)
+            .map(P::read_be_bytes)
     }
 
-    /// Reads a primitive from the given physical address, but only if it can't possibly have a
+    /// Reads a primitive from the given logical address, but only if it can't possibly have a
     /// side effect.
     pub fn read_pure<P: Primitive>(&self, addr: Address) -> Option<P> {
         self.translate_data_addr(addr)
             .and_then(|addr| self.read_phys_pure(addr))
     }
 
+    /// Copies the logical range starting at `addr` into `buf`, translating it page by page like
+    /// data accesses do. Bytes which are untranslated or unbacked (see [`mem::Chunk::data`]) are
+    /// zeroed. Returns how many bytes were backed.
+    ///
+    /// [`mem::Chunk::data`]: crate::system::mem::Chunk::data
+    pub fn copy_to_host(&self, addr: Address, buf: &mut [u8]) -> usize {
+        const PAGE_LEN: u32 = 1 << 12;
+
+        let mut backed = 0;
+        let mut addr = addr.value();
+        let mut out = buf;
+        while !out.is_empty() {
+            let page_remaining = PAGE_LEN - (addr % PAGE_LEN);
+            let len = (page_remaining as usize).min(out.len());
+            let (dst, rest) = out.split_at_mut(len);
+
+            match self.translate_data_addr(Address(addr)) {
+                Some(physical) => backed += self.mem.copy_to_host(physical, dst),
+                None => dst.fill(0),
+            }
+
+            addr = addr.wrapping_add(len as u32);
+            out = rest;
+        }
+
+        backed
+    }
+
     fn read_mmio<P: Primitive>(&mut self, offset: u16) -> P {
         let Some((reg, offset)) = Mmio::find(offset) else {
             tracing::error!(pc = ?self.cpu.pc, "reading from unknown mmio register ({offset:04X})");
@@ -125,10 +191,16 @@ impl System {
             Mmio::CpFifoWritePtrHigh => ne!(self.gpu.cmd.fifo.write_ptr.as_bytes()[2..4]),
             Mmio::CpFifoReadPtrLow => ne!(self.gpu.cmd.fifo.read_ptr.as_bytes()[0..2]),
             Mmio::CpFifoReadPtrHigh => ne!(self.gpu.cmd.fifo.read_ptr.as_bytes()[2..4]),
+            Mmio::CpFifoBreakpointLow => ne!(self.gpu.cmd.fifo.breakpoint.as_bytes()[0..2]),
+            Mmio::CpFifoBreakpointHigh => ne!(self.gpu.cmd.fifo.breakpoint.as_bytes()[2..4]),
 
             // === Pixel Engine ===
             Mmio::PixelInterruptStatus => ne!(self.gpu.pix.interrupt.as_bytes()),
             Mmio::PixelToken => ne!((self.gpu.pix.token as u16).as_bytes()),
+            Mmio::PixelBoundingBoxLeft => ne!(gx::bounding_box(self).left.as_bytes()),
+            Mmio::PixelBoundingBoxRight => ne!(gx::bounding_box(self).right.as_bytes()),
+            Mmio::PixelBoundingBoxTop => ne!(gx::bounding_box(self).top.as_bytes()),
+            Mmio::PixelBoundingBoxBottom => ne!(gx::bounding_box(self).bottom.as_bytes()),
 
             // === Video Interface ===
             Mmio::VideoVerticalTiming => ne!(self.video.vertical_timing.as_bytes()),
@@ -167,9 +239,7 @@ impl System {
 
             // === Processor Interface ===
             // Interrupts
-            Mmio::ProcessorInterruptCause => {
-                ne!((pi::get_raised_interrupts(self).to_bits().value() as u32).as_bytes())
-            }
+            Mmio::ProcessorInterruptCause => ne!(pi::interrupt_cause(self).as_bytes()),
             Mmio::ProcessorInterruptMask => ne!(self.processor.mask.as_bytes()),
 
             // FIFO
@@ -299,9 +369,9 @@ impl System {
 
     /// Reads a primitive from the given logical address.
     #[inline(always)]
-    pub fn read_slow<P: Primitive>(&mut self, addr: Address) -> Option<P> {
-        let addr = self.translate_data_addr(addr)?;
-        Some(self.read_phys_slow(addr))
+    pub fn read_slow<P: Primitive>(&mut self, addr: Address) -> Result<P, Fault> {
+        let addr = self.translate_data_access(addr, false)?;
+        Ok(self.read_phys_slow(addr))
     }
 
     /// Reads a primitive from the given logical address using fastmem, if possible.
@@ -313,7 +383,7 @@ impl System {
         };
 
         let page = addr.value() >> 17;
-        let base = lut[page as usize];
+        let base = lut.loads[page as usize];
 
         base.map(|base| {
             let offset = addr.value().bits(0, 17) as usize;
@@ -326,7 +396,7 @@ impl System {
     /// falling back to slowmem if not possible.
     #[inline(always)]
     pub fn read<P: Primitive>(&mut self, addr: Address) -> Option<P> {
-        self.read_fast(addr).or_else(|| self.read_slow(addr))
+        self.read_fast(addr).or_else(|| self.read_slow(addr).ok())
     }
 
     fn write_mmio<P: Primitive>(&mut self, offset: u16, value: P) {
@@ -366,15 +436,21 @@ impl System {
             // === Command Processor ===
             Mmio::CpStatus => ne!(self.gpu.cmd.status.as_mut_bytes()),
             Mmio::CpControl => {
-                ne!(self.gpu.cmd.control.as_mut_bytes());
+                let mut written = self.gpu.cmd.control;
+                ne!(written.as_mut_bytes());
+                self.gpu.cmd.write_control(written);
                 if self.gpu.cmd.control.linked_mode() {
                     gx::cmd::sync_to_pi(self);
                 }
+
+                gx::cmd::consume(self);
+                pi::interrupt_written(self);
             }
             Mmio::CpClear => {
                 let mut written = 0;
                 ne!(written.as_mut_bytes());
                 self.gpu.cmd.write_clear(written);
+                pi::interrupt_written(self);
             }
             Mmio::CpFifoStartLow => {
                 ne!(self.gpu.cmd.fifo.start.as_mut_bytes()[0..2]);
@@ -426,12 +502,21 @@ impl System {
                 ne!(self.gpu.cmd.fifo.read_ptr.as_mut_bytes()[2..4]);
                 gx::cmd::consume(self);
             }
+            Mmio::CpFifoBreakpointLow => {
+                ne!(self.gpu.cmd.fifo.breakpoint.as_mut_bytes()[0..2]);
+                gx::cmd::consume(self);
+            }
+            Mmio::CpFifoBreakpointHigh => {
+                ne!(self.gpu.cmd.fifo.breakpoint.as_mut_bytes()[2..4]);
+                gx::cmd::consume(self);
+            }
 
             // === Pixel Engine ===
             Mmio::PixelInterruptStatus => {
                 let mut written = 0;
                 ne!(written.as_mut_bytes());
                 self.gpu.pix.write_interrupt(written);
+                pi::interrupt_written(self);
             }
 
             // === Video Interface ===
@@ -459,21 +544,25 @@ impl System {
                 let mut written = self.video.interrupts[0];
                 ne!(written.as_mut_bytes());
                 self.video.write_interrupt::<0>(written);
+                pi::interrupt_written(self);
             }
             Mmio::VideoDisplayInterrupt1 => {
                 let mut written = self.video.interrupts[1];
                 ne!(written.as_mut_bytes());
                 self.video.write_interrupt::<1>(written);
+                pi::interrupt_written(self);
             }
             Mmio::VideoDisplayInterrupt2 => {
                 let mut written = self.video.interrupts[2];
                 ne!(written.as_mut_bytes());
                 self.video.write_interrupt::<2>(written);
+                pi::interrupt_written(self);
             }
             Mmio::VideoDisplayInterrupt3 => {
                 let mut written = self.video.interrupts[3];
                 ne!(written.as_mut_bytes());
                 self.video.write_interrupt::<3>(written);
+                pi::interrupt_written(self);
             }
 
             Mmio::VideoExternalFramebufferWidth => {
@@ -496,9 +585,14 @@ impl System {
 
             // === Processor Interface ===
             // Interrupts
+            Mmio::ProcessorInterruptCause => {
+                let mut value = 0u32;
+                ne!(value.as_mut_bytes());
+                pi::write_interrupt_cause(self, value);
+            }
             Mmio::ProcessorInterruptMask => {
                 ne!(self.processor.mask.as_mut_bytes());
-                self.scheduler.schedule_now(pi::check_interrupts);
+                pi::interrupt_written(self);
             }
 
             // FIFO
@@ -524,18 +618,25 @@ impl System {
                 } else {
                     self.dsp.cpu_mailbox.set_status(status);
                 }
+
+                self.dsp.kick = true;
             }
             Mmio::DspRecvMailbox => panic!("shouldnt be writing to recv mailbox"),
             Mmio::DspControl => {
                 let mut written = self.dsp.control;
                 ne!(written.as_mut_bytes());
                 dspi::write_control(self, written);
+                self.dsp.kick = true;
             }
             Mmio::DspAramDmaRamBase => ne!(self.dsp.aram_dma.ram_base.as_mut_bytes()),
             Mmio::DspAramDmaAramBase => ne!(self.dsp.aram_dma.aram_base.as_mut_bytes()),
             Mmio::DspAramDmaControl => {
                 ne!(self.dsp.aram_dma.control.as_mut_bytes());
-                self.scheduler.schedule(10000, dspi::aram_dma);
+
+                // the transfer starts once the low half of the length is written
+                if range_overlap(mmio_range, 0..2) {
+                    dspi::start_aram_dma(self);
+                }
             }
             Mmio::AudioDmaBase => ne!(self.audio.dma_base.as_mut_bytes()),
             Mmio::AudioDmaControl => {
@@ -554,13 +655,16 @@ impl System {
                 ne!(written.as_mut_bytes());
                 self.disk.write_status(written);
                 tracing::debug!(diskstatus = ?self.disk.status);
+                if written.break_request() {
+                    di::request_break(self);
+                }
+
                 self.scheduler.schedule_now(pi::check_interrupts);
             }
             Mmio::DiskCover => {
                 let mut written = di::Cover::from_bits(0);
                 ne!(written.as_mut_bytes());
                 self.disk.write_cover(written);
-                self.disk.cover.set_open(false);
                 tracing::debug!(diskcover = ?self.disk.cover);
                 self.scheduler.schedule_now(pi::check_interrupts);
             }
@@ -617,12 +721,7 @@ impl System {
             Mmio::ExiChannel0Param => {
                 let mut written = exi::Parameter::from_bits(0);
                 ne!(written.as_mut_bytes());
-                self.external.channel0.parameter.write(written);
-
-                // TODO: move this to exi
-                if self.external.channel0.parameter.device_select().value() == 0 {
-                    self.external.channel0.ipl_state = exi::IplChipState::Idle;
-                }
+                self.external.write_parameter(0, written);
             }
             Mmio::ExiChannel0DmaBase => ne!(self.external.channel0.dma_base.as_mut_bytes()),
             Mmio::ExiChannel0DmaLength => ne!(self.external.channel0.dma_length.as_mut_bytes()),
@@ -634,7 +733,7 @@ impl System {
             Mmio::ExiChannel1Param => {
                 let mut written = exi::Parameter::from_bits(0);
                 ne!(written.as_mut_bytes());
-                self.external.channel1.parameter.write(written);
+                self.external.write_parameter(1, written);
             }
             Mmio::ExiChannel1DmaBase => ne!(self.external.channel1.dma_base.as_mut_bytes()),
             Mmio::ExiChannel1DmaLength => ne!(self.external.channel1.dma_length.as_mut_bytes()),
@@ -646,7 +745,7 @@ impl System {
             Mmio::ExiChannel2Param => {
                 let mut written = exi::Parameter::from_bits(0);
                 ne!(written.as_mut_bytes());
-                self.external.channel2.parameter.write(written);
+                self.external.write_parameter(2, written);
             }
             Mmio::ExiChannel2DmaBase => ne!(self.external.channel2.dma_base.as_mut_bytes()),
             Mmio::ExiChannel2DmaLength => ne!(self.external.channel2.dma_length.as_mut_bytes()),
@@ -659,10 +758,18 @@ impl System {
             // === Audio Interface ===
             Mmio::AudioControl => {
                 let already_playing = self.audio.control.playing();
+                let dsp_sample_rate = self.audio.control.dsp_sample_rate();
                 let mut written = self.audio.control;
                 ne!(written.as_mut_bytes());
                 self.audio.write_control(written);
 
+                // DMA blocks are rescheduled with the current rate, but the module must know too
+                if self.audio.control.dsp_sample_rate() != dsp_sample_rate {
+                    self.modules
+                        .audio
+                        .set_sample_rate(self.audio.control.dsp_sample_rate());
+                }
+
                 if !already_playing && self.audio.control.playing() {
                     ai::start_streaming(self);
                 } else if !self.audio.control.playing() {
@@ -675,7 +782,9 @@ impl System {
             Mmio::FakeStdout => {
                 let mut written = 0u8;
                 ne!(written.as_mut_bytes());
-                print!("{}", written as char);
+                if let Some(line) = self.debug_output.write(written) {
+                    println!("{line}");
+                }
             }
 
             // === PI FIFO ===
@@ -690,7 +799,10 @@ impl System {
         map! {
             offset, addr;
             0x0C00_0000, 0xFFFF => self.write_mmio(addr.value() as u16, value),
-            0x0000_0000, RAM_LEN => value.write_be_bytes(&mut self.mem.ram_mut()[offset..]),
+            0x0000_0000, RAM_LEN => {
+                let range = offset..offset + size_of::<P>();
+                value.write_be_bytes(self.mem.ram_range_mut(range));
+            },
             0xE000_0000, L2C_LEN => value.write_be_bytes(&mut self.mem.l2c_mut()[offset..]),
             0xFFF0_0000, IPL_LEN / 2 => tracing::warn!("bus write to IPL"),
             @default => {
@@ -702,13 +814,10 @@ impl System {
 
     /// Writes a primitive to the given logical address.
     #[inline(always)]
-    pub fn write_slow<P: Primitive>(&mut self, addr: Address, value: P) -> bool {
-        if let Some(addr) = self.translate_data_addr(addr) {
-            self.write_phys_slow(addr, value);
-            true
-        } else {
-            false
-        }
+    pub fn write_slow<P: Primitive>(&mut self, addr: Address, value: P) -> Result<(), Fault> {
+        let addr = self.translate_data_access(addr, true)?;
+        self.write_phys_slow(addr, value);
+        Ok(())
     }
 
     /// Writes a primitive to the given logical address using fastmem, if possible.
@@ -720,7 +829,7 @@ impl System {
         };
 
         let page = addr.value() >> 17;
-        let base = lut[page as usize];
+        let base = lut.stores[page as usize];
 
         if let Some(base) = base {
             let offset = addr.value().bits(0, 17) as usize;
@@ -736,6 +845,6 @@ impl System {
     /// falling back to slowmem if not possible.
     #[inline(always)]
     pub fn write<P: Primitive>(&mut self, addr: Address, value: P) -> bool {
-        self.write_fast(addr, value) || self.write_slow(addr, value)
+        self.write_fast(addr, value) || self.write_slow(addr, value).is_ok()
     }
 }
@@ -19,7 +19,7 @@ macro_rules! mmio {
         impl Mmio {
             #[inline(always)]
             pub fn address(self) -> Address {
-                Address(0x0C00_0000 | (self as u32 & 0xFFFF))
+                Address(crate::system::mem::MMIO_START | (self as u32 & 0xFFFF))
             }
 
             #[inline(always)]
@@ -68,6 +68,10 @@ mmio! {
     // === Pixel Engine ===
     0x100A, 2, PixelInterruptStatus;
     0x100E, 2, PixelToken;
+    0x1010, 2, PixelBoundingBoxLeft;
+    0x1012, 2, PixelBoundingBoxRight;
+    0x1014, 2, PixelBoundingBoxTop;
+    0x1016, 2, PixelBoundingBoxBottom;
 
     // === Video Interface ===
     0x2000, 2, VideoVerticalTiming;
@@ -0,0 +1,115 @@
+//! The debug output port at `0xCC00_7000`, which the IPL HLE (and OSReport, through it) writes text
+//! to a byte at a time.
+use std::collections::VecDeque;
+
+/// How many lines are kept, after which the oldest ones are dropped.
+pub const MAX_LINES: usize = 4096;
+
+/// Text written to the debug output port, split into lines.
+#[derive(Debug, Default)]
+pub struct DebugOutput {
+    /// The line being written.
+    line: Vec<u8>,
+    /// The last [`MAX_LINES`] complete lines.
+    lines: VecDeque<String>,
+    /// How many lines were completed so far, including dropped ones.
+    total: u64,
+}
+
+impl DebugOutput {
+    /// Writes a byte to the output. Returns the line it completes, if any.
+    pub fn write(&mut self, byte: u8) -> Option<&str> {
+        match byte {
+            b'\r' => None,
+            b'\n' => {
+                let line = String::from_utf8_lossy(&self.line).into_owned();
+                self.line.clear();
+
+                if self.lines.len() == MAX_LINES {
+                    self.lines.pop_front();
+                }
+
+                self.lines.push_back(line);
+                self.total += 1;
+                self.lines.back().map(String::as_str)
+            }
+            _ => {
+                self.line.push(byte);
+                None
+            }
+        }
+    }
+
+    /// The kept complete lines, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    /// How many lines were completed so far, including the ones no longer kept.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The kept lines completed after the first `seen` ones, i.e. the new lines since
+    /// [`total`](Self::total) was `seen`.
+    pub fn lines_since(&self, seen: u64) -> impl Iterator<Item = &str> {
+        let first_kept = self.total - self.lines.len() as u64;
+        let skip = seen.saturating_sub(first_kept).min(self.lines.len() as u64);
+        self.lines().skip(skip as usize)
+    }
+
+    /// The line being written, which is not complete yet.
+    pub fn pending(&self) -> &[u8] {
+        &self.line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(output: &mut DebugOutput, text: &str) -> Vec<String> {
+        text.bytes()
+            .filter_map(|b| output.write(b).map(str::to_owned))
+            .collect()
+    }
+
+    #[test]
+    fn line_buffering() {
+        let mut output = DebugOutput::default();
+
+        assert!(write(&mut output, "hello").is_empty());
+        assert_eq!(output.pending(), b"hello");
+
+        assert_eq!(
+            write(&mut output, " world\r\nsecond\n"),
+            ["hello world", "second"]
+        );
+        assert!(output.pending().is_empty());
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            ["hello world", "second"]
+        );
+        assert_eq!(output.total(), 2);
+
+        write(&mut output, "third\n");
+        assert_eq!(output.lines_since(2).collect::<Vec<_>>(), ["third"]);
+        assert_eq!(output.lines_since(3).count(), 0);
+    }
+
+    #[test]
+    fn oldest_lines_are_dropped() {
+        let mut output = DebugOutput::default();
+        for i in 0..MAX_LINES + 2 {
+            write(&mut output, &format!("{i}\n"));
+        }
+
+        assert_eq!(output.lines().count(), MAX_LINES);
+        assert_eq!(output.lines().next(), Some("2"));
+        assert_eq!(output.lines_since(0).next(), Some("2"));
+        assert_eq!(
+            output.lines_since(MAX_LINES as u64 + 1).collect::<Vec<_>>(),
+            [(MAX_LINES + 1).to_string()]
+        );
+    }
+}
@@ -7,6 +7,8 @@ use strum::FromRepr;
 
 use crate::system::{System, pi};
 
+const SECTOR_LEN: u32 = 2048;
+
 #[bitos(32)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Status {
@@ -28,7 +30,7 @@ pub struct Status {
 
 impl Status {
     pub fn any_interrupt(&self) -> bool {
-        let device_err = self.device_err_interrupt() && self.device_err_interrupt();
+        let device_err = self.device_err_interrupt() && self.device_err_interrupt_mask();
         let transfer = self.transfer_interrupt() && self.transfer_interrupt_mask();
         let break_ = self.break_interrupt() && self.break_interrupt_mask();
         device_err || transfer || break_
@@ -64,6 +66,48 @@ pub struct Cover {
     pub interrupt: bool,
 }
 
+/// State of the drive, reported in the upper byte of a request error reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DriveStatus {
+    Ready        = 0x00,
+    CoverOpened  = 0x01,
+    DiscChanged  = 0x02,
+    NoDisc       = 0x03,
+    MotorStopped = 0x04,
+}
+
+/// Last error of the drive, reported in the lower 24 bits of a request error reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DriveError {
+    #[default]
+    None,
+    /// A command needing the disc was issued with the motor stopped.
+    MotorStopped,
+    /// A command needing the disc was issued with the cover open or without a disc.
+    MediumNotPresent,
+    /// A sector could not be read. `offset` is where the read started.
+    UnrecoveredRead { offset: u32 },
+    /// A read went past the end of the disc. `offset` is where the read started.
+    OutOfRange { offset: u32 },
+    /// A command needing the disc was issued after it was changed, without resetting the drive.
+    MediumChanged,
+}
+
+impl DriveError {
+    /// The code of this error, as returned by the drive.
+    pub fn code(self) -> u32 {
+        match self {
+            Self::None => 0x00_0000,
+            Self::MotorStopped => 0x02_0400,
+            Self::MediumNotPresent => 0x02_3A00,
+            Self::UnrecoveredRead { .. } => 0x03_1100,
+            Self::OutOfRange { .. } => 0x05_2100,
+            Self::MediumChanged => 0x06_2800,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
 #[repr(u8)]
 pub enum Opcode {
@@ -111,6 +155,12 @@ pub struct Interface {
     pub cover: Cover,
     pub config: u32,
     pub immediate: u32,
+    /// The last error, cleared once requested.
+    pub error: DriveError,
+    /// Whether the cover has been opened since the last drive reset.
+    pub disc_changed: bool,
+    /// Whether the motor has been stopped since the last drive reset.
+    pub motor_stopped: bool,
 }
 
 impl Interface {
@@ -132,6 +182,11 @@ impl Interface {
             .set_break_interrupt(self.status.break_interrupt() & !value.break_interrupt());
     }
 
+    pub fn any_interrupt(&self) -> bool {
+        let cover = self.cover.interrupt() && self.cover.interrupt_mask();
+        self.status.any_interrupt() || cover
+    }
+
     pub fn write_cover(&mut self, value: Cover) {
         self.cover.set_interrupt_mask(value.interrupt_mask());
         self.cover
@@ -179,6 +234,69 @@ impl Interface {
     }
 }
 
+/// Returns the current state of the drive.
+pub fn drive_status(sys: &System) -> DriveStatus {
+    if sys.disk.cover.open() {
+        DriveStatus::CoverOpened
+    } else if !sys.modules.disk.has_disk() {
+        DriveStatus::NoDisc
+    } else if sys.disk.disc_changed {
+        DriveStatus::DiscChanged
+    } else if sys.disk.motor_stopped {
+        DriveStatus::MotorStopped
+    } else {
+        DriveStatus::Ready
+    }
+}
+
+/// Returns the error a command needing the disc fails with in the current drive state, if any.
+fn disc_access_error(sys: &System) -> Option<DriveError> {
+    match drive_status(sys) {
+        DriveStatus::Ready => None,
+        DriveStatus::CoverOpened | DriveStatus::NoDisc => Some(DriveError::MediumNotPresent),
+        DriveStatus::DiscChanged => Some(DriveError::MediumChanged),
+        DriveStatus::MotorStopped => Some(DriveError::MotorStopped),
+    }
+}
+
+/// Ends the ongoing command with the given error, raising a device error interrupt.
+fn fail_command(sys: &mut System, error: DriveError) {
+    tracing::warn!("DI command failed: {error:?}");
+    sys.disk.error = error;
+    sys.disk.status.set_device_err_interrupt(true);
+    sys.disk.control.set_transfer_ongoing(false);
+    pi::check_interrupts(sys);
+}
+
+/// Opens or closes the drive cover, as done by the user. Opening it counts as changing the disc,
+/// so commands needing it fail until the drive is reset.
+pub fn set_cover_open(sys: &mut System, open: bool) {
+    if sys.disk.cover.open() == open {
+        return;
+    }
+
+    tracing::info!(open, "DVD cover state changed");
+    sys.disk.cover.set_open(open);
+    sys.disk.cover.set_interrupt(true);
+    if open {
+        sys.disk.disc_changed = true;
+    }
+
+    pi::check_interrupts(sys);
+}
+
+/// Handles a break request, aborting the ongoing command.
+pub fn request_break(sys: &mut System) {
+    tracing::debug!("DI break requested");
+    sys.scheduler.cancel(complete_transfer);
+    sys.scheduler.cancel(complete_seek);
+
+    sys.disk.control.set_transfer_ongoing(false);
+    sys.disk.status.set_break_request(false);
+    sys.disk.status.set_break_interrupt(true);
+    pi::check_interrupts(sys);
+}
+
 pub fn complete_transfer(sys: &mut System) {
     tracing::debug!("completed DI transfer");
     sys.disk.status.set_transfer_interrupt(true);
@@ -210,13 +328,15 @@ pub fn write_control(sys: &mut System, value: Control) {
                 let length = sys.disk.dma_length;
                 assert_eq!(length, 32);
 
-                sys.mem.ram_mut()[target.value() as usize..][..12].copy_from_slice(&[
+                let target = target.value() as usize;
+                let output = sys.mem.ram_range_mut(target..target + 32);
+                output[..12].copy_from_slice(&[
                     0x00, 0x00, 0x00, 0x00, // zeros
                     0x20, 0x02, 0x04, 0x02, // date
                     0x61, 0x00, 0x00, 0x00, // version
                 ]);
 
-                sys.mem.ram_mut()[target.value() as usize + 12..][..32 - 12].fill(0);
+                output[12..].fill(0);
                 sys.scheduler.schedule(10000, complete_transfer);
             }
             Command::Read { offset, length } => {
@@ -236,32 +356,59 @@ pub fn write_control(sys: &mut System, value: Control) {
                     "reading 0x{length:08X} bytes from disk at 0x{offset:08X} into {target}"
                 );
 
-                let target = target.value().with_bit(31, false);
-                let slice = &mut sys.mem.ram_mut()[target as usize..][..length as usize];
+                if let Some(error) = disc_access_error(sys) {
+                    fail_command(sys, error);
+                    return;
+                }
+
+                let target = target.value().with_bit(31, false) as usize;
+                let slice = sys.mem.ram_range_mut(target..target + length as usize);
+
+                let disk = &mut sys.modules.disk;
+                let result = disk
+                    .seek(SeekFrom::Start(offset as u64))
+                    .and_then(|_| disk.read_exact(slice));
 
-                if !sys.modules.disk.has_disk() {
-                    tracing::error!("tried to read from disk but no disk is inserted");
-                    slice.fill(0);
-                } else {
-                    let new = sys
-                        .modules
-                        .disk
-                        .seek(SeekFrom::Start(offset as u64))
-                        .unwrap();
+                if let Err(e) = result {
+                    tracing::error!(
+                        "failed to read disk sector 0x{:X} (offset 0x{offset:08X}): {e}",
+                        offset / SECTOR_LEN
+                    );
 
-                    assert_eq!(new, offset as u64);
+                    let error = if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                        DriveError::OutOfRange { offset }
+                    } else {
+                        DriveError::UnrecoveredRead { offset }
+                    };
 
-                    sys.modules.disk.read_exact(slice).unwrap();
+                    fail_command(sys, error);
+                    return;
                 }
 
                 sys.scheduler.schedule(10000, complete_transfer);
             }
             Command::Seek { .. } => {
+                if let Some(error) = disc_access_error(sys) {
+                    fail_command(sys, error);
+                    return;
+                }
+
                 tracing::warn!("stubbed DVD command - disk seek");
                 sys.scheduler.schedule(5000, complete_seek);
             }
+            Command::Status => {
+                let status = drive_status(sys);
+                let error = std::mem::take(&mut sys.disk.error);
+                tracing::debug!(?status, ?error, "DI error requested");
+
+                sys.disk.immediate = ((status as u32) << 24) | error.code();
+                sys.disk.status.set_transfer_interrupt(true);
+                sys.disk.control.set_transfer_ongoing(false);
+                pi::check_interrupts(sys);
+            }
             Command::StopMotor => {
-                tracing::warn!("stubbed DVD command - stop motor");
+                tracing::debug!("DVD motor stopped");
+                sys.disk.motor_stopped = true;
                 sys.disk.status.set_transfer_interrupt(true);
                 sys.disk.control.set_transfer_ongoing(false);
                 sys.disk.immediate = 0;
@@ -308,5 +455,200 @@ pub fn reset(sys: &mut System, value: u32) {
     }
 
     tracing::warn!("dvd drive reset through processor interface");
+    sys.scheduler.cancel(complete_transfer);
+    sys.scheduler.cancel(complete_seek);
+
+    // the cover is not part of the drive, and with it open the disc can still change
+    let cover_open = sys.disk.cover.open();
     sys.disk = Default::default();
+    sys.disk.cover.set_open(cover_open);
+    sys.disk.disc_changed = cover_open;
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Seek};
+
+    use gekko::Cycles;
+
+    use super::*;
+    use crate::modules::disk::{DiskModule, NopDiskModule};
+    use crate::system::{BootMode, Config, Modules};
+
+    const DI_STATUS: u32 = 0x0C00_6000;
+    const DI_COVER: u32 = 0x0C00_6004;
+    const DI_IMMEDIATE: u32 = 0x0C00_6020;
+    const PI_DVD_RESET: u32 = 0x0C00_3024;
+
+    const DISC_LEN: u64 = 0x10000;
+    /// Offset from which reads of the test disc fail, as if it was scratched.
+    const DAMAGED: u64 = 0x8000;
+
+    struct TestDisk(Cursor<Vec<u8>>);
+
+    impl TestDisk {
+        fn boxed() -> Box<Self> {
+            Box::new(Self(Cursor::new(vec![0xAB; DISC_LEN as usize])))
+        }
+    }
+
+    impl Read for TestDisk {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let position = self.0.position();
+            if position < DISC_LEN && position + buf.len() as u64 > DAMAGED {
+                return Err(std::io::Error::other("damaged sector"));
+            }
+
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for TestDisk {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    impl DiskModule for TestDisk {
+        fn has_disk(&self) -> bool {
+            true
+        }
+    }
+
+    fn system() -> System {
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut sys = System::new(Modules::nop(), config).unwrap();
+        sys.modules.disk = TestDisk::boxed();
+        sys
+    }
+
+    /// Starts a command, with DMA into `0x1000`.
+    fn start(sys: &mut System, command: [u32; 3]) {
+        for (i, word) in command.into_iter().enumerate() {
+            sys.write_phys_slow(Address(0x0C00_6008 + 4 * i as u32), word);
+        }
+
+        sys.write_phys_slow(Address(0x0C00_6014), 0x1000u32);
+        sys.write_phys_slow(Address(0x0C00_6018), 0x20u32);
+        sys.write_phys_slow(Address(0x0C00_601C), 0b011u32);
+    }
+
+    /// Runs a command to completion and returns whether it raised the device error and transfer
+    /// complete interrupts, acknowledging them.
+    fn command(sys: &mut System, command: [u32; 3]) -> (bool, bool) {
+        start(sys, command);
+        if sys.disk.control.transfer_ongoing() {
+            sys.advance_by(Cycles(10_000));
+        }
+
+        let status = Status::from_bits(sys.read_phys_slow::<u32>(Address(DI_STATUS)));
+        sys.write_phys_slow(Address(DI_STATUS), 0b1_0100u32);
+
+        (status.device_err_interrupt(), status.transfer_interrupt())
+    }
+
+    fn read(sys: &mut System, offset: u32) -> (bool, bool) {
+        command(sys, [0xA800_0000, offset >> 2, 0x20])
+    }
+
+    /// Requests the drive status and error, as a game would after a device error interrupt.
+    fn request_error(sys: &mut System) -> u32 {
+        assert_eq!(command(sys, [0xE000_0000, 0, 0]), (false, true));
+        sys.read_phys_slow(Address(DI_IMMEDIATE))
+    }
+
+    #[test]
+    fn cover_and_disc_changes() {
+        let mut sys = system();
+        assert_eq!(request_error(&mut sys), 0x0000_0000);
+        assert_eq!(read(&mut sys, 0), (false, true));
+        assert_eq!(sys.read_phys_slow::<u32>(Address(0x1000)), 0xABAB_ABAB);
+
+        // opening the cover raises its interrupt and makes reads fail
+        sys.write_phys_slow(Address(DI_COVER), 0b010u32);
+        set_cover_open(&mut sys, true);
+        assert_eq!(sys.read_phys_slow::<u32>(Address(DI_COVER)), 0b111);
+        assert!(sys.disk.any_interrupt());
+        sys.write_phys_slow(Address(DI_COVER), 0b110u32);
+        assert!(!sys.disk.any_interrupt());
+
+        assert_eq!(request_error(&mut sys), 0x0100_0000);
+        assert_eq!(read(&mut sys, 0), (true, false));
+        assert_eq!(request_error(&mut sys), 0x0102_3A00);
+        assert_eq!(request_error(&mut sys), 0x0100_0000);
+
+        // closing it without a disc
+        sys.modules.disk = Box::new(NopDiskModule);
+        set_cover_open(&mut sys, false);
+        assert_eq!(sys.read_phys_slow::<u32>(Address(DI_COVER)), 0b110);
+        assert_eq!(request_error(&mut sys), 0x0300_0000);
+        assert_eq!(read(&mut sys, 0), (true, false));
+        assert_eq!(request_error(&mut sys), 0x0302_3A00);
+
+        // inserting one, which needs a reset before it can be read
+        sys.modules.disk = TestDisk::boxed();
+        assert_eq!(request_error(&mut sys), 0x0200_0000);
+        assert_eq!(read(&mut sys, 0), (true, false));
+        assert_eq!(request_error(&mut sys), 0x0206_2800);
+
+        sys.write_phys_slow(Address(PI_DVD_RESET), 0b100u32);
+        assert_eq!(request_error(&mut sys), 0x0000_0000);
+        assert_eq!(read(&mut sys, 0), (false, true));
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut sys = system();
+
+        assert_eq!(read(&mut sys, DAMAGED as u32), (true, false));
+        assert_eq!(
+            sys.disk.error,
+            DriveError::UnrecoveredRead {
+                offset: DAMAGED as u32
+            }
+        );
+        assert_eq!(request_error(&mut sys), 0x0003_1100);
+
+        assert_eq!(read(&mut sys, DISC_LEN as u32), (true, false));
+        assert_eq!(request_error(&mut sys), 0x0005_2100);
+
+        // errors don't stick around
+        assert_eq!(request_error(&mut sys), 0x0000_0000);
+        assert_eq!(read(&mut sys, 0), (false, true));
+    }
+
+    #[test]
+    fn stopped_motor_until_reset() {
+        let mut sys = system();
+
+        assert_eq!(command(&mut sys, [0xE300_0000, 0, 0]), (false, true));
+        assert_eq!(request_error(&mut sys), 0x0400_0000);
+        assert_eq!(read(&mut sys, 0), (true, false));
+        assert_eq!(request_error(&mut sys), 0x0402_0400);
+
+        sys.write_phys_slow(Address(PI_DVD_RESET), 0b100u32);
+        assert_eq!(request_error(&mut sys), 0x0000_0000);
+    }
+
+    #[test]
+    fn break_aborts_command() {
+        let mut sys = system();
+
+        start(&mut sys, [0xA800_0000, 0, 0x20]);
+        sys.write_phys_slow(Address(DI_STATUS), 0b1u32);
+
+        let status = Status::from_bits(sys.read_phys_slow::<u32>(Address(DI_STATUS)));
+        assert!(status.break_interrupt());
+        assert!(!status.break_request());
+        assert!(!sys.disk.control.transfer_ongoing());
+        assert!(!sys.scheduler.contains(complete_transfer));
+    }
 }
@@ -4,12 +4,15 @@ use bitos::{BitUtils, bitos};
 use gekko::Address;
 use util::boxed_array;
 
-use crate::system::System;
+use crate::system::{System, pi};
 
 pub const ARAM_LEN: usize = 16 * bytesize::MIB as usize;
 
+/// How many CPU cycles an ARAM DMA takes to transfer a 32 byte block.
+const ARAM_DMA_CYCLES_PER_BLOCK: u64 = 246;
+
 #[bitos(32)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Mailbox {
     #[bits(0..16)]
     pub low: u16,
@@ -45,8 +48,9 @@ pub struct Control {
     pub dsp_interrupt: bool,
     #[bits(8)]
     pub dsp_interrupt_mask: bool,
+    /// Whether a DMA is in progress, either an ARAM DMA or a DSP DMA.
     #[bits(9)]
-    pub dsp_dma_ongoing: bool,
+    pub dma_ongoing: bool,
     #[bits(10)]
     pub unknown: bool,
     #[bits(11)]
@@ -89,6 +93,8 @@ pub struct AramDma {
     pub ram_base: Address,
     pub aram_base: u32,
     pub control: AramDmaControl,
+    /// Whether the transfer is due but was deferred because ARAM was lent.
+    pub deferred: bool,
 }
 
 #[bitos(1)]
@@ -116,7 +122,7 @@ pub struct DspDmaControl {
     pub transfer_ongoing: bool,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DspDma {
     pub ram_base: u32,
     pub dsp_base: u16,
@@ -124,7 +130,11 @@ pub struct DspDma {
     pub control: DspDmaControl,
 }
 
-pub struct Dsp {
+/// State of the DSP interface, shared by the CPU and the DSP.
+///
+/// Other than the ARAM DMA registers, this is everything the DSP has access to outside of its own
+/// memories: the control register, the mailboxes, the DSP DMA registers and ARAM.
+pub struct DspIo {
     pub control: Control,
     /// Data from DSP to CPU
     pub dsp_mailbox: Mailbox,
@@ -133,9 +143,16 @@ pub struct Dsp {
     pub dsp_dma: DspDma,
     pub aram_dma: AramDma,
     pub aram: Box<[u8; ARAM_LEN]>,
+    /// Whether ARAM is currently lent to a DSP running on another thread. ARAM DMAs are deferred
+    /// until it is given back.
+    pub aram_lent: bool,
+    /// Whether the CPU wrote to a register the DSP is likely waiting on (the CPU mailbox or the
+    /// control register). CPU cores should return as soon as possible when this is set, so that
+    /// the DSP gets to see the write before its next regular step.
+    pub kick: bool,
 }
 
-impl Dsp {
+impl DspIo {
     pub fn new() -> Self {
         Self {
             control: Default::default(),
@@ -144,6 +161,8 @@ impl Dsp {
             dsp_dma: Default::default(),
             aram_dma: Default::default(),
             aram: boxed_array(0),
+            aram_lent: false,
+            kick: false,
         }
     }
 }
@@ -181,35 +200,81 @@ pub fn write_control(sys: &mut System, value: Control) {
     sys.dsp.control.set_reset_high(value.reset_high());
 }
 
-/// Performs the ARAM DMA if length is not zero.
+/// Starts the ARAM DMA described by the ARAM DMA registers. The transfer itself happens once it
+/// would have completed.
+pub fn start_aram_dma(sys: &mut System) {
+    let length = sys.dsp.aram_dma.control.length().value() as u64;
+    if length == 0 {
+        return;
+    }
+
+    sys.dsp.control.set_dma_ongoing(true);
+    sys.dsp.aram_dma.deferred = false;
+    sys.scheduler.cancel(aram_dma);
+    sys.scheduler
+        .schedule(length.div_ceil(32) * ARAM_DMA_CYCLES_PER_BLOCK, aram_dma);
+}
+
+/// Performs the ARAM DMA if it was deferred because ARAM was lent.
+pub fn deferred_aram_dma(sys: &mut System) {
+    if std::mem::take(&mut sys.dsp.aram_dma.deferred) {
+        aram_dma(sys);
+    }
+}
+
+/// Performs the ARAM DMA, raising the ARAM interrupt once it's done. If ARAM is lent, the transfer
+/// is deferred until it's given back.
 pub fn aram_dma(sys: &mut System) {
-    let length = sys.dsp.aram_dma.control.length().value() as usize;
-    if length != 0 {
-        let ram_base = sys.dsp.aram_dma.ram_base.value().with_bits(26, 32, 0);
-        let aram_base = sys.dsp.aram_dma.aram_base & 0x00FF_FFFF;
-
-        match sys.dsp.aram_dma.control.direction() {
-            AramDmaDirection::FromRamToAram => {
-                tracing::debug!(
-                    "ARAM DMA {length} bytes from RAM {} to ARAM {aram_base:08X}",
-                    Address(ram_base)
-                );
-
-                let aram = &mut sys.dsp.aram[aram_base as usize..][..length];
-                aram.copy_from_slice(&sys.mem.ram()[ram_base as usize..][..length]);
-            }
-            AramDmaDirection::FromAramToRam => {
-                tracing::debug!(
-                    "ARAM DMA {length} bytes from ARAM {aram_base:08X} to RAM {}",
-                    Address(ram_base)
-                );
-
-                sys.mem.ram_mut()[ram_base as usize..][..length]
-                    .copy_from_slice(&sys.dsp.aram[aram_base as usize..][..length]);
-            }
+    if sys.dsp.aram_lent {
+        sys.dsp.aram_dma.deferred = true;
+        return;
+    }
+
+    // transfers are done in 32 byte blocks
+    let length = sys.dsp.aram_dma.control.length().value() as usize & !31;
+    if length == 0 {
+        return;
+    }
+
+    let ram_base = sys.dsp.aram_dma.ram_base.value().with_bits(26, 32, 0) as usize & !31;
+    let aram_base = sys.dsp.aram_dma.aram_base as usize & 0x00FF_FFE0;
+
+    let ram_len = sys.mem.ram().len();
+    let clamped = length
+        .min(ram_len.saturating_sub(ram_base))
+        .min(ARAM_LEN - aram_base);
+
+    if clamped != length {
+        tracing::warn!(
+            "ARAM DMA of {length} bytes between RAM {} and ARAM {aram_base:08X} is out of bounds",
+            Address(ram_base as u32)
+        );
+    }
+
+    match sys.dsp.aram_dma.control.direction() {
+        AramDmaDirection::FromRamToAram => {
+            tracing::debug!(
+                "ARAM DMA {length} bytes from RAM {} to ARAM {aram_base:08X}",
+                Address(ram_base as u32)
+            );
+
+            let aram = &mut sys.dsp.aram[aram_base..][..clamped];
+            aram.copy_from_slice(&sys.mem.ram()[ram_base..][..clamped]);
         }
+        AramDmaDirection::FromAramToRam => {
+            tracing::debug!(
+                "ARAM DMA {length} bytes from ARAM {aram_base:08X} to RAM {}",
+                Address(ram_base as u32)
+            );
 
-        sys.dsp.aram_dma.control.set_length(u31::new(0));
-        sys.dsp.control.set_aram_interrupt(true);
+            sys.mem
+                .ram_range_mut(ram_base..ram_base + clamped)
+                .copy_from_slice(&sys.dsp.aram[aram_base..][..clamped]);
+        }
     }
+
+    sys.dsp.aram_dma.control.set_length(u31::new(0));
+    sys.dsp.control.set_dma_ongoing(false);
+    sys.dsp.control.set_aram_interrupt(true);
+    pi::check_interrupts(sys);
 }
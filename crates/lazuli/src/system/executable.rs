@@ -1,8 +1,8 @@
+use std::fs::File;
 use std::path::Path;
 
-use disks::binrw::BinRead;
 use disks::binrw::io::BufReader;
-use disks::dol::Dol;
+use disks::dol::{self, Dol, DolRef, ElfToDolError, Header};
 use easyerr::{Error, ResultExt};
 
 #[derive(Debug, Error)]
@@ -11,18 +11,41 @@ pub enum OpenError {
     UnknownFormat,
     #[error(transparent)]
     Io { source: std::io::Error },
+    #[error("failed to parse dol")]
+    Dol { source: disks::binrw::Error },
+    #[error("failed to convert elf to dol")]
+    Elf { source: ElfToDolError },
 }
 
 pub enum Executable {
+    /// A .dol in memory, e.g. converted from an `.elf`.
     Dol(Dol),
+    /// A .dol file, whose sections are read from it whenever it's loaded.
+    DolFile(DolRef<BufReader<File>>),
 }
 
 impl Executable {
+    /// Opens an executable. Supported formats are `.dol` and `.elf`, which is converted to a
+    /// `.dol`.
     pub fn open(exec: &Path) -> Result<Self, OpenError> {
-        let exec_file = std::fs::File::open(exec).context(OpenCtx::Io)?;
-        Ok(match exec.extension().and_then(|s| s.to_str()) {
-            Some("dol") => Executable::Dol(Dol::read(&mut BufReader::new(exec_file)).unwrap()),
+        let extension = exec
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase());
+
+        let exec_file = File::open(exec).context(OpenCtx::Io)?;
+        let reader = BufReader::new(exec_file);
+        Ok(match extension.as_deref() {
+            Some("dol") => Executable::DolFile(DolRef::read(reader).context(OpenCtx::Dol)?),
+            Some("elf") => Executable::Dol(dol::elf_to_dol(reader).context(OpenCtx::Elf)?),
             _ => return Err(OpenError::UnknownFormat),
         })
     }
+
+    pub fn header(&self) -> &Header {
+        match self {
+            Executable::Dol(dol) => &dol.header,
+            Executable::DolFile(dol) => &dol.header,
+        }
+    }
 }
@@ -1,31 +1,70 @@
 //! External interface (EXI).
-use std::io::Write;
+//!
+//! The EXI controller has three channels, each of which can select one of up to three devices
+//! through the chip select bits of its parameter register. Devices are [`ExiDevice`]s plugged
+//! into [`Slot`]s, and the controller itself knows nothing about them. It drives them as follows:
+//!
+//! - When the chip select bits of a channel change, the previously selected device (if any) is
+//!   [deselected](ExiDevice::deselect) and the newly selected one (if any) is
+//!   [selected](ExiDevice::select). A device usually resets its command state on deselection.
+//! - A transfer started through the control register is handed to the selected device as a single
+//!   [`transfer`](ExiDevice::transfer) call. EXI is full duplex: for every byte sent to the device,
+//!   one byte is received from it.
+//!   - Immediate transfers send the upper `imm_length` bytes of the immediate register (in big
+//!     endian order). For reads, the bytes received are stored in the upper bytes of the immediate
+//!     register, and the remaining bytes are cleared.
+//!   - DMA transfers send `dma_length` bytes of RAM starting at `dma_base` when writing. When
+//!     reading, the bytes received are stored at that same location.
+//!   - For reads, the bytes sent are zero.
+//! - A transfer with no device selected, or with an empty slot selected, receives zeros.
+pub mod ad16;
+pub mod gecko;
+pub mod memcard;
+pub mod rtc;
 
 use bitos::bitos;
 use bitos::integer::{u2, u3};
 use gekko::Address;
-use util::boxed_array;
 
-use crate::Primitive;
 use crate::system::System;
 
-pub const SRAM_LEN: usize = 64;
+/// A device which can be plugged into an EXI slot.
+pub trait ExiDevice: Send {
+    /// Called when the device is selected by its channel.
+    fn select(&mut self) {}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Device0 {
-    MemoryCardA,
-    IplRtcSram,
-    SerialPort1,
+    /// Called when the device is deselected by its channel.
+    fn deselect(&mut self) {}
+
+    /// Called before every transfer with how many CPU cycles have elapsed since power-on, for
+    /// devices which keep time.
+    fn tick(&mut self, elapsed: u64) {
+        _ = elapsed;
+    }
+
+    /// Transfers the given bytes to the device, returning the bytes it sent back. The returned
+    /// bytes must have the same length as the given ones.
+    fn transfer(&mut self, bytes: &[u8]) -> Vec<u8>;
 }
 
+/// A device slot, i.e. a channel and a chip select line within it.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Device1 {
-    MemoryCardB,
+pub struct Slot {
+    pub channel: usize,
+    pub device: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Device2 {
-    AD16,
+impl Slot {
+    pub const MEMORY_CARD_A: Self = Self::new(0, 0);
+    pub const IPL_RTC_SRAM: Self = Self::new(0, 1);
+    pub const SERIAL_PORT_1: Self = Self::new(0, 2);
+    pub const MEMORY_CARD_B: Self = Self::new(1, 0);
+    pub const AD16: Self = Self::new(2, 0);
+
+    pub const fn new(channel: usize, device: usize) -> Self {
+        assert!(channel < 3 && device < 3);
+        Self { channel, device }
+    }
 }
 
 #[bitos(32)]
@@ -65,25 +104,12 @@ impl Parameter {
         self.set_attach_interrupt(self.attach_interrupt() & !value.attach_interrupt());
     }
 
-    pub fn device0(&self) -> Option<Device0> {
+    /// The index of the selected device, if exactly one is selected.
+    pub fn selected(&self) -> Option<usize> {
         Some(match self.device_select().value() {
-            0b001 => Device0::MemoryCardA,
-            0b010 => Device0::IplRtcSram,
-            0b100 => Device0::SerialPort1,
-            _ => return None,
-        })
-    }
-
-    pub fn device1(&self) -> Option<Device1> {
-        Some(match self.device_select().value() {
-            0b001 => Device1::MemoryCardB,
-            _ => return None,
-        })
-    }
-
-    pub fn device2(&self) -> Option<Device2> {
-        Some(match self.device_select().value() {
-            0b001 => Device2::AD16,
+            0b001 => 0,
+            0b010 => 1,
+            0b100 => 2,
             _ => return None,
         })
     }
@@ -117,38 +143,9 @@ impl Control {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub enum IplChipState {
-    #[default]
-    Idle,
-    SramWrite(u8),
-    UartWrite,
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct Channel0 {
-    pub rtc: u32,
-    pub ipl_base: u32,
-    pub ipl_state: IplChipState,
-
-    pub parameter: Parameter,
-    pub control: Control,
-    pub dma_base: Address,
-    pub dma_length: u32,
-    pub immediate: u32,
-}
-
-#[derive(Default, Debug, Clone)]
-pub struct Channel1 {
-    pub parameter: Parameter,
-    pub control: Control,
-    pub dma_base: Address,
-    pub dma_length: u32,
-    pub immediate: u32,
-}
-
+/// Registers of an EXI channel.
 #[derive(Default, Debug, Clone)]
-pub struct Channel2 {
+pub struct Channel {
     pub parameter: Parameter,
     pub control: Control,
     pub dma_base: Address,
@@ -157,213 +154,195 @@ pub struct Channel2 {
 }
 
 pub struct Interface {
-    pub sram: Box<[u8; SRAM_LEN]>,
-    pub channel0: Channel0,
-    pub channel1: Channel0,
-    pub channel2: Channel0,
+    pub channel0: Channel,
+    pub channel1: Channel,
+    pub channel2: Channel,
+    slots: [[Option<Box<dyn ExiDevice>>; 3]; 3],
 }
 
 impl Interface {
-    pub fn new() -> Self {
-        Self {
-            sram: boxed_array(0),
+    /// Creates the interface with the devices which are always present on the console, i.e. the
+    /// IPL ROM/RTC/SRAM chip, with its RTC at `rtc`, and the AD16. Memory card slots start empty.
+    pub fn new(ipl: &[u8], rtc: u32) -> Self {
+        let mut interface = Self {
             channel0: Default::default(),
             channel1: Default::default(),
             channel2: Default::default(),
-        }
+            slots: Default::default(),
+        };
+
+        interface.plug(
+            Slot::IPL_RTC_SRAM,
+            Box::new(rtc::IplRtcSram::new(ipl.into(), rtc)),
+        );
+        interface.plug(Slot::AD16, Box::new(ad16::Ad16::default()));
+        interface
     }
-}
 
-fn ipl_transfer(sys: &mut System) {
-    if !sys.external.channel0.control.dma() {
-        sys.external.channel0.ipl_base = sys.external.channel0.immediate >> 6;
-        tracing::debug!("set IPL base to 0x{:08X}", sys.external.channel0.ipl_base);
-        return;
+    pub fn channel(&self, index: usize) -> &Channel {
+        match index {
+            0 => &self.channel0,
+            1 => &self.channel1,
+            2 => &self.channel2,
+            _ => unreachable!(),
+        }
     }
 
-    let ram_base = sys.external.channel0.dma_base.value() as usize;
-    let ipl_base = sys.external.channel0.ipl_base as usize;
-    let length = sys.external.channel0.dma_length as usize;
-    tracing::debug!(
-        "IPL ROM DMA: 0x{:08X} bytes from IPL 0x{:08X} to RAM 0x{:08X}",
-        length,
-        ipl_base,
-        ram_base
-    );
-
-    let regions = sys.mem.regions();
-    regions.ram[ram_base..][..length].copy_from_slice(&regions.ipl[ipl_base..][..length]);
-}
-
-fn update_sram_checksum(sys: &mut System) {
-    let mut c1 = 0u16;
-    let mut c2 = 0u16;
-    sys.external.sram[0x13] = 0x2C;
-
-    for i in 0..4 {
-        let word = u16::read_be_bytes(&sys.external.sram[0xC + 2 * i..]);
-        c1 = c1.wrapping_add(word);
-        c2 = c2.wrapping_add(word ^ 0xFFFF);
+    pub fn channel_mut(&mut self, index: usize) -> &mut Channel {
+        match index {
+            0 => &mut self.channel0,
+            1 => &mut self.channel1,
+            2 => &mut self.channel2,
+            _ => unreachable!(),
+        }
     }
 
-    c1.write_be_bytes(&mut sys.external.sram[0..2]);
-    c2.write_be_bytes(&mut sys.external.sram[2..4]);
-}
-
-fn sram_transfer_read(sys: &mut System) {
-    self::update_sram_checksum(sys);
-
-    let sram_base =
-        (((sys.external.channel0.immediate & !0xA000_0000) - 0x0000_0100) >> 6) as usize;
-    tracing::debug!("SRAM TRANSFER {:?}", sys.external.channel0.control);
+    /// Plugs a device into the given slot, returning the device that was previously plugged in,
+    /// if any.
+    pub fn plug(&mut self, slot: Slot, device: Box<dyn ExiDevice>) -> Option<Box<dyn ExiDevice>> {
+        let previous = self.unplug(slot);
+        self.slots[slot.channel][slot.device] = Some(device);
+        self.update_connected();
+
+        if self.channel(slot.channel).parameter.selected() == Some(slot.device) {
+            self.slots[slot.channel][slot.device]
+                .as_mut()
+                .unwrap()
+                .select();
+        }
 
-    if !sys.external.channel0.control.dma() {
-        return;
+        previous
     }
 
-    let ram_base = sys.external.channel0.dma_base.value() as usize;
-    let length = sys.external.channel0.dma_length as usize;
-    tracing::debug!(
-        "SRAM DMA: 0x{:08X} bytes from SRAM 0x{:08X} to RAM 0x{:08X}",
-        length,
-        sram_base,
-        ram_base
-    );
-
-    sys.mem.ram_mut()[ram_base..][..length]
-        .copy_from_slice(&sys.external.sram[sram_base..][..length]);
-}
-
-fn sram_transfer_write(sys: &mut System, current: u8) {
-    assert!(!sys.external.channel0.control.dma());
-
-    sys.external
-        .channel0
-        .immediate
-        .write_be_bytes(&mut sys.external.sram[current as usize..]);
+    /// Unplugs the device in the given slot, if any.
+    pub fn unplug(&mut self, slot: Slot) -> Option<Box<dyn ExiDevice>> {
+        let mut device = self.slots[slot.channel][slot.device].take()?;
+        if self.channel(slot.channel).parameter.selected() == Some(slot.device) {
+            device.deselect();
+        }
 
-    let next = current + 4;
-    if next == 64 {
-        sys.external.channel0.ipl_state = IplChipState::Idle;
-    } else {
-        sys.external.channel0.ipl_state = IplChipState::SramWrite(next);
+        self.update_connected();
+        Some(device)
     }
-}
 
-fn uart_transfer_write(sys: &mut System) {
-    assert!(!sys.external.channel0.control.dma());
-    let value = sys.external.channel0.immediate;
-
-    for byte in value.to_be_bytes() {
-        if byte == 0x1B {
-            continue;
+    /// Resets the registers of every channel, deselecting any selected device. Plugged devices are
+    /// kept.
+    pub fn reset(&mut self) {
+        for index in 0..3 {
+            self.write_parameter(index, Parameter::default());
+            *self.channel_mut(index) = Channel::default();
         }
 
-        print!("{}", char::from_u32(byte as u32).unwrap());
-        if byte == b'\r' {
-            println!();
-        }
+        self.update_connected();
     }
 
-    std::io::stdout().flush().unwrap();
-}
-
-fn uart_transfer_read(sys: &mut System) {
-    if !sys.external.channel0.control.dma() {
-        sys.external.channel0.immediate = 0;
-        return;
-    }
+    /// Writes to the parameter register of a channel, selecting and deselecting devices as needed.
+    pub fn write_parameter(&mut self, index: usize, value: Parameter) {
+        let previous = self.channel(index).parameter.selected();
+        self.channel_mut(index).parameter.write(value);
+        let current = self.channel(index).parameter.selected();
 
-    let ram_base = sys.external.channel0.dma_base.value() as usize;
-    let length = sys.external.channel0.dma_length as usize;
-    tracing::debug!(
-        "UART DMA: 0x{:08X} bytes from UART to RAM 0x{:08X}",
-        length,
-        ram_base
-    );
+        if previous == current {
+            return;
+        }
 
-    sys.mem.ram_mut()[ram_base..][..length].fill(0);
-}
+        if let Some(device) = previous.and_then(|d| self.slots[index][d].as_mut()) {
+            device.deselect();
+        }
 
-fn ipl_rtc_sram_transfer(sys: &mut System) {
-    match sys.external.channel0.clone().ipl_state {
-        IplChipState::SramWrite(current) => self::sram_transfer_write(sys, current),
-        IplChipState::UartWrite => self::uart_transfer_write(sys),
-        IplChipState::Idle => {
-            // new transfer
-            match sys.external.channel0.clone().immediate {
-                0x0000_0000..0x2000_0000 => self::ipl_transfer(sys),
-                0x2000_0000 => {
-                    tracing::debug!("RTC read: 0x{:08X}", sys.external.channel0.rtc);
-                    assert!(!sys.external.channel0.control.dma());
-                    sys.external.channel0.immediate = sys.external.channel0.rtc;
-                }
-                0x2000_0100..0x2000_1100 => self::sram_transfer_read(sys),
-                0x2001_0000 => self::uart_transfer_read(sys),
-                0xA000_0000 => {
-                    tracing::debug!("RTC write: 0x{:08X}", sys.external.channel0.immediate);
-                    assert!(!sys.external.channel0.control.dma());
-                    sys.external.channel0.rtc = sys.external.channel0.immediate;
-                }
-                0xA000_0100..0xA000_1100 => {
-                    let sram_base = (((sys.external.channel0.immediate & !0xA000_0000)
-                        - 0x0000_0100)
-                        >> 6) as u8;
-                    tracing::debug!("starting SRAM write: 0x{:08X}", sram_base);
-                    assert!(!sys.external.channel0.control.dma());
-
-                    sys.external.channel0.ipl_state = IplChipState::SramWrite(sram_base);
-                }
-                0xA001_0000 => {
-                    tracing::debug!("starting EXI UART write");
-                    sys.external.channel0.ipl_state = IplChipState::UartWrite;
-                }
-                _ => todo!("{:08X}", sys.external.channel0.immediate),
-            }
+        if let Some(device) = current.and_then(|d| self.slots[index][d].as_mut()) {
+            device.select();
         }
     }
 
-    sys.external.channel0.control.set_transfer_ongoing(false);
-}
-
-pub fn channel0_transfer(sys: &mut System) {
-    match sys.external.channel0.parameter.device0().unwrap() {
-        Device0::IplRtcSram => {
-            self::ipl_rtc_sram_transfer(sys);
-        }
-        Device0::SerialPort1 => {
-            // no ethernet adapter
-            tracing::debug!("SP1 read - ignoring");
-            sys.external.channel0.immediate = 0;
-            sys.external.channel0.control.set_transfer_ongoing(false);
+    /// Updates the connected bit of the channels with an external slot (i.e. memory card slots).
+    fn update_connected(&mut self) {
+        for index in 0..2 {
+            let connected = self.slots[index][0].is_some();
+            self.channel_mut(index)
+                .parameter
+                .set_device_connected(connected);
         }
-        _ => todo!(),
     }
 }
 
-pub fn channel2_transfer(sys: &mut System) {
-    assert_eq!(
-        sys.external.channel2.parameter.device2(),
-        Some(Device2::AD16)
-    );
+fn transfer(sys: &mut System, index: usize) {
+    let channel = sys.external.channel(index).clone();
+    let control = channel.control;
+    let (read, write) = match control.transfer_mode() {
+        TransferMode::Read => (true, false),
+        TransferMode::Write => (false, true),
+        TransferMode::ReadWrite => (true, true),
+        TransferMode::Reserved => {
+            tracing::warn!("reserved EXI transfer mode on channel {index}");
+            sys.external
+                .channel_mut(index)
+                .control
+                .set_transfer_ongoing(false);
+            return;
+        }
+    };
 
-    let op = sys.external.channel2.immediate;
-    if op == 0 {
-        tracing::warn!("checking AD16 ID");
-        sys.external.channel2.immediate = 0x0412_0000;
+    let length = if control.dma() {
+        channel.dma_length as usize
     } else {
-        tracing::warn!("unknown AD16 op");
+        control.imm_length() as usize
+    };
+
+    let input = match (write, control.dma()) {
+        (false, _) => vec![0; length],
+        (true, false) => channel.immediate.to_be_bytes()[..length].to_vec(),
+        (true, true) => {
+            let base = channel.dma_base.value() as usize;
+            sys.mem.ram()[base..][..length].to_vec()
+        }
+    };
+
+    let elapsed = sys.scheduler.elapsed();
+    let device = channel
+        .parameter
+        .selected()
+        .and_then(|d| sys.external.slots[index][d].as_mut());
+
+    let output = match device {
+        Some(device) => {
+            device.tick(elapsed);
+            device.transfer(&input)
+        }
+        None => {
+            tracing::debug!(
+                "EXI transfer on channel {index} with no device ({:?})",
+                channel.parameter.device_select()
+            );
+            vec![0; length]
+        }
+    };
+
+    assert_eq!(output.len(), length);
+
+    if read {
+        if control.dma() {
+            let base = channel.dma_base.value() as usize;
+            sys.mem
+                .ram_range_mut(base..base + length)
+                .copy_from_slice(&output);
+        } else {
+            let mut immediate = [0; 4];
+            immediate[..length].copy_from_slice(&output);
+            sys.external.channel_mut(index).immediate = u32::from_be_bytes(immediate);
+        }
     }
 
-    sys.external.channel2.control.set_transfer_ongoing(false);
+    sys.external
+        .channel_mut(index)
+        .control
+        .set_transfer_ongoing(false);
 }
 
 pub fn update(sys: &mut System) {
-    if sys.external.channel0.control.transfer_ongoing() {
-        self::channel0_transfer(sys);
-    }
-
-    if sys.external.channel2.control.transfer_ongoing() {
-        self::channel2_transfer(sys);
+    for index in 0..3 {
+        if sys.external.channel(index).control.transfer_ongoing() {
+            self::transfer(sys, index);
+        }
     }
 }
@@ -0,0 +1,58 @@
+//! The AD16, a debugging register on channel 2 device 0.
+//!
+//! The first byte of an access is a command: `0x00` reads the device ID (after a dummy byte),
+//! `0xA0` writes the 32 bit register and `0xA2` reads it.
+use crate::system::exi::ExiDevice;
+
+const ID: u32 = 0x0412_0000;
+
+#[derive(Debug, Default)]
+pub struct Ad16 {
+    register: u32,
+    command: Option<u8>,
+    position: usize,
+}
+
+impl Ad16 {
+    fn data(&mut self, command: u8, byte: u8) -> u8 {
+        let position = self.position;
+        self.position += 1;
+
+        match (command, position) {
+            (0x00, 1..=4) => ID.to_be_bytes()[position - 1],
+            (0xA0, 0..=3) => {
+                let mut register = self.register.to_be_bytes();
+                register[position] = byte;
+                self.register = u32::from_be_bytes(register);
+                tracing::debug!("AD16 write: 0x{:08X}", self.register);
+                0
+            }
+            (0xA2, 0..=3) => self.register.to_be_bytes()[position],
+            _ => 0,
+        }
+    }
+}
+
+impl ExiDevice for Ad16 {
+    fn deselect(&mut self) {
+        self.command = None;
+        self.position = 0;
+    }
+
+    fn transfer(&mut self, bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .map(|&byte| match self.command {
+                Some(command) => self.data(command, byte),
+                None => {
+                    if !matches!(byte, 0x00 | 0xA0 | 0xA2) {
+                        tracing::warn!("unknown AD16 command 0x{byte:02X}");
+                    }
+
+                    self.command = Some(byte);
+                    0
+                }
+            })
+            .collect()
+    }
+}
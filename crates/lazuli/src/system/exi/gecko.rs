@@ -0,0 +1,153 @@
+//! The USB Gecko, a USB serial adapter plugged into a memory card slot, which homebrew uses as a
+//! debug console. Bytes sent by the guest go to the [`GuestConsole`] output, and the guest reads
+//! the console input.
+//!
+//! Accesses are 16 bit commands, whose top nibble is the command:
+//!
+//! - `0x7`/`0x8`: turn the LED off/on
+//! - `0x9`: read the device ID, `0x0470`
+//! - `0xA`: receive a byte. Answers `0x08nn` with byte `nn`, or zero if there's no input
+//! - `0xB`: send the byte in bits 4 to 11. Answers `0x0400`
+//! - `0xC`: check whether a byte can be sent. Answers `0x0400`, since it always can
+//! - `0xD`: check whether there's a byte to receive. Answers `0x0400` if there is, zero otherwise
+use crate::system::exi::ExiDevice;
+use crate::system::guest_console::GuestConsole;
+
+const ID: u16 = 0x0470;
+const ACK: u16 = 0x0400;
+const RECEIVED: u16 = 0x0800;
+
+pub struct UsbGecko {
+    console: GuestConsole,
+    command: Option<u8>,
+    /// Answer to the current command.
+    answer: u16,
+    position: usize,
+}
+
+impl UsbGecko {
+    pub fn new(console: GuestConsole) -> Self {
+        Self {
+            console,
+            command: None,
+            answer: 0,
+            position: 0,
+        }
+    }
+
+    /// Starts a command, given the first byte of it, returning the answer to it.
+    fn start(&mut self, command: u8) -> u16 {
+        match command >> 4 {
+            0x7 | 0x8 => 0,
+            0x9 => ID,
+            0xA => self
+                .console
+                .read_input()
+                .map_or(0, |byte| RECEIVED | byte as u16),
+            0xB | 0xC => ACK,
+            0xD if self.console.has_input() => ACK,
+            0xD => 0,
+            _ => {
+                tracing::warn!("unknown USB Gecko command 0x{command:02X}");
+                0
+            }
+        }
+    }
+}
+
+impl ExiDevice for UsbGecko {
+    fn deselect(&mut self) {
+        self.command = None;
+        self.answer = 0;
+        self.position = 0;
+    }
+
+    fn transfer(&mut self, bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .map(|&byte| {
+                let position = self.position;
+                self.position += 1;
+
+                let command = match self.command {
+                    Some(command) => command,
+                    None => {
+                        self.command = Some(byte);
+                        self.answer = self.start(byte);
+                        byte
+                    }
+                };
+
+                if position == 1 && command >> 4 == 0xB {
+                    self.console.write(&[command << 4 | byte >> 4]);
+                }
+
+                self.answer
+                    .to_be_bytes()
+                    .get(position)
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(gecko: &mut UsbGecko, command: u16) -> u16 {
+        gecko.select();
+        let output = gecko.transfer(&command.to_be_bytes());
+        gecko.deselect();
+        u16::from_be_bytes([output[0], output[1]])
+    }
+
+    #[test]
+    fn identify_and_send() {
+        let console = GuestConsole::default();
+        let mut gecko = UsbGecko::new(console.clone());
+
+        assert_eq!(command(&mut gecko, 0x9000), ID);
+        assert_eq!(command(&mut gecko, 0xC000), ACK);
+
+        for &byte in b"hi\n" {
+            assert_eq!(command(&mut gecko, 0xB000 | (byte as u16) << 4), ACK);
+        }
+
+        assert_eq!(console.take_output(), b"hi\n");
+        assert!(console.take_output().is_empty());
+    }
+
+    #[test]
+    fn receive() {
+        let console = GuestConsole::default();
+        let mut gecko = UsbGecko::new(console.clone());
+
+        assert_eq!(command(&mut gecko, 0xD000), 0);
+        assert_eq!(command(&mut gecko, 0xA000), 0);
+
+        console.send_input(b"ls");
+        assert_eq!(command(&mut gecko, 0xD000), ACK);
+        assert_eq!(command(&mut gecko, 0xA000), RECEIVED | b'l' as u16);
+        assert_eq!(command(&mut gecko, 0xA000), RECEIVED | b's' as u16);
+        assert_eq!(command(&mut gecko, 0xD000), 0);
+    }
+
+    #[test]
+    fn commands_span_transfers() {
+        let console = GuestConsole::default();
+        let mut gecko = UsbGecko::new(console.clone());
+
+        // a 32 bit transfer, with the command in the upper half, sent a byte at a time
+        gecko.select();
+        let mut output = Vec::new();
+        for byte in (0xB000_0000_u32 | (b'x' as u32) << 20).to_be_bytes() {
+            output.extend(gecko.transfer(&[byte]));
+        }
+        gecko.deselect();
+
+        assert_eq!(output, [0x04, 0x00, 0x00, 0x00]);
+        assert_eq!(console.take_output(), b"x");
+    }
+}
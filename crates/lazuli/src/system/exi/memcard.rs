@@ -0,0 +1,188 @@
+//! Memory cards, on channel 0 device 0 (slot A) and channel 1 device 0 (slot B).
+//!
+//! The first byte of an access is a command, followed by its arguments and data. Addresses are
+//! sent as 4 bytes, `aaaaaaa bbbbbbbb 000000cc 0ddddddd`, which encode `a << 17 | b << 9 | c << 7 |
+//! d`. Sector erases only send the first 2 bytes. Supported commands are:
+//!
+//! - `0x00`: read the device ID, after a dummy byte
+//! - `0x52`: read data, after the address and 4 bytes of latency
+//! - `0x83`: read the status register
+//! - `0x89`: clear the error bits of the status register
+//! - `0xF1`: erase a sector
+//! - `0xF2`: program a page, after the address (the offset wraps around within the page)
+//! - `0xF4`: erase the whole card, after 2 dummy bytes
+//!
+//! Erasing and programming complete immediately, so the card is never busy.
+use crate::system::exi::ExiDevice;
+
+pub const SECTOR_LEN: usize = 0x2000;
+pub const PAGE_LEN: usize = 0x80;
+
+const READ_LATENCY: usize = 4;
+
+const STATUS_READY: u8 = 1 << 0;
+const STATUS_UNLOCKED: u8 = 1 << 6;
+const STATUS_ERRORS: u8 = 0b0001_1000;
+
+pub struct MemoryCard {
+    data: Vec<u8>,
+    status: u8,
+    command: Option<u8>,
+    args: Vec<u8>,
+    position: usize,
+}
+
+impl MemoryCard {
+    /// Creates a memory card with the given contents. The length must be a whole number of
+    /// megabits.
+    pub fn new(data: Vec<u8>) -> Self {
+        assert!(!data.is_empty() && data.len().is_multiple_of(1 << 17));
+
+        Self {
+            data,
+            status: STATUS_READY | STATUS_UNLOCKED,
+            command: None,
+            args: Vec::with_capacity(4),
+            position: 0,
+        }
+    }
+
+    /// Creates an erased memory card with the given size in megabits (e.g. 4 for a 59 block card).
+    pub fn blank(size_mbits: usize) -> Self {
+        Self::new(vec![0xFF; size_mbits << 17])
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn size_mbits(&self) -> u32 {
+        (self.data.len() >> 17) as u32
+    }
+
+    fn address(&self) -> usize {
+        let arg = |i: usize| self.args.get(i).copied().unwrap_or(0) as usize;
+        let address =
+            ((arg(0) & 0x7F) << 17) | (arg(1) << 9) | ((arg(2) & 0x03) << 7) | (arg(3) & 0x7F);
+
+        address % self.data.len()
+    }
+
+    fn arg_count(command: u8) -> usize {
+        match command {
+            0x52 | 0xF2 => 4,
+            0xF1 | 0xF4 => 2,
+            _ => 0,
+        }
+    }
+
+    fn execute(&mut self, command: u8) {
+        match command {
+            0x89 => self.status &= !STATUS_ERRORS,
+            0xF1 => {
+                let start = self.address() & !(SECTOR_LEN - 1);
+                tracing::debug!("memory card sector erase at 0x{start:06X}");
+                self.data[start..][..SECTOR_LEN].fill(0xFF);
+            }
+            0xF4 => {
+                tracing::debug!("memory card erase");
+                self.data.fill(0xFF);
+            }
+            0x00 | 0x52 | 0x83 | 0xF2 => (),
+            0x81 | 0x87 | 0x88 => tracing::debug!("ignoring memory card command 0x{command:02X}"),
+            _ => tracing::warn!("unknown memory card command 0x{command:02X}"),
+        }
+    }
+
+    fn data_byte(&mut self, command: u8, byte: u8) -> u8 {
+        let position = self.position;
+        self.position += 1;
+
+        match command {
+            0x00 => match position {
+                1..=4 => self.size_mbits().to_be_bytes()[position - 1],
+                _ => 0,
+            },
+            0x52 => {
+                let Some(offset) = position.checked_sub(READ_LATENCY) else {
+                    return 0;
+                };
+
+                self.data[(self.address() + offset) % self.data.len()]
+            }
+            0x83 => self.status,
+            0xF2 => {
+                let address = self.address();
+                let page = address & !(PAGE_LEN - 1);
+                let offset = (address + position) % PAGE_LEN;
+                self.data[page + offset] = byte;
+                0
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl ExiDevice for MemoryCard {
+    fn deselect(&mut self) {
+        self.command = None;
+        self.args.clear();
+        self.position = 0;
+    }
+
+    fn transfer(&mut self, bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .map(|&byte| {
+                let Some(command) = self.command else {
+                    self.command = Some(byte);
+                    if Self::arg_count(byte) == 0 {
+                        self.execute(byte);
+                    }
+
+                    return 0;
+                };
+
+                if self.args.len() < Self::arg_count(command) {
+                    self.args.push(byte);
+                    if self.args.len() == Self::arg_count(command) {
+                        self.execute(command);
+                    }
+
+                    return 0;
+                }
+
+                self.data_byte(command, byte)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn access(card: &mut MemoryCard, bytes: &[u8]) -> Vec<u8> {
+        card.select();
+        let output = card.transfer(bytes);
+        card.deselect();
+        output
+    }
+
+    #[test]
+    fn program_and_read() {
+        let mut card = MemoryCard::blank(4);
+        assert_eq!(access(&mut card, &[0, 0, 0, 0, 0, 0])[2..], [0, 0, 0, 4]);
+
+        // program 2 bytes at 0x2000 + 0x7F, wrapping around within the page
+        access(&mut card, &[0xF2, 0x00, 0x10, 0x00, 0x7F, 0x12, 0x34]);
+        assert_eq!(card.data()[0x207F], 0x12);
+        assert_eq!(card.data()[0x2000], 0x34);
+
+        let read = access(&mut card, &[0x52, 0x00, 0x10, 0x00, 0x00, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(read[9..], [0x34, 0xFF]);
+
+        access(&mut card, &[0xF1, 0x00, 0x10]);
+        assert!(card.data()[0x2000..0x4000].iter().all(|&b| b == 0xFF));
+    }
+}
@@ -0,0 +1,281 @@
+//! The IPL ROM, RTC and SRAM chip, on channel 0 device 1.
+//!
+//! Every access starts with a 4 byte command, where the upper bit selects a write and the
+//! remaining bits, shifted right by 6, select the address. Data follows the command, with the
+//! address increasing by one for every byte transferred. The address space is:
+//!
+//! - `0x0000_0000..0x0080_0000`: IPL ROM (read only)
+//! - `0x0080_0000..0x0080_0004`: RTC counter, in seconds, which advances with emulated time
+//! - `0x0080_0004..0x0080_0044`: SRAM
+//! - `0x0080_0400`: UART (the address does not increase)
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gekko::FREQUENCY;
+use util::boxed_array;
+
+use crate::Primitive;
+use crate::system::exi::ExiDevice;
+
+pub const SRAM_LEN: usize = 64;
+
+const RTC_START: u32 = 0x0080_0000;
+const SRAM_START: u32 = 0x0080_0004;
+const SRAM_END: u32 = SRAM_START + SRAM_LEN as u32;
+const UART: u32 = 0x0080_0400;
+
+/// Seconds between the Unix epoch and the RTC epoch, 2000-01-01.
+const RTC_EPOCH_OFFSET: u64 = 946_684_800;
+
+/// The host's current time as an RTC value, i.e. in seconds since 2000-01-01.
+pub fn host_rtc() -> u32 {
+    let unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    unix.saturating_sub(RTC_EPOCH_OFFSET) as u32
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Command { command: [u8; 4], received: usize },
+    Data { write: bool, address: u32 },
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::Command {
+            command: [0; 4],
+            received: 0,
+        }
+    }
+}
+
+pub struct IplRtcSram {
+    rom: Box<[u8]>,
+    sram: Box<[u8; SRAM_LEN]>,
+    rtc: u32,
+    /// Cycles elapsed since the RTC last advanced.
+    subsecond: u64,
+    /// Elapsed cycles the chip was last ticked with.
+    last_tick: u64,
+    state: State,
+}
+
+impl IplRtcSram {
+    /// Creates the chip with the given (decoded) IPL ROM contents, a cleared SRAM and the RTC at
+    /// `rtc`.
+    pub fn new(rom: Box<[u8]>, rtc: u32) -> Self {
+        let mut chip = Self {
+            rom,
+            sram: boxed_array(0),
+            rtc,
+            subsecond: 0,
+            last_tick: 0,
+            state: State::default(),
+        };
+
+        chip.update_sram_checksum();
+        chip
+    }
+
+    pub fn sram(&self) -> &[u8; SRAM_LEN] {
+        &self.sram
+    }
+
+    fn update_sram_checksum(&mut self) {
+        let mut c1 = 0u16;
+        let mut c2 = 0u16;
+        self.sram[0x13] = 0x2C;
+
+        for i in 0..4 {
+            let word = u16::read_be_bytes(&self.sram[0xC + 2 * i..]);
+            c1 = c1.wrapping_add(word);
+            c2 = c2.wrapping_add(word ^ 0xFFFF);
+        }
+
+        c1.write_be_bytes(&mut self.sram[0..2]);
+        c2.write_be_bytes(&mut self.sram[2..4]);
+    }
+
+    fn start(&mut self, command: u32) {
+        let write = command & 0x8000_0000 != 0;
+        let address = (command & 0x7FFF_FFFF) >> 6;
+
+        match address {
+            0..RTC_START => tracing::debug!("IPL ROM access at 0x{address:08X}"),
+            RTC_START..SRAM_START => tracing::debug!("RTC access (write: {write})"),
+            SRAM_START..SRAM_END => {
+                tracing::debug!(
+                    "SRAM access at 0x{:02X} (write: {write})",
+                    address - SRAM_START
+                );
+                if !write {
+                    self.update_sram_checksum();
+                }
+            }
+            UART => tracing::debug!("UART access (write: {write})"),
+            _ => tracing::warn!("unknown IPL/RTC/SRAM command 0x{command:08X}"),
+        }
+
+        self.state = State::Data { write, address };
+    }
+
+    fn data(&mut self, write: bool, address: u32, byte: u8) -> u8 {
+        match address {
+            0..RTC_START => {
+                if write {
+                    tracing::warn!("ignoring write to IPL ROM at 0x{address:08X}");
+                    return 0;
+                }
+
+                self.rom.get(address as usize).copied().unwrap_or(0)
+            }
+            RTC_START..SRAM_START => {
+                let mut rtc = self.rtc.to_be_bytes();
+                let offset = (address - RTC_START) as usize;
+                if write {
+                    rtc[offset] = byte;
+                    self.rtc = u32::from_be_bytes(rtc);
+                }
+
+                rtc[offset]
+            }
+            SRAM_START..SRAM_END => {
+                let offset = (address - SRAM_START) as usize;
+                if write {
+                    self.sram[offset] = byte;
+                }
+
+                self.sram[offset]
+            }
+            UART => {
+                if write && byte != 0x1B {
+                    print!("{}", char::from(byte));
+                    if byte == b'\r' {
+                        println!();
+                    }
+                }
+
+                0
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl ExiDevice for IplRtcSram {
+    fn deselect(&mut self) {
+        self.state = State::default();
+    }
+
+    fn tick(&mut self, elapsed: u64) {
+        // the cycle count starts over when the system is reset, but the RTC keeps going
+        let delta = elapsed.checked_sub(self.last_tick).unwrap_or(elapsed);
+        self.last_tick = elapsed;
+
+        self.subsecond += delta;
+        self.rtc = self.rtc.wrapping_add((self.subsecond / FREQUENCY) as u32);
+        self.subsecond %= FREQUENCY;
+    }
+
+    fn transfer(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(bytes.len());
+        for &byte in bytes {
+            match self.state {
+                State::Command {
+                    mut command,
+                    received,
+                } => {
+                    command[received] = byte;
+                    if received == 3 {
+                        self.start(u32::from_be_bytes(command));
+                    } else {
+                        self.state = State::Command {
+                            command,
+                            received: received + 1,
+                        };
+                    }
+
+                    output.push(0);
+                }
+                State::Data { write, address } => {
+                    output.push(self.data(write, address, byte));
+                    if address != UART {
+                        self.state = State::Data {
+                            write,
+                            address: address + 1,
+                        };
+                    }
+                }
+            }
+        }
+
+        if let State::Data { address: UART, .. } = self.state {
+            std::io::stdout().flush().unwrap();
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(chip: &mut IplRtcSram, command: u32) {
+        chip.deselect();
+        chip.select();
+        chip.transfer(&command.to_be_bytes());
+    }
+
+    #[test]
+    fn rom_read() {
+        let rom: Box<[u8]> = (0..=255).collect();
+        let mut chip = IplRtcSram::new(rom, 0);
+
+        command(&mut chip, 0x10 << 6);
+        assert_eq!(chip.transfer(&[0; 4]), [0x10, 0x11, 0x12, 0x13]);
+    }
+
+    #[test]
+    fn rtc_and_sram_round_trip() {
+        let mut chip = IplRtcSram::new(Box::new([]), 0);
+
+        command(&mut chip, 0xA000_0000);
+        chip.transfer(&0x1234_5678u32.to_be_bytes());
+        command(&mut chip, 0x2000_0000);
+        assert_eq!(chip.transfer(&[0; 4]), 0x1234_5678u32.to_be_bytes());
+
+        // writes to SRAM are split across transfers, and the command is reset on deselection
+        command(&mut chip, 0xA000_0100 + (0x0C << 6));
+        chip.transfer(&[0xAA, 0xBB]);
+        chip.transfer(&[0xCC, 0xDD]);
+        command(&mut chip, 0x2000_0100);
+
+        let sram = chip.transfer(&[0; SRAM_LEN]);
+        assert_eq!(&sram[0x0C..0x10], &[0xAA, 0xBB, 0xCC, 0xDD]);
+        assert_eq!(sram[0x13], 0x2C);
+        let c1 = 0xAABBu16.wrapping_add(0xCCDD).wrapping_add(0x002C);
+        assert_eq!(u16::from_be_bytes([sram[0], sram[1]]), c1);
+    }
+
+    #[test]
+    fn rtc_advances_with_emulated_time() {
+        fn read(chip: &mut IplRtcSram, elapsed: u64) -> u32 {
+            chip.tick(elapsed);
+            command(chip, 0x2000_0000);
+            u32::from_be_bytes(chip.transfer(&[0; 4]).try_into().unwrap())
+        }
+
+        let mut chip = IplRtcSram::new(Box::new([]), 100);
+        assert_eq!(read(&mut chip, 0), 100);
+        assert_eq!(read(&mut chip, FREQUENCY - 1), 100);
+        assert_eq!(read(&mut chip, FREQUENCY), 101);
+        assert_eq!(read(&mut chip, 3 * FREQUENCY + 1), 103);
+
+        // after a reset, the cycle count starts over but the RTC does not
+        assert_eq!(read(&mut chip, FREQUENCY), 104);
+    }
+}
@@ -0,0 +1,53 @@
+//! The guest console: text the guest writes through debug devices (e.g. a USB Gecko) for the user
+//! to read, and text the user types for the guest to read back.
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How many bytes of output are kept until taken, after which the oldest ones are dropped.
+pub const OUTPUT_CAPACITY: usize = 1 << 20;
+
+#[derive(Debug, Default)]
+struct Streams {
+    output: VecDeque<u8>,
+    input: VecDeque<u8>,
+}
+
+/// A handle to the guest console. Clones share the same streams, so that every device writing to
+/// it can own a handle.
+#[derive(Debug, Clone, Default)]
+pub struct GuestConsole(Arc<Mutex<Streams>>);
+
+impl GuestConsole {
+    fn streams(&self) -> MutexGuard<'_, Streams> {
+        self.0.lock().unwrap()
+    }
+
+    /// Appends bytes written by the guest to the output.
+    pub fn write(&self, bytes: &[u8]) {
+        let mut streams = self.streams();
+        streams.output.extend(bytes);
+
+        let excess = streams.output.len().saturating_sub(OUTPUT_CAPACITY);
+        streams.output.drain(..excess);
+    }
+
+    /// Takes the output written by the guest since the last call.
+    pub fn take_output(&self) -> Vec<u8> {
+        self.streams().output.drain(..).collect()
+    }
+
+    /// Queues bytes for the guest to read.
+    pub fn send_input(&self, bytes: &[u8]) {
+        self.streams().input.extend(bytes);
+    }
+
+    /// Takes the next byte of input, if any.
+    pub fn read_input(&self) -> Option<u8> {
+        self.streams().input.pop_front()
+    }
+
+    /// Whether there's input for the guest to read.
+    pub fn has_input(&self) -> bool {
+        !self.streams().input.is_empty()
+    }
+}
@@ -21,7 +21,8 @@ use zerocopy::IntoBytes;
 
 use crate::modules::{render, vertex};
 use crate::system::gx::cmd::VertexAttributeStream;
-use crate::system::pi;
+use crate::system::mem::RAM_LEN;
+use crate::system::{pi, vi};
 use crate::{Primitive, System};
 
 #[rustfmt::skip]
@@ -355,6 +356,24 @@ impl Reg {
         )
     }
 
+    #[inline]
+    pub fn is_fog(&self) -> bool {
+        matches!(
+            self,
+            Self::TevRangeAdjC
+                | Self::TevRangeAdj0
+                | Self::TevRangeAdj1
+                | Self::TevRangeAdj2
+                | Self::TevRangeAdj3
+                | Self::TevRangeAdj4
+                | Self::TevFog0
+                | Self::TevFog1
+                | Self::TevFog2
+                | Self::TevFog3
+                | Self::TevFogColor
+        )
+    }
+
     #[inline]
     pub fn is_pixel_clear(&self) -> bool {
         matches!(
@@ -404,6 +423,65 @@ pub struct GenMode {
     pub z_freeze: bool,
 }
 
+/// Offset added to the texture coordinates of the far corners of lines and points.
+#[bitos(3)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TexOffset {
+    #[default]
+    Zero      = 0b000,
+    Sixteenth = 0b001,
+    Eighth    = 0b010,
+    Quarter   = 0b011,
+    Half      = 0b100,
+    One       = 0b101,
+    Reserved0 = 0b110,
+    Reserved1 = 0b111,
+}
+
+impl TexOffset {
+    pub fn value(self) -> f32 {
+        match self {
+            Self::Zero => 0.0,
+            Self::Sixteenth => 1.0 / 16.0,
+            Self::Eighth => 1.0 / 8.0,
+            Self::Quarter => 1.0 / 4.0,
+            Self::Half => 1.0 / 2.0,
+            Self::One | Self::Reserved0 | Self::Reserved1 => 1.0,
+        }
+    }
+}
+
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LinePointSize {
+    /// Width of lines, in units of 1/16th of a pixel.
+    #[bits(0..8)]
+    pub line_width: u8,
+    /// Size of points, in units of 1/16th of a pixel.
+    #[bits(8..16)]
+    pub point_size: u8,
+    #[bits(16..19)]
+    pub line_tex_offset: TexOffset,
+    #[bits(19..22)]
+    pub point_tex_offset: TexOffset,
+    /// Whether lines are half as thick vertically, as when rendering a single field of an
+    /// interlaced frame.
+    #[bits(22)]
+    pub half_aspect: bool,
+}
+
+impl LinePointSize {
+    /// Width of lines, in pixels.
+    pub fn line_width_pixels(&self) -> f32 {
+        self.line_width() as f32 / 16.0
+    }
+
+    /// Size of points, in pixels.
+    pub fn point_size_pixels(&self) -> f32 {
+        self.point_size() as f32 / 16.0
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct MatrixId(u8);
 
@@ -464,6 +542,10 @@ impl MatrixId {
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
+    /// Only present for vertices with NBT normals, zero otherwise.
+    pub binormal: Vec3,
+    /// Only present for vertices with NBT normals, zero otherwise.
+    pub tangent: Vec3,
     pub pos_norm_matrix: MatrixId,
 
     pub chan0: Rgba,
@@ -480,6 +562,26 @@ pub struct VertexStream {
 }
 
 impl VertexStream {
+    /// Creates a stream from the given vertices and matrices.
+    pub fn new(vertices: Vec<Vertex>, matrices: Vec<(MatrixId, Mat4)>) -> Self {
+        let mut vertices_handle = alloc_vertices_handle(vertices.len());
+        let vertices_slice = unsafe { vertices_handle.as_mut_slice() };
+        for (slot, vertex) in vertices_slice.iter_mut().zip(vertices) {
+            slot.write(vertex);
+        }
+
+        let mut matrices_handle = alloc_matrices_handle(matrices.len());
+        let matrices_slice = unsafe { matrices_handle.as_mut_slice() };
+        for (slot, matrix) in matrices_slice.iter_mut().zip(matrices) {
+            slot.write(matrix);
+        }
+
+        Self {
+            vertices: vertices_handle,
+            matrices: matrices_handle,
+        }
+    }
+
     pub fn vertices(&self) -> &[Vertex] {
         // SAFETY: this struct is only created inside `extract_vertices`, which mantains
         // a static arena
@@ -527,6 +629,7 @@ impl MatrixSet {
 
 pub struct Gpu {
     pub mode: GenMode,
+    pub line_point: LinePointSize,
     pub cmd: cmd::Interface,
     pub xform: xform::Interface,
     pub env: tev::Interface,
@@ -540,6 +643,7 @@ impl Default for Gpu {
     fn default() -> Self {
         Self {
             mode: Default::default(),
+            line_point: Default::default(),
             cmd: Default::default(),
             xform: Default::default(),
             env: Default::default(),
@@ -590,6 +694,23 @@ pub fn update_texenv(sys: &mut System) {
         .exec(render::Action::SetTexEnvConfig(config));
 }
 
+pub fn update_line_point(sys: &mut System) {
+    let mut line_tex_offsets = 0;
+    let mut point_tex_offsets = 0;
+    for (i, map) in sys.gpu.tex.maps.iter().enumerate() {
+        line_tex_offsets |= (map.scaling.u.offset_lines() as u8) << i;
+        point_tex_offsets |= (map.scaling.u.offset_points() as u8) << i;
+    }
+
+    sys.modules
+        .render
+        .exec(render::Action::SetLinePoint(render::LinePoint {
+            size: sys.gpu.line_point,
+            line_tex_offsets,
+            point_tex_offsets,
+        }));
+}
+
 pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
     let mask = std::mem::replace(&mut sys.gpu.write_mask, 0x00FF_FFFF);
     let masked = value & mask;
@@ -615,6 +736,7 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
             sys.gpu.env.active_stages = mode.tev_stages_minus_one().value() + 1;
             sys.gpu.env.active_channels = mode.color_channels_count().value();
         }
+        Reg::SetupLpSize => write_masked!(sys.gpu.line_point),
 
         Reg::TevRefs01 => write_masked!(sys.gpu.env.stage_refs[0]),
         Reg::TevRefs23 => write_masked!(sys.gpu.env.stage_refs[1]),
@@ -666,6 +788,9 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
                 .exec(render::Action::SetFramebufferFormat(
                     sys.gpu.pix.control.format(),
                 ));
+            sys.modules.render.exec(render::Action::SetEarlyDepth(
+                sys.gpu.pix.control.depth_compress_before_tex(),
+            ));
         }
         Reg::PixelDone => {
             sys.gpu.pix.interrupt.set_finish(true);
@@ -677,6 +802,22 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
             sys.gpu.pix.interrupt.set_token(true);
             sys.scheduler.schedule_now(pi::check_interrupts);
         }
+        Reg::PixelXBound => {
+            write_masked!(sys.gpu.pix.x_bound);
+            sys.modules.render.exec(render::Action::SetBoundingBox {
+                vertical: false,
+                min: sys.gpu.pix.x_bound.min().value(),
+                max: sys.gpu.pix.x_bound.max().value(),
+            });
+        }
+        Reg::PixelYBound => {
+            write_masked!(sys.gpu.pix.y_bound);
+            sys.modules.render.exec(render::Action::SetBoundingBox {
+                vertical: true,
+                min: sys.gpu.pix.y_bound.min().value(),
+                max: sys.gpu.pix.y_bound.max().value(),
+            });
+        }
         Reg::PixelCopySrc => write_masked!(sys.gpu.pix.copy_src),
         Reg::PixelCopyDimensions => write_masked!(sys.gpu.pix.copy_dimensions),
         Reg::PixelCopyDst => {
@@ -685,6 +826,9 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
             sys.gpu.pix.copy_dst = Address((value << 5).with_bits(26, 32, 0));
         }
         Reg::PixelCopyDstStride => write_masked!(sys.gpu.pix.copy_stride),
+        Reg::PixelCopyScale => write_masked!(sys.gpu.pix.copy_scale),
+        Reg::PixelCopyFilter0 => write_masked!(sys.gpu.pix.copy_filter.registers[0]),
+        Reg::PixelCopyFilter1 => write_masked!(sys.gpu.pix.copy_filter.registers[1]),
         Reg::PixelCopyClearAr => {
             let mut value = 0
                 .with_bits(0, 8, sys.gpu.pix.clear_color.r as u32)
@@ -926,6 +1070,18 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
             ));
         }
 
+        Reg::TevRangeAdjC => write_masked!(sys.gpu.env.fog.range),
+        Reg::TevRangeAdj0 => write_masked!(sys.gpu.env.fog.range_factors[0]),
+        Reg::TevRangeAdj1 => write_masked!(sys.gpu.env.fog.range_factors[1]),
+        Reg::TevRangeAdj2 => write_masked!(sys.gpu.env.fog.range_factors[2]),
+        Reg::TevRangeAdj3 => write_masked!(sys.gpu.env.fog.range_factors[3]),
+        Reg::TevRangeAdj4 => write_masked!(sys.gpu.env.fog.range_factors[4]),
+        Reg::TevFog0 => write_masked!(sys.gpu.env.fog.scale),
+        Reg::TevFog1 => write_masked!(sys.gpu.env.fog.b_magnitude),
+        Reg::TevFog2 => write_masked!(0x1F; sys.gpu.env.fog.b_shift),
+        Reg::TevFog3 => write_masked!(sys.gpu.env.fog.mode),
+        Reg::TevFogColor => write_masked!(sys.gpu.env.fog.color),
+
         Reg::TevDepthTexBias => write_masked!(sys.gpu.env.depth_tex.bias),
         Reg::TevDepthTexMode => write_masked!(sys.gpu.env.depth_tex.mode),
 
@@ -957,10 +1113,31 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
         sys.gpu.tex.maps[map as usize].dirty = true;
     }
 
+    if matches!(
+        reg,
+        Reg::SetupLpSize
+            | Reg::TexScaleU0
+            | Reg::TexScaleU1
+            | Reg::TexScaleU2
+            | Reg::TexScaleU3
+            | Reg::TexScaleU4
+            | Reg::TexScaleU5
+            | Reg::TexScaleU6
+            | Reg::TexScaleU7
+    ) {
+        update_line_point(sys);
+    }
+
     if reg.is_tev() {
         sys.gpu.env.stages_dirty = true;
     }
 
+    if reg.is_fog() {
+        sys.modules
+            .render
+            .exec(render::Action::SetFog(sys.gpu.env.fog));
+    }
+
     if reg.is_pixel_clear() {
         sys.modules.render.exec(render::Action::SetClearColor(
             sys.gpu.pix.clear_color.into(),
@@ -1006,7 +1183,7 @@ fn extract_vertices(sys: &mut System, stream: &VertexAttributeStream) -> VertexS
     let vat = &sys.gpu.cmd.internal.vertex_attr_tables[stream.table_index()];
     assert!(vcd.position().is_present());
 
-    sys.modules.vertex.parse(
+    let violations = sys.modules.vertex.parse(
         ctx,
         vcd,
         vat,
@@ -1015,6 +1192,12 @@ fn extract_vertices(sys: &mut System, stream: &VertexAttributeStream) -> VertexS
         &mut sys.gpu.matrix_set,
     );
 
+    if violations > 0 {
+        tracing::debug!("{violations} vertex array elements out of bounds in draw");
+    }
+
+    sys.gpu.cmd.array_violations.record(violations);
+
     let mut matrices = alloc_matrices_handle(sys.gpu.matrix_set.len());
     let matrices_slice = unsafe { matrices.as_mut_slice() };
 
@@ -1073,12 +1256,48 @@ fn call(sys: &mut System, address: Address, length: u32) {
     let address = address.value().with_bits(26, 32, 0) & !0x1F;
     // TODO: consider this
     // let length = length.value().with_bit(31, false) & !0x1F;
-    let data = &sys.mem.ram()[address.value() as usize..][..length as usize];
+    let Some(data) = sys.mem.slice(address, length) else {
+        tracing::warn!("display list at {address} with length 0x{length:08X} is out of memory");
+        return;
+    };
+
     sys.gpu.cmd.queue.push_front_bytes(data);
 }
 
+/// Reads the current bounding box from the render module.
+pub fn bounding_box(sys: &mut System) -> render::BoundingBox {
+    let (sender, receiver) = oneshot::channel();
+    sys.modules
+        .render
+        .exec(render::Action::GetBoundingBox { response: sender });
+
+    receiver.recv().unwrap_or_else(|_| {
+        tracing::warn!("render module did not answer bounding box request");
+        render::BoundingBox {
+            left: sys.gpu.pix.x_bound.min().value(),
+            right: sys.gpu.pix.x_bound.max().value(),
+            top: sys.gpu.pix.y_bound.min().value(),
+            bottom: sys.gpu.pix.y_bound.max().value(),
+        }
+    })
+}
+
+/// Returns the RAM an EFB copy of the given dimensions to `dst` writes to: rows of tiles, which
+/// are at least 4 texels tall, `stride` cache lines apart. Rows wider than the stride can't take
+/// more than RGBA8 tiles do.
+fn copy_output(sys: &mut System, dst: Address, stride: u32, width: u32, height: u32) -> &mut [u8] {
+    let row_len = (stride as usize * 32).max(width.div_ceil(4) as usize * 64);
+    let len = height.div_ceil(4) as usize * row_len;
+    let start = dst.value() as usize;
+    sys.mem.ram_range_mut(start..(start + len).min(RAM_LEN))
+}
+
 fn efb_copy(sys: &mut System, cmd: pix::CopyCmd) {
     if cmd.to_xfb() {
+        sys.video.last_xfb_copy = Some(vi::XfbCopy {
+            address: sys.gpu.pix.copy_dst,
+            time: sys.scheduler.elapsed(),
+        });
         sys.modules
             .render
             .exec(render::Action::XfbCopy { clear: cmd.clear() });
@@ -1111,7 +1330,7 @@ fn efb_copy(sys: &mut System, cmd: pix::CopyCmd) {
         let divisor = if cmd.half() { 2 } else { 1 };
         let width = width as u32 / divisor;
         let height = height as u32 / divisor;
-        let output = &mut sys.mem.ram_mut()[dst.value() as usize..];
+        let output = copy_output(sys, dst, stride, width, height);
         tex::encode_depth_texture(pixels, cmd.depth_format(), stride, width, height, output);
     } else {
         let (sender, receiver) = oneshot::channel();
@@ -1139,7 +1358,14 @@ fn efb_copy(sys: &mut System, cmd: pix::CopyCmd) {
         let divisor = if cmd.half() { 2 } else { 1 };
         let width = width as u32 / divisor;
         let height = height as u32 / divisor;
-        let output = &mut sys.mem.ram_mut()[dst.value() as usize..];
+        let pixels = pix::filter_color_copy(
+            pixels,
+            width as usize,
+            &sys.gpu.pix.copy_filter,
+            cmd.gamma(),
+        );
+
+        let output = copy_output(sys, dst, stride, width, height);
         tex::encode_color_texture(pixels, cmd.color_format(), stride, width, height, output);
     }
 }
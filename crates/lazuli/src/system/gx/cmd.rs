@@ -10,9 +10,9 @@ use zerocopy::IntoBytes;
 
 use crate::Primitive;
 use crate::stream::{BinRingBuffer, BinaryStream};
-use crate::system::System;
 use crate::system::gx::cmd::attributes::{AttributeDescriptor, AttributeMode};
 use crate::system::gx::{self, Gpu, Reg as GxReg, Topology};
+use crate::system::{System, pi};
 
 /// A command processor register.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromRepr)]
@@ -182,14 +182,17 @@ pub enum Command {
 #[bitos(16)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Status {
+    /// Whether the FIFO count went above the high watermark.
     #[bits(0)]
     pub fifo_overflow: bool,
+    /// Whether the FIFO count went below the low watermark.
     #[bits(1)]
     pub fifo_underflow: bool,
     #[bits(2)]
     pub read_idle: bool,
     #[bits(3)]
     pub write_idle: bool,
+    /// Whether the read pointer reached the breakpoint.
     #[bits(4)]
     pub breakpoint_interrupt: bool,
 }
@@ -226,6 +229,7 @@ pub struct Fifo {
     pub low_mark: u32,
     pub write_ptr: Address,
     pub read_ptr: Address,
+    pub breakpoint: Address,
 }
 
 impl Fifo {
@@ -285,6 +289,29 @@ pub struct ArrayDescriptor {
     pub stride: u32,
 }
 
+/// Counts of vertex array elements out of bounds of RAM, which were read as zeroes. These usually
+/// mean that the CP state tracking is wrong. Only counted by vertex modules that validate indices.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArrayViolations {
+    /// Violations in the last draw.
+    pub last_draw: u32,
+    /// Draws with at least one violation.
+    pub draws: u64,
+    /// Violations across all draws.
+    pub total: u64,
+}
+
+impl ArrayViolations {
+    /// Records the violations of a draw.
+    pub fn record(&mut self, violations: u32) {
+        self.last_draw = violations;
+        if violations > 0 {
+            self.draws += 1;
+            self.total += violations as u64;
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Arrays {
     pub position: ArrayDescriptor,
@@ -362,6 +389,11 @@ pub struct VertexAttributeStream {
 }
 
 impl VertexAttributeStream {
+    /// Creates a stream of `count` vertices, in the format described by attribute table `table`.
+    pub fn new(table: u8, count: u16, data: Vec<u8>) -> Self {
+        Self { table, count, data }
+    }
+
     pub fn table_index(&self) -> usize {
         self.table as usize
     }
@@ -387,19 +419,51 @@ pub struct Interface {
     pub fifo: Fifo,
     pub internal: Internal,
     pub queue: BinRingBuffer,
+    pub array_violations: ArrayViolations,
 }
 
 impl Interface {
     /// Write a value to the clear register.
     pub fn write_clear(&mut self, value: u16) {
-        if value.bit(0) {
-            self.status.set_fifo_overflow(false);
-        }
+        let overflow = pi::Ack::WriteOne.write(self.status.fifo_overflow(), value.bit(0));
+        let underflow = pi::Ack::WriteOne.write(self.status.fifo_underflow(), value.bit(1));
 
-        if value.bit(1) {
-            self.status.set_fifo_underflow(false);
+        self.status.set_fifo_overflow(overflow);
+        self.status.set_fifo_underflow(underflow);
+    }
+
+    /// Write a value to the control register.
+    pub fn write_control(&mut self, value: Control) {
+        self.control = value;
+
+        // disabling the breakpoint also acknowledges it
+        if !self.control.fifo_breakpoint_enable() {
+            self.status.set_breakpoint_interrupt(false);
         }
     }
+
+    /// Whether the read pointer is stopped at the breakpoint.
+    pub fn at_breakpoint(&self) -> bool {
+        self.control.fifo_breakpoint_enable() && self.fifo.read_ptr == self.fifo.breakpoint
+    }
+
+    /// Updates the watermark bits of the status register with the current FIFO count.
+    pub fn update_watermarks(&mut self) {
+        let count = self.fifo.count();
+        self.status.set_fifo_overflow(count > self.fifo.high_mark);
+        self.status.set_fifo_underflow(count < self.fifo.low_mark);
+    }
+
+    /// Whether any CP interrupt is raised.
+    pub fn any_interrupt(&self) -> bool {
+        let overflow = self.status.fifo_overflow() && self.control.fifo_overflow_interrupt_enable();
+        let underflow =
+            self.status.fifo_underflow() && self.control.fifo_underflow_interrupt_enable();
+        let breakpoint =
+            self.status.breakpoint_interrupt() && self.control.fifo_breakpoint_interrupt_enable();
+
+        overflow || underflow || breakpoint
+    }
 }
 
 impl Gpu {
@@ -524,11 +588,11 @@ impl Gpu {
                 }
 
                 let vertex_attributes = reader.read_bytes(attribute_stream_size)?;
-                let vertex_attributes = VertexAttributeStream {
-                    table: opcode.vat_index().value(),
-                    count: vertex_count,
-                    data: vertex_attributes,
-                };
+                let vertex_attributes = VertexAttributeStream::new(
+                    opcode.vat_index().value(),
+                    vertex_count,
+                    vertex_attributes,
+                );
 
                 let topology = match operation {
                     Operation::DrawQuadList => Topology::QuadList,
@@ -656,20 +720,36 @@ fn fifo_pop(sys: &mut System) -> u8 {
     data
 }
 
-/// Consumes commands available in the CP FIFO.
+/// Consumes commands available in the CP FIFO, stopping at the breakpoint if it is enabled.
 pub fn consume(sys: &mut System) {
-    if !sys.gpu.cmd.control.fifo_read_enable() {
-        return;
+    let raised = sys.gpu.cmd.any_interrupt();
+
+    if sys.gpu.cmd.control.fifo_read_enable() {
+        while sys.gpu.cmd.fifo.count() > 0 {
+            if sys.gpu.cmd.at_breakpoint() {
+                std::hint::cold_path();
+                sys.gpu.cmd.status.set_breakpoint_interrupt(true);
+                break;
+            }
+
+            let data = self::fifo_pop(sys);
+            sys.gpu.cmd.queue.push_be(data);
+        }
     }
 
-    while sys.gpu.cmd.fifo.count() > 0 {
-        let data = self::fifo_pop(sys);
-        sys.gpu.cmd.queue.push_be(data);
+    sys.gpu.cmd.update_watermarks();
+    let idle = sys.gpu.cmd.fifo.count() == 0;
+    sys.gpu.cmd.status.set_read_idle(idle);
+    sys.gpu.cmd.status.set_write_idle(idle);
+
+    if sys.gpu.cmd.any_interrupt() != raised {
+        sys.scheduler.schedule_now(pi::check_interrupts);
     }
 }
 
 /// Process consumed CP commands until the queue is either empty or incomplete.
 pub fn process(sys: &mut System) {
+    profiling::scope!("process CP commands");
     let current_token = sys.gpu.pix.token;
     loop {
         let draw_done = sys.gpu.pix.interrupt.finish();
@@ -754,3 +834,67 @@ pub fn sync_to_pi(sys: &mut System) {
     sys.gpu.cmd.fifo.end = sys.processor.fifo_end;
     sys.gpu.cmd.fifo.write_ptr = sys.processor.fifo_current.address();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn interface() -> Interface {
+        let mut cp = Interface::default();
+        cp.fifo.start = Address(0x1000);
+        cp.fifo.end = Address(0x1FFF);
+        cp.fifo.read_ptr = Address(0x1000);
+        cp.fifo.write_ptr = Address(0x1000);
+        cp.fifo.high_mark = 0x800;
+        cp.fifo.low_mark = 0x100;
+        cp
+    }
+
+    #[test]
+    fn watermark_interrupts() {
+        let mut cp = interface();
+        cp.write_control(
+            Control::default()
+                .with_fifo_overflow_interrupt_enable(true)
+                .with_fifo_underflow_interrupt_enable(true),
+        );
+
+        cp.update_watermarks();
+        assert!(cp.status.fifo_underflow() && !cp.status.fifo_overflow());
+        assert!(cp.any_interrupt());
+
+        cp.fifo.write_ptr = Address(0x1400);
+        cp.update_watermarks();
+        assert!(!cp.status.fifo_underflow() && !cp.status.fifo_overflow());
+        assert!(!cp.any_interrupt());
+
+        // the count wraps around the end of the FIFO
+        cp.fifo.read_ptr = Address(0x1C00);
+        cp.fifo.write_ptr = Address(0x1A00);
+        cp.update_watermarks();
+        assert!(cp.status.fifo_overflow());
+        assert!(cp.any_interrupt());
+    }
+
+    #[test]
+    fn breakpoint_interrupt() {
+        let mut cp = interface();
+        cp.fifo.breakpoint = Address(0x1000);
+        assert!(!cp.at_breakpoint());
+
+        cp.write_control(
+            Control::default()
+                .with_fifo_breakpoint_enable(true)
+                .with_fifo_breakpoint_interrupt_enable(true),
+        );
+        assert!(cp.at_breakpoint());
+
+        cp.status.set_breakpoint_interrupt(true);
+        assert!(cp.any_interrupt());
+
+        // disabling the breakpoint acknowledges it
+        cp.write_control(Control::default());
+        assert!(!cp.status.breakpoint_interrupt());
+        assert!(!cp.any_interrupt());
+    }
+}
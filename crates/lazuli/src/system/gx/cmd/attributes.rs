@@ -135,8 +135,17 @@ pub struct NormalDescriptor {
     pub format: CoordsFormat,
 }
 
+/// The normal of a vertex, along with its binormal and tangent, which are only present for
+/// [`NormalKind::N9`] and zero otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Normals {
+    pub normal: Vec3,
+    pub binormal: Vec3,
+    pub tangent: Vec3,
+}
+
 impl AttributeDescriptor for NormalDescriptor {
-    type Value = Vec3;
+    type Value = Normals;
 
     fn size(&self) -> u32 {
         match self.kind() {
@@ -145,7 +154,7 @@ impl AttributeDescriptor for NormalDescriptor {
         }
     }
 
-    fn read(&self, reader: &mut BinReader) -> Option<Vec3> {
+    fn read(&self, reader: &mut BinReader) -> Option<Normals> {
         let mut component = || {
             let shift_6 = 2.0f32.powi(6);
             let shift_14 = 2.0f32.powi(14);
@@ -161,16 +170,15 @@ impl AttributeDescriptor for NormalDescriptor {
 
         let mut vec = || Some(Vec3::new(component()?, component()?, component()?));
         Some(match self.kind() {
-            NormalKind::N3 => vec()?,
-            NormalKind::N9 => {
-                tracing::warn!("parsing binormal and tangent");
-
-                let normal = vec()?;
-                vec()?;
-                vec()?;
-
-                normal
-            }
+            NormalKind::N3 => Normals {
+                normal: vec()?,
+                ..Default::default()
+            },
+            NormalKind::N9 => Normals {
+                normal: vec()?,
+                binormal: vec()?,
+                tangent: vec()?,
+            },
         })
     }
 }
@@ -604,3 +612,40 @@ impl<const N: usize> Attribute for TexCoords<N> {
         Some(arrays.tex_coords[N])
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stream::BinaryStream;
+
+    #[test]
+    fn nbt_normals() {
+        // 1.0 in I8 normals is 1 << 6
+        let mut data: &[u8] = &[64, 0, 0, 0, 64, 0, 0, 0, 192];
+        let n3 = NormalDescriptor::default().with_format(CoordsFormat::I8);
+        let n9 = n3.clone().with_kind(NormalKind::N9);
+
+        let normals = n3.read(&mut data.reader()).unwrap();
+        assert_eq!(
+            normals,
+            Normals {
+                normal: Vec3::X,
+                ..Default::default()
+            }
+        );
+
+        let normals = n9.read(&mut data.reader()).unwrap();
+        assert_eq!(
+            normals,
+            Normals {
+                normal: Vec3::X,
+                binormal: Vec3::Y,
+                tangent: Vec3::NEG_Z,
+            }
+        );
+
+        assert_eq!(n9.size(), 9);
+        let mut short: &[u8] = &data[..8];
+        assert!(n9.read(&mut short.reader()).is_none());
+    }
+}
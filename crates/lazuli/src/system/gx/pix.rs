@@ -1,10 +1,11 @@
 //! Pixel engine (PE).
-use bitos::integer::{u2, u3, u4, u10};
+use bitos::integer::{u3, u4, u6, u9, u10};
 use bitos::{Bits, bitos};
-use color::Abgr8;
+use color::{Abgr8, GammaTable, Rgba8};
 use gekko::Address;
 
 use crate::system::gx::tex;
+use crate::system::pi::Ack;
 
 #[bitos(3)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -47,6 +48,7 @@ pub struct Control {
     pub format: BufferFormat,
     #[bits(3..5)]
     pub depth_compression: DepthCompression,
+    /// Whether depth is tested before texturing (zcomploc), rather than after the alpha test.
     #[bits(6)]
     pub depth_compress_before_tex: bool,
 }
@@ -69,6 +71,16 @@ pub struct CopySrc {
     pub y: u10,
 }
 
+/// Horizontal or vertical extents of the bounding box, in EFB pixels.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BoundRange {
+    #[bits(0..10)]
+    pub min: u10,
+    #[bits(10..20)]
+    pub max: u10,
+}
+
 #[bitos(32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct CopyDimensions {
@@ -172,19 +184,49 @@ impl ColorCopyFormat {
     }
 }
 
+/// Gamma the RGB channels of EFB copies to textures are encoded with.
+#[bitos(2)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyGamma {
+    #[default]
+    Linear   = 0x0,
+    Gamma17  = 0x1,
+    Gamma22  = 0x2,
+    Reserved = 0x3,
+}
+
+impl CopyGamma {
+    /// The exponent of the gamma. The reserved setting behaves like 2.2.
+    pub fn value(self) -> f32 {
+        match self {
+            Self::Linear => 1.0,
+            Self::Gamma17 => 1.7,
+            Self::Gamma22 | Self::Reserved => 2.2,
+        }
+    }
+}
+
 #[bitos(32)]
 #[derive(Debug, Default)]
 pub struct CopyCmd {
-    #[bits(0..2)]
-    pub clamp: u2,
+    /// Whether the copy filter repeats the first row instead of reading the one above it.
+    #[bits(0)]
+    pub clamp_top: bool,
+    /// Whether the copy filter repeats the last row instead of reading the one below it.
+    #[bits(1)]
+    pub clamp_bottom: bool,
     #[bits(3)]
     pub format_bit_3: bool,
     #[bits(4..7)]
     pub format_bits_0to2: u3,
     #[bits(7..9)]
-    pub gamma: u2,
+    pub gamma: CopyGamma,
+    /// Whether to downscale by half with a 2x2 box filter.
     #[bits(9)]
     pub half: bool,
+    /// Whether to scale XFB copies vertically by [`CopyScale`].
+    #[bits(10)]
+    pub scale_vertical: bool,
     #[bits(11)]
     pub clear: bool,
     /// to XFB or to texture?
@@ -206,15 +248,151 @@ impl CopyCmd {
     }
 }
 
+/// Vertical scale of XFB copies, as the 1.8 fixed point reciprocal of the factor. Only kept
+/// track of, since XFB copies are presented by the renderer straight from the EFB.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CopyScale {
+    #[bits(0..9)]
+    pub reciprocal: u9,
+}
+
+impl CopyScale {
+    /// How many destination rows each source row becomes.
+    pub fn factor(&self) -> f32 {
+        256.0 / self.reciprocal().value().max(1) as f32
+    }
+}
+
+/// Coefficients of the vertical copy filter, in 64ths. The first register holds coefficients 0
+/// to 3 and the second coefficients 4 to 6.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CopyFilterCoefficients {
+    #[bits(0..24)]
+    pub coefficients: [u6; 4],
+}
+
+/// The vertical filter applied to color copies, used by games for deflickering and blurring.
+///
+/// The seven coefficients line up with the sample pattern of antialiased rendering: 0 and 1 weigh
+/// the row above a pixel, 2 to 4 the row of the pixel itself and 5 and 6 the row below. Since
+/// samples are not kept around, each row is weighed by the sum of its coefficients, three taps in
+/// total. The alpha channel is never filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyFilter {
+    pub registers: [CopyFilterCoefficients; 2],
+}
+
+impl Default for CopyFilter {
+    /// The filter set up by GX_Init, which leaves pixels untouched.
+    fn default() -> Self {
+        Self {
+            registers: [
+                CopyFilterCoefficients::from_bits(0x0059_5000),
+                CopyFilterCoefficients::from_bits(0x0000_0015),
+            ],
+        }
+    }
+}
+
+impl CopyFilter {
+    /// The weights of the rows above, at and below each pixel, in 64ths.
+    pub fn weights(&self) -> [u32; 3] {
+        let [low, high] = self
+            .registers
+            .map(|r| r.coefficients().map(|c| c.value() as u32));
+        [
+            low[0] + low[1],
+            low[2] + low[3] + high[0],
+            high[1] + high[2],
+        ]
+    }
+
+    /// Filters `width` pixel wide rows. Only the copied rectangle is available, so rows beyond it
+    /// are always clamped, regardless of [`CopyCmd::clamp_top`] and [`CopyCmd::clamp_bottom`].
+    pub fn apply(&self, pixels: &[Rgba8], width: usize) -> Vec<Rgba8> {
+        let [above, at, below] = self.weights();
+        let rows = pixels.chunks_exact(width).collect::<Vec<_>>();
+        let last = rows.len().saturating_sub(1);
+
+        let mut out = Vec::with_capacity(pixels.len());
+        for (y, row) in rows.iter().enumerate() {
+            let prev = rows[y.saturating_sub(1)];
+            let next = rows[(y + 1).min(last)];
+            out.extend((0..width).map(|x| {
+                let channel = |c: fn(Rgba8) -> u8| {
+                    let sum = c(prev[x]) as u32 * above
+                        + c(row[x]) as u32 * at
+                        + c(next[x]) as u32 * below;
+                    (sum >> 6).min(255) as u8
+                };
+
+                Rgba8 {
+                    r: channel(|p| p.r),
+                    g: channel(|p| p.g),
+                    b: channel(|p| p.b),
+                    a: row[x].a,
+                }
+            }));
+        }
+
+        out
+    }
+}
+
+/// Applies the copy filter and then the gamma of a color copy to its `width` pixel wide rows. The
+/// 2x2 box filter of half sized copies is applied by the renderer beforehand, so the copy filter
+/// works on the downscaled rows.
+pub fn filter_color_copy(
+    pixels: Vec<Rgba8>,
+    width: usize,
+    filter: &CopyFilter,
+    gamma: CopyGamma,
+) -> Vec<Rgba8> {
+    let mut pixels = if filter.weights() == [0, 64, 0] || width == 0 {
+        pixels
+    } else {
+        filter.apply(&pixels, width)
+    };
+
+    if gamma != CopyGamma::Linear {
+        let table = GammaTable::new(gamma.value());
+        for pixel in &mut pixels {
+            *pixel = pixel.with_gamma(&table);
+        }
+    }
+
+    pixels
+}
+
 #[bitos(16)]
 #[derive(Debug, Default)]
 pub struct InterruptStatus {
+    #[bits(0)]
+    pub token_enable: bool,
+    #[bits(1)]
+    pub finish_enable: bool,
+    /// Whether the token interrupt is asserted. Cleared by writing 1.
     #[bits(2)]
     pub token: bool,
+    /// Whether the finish interrupt is asserted. Cleared by writing 1.
     #[bits(3)]
     pub finish: bool,
 }
 
+impl InterruptStatus {
+    /// Whether the token interrupt is asserted and enabled.
+    pub fn token_raised(&self) -> bool {
+        self.token() && self.token_enable()
+    }
+
+    /// Whether the finish interrupt is asserted and enabled.
+    pub fn finish_raised(&self) -> bool {
+        self.finish() && self.finish_enable()
+    }
+}
+
 #[bitos(3)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum CompareMode {
@@ -319,9 +497,13 @@ pub struct Interface {
     pub interrupt: InterruptStatus,
     pub constant_alpha: ConstantAlpha,
     pub copy_src: CopySrc,
+    pub x_bound: BoundRange,
+    pub y_bound: BoundRange,
     pub copy_dst: Address,
     pub copy_dimensions: CopyDimensions,
     pub copy_stride: u32,
+    pub copy_scale: CopyScale,
+    pub copy_filter: CopyFilter,
     pub clear_color: Abgr8,
     pub clear_depth: u32,
     pub depth_mode: DepthMode,
@@ -331,6 +513,73 @@ pub struct Interface {
 
 impl Interface {
     pub fn write_interrupt(&mut self, status: u16) {
-        self.interrupt = InterruptStatus::from_bits(self.interrupt.to_bits() & !status)
+        let written = InterruptStatus::from_bits(status);
+        let token = Ack::WriteOne.write(self.interrupt.token(), written.token());
+        let finish = Ack::WriteOne.write(self.interrupt.finish(), written.finish());
+
+        self.interrupt = written.with_token(token).with_finish(finish);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray(value: u8) -> Rgba8 {
+        Rgba8 {
+            r: value,
+            g: value,
+            b: value,
+            a: 255 - value,
+        }
+    }
+
+    /// A filter with the given seven coefficients.
+    fn filter(c: [u32; 7]) -> CopyFilter {
+        let pack = |c: &[u32]| c.iter().rev().fold(0, |acc, c| acc << 6 | c);
+        CopyFilter {
+            registers: [
+                CopyFilterCoefficients::from_bits(pack(&c[..4])),
+                CopyFilterCoefficients::from_bits(pack(&c[4..])),
+            ],
+        }
+    }
+
+    #[test]
+    fn default_filter_is_identity() {
+        assert_eq!(CopyFilter::default().weights(), [0, 64, 0]);
+
+        let pixels = (0..16).map(|i| gray(i * 16)).collect::<Vec<_>>();
+        let filtered =
+            filter_color_copy(pixels.clone(), 4, &CopyFilter::default(), CopyGamma::Linear);
+        assert_eq!(filtered, pixels);
+    }
+
+    #[test]
+    fn vertical_filter() {
+        let blur = filter([8, 8, 16, 8, 8, 8, 8]);
+        assert_eq!(blur.weights(), [16, 32, 16]);
+
+        // a single column, with the rows beyond the edges clamped
+        let pixels = [gray(0), gray(255), gray(0)];
+        let filtered = filter_color_copy(pixels.to_vec(), 1, &blur, CopyGamma::Linear);
+        let rgb = filtered.iter().map(|p| p.r).collect::<Vec<_>>();
+        let alpha = filtered.iter().map(|p| p.a).collect::<Vec<_>>();
+        assert_eq!(rgb, [63, 127, 63]);
+        assert_eq!(alpha, [255, 0, 255]);
+
+        // weights summing past 64 saturate
+        let bright = filter([0, 0, 32, 32, 32, 0, 0]);
+        let filtered = filter_color_copy(vec![gray(200); 2], 1, &bright, CopyGamma::Linear);
+        assert_eq!(filtered[0].r, 255);
+    }
+
+    #[test]
+    fn gamma() {
+        let pixels = vec![gray(0), gray(128), gray(255)];
+        let filtered = filter_color_copy(pixels, 3, &CopyFilter::default(), CopyGamma::Gamma22);
+        let rgb = filtered.iter().map(|p| p.r).collect::<Vec<_>>();
+        assert_eq!(rgb, [0, 186, 255]);
+        assert_eq!(filtered[1].a, 127);
     }
 }
@@ -1,6 +1,6 @@
 //! Texture Environment (TEV).
 use bitos::bitos;
-use bitos::integer::{u2, u3};
+use bitos::integer::{u2, u3, u10, u12, u20};
 use color::Rgba16;
 
 #[bitos(3)]
@@ -282,7 +282,8 @@ impl StageColor {
     }
 
     pub fn pattern(&self) -> Option<StageColorPattern> {
-        use {ColorInputSrc as Input, StageColorPattern as Pattern};
+        use ColorInputSrc as Input;
+        use StageColorPattern as Pattern;
 
         if self.is_comparative() {
             return None;
@@ -561,6 +562,188 @@ pub struct DepthTexture {
     pub bias: u32,
 }
 
+/// How the fog density is computed from the distance to the camera.
+///
+/// The exponential kinds map the linear density `d` to `1 - 2^(-8d)` and `1 - 2^(-8d²)`, while the
+/// backward ones map it to `2^(-8(1 - d))` and `2^(-8(1 - d)²)`.
+#[bitos(3)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FogKind {
+    #[default]
+    Off          = 0b000,
+    Reserved0    = 0b001,
+    Linear       = 0b010,
+    Reserved1    = 0b011,
+    Exp          = 0b100,
+    Exp2         = 0b101,
+    BackwardExp  = 0b110,
+    BackwardExp2 = 0b111,
+}
+
+#[bitos(1)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FogProjection {
+    #[default]
+    Perspective  = 0b0,
+    Orthographic = 0b1,
+}
+
+/// Converts a 20 bit fog float (an `f32` without the 12 least significant bits of its mantissa)
+/// to an `f32`.
+fn fog_float(bits: u20) -> f32 {
+    f32::from_bits(bits.value() << 12)
+}
+
+/// The `A` fog parameter, which scales the depth.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FogScale {
+    #[bits(0..20)]
+    pub raw: u20,
+}
+
+impl FogScale {
+    pub fn value(&self) -> f32 {
+        fog_float(self.raw())
+    }
+}
+
+/// The `C` fog parameter, which offsets the depth, along with the fog mode.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FogMode {
+    #[bits(0..20)]
+    pub raw_offset: u20,
+    #[bits(20)]
+    pub projection: FogProjection,
+    #[bits(21..24)]
+    pub kind: FogKind,
+}
+
+impl FogMode {
+    pub fn offset(&self) -> f32 {
+        fog_float(self.raw_offset())
+    }
+}
+
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FogColor {
+    #[bits(0..8)]
+    pub b: u8,
+    #[bits(8..16)]
+    pub g: u8,
+    #[bits(16..24)]
+    pub r: u8,
+}
+
+/// Range adjustment increases the density of fog towards the horizontal edges of the screen, to
+/// approximate the actual distance to the camera instead of just the depth.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FogRange {
+    /// Horizontal center of the screen, plus 342.
+    #[bits(0..10)]
+    pub center: u10,
+    #[bits(10)]
+    pub enabled: bool,
+}
+
+/// A pair of range adjustment factors, in units of 1/256.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FogRangeFactors {
+    #[bits(0..24)]
+    pub factors: [u12; 2],
+}
+
+/// The fog configuration. The linear fog density is `A / (B - (Z >> B_SHIFT)) - C` for perspective
+/// projections and `A * Z - C` for orthographic ones, where `Z` is the 24 bit screen space depth.
+/// Both `Z` and `B` are fixed point fractions with 24 fractional bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fog {
+    pub scale: FogScale,
+    pub b_magnitude: u32,
+    pub b_shift: u32,
+    pub mode: FogMode,
+    pub color: FogColor,
+    pub range: FogRange,
+    pub range_factors: [FogRangeFactors; 5],
+}
+
+impl Fog {
+    /// Horizontal center of the range adjustment, in pixels relative to the center of the EFB.
+    pub fn range_center(&self) -> f32 {
+        self.range.center().value() as f32 - 342.0
+    }
+
+    /// The ten range adjustment factors, from the edges of the screen to its center.
+    pub fn range_factors(&self) -> [f32; 10] {
+        let mut factors = [0.0; 10];
+        for (i, pair) in self.range_factors.iter().enumerate() {
+            for (j, factor) in pair.factors().into_iter().enumerate() {
+                factors[2 * i + j] = factor.value() as f32 / 256.0;
+            }
+        }
+
+        factors
+    }
+
+    /// How much the distance of a fragment in column `x` of a `width` pixels wide viewport is
+    /// scaled by range adjustment, interpolating linearly between factors. One if disabled.
+    pub fn range_adjustment(&self, x: f32, width: f32) -> f32 {
+        if !self.range.enabled() {
+            return 1.0;
+        }
+
+        let factors = self.range_factors();
+        let half_width = width / 2.0;
+        let offset = (x - self.range_center()) / half_width - 1.0;
+        let index = (9.0 - offset.abs() * 9.0).clamp(0.0, 9.0);
+        let lower = factors[index as usize];
+        let upper = factors[(index as usize + 1).min(9)];
+        let k = lower + (upper - lower) * index.fract();
+
+        (offset * offset + k * k).sqrt() / k
+    }
+
+    /// The fog density, in the `0.0..=1.0` range, of a fragment at the 24 bit screen space depth
+    /// `z` whose distance is scaled by `adjustment` (see [`Fog::range_adjustment`]).
+    ///
+    /// This is the reference the fog stage of renderers follows.
+    pub fn density(&self, z: u32, adjustment: f32) -> f32 {
+        const ONE: f32 = (1 << 24) as f32;
+
+        let distance = match self.mode.projection() {
+            FogProjection::Perspective => {
+                let denom = self.b_magnitude as i32 - (z >> self.b_shift) as i32;
+                self.scale.value() * ONE / denom as f32
+            }
+            FogProjection::Orthographic => self.scale.value() * z as f32 / ONE,
+        };
+
+        let fog = (distance * adjustment - self.mode.offset()).clamp(0.0, 1.0);
+        match self.mode.kind() {
+            FogKind::Off => 0.0,
+            FogKind::Exp => 1.0 - (-8.0 * fog).exp2(),
+            FogKind::Exp2 => 1.0 - (-8.0 * fog * fog).exp2(),
+            FogKind::BackwardExp => (-8.0 * (1.0 - fog)).exp2(),
+            FogKind::BackwardExp2 => (-8.0 * (1.0 - fog) * (1.0 - fog)).exp2(),
+            FogKind::Linear | FogKind::Reserved0 | FogKind::Reserved1 => fog,
+        }
+    }
+
+    /// Blends an RGB color with the fog color by the given density, with 8 bits of precision like
+    /// the hardware.
+    pub fn blend(&self, color: [u8; 3], density: f32) -> [u8; 3] {
+        let amount = (density * 256.0).round() as u32;
+        let fog = [self.color.r(), self.color.g(), self.color.b()];
+        std::array::from_fn(|i| {
+            ((color[i] as u32 * (256 - amount) + fog[i] as u32 * amount) / 256) as u8
+        })
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Interface {
     pub active_stages: u8,
@@ -571,5 +754,174 @@ pub struct Interface {
     pub constants: [Rgba16; 4],
     pub alpha_function: AlphaFunction,
     pub depth_tex: DepthTexture,
+    pub fog: Fog,
     pub stages_dirty: bool,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NEAR: f32 = 1.0;
+    const FAR: f32 = 1000.0;
+    const START: f32 = 100.0;
+    const END: f32 = 500.0;
+
+    /// Builds the fog registers like GX_SetFog does, for the given libogc fog type.
+    fn gx_set_fog(kind: FogKind, projection: FogProjection, color: [u8; 3]) -> Fog {
+        let (a, b_magnitude, b_shift, c) = match projection {
+            FogProjection::Perspective => {
+                let a = (FAR * NEAR) / ((FAR - NEAR) * (END - START));
+                let b = FAR / (FAR - NEAR);
+                let c = START / (END - START);
+
+                let mut b_mantissa = b;
+                let mut b_exponent = 1i32;
+                while b_mantissa > 1.0 {
+                    b_mantissa /= 2.0;
+                    b_exponent += 1;
+                }
+                while b_mantissa > 0.0 && b_mantissa < 0.5 {
+                    b_mantissa *= 2.0;
+                    b_exponent -= 1;
+                }
+
+                let a = a / (1 << b_exponent) as f32;
+                let b_magnitude = (b_mantissa * 8388638.0) as u32;
+                (a, b_magnitude, b_exponent as u32, c)
+            }
+            FogProjection::Orthographic => {
+                let a = (FAR - NEAR) / (END - START);
+                let c = (START - NEAR) / (END - START);
+                (a, 0, 0, c)
+            }
+        };
+
+        Fog {
+            scale: FogScale::from_bits(a.to_bits() >> 12),
+            b_magnitude,
+            b_shift,
+            mode: FogMode::from_bits(c.to_bits() >> 12)
+                .with_projection(projection)
+                .with_kind(kind),
+            color: FogColor::from_bits(u32::from_be_bytes([0, color[0], color[1], color[2]])),
+            ..Default::default()
+        }
+    }
+
+    /// The 24 bit screen space depth of a point `distance` away from the camera, as produced by a
+    /// GX projection matrix and a viewport with a depth range of `0.0..=1.0`.
+    fn screen_depth(projection: FogProjection, distance: f32) -> u32 {
+        let z = match projection {
+            FogProjection::Perspective => {
+                FAR / (FAR - NEAR) - (FAR * NEAR) / ((FAR - NEAR) * distance)
+            }
+            FogProjection::Orthographic => (distance - NEAR) / (FAR - NEAR),
+        };
+
+        (z as f64 * (1 << 24) as f64).round().min(0xFF_FFFF as f64) as u32
+    }
+
+    /// The expected fog density at the given distance from the camera.
+    fn expected_density(kind: FogKind, distance: f32) -> f32 {
+        let fog = ((distance - START) / (END - START)).clamp(0.0, 1.0);
+        match kind {
+            FogKind::Exp => 1.0 - 2f32.powf(-8.0 * fog),
+            FogKind::Exp2 => 1.0 - 2f32.powf(-8.0 * fog.powi(2)),
+            FogKind::BackwardExp => 2f32.powf(-8.0 * (1.0 - fog)),
+            FogKind::BackwardExp2 => 2f32.powf(-8.0 * (1.0 - fog).powi(2)),
+            _ => fog,
+        }
+    }
+
+    #[test]
+    fn depth_ramps() {
+        let kinds = [
+            FogKind::Linear,
+            FogKind::Exp,
+            FogKind::Exp2,
+            FogKind::BackwardExp,
+            FogKind::BackwardExp2,
+        ];
+
+        let color = [0x20, 0x80, 0xF0];
+        let fog_color = [0xC0, 0xD0, 0xE0];
+        for projection in [FogProjection::Perspective, FogProjection::Orthographic] {
+            for kind in kinds {
+                let fog = gx_set_fog(kind, projection, fog_color);
+                for distance in (1..100).map(|i| i as f32 * 10.0) {
+                    let z = screen_depth(projection, distance);
+                    let density = fog.density(z, 1.0);
+                    let expected = expected_density(kind, distance);
+
+                    // the parameters lose precision when encoded, which the steep end of the
+                    // backward exponential curve amplifies the most
+                    assert!(
+                        (density - expected).abs() < 0.02,
+                        "{kind:?} {projection:?} fog at {distance}: {density} vs {expected}"
+                    );
+
+                    let blended = fog.blend(color, density);
+                    let channels = blended.into_iter().zip(color).zip(fog_color);
+                    for ((blended, color), fog_color) in channels {
+                        let expected =
+                            color as f32 * (1.0 - expected) + fog_color as f32 * expected;
+                        assert!((blended as f32 - expected).abs() <= 4.0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn off() {
+        let fog = gx_set_fog(FogKind::Off, FogProjection::Perspective, [0xFF; 3]);
+        let z = screen_depth(FogProjection::Perspective, FAR);
+        assert_eq!(fog.density(z, 1.0), 0.0);
+        assert_eq!(fog.blend([1, 2, 3], 0.0), [1, 2, 3]);
+    }
+
+    #[test]
+    fn range_adjustment() {
+        let mut fog = gx_set_fog(FogKind::Linear, FogProjection::Perspective, [0; 3]);
+        assert_eq!(fog.range_adjustment(0.0, 640.0), 1.0);
+
+        // centered, with factors of 1.0
+        fog.range = FogRange::from_bits(342).with_enabled(true);
+        fog.range_factors = [FogRangeFactors::from_bits(0x100 | (0x100 << 12)); 5];
+        assert_eq!(fog.range_adjustment(320.0, 640.0), 1.0);
+
+        // grows towards both edges
+        let left = fog.range_adjustment(0.0, 640.0);
+        let right = fog.range_adjustment(640.0, 640.0);
+        assert!((left - 2f32.sqrt()).abs() < 1e-3, "{left}");
+        assert!((right - 2f32.sqrt()).abs() < 1e-3, "{right}");
+
+        // and so does the density
+        let z = screen_depth(FogProjection::Perspective, 300.0);
+        assert!(fog.density(z, left) > fog.density(z, 1.0));
+    }
+
+    #[test]
+    fn range_adjustment_off_center() {
+        let mut fog = gx_set_fog(FogKind::Linear, FogProjection::Perspective, [0; 3]);
+        fog.range_factors = [FogRangeFactors::from_bits(0x100 | (0x100 << 12)); 5];
+
+        // a range center 100 pixels right of the middle of a 640 pixels wide viewport, i.e. at
+        // x = 420. With factors of 1.0, the adjustment is sqrt(d² + 1) for a fragment d half
+        // widths away from it.
+        fog.range = FogRange::from_bits(342 + 100).with_enabled(true);
+        assert_eq!(fog.range_center(), 100.0);
+
+        let expected = [(420.0, 1.0), (260.0, 1.25f32.sqrt()), (100.0, 2f32.sqrt())];
+        for (x, expected) in expected {
+            let adjustment = fog.range_adjustment(x, 640.0);
+            assert!((adjustment - expected).abs() < 1e-4, "{x}: {adjustment}");
+        }
+
+        // symmetric around the center
+        let left = fog.range_adjustment(420.0 - 64.0, 640.0);
+        let right = fog.range_adjustment(420.0 + 64.0, 640.0);
+        assert!((left - right).abs() < 1e-4, "{left} {right}");
+    }
+}
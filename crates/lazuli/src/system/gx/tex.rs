@@ -106,6 +106,26 @@ impl Format {
     pub fn is_direct(&self) -> bool {
         !matches!(self, Self::CI4 | Self::CI8 | Self::CI14X2)
     }
+
+    /// The tile layout of this format, for uploading encoded texture data as-is.
+    pub fn tile_layout(&self) -> Option<gxtex::TileLayout> {
+        use gxtex::{CI4, CI8, CI14X2, Cmpr, Format as _, I4, I8, IA4, IA8, Rgb5A3, Rgb565, Rgba8};
+
+        Some(match self {
+            Self::I4 => I4::<gxtex::Luma>::LAYOUT,
+            Self::I8 => I8::<gxtex::Luma>::LAYOUT,
+            Self::IA4 => IA4::<gxtex::Luma>::LAYOUT,
+            Self::IA8 => IA8::<gxtex::Luma>::LAYOUT,
+            Self::Rgb565 => Rgb565::LAYOUT,
+            Self::Rgb5A3 => Rgb5A3::LAYOUT,
+            Self::Rgba8 => Rgba8::LAYOUT,
+            Self::CI4 => CI4::LAYOUT,
+            Self::CI8 => CI8::LAYOUT,
+            Self::CI14X2 => CI14X2::LAYOUT,
+            Self::Cmp => Cmpr::LAYOUT,
+            _ => return None,
+        })
+    }
 }
 
 #[bitos(32)]
@@ -557,18 +577,22 @@ pub fn update_texture(sys: &mut System, index: usize) {
         (map.encoding.length() as usize, 1)
     };
 
-    let data = &sys.mem.ram()[base.value() as usize..][..len];
-    if sys.gpu.tex.is_tex_dirty(base, data) {
-        let data = self::decode_mipmap(data, width, height, format, lods);
-        sys.modules.render.exec(render::Action::LoadTexture {
-            id: texture_id,
-            texture: render::Texture {
-                width,
-                height,
-                format,
-                data,
-            },
-        });
+    match sys.mem.slice(base, len as u32) {
+        Some(data) if sys.gpu.tex.is_tex_dirty(base, data) => {
+            profiling::scope!("decode texture");
+            let data = self::decode_mipmap(data, width, height, format, lods);
+            sys.modules.render.exec(render::Action::LoadTexture {
+                id: texture_id,
+                texture: render::Texture {
+                    width,
+                    height,
+                    format,
+                    data,
+                },
+            });
+        }
+        Some(_) => (),
+        None => tracing::warn!("texture at {base} with length 0x{len:08X} is out of memory"),
     }
 
     let scale_u = map.scaling.u.scale().unwrap_or(width) as f32 / width as f32;
@@ -595,8 +619,11 @@ pub fn update_clut(sys: &mut System) {
     let clut_addr = render::ClutAddress(load.tmem_offset().value());
 
     let base = sys.gpu.tex.clut_addr;
-    let len = load.count().value() as usize * 16 * 2;
-    let data = &sys.mem.ram()[base.value() as usize..][..len];
+    let len = load.count().value() as u32 * 16 * 2;
+    let Some(data) = sys.mem.slice(base, len) else {
+        tracing::warn!("CLUT at {base} with length 0x{len:08X} is out of memory");
+        return;
+    };
 
     if sys.gpu.tex.is_clut_dirty(base, data) {
         let clut = data
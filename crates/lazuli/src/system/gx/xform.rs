@@ -98,7 +98,8 @@ impl Reg {
     pub fn is_texgen(&self) -> bool {
         matches!(
             self,
-            Reg::TexGenCount
+            Reg::DualTextureTransform
+                | Reg::TexGenCount
                 | Reg::TexGen0
                 | Reg::TexGen1
                 | Reg::TexGen2
@@ -301,6 +302,8 @@ pub struct Internal {
     pub texgen: [TexGen; 8],
     pub post_texgen: [PostTexGen; 8],
     pub active_texgens: u8,
+    /// Whether the post texgen transform is applied to regular texgens.
+    pub dual_texture_transform: bool,
     pub stages_dirty: bool,
 }
 
@@ -394,6 +397,7 @@ pub fn update_texgen(sys: &mut System) {
     {
         let stage = render::TexGenStage {
             base: texgen.base,
+            dual: sys.gpu.xform.internal.dual_texture_transform,
             normalize: texgen.post.normalize(),
             post_matrix: sys.gpu.xform.post_matrix(texgen.post.mat_index().value()),
         };
@@ -482,6 +486,7 @@ pub fn set_register(sys: &mut System, reg: Reg, value: u32) {
         Reg::ProjectionParam5 => xf.projection_mat.params[5] = f32::from_bits(value),
         Reg::ProjectionOrthographic => xf.projection_mat.orthographic = value != 0,
 
+        Reg::DualTextureTransform => xf.dual_texture_transform = value & 1 != 0,
         Reg::TexGenCount => xf.active_texgens = value as u8,
         Reg::TexGen0 => xf.texgen[0].base = BaseTexGen::from_bits(value),
         Reg::TexGen1 => xf.texgen[1].base = BaseTexGen::from_bits(value),
@@ -55,20 +55,42 @@ fn decode_ipl(ipl: &mut [u8]) {
     }
 }
 
+/// Start of the scrambled part of the IPL, which contains BS1 and BS2. Only the copyright header
+/// before it is plain.
+const SCRAMBLED_START: usize = 0x0000_0100;
+
+/// End of the scrambled part of the IPL, where the fonts start. The same for every region and
+/// revision, since the BS2 of each ends before it and the keystream doesn't depend on the data.
+const SCRAMBLED_END: usize = 0x001A_FF00;
+
+/// Start of the copyright header every IPL begins with.
+const HEADER_PREFIX: &str = "(C) 1999";
+
 pub struct Ipl(Vec<u8>);
 
 impl Ipl {
+    /// Creates an IPL from the given ROM dump, descrambling it. Data that is not an IPL (e.g. a
+    /// zeroed placeholder when no IPL is given) is kept as is.
     pub fn new(mut data: Vec<u8>) -> Self {
         assert_eq!(data.len(), mem::IPL_LEN);
 
-        let ipl_message = CStr::from_bytes_until_nul(&data).unwrap();
-        let pal_message = "(C) 1999-2001 Nintendo.  All rights reserved.(C) 1999 ArtX Inc.  All rights reserved.PAL  Revision 1.0  ";
-        if ipl_message.to_str().is_ok_and(|s| s == pal_message) {
-            tracing::info!("IPL was detected as EU/PAL.");
-            decode_ipl(&mut data[0x0000_0100..0x001A_EEE8]);
-        } else {
-            tracing::info!("IPL was not detected as EU/PAL. Assuming USA/NTSC.");
-            decode_ipl(&mut data[0x0000_0100..0x0015_EE40]);
+        let header = CStr::from_bytes_until_nul(&data[..SCRAMBLED_START])
+            .ok()
+            .and_then(|header| header.to_str().ok())
+            .filter(|header| header.starts_with(HEADER_PREFIX));
+
+        match header {
+            Some(header) => {
+                let region = if header.contains("PAL") {
+                    "EU/PAL"
+                } else {
+                    "USA/NTSC"
+                };
+
+                tracing::info!("IPL was detected as {region}: {}", header.trim_end());
+                decode_ipl(&mut data[SCRAMBLED_START..SCRAMBLED_END]);
+            }
+            None => tracing::debug!("no IPL header found, not descrambling"),
         }
 
         Self(data)
@@ -88,3 +110,33 @@ impl DerefMut for Ipl {
         &mut self.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placeholder_is_kept() {
+        let ipl = Ipl::new(vec![0; mem::IPL_LEN]);
+        assert!(ipl.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn descrambling_is_an_involution() {
+        let header = b"(C) 1999-2001 Nintendo.  All rights reserved.";
+        let mut data = vec![0; mem::IPL_LEN];
+        data[..header.len()].copy_from_slice(header);
+
+        let scrambled = Ipl::new(data.clone());
+        assert_eq!(scrambled[..SCRAMBLED_START], data[..SCRAMBLED_START]);
+        assert!(
+            scrambled[SCRAMBLED_START..SCRAMBLED_END]
+                .iter()
+                .any(|&b| b != 0)
+        );
+        assert!(scrambled[SCRAMBLED_END..].iter().all(|&b| b == 0));
+
+        let descrambled = Ipl::new(scrambled.to_vec());
+        assert_eq!(*descrambled, *data);
+    }
+}
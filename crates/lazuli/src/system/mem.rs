@@ -1,6 +1,8 @@
 //! Memory of the system.
 use std::alloc::Layout;
+use std::ops::{Range, RangeInclusive};
 use std::ptr::NonNull;
+use std::sync::Arc;
 
 use bitos::BitUtils;
 use gekko::{Address, Bat, MemoryManagement};
@@ -11,6 +13,33 @@ pub const RAM_LEN: usize = 24 * bytesize::MIB as usize;
 pub const L2C_LEN: usize = 16 * bytesize::KIB as usize;
 pub const IPL_LEN: usize = 2 * bytesize::MIB as usize;
 
+/// Length of the chunks RAM is split into to track which parts of it were written to since the
+/// last snapshot. It's the size of a fastmem page.
+pub const RAM_CHUNK_LEN: usize = 1 << 17;
+/// How many chunks RAM is split into. See [`RAM_CHUNK_LEN`].
+pub const RAM_CHUNKS: usize = RAM_LEN / RAM_CHUNK_LEN;
+
+/// Fills a memory region as it is at power-on: zeroed if `seed` is `None`, otherwise with a
+/// pseudo-random pattern derived from the seed. The same seed always gives the same pattern.
+pub fn power_on_fill(region: &mut [u8], seed: Option<u64>) {
+    let Some(seed) = seed else {
+        region.fill(0);
+        return;
+    };
+
+    // splitmix64
+    let mut state = seed;
+    for chunk in region.chunks_mut(8) {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        chunk.copy_from_slice(&z.to_be_bytes()[..chunk.len()]);
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct PageTranslation(u16);
@@ -43,6 +72,101 @@ const PAGES_COUNT: usize = 1 << 15;
 type TranslationLut = [PageTranslation; PAGES_COUNT];
 type FastmemLut = [Option<NonNull<u8>>; PAGES_COUNT];
 
+/// The fastmem LUTs of an address space. Stores use a LUT of their own so that pages of RAM chunks
+/// which are clean since the last snapshot can be left out of it: stores to them then take the
+/// slow path, which marks the chunk dirty and adds its pages back.
+#[repr(C)]
+pub struct FastmemLuts {
+    pub loads: FastmemLut,
+    pub stores: FastmemLut,
+}
+
+impl FastmemLuts {
+    fn new() -> Box<Self> {
+        let luts: Box<[Option<NonNull<u8>>; 2 * PAGES_COUNT]> = util::boxed_array(None);
+        // SAFETY: FastmemLuts is repr(C) and made of two FastmemLuts, so it has the same layout
+        unsafe { Box::from_raw(Box::into_raw(luts).cast()) }
+    }
+
+    /// Fills the store LUT from the load LUT, leaving out the pages of clean RAM chunks.
+    fn sync_stores(&mut self, ram: *mut u8, dirty: &DirtyChunks) {
+        for (load, store) in self.loads.iter().zip(self.stores.iter_mut()) {
+            *store = load.filter(|&ptr| ram_chunk_of(ram, ptr).is_none_or(|c| dirty.get(c)));
+        }
+    }
+
+    /// Adds the page back to the store LUT if it maps the given RAM chunk, which must be dirty.
+    fn sync_chunk_store(&mut self, ram: *mut u8, page: usize, chunk: usize) {
+        let load = self.loads[page];
+        if load.is_some_and(|ptr| ram_chunk_of(ram, ptr) == Some(chunk)) {
+            self.stores[page] = load;
+        }
+    }
+}
+
+/// Returns the RAM chunk `ptr` points into, if any.
+fn ram_chunk_of(ram: *mut u8, ptr: NonNull<u8>) -> Option<usize> {
+    let offset = (ptr.as_ptr() as usize).wrapping_sub(ram as usize);
+    (offset < RAM_LEN).then_some(offset / RAM_CHUNK_LEN)
+}
+
+/// Bitmap of the RAM chunks written to since the last snapshot.
+#[derive(Default)]
+struct DirtyChunks([u64; RAM_CHUNKS.div_ceil(64)]);
+
+impl DirtyChunks {
+    fn get(&self, chunk: usize) -> bool {
+        self.0[chunk / 64] & (1 << (chunk % 64)) != 0
+    }
+
+    /// Marks the chunk as dirty. Returns whether it was clean.
+    fn set(&mut self, chunk: usize) -> bool {
+        let was_clean = !self.get(chunk);
+        self.0[chunk / 64] |= 1 << (chunk % 64);
+        was_clean
+    }
+
+    fn clear(&mut self) {
+        self.0.fill(0);
+    }
+}
+
+/// A copy of RAM taken by [`Memory::snapshot`]. Chunks which were not written to between two
+/// snapshots are shared by them, so snapshots are cheap to keep around.
+#[derive(Clone)]
+pub struct RamSnapshot {
+    chunks: Box<[Arc<[u8]>]>,
+}
+
+impl RamSnapshot {
+    /// The chunks of the snapshot, in order. Each one is [`RAM_CHUNK_LEN`] bytes long.
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.iter().map(|chunk| &**chunk)
+    }
+
+    /// Copies `buf.len()` bytes of the snapshot starting at `offset` into `buf`.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) {
+        assert!(offset + buf.len() <= RAM_LEN);
+
+        let mut offset = offset;
+        let mut out = buf;
+        while !out.is_empty() {
+            let chunk = &self.chunks[offset / RAM_CHUNK_LEN][offset % RAM_CHUNK_LEN..];
+            let len = chunk.len().min(out.len());
+            let (dst, rest) = out.split_at_mut(len);
+            dst.copy_from_slice(&chunk[..len]);
+
+            offset += len;
+            out = rest;
+        }
+    }
+
+    /// Returns the whole snapshot as a contiguous copy of RAM.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.chunks.concat()
+    }
+}
+
 enum Region {
     Ram,
     L2c,
@@ -55,6 +179,79 @@ pub const L2C_START: u32 = 0xE000_0000;
 pub const L2C_END: u32 = L2C_START + L2C_LEN as u32 - 1;
 pub const IPL_START: u32 = 0xFFF0_0000;
 pub const IPL_END: u32 = IPL_START + (IPL_LEN as u32 / 2 - 1);
+pub const EFB_START: u32 = 0x0800_0000;
+pub const EFB_END: u32 = 0x083F_FFFF;
+pub const MMIO_START: u32 = 0x0C00_0000;
+pub const MMIO_END: u32 = MMIO_START + 0xFFFF;
+
+/// The kind of a region in the physical address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemRegionKind {
+    /// Main memory.
+    Ram,
+    /// L2 cache locked as scratchpad memory.
+    LockedCache,
+    /// Memory mapped hardware registers.
+    Mmio,
+    /// Direct access to the embedded framebuffer.
+    Efb,
+    /// The IPL ROM.
+    Rom,
+}
+
+/// A region in the physical address space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemRegion {
+    pub name: &'static str,
+    pub range: RangeInclusive<u32>,
+    pub kind: MemRegionKind,
+}
+
+impl MemRegion {
+    /// Whether accesses to this region are actually emulated. EFB peeks and pokes are not, so
+    /// they read as zero and discard writes.
+    pub fn is_backed(&self) -> bool {
+        self.kind != MemRegionKind::Efb
+    }
+}
+
+/// The physical memory map of the system, ordered by address.
+///
+/// ARAM is not part of it, since it is only reachable through DSP DMA.
+pub static MEMORY_MAP: [MemRegion; 5] = [
+    MemRegion {
+        name: "RAM",
+        range: RAM_START..=RAM_END,
+        kind: MemRegionKind::Ram,
+    },
+    MemRegion {
+        name: "EFB",
+        range: EFB_START..=EFB_END,
+        kind: MemRegionKind::Efb,
+    },
+    MemRegion {
+        name: "MMIO",
+        range: MMIO_START..=MMIO_END,
+        kind: MemRegionKind::Mmio,
+    },
+    MemRegion {
+        name: "L2 Cache",
+        range: L2C_START..=L2C_END,
+        kind: MemRegionKind::LockedCache,
+    },
+    MemRegion {
+        name: "IPL",
+        range: IPL_START..=IPL_END,
+        kind: MemRegionKind::Rom,
+    },
+];
+
+/// Returns the region of the memory map containing the given physical address, if any.
+pub fn region_of(addr: Address) -> Option<&'static MemRegion> {
+    MEMORY_MAP
+        .iter()
+        .find(|region| region.range.contains(&addr.value()))
+}
 
 impl Region {
     fn of(addr: Address) -> Option<(Self, u32)> {
@@ -68,9 +265,68 @@ impl Region {
     }
 }
 
+/// A contiguous part of a physical range. See [`Memory::chunks`].
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'mem> {
+    /// Physical address of the start of the chunk.
+    pub address: Address,
+    /// Length of the chunk, in bytes.
+    pub len: u32,
+    /// The memory backing the chunk, if any. Chunks in MMIO, the EFB or unmapped holes are not
+    /// backed, since reading them either has side effects or is not emulated.
+    pub data: Option<&'mem [u8]>,
+}
+
+/// Iterator over the chunks of a physical range. See [`Memory::chunks`].
+pub struct Chunks<'mem> {
+    memory: &'mem Memory,
+    address: u32,
+    remaining: u32,
+}
+
+impl<'mem> Iterator for Chunks<'mem> {
+    type Item = Chunk<'mem>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let address = Address(self.address);
+        let (len, data) = match Region::of(address) {
+            Some((region, offset)) => {
+                let (backing, end) = match region {
+                    Region::Ram => (self.memory.ram(), RAM_END),
+                    Region::L2c => (self.memory.l2c(), L2C_END),
+                    Region::Ipl => (self.memory.ipl(), IPL_END),
+                };
+
+                let len = (end - self.address).saturating_add(1).min(self.remaining);
+                (len, Some(&backing[offset as usize..][..len as usize]))
+            }
+            None => {
+                // up to the next backed region, which always exists since the IPL is at the end
+                // of the address space
+                let next = [RAM_START, L2C_START, IPL_START]
+                    .into_iter()
+                    .filter(|&start| start > self.address)
+                    .min()
+                    .unwrap();
+
+                ((next - self.address).min(self.remaining), None)
+            }
+        };
+
+        self.address = self.address.wrapping_add(len);
+        self.remaining -= len;
+
+        Some(Chunk { address, len, data })
+    }
+}
+
 pub struct Regions<'mem> {
-    pub ram: &'mem mut [u8],
-    pub l2c: &'mem mut [u8],
+    pub ram: &'mem [u8],
+    pub l2c: &'mem [u8],
     pub ipl: &'mem [u8],
 }
 
@@ -79,10 +335,17 @@ pub struct Memory {
     l2c: NonNull<u8>,
     ipl: NonNull<u8>,
 
-    data_fastmem_lut_physical: Box<FastmemLut>,
-    data_fastmem_lut_logical: Box<FastmemLut>,
+    data_fastmem_lut_physical: Box<FastmemLuts>,
+    data_fastmem_lut_logical: Box<FastmemLuts>,
     data_translation_lut: Box<TranslationLut>,
     inst_translation_lut: Box<TranslationLut>,
+    /// The physical pages mapped by each enabled DBAT, along with the logical page the first one
+    /// is mapped to. Used to find the logical pages of a RAM chunk.
+    data_bat_pages: Vec<(u32, RangeInclusive<u32>)>,
+
+    dirty: DirtyChunks,
+    /// The chunks of the last snapshot.
+    snapshot: Box<[Arc<[u8]>]>,
 }
 
 fn update_fastmem_lut(
@@ -157,7 +420,9 @@ fn update_translation_lut_with(translation: &mut TranslationLut, bat: &Bat) {
 }
 
 impl Memory {
-    pub fn new(ipl_data: &Ipl) -> Self {
+    /// Creates the memory with the given IPL contents. RAM and the L2C are filled with
+    /// [`power_on_fill`].
+    pub fn new(ipl_data: &Ipl, fill_seed: Option<u64>) -> Self {
         let alloc = |len| {
             NonNull::new(unsafe { std::alloc::alloc(Layout::array::<u8>(len).unwrap()) }).unwrap()
         };
@@ -170,24 +435,51 @@ impl Memory {
             std::ptr::copy_nonoverlapping(ipl_data.as_ptr(), ipl.as_ptr(), IPL_LEN);
         }
 
-        let mut data_fastmem_lut_physical = util::boxed_array(None);
+        let mut data_fastmem_lut_physical = FastmemLuts::new();
         update_fastmem_lut_physical(
             ram.as_ptr(),
             l2c.as_ptr(),
             ipl.as_ptr(),
-            &mut data_fastmem_lut_physical,
+            &mut data_fastmem_lut_physical.loads,
         );
 
-        Self {
+        // every chunk starts dirty, so the first snapshot replaces these
+        let empty_chunk: Arc<[u8]> = Arc::from(vec![0; RAM_CHUNK_LEN]);
+
+        let mut memory = Self {
             ram,
             l2c,
             ipl,
 
             data_fastmem_lut_physical,
-            data_fastmem_lut_logical: util::boxed_array(None),
+            data_fastmem_lut_logical: FastmemLuts::new(),
             data_translation_lut: util::boxed_array(PageTranslation::NO_MAPPING),
             inst_translation_lut: util::boxed_array(PageTranslation::NO_MAPPING),
-        }
+            data_bat_pages: Vec::new(),
+
+            dirty: DirtyChunks::default(),
+            snapshot: vec![empty_chunk; RAM_CHUNKS].into_boxed_slice(),
+        };
+
+        // allocations are uninitialized, so this must happen before anything reads them. it also
+        // marks all of RAM dirty
+        power_on_fill(memory.ram_mut(), fill_seed);
+        power_on_fill(memory.l2c_mut(), fill_seed);
+        memory.sync_store_luts();
+        memory
+    }
+
+    /// Resets memory to its power-on state: RAM and the L2C are filled with [`power_on_fill`]
+    /// and every logical mapping is removed. The IPL is kept as is.
+    pub fn reset(&mut self, fill_seed: Option<u64>) {
+        power_on_fill(self.ram_mut(), fill_seed);
+        power_on_fill(self.l2c_mut(), fill_seed);
+
+        self.data_fastmem_lut_logical.loads.fill(None);
+        self.data_fastmem_lut_logical.stores.fill(None);
+        self.data_translation_lut.fill(PageTranslation::NO_MAPPING);
+        self.inst_translation_lut.fill(PageTranslation::NO_MAPPING);
+        self.data_bat_pages.clear();
     }
 
     #[inline(always)]
@@ -195,9 +487,121 @@ impl Memory {
         unsafe { std::slice::from_raw_parts(self.ram.as_ptr(), RAM_LEN) }
     }
 
+    /// Returns RAM for writing, marking all of it dirty. Prefer [`Self::ram_range_mut`] when the
+    /// written range is known, so that the next snapshot doesn't copy all of RAM.
     #[inline(always)]
     pub fn ram_mut(&mut self) -> &mut [u8] {
-        unsafe { std::slice::from_raw_parts_mut(self.ram.as_ptr(), RAM_LEN) }
+        self.ram_range_mut(0..RAM_LEN)
+    }
+
+    /// Returns the given range of RAM for writing, marking it dirty.
+    #[inline(always)]
+    pub fn ram_range_mut(&mut self, range: Range<usize>) -> &mut [u8] {
+        self.mark_ram_dirty(range.clone());
+        let ram = unsafe { std::slice::from_raw_parts_mut(self.ram.as_ptr(), RAM_LEN) };
+        &mut ram[range]
+    }
+
+    /// Lends RAM for writing to `f`, which returns the range it wrote to, if any. Only that range
+    /// is marked dirty, so this is meant for writes whose range isn't known upfront.
+    #[inline(always)]
+    pub fn write_ram<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut [u8]) -> Option<Range<usize>>,
+    {
+        let ram = unsafe { std::slice::from_raw_parts_mut(self.ram.as_ptr(), RAM_LEN) };
+        if let Some(written) = f(ram) {
+            self.mark_ram_dirty(written);
+        }
+    }
+
+    /// Copies `len` bytes of the L2C starting at `l2c_offset` into RAM at `ram_offset`, marking
+    /// them dirty.
+    pub fn copy_l2c_to_ram(&mut self, l2c_offset: usize, ram_offset: usize, len: usize) {
+        let l2c = unsafe { std::slice::from_raw_parts(self.l2c.as_ptr(), L2C_LEN) };
+        self.ram_range_mut(ram_offset..ram_offset + len)
+            .copy_from_slice(&l2c[l2c_offset..][..len]);
+    }
+
+    /// Copies `len` bytes of RAM starting at `ram_offset` into the L2C at `l2c_offset`.
+    pub fn copy_ram_to_l2c(&mut self, ram_offset: usize, l2c_offset: usize, len: usize) {
+        let ram = unsafe { std::slice::from_raw_parts(self.ram.as_ptr(), RAM_LEN) };
+        self.l2c_mut()[l2c_offset..][..len].copy_from_slice(&ram[ram_offset..][..len]);
+    }
+
+    /// Marks the given range of RAM as written to, so that the next snapshot copies it. Only
+    /// needed for writes which don't go through [`Self::ram_range_mut`], [`Self::write_ram`] or
+    /// fastmem.
+    pub fn mark_ram_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let first = range.start / RAM_CHUNK_LEN;
+        let last = ((range.end - 1) / RAM_CHUNK_LEN).min(RAM_CHUNKS - 1);
+        for chunk in first..=last {
+            if self.dirty.set(chunk) {
+                self.sync_chunk_stores(chunk);
+            }
+        }
+    }
+
+    /// Adds the pages of a newly dirtied RAM chunk back to the store LUTs. Chunks are as large
+    /// as pages, so the chunk has a single physical page and a logical one for each DBAT mapping
+    /// it.
+    ///
+    /// Only physical and DBAT pages are restored because they are the only ones fastmem ever maps.
+    /// Addresses translated through the page table are never in the logical LUTs, so stores to
+    /// them always take the slow path, which marks the chunks it writes to dirty through
+    /// [`Self::ram_range_mut`].
+    fn sync_chunk_stores(&mut self, chunk: usize) {
+        let ram = self.ram.as_ptr();
+        let physical = (RAM_START >> 17) + chunk as u32;
+        self.data_fastmem_lut_physical
+            .sync_chunk_store(ram, physical as usize, chunk);
+
+        for (logical, physical_pages) in &self.data_bat_pages {
+            if physical_pages.contains(&physical) {
+                let page = logical + (physical - physical_pages.start());
+                self.data_fastmem_lut_logical
+                    .sync_chunk_store(ram, page as usize, chunk);
+            }
+        }
+    }
+
+    fn sync_store_luts(&mut self) {
+        let ram = self.ram.as_ptr();
+        self.data_fastmem_lut_physical.sync_stores(ram, &self.dirty);
+        self.data_fastmem_lut_logical.sync_stores(ram, &self.dirty);
+    }
+
+    /// Takes a snapshot of RAM. Only the chunks written to since the last snapshot are copied, the
+    /// others are shared with it, so this takes time proportional to how much RAM was dirtied.
+    ///
+    /// Nothing takes snapshots yet: this is groundwork for savestates and rewind, neither of
+    /// which exists so far. Until then every chunk stays dirty, as they all start out, so no store
+    /// is diverted from fastmem.
+    pub fn snapshot(&mut self) -> RamSnapshot {
+        let ram = unsafe { std::slice::from_raw_parts(self.ram.as_ptr(), RAM_LEN) };
+        for (index, chunk) in self.snapshot.iter_mut().enumerate() {
+            if !self.dirty.get(index) {
+                continue;
+            }
+
+            let data = &ram[index * RAM_CHUNK_LEN..][..RAM_CHUNK_LEN];
+            match Arc::get_mut(chunk) {
+                // no snapshot holds on to the old copy anymore, so reuse it
+                Some(old) => old.copy_from_slice(data),
+                None => *chunk = Arc::from(data),
+            }
+        }
+
+        self.dirty.clear();
+        self.sync_store_luts();
+
+        RamSnapshot {
+            chunks: self.snapshot.clone(),
+        }
     }
 
     #[inline(always)]
@@ -217,33 +621,87 @@ impl Memory {
 
     #[inline(always)]
     pub fn regions(&self) -> Regions<'_> {
-        let ram = unsafe { std::slice::from_raw_parts_mut(self.ram.as_ptr(), RAM_LEN) };
-        let l2c = unsafe { std::slice::from_raw_parts_mut(self.l2c.as_ptr(), L2C_LEN) };
-        let ipl = unsafe { std::slice::from_raw_parts(self.ipl.as_ptr(), IPL_LEN) };
+        Regions {
+            ram: self.ram(),
+            l2c: self.l2c(),
+            ipl: self.ipl(),
+        }
+    }
+
+    /// Splits the physical range of `len` bytes starting at `address` into chunks which are
+    /// either entirely backed by a single memory region or entirely unbacked. Ranges wrap around
+    /// the end of the address space.
+    pub fn chunks(&self, address: Address, len: u32) -> Chunks<'_> {
+        Chunks {
+            memory: self,
+            address: address.value(),
+            remaining: len,
+        }
+    }
+
+    /// Returns the memory backing the physical range of `len` bytes starting at `address`, but
+    /// only if it is entirely contained in a single memory region.
+    pub fn slice(&self, address: Address, len: u32) -> Option<&[u8]> {
+        if len == 0 {
+            return Some(&[]);
+        }
+
+        let chunk = self.chunks(address, len).next()?;
+        chunk.data.filter(|_| chunk.len == len)
+    }
+
+    /// Copies the physical range starting at `address` into `buf`. Unbacked bytes (see
+    /// [`Chunk::data`]) are zeroed. Returns how many bytes were backed.
+    pub fn copy_to_host(&self, address: Address, buf: &mut [u8]) -> usize {
+        let len = u32::try_from(buf.len()).expect("buffer fits in the address space");
+
+        let mut backed = 0;
+        let mut out = buf;
+        for chunk in self.chunks(address, len) {
+            let (dst, rest) = out.split_at_mut(chunk.len as usize);
+            match chunk.data {
+                Some(data) => {
+                    dst.copy_from_slice(data);
+                    backed += data.len();
+                }
+                None => dst.fill(0),
+            }
 
-        Regions { ram, l2c, ipl }
+            out = rest;
+        }
+
+        backed
     }
 
     pub fn build_data_bat_lut(&mut self, dbats: &[Bat; 4]) {
         let _span = tracing::info_span!("building dbat lut").entered();
 
-        self.data_fastmem_lut_logical.fill(None);
+        self.data_fastmem_lut_logical.loads.fill(None);
         self.data_translation_lut.fill(PageTranslation::NO_MAPPING);
+        self.data_bat_pages.clear();
         for (i, bat) in dbats.iter().enumerate() {
             if !bat.supervisor_mode() {
                 tracing::warn!("dbat{i} is disabled in supervisor mode");
                 continue;
             }
 
+            self.data_bat_pages.push((
+                bat.start().value() >> 17,
+                (bat.physical_start().value() >> 17)..=(bat.physical_end().value() >> 17),
+            ));
+
             update_translation_lut_with(&mut self.data_translation_lut, bat);
             update_fastmem_lut_with_bat(
                 self.ram.as_ptr(),
                 self.l2c.as_ptr(),
                 self.ipl.as_ptr(),
-                &mut self.data_fastmem_lut_logical,
+                &mut self.data_fastmem_lut_logical.loads,
                 bat,
             );
         }
+
+        self.data_fastmem_lut_logical
+            .sync_stores(self.ram.as_ptr(), &self.dirty);
     }
 
     pub fn build_instr_bat_lut(&mut self, ibats: &[Bat; 4]) {
@@ -290,19 +748,20 @@ impl Memory {
             .map(Into::into)
     }
 
-    /// Returns the fastmem LUT.
+    /// Returns the fastmem LUTs of the logical address space.
     #[inline(always)]
-    pub fn data_fastmem_lut_logical(&self) -> &FastmemLut {
+    pub fn data_fastmem_lut_logical(&self) -> &FastmemLuts {
         &self.data_fastmem_lut_logical
     }
 
-    /// Returns the fastmem LUT.
+    /// Returns the fastmem LUTs of the physical address space.
     #[inline(always)]
-    pub fn data_fastmem_lut_physical(&self) -> &FastmemLut {
+    pub fn data_fastmem_lut_physical(&self) -> &FastmemLuts {
         &self.data_fastmem_lut_physical
     }
 }
 
+unsafe impl Send for FastmemLuts {}
 unsafe impl Send for Memory {}
 
 impl Drop for Memory {
@@ -316,3 +775,202 @@ impl Drop for Memory {
         dealloc(self.ipl, IPL_LEN);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn power_on_fill_is_deterministic() {
+        let fill = |seed| {
+            let mut region = vec![0xAA; 37];
+            power_on_fill(&mut region, seed);
+            region
+        };
+
+        assert!(fill(None).iter().all(|&b| b == 0));
+        assert_eq!(fill(Some(1)), fill(Some(1)));
+        assert_ne!(fill(Some(1)), fill(Some(2)));
+        assert!(fill(Some(1)).iter().any(|&b| b != 0));
+    }
+
+    fn memory() -> Memory {
+        let mut memory = Memory::new(&Ipl::new(vec![0; IPL_LEN]), None);
+        for (i, byte) in memory.ram_mut().iter_mut().enumerate() {
+            *byte = i as u8 | 1;
+        }
+
+        memory
+    }
+
+    #[test]
+    fn chunks_cross_the_ram_end() {
+        let memory = memory();
+        let start = Address(RAM_END - 3);
+        let chunks = memory.chunks(start, 8).collect::<Vec<_>>();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].address, start);
+        assert_eq!(chunks[0].data, Some(&memory.ram()[RAM_LEN - 4..]));
+        assert_eq!(chunks[1].address, Address(RAM_END + 1));
+        assert_eq!((chunks[1].len, chunks[1].data), (4, None));
+
+        assert!(memory.slice(start, 4).is_some());
+        assert!(memory.slice(start, 5).is_none());
+
+        let mut buf = [0xFF; 8];
+        assert_eq!(memory.copy_to_host(start, &mut buf), 4);
+        assert_eq!(buf[..4], memory.ram()[RAM_LEN - 4..]);
+        assert_eq!(buf[4..], [0; 4]);
+    }
+
+    #[test]
+    fn chunks_skip_holes() {
+        let memory = memory();
+
+        // MMIO is never backed, and the hole after it extends up to the L2C
+        let chunks = memory
+            .chunks(Address(MMIO_START), 0x100)
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].len, chunks[0].data), (0x100, None));
+        assert!(memory.slice(Address(MMIO_START), 4).is_none());
+
+        let chunks = memory
+            .chunks(Address(L2C_START - 2), 4)
+            .map(|chunk| (chunk.address, chunk.len, chunk.data.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            [
+                (Address(L2C_START - 2), 2, false),
+                (Address(L2C_START), 2, true)
+            ]
+        );
+
+        // ranges wrap around the end of the address space, right after the IPL
+        let chunks = memory
+            .chunks(Address(IPL_END - 1), 4)
+            .map(|chunk| (chunk.address, chunk.len, chunk.data.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            [
+                (Address(IPL_END - 1), 2, true),
+                (Address(RAM_START), 2, true)
+            ]
+        );
+
+        assert_eq!(memory.slice(Address(0x100), 0), Some(&[][..]));
+    }
+
+    #[test]
+    fn snapshots_only_copy_dirty_chunks() {
+        let mut memory = memory();
+        let first = memory.snapshot();
+        assert_eq!(first.to_vec(), memory.ram());
+
+        let crossing = 5 * RAM_CHUNK_LEN - 2;
+        memory.ram_range_mut(0x10..0x14).fill(0xEE);
+        memory.ram_range_mut(crossing..crossing + 4).fill(0xEE);
+
+        let second = memory.snapshot();
+        assert_eq!(second.to_vec(), memory.ram());
+
+        // the first snapshot is not affected by writes after it
+        let mut buf = [0; 4];
+        first.read(0x10, &mut buf);
+        assert_eq!(buf, [0x11, 0x11, 0x13, 0x13]);
+        second.read(crossing, &mut buf);
+        assert_eq!(buf, [0xEE; 4]);
+
+        for index in 0..RAM_CHUNKS {
+            let shared = Arc::ptr_eq(&first.chunks[index], &second.chunks[index]);
+            assert_eq!(shared, ![0, 4, 5].contains(&index), "chunk {index}");
+        }
+
+        // nothing was written since the last snapshot
+        let third = memory.snapshot();
+        assert!(
+            (0..RAM_CHUNKS).all(|index| Arc::ptr_eq(&second.chunks[index], &third.chunks[index]))
+        );
+    }
+
+    #[test]
+    fn snapshots_stay_consistent_across_writes() {
+        let mut memory = memory();
+        let mut expected = memory.ram().to_vec();
+        let mut snapshots = vec![(memory.snapshot(), expected.clone())];
+
+        // dropped snapshots let their chunks be reused, kept ones must not see it
+        for round in 0..8u8 {
+            let start = (round as usize % 3 + 1) * RAM_CHUNK_LEN - 1;
+            memory.ram_range_mut(start..start + 2).fill(round);
+            expected[start..start + 2].fill(round);
+
+            let snapshot = memory.snapshot();
+            if round % 2 == 0 {
+                snapshots.push((snapshot, expected.clone()));
+            }
+        }
+
+        for (snapshot, expected) in snapshots {
+            assert!(snapshot.to_vec() == expected);
+        }
+    }
+
+    #[test]
+    fn stores_to_clean_chunks_take_the_slow_path() {
+        let mut memory = memory();
+        let mut mmu = MemoryManagement::default();
+        mmu.setup_default_bats();
+        memory.build_data_bat_lut(&mmu.dbat);
+
+        // every chunk starts dirty
+        let physical = memory.data_fastmem_lut_physical();
+        assert_eq!(physical.stores, physical.loads);
+
+        memory.snapshot();
+        let page = |base: u32| (base >> 17) as usize + 3;
+        for luts in [
+            memory.data_fastmem_lut_physical(),
+            memory.data_fastmem_lut_logical(),
+        ] {
+            for base in [RAM_START, 0x8000_0000, 0xC000_0000] {
+                assert!(luts.stores[page(base)].is_none());
+            }
+
+            // only RAM is tracked
+            assert_eq!(luts.stores[page(L2C_START)], luts.loads[page(L2C_START)]);
+        }
+
+        memory.mark_ram_dirty(3 * RAM_CHUNK_LEN..3 * RAM_CHUNK_LEN + 1);
+        let logical = memory.data_fastmem_lut_logical();
+        assert!(logical.stores[page(0x8000_0000)].is_some());
+        assert!(logical.stores[page(0xC000_0000)].is_some());
+        assert!(logical.stores[page(0x8000_0000) + 1].is_none());
+        assert!(memory.data_fastmem_lut_physical().stores[page(RAM_START)].is_some());
+
+        // only the pages of the dirtied chunks are synced, but the result is the same as a full
+        // sync
+        memory.write_ram(|ram| {
+            ram[9 * RAM_CHUNK_LEN] = 0xEE;
+            Some(9 * RAM_CHUNK_LEN..9 * RAM_CHUNK_LEN + 1)
+        });
+        memory.copy_l2c_to_ram(0, 11 * RAM_CHUNK_LEN - 2, 4);
+
+        let stores = |memory: &Memory| {
+            (
+                memory.data_fastmem_lut_physical().stores.to_vec(),
+                memory.data_fastmem_lut_logical().stores.to_vec(),
+            )
+        };
+        let synced = stores(&memory);
+        memory.sync_store_luts();
+        assert!(synced == stores(&memory));
+        assert!(synced.1[page(0x8000_0000) + 6].is_some());
+        assert!(synced.1[page(0xC000_0000) + 8].is_some());
+        assert!(synced.1[page(0xC000_0000) + 7].is_some());
+        assert!(synced.1[page(0xC000_0000) + 5].is_none());
+    }
+}
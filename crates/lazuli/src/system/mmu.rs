@@ -0,0 +1,373 @@
+//! Page table translation, as done by the memory management unit (MMU) when an access is not
+//! covered by a BAT.
+//!
+//! The logical address space is split into 16 segments, selected by the upper 4 bits of the
+//! address. Each segment register holds a virtual segment ID (VSID) which, together with the page
+//! index, is hashed to locate a page table entry group (PTEG) in the hashed page table pointed to
+//! by SDR1. Translations are cached in a software TLB, which is invalidated by `tlbie` and by
+//! changes to SDR1 or to the segment registers.
+use bitos::BitUtils;
+use gekko::Address;
+
+use crate::system::System;
+use crate::system::mem::RAM_LEN;
+
+/// Kind of access being translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Fetch,
+}
+
+/// Reason why a page table translation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// No entry for the page exists in either the primary or the secondary PTEG.
+    NoEntry,
+    /// An entry exists, but its protection bits do not allow the access.
+    Protection,
+    /// The segment is a direct-store segment or, for fetches, a no-execute segment.
+    Segment,
+}
+
+impl Fault {
+    /// Returns the DSISR value for a DSI exception caused by this fault.
+    pub fn dsisr(self, write: bool) -> u32 {
+        let cause = match self {
+            Self::NoEntry => 1 << 30,
+            Self::Protection => 1 << 27,
+            Self::Segment => 1 << 26,
+        };
+
+        cause | ((write as u32) << 25)
+    }
+
+    /// Returns the exception specific SRR1 bits for an ISI exception caused by this fault.
+    pub fn isi_srr1(self) -> u32 {
+        match self {
+            Self::NoEntry => 1 << 30,
+            Self::Protection => 1 << 27,
+            Self::Segment => 1 << 28,
+        }
+    }
+}
+
+/// A segment register.
+#[derive(Debug, Clone, Copy)]
+struct Segment(u32);
+
+impl Segment {
+    fn direct_store(self) -> bool {
+        self.0.bit(31)
+    }
+
+    fn supervisor_key(self) -> bool {
+        self.0.bit(30)
+    }
+
+    fn user_key(self) -> bool {
+        self.0.bit(29)
+    }
+
+    fn no_execute(self) -> bool {
+        self.0.bit(28)
+    }
+
+    fn vsid(self) -> u32 {
+        self.0.bits(0, 24)
+    }
+}
+
+/// A page table entry, as found in a PTEG.
+#[derive(Debug, Clone, Copy)]
+struct Pte {
+    upper: u32,
+    lower: u32,
+}
+
+impl Pte {
+    const REFERENCED: u32 = 1 << 8;
+    const CHANGED: u32 = 1 << 7;
+
+    fn valid(&self) -> bool {
+        self.upper.bit(31)
+    }
+
+    fn vsid(&self) -> u32 {
+        self.upper.bits(7, 31)
+    }
+
+    fn secondary(&self) -> bool {
+        self.upper.bit(6)
+    }
+
+    fn api(&self) -> u32 {
+        self.upper.bits(0, 6)
+    }
+
+    fn rpn(&self) -> u32 {
+        self.lower & !0xFFF
+    }
+
+    fn protection(&self) -> u8 {
+        self.lower.bits(0, 2) as u8
+    }
+}
+
+/// Whether the given protection bits allow an access, given the key of the segment.
+fn allowed(protection: u8, key: bool, access: Access) -> bool {
+    let write = access == Access::Write;
+    match (key, protection) {
+        (false, 0..=2) => true,
+        (false, _) => !write,
+        (true, 0) => false,
+        (true, 2) => true,
+        (true, _) => !write,
+    }
+}
+
+/// A successful page table lookup.
+#[derive(Debug, Clone, Copy)]
+struct Lookup {
+    /// Physical address of the PTE.
+    pte_addr: u32,
+    pte: Pte,
+}
+
+/// Searches the page table for the entry of the given page.
+fn lookup(ram: &[u8], sdr1: u32, vsid: u32, page_index: u32) -> Option<Lookup> {
+    let htaborg = sdr1 & 0xFFFF_0000;
+    let htabmask = sdr1.bits(0, 9);
+    let primary = vsid.bits(0, 19) ^ page_index;
+    let api = page_index >> 10;
+
+    for (secondary, hash) in [(false, primary), (true, !primary)] {
+        let hash_upper = (hash.bits(10, 19) & htabmask) << 16;
+        let pteg = (htaborg | hash_upper) | (hash.bits(0, 10) << 6);
+
+        for i in 0..8 {
+            let pte_addr = pteg + i * 8;
+            let Some(bytes) = ram.get(pte_addr as usize..pte_addr as usize + 8) else {
+                return None;
+            };
+
+            let pte = Pte {
+                upper: u32::from_be_bytes(bytes[0..4].try_into().unwrap()),
+                lower: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+            };
+
+            let matches = pte.vsid() == vsid && pte.secondary() == secondary && pte.api() == api;
+            if pte.valid() && matches {
+                return Some(Lookup { pte_addr, pte });
+            }
+        }
+    }
+
+    None
+}
+
+const TLB_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct TlbEntry {
+    /// VSID and page index of the cached translation. The VSID is 24 bits wide, so this doesn't
+    /// fit in 32 bits.
+    tag: u64,
+    /// Physical address of the PTE this entry was loaded from.
+    pte_addr: u32,
+    rpn: u32,
+    protection: u8,
+    changed: bool,
+}
+
+/// A software TLB, caching page table translations. Like the Gekko TLBs, it has separate halves
+/// for data and instructions, indexed by the lower bits of the page index (i.e. the congruence
+/// class).
+pub struct Tlb {
+    data: [Option<TlbEntry>; TLB_LEN],
+    instr: [Option<TlbEntry>; TLB_LEN],
+}
+
+impl Default for Tlb {
+    fn default() -> Self {
+        Self {
+            data: [None; TLB_LEN],
+            instr: [None; TLB_LEN],
+        }
+    }
+}
+
+impl Tlb {
+    fn index(addr: Address) -> usize {
+        addr.value().bits(12, 18) as usize
+    }
+
+    /// Invalidates every entry in the congruence class of the given address, as `tlbie` does.
+    pub fn invalidate(&mut self, addr: Address) {
+        let index = Self::index(addr);
+        self.data[index] = None;
+        self.instr[index] = None;
+    }
+
+    /// Invalidates every entry.
+    pub fn invalidate_all(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl System {
+    fn segment(&self, addr: Address) -> Segment {
+        Segment(self.cpu.supervisor.memory.sr[addr.value().bits(28, 32) as usize])
+    }
+
+    fn segment_key(&self, segment: Segment) -> bool {
+        if self.cpu.supervisor.config.msr.user_mode() {
+            segment.user_key()
+        } else {
+            segment.supervisor_key()
+        }
+    }
+
+    /// Translates a logical address through the page table, without any side effects (i.e. the
+    /// TLB and the referenced/changed bits of the page table are left untouched).
+    pub fn page_translate(&self, addr: Address, access: Access) -> Result<Address, Fault> {
+        let segment = self.segment(addr);
+        if segment.direct_store() || (access == Access::Fetch && segment.no_execute()) {
+            return Err(Fault::Segment);
+        }
+
+        let page_index = addr.value().bits(12, 28);
+        let sdr1 = self.cpu.supervisor.memory.sdr1;
+        let lookup =
+            self::lookup(self.mem.ram(), sdr1, segment.vsid(), page_index).ok_or(Fault::NoEntry)?;
+
+        if !allowed(lookup.pte.protection(), self.segment_key(segment), access) {
+            return Err(Fault::Protection);
+        }
+
+        Ok(Address(lookup.pte.rpn() | addr.value().bits(0, 12)))
+    }
+
+    /// Translates a logical address through the page table, going through the TLB and updating
+    /// the referenced/changed bits of the page table entry.
+    pub fn page_translate_mut(&mut self, addr: Address, access: Access) -> Result<Address, Fault> {
+        let segment = self.segment(addr);
+        if segment.direct_store() || (access == Access::Fetch && segment.no_execute()) {
+            return Err(Fault::Segment);
+        }
+
+        let page_index = addr.value().bits(12, 28);
+        let tag = ((segment.vsid() as u64) << 16) | page_index as u64;
+        let index = Tlb::index(addr);
+        let key = self.segment_key(segment);
+        let write = access == Access::Write;
+
+        let cached = if access == Access::Fetch {
+            self.tlb.instr[index]
+        } else {
+            self.tlb.data[index]
+        };
+
+        let mut entry = match cached {
+            Some(entry) if entry.tag == tag => entry,
+            _ => {
+                let sdr1 = self.cpu.supervisor.memory.sdr1;
+                let lookup = self::lookup(self.mem.ram(), sdr1, segment.vsid(), page_index)
+                    .ok_or(Fault::NoEntry)?;
+
+                // the referenced bit is set on every table search
+                let mut lower = lookup.pte.lower;
+                if lower & Pte::REFERENCED == 0 {
+                    lower |= Pte::REFERENCED;
+                    self.write_pte_lower(lookup.pte_addr, lower);
+                }
+
+                TlbEntry {
+                    tag,
+                    pte_addr: lookup.pte_addr,
+                    rpn: lookup.pte.rpn(),
+                    protection: lookup.pte.protection(),
+                    changed: lower & Pte::CHANGED != 0,
+                }
+            }
+        };
+
+        if !allowed(entry.protection, key, access) {
+            return Err(Fault::Protection);
+        }
+
+        if write && !entry.changed {
+            let lower = self
+                .read_phys_pure::<u32>(Address(entry.pte_addr + 4))
+                .unwrap();
+            self.write_pte_lower(entry.pte_addr, lower | Pte::CHANGED);
+            entry.changed = true;
+        }
+
+        if access == Access::Fetch {
+            self.tlb.instr[index] = Some(entry);
+        } else {
+            self.tlb.data[index] = Some(entry);
+        }
+
+        Ok(Address(entry.rpn | addr.value().bits(0, 12)))
+    }
+
+    fn write_pte_lower(&mut self, pte_addr: u32, lower: u32) {
+        debug_assert!((pte_addr as usize) + 8 <= RAM_LEN);
+        let lower_addr = pte_addr as usize + 4;
+        self.mem
+            .ram_range_mut(lower_addr..lower_addr + 4)
+            .copy_from_slice(&lower.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::system::{BootMode, Config, Modules};
+
+    fn system() -> System {
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        System::new(Modules::nop(), config).unwrap()
+    }
+
+    /// Writes a valid, read/write primary PTE for the given page into the given slot of its PTEG,
+    /// assuming a 64 KiB page table at 0x0010_0000.
+    fn map(sys: &mut System, slot: u32, vsid: u32, page_index: u32, rpn: u32) {
+        let hash = vsid.bits(0, 19) ^ page_index;
+        let pteg = 0x0010_0000 | (hash.bits(0, 10) << 6);
+        let upper = (1 << 31) | (vsid << 7) | (page_index >> 10);
+
+        let pte_addr = Address(pteg + slot * 8);
+        sys.write_phys_slow::<u32>(pte_addr, upper);
+        sys.write_phys_slow::<u32>(pte_addr + 4u32, rpn | 0b10);
+    }
+
+    #[test]
+    fn tlb_distinguishes_high_vsid_bits() {
+        let mut sys = system();
+        sys.cpu.supervisor.memory.sdr1 = 0x0010_0000;
+
+        // both VSIDs hash to the same PTEG, and both addresses share a TLB congruence class
+        let (vsid_a, vsid_b) = (0x00_0001, 0x80_0001);
+        sys.cpu.supervisor.memory.sr[0] = vsid_a;
+        sys.cpu.supervisor.memory.sr[1] = vsid_b;
+        map(&mut sys, 0, vsid_a, 5, 0x0020_0000);
+        map(&mut sys, 1, vsid_b, 5, 0x0030_0000);
+
+        let mut translate = |addr| sys.page_translate_mut(Address(addr), Access::Read);
+        assert_eq!(translate(0x0000_5123), Ok(Address(0x0020_0123)));
+        assert_eq!(translate(0x1000_5123), Ok(Address(0x0030_0123)));
+        assert_eq!(translate(0x0000_5123), Ok(Address(0x0020_0123)));
+    }
+}
@@ -1,6 +1,6 @@
 //! Processor interface (PI).
-use bitos::bitos;
 use bitos::integer::u26;
+use bitos::{BitUtils, bitos};
 use gekko::{Address, Exception};
 
 use crate::Primitive;
@@ -103,6 +103,12 @@ pub struct Interface {
     // interrupts
     pub mask: InterruptMask,
 
+    // reset switch
+    /// Whether the reset switch is held down.
+    pub reset_switch: bool,
+    /// Whether the reset switch interrupt has been triggered and not yet acknowledged.
+    pub reset_switch_interrupt: bool,
+
     // fifo
     pub fifo_start: Address,
     pub fifo_end: Address,
@@ -116,6 +122,10 @@ impl Default for Interface {
     fn default() -> Self {
         Self {
             mask: Default::default(),
+
+            reset_switch: false,
+            reset_switch_interrupt: false,
+
             fifo_start: Default::default(),
             fifo_end: Default::default(),
             fifo_current: Default::default(),
@@ -126,6 +136,36 @@ impl Default for Interface {
     }
 }
 
+/// How a source acknowledges one of its interrupt causes through its registers.
+///
+/// Every cause follows the same rules: once raised, it stays raised until acknowledged through
+/// its register (reading it never clears it) and its mask only decides whether it reaches PI, so
+/// masking and unmasking never drops a pending cause. Writes which might acknowledge a cause or
+/// change a mask must be followed by [`interrupt_written`], so that PI re-evaluates its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ack {
+    /// The cause is cleared by writing 1 to its bit.
+    WriteOne,
+    /// The cause is cleared by writing 0 to its bit.
+    WriteZero,
+}
+
+impl Ack {
+    /// Returns the state of a cause after `written` is written to its bit. Writes never raise a
+    /// cause.
+    pub fn write(self, raised: bool, written: bool) -> bool {
+        match self {
+            Self::WriteOne => raised && !written,
+            Self::WriteZero => raised && written,
+        }
+    }
+}
+
+/// Re-evaluates the interrupt line after a write to the interrupt registers of a source.
+pub fn interrupt_written(sys: &mut System) {
+    sys.scheduler.schedule_now(check_interrupts);
+}
+
 /// Returns which interrupt sources are active (i.e. triggered but maybe masked).
 pub fn get_active_interrupts(sys: &System) -> InterruptSources {
     let mut sources = InterruptSources::default();
@@ -137,9 +177,12 @@ pub fn get_active_interrupts(sys: &System) -> InterruptSources {
     }
     sources.set_video_interface(video);
 
+    // CP
+    sources.set_command_processor(sys.gpu.cmd.any_interrupt());
+
     // PE
-    sources.set_pe_token(sys.gpu.pix.interrupt.token());
-    sources.set_pe_finish(sys.gpu.pix.interrupt.finish());
+    sources.set_pe_token(sys.gpu.pix.interrupt.token_raised());
+    sources.set_pe_finish(sys.gpu.pix.interrupt.finish_raised());
 
     // AI
     sources.set_audio_interface(
@@ -150,11 +193,14 @@ pub fn get_active_interrupts(sys: &System) -> InterruptSources {
     sources.set_dsp_interface(sys.dsp.control.any_interrupt());
 
     // DI
-    sources.set_dvd_interface(sys.disk.status.any_interrupt());
+    sources.set_dvd_interface(sys.disk.any_interrupt());
 
     // SI
     sources.set_serial_interface(sys.serial.any_interrupt());
 
+    // RSW
+    sources.set_reset(sys.processor.reset_switch_interrupt);
+
     sources
 }
 
@@ -179,6 +225,43 @@ pub fn check_interrupts(sys: &mut System) {
     }
 }
 
+/// Returns the value of the interrupt cause register.
+pub fn interrupt_cause(sys: &System) -> u32 {
+    let raised = self::get_raised_interrupts(sys).to_bits().value() as u32;
+
+    // bit 16 holds the state of the reset switch, and is set while the switch is _not_ pressed
+    raised | ((!sys.processor.reset_switch as u32) << 16)
+}
+
+/// Writes to the interrupt cause register, acknowledging the reset switch interrupt if requested.
+pub fn write_interrupt_cause(sys: &mut System, value: u32) {
+    if value.bit(1) {
+        sys.processor.reset_switch_interrupt = false;
+    }
+}
+
+/// How long the reset switch is held down when pressed, in CPU cycles (half a second).
+const RESET_SWITCH_HOLD: u64 = gekko::FREQUENCY / 2;
+
+/// Presses the reset switch, as the console's reset button does, releasing it after a while.
+///
+/// The switch does not reset anything by itself: it raises an interrupt and it's up to the running
+/// software to react to it (usually by resetting the system through the PI reset register once
+/// the switch is released).
+pub fn press_reset_switch(sys: &mut System) {
+    tracing::info!("reset switch pressed");
+    sys.processor.reset_switch = true;
+    sys.processor.reset_switch_interrupt = true;
+    sys.scheduler
+        .schedule(RESET_SWITCH_HOLD, release_reset_switch);
+    sys.scheduler.schedule_now(check_interrupts);
+}
+
+fn release_reset_switch(sys: &mut System) {
+    tracing::info!("reset switch released");
+    sys.processor.reset_switch = false;
+}
+
 /// Pushes a value into the PI FIFO. Values are queued up until 32 bytes are available, then
 /// written all at once.
 pub fn fifo_push<P: Primitive>(sys: &mut System, value: P) {
@@ -217,3 +300,147 @@ pub fn fifo_push<P: Primitive>(sys: &mut System, value: P) {
         gx::cmd::consume(sys);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::system::{BootMode, Config, Modules, vi};
+
+    fn system() -> System {
+        let config = Config {
+            boot: BootMode::Ipl,
+            ipl: None,
+            sideload: None,
+            fill_seed: None,
+            deterministic: true,
+            rtc_epoch: 0,
+        };
+
+        let mut sys = System::new(Modules::nop(), config).unwrap();
+        sys.write_phys_slow::<u32>(Address(0x0C00_3004), 0x3FFF);
+        sys
+    }
+
+    /// Writes `value` to an interrupt register and checks that PI is going to re-evaluate its
+    /// line.
+    fn write<P: Primitive>(sys: &mut System, addr: u32, value: P) {
+        sys.scheduler.cancel(check_interrupts);
+        sys.write_phys_slow(Address(addr), value);
+        assert!(sys.scheduler.contains(check_interrupts));
+    }
+
+    #[test]
+    fn pe_interrupts() {
+        const PE_INTERRUPT: u32 = 0x0C00_100A;
+
+        type Source = fn(&InterruptSources) -> bool;
+        let cases: [(u16, fn(&mut System), Source); 2] = [
+            (
+                0,
+                |sys| sys.gpu.pix.interrupt.set_token(true),
+                |s| s.pe_token(),
+            ),
+            (
+                1,
+                |sys| sys.gpu.pix.interrupt.set_finish(true),
+                |s| s.pe_finish(),
+            ),
+        ];
+
+        for (bit, raise, source) in cases {
+            let mut sys = system();
+            let line = |sys: &System| source(&get_raised_interrupts(sys));
+            let enable = 1 << bit;
+            let ack = 1 << (bit + 2);
+
+            write(&mut sys, PE_INTERRUPT, enable);
+            raise(&mut sys);
+            assert!(line(&sys));
+
+            // reading doesn't acknowledge
+            sys.read_phys_slow::<u16>(Address(PE_INTERRUPT));
+            assert!(line(&sys));
+
+            // masking hides it, but keeps it pending
+            write(&mut sys, PE_INTERRUPT, 0u16);
+            assert!(!line(&sys));
+            write(&mut sys, PE_INTERRUPT, enable);
+            assert!(line(&sys));
+
+            write(&mut sys, PE_INTERRUPT, enable | ack);
+            assert!(!line(&sys));
+            write(&mut sys, PE_INTERRUPT, enable);
+            assert!(!line(&sys));
+        }
+    }
+
+    #[test]
+    fn vi_interrupts() {
+        const DISPLAY_INTERRUPT_0: u32 = 0x0C00_2030;
+        const LINE: u32 = 1 << 16;
+        const ENABLE: u32 = 1 << 28;
+        const STATUS: u32 = 1 << 31;
+
+        let mut sys = system();
+        let line = |sys: &System| get_raised_interrupts(sys).video_interface();
+
+        write(&mut sys, DISPLAY_INTERRUPT_0, ENABLE | LINE);
+        sys.video.vertical_count = 1;
+        vi::update_display_interrupts(&mut sys);
+        assert!(line(&sys));
+
+        // reading doesn't acknowledge and neither does the beam moving on
+        sys.read_phys_slow::<u32>(Address(DISPLAY_INTERRUPT_0));
+        sys.video.vertical_count = 2;
+        vi::update_display_interrupts(&mut sys);
+        assert!(line(&sys));
+
+        // masking hides it, but keeps it pending
+        write(&mut sys, DISPLAY_INTERRUPT_0, STATUS | LINE);
+        assert!(!line(&sys));
+        write(&mut sys, DISPLAY_INTERRUPT_0, STATUS | ENABLE | LINE);
+        assert!(line(&sys));
+
+        write(&mut sys, DISPLAY_INTERRUPT_0, ENABLE | LINE);
+        assert!(!line(&sys));
+    }
+
+    #[test]
+    fn cp_interrupts() {
+        const CP_STATUS: u32 = 0x0C00_0000;
+        const CP_CONTROL: u32 = 0x0C00_0002;
+        const BREAKPOINT: u16 = 1 << 1;
+        const BREAKPOINT_INTERRUPT: u16 = 1 << 5;
+
+        let mut sys = system();
+        let line = |sys: &System| get_raised_interrupts(sys).command_processor();
+
+        write(&mut sys, CP_CONTROL, BREAKPOINT | BREAKPOINT_INTERRUPT);
+        sys.gpu.cmd.status.set_breakpoint_interrupt(true);
+        assert!(line(&sys));
+
+        // reading doesn't acknowledge
+        sys.read_phys_slow::<u16>(Address(CP_STATUS));
+        assert!(line(&sys));
+
+        // masking hides it, but keeps it pending
+        write(&mut sys, CP_CONTROL, BREAKPOINT);
+        assert!(!line(&sys));
+        write(&mut sys, CP_CONTROL, BREAKPOINT | BREAKPOINT_INTERRUPT);
+        assert!(line(&sys));
+
+        // disabling the breakpoint acknowledges it
+        write(&mut sys, CP_CONTROL, BREAKPOINT_INTERRUPT);
+        assert!(!line(&sys));
+    }
+
+    #[test]
+    fn acks() {
+        assert!(Ack::WriteOne.write(true, false));
+        assert!(!Ack::WriteOne.write(true, true));
+        assert!(!Ack::WriteOne.write(false, false));
+        assert!(Ack::WriteZero.write(true, true));
+        assert!(!Ack::WriteZero.write(true, false));
+        assert!(!Ack::WriteZero.write(false, true));
+    }
+}
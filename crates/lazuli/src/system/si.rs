@@ -208,6 +208,10 @@ pub fn poll_controller(sys: &mut System, channel: usize) {
     }
 
     let Some(controller) = sys.modules.input.controller(channel) else {
+        // the error status bit of the input buffer tells games the controller is gone
+        sys.serial.channel_input[channel].low = 0;
+        sys.serial.channel_input[channel].high = 1 << 31;
+        self::set_no_response(sys, channel);
         return;
     };
 
@@ -237,10 +241,19 @@ pub fn poll_controller(sys: &mut System, channel: usize) {
 
     let mut status = sys.serial.status.channel(channel);
     status.set_input_ready(true);
+    status.set_no_response(false);
     sys.serial.status.set_channel(channel, status);
     sys.serial.comm_control.set_read_interrupt(true);
 }
 
+/// Flags a transfer to a channel without a device as an error.
+fn set_no_response(sys: &mut System, channel: usize) {
+    let mut status = sys.serial.status.channel(channel);
+    status.set_no_response(true);
+    sys.serial.status.set_channel(channel, status);
+    sys.serial.comm_control.set_communication_error(true);
+}
+
 fn process_cmd(sys: &mut System, channel: usize) {
     let mut i = 0;
     let mut read = || {
@@ -254,6 +267,12 @@ fn process_cmd(sys: &mut System, channel: usize) {
         todo!("unknown SI command {cmd:?}")
     };
 
+    if !sys.modules.input.is_connected(channel) {
+        tracing::debug!("SI command {cmd:?} to channel {channel} without a device");
+        self::set_no_response(sys, channel);
+        return;
+    }
+
     match cmd {
         Command::Info => {
             tracing::debug!("info");
@@ -280,6 +299,7 @@ fn do_transfer(sys: &mut System) {
     // dbg!(sys.serial.comm_control);
     tracing::debug!("transfer");
 
+    sys.serial.comm_control.set_communication_error(false);
     process_cmd(sys, sys.serial.comm_control.channel().value() as usize);
 
     sys.serial.comm_control.set_transfer_start(false);
@@ -330,6 +350,17 @@ pub fn write_comm_control(sys: &mut System, value: CommControl) {
 }
 
 pub fn write_status(sys: &mut System, value: Status) {
+    // error bits are cleared by writing 1 to them
+    for channel in 0..4 {
+        let written = value.channel(channel);
+        let mut status = sys.serial.status.channel(channel);
+        status.set_underrun(status.underrun() && !written.underrun());
+        status.set_overrun(status.overrun() && !written.overrun());
+        status.set_collision(status.collision() && !written.collision());
+        status.set_no_response(status.no_response() && !written.no_response());
+        sys.serial.status.set_channel(channel, status);
+    }
+
     if value.copy_buffers() {
         for channel in 0..4 {
             if std::mem::take(&mut sys.serial.channel_output[channel].dirty) {
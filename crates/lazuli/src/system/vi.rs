@@ -1,8 +1,10 @@
 //! Video interface (VI).
 use bitos::bitos;
 use bitos::integer::{u4, u7, u9, u10, u24};
+use color::Rgba8;
 use gekko::{Address, FREQUENCY};
 
+use crate::modules::render::{Action, FrameSkip};
 use crate::system::{System, pi, si};
 
 #[bitos(16)]
@@ -123,7 +125,7 @@ pub struct DisplayInterrupt {
     /// Whether this interrupt is enabled.
     #[bits(28)]
     pub enable: bool,
-    /// Whether this interrupt is asserted. Clear on write.
+    /// Whether this interrupt is asserted. Cleared by writing 0.
     #[bits(31)]
     pub status: bool,
 }
@@ -174,6 +176,19 @@ impl FieldBase {
     }
 }
 
+/// How long an EFB copy to the scanned out XFB keeps being presented without a new one, in CPU
+/// cycles. Past that, the game is assumed to be drawing the XFB with the CPU instead.
+const XFB_COPY_LIFETIME: u64 = FREQUENCY;
+
+/// An EFB copy to an XFB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XfbCopy {
+    /// Physical address the copy was made to.
+    pub address: Address,
+    /// When the copy was made, in CPU cycles.
+    pub time: u64,
+}
+
 #[derive(Debug, Default)]
 pub struct Interface {
     pub vertical_timing: VerticalTiming,
@@ -191,6 +206,8 @@ pub struct Interface {
     pub xfb_width: ExternalFramebufferWidth,
     pub horizontal_scaling: HorizontalScaling,
     pub clock: ClockMode,
+    /// The last EFB copy to an XFB.
+    pub last_xfb_copy: Option<XfbCopy>,
 }
 
 impl Interface {
@@ -280,6 +297,15 @@ impl Interface {
         self.bottom_base_left.xfb_address()
     }
 
+    /// Whether the XFB being scanned out at `now` was produced by a recent enough EFB copy, in
+    /// which case the renderer already has it.
+    pub fn scanning_out_copy(&self, now: u64) -> bool {
+        self.last_xfb_copy.is_some_and(|copy| {
+            copy.address == self.top_xfb_address()
+                && now.saturating_sub(copy.time) <= XFB_COPY_LIFETIME
+        })
+    }
+
     /// Height of the XFB.
     pub fn xfb_height(&self) -> u16 {
         let acv = self.vertical_timing.active_video_lines().value();
@@ -311,20 +337,123 @@ impl Interface {
 
     pub fn write_interrupt<const N: usize>(&mut self, new: DisplayInterrupt) {
         const { assert!(N < 4) };
-        self.interrupts[N] = new.with_status(self.interrupts[N].status() && new.status());
+        let status = pi::Ack::WriteZero.write(self.interrupts[N].status(), new.status());
+        self.interrupts[N] = new.with_status(status);
+    }
+}
+
+/// How many frames to skip, as chosen by the frontend while emulation can't keep up with real
+/// time. Skipped frames are fully emulated, they just aren't presented. The default rate never
+/// skips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameSkipRate {
+    /// How many frames out of every `cycle` frames are skipped. At least one frame per cycle is
+    /// always presented.
+    pub skip: u8,
+    /// Length of the cycle, in frames.
+    pub cycle: u8,
+    /// Whether the renderer should also drop the draws of skipped frames.
+    pub draws: bool,
+}
+
+impl FrameSkipRate {
+    /// How many frames are actually skipped per cycle.
+    pub fn skipped(&self) -> u8 {
+        self.skip.min(self.cycle.saturating_sub(1))
+    }
+
+    /// Whether the frame at `index` in the cycle is skipped. Skipped frames are spread evenly over
+    /// the cycle, so that presented frames are as regularly spaced as possible.
+    pub fn skips(&self, index: u8) -> bool {
+        let (index, skip, cycle) = (index as u32, self.skipped() as u32, self.cycle as u32);
+        skip != 0 && (index + 1) * skip / cycle > index * skip / cycle
     }
 }
 
+/// Decides which frames are skipped, following a [`FrameSkipRate`].
+///
+/// This lives outside of [`Interface`] since it is a setting of the frontend rather than state of
+/// the emulated hardware, and therefore survives resets.
+#[derive(Debug, Default)]
+pub struct FrameSkipper {
+    rate: FrameSkipRate,
+    /// Index of the current frame in the cycle.
+    index: u8,
+    /// What the renderer skips of the current frame.
+    current: FrameSkip,
+    /// How many frames have been skipped so far.
+    skipped: u64,
+}
+
+impl FrameSkipper {
+    pub fn rate(&self) -> FrameSkipRate {
+        self.rate
+    }
+
+    /// Sets the rate to follow from the next frame on.
+    pub fn set_rate(&mut self, rate: FrameSkipRate) {
+        self.rate = rate;
+    }
+
+    /// Whether the current frame is being skipped.
+    pub fn skipping(&self) -> bool {
+        self.current != FrameSkip::None
+    }
+
+    /// How many frames have been skipped so far.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Ends the current frame. Returns what to skip of the next one if it changed, in which case
+    /// the renderer has to be told about it.
+    pub fn next_frame(&mut self) -> Option<FrameSkip> {
+        if self.skipping() {
+            self.skipped += 1;
+        }
+
+        let rate = self.rate;
+        self.index = if rate.cycle == 0 {
+            0
+        } else {
+            (self.index + 1) % rate.cycle
+        };
+
+        let next = if !rate.skips(self.index) {
+            FrameSkip::None
+        } else if rate.draws {
+            FrameSkip::Draws
+        } else {
+            FrameSkip::Present
+        };
+
+        (next != self.current).then(|| {
+            self.current = next;
+            next
+        })
+    }
+
+    /// Forgets the current frame, as needed when the renderer is reset.
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.current = FrameSkip::None;
+    }
+}
+
+/// Asserts the display interrupts whose line is the current one. Asserted interrupts stay so until
+/// acknowledged, even if disabled.
 pub fn update_display_interrupts(sys: &mut System) {
     let mut raised = false;
     for (index, interrupt) in sys.video.interrupts.iter_mut().enumerate() {
-        if interrupt.enable() && interrupt.vertical_count().value() == sys.video.vertical_count {
+        if interrupt.vertical_count().value() != sys.video.vertical_count {
+            continue;
+        }
+
+        interrupt.set_status(true);
+        if interrupt.enable() {
             raised = true;
-            interrupt.set_status(true);
             sys.video.horizontal_count = interrupt.horizontal_count().value();
             tracing::debug!("raised display interrupt {index} ({interrupt:?})");
-        } else {
-            interrupt.set_status(false);
         }
     }
 
@@ -341,6 +470,12 @@ pub fn vertical_count(sys: &mut System) {
 
     if sys.video.vertical_count as u32 > sys.video.lines_per_frame() {
         sys.video.vertical_count = 1;
+        self::present_xfb(sys);
+        profiling::finish_frame!();
+
+        if let Some(skip) = sys.frame_skip.next_frame() {
+            sys.modules.render.exec(Action::SetFrameSkip(skip));
+        }
     }
 
     if sys
@@ -385,7 +520,7 @@ fn xfb_inner(sys: &System, base: Address) -> Option<&[u8]> {
     }
 
     let length = 2 * pixels;
-    Some(&sys.mem.ram()[xfb as usize..xfb as usize + length as usize])
+    sys.mem.slice(Address(xfb), length)
 }
 
 /// Returns the data of the top XFB in YCbCr format (y0, cb, y1, cr).
@@ -399,3 +534,165 @@ pub fn bottom_xfb(sys: &System) -> Option<&[u8]> {
     let base = sys.video.bottom_xfb_address();
     xfb_inner(sys, base)
 }
+
+#[inline]
+fn ycbcr_to_rgb(y: u8, cb: u8, cr: u8) -> Rgba8 {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+
+    let r = y + 1.371 * cr;
+    let g = y - 0.698 * cr - 0.336 * cb;
+    let b = y + 1.732 * cb;
+
+    let [r, g, b] = [r, g, b].map(|x| x.clamp(0.0, 255.0) as u8);
+    Rgba8 { r, g, b, a: 255 }
+}
+
+/// Converts XFB data in YCbCr format (y0, cb, y1, cr) into RGBA pixels.
+pub fn xfb_to_rgba(data: &[u8]) -> Vec<Rgba8> {
+    let mut pixels = Vec::with_capacity(data.len() / 2);
+    for ycbcr in data.chunks_exact(4) {
+        pixels.push(ycbcr_to_rgb(ycbcr[0], ycbcr[1], ycbcr[3]));
+        pixels.push(ycbcr_to_rgb(ycbcr[2], ycbcr[1], ycbcr[3]));
+    }
+
+    pixels
+}
+
+/// Presents the current frame.
+///
+/// If the XFB being scanned out was produced by an EFB copy, the renderer already has it
+/// composited and nothing needs to be done. Otherwise, the XFB was drawn by the CPU and is fetched
+/// from RAM. Games don't necessarily copy every frame, so this looks at the address of the last
+/// copy instead of whether one happened since the last frame.
+fn present_xfb(sys: &mut System) {
+    // skipped frames are dropped by the renderer, but converting the XFB isn't free either
+    let now = sys.scheduler.elapsed();
+    if sys.video.scanning_out_copy(now) || sys.frame_skip.skipping() {
+        return;
+    }
+
+    let (width, height) = sys.video.xfb_resolution();
+    let Some(xfb) = self::top_xfb(sys) else {
+        return;
+    };
+
+    let data = self::xfb_to_rgba(xfb);
+    sys.modules.render.exec(Action::PresentXfb {
+        width,
+        height,
+        data,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs `frames` frames, returning which ones were skipped and the actions sent.
+    fn run(skipper: &mut FrameSkipper, frames: usize) -> (Vec<bool>, Vec<FrameSkip>) {
+        let mut skipped = Vec::new();
+        let mut actions = Vec::new();
+        for _ in 0..frames {
+            actions.extend(skipper.next_frame());
+            skipped.push(skipper.skipping());
+        }
+
+        (skipped, actions)
+    }
+
+    #[test]
+    fn off_never_skips() {
+        let mut skipper = FrameSkipper::default();
+        let (skipped, actions) = run(&mut skipper, 120);
+
+        assert!(skipped.iter().all(|s| !s));
+        assert!(actions.is_empty());
+        assert_eq!(skipper.skipped(), 0);
+    }
+
+    #[test]
+    fn skipped_frames_are_spread() {
+        let rate = FrameSkipRate {
+            skip: 2,
+            cycle: 4,
+            draws: false,
+        };
+
+        assert_eq!(
+            (0..4).map(|i| rate.skips(i)).collect::<Vec<_>>(),
+            [false, true, false, true]
+        );
+
+        let mut skipper = FrameSkipper::default();
+        skipper.set_rate(rate);
+        let (skipped, actions) = run(&mut skipper, 8);
+
+        assert_eq!(skipped.iter().filter(|s| **s).count(), 4);
+        assert!(skipped.windows(2).all(|w| !(w[0] && w[1])));
+        assert_eq!(actions.len(), 8);
+        assert!(actions.iter().all(|a| *a != FrameSkip::Draws));
+    }
+
+    #[test]
+    fn a_frame_per_cycle_is_presented() {
+        let rate = FrameSkipRate {
+            skip: 9,
+            cycle: 3,
+            draws: true,
+        };
+
+        assert_eq!(rate.skipped(), 2);
+        assert_eq!((0..3).filter(|i| !rate.skips(*i)).count(), 1);
+
+        let mut skipper = FrameSkipper::default();
+        skipper.set_rate(rate);
+        let (skipped, actions) = run(&mut skipper, 8);
+
+        assert_eq!(skipped, [true, true, false, true, true, false, true, true]);
+        assert_eq!(skipper.skipped(), 5);
+        assert_eq!(
+            actions,
+            [
+                FrameSkip::Draws,
+                FrameSkip::None,
+                FrameSkip::Draws,
+                FrameSkip::None,
+                FrameSkip::Draws
+            ]
+        );
+
+        // turning skipping off is reported as soon as the current frame ends
+        skipper.set_rate(FrameSkipRate::default());
+        let (skipped, actions) = run(&mut skipper, 3);
+        assert!(skipped.iter().all(|s| !s));
+        assert_eq!(actions, [FrameSkip::None]);
+        assert_eq!(skipper.skipped(), 6);
+    }
+
+    #[test]
+    fn copies_are_presented_while_scanned_out() {
+        let mut video = Interface::default();
+        let scan_out = |video: &mut Interface, address: u32| {
+            video.top_base_left = FieldBase::default().with_xfb_address_base(u24::new(address));
+        };
+
+        scan_out(&mut video, 0x30_0000);
+        assert!(!video.scanning_out_copy(0));
+
+        // a copy every other frame keeps being presented in between
+        video.last_xfb_copy = Some(XfbCopy {
+            address: Address(0x30_0000),
+            time: 1000,
+        });
+        assert!(video.scanning_out_copy(1000));
+        assert!(video.scanning_out_copy(1000 + FREQUENCY / 30));
+
+        // double buffering: the other XFB was drawn by the CPU
+        scan_out(&mut video, 0x38_0000);
+        assert!(!video.scanning_out_copy(1000 + FREQUENCY / 30));
+
+        // no copies for a long time: the CPU took over
+        scan_out(&mut video, 0x30_0000);
+        assert!(!video.scanning_out_copy(2000 + XFB_COPY_LIFETIME));
+    }
+}
@@ -23,117 +23,140 @@ impl From<Frame> for FrameF32 {
     }
 }
 
+/// Maximum number of pending frames. Older frames are dropped once this is exceeded, which keeps
+/// latency bounded when emulation runs faster than the host consumes audio.
+const MAX_PENDING_FRAMES: usize = 4096;
+
 struct State {
     sample_rate: SampleRate,
     resampler: ResamplerFir,
     resampled: Vec<f32>,
+    /// Frames at the emulated sample rate.
     frames: VecDeque<FrameF32>,
+    /// Frames at the host sample rate, ready to be played.
+    output: VecDeque<FrameF32>,
     last: FrameF32,
+    /// Whether frames are discarded instead of played.
+    muted: bool,
     writer: Option<hound::WavWriter<std::io::BufWriter<std::fs::File>>>,
 }
 
 impl Drop for State {
     fn drop(&mut self) {
-        self.writer.take().unwrap().finalize().unwrap();
+        if let Some(writer) = self.writer.take() {
+            writer.finalize().unwrap();
+        }
     }
 }
 
-fn fill_buffer(state: &Arc<Mutex<State>>, out: &mut [f32]) {
-    let mut state = state.lock().unwrap();
-    let state = &mut *state;
-
-    match state.sample_rate {
-        SampleRate::KHz48 => {
-            let mut last = state.last;
-            for out in out.chunks_exact_mut(2) {
-                let frame = if let Some(frame) = state.frames.pop_front() {
-                    state
-                        .writer
-                        .as_mut()
-                        .unwrap()
-                        .write_sample(frame.left)
-                        .unwrap();
-                    state
-                        .writer
-                        .as_mut()
-                        .unwrap()
-                        .write_sample(frame.right)
-                        .unwrap();
-
-                    frame
-                } else {
-                    last
-                };
-
-                out[0] = frame.left;
-                out[1] = frame.right;
-                last = frame;
-            }
+impl State {
+    fn new(writer: Option<hound::WavWriter<std::io::BufWriter<std::fs::File>>>) -> Self {
+        let resampler = ResamplerFir::new(
+            2,
+            resampler::SampleRate::Hz32000,
+            resampler::SampleRate::Hz48000,
+            resampler::Latency::Sample64,
+            resampler::Attenuation::Db90,
+        );
 
-            state.last = last;
+        Self {
+            sample_rate: SampleRate::KHz48,
+            resampled: vec![0.0; resampler.buffer_size_output()],
+            resampler,
+            frames: VecDeque::with_capacity(MAX_PENDING_FRAMES),
+            output: VecDeque::with_capacity(MAX_PENDING_FRAMES),
+            last: FrameF32::default(),
+            muted: false,
+            writer,
         }
-        SampleRate::KHz32 => {
-            let slices = state.frames.as_slices();
-            let frames = match (slices.0.is_empty(), slices.1.is_empty()) {
-                (true, true) => slices.0,
-                (false, true) => slices.0,
-                (true, false) => slices.1,
-                (false, false) => state.frames.make_contiguous(),
-            };
+    }
+
+    fn push(&mut self, frame: FrameF32) {
+        if self.muted {
+            return;
+        }
+
+        if self.frames.len() >= MAX_PENDING_FRAMES {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(frame);
+    }
 
+    fn set_muted(&mut self, muted: bool) {
+        if muted {
+            // play silence instead of repeating the last frame
+            self.frames.clear();
+            self.output.clear();
+            self.last = FrameF32::default();
+        }
+
+        self.muted = muted;
+    }
+
+    /// Resamples pending 32 kHz frames until there are at least `needed` output frames or no more
+    /// input is available.
+    fn resample(&mut self, needed: usize) {
+        while self.output.len() < needed && !self.frames.is_empty() {
+            let frames: &[FrameF32] = self.frames.make_contiguous();
             let samples: &[f32] = zerocopy::transmute_ref!(frames);
-            let samples_needed = (2 * out.len()) / 3;
 
-            let (consumed, produced) = state
+            let (consumed, produced) = self
                 .resampler
-                .resample(
-                    &samples[..samples_needed.min(samples.len())],
-                    &mut state.resampled,
-                )
+                .resample(samples, &mut self.resampled)
                 .unwrap();
 
-            state.frames.drain(..consumed / 2);
-
-            let mut produced = state
-                .resampled
-                .chunks_exact(2)
-                .map(|s| FrameF32 {
-                    left: s[0],
-                    right: s[1],
-                })
-                .take(produced / 2);
-
-            let mut last = state.last;
-            for out in out.chunks_exact_mut(2) {
-                let frame = if let Some(frame) = produced.next() {
-                    state
-                        .writer
-                        .as_mut()
-                        .unwrap()
-                        .write_sample(frame.left)
-                        .unwrap();
-                    state
-                        .writer
-                        .as_mut()
-                        .unwrap()
-                        .write_sample(frame.right)
-                        .unwrap();
-
-                    frame
-                } else {
-                    last
-                };
-
-                out[0] = frame.left;
-                out[1] = frame.right;
-                last = frame;
+            self.frames.drain(..consumed / 2);
+            self.output.extend(
+                self.resampled[..produced]
+                    .chunks_exact(2)
+                    .map(|s| FrameF32 {
+                        left: s[0],
+                        right: s[1],
+                    }),
+            );
+
+            if consumed == 0 && produced == 0 {
+                break;
             }
+        }
+    }
 
-            state.last = last;
+    /// Fills `out` with interleaved stereo samples at the host sample rate.
+    fn render(&mut self, out: &mut [f32]) {
+        if self.sample_rate == SampleRate::KHz32 {
+            self.resample(out.len() / 2);
+        }
+
+        for out in out.chunks_exact_mut(2) {
+            // resampled frames left over from a rate switch are played first
+            let next = match self.sample_rate {
+                SampleRate::KHz48 => self.output.pop_front().or_else(|| self.frames.pop_front()),
+                SampleRate::KHz32 => self.output.pop_front(),
+            };
+
+            let frame = if let Some(frame) = next {
+                if let Some(writer) = &mut self.writer {
+                    writer.write_sample(frame.left).unwrap();
+                    writer.write_sample(frame.right).unwrap();
+                }
+
+                frame
+            } else {
+                self.last
+            };
+
+            out[0] = frame.left;
+            out[1] = frame.right;
+            self.last = frame;
         }
     }
 }
 
+fn fill_buffer(state: &Arc<Mutex<State>>, out: &mut [f32]) {
+    state.lock().unwrap().render(out);
+}
+
 pub struct CpalModule {
     state: Arc<Mutex<State>>,
     _stream: Stream,
@@ -213,30 +236,14 @@ impl CpalModule {
             }
         }
 
-        let resampler = ResamplerFir::new(
-            2,
-            resampler::SampleRate::Hz32000,
-            resampler::SampleRate::Hz48000,
-            resampler::Latency::Sample64,
-            resampler::Attenuation::Db90,
-        );
-
         let spec = hound::WavSpec {
             channels: 2,
-            sample_rate: 48000,
+            sample_rate: SAMPLE_RATE,
             bits_per_sample: 32,
             sample_format: hound::SampleFormat::Float,
         };
         let writer = hound::WavWriter::create("audio.wav", spec).unwrap();
-
-        let state = State {
-            sample_rate: SampleRate::KHz48,
-            resampled: vec![0.0; resampler.buffer_size_output()],
-            resampler,
-            frames: VecDeque::with_capacity(8192),
-            last: FrameF32::default(),
-            writer: Some(writer),
-        };
+        let state = State::new(Some(writer));
 
         let state = Arc::new(Mutex::new(state));
         let stream = device
@@ -268,6 +275,102 @@ impl AudioModule for CpalModule {
     }
 
     fn play(&mut self, sample: Frame) {
-        self.state.lock().unwrap().frames.push_back(sample.into());
+        self.state.lock().unwrap().push(sample.into());
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.state.lock().unwrap().set_muted(muted);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Estimates the frequency of the left channel of interleaved stereo `samples` at `rate` by
+    /// counting zero crossings.
+    fn estimate_frequency(samples: &[f32], rate: f32) -> f32 {
+        let left = samples.iter().step_by(2).copied().collect::<Vec<_>>();
+        let crossings = left
+            .windows(2)
+            .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+            .count();
+
+        crossings as f32 / 2.0 / (left.len() as f32 / rate)
+    }
+
+    fn sine_through_pipeline(sample_rate: SampleRate) -> f32 {
+        let mut state = State::new(None);
+        state.sample_rate = sample_rate;
+
+        let rate = sample_rate.value() as usize;
+        let mut index = 0;
+        let mut push_until = |state: &mut State, count: usize| {
+            while index < count {
+                let t = index as f32 / rate as f32;
+                let sample = ((2.0 * std::f32::consts::PI * 1000.0 * t).sin() * 16_000.0) as i16;
+                state.push(
+                    Frame {
+                        left: sample,
+                        right: sample,
+                    }
+                    .into(),
+                );
+
+                index += 1;
+            }
+        };
+
+        // 200ms at the host rate, in callback sized chunks, with the emulated side producing a
+        // little ahead of the host
+        let mut out = vec![0.0; 2 * SAMPLE_RATE as usize / 5];
+        for (i, chunk) in out.chunks_mut(1024).enumerate() {
+            let host_frames = (i + 2) * 512;
+            push_until(&mut state, host_frames * rate / SAMPLE_RATE as usize);
+            state.render(chunk);
+        }
+
+        // skip the resampler latency
+        estimate_frequency(&out[2 * 256..], SAMPLE_RATE as f32)
+    }
+
+    #[test]
+    fn muted_plays_silence() {
+        let mut state = State::new(None);
+        let frame = Frame {
+            left: 16_000,
+            right: -16_000,
+        };
+
+        state.push(frame.into());
+        state.set_muted(true);
+        state.push(frame.into());
+
+        let mut out = [1.0; 8];
+        state.render(&mut out);
+        assert_eq!(out, [0.0; 8]);
+
+        state.set_muted(false);
+        state.push(frame.into());
+        state.render(&mut out[..2]);
+        assert_ne!(out[..2], [0.0; 2]);
+    }
+
+    #[test]
+    fn sine_48khz() {
+        let freq = sine_through_pipeline(SampleRate::KHz48);
+        assert!(
+            (freq - 1000.0).abs() < 10.0,
+            "dominant frequency is {freq} Hz"
+        );
+    }
+
+    #[test]
+    fn sine_32khz() {
+        let freq = sine_through_pipeline(SampleRate::KHz32);
+        assert!(
+            (freq - 1000.0).abs() < 10.0,
+            "dominant frequency is {freq} Hz"
+        );
     }
 }
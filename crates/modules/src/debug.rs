@@ -1,11 +1,15 @@
+mod dwarf;
+
 use std::borrow::Cow;
 use std::path::Path;
 
 use addr2line::gimli;
 use lazuli::Address;
-use lazuli::modules::debug::{DebugModule, Location};
+use lazuli::modules::debug::{DebugModule, Location, Type, TypeId, Variable};
 use mapfile_parser::MapFile;
 
+use crate::debug::dwarf::DebugTypes;
+
 fn demangle(s: &str) -> String {
     let cw_options = cwdemangle::DemangleOptions {
         omit_empty_parameters: true,
@@ -18,21 +22,34 @@ fn demangle(s: &str) -> String {
     addr2line::demangle_auto(Cow::Borrowed(s), Some(gimli::DW_LANG_C_plus_plus)).into_owned()
 }
 
-pub struct Addr2LineModule(addr2line::Loader);
+pub struct Addr2LineModule {
+    loader: addr2line::Loader,
+    types: DebugTypes,
+}
 
 impl Addr2LineModule {
     pub fn new(path: impl AsRef<Path>) -> Option<Self> {
-        addr2line::Loader::new(path).ok().map(Self)
+        let path = path.as_ref();
+        let loader = addr2line::Loader::new(path).ok()?;
+        let types = std::fs::read(path)
+            .ok()
+            .and_then(|data| DebugTypes::parse(&data))
+            .unwrap_or_else(|| {
+                tracing::warn!("failed to parse types from {}", path.display());
+                DebugTypes::default()
+            });
+
+        Some(Self { loader, types })
     }
 }
 
 impl DebugModule for Addr2LineModule {
     fn find_symbol(&self, addr: Address) -> Option<String> {
-        self.0.find_symbol(addr.value() as u64).map(demangle)
+        self.loader.find_symbol(addr.value() as u64).map(demangle)
     }
 
     fn find_location(&self, addr: Address) -> Option<Location<'_>> {
-        self.0
+        self.loader
             .find_location(addr.value() as u64)
             .ok()
             .flatten()
@@ -42,6 +59,14 @@ impl DebugModule for Addr2LineModule {
                 column: l.column,
             })
     }
+
+    fn find_variable(&self, name: &str) -> Option<Variable> {
+        self.types.find_variable(name).cloned()
+    }
+
+    fn find_type(&self, ty: TypeId) -> Option<&Type> {
+        self.types.find_type(ty)
+    }
 }
 
 pub struct MapFileModule(MapFile);
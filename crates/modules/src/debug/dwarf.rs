@@ -0,0 +1,701 @@
+//! Types and variables described by DWARF debug info.
+//!
+//! Everything is parsed upfront into owned tables, so that nothing borrows from the object file.
+//! Typedefs, qualifiers and declarations of types defined elsewhere are resolved once every type
+//! is known.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use addr2line::gimli::{self, AttributeValue, EndianSlice, RunTimeEndian};
+use lazuli::Address;
+use lazuli::modules::debug::{Member, ScalarKind, Type, TypeId, TypeKind, Variable};
+use object::{Object, ObjectSection};
+
+type Reader<'a> = EndianSlice<'a, RunTimeEndian>;
+type Unit<'a> = gimli::Unit<Reader<'a>>;
+type Entry<'abbrev, 'unit, 'a> = gimli::DebuggingInformationEntry<'abbrev, 'unit, Reader<'a>>;
+type Node<'abbrev, 'unit, 'tree, 'a> = gimli::EntriesTreeNode<'abbrev, 'unit, 'tree, Reader<'a>>;
+
+/// How many typedefs and qualifiers are followed before giving up. Chains this long are either
+/// broken or cyclic.
+const MAX_ALIAS_DEPTH: u32 = 32;
+
+/// A type as parsed, before anything it refers to is resolved.
+enum Raw {
+    Type(Type),
+    Pointer {
+        name: Option<String>,
+        size: u32,
+        pointee: Option<TypeId>,
+    },
+    Array {
+        element: Option<TypeId>,
+        count: u32,
+    },
+    /// A typedef if named, a qualifier otherwise.
+    Alias {
+        name: Option<String>,
+        qualifier: &'static str,
+        target: Option<TypeId>,
+    },
+    /// A struct, class, union or enum defined somewhere else.
+    Declaration(String),
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{scope}::{name}")
+    }
+}
+
+fn void() -> Type {
+    Type {
+        name: "void".to_owned(),
+        size: 0,
+        kind: TypeKind::Opaque,
+    }
+}
+
+fn udata(entry: &Entry, attr: gimli::DwAt) -> Option<u64> {
+    entry.attr_value(attr).ok().flatten()?.udata_value()
+}
+
+fn type_id(unit: &Unit, value: AttributeValue<Reader>) -> Option<TypeId> {
+    match value {
+        AttributeValue::UnitRef(offset) => offset
+            .to_debug_info_offset(&unit.header)
+            .map(|offset| TypeId(offset.0 as u64)),
+        AttributeValue::DebugInfoRef(offset) => Some(TypeId(offset.0 as u64)),
+        _ => None,
+    }
+}
+
+fn entry_id(unit: &Unit, entry: &Entry) -> Option<TypeId> {
+    type_id(unit, AttributeValue::UnitRef(entry.offset()))
+}
+
+fn type_of(unit: &Unit, entry: &Entry) -> Option<TypeId> {
+    type_id(unit, entry.attr_value(gimli::DW_AT_type).ok().flatten()?)
+}
+
+fn scalar_kind(encoding: gimli::DwAte) -> Option<ScalarKind> {
+    Some(match encoding {
+        gimli::DW_ATE_unsigned | gimli::DW_ATE_unsigned_char | gimli::DW_ATE_UTF => {
+            ScalarKind::Unsigned
+        }
+        gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => ScalarKind::Signed,
+        gimli::DW_ATE_float => ScalarKind::Float,
+        gimli::DW_ATE_boolean => ScalarKind::Bool,
+        _ => return None,
+    })
+}
+
+/// Offset of a member from the start of its parent, either as a constant or as the
+/// `DW_OP_plus_uconst` expression older producers emit.
+fn member_offset(unit: &Unit, entry: &Entry) -> u32 {
+    if let Some(bits) = udata(entry, gimli::DW_AT_data_bit_offset) {
+        return (bits / 8) as u32;
+    }
+
+    let offset = match entry.attr_value(gimli::DW_AT_data_member_location) {
+        Ok(Some(AttributeValue::Exprloc(expr))) => match expr.operations(unit.encoding()).next() {
+            Ok(Some(gimli::Operation::PlusConstant { value })) => value,
+            _ => 0,
+        },
+        Ok(Some(value)) => value.udata_value().unwrap_or(0),
+        _ => 0,
+    };
+
+    offset as u32
+}
+
+/// Address of a variable whose location is a plain `DW_OP_addr`.
+fn fixed_address(unit: &Unit, entry: &Entry) -> Option<Address> {
+    let Ok(Some(AttributeValue::Exprloc(expr))) = entry.attr_value(gimli::DW_AT_location) else {
+        return None;
+    };
+
+    match expr.operations(unit.encoding()).next() {
+        Ok(Some(gimli::Operation::Address { address })) => Some(Address(address as u32)),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    dwarf: gimli::Dwarf<Reader<'a>>,
+    raw: HashMap<TypeId, Raw>,
+    definitions: HashMap<String, TypeId>,
+    variables: HashMap<String, Variable>,
+}
+
+impl<'a> Parser<'a> {
+    fn name(&self, unit: &Unit<'a>, entry: &Entry<'_, '_, 'a>) -> Option<String> {
+        let value = entry.attr_value(gimli::DW_AT_name).ok().flatten()?;
+        let name = self.dwarf.attr_string(unit, value).ok()?;
+        Some(name.to_string_lossy().into_owned())
+    }
+
+    fn walk(
+        &mut self,
+        unit: &Unit<'a>,
+        node: Node<'_, '_, '_, 'a>,
+        scope: &str,
+    ) -> gimli::Result<()> {
+        let entry = node.entry();
+        let Some(id) = entry_id(unit, entry) else {
+            return Ok(());
+        };
+
+        let tag = entry.tag();
+        let name = self.name(unit, entry);
+        let target = type_of(unit, entry);
+        let declaration = entry.attr_value(gimli::DW_AT_declaration)?.is_some();
+        let size = udata(entry, gimli::DW_AT_byte_size).unwrap_or(0) as u32;
+
+        let raw = match tag {
+            gimli::DW_TAG_base_type => {
+                let kind = match entry.attr_value(gimli::DW_AT_encoding)? {
+                    Some(AttributeValue::Encoding(encoding)) if size <= 8 => scalar_kind(encoding),
+                    _ => None,
+                };
+
+                Raw::Type(Type {
+                    name: name.unwrap_or_else(|| "<unnamed>".to_owned()),
+                    size,
+                    kind: kind.map_or(TypeKind::Opaque, TypeKind::Scalar),
+                })
+            }
+            gimli::DW_TAG_unspecified_type => Raw::Type(Type {
+                name: name.unwrap_or_else(|| "void".to_owned()),
+                size,
+                kind: TypeKind::Opaque,
+            }),
+            gimli::DW_TAG_subroutine_type => Raw::Type(Type {
+                name: "<function>".to_owned(),
+                size: 0,
+                kind: TypeKind::Opaque,
+            }),
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => Raw::Pointer {
+                name,
+                size: if size == 0 {
+                    unit.encoding().address_size as u32
+                } else {
+                    size
+                },
+                pointee: target,
+            },
+            gimli::DW_TAG_typedef => Raw::Alias {
+                name: name.map(|name| qualify(scope, &name)),
+                qualifier: "",
+                target,
+            },
+            gimli::DW_TAG_const_type => Raw::Alias {
+                name: None,
+                qualifier: "const",
+                target,
+            },
+            gimli::DW_TAG_volatile_type => Raw::Alias {
+                name: None,
+                qualifier: "volatile",
+                target,
+            },
+            gimli::DW_TAG_restrict_type => Raw::Alias {
+                name: None,
+                qualifier: "restrict",
+                target,
+            },
+            gimli::DW_TAG_array_type => return self.array(unit, node, id, target),
+            gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type => {
+                let name = name.map(|name| qualify(scope, &name));
+                if declaration {
+                    if let Some(name) = name {
+                        self.raw.insert(id, Raw::Declaration(name));
+                    }
+
+                    return Ok(());
+                }
+
+                let kind = if tag == gimli::DW_TAG_enumeration_type {
+                    TypeKind::Enum(self.enumerators(unit, node)?)
+                } else {
+                    let scope = name.as_deref().unwrap_or(scope);
+                    TypeKind::Struct(self.members(unit, node, scope)?)
+                };
+
+                if let Some(name) = &name {
+                    self.definitions.entry(name.clone()).or_insert(id);
+                }
+
+                Raw::Type(Type {
+                    name: name.unwrap_or_else(|| "<anonymous>".to_owned()),
+                    size,
+                    kind,
+                })
+            }
+            gimli::DW_TAG_variable => {
+                self.variable(unit, entry, scope);
+                return Ok(());
+            }
+            gimli::DW_TAG_namespace => {
+                let name = name.as_deref().unwrap_or("(anonymous namespace)");
+                return self.children(unit, node, &qualify(scope, name));
+            }
+            gimli::DW_TAG_subprogram => {
+                let scope = name.map_or_else(|| scope.to_owned(), |name| qualify(scope, &name));
+                return self.children(unit, node, &scope);
+            }
+            _ => return self.children(unit, node, scope),
+        };
+
+        self.raw.insert(id, raw);
+        Ok(())
+    }
+
+    fn children(
+        &mut self,
+        unit: &Unit<'a>,
+        node: Node<'_, '_, '_, 'a>,
+        scope: &str,
+    ) -> gimli::Result<()> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            self.walk(unit, child, scope)?;
+        }
+
+        Ok(())
+    }
+
+    fn array(
+        &mut self,
+        unit: &Unit<'a>,
+        node: Node<'_, '_, '_, 'a>,
+        id: TypeId,
+        element: Option<TypeId>,
+    ) -> gimli::Result<()> {
+        // multidimensional arrays are arrays of arrays, each inner one being identified by the
+        // subrange of its first dimension
+        let mut dimensions = Vec::new();
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            if entry.tag() != gimli::DW_TAG_subrange_type {
+                continue;
+            }
+
+            let count = udata(entry, gimli::DW_AT_count).or_else(|| {
+                let lower = udata(entry, gimli::DW_AT_lower_bound).unwrap_or(0);
+                let upper = udata(entry, gimli::DW_AT_upper_bound)?;
+                Some((upper + 1).saturating_sub(lower))
+            });
+
+            let dimension = if dimensions.is_empty() {
+                Some(id)
+            } else {
+                entry_id(unit, entry)
+            };
+
+            if let Some(dimension) = dimension {
+                dimensions.push((dimension, count.unwrap_or(0) as u32));
+            }
+        }
+
+        if dimensions.is_empty() {
+            dimensions.push((id, 0));
+        }
+
+        let mut element = element;
+        for (dimension, count) in dimensions.into_iter().rev() {
+            self.raw.insert(dimension, Raw::Array { element, count });
+            element = Some(dimension);
+        }
+
+        Ok(())
+    }
+
+    fn members(
+        &mut self,
+        unit: &Unit<'a>,
+        node: Node<'_, '_, '_, 'a>,
+        scope: &str,
+    ) -> gimli::Result<Vec<Member>> {
+        let mut members = Vec::new();
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_member | gimli::DW_TAG_inheritance => {
+                    // static members are declared as members, but live somewhere else
+                    if entry.attr_value(gimli::DW_AT_declaration)?.is_some()
+                        || entry.attr_value(gimli::DW_AT_external)?.is_some()
+                    {
+                        continue;
+                    }
+
+                    let Some(ty) = type_of(unit, entry) else {
+                        continue;
+                    };
+
+                    let name = if entry.tag() == gimli::DW_TAG_inheritance {
+                        "<base>".to_owned()
+                    } else {
+                        self.name(unit, entry)
+                            .unwrap_or_else(|| "<anonymous>".to_owned())
+                    };
+
+                    members.push(Member {
+                        name,
+                        offset: member_offset(unit, entry),
+                        ty,
+                    });
+                }
+                _ => self.walk(unit, child, scope)?,
+            }
+        }
+
+        Ok(members)
+    }
+
+    fn enumerators(
+        &mut self,
+        unit: &Unit<'a>,
+        node: Node<'_, '_, '_, 'a>,
+    ) -> gimli::Result<Vec<(String, i64)>> {
+        let mut enumerators = Vec::new();
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            if entry.tag() != gimli::DW_TAG_enumerator {
+                continue;
+            }
+
+            let value = entry
+                .attr_value(gimli::DW_AT_const_value)?
+                .and_then(|value| {
+                    value
+                        .udata_value()
+                        .map(|v| v as i64)
+                        .or(value.sdata_value())
+                });
+
+            if let (Some(name), Some(value)) = (self.name(unit, entry), value) {
+                enumerators.push((name, value));
+            }
+        }
+
+        Ok(enumerators)
+    }
+
+    fn variable(&mut self, unit: &Unit<'a>, entry: &Entry<'_, '_, 'a>, scope: &str) {
+        let Some(address) = fixed_address(unit, entry) else {
+            return;
+        };
+
+        // definitions of static members only refer to their declaration, which holds the name
+        // and the type
+        let specification = match entry.attr_value(gimli::DW_AT_specification) {
+            Ok(Some(AttributeValue::UnitRef(offset))) => unit.entry(offset).ok(),
+            _ => None,
+        };
+
+        let name = self
+            .name(unit, entry)
+            .or_else(|| self.name(unit, specification.as_ref()?));
+        let ty = type_of(unit, entry).or_else(|| type_of(unit, specification.as_ref()?));
+        let (Some(name), Some(ty)) = (name, ty) else {
+            return;
+        };
+
+        let variable = Variable {
+            name: qualify(scope, &name),
+            address,
+            ty,
+        };
+
+        // also reachable by the unqualified name, unless something else already is
+        if !scope.is_empty() {
+            self.variables
+                .entry(name)
+                .or_insert_with(|| variable.clone());
+        }
+
+        self.variables
+            .entry(variable.name.clone())
+            .or_insert(variable);
+    }
+
+    fn name_of(&self, id: Option<TypeId>, depth: u32) -> String {
+        let Some(id) = id else {
+            return "void".to_owned();
+        };
+
+        if depth > MAX_ALIAS_DEPTH {
+            return "?".to_owned();
+        }
+
+        match self.raw.get(&id) {
+            Some(Raw::Type(ty)) => ty.name.clone(),
+            Some(
+                Raw::Pointer {
+                    name: Some(name), ..
+                }
+                | Raw::Alias {
+                    name: Some(name), ..
+                }
+                | Raw::Declaration(name),
+            ) => name.clone(),
+            Some(Raw::Pointer { pointee, .. }) => {
+                format!("{} *", self.name_of(*pointee, depth + 1))
+            }
+            Some(Raw::Array { element, count }) => {
+                // the dimensions of inner arrays go after the outer one
+                let element = self.name_of(*element, depth + 1);
+                match element.find('[') {
+                    Some(i) => format!("{}[{count}]{}", &element[..i], &element[i..]),
+                    None => format!("{element}[{count}]"),
+                }
+            }
+            Some(Raw::Alias {
+                qualifier, target, ..
+            }) => format!("{qualifier} {}", self.name_of(*target, depth + 1)),
+            None => "?".to_owned(),
+        }
+    }
+
+    fn size_of(&self, id: Option<TypeId>, depth: u32) -> u32 {
+        let Some(id) = id else {
+            return 0;
+        };
+
+        if depth > MAX_ALIAS_DEPTH {
+            return 0;
+        }
+
+        match self.raw.get(&id) {
+            Some(Raw::Type(ty)) => ty.size,
+            Some(Raw::Pointer { size, .. }) => *size,
+            Some(Raw::Array { element, count }) => {
+                count.saturating_mul(self.size_of(*element, depth + 1))
+            }
+            Some(Raw::Alias { target, .. }) => self.size_of(*target, depth + 1),
+            Some(Raw::Declaration(name)) => {
+                let definition = self.definitions.get(name).copied();
+                self.size_of(definition.filter(|&d| d != id), depth + 1)
+            }
+            None => 0,
+        }
+    }
+
+    fn resolve(&self, id: TypeId, depth: u32) -> Option<Type> {
+        if depth > MAX_ALIAS_DEPTH {
+            return None;
+        }
+
+        Some(match self.raw.get(&id)? {
+            Raw::Type(ty) => ty.clone(),
+            Raw::Pointer { pointee, size, .. } => Type {
+                name: self.name_of(Some(id), depth),
+                size: *size,
+                kind: TypeKind::Pointer(*pointee),
+            },
+            Raw::Array { element, count } => Type {
+                name: self.name_of(Some(id), depth),
+                size: self.size_of(Some(id), depth),
+                kind: match element {
+                    Some(element) => TypeKind::Array {
+                        element: *element,
+                        count: *count,
+                    },
+                    None => TypeKind::Opaque,
+                },
+            },
+            Raw::Alias { target, .. } => {
+                let ty = match target {
+                    Some(target) => self.resolve(*target, depth + 1)?,
+                    None => void(),
+                };
+
+                Type {
+                    name: self.name_of(Some(id), depth),
+                    ..ty
+                }
+            }
+            Raw::Declaration(name) => match self.definitions.get(name) {
+                Some(&definition) if definition != id => self.resolve(definition, depth + 1)?,
+                _ => Type {
+                    name: name.clone(),
+                    size: 0,
+                    kind: TypeKind::Opaque,
+                },
+            },
+        })
+    }
+}
+
+/// Types and variables with fixed addresses described by the DWARF info of an object file.
+#[derive(Default)]
+pub struct DebugTypes {
+    types: HashMap<TypeId, Type>,
+    variables: HashMap<String, Variable>,
+}
+
+impl DebugTypes {
+    /// Parses the DWARF info of the given object file. Units which fail to parse are skipped.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let file = object::File::parse(data).ok()?;
+        let endian = if file.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+
+        let Ok(sections) = gimli::DwarfSections::load(|id| {
+            let data = file
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok());
+            Ok::<_, Infallible>(data.unwrap_or(Cow::Borrowed(&[])))
+        });
+
+        let mut parser = Parser {
+            dwarf: sections.borrow(|section| EndianSlice::new(section, endian)),
+            raw: HashMap::new(),
+            definitions: HashMap::new(),
+            variables: HashMap::new(),
+        };
+
+        let mut units = parser.dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let result = parser.dwarf.unit(header).and_then(|unit| {
+                let mut tree = unit.entries_tree(None)?;
+                let root = tree.root()?;
+                parser.walk(&unit, root, "")
+            });
+
+            if let Err(e) = result {
+                tracing::warn!("skipping debug info unit: {e}");
+            }
+        }
+
+        let types = parser
+            .raw
+            .keys()
+            .filter_map(|&id| Some((id, parser.resolve(id, 0)?)))
+            .collect();
+
+        Some(Self {
+            types,
+            variables: parser.variables,
+        })
+    }
+
+    pub fn find_variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.get(name)
+    }
+
+    pub fn find_type(&self, ty: TypeId) -> Option<&Type> {
+        self.types.get(&ty)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Built from `testdata/types.rs`, see there for how.
+    const FIXTURE: &[u8] = include_bytes!("../../testdata/types.elf");
+
+    fn members(types: &DebugTypes, ty: TypeId) -> Vec<(&str, u32, &Type)> {
+        let TypeKind::Struct(members) = &types.find_type(ty).unwrap().kind else {
+            panic!("not a struct");
+        };
+
+        members
+            .iter()
+            .map(|m| (m.name.as_str(), m.offset, types.find_type(m.ty).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn variables() {
+        let types = DebugTypes::parse(FIXTURE).unwrap();
+
+        let counter = types.find_variable("COUNTER").unwrap();
+        assert_eq!(counter.address, Address(0x8000_3100));
+        let ty = types.find_type(counter.ty).unwrap();
+        assert_eq!(ty.name, "u32");
+        assert_eq!(ty.size, 4);
+        assert!(matches!(ty.kind, TypeKind::Scalar(ScalarKind::Unsigned)));
+
+        let a = types.find_variable("NODE_A").unwrap();
+        let b = types.find_variable("types::NODE_B").unwrap();
+        assert_eq!(a.address, Address(0x8000_3104));
+        assert_eq!(b.address, Address(0x8000_3124));
+        assert_eq!(a.ty, b.ty);
+
+        assert!(types.find_variable("NODE_C").is_none());
+    }
+
+    #[test]
+    fn struct_layout() {
+        let types = DebugTypes::parse(FIXTURE).unwrap();
+        let node = types.find_variable("NODE_A").unwrap().ty;
+        assert_eq!(types.find_type(node).unwrap().name, "types::Node");
+        assert_eq!(types.find_type(node).unwrap().size, 32);
+
+        let members = members(&types, node);
+        let layout: Vec<_> = members
+            .iter()
+            .map(|(name, offset, ty)| (*name, *offset, ty.name.as_str(), ty.size))
+            .collect();
+
+        assert_eq!(
+            layout,
+            [
+                ("id", 0, "u16", 2),
+                ("flags", 2, "u8", 1),
+                ("position", 4, "types::Vec3", 12),
+                ("scores", 16, "i32[3]", 12),
+                ("next", 28, "*mut types::Node", 4),
+            ]
+        );
+
+        let TypeKind::Struct(position) = &members[2].2.kind else {
+            panic!("not a struct");
+        };
+
+        for (member, (name, offset)) in position.iter().zip([("x", 0), ("y", 4), ("z", 8)]) {
+            assert_eq!((member.name.as_str(), member.offset), (name, offset));
+            let ty = types.find_type(member.ty).unwrap();
+            assert!(matches!(ty.kind, TypeKind::Scalar(ScalarKind::Float)));
+        }
+    }
+
+    #[test]
+    fn arrays_and_pointers() {
+        let types = DebugTypes::parse(FIXTURE).unwrap();
+        let node = types.find_variable("NODE_A").unwrap().ty;
+        let members = members(&types, node);
+
+        let TypeKind::Array { element, count } = members[3].2.kind else {
+            panic!("not an array");
+        };
+        assert_eq!(count, 3);
+        let element = types.find_type(element).unwrap();
+        assert_eq!(element.size, 4);
+        assert!(matches!(element.kind, TypeKind::Scalar(ScalarKind::Signed)));
+
+        // the pointer leads back to the struct it is a member of
+        let TypeKind::Pointer(pointee) = members[4].2.kind else {
+            panic!("not a pointer");
+        };
+        assert_eq!(pointee, Some(node));
+    }
+}
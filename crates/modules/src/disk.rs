@@ -1,79 +1,504 @@
+use std::collections::VecDeque;
 use std::io::{Read, Seek, SeekFrom};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Instant;
 
-use lazuli::disks::rvz::{Rvz, RvzReader};
-use lazuli::modules::disk::DiskModule;
+use lazuli::disks::DiscImage;
+use lazuli::disks::iso::mmap::IsoMmap;
+use lazuli::modules::disk::{DiskModule, DiskRead};
 
-/// An implementation of [`DiskModule`] for raw .iso data from a reader.
-#[derive(Debug)]
-pub struct IsoModule<R>(pub Option<R>);
+/// How many reads an [`ActivityLog`] keeps around.
+const ACTIVITY_LEN: usize = 1024;
 
-impl<R> Read for IsoModule<R>
-where
-    R: Read,
-{
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if let Some(r) = &mut self.0 {
-            r.read(buf)
-        } else {
-            Err(std::io::Error::other("no disk inserted"))
+/// Rolling window of the most recent reads of a disk.
+#[derive(Default)]
+struct ActivityLog {
+    reads: VecDeque<DiskRead>,
+    /// Index of the first read in `reads` since the log was created.
+    first: u64,
+    in_flight: u32,
+}
+
+impl ActivityLog {
+    /// Records the start of a read, returning an id to later [`end`](Self::end) it with.
+    fn begin(&mut self, offset: u64, length: u64) -> u64 {
+        if self.reads.len() == ACTIVITY_LEN {
+            self.reads.pop_front();
+            self.first += 1;
+        }
+
+        self.in_flight += 1;
+        self.reads.push_back(DiskRead {
+            start: Instant::now(),
+            offset,
+            length,
+            duration: None,
+            queue_depth: self.in_flight,
+        });
+
+        self.first + self.reads.len() as u64 - 1
+    }
+
+    /// Records the end of a read. Reads which already left the window are only counted out.
+    fn end(&mut self, id: u64) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        if let Some(index) = id.checked_sub(self.first)
+            && let Some(read) = self.reads.get_mut(index as usize)
+        {
+            read.duration = Some(read.start.elapsed());
+        }
+    }
+
+    fn recent(&self) -> Vec<DiskRead> {
+        self.reads.iter().copied().collect()
+    }
+}
+
+/// An implementation of [`DiskModule`] for any [`DiscImage`].
+pub struct DiscModule {
+    image: Box<dyn DiscImage + Send>,
+    position: u64,
+    activity: ActivityLog,
+}
+
+impl DiscModule {
+    pub fn new(image: Box<dyn DiscImage + Send>) -> Self {
+        Self {
+            image,
+            position: 0,
+            activity: ActivityLog::default(),
         }
     }
 }
 
-impl<R> Seek for IsoModule<R>
-where
-    R: Seek,
-{
+impl Read for DiscModule {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let id = self.activity.begin(self.position, buf.len() as u64);
+        let read = self.image.read(self.position, buf);
+        self.activity.end(id);
+
+        let read = read?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for DiscModule {
     fn seek(&mut self, from: SeekFrom) -> std::io::Result<u64> {
-        if let Some(r) = &mut self.0 {
-            r.seek(from)
-        } else {
-            Err(std::io::Error::other("no disk inserted"))
+        match from {
+            SeekFrom::Start(x) => self.position = x,
+            SeekFrom::End(x) => self.position = self.image.size().saturating_add_signed(x),
+            SeekFrom::Current(x) => self.position = self.position.saturating_add_signed(x),
         }
+
+        Ok(self.position)
     }
 }
 
-impl<R> DiskModule for IsoModule<R>
-where
-    R: Read + Seek + Send,
-{
+impl DiskModule for DiscModule {
     fn has_disk(&self) -> bool {
-        self.0.is_some()
+        true
+    }
+
+    fn recent_activity(&self) -> Vec<DiskRead> {
+        self.activity.recent()
     }
 }
 
-/// An implementation of [`DiskModule`] for .rvz disks.
-pub struct RvzModule<R>(RvzReader<R>);
+/// An implementation of [`DiskModule`] for a memory mapped .iso. Reads copy straight from the
+/// mapping, so no intermediate buffering is needed.
+pub struct MappedDiscModule {
+    iso: IsoMmap,
+    position: u64,
+    activity: ActivityLog,
+}
 
-impl<R> RvzModule<R> {
-    pub fn new(rvz: Rvz<R>) -> Self {
-        Self(RvzReader::new(rvz))
+impl MappedDiscModule {
+    pub fn new(iso: IsoMmap) -> Self {
+        Self {
+            iso,
+            position: 0,
+            activity: ActivityLog::default(),
+        }
     }
 }
 
-impl<R> Read for RvzModule<R>
-where
-    R: Read + Seek,
-{
+impl Read for MappedDiscModule {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.0.read(buf)
+        let id = self.activity.begin(self.position, buf.len() as u64);
+        let read = self.iso.slice(self.position, buf.len()).map(|data| {
+            buf[..data.len()].copy_from_slice(data);
+            data.len()
+        });
+        self.activity.end(id);
+
+        let read = read?;
+
+        self.position += read as u64;
+        Ok(read)
     }
 }
 
-impl<R> Seek for RvzModule<R>
-where
-    R: Read + Seek,
-{
+impl Seek for MappedDiscModule {
     fn seek(&mut self, from: SeekFrom) -> std::io::Result<u64> {
-        self.0.seek(from)
+        match from {
+            SeekFrom::Start(x) => self.position = x,
+            SeekFrom::End(x) => self.position = self.iso.size().saturating_add_signed(x),
+            SeekFrom::Current(x) => self.position = self.position.saturating_add_signed(x),
+        }
+
+        Ok(self.position)
     }
 }
 
-impl<R> DiskModule for RvzModule<R>
-where
-    R: Read + Seek + Send,
-{
+impl DiskModule for MappedDiscModule {
     fn has_disk(&self) -> bool {
         true
     }
+
+    fn recent_activity(&self) -> Vec<DiskRead> {
+        self.activity.recent()
+    }
+}
+
+/// Size of a single read-ahead chunk.
+const PREFETCH_CHUNK_SIZE: usize = 256 * 1024;
+/// How many chunks are kept ready (or in flight) ahead of a sequential reader.
+const PREFETCH_CHUNK_COUNT: usize = 16;
+
+/// Read-ahead statistics of a [`Prefetcher`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrefetchStats {
+    /// How many reads were served from the prefetch buffers.
+    pub hits: u64,
+    /// How many reads had to go to the underlying reader.
+    pub misses: u64,
+}
+
+impl PrefetchStats {
+    /// Ratio of reads served from the prefetch buffers.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+struct Chunk {
+    offset: u64,
+    data: std::io::Result<Vec<u8>>,
+}
+
+/// A [`Read`] + [`Seek`] adapter which detects sequential reads and prefetches the data that
+/// follows them on a background thread.
+///
+/// Seeking is free - the underlying reader is only touched by the background thread, and prefetched
+/// data is dropped once a read lands outside of it.
+pub struct Prefetcher {
+    requests: Option<mpsc::Sender<(u64, usize)>>,
+    responses: mpsc::Receiver<Chunk>,
+    worker: Option<JoinHandle<()>>,
+
+    len: u64,
+    position: u64,
+    last_read_end: Option<u64>,
+    streaming: bool,
+
+    /// Chunks which have been read, in order.
+    ready: VecDeque<(u64, Vec<u8>)>,
+    /// Chunks which have been requested but not received yet, in order.
+    in_flight: VecDeque<(u64, usize)>,
+    /// End of the last requested chunk.
+    requested_end: u64,
+
+    stats: PrefetchStats,
+}
+
+impl Prefetcher {
+    pub fn new<R>(mut reader: R) -> std::io::Result<Self>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let (requests, worker_requests) = mpsc::channel::<(u64, usize)>();
+        let (worker_responses, responses) = mpsc::channel();
+
+        let worker = std::thread::Builder::new()
+            .name("disk prefetcher".to_owned())
+            .spawn(move || {
+                while let Ok((offset, len)) = worker_requests.recv() {
+                    let data = reader.seek(SeekFrom::Start(offset)).and_then(|_| {
+                        let mut data = Vec::with_capacity(len);
+                        (&mut reader).take(len as u64).read_to_end(&mut data)?;
+                        Ok(data)
+                    });
+
+                    if worker_responses.send(Chunk { offset, data }).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            requests: Some(requests),
+            responses,
+            worker: Some(worker),
+
+            len,
+            position: 0,
+            last_read_end: None,
+            streaming: false,
+
+            ready: VecDeque::new(),
+            in_flight: VecDeque::new(),
+            requested_end: 0,
+
+            stats: PrefetchStats::default(),
+        })
+    }
+
+    /// Read-ahead statistics.
+    pub fn stats(&self) -> PrefetchStats {
+        self.stats
+    }
+
+    fn request(&mut self, offset: u64, len: usize) {
+        self.requests
+            .as_ref()
+            .unwrap()
+            .send((offset, len))
+            .expect("prefetcher thread is alive");
+
+        self.in_flight.push_back((offset, len));
+        self.requested_end = offset + len as u64;
+    }
+
+    /// Receives the oldest in-flight chunk.
+    fn receive(&mut self) -> std::io::Result<()> {
+        let chunk = self.responses.recv().expect("prefetcher thread is alive");
+        let expected = self.in_flight.pop_front();
+        debug_assert_eq!(expected.map(|(offset, _)| offset), Some(chunk.offset));
+
+        self.ready.push_back((chunk.offset, chunk.data?));
+        Ok(())
+    }
+
+    /// Drops all prefetched data.
+    fn invalidate(&mut self) {
+        while !self.in_flight.is_empty() {
+            _ = self.responses.recv();
+            self.in_flight.pop_front();
+        }
+
+        self.ready.clear();
+        self.streaming = false;
+    }
+
+    /// Keeps the read-ahead window full while streaming.
+    fn refill(&mut self) {
+        if !self.streaming {
+            return;
+        }
+
+        while self.ready.len() + self.in_flight.len() < PREFETCH_CHUNK_COUNT
+            && self.requested_end < self.len
+        {
+            let len = PREFETCH_CHUNK_SIZE.min((self.len - self.requested_end) as usize);
+            self.request(self.requested_end, len);
+        }
+    }
+
+    /// Copies data at the current position from the ready chunks, if available.
+    fn serve(&mut self, buf: &mut [u8]) -> Option<usize> {
+        // chunks behind the current position won't be needed anymore
+        while let Some((offset, data)) = self.ready.front()
+            && offset + data.len() as u64 <= self.position
+        {
+            self.ready.pop_front();
+        }
+
+        let (offset, data) = self.ready.front()?;
+        if *offset > self.position {
+            return None;
+        }
+
+        let data = &data[(self.position - offset) as usize..];
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+
+        Some(len)
+    }
+
+    fn is_in_flight(&self, position: u64) -> bool {
+        self.in_flight
+            .iter()
+            .any(|&(offset, len)| (offset..offset + len as u64).contains(&position))
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        // closing the channel stops the worker
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+impl Read for Prefetcher {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        let sequential = self.last_read_end == Some(self.position);
+        let mut hit = true;
+        let read = loop {
+            if let Some(read) = self.serve(buf) {
+                break read;
+            }
+
+            if self.is_in_flight(self.position) {
+                self.receive()?;
+                continue;
+            }
+
+            // not prefetched - go to the reader, and start streaming if this read is sequential
+            hit = false;
+            self.invalidate();
+            if sequential {
+                self.streaming = true;
+                self.requested_end = self.position;
+                self.refill();
+            } else {
+                let len = buf.len().min((self.len - self.position) as usize);
+                self.request(self.position, len);
+            }
+        };
+
+        if hit {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+
+        self.position += read as u64;
+        self.last_read_end = Some(self.position);
+        self.refill();
+
+        Ok(read)
+    }
+}
+
+impl Seek for Prefetcher {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        let Some(new) = new else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        };
+
+        self.position = new;
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i ^ (i >> 8) ^ (i >> 16)) as u8).collect()
+    }
+
+    #[test]
+    fn sequential_reads_are_prefetched() {
+        let data = data(8 * 1024 * 1024);
+        let mut prefetcher = Prefetcher::new(Cursor::new(data.clone())).unwrap();
+
+        let mut offset = 0x1000;
+        prefetcher.seek(SeekFrom::Start(offset as u64)).unwrap();
+
+        let mut buf = vec![0; 0x800];
+        for _ in 0..2048 {
+            prefetcher.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[offset..][..buf.len()]);
+            offset += buf.len();
+        }
+
+        let stats = prefetcher.stats();
+        assert!(stats.hit_rate() > 0.95, "{stats:?}");
+    }
+
+    #[test]
+    fn distant_seek_invalidates() {
+        let data = data(8 * 1024 * 1024);
+        let mut prefetcher = Prefetcher::new(Cursor::new(data.clone())).unwrap();
+
+        let mut buf = vec![0; 0x800];
+        for offset in [0, 0x800, 0x1000, 0x70_0000, 0x70_0800, 0x10] {
+            prefetcher.seek(SeekFrom::Start(offset as u64)).unwrap();
+            prefetcher.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, data[offset..][..buf.len()]);
+        }
+
+        let stats = prefetcher.stats();
+        assert_eq!(stats.misses, 5, "{stats:?}");
+    }
+
+    #[test]
+    fn reads_past_end() {
+        let data = data(PREFETCH_CHUNK_SIZE + 100);
+        let mut prefetcher = Prefetcher::new(Cursor::new(data.clone())).unwrap();
+
+        let mut read = Vec::new();
+        prefetcher.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn activity_is_a_rolling_window() {
+        let mut log = ActivityLog::default();
+
+        let outer = log.begin(0, 0x800);
+        let inner = log.begin(0x800, 0x800);
+        let recent = log.recent();
+        assert_eq!(recent.len(), 2);
+        assert_eq!([recent[0].queue_depth, recent[1].queue_depth], [1, 2]);
+        assert!(recent.iter().all(|read| read.duration.is_none()));
+
+        log.end(inner);
+        let recent = log.recent();
+        assert!(recent[0].duration.is_none());
+        assert!(recent[1].duration.is_some());
+
+        for i in 0..2 * ACTIVITY_LEN as u64 {
+            let id = log.begin(0x1000 + i, 1);
+            log.end(id);
+        }
+
+        // the outer read left the window, ending it must not touch the newer ones
+        log.end(outer);
+        let recent = log.recent();
+        assert_eq!(recent.len(), ACTIVITY_LEN);
+        assert_eq!(recent[0].offset, 0x1000 + ACTIVITY_LEN as u64);
+        assert!(recent.windows(2).all(|w| w[0].offset < w[1].offset));
+        assert!(recent.windows(2).all(|w| w[0].start <= w[1].start));
+        assert!(recent.iter().all(|read| read.queue_depth == 2));
+        assert_eq!(log.in_flight, 0);
+    }
 }
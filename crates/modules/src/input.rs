@@ -1,9 +1,87 @@
-use gilrs::{Axis, Button, GamepadId, Gilrs};
-use lazuli::modules::input::{ControllerState, InputModule};
+use std::collections::HashMap;
+
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use lazuli::modules::input::{ControllerState, Device, DeviceId, InputModule, PORT_COUNT};
+
+/// GUID of a device model, as reported by SDL compatible mappings.
+pub type Guid = [u8; 16];
+
+/// Deadzone a calibration starts with.
+const MIN_DEADZONE: f32 = 0.05;
+/// Largest deadzone a calibration adapts to.
+const MAX_DEADZONE: f32 = 0.3;
+/// For how many polls after a device is connected its sticks are sampled to adapt the deadzone.
+/// Like the controllers themselves do, sticks are assumed to be at rest while this happens.
+const CALIBRATION_POLLS: u32 = 30;
+
+/// Stick axes, in calibration order.
+const STICK_AXES: [Axis; 4] = [
+    Axis::LeftStickX,
+    Axis::LeftStickY,
+    Axis::RightStickX,
+    Axis::RightStickY,
+];
+
+/// Stick calibration of a device model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// Resting position of each stick axis, in [`STICK_AXES`] order.
+    pub center: [f32; 4],
+    /// How far from its resting position an axis has to be to not read as centered.
+    pub deadzone: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            center: [0.0; 4],
+            deadzone: MIN_DEADZONE,
+        }
+    }
+}
+
+impl Calibration {
+    /// Adapts the calibration to a sample of sticks at rest. The first sample sets the center,
+    /// while later ones widen the deadzone to cover any drift around it.
+    pub fn adapt(&mut self, rest: [f32; 4], first: bool) {
+        if first {
+            self.center = rest;
+            return;
+        }
+
+        for (value, center) in rest.into_iter().zip(self.center) {
+            let drift = (value - center).abs();
+            self.deadzone = self.deadzone.max(drift).min(MAX_DEADZONE);
+        }
+    }
+
+    /// Converts the value of the stick axis with the given index into a controller value, where
+    /// 128 is centered.
+    pub fn apply(&self, axis: usize, value: f32) -> u8 {
+        let value = value - self.center[axis];
+        let magnitude = ((value.abs() - self.deadzone) / (1.0 - self.deadzone)).clamp(0.0, 1.0);
+        let value = magnitude.copysign(value);
+
+        (128.0 + 127.5 * value).clamp(0.0, 255.0) as u8
+    }
+}
+
+struct GilrsDevice {
+    id: DeviceId,
+    gamepad: GamepadId,
+    name: String,
+    guid: Guid,
+    connected: bool,
+    /// How many more polls to adapt the calibration with.
+    calibrating: u32,
+}
 
 pub struct GilrsModule {
     gilrs: Gilrs,
-    active_gamepad: Option<GamepadId>,
+    devices: Vec<GilrsDevice>,
+    ports: [Option<DeviceId>; PORT_COUNT],
+    auto_assign: bool,
+    calibrations: HashMap<Guid, Calibration>,
 }
 
 impl Default for GilrsModule {
@@ -15,44 +93,123 @@ impl Default for GilrsModule {
 impl GilrsModule {
     pub fn new() -> Self {
         let gilrs = Gilrs::new().unwrap();
-        Self {
-            active_gamepad: gilrs.gamepads().next().map(|g| g.0),
+        let connected = gilrs.gamepads().map(|(id, _)| id).collect::<Vec<_>>();
+
+        let mut module = Self {
             gilrs,
+            devices: Vec::new(),
+            ports: [None; PORT_COUNT],
+            auto_assign: true,
+            calibrations: HashMap::new(),
+        };
+
+        for gamepad in connected {
+            module.connect(gamepad);
+        }
+
+        module
+    }
+
+    /// Sets whether newly connected devices are assigned to the first port without a device.
+    pub fn set_auto_assign(&mut self, auto_assign: bool) {
+        self.auto_assign = auto_assign;
+    }
+
+    /// Calibrations of every device model seen so far.
+    pub fn calibrations(&self) -> &HashMap<Guid, Calibration> {
+        &self.calibrations
+    }
+
+    /// Sets the calibrations of device models, e.g. ones stored from a previous session.
+    pub fn set_calibrations(&mut self, calibrations: HashMap<Guid, Calibration>) {
+        self.calibrations = calibrations;
+    }
+
+    fn connect(&mut self, gamepad: GamepadId) {
+        let device = match self.devices.iter_mut().position(|d| d.gamepad == gamepad) {
+            Some(index) => &mut self.devices[index],
+            None => {
+                let info = self.gilrs.gamepad(gamepad);
+                let id = DeviceId(self.devices.len() as u32);
+                self.devices.push(GilrsDevice {
+                    id,
+                    gamepad,
+                    name: info.name().to_owned(),
+                    guid: info.uuid(),
+                    connected: false,
+                    calibrating: 0,
+                });
+
+                self.devices.last_mut().unwrap()
+            }
+        };
+
+        tracing::info!("gamepad {:?} ({}) connected", device.id, device.name);
+        device.connected = true;
+        device.calibrating = CALIBRATION_POLLS;
+
+        let id = device.id;
+        if self.auto_assign
+            && !self.ports.contains(&Some(id))
+            && let Some(port) = self.ports.iter_mut().find(|port| port.is_none())
+        {
+            *port = Some(id);
+        }
+    }
+
+    fn disconnect(&mut self, gamepad: GamepadId) {
+        if let Some(device) = self.devices.iter_mut().find(|d| d.gamepad == gamepad) {
+            tracing::info!("gamepad {:?} ({}) disconnected", device.id, device.name);
+            device.connected = false;
         }
     }
 
     fn process_events(&mut self) {
         while let Some(event) = self.gilrs.next_event() {
-            if self.active_gamepad.is_none() {
-                self.active_gamepad = Some(event.id);
+            match event.event {
+                EventType::Connected => self.connect(event.id),
+                EventType::Disconnected => self.disconnect(event.id),
+                _ => (),
             }
         }
     }
+
+    /// Returns the connected device assigned to the given port.
+    fn port_device(&mut self, index: usize) -> Option<&mut GilrsDevice> {
+        let id = (*self.ports.get(index)?)?;
+        self.devices
+            .iter_mut()
+            .find(|d| d.id == id)
+            .filter(|d| d.connected)
+    }
 }
 
 impl InputModule for GilrsModule {
     fn controller(&mut self, index: usize) -> Option<ControllerState> {
         self.process_events();
 
-        if index != 0 {
-            return None;
-        }
+        let device = self.port_device(index)?;
+        let (gamepad_id, guid) = (device.gamepad, device.guid);
+        let calibrating = device.calibrating;
+        device.calibrating = calibrating.saturating_sub(1);
 
-        let gamepad_id = self.active_gamepad?;
-        let Some(gamepad) = self.gilrs.connected_gamepad(gamepad_id) else {
-            self.active_gamepad = None;
-            return None;
-        };
+        let gamepad = self.gilrs.connected_gamepad(gamepad_id)?;
+        let sticks = STICK_AXES.map(|axis| gamepad.value(axis));
 
-        let axis = |axis| (255.0 * ((gamepad.value(axis) + 1.0) / 2.0)) as u8;
+        let calibration = self.calibrations.entry(guid).or_default();
+        if calibrating > 0 {
+            calibration.adapt(sticks, calibrating == CALIBRATION_POLLS);
+        }
+
+        let stick = |index: usize| calibration.apply(index, sticks[index]);
         let button =
             |button| (255.0 * gamepad.button_data(button).map_or(0.0, |v| v.value())) as u8;
 
         Some(ControllerState {
-            analog_x: axis(Axis::LeftStickX),
-            analog_y: axis(Axis::LeftStickY),
-            analog_sub_x: axis(Axis::RightStickX),
-            analog_sub_y: axis(Axis::RightStickY),
+            analog_x: stick(0),
+            analog_y: stick(1),
+            analog_sub_x: stick(2),
+            analog_sub_y: stick(3),
             analog_trigger_left: button(Button::LeftTrigger2),
             analog_trigger_right: button(Button::RightTrigger2),
             trigger_z: gamepad.is_pressed(Button::Z),
@@ -69,4 +226,70 @@ impl InputModule for GilrsModule {
             button_start: gamepad.is_pressed(Button::Start),
         })
     }
+
+    fn is_connected(&mut self, index: usize) -> bool {
+        self.process_events();
+        self.port_device(index).is_some()
+    }
+
+    fn devices(&self) -> Vec<Device> {
+        self.devices
+            .iter()
+            .map(|d| Device {
+                id: d.id,
+                name: d.name.clone(),
+                connected: d.connected,
+            })
+            .collect()
+    }
+
+    fn ports(&self) -> [Option<DeviceId>; PORT_COUNT] {
+        self.ports
+    }
+
+    fn assign(&mut self, port: usize, device: Option<DeviceId>) {
+        if let Some(device) = device {
+            for assigned in &mut self.ports {
+                if *assigned == Some(device) {
+                    *assigned = None;
+                }
+            }
+        }
+
+        self.ports[port] = device;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn calibration_deadzone() {
+        let calibration = Calibration::default();
+        assert_eq!(calibration.apply(0, 0.0), 128);
+        assert_eq!(calibration.apply(0, MIN_DEADZONE * 0.9), 128);
+        assert_eq!(calibration.apply(0, 1.0), 255);
+        assert_eq!(calibration.apply(0, -1.0), 0);
+    }
+
+    #[test]
+    fn calibration_adapts_to_drift() {
+        let mut calibration = Calibration::default();
+        calibration.adapt([0.1, -0.1, 0.0, 0.0], true);
+        assert_eq!(calibration.center, [0.1, -0.1, 0.0, 0.0]);
+        assert_eq!(calibration.deadzone, MIN_DEADZONE);
+
+        // a drifting stick widens the deadzone, up to a limit
+        calibration.adapt([0.2, -0.1, 0.0, 0.0], false);
+        assert!((calibration.deadzone - 0.1).abs() < 1e-6);
+        calibration.adapt([1.0, -0.1, 0.0, 0.0], false);
+        assert_eq!(calibration.deadzone, MAX_DEADZONE);
+
+        // the resting position reads as centered, and the full range is still reachable
+        assert_eq!(calibration.apply(0, 0.1), 128);
+        assert_eq!(calibration.apply(1, -0.1), 128);
+        assert_eq!(calibration.apply(0, 1.1), 255);
+        assert_eq!(calibration.apply(0, -0.9), 0);
+    }
 }
@@ -10,19 +10,29 @@ use lazuli::system::gx::glam::Vec2;
 use lazuli::system::gx::{MatrixId, MatrixSet, Vertex};
 use seq_macro::seq;
 
+/// Reads an element of an array. If it's out of bounds of RAM, counts a violation and returns
+/// `None`, so that it reads as zero.
 #[inline(always)]
 fn read_attribute_from_array<D: AttributeDescriptor>(
     ram: &[u8],
     descriptor: &D,
     array: ArrayDescriptor,
     index: u16,
-) -> D::Value {
+    violations: &mut u32,
+) -> Option<D::Value> {
     let base = array.address.value() as usize;
     let offset = array.stride as usize * index as usize;
     let address = base + offset;
-    let mut array = &ram[address..];
-    let mut reader = array.reader();
-    descriptor.read(&mut reader).unwrap()
+    let value = ram.get(address..).and_then(|mut array| {
+        let mut reader = array.reader();
+        descriptor.read(&mut reader)
+    });
+
+    if value.is_none() {
+        *violations += 1;
+    }
+
+    value
 }
 
 #[inline(always)]
@@ -31,6 +41,7 @@ fn read_attribute<A: Attribute>(
     vcd: &VertexDescriptor,
     vat: &VertexAttributeTable,
     reader: &mut BinReader,
+    violations: &mut u32,
 ) -> Option<<A::Descriptor as AttributeDescriptor>::Value> {
     let mode = A::get_mode(vcd);
     let descriptor = A::get_descriptor(vat);
@@ -45,22 +56,12 @@ fn read_attribute<A: Attribute>(
         AttributeMode::Index8 => {
             let index = reader.read_be::<u8>().unwrap() as u16;
             let array = A::get_array(ctx.arrays).unwrap();
-            Some(read_attribute_from_array(
-                ctx.ram,
-                &descriptor,
-                array,
-                index,
-            ))
+            read_attribute_from_array(ctx.ram, &descriptor, array, index, violations)
         }
         AttributeMode::Index16 => {
             let index = reader.read_be::<u16>().unwrap();
             let array = A::get_array(ctx.arrays).unwrap();
-            Some(read_attribute_from_array(
-                ctx.ram,
-                &descriptor,
-                array,
-                index,
-            ))
+            read_attribute_from_array(ctx.ram, &descriptor, array, index, violations)
         }
     }
 }
@@ -76,15 +77,21 @@ impl VertexModule for InterpreterModule {
         stream: &VertexAttributeStream,
         vertices: &mut [MaybeUninit<Vertex>],
         matrix_set: &mut MatrixSet,
-    ) {
+    ) -> u32 {
         let default_pos_matrix_idx = ctx.default_matrices.view().value();
 
         let mut data = stream.data();
         let mut reader = data.reader();
+        let mut violations = 0;
         for i in 0..stream.count() {
-            let pos_norm_matrix =
-                read_attribute::<attributes::PosMatrixIndex>(ctx, vcd, vat, &mut reader)
-                    .unwrap_or(default_pos_matrix_idx);
+            let pos_norm_matrix = read_attribute::<attributes::PosMatrixIndex>(
+                ctx,
+                vcd,
+                vat,
+                &mut reader,
+                &mut violations,
+            )
+            .unwrap_or(default_pos_matrix_idx);
 
             let pos_norm_matrix = MatrixId::from_position_idx(pos_norm_matrix);
             matrix_set.include(pos_norm_matrix);
@@ -99,39 +106,55 @@ impl VertexModule for InterpreterModule {
                         .unwrap()
                         .value();
 
-                    let tex_matrix_index =
-                        read_attribute::<attributes::TexMatrixIndex<N>>(ctx, vcd, vat, &mut reader)
-                            .unwrap_or(default);
+                    let tex_matrix_index = read_attribute::<attributes::TexMatrixIndex<N>>(
+                        ctx,
+                        vcd,
+                        vat,
+                        &mut reader,
+                        &mut violations,
+                    )
+                    .unwrap_or(default);
 
                     tex_coords_matrix[N] = MatrixId::from_position_idx(tex_matrix_index);
                     matrix_set.include(tex_coords_matrix[N]);
                 }
             }
 
-            let position = read_attribute::<attributes::Position>(ctx, vcd, vat, &mut reader)
-                .unwrap_or_default();
+            let position =
+                read_attribute::<attributes::Position>(ctx, vcd, vat, &mut reader, &mut violations)
+                    .unwrap_or_default();
 
-            let normal = read_attribute::<attributes::Normal>(ctx, vcd, vat, &mut reader)
-                .unwrap_or_default();
+            let normals =
+                read_attribute::<attributes::Normal>(ctx, vcd, vat, &mut reader, &mut violations)
+                    .unwrap_or_default();
 
             let chan0 =
-                read_attribute::<attributes::Chan0>(ctx, vcd, vat, &mut reader).unwrap_or_default();
+                read_attribute::<attributes::Chan0>(ctx, vcd, vat, &mut reader, &mut violations)
+                    .unwrap_or_default();
 
             let chan1 =
-                read_attribute::<attributes::Chan1>(ctx, vcd, vat, &mut reader).unwrap_or_default();
+                read_attribute::<attributes::Chan1>(ctx, vcd, vat, &mut reader, &mut violations)
+                    .unwrap_or_default();
 
             let mut tex_coords = [Vec2::ZERO; 8];
             seq! {
                 N in 0..8 {
-                    tex_coords[N] =
-                        read_attribute::<attributes::TexCoords<N>>(ctx, vcd, vat, &mut reader)
-                        .unwrap_or_default();
+                    tex_coords[N] = read_attribute::<attributes::TexCoords<N>>(
+                        ctx,
+                        vcd,
+                        vat,
+                        &mut reader,
+                        &mut violations,
+                    )
+                    .unwrap_or_default();
                 }
             }
 
             vertices[i as usize].write(Vertex {
                 position,
-                normal,
+                normal: normals.normal,
+                binormal: normals.binormal,
+                tangent: normals.tangent,
                 pos_norm_matrix,
                 chan0,
                 chan1,
@@ -139,5 +162,7 @@ impl VertexModule for InterpreterModule {
                 tex_coords_matrix,
             });
         }
+
+        violations
     }
 }
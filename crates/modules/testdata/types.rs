@@ -0,0 +1,75 @@
+//! Source of `types.elf`, the fixture of the DWARF type tests: a big-endian PowerPC executable
+//! with debug info, laid out like a game would be. There's no core for that target, so it brings
+//! its own lang items. Built with:
+//!
+//! ```sh
+//! rustc +nightly --target powerpc-unknown-linux-gnu --crate-type lib -C panic=abort -g \
+//!     --emit obj -o types.o types.rs
+//! rust-lld -flavor gnu -m elf32ppc -N -e 0 --section-start=.data=0x80003100 -o types.elf types.o
+//! ```
+#![feature(no_core, lang_items, auto_traits)]
+#![no_core]
+#![allow(internal_features)]
+
+#[lang = "pointee_sized"]
+pub trait PointeeSized {}
+
+#[lang = "meta_sized"]
+pub trait MetaSized: PointeeSized {}
+
+#[lang = "sized"]
+pub trait Sized: MetaSized {}
+
+#[lang = "freeze"]
+pub unsafe auto trait Freeze {}
+
+#[lang = "copy"]
+pub trait Copy {}
+
+#[lang = "drop_glue"]
+unsafe fn drop_glue<T: PointeeSized>(_: *mut T) {}
+
+#[repr(C)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+pub struct Node {
+    pub id: u16,
+    pub flags: u8,
+    pub position: Vec3,
+    pub scores: [i32; 3],
+    pub next: *mut Node,
+}
+
+#[unsafe(no_mangle)]
+pub static mut COUNTER: u32 = 0xDEAD_BEEF;
+
+#[unsafe(no_mangle)]
+pub static mut NODE_A: Node = Node {
+    id: 0x1234,
+    flags: 0x56,
+    position: Vec3 {
+        x: 1.0,
+        y: 2.5,
+        z: 0.0,
+    },
+    scores: [1, 2, 3],
+    next: &raw mut NODE_B,
+};
+
+#[unsafe(no_mangle)]
+pub static mut NODE_B: Node = Node {
+    id: 2,
+    flags: 0,
+    position: Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    scores: [0, 0, 0],
+    next: &raw mut NODE_A,
+};
@@ -21,8 +21,10 @@ use rustc_hash::FxHashMap;
 use crate::block::Info;
 use crate::builder::util::IntoIrValue;
 use crate::hooks::{HookKind, Hooks};
+use crate::sequence::BulkIdiom;
 use crate::{
-    Compiler, INTERNAL_RAISE_EXCEPTION, NAMESPACE_INTERNALS, NAMESPACE_USER_HOOKS, Sequence,
+    Compiler, INTERNAL_BULK_COPY, INTERNAL_BULK_FILL, INTERNAL_RAISE_EXCEPTION,
+    NAMESPACE_INTERNALS, NAMESPACE_USER_HOOKS, Sequence,
 };
 
 const MEMFLAGS: ir::MemFlags = ir::MemFlags::trusted();
@@ -48,15 +50,29 @@ fn is_cacheable(reg: Reg) -> bool {
             | SPR::DMAU
             | SPR::SRR0
             | SPR::SRR1
-            | SPR::DAR => false,
+            | SPR::DAR
+            | SPR::DSISR
+            | SPR::SDR1 => false,
             spr if spr.is_bat() => false,
             spr if spr.is_gqr() => false,
             _ => true,
         },
+        reg if Reg::SR.contains(&reg) => false,
         _ => true,
     }
 }
 
+/// Whether reads of the given uncacheable register can be reused for the rest of the block. Hooks
+/// only ever read these, so they can only change through the block itself: writes still go
+/// straight to the registers struct, but the value read first (or written last) stays valid.
+fn is_read_cacheable(reg: Reg) -> bool {
+    match reg {
+        Reg::MSR => true,
+        Reg::SPR(spr) => spr.is_gqr(),
+        _ => false,
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum BuilderError {
     #[error("illegal instruction {f0:?}")]
@@ -100,9 +116,11 @@ struct Signatures {
     read_quant_hook: ir::SigRef,
     write_quant_hook: ir::SigRef,
     invalidate_icache_hook: ir::SigRef,
+    unimplemented_hook: ir::SigRef,
     generic_hook: ir::SigRef,
 
     raise_exception: ir::SigRef,
+    bulk_memory: ir::SigRef,
 }
 
 struct HookFuncs {
@@ -119,19 +137,26 @@ struct HookFuncs {
     read_quant: ir::FuncRef,
     write_quant: ir::FuncRef,
     inv_icache: ir::FuncRef,
+    inv_tlb: ir::FuncRef,
 
     // generic
     dcache_dma: ir::FuncRef,
     msr_changed: ir::FuncRef,
     ibat_changed: ir::FuncRef,
     dbat_changed: ir::FuncRef,
+    page_table_changed: ir::FuncRef,
     tb_read: ir::FuncRef,
     tb_changed: ir::FuncRef,
     dec_read: ir::FuncRef,
     dec_changed: ir::FuncRef,
 
+    // others
+    unimplemented: ir::FuncRef,
+
     // special
     raise_exception: ir::FuncRef,
+    bulk_fill: ir::FuncRef,
+    bulk_copy: ir::FuncRef,
 }
 
 /// Constants used through block building.
@@ -172,6 +197,7 @@ pub struct BlockBuilder<'ctx> {
 
     ibat_changed: bool,
     dbat_changed: bool,
+    page_table_changed: bool,
     floats_checked: bool,
 }
 
@@ -212,27 +238,33 @@ impl<'ctx> BlockBuilder<'ctx> {
             write_quant_hook: builder.import_signature(Hooks::write_quantized_sig(ptr_type)),
             invalidate_icache_hook: builder
                 .import_signature(Hooks::invalidate_icache_sig(ptr_type)),
+            unimplemented_hook: builder.import_signature(Hooks::unimplemented_sig(ptr_type)),
             generic_hook: builder.import_signature(Hooks::generic_hook_sig(ptr_type)),
 
             raise_exception: builder.import_signature(exception::raise_exception_sig(ptr_type)),
+            bulk_memory: builder.import_signature(memory::bulk_memory_sig(ptr_type)),
         };
 
-        let raise_exception = {
+        let mut internal = |sig, index| {
             let name = builder
                 .func
                 .declare_imported_user_function(ir::UserExternalName::new(
                     NAMESPACE_INTERNALS,
-                    INTERNAL_RAISE_EXCEPTION,
+                    index,
                 ));
 
             builder.import_function(ir::ExtFuncData {
                 name: ir::ExternalName::User(name),
-                signature: sigs.raise_exception,
+                signature: sig,
                 colocated: false,
                 patchable: false,
             })
         };
 
+        let raise_exception = internal(sigs.raise_exception, INTERNAL_RAISE_EXCEPTION);
+        let bulk_fill = internal(sigs.bulk_memory, INTERNAL_BULK_FILL);
+        let bulk_copy = internal(sigs.bulk_memory, INTERNAL_BULK_COPY);
+
         let mut hook = |sig, kind| {
             let name = builder
                 .func
@@ -263,15 +295,20 @@ impl<'ctx> BlockBuilder<'ctx> {
             read_quant: hook(sigs.read_quant_hook, HookKind::ReadQuant),
             write_quant: hook(sigs.write_quant_hook, HookKind::WriteQuant),
             inv_icache: hook(sigs.invalidate_icache_hook, HookKind::InvICache),
+            inv_tlb: hook(sigs.invalidate_icache_hook, HookKind::InvTlb),
             dcache_dma: hook(sigs.generic_hook, HookKind::DCacheDma),
             msr_changed: hook(sigs.generic_hook, HookKind::MsrChanged),
             ibat_changed: hook(sigs.generic_hook, HookKind::IBatChanged),
             dbat_changed: hook(sigs.generic_hook, HookKind::DBatChanged),
+            page_table_changed: hook(sigs.generic_hook, HookKind::PageTableChanged),
             tb_read: hook(sigs.generic_hook, HookKind::TbRead),
             tb_changed: hook(sigs.generic_hook, HookKind::TbChanged),
             dec_read: hook(sigs.generic_hook, HookKind::DecRead),
             dec_changed: hook(sigs.generic_hook, HookKind::DecChanged),
+            unimplemented: hook(sigs.unimplemented_hook, HookKind::Unimplemented),
             raise_exception,
+            bulk_fill,
+            bulk_copy,
         };
 
         let consts = Consts {
@@ -305,6 +342,7 @@ impl<'ctx> BlockBuilder<'ctx> {
 
             ibat_changed: false,
             dbat_changed: false,
+            page_table_changed: false,
             floats_checked: false,
         }
     }
@@ -342,7 +380,7 @@ impl<'ctx> BlockBuilder<'ctx> {
         }
 
         let dumped = self.load_reg(reg);
-        if is_cacheable(reg) {
+        if is_cacheable(reg) || is_read_cacheable(reg) {
             self.cache.insert(
                 reg,
                 CachedValue {
@@ -364,23 +402,34 @@ impl<'ctx> BlockBuilder<'ctx> {
             self.invalidate_ps(fpr);
         }
 
+        if !is_cacheable(reg) {
+            self.store_reg(reg, value);
+            if is_read_cacheable(reg) {
+                self.cache.insert(
+                    reg,
+                    CachedValue {
+                        value,
+                        modified: false,
+                    },
+                );
+            }
+
+            return;
+        }
+
         if let Some(reg) = self.cache.get_mut(&reg) {
             reg.value = value;
             reg.modified = true;
             return;
         }
 
-        if is_cacheable(reg) {
-            self.cache.insert(
-                reg,
-                CachedValue {
-                    value,
-                    modified: true,
-                },
-            );
-        } else {
-            self.store_reg(reg, value);
-        }
+        self.cache.insert(
+            reg,
+            CachedValue {
+                value,
+                modified: true,
+            },
+        );
     }
 
     fn get_ps(&mut self, fpr: FPR) -> ir::Value {
@@ -548,7 +597,7 @@ impl<'ctx> BlockBuilder<'ctx> {
     }
 
     /// Emits the prologue:
-    /// - Call BAT hooks if they were changed
+    /// - Call BAT and page table hooks if they were changed
     /// - Returns
     fn prologue(&mut self) {
         self.update_info();
@@ -561,17 +610,29 @@ impl<'ctx> BlockBuilder<'ctx> {
             self.call_generic_hook(self.hooks.ibat_changed);
         }
 
+        if self.page_table_changed {
+            self.call_generic_hook(self.hooks.page_table_changed);
+        }
+
         self.bd.ins().return_(&[]);
         self.bd
             .set_srcloc(ir::SourceLoc::new(self.executed_instructions));
     }
 
-    /// Calls [`prologue`] as if an instruction with `info` had been executed.
+    /// Emits an exit which branches off the main path through `f`. Since the main path goes on
+    /// afterwards, what has been accounted for in the Info struct is restored once `f` returns.
+    fn side_exit(&mut self, f: impl FnOnce(&mut Self)) {
+        let last_updated = (self.last_updated_cycles, self.last_updated_instructions);
+        f(self);
+        (self.last_updated_cycles, self.last_updated_instructions) = last_updated;
+    }
+
+    /// Emits the prologue of a side exit as if an instruction with `info` had been executed.
     fn prologue_with(&mut self, info: InstructionInfo) {
         self.executed_instructions += 1;
         self.executed_cycles += info.cycles as u32;
 
-        self.prologue();
+        self.side_exit(Self::prologue);
 
         self.executed_instructions -= 1;
         self.executed_cycles -= info.cycles as u32;
@@ -675,6 +736,7 @@ impl<'ctx> BlockBuilder<'ctx> {
             Opcode::Lhzx => self.lhzx(ins),
             Opcode::Lmw => self.lmw(ins),
             Opcode::Lswi => self.lswi(ins),
+            Opcode::Lwarx => self.lwarx(ins),
             Opcode::Lwbrx => self.lwbrx(ins),
             Opcode::Lwz => self.lwz(ins),
             Opcode::Lwzu => self.lwzu(ins),
@@ -766,6 +828,7 @@ impl<'ctx> BlockBuilder<'ctx> {
             Opcode::Stswi => self.stswi(ins),
             Opcode::Stw => self.stw(ins),
             Opcode::Stwbrx => self.stwbrx(ins),
+            Opcode::Stwcx_ => self.stwcx_record(ins),
             Opcode::Stwu => self.stwu(ins),
             Opcode::Stwux => self.stwux(ins),
             Opcode::Stwx => self.stwx(ins),
@@ -777,7 +840,7 @@ impl<'ctx> BlockBuilder<'ctx> {
             Opcode::Subfze => self.subfze(ins),
             Opcode::Sync => self.nop(Action::FlushAndPrologue),
             Opcode::Tlbsync => self.nop(Action::Continue),
-            Opcode::Tlbie => self.nop(Action::Continue),
+            Opcode::Tlbie => self.tlbie(ins),
             Opcode::Xor => self.xor(ins),
             Opcode::Xori => self.xori(ins),
             Opcode::Xoris => self.xoris(ins),
@@ -813,12 +876,25 @@ impl<'ctx> BlockBuilder<'ctx> {
         mut self,
         mut instructions: impl Iterator<Item = Ins>,
     ) -> Result<(Sequence, u32), BuilderError> {
+        let mut prefix = Vec::new();
+        if self.compiler.settings.bulk_memory_idioms
+            && let Some(first) = instructions.next()
+        {
+            let len = BulkIdiom::len_starting_with(&first).unwrap_or(1);
+            prefix.push(first);
+            prefix.extend(instructions.by_ref().take(len - 1));
+
+            if let Some(idiom) = BulkIdiom::detect(&prefix) {
+                self.bulk_memory(idiom);
+            }
+        }
+
+        let mut instructions = prefix.into_iter().chain(instructions);
         let mut sequence = Sequence::default();
         loop {
             let Some(ins) = instructions.next() else {
                 self.bd.set_srcloc(ir::SourceLoc::new(u32::MAX));
-                self.flush();
-                self.prologue();
+                self.fall_through();
                 self.bd.finalize();
                 break;
             };
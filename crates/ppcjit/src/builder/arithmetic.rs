@@ -559,6 +559,7 @@ impl BlockBuilder<'_> {
         let fpr_b = self.get(ins.fpr_b());
 
         let value = self.bd.ins().fadd(fpr_a, fpr_b);
+        self.update_fpscr_add(fpr_a, fpr_b, value, value);
 
         self.set(ins.fpr_d(), value);
         self.update_fprf_cmpz(value);
@@ -576,8 +577,9 @@ impl BlockBuilder<'_> {
         let fpr_a = self.get(ins.fpr_a());
         let fpr_b = self.get(ins.fpr_b());
 
-        let value = self.bd.ins().fadd(fpr_a, fpr_b);
-        let value = self.round_to_single(value);
+        let unrounded = self.bd.ins().fadd(fpr_a, fpr_b);
+        let value = self.round_to_single(unrounded);
+        self.update_fpscr_add(fpr_a, fpr_b, unrounded, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -620,6 +622,7 @@ impl BlockBuilder<'_> {
         let fpr_b = self.get(ins.fpr_b());
 
         let value = self.bd.ins().fsub(fpr_a, fpr_b);
+        self.update_fpscr_sub(fpr_a, fpr_b, value, value);
         self.set(ins.fpr_d(), value);
 
         self.update_fprf_cmpz(value);
@@ -637,8 +640,9 @@ impl BlockBuilder<'_> {
         let fpr_a = self.get(ins.fpr_a());
         let fpr_b = self.get(ins.fpr_b());
 
-        let value = self.bd.ins().fsub(fpr_a, fpr_b);
-        let value = self.round_to_single(value);
+        let unrounded = self.bd.ins().fsub(fpr_a, fpr_b);
+        let value = self.round_to_single(unrounded);
+        self.update_fpscr_sub(fpr_a, fpr_b, unrounded, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -695,8 +699,9 @@ impl BlockBuilder<'_> {
         let fpr_a = self.get(ins.fpr_a());
         let fpr_c = self.get(ins.fpr_c());
 
-        let value = self.bd.ins().fmul(fpr_a, fpr_c);
-        let value = self.round_to_single(value);
+        let unrounded = self.bd.ins().fmul(fpr_a, fpr_c);
+        let value = self.round_to_single(unrounded);
+        self.update_fpscr_mul(fpr_a, fpr_c, unrounded, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -717,6 +722,7 @@ impl BlockBuilder<'_> {
         let fpr_c = self.get(ins.fpr_c());
 
         let value = self.bd.ins().fmul(fpr_a, fpr_c);
+        self.update_fpscr_mul(fpr_a, fpr_c, value, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -908,8 +914,9 @@ impl BlockBuilder<'_> {
         let fpr_a = self.get(ins.fpr_a());
         let fpr_b = self.get(ins.fpr_b());
 
-        let value = self.bd.ins().fdiv(fpr_a, fpr_b);
-        let value = self.round_to_single(value);
+        let unrounded = self.bd.ins().fdiv(fpr_a, fpr_b);
+        let value = self.round_to_single(unrounded);
+        self.update_fpscr_div(fpr_a, fpr_b, unrounded, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -930,6 +937,7 @@ impl BlockBuilder<'_> {
         let fpr_b = self.get(ins.fpr_b());
 
         let value = self.bd.ins().fdiv(fpr_a, fpr_b);
+        self.update_fpscr_div(fpr_a, fpr_b, value, value);
 
         self.set(ins.fpr_d(), value);
         self.set(Reg::PS1(ins.fpr_d()), value);
@@ -169,6 +169,21 @@ impl BlockBuilder<'_> {
         self.prologue();
     }
 
+    /// Exits the block by falling through to the next instruction, linking to the block which
+    /// starts there. This is used when a block ends without a terminal instruction (i.e. it hit
+    /// the instruction limit), so that straight-line code doesn't have to go through the dispatcher.
+    pub(super) fn fall_through(&mut self) {
+        // linked blocks skip the prologue, so its hooks must not be pending
+        if self.dbat_changed || self.ibat_changed || self.page_table_changed {
+            self.flush();
+            self.prologue();
+            return;
+        }
+
+        let next = self.get(Reg::PC);
+        self.jump_with_block_link(next);
+    }
+
     fn jump(&mut self, relative: bool, link_register: bool, block_link: bool, data: ir::Value) {
         let current_pc = self.get(Reg::PC);
         let destination = if relative {
@@ -272,7 +287,7 @@ impl BlockBuilder<'_> {
             // => exit (take branch)
             self.switch_to_bb(exit_block);
             let target = self.ir_value(target);
-            self.jump(relative, ins.field_lk(), block_link, target);
+            self.side_exit(|builder| builder.jump(relative, ins.field_lk(), block_link, target));
 
             // => continue (do not take branch)
             self.switch_to_bb(continue_block);
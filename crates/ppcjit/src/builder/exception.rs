@@ -1,5 +1,5 @@
 use cranelift::codegen::ir;
-use cranelift::prelude::{InstBuilder, isa};
+use cranelift::prelude::{InstBuilder, IntCC, isa};
 use gekko::disasm::Ins;
 use gekko::{Exception, Reg, SPR};
 
@@ -18,6 +18,20 @@ const EXCEPTION_INFO: InstructionInfo = InstructionInfo {
     action: Action::Prologue,
 };
 
+/// DSISR value of an alignment exception caused by the given X-form instruction.
+fn alignment_dsisr(ins: Ins) -> u32 {
+    // DSISR[15..17] = bits 29..30 and 25 of the instruction, DSISR[18..21] = its bits 21..24
+    let xo_low = (ins.code >> 1) & 0b11;
+    let xo_mid = (ins.code >> 6) & 0b1;
+    let xo_high = (ins.code >> 7) & 0b1111;
+
+    (xo_low << 15)
+        | (xo_mid << 14)
+        | (xo_high << 10)
+        | ((ins.field_rd() as u32) << 5)
+        | ins.field_ra() as u32
+}
+
 pub fn raise_exception_sig(ptr_type: ir::Type) -> ir::Signature {
     ir::Signature {
         params: vec![
@@ -46,34 +60,67 @@ impl BlockBuilder<'_> {
         );
     }
 
-    /// Checks whether floating point operations are enabled in MSR and raises an exception if not.
-    pub fn check_floats(&mut self) {
-        if self.floats_checked || self.compiler.settings.force_fpu {
-            return;
-        }
-        self.floats_checked = true;
-
-        let msr = self.get(Reg::MSR);
-        let fp_enabled = self.get_bit(msr, 13);
-
+    /// Exits the block by raising `exception` unless `ok` is set. `setup` is emitted in the exit
+    /// path right before the exception is raised.
+    ///
+    /// If `info` is given, the faulting instruction is accounted for as if it had been executed
+    /// with it. Either way, PC still points at the faulting instruction, so SRR0 is precise and
+    /// none of the following instructions are executed.
+    pub fn raise_exception_unless(
+        &mut self,
+        ok: ir::Value,
+        exception: Exception,
+        info: Option<InstructionInfo>,
+        setup: impl FnOnce(&mut Self),
+    ) {
         let exit_block = self.bd.create_block();
         let continue_block = self.bd.create_block();
 
         self.bd.set_cold_block(exit_block);
-
-        self.bd
-            .ins()
-            .brif(fp_enabled, continue_block, &[], exit_block, &[]);
+        self.bd.ins().brif(ok, continue_block, &[], exit_block, &[]);
 
         self.bd.seal_block(exit_block);
         self.bd.seal_block(continue_block);
 
         self.switch_to_bb(exit_block);
-        self.raise_exception(Exception::FloatUnavailable);
-        self.prologue();
+        setup(self);
+        self.raise_exception(exception);
+        match info {
+            Some(info) => self.prologue_with(info),
+            None => self.side_exit(Self::prologue),
+        }
 
         self.switch_to_bb(continue_block);
-        self.current_bb = continue_block;
+    }
+
+    /// Checks whether floating point operations are enabled in MSR and raises an exception if not.
+    pub fn check_floats(&mut self) {
+        if self.floats_checked || self.compiler.settings.force_fpu {
+            return;
+        }
+        self.floats_checked = true;
+
+        let msr = self.get(Reg::MSR);
+        let fp_enabled = self.get_bit(msr, 13);
+        self.raise_exception_unless(fp_enabled, Exception::FloatUnavailable, None, |_| ());
+    }
+
+    /// Raises an alignment exception for `ins` unless `addr` is aligned to `align` bytes.
+    pub fn check_alignment(
+        &mut self,
+        ins: Ins,
+        addr: ir::Value,
+        align: u32,
+        info: InstructionInfo,
+    ) {
+        let offset = self.bd.ins().band_imm(addr, (align - 1) as i64);
+        let aligned = self.bd.ins().icmp_imm(IntCC::Equal, offset, 0);
+        let dsisr = alignment_dsisr(ins);
+
+        self.raise_exception_unless(aligned, Exception::Alignment, Some(info), |builder| {
+            builder.set(SPR::DAR, addr);
+            builder.set(SPR::DSISR, dsisr);
+        });
     }
 
     pub fn sc(&mut self, _: Ins) -> InstructionInfo {
@@ -1,10 +1,31 @@
+use std::mem::offset_of;
+
 use cranelift::codegen::ir;
-use cranelift::prelude::InstBuilder;
+use cranelift::prelude::{InstBuilder, IntCC, isa};
 use gekko::disasm::Ins;
 use gekko::{Exception, GPR, InsExt, Reg, SPR};
 
 use super::BlockBuilder;
+use crate::FastmemLuts;
+use crate::block::Info;
 use crate::builder::{Action, InstructionInfo, MEMFLAGS, MEMFLAGS_READONLY};
+use crate::sequence::BulkIdiom;
+
+pub fn bulk_memory_sig(ptr_type: ir::Type) -> ir::Signature {
+    ir::Signature {
+        params: vec![
+            ir::AbiParam::new(ptr_type),       // fastmem
+            ir::AbiParam::new(ir::types::I32), // dst
+            ir::AbiParam::new(ir::types::I32), // value or src
+            ir::AbiParam::new(ir::types::I32), // size
+            ir::AbiParam::new(ir::types::I32), // ctr
+        ],
+        returns: vec![
+            ir::AbiParam::new(ir::types::I32), // iterations
+        ],
+        call_conv: isa::CallConv::SystemV,
+    }
+}
 
 pub trait ReadWriteAble {
     const IR_TYPE: ir::Type;
@@ -62,6 +83,14 @@ impl ReadWriteAble for i64 {
 
 /// Helpers
 impl BlockBuilder<'_> {
+    /// Exits the block with a DSI exception for an access to `addr` unless `ok` is set. The hook
+    /// which failed the access is responsible for setting DSISR.
+    fn raise_data_fault_unless(&mut self, ok: ir::Value, addr: ir::Value, info: InstructionInfo) {
+        self.raise_exception_unless(ok, Exception::DSI, Some(info), |builder| {
+            builder.set(SPR::DAR, addr);
+        });
+    }
+
     pub fn slow_mem_load<P: ReadWriteAble>(&mut self, addr: ir::Value) -> ir::Value {
         let func = P::read_hook(self);
         let stack_slot_addr =
@@ -75,23 +104,8 @@ impl BlockBuilder<'_> {
             .call(func, &[self.consts.ctx_ptr, addr, stack_slot_addr]);
 
         let success = self.bd.inst_results(inst)[0];
-        let exit_block = self.bd.create_block();
-        let continue_block = self.bd.create_block();
+        self.raise_data_fault_unless(success, addr, LOAD_INFO);
 
-        self.bd.set_cold_block(exit_block);
-        self.bd
-            .ins()
-            .brif(success, continue_block, &[], exit_block, &[]);
-
-        self.bd.seal_block(exit_block);
-        self.bd.seal_block(continue_block);
-
-        self.switch_to_bb(exit_block);
-        self.set(SPR::DAR, addr);
-        self.raise_exception(Exception::DSI);
-        self.prologue_with(LOAD_INFO);
-
-        self.switch_to_bb(continue_block);
         self.bd
             .ins()
             .stack_load(P::IR_TYPE, self.consts.read_stack_slot, 0)
@@ -105,23 +119,7 @@ impl BlockBuilder<'_> {
             .call(func, &[self.consts.ctx_ptr, addr, value]);
 
         let success = self.bd.inst_results(inst)[0];
-        let exit_block = self.bd.create_block();
-        let continue_block = self.bd.create_block();
-
-        self.bd.set_cold_block(exit_block);
-        self.bd
-            .ins()
-            .brif(success, continue_block, &[], exit_block, &[]);
-
-        self.bd.seal_block(exit_block);
-        self.bd.seal_block(continue_block);
-
-        self.switch_to_bb(exit_block);
-        self.set(SPR::DAR, addr);
-        self.raise_exception(Exception::DSI);
-        self.prologue_with(STORE_INFO);
-
-        self.switch_to_bb(continue_block);
+        self.raise_data_fault_unless(success, addr, STORE_INFO);
     }
 
     pub fn mem_load<P: ReadWriteAble>(&mut self, addr: ir::Value) -> ir::Value {
@@ -180,10 +178,12 @@ impl BlockBuilder<'_> {
         let lut_offset = self.bd.ins().imul_imm(lut_index, size_of::<usize>() as i64);
 
         let lut_ptr = self.bd.ins().iadd(self.consts.fmem_ptr, lut_offset);
-        let ptr = self
-            .bd
-            .ins()
-            .load(self.consts.ptr_type, MEMFLAGS_READONLY, lut_ptr, 0);
+        let ptr = self.bd.ins().load(
+            self.consts.ptr_type,
+            MEMFLAGS_READONLY,
+            lut_ptr,
+            std::mem::offset_of!(FastmemLuts, stores) as i32,
+        );
 
         let fast_block = self.bd.create_block();
         let slow_block = self.bd.create_block();
@@ -230,23 +230,8 @@ impl BlockBuilder<'_> {
         );
 
         let size = self.bd.inst_results(inst)[0];
-        let exit_block = self.bd.create_block();
-        let continue_block = self.bd.create_block();
-
-        self.bd.set_cold_block(exit_block);
-        self.bd
-            .ins()
-            .brif(size, continue_block, &[], exit_block, &[]);
-
-        self.bd.seal_block(exit_block);
-        self.bd.seal_block(continue_block);
+        self.raise_data_fault_unless(size, addr, LOAD_INFO);
 
-        self.switch_to_bb(exit_block);
-        self.set(SPR::DAR, addr);
-        self.raise_exception(Exception::DSI);
-        self.prologue_with(LOAD_INFO);
-
-        self.switch_to_bb(continue_block);
         (
             self.bd
                 .ins()
@@ -263,23 +248,8 @@ impl BlockBuilder<'_> {
         );
 
         let size = self.bd.inst_results(inst)[0];
-        let exit_block = self.bd.create_block();
-        let continue_block = self.bd.create_block();
-
-        self.bd.set_cold_block(exit_block);
-        self.bd
-            .ins()
-            .brif(size, continue_block, &[], exit_block, &[]);
+        self.raise_data_fault_unless(size, addr, STORE_INFO);
 
-        self.bd.seal_block(exit_block);
-        self.bd.seal_block(continue_block);
-
-        self.switch_to_bb(exit_block);
-        self.set(SPR::DAR, addr);
-        self.raise_exception(Exception::DSI);
-        self.prologue_with(STORE_INFO);
-
-        self.switch_to_bb(continue_block);
         self.bd.ins().uextend(ir::types::I32, size)
     }
 }
@@ -555,6 +525,25 @@ impl BlockBuilder<'_> {
         )
     }
 
+    pub fn lwarx(&mut self, ins: Ins) -> InstructionInfo {
+        let rb = self.get(ins.gpr_b());
+        let addr = if ins.field_ra() == 0 {
+            rb
+        } else {
+            let ra = self.get(ins.gpr_a());
+            self.bd.ins().iadd(ra, rb)
+        };
+
+        self.check_alignment(ins, addr, 4, LOAD_INFO);
+        let value = self.mem_load::<i32>(addr);
+
+        let reservation = self.bd.ins().bor_imm(addr, 1);
+        self.set(Reg::RESERVATION, reservation);
+        self.set(ins.gpr_d(), value);
+
+        LOAD_INFO
+    }
+
     pub fn lmw(&mut self, ins: Ins) -> InstructionInfo {
         let mut addr = if ins.field_ra() == 0 {
             self.ir_value(ins.field_offset() as i32)
@@ -818,12 +807,13 @@ impl BlockBuilder<'_> {
             value = self.bd.ins().ireduce(P::IR_TYPE, value);
         }
 
+        self.mem_store::<P>(addr, value);
+
+        // only update after the store, so that a fault leaves rA untouched
         if update {
             self.set(ins.gpr_a(), addr);
         }
 
-        self.mem_store::<P>(addr, value);
-
         STORE_INFO
     }
 
@@ -850,12 +840,13 @@ impl BlockBuilder<'_> {
             value = self.bd.ins().bswap(value);
         }
 
+        self.mem_store::<P>(addr, value);
+
+        // only update after the store, so that a fault leaves rA untouched
         if update {
             self.set(ins.gpr_a(), addr);
         }
 
-        self.mem_store::<P>(addr, value);
-
         STORE_INFO
     }
 
@@ -915,6 +906,49 @@ impl BlockBuilder<'_> {
         self.store_indexed::<i32>(ins, true, false)
     }
 
+    pub fn stwcx_record(&mut self, ins: Ins) -> InstructionInfo {
+        let rb = self.get(ins.gpr_b());
+        let addr = if ins.field_ra() == 0 {
+            rb
+        } else {
+            let ra = self.get(ins.gpr_a());
+            self.bd.ins().iadd(ra, rb)
+        };
+
+        self.check_alignment(ins, addr, 4, STORE_INFO);
+
+        // the store only happens if there's a reservation for this address
+        let reservation = self.get(Reg::RESERVATION);
+        let expected = self.bd.ins().bor_imm(addr, 1);
+        let reserved = self.bd.ins().icmp(IntCC::Equal, reservation, expected);
+
+        // get everything needed beforehand, as values cached inside the conditional block would
+        // not be available after it
+        let value = self.get(ins.gpr_s());
+        let xer = self.get(SPR::XER);
+
+        let store_block = self.bd.create_block();
+        let continue_block = self.bd.create_block();
+        self.bd
+            .ins()
+            .brif(reserved, store_block, &[], continue_block, &[]);
+        self.bd.seal_block(store_block);
+
+        self.switch_to_bb(store_block);
+        self.mem_store::<i32>(addr, value);
+        self.bd.ins().jump(continue_block, &[]);
+
+        self.bd.seal_block(continue_block);
+        self.switch_to_bb(continue_block);
+
+        let no = self.ir_value(false);
+        let ov = self.get_bit(xer, 31);
+        self.update_cr(0, no, no, reserved, ov);
+        self.set(Reg::RESERVATION, 0u32);
+
+        STORE_INFO
+    }
+
     pub fn stmw(&mut self, ins: Ins) -> InstructionInfo {
         let mut addr = if ins.field_ra() == 0 {
             self.ir_value(ins.field_offset() as i32)
@@ -1315,4 +1349,63 @@ impl BlockBuilder<'_> {
 
         STORE_INFO
     }
+
+    /// Performs all but the last iteration of the given idiom, which must be at the start of the
+    /// block, at once. Iterations which can't be done through fastmem are left to the loop itself.
+    pub fn bulk_memory(&mut self, idiom: BulkIdiom) {
+        let (func, size, dst, cycles) = match idiom {
+            BulkIdiom::Fill { size, dst, .. } => (self.hooks.bulk_fill, size, dst, 4),
+            BulkIdiom::Copy { size, dst, .. } => (self.hooks.bulk_copy, size, dst, 6),
+        };
+
+        let ctr = self.get(SPR::CTR);
+        let dst_reg = self.get(dst);
+        let dst_start = self.bd.ins().iadd_imm(dst_reg, size as i64);
+        let arg = match idiom {
+            BulkIdiom::Fill { value, .. } => self.get(value),
+            BulkIdiom::Copy { src, .. } => {
+                let src_reg = self.get(src);
+                self.bd.ins().iadd_imm(src_reg, size as i64)
+            }
+        };
+
+        let size_value = self.ir_value(size as u32);
+        let inst = self.bd.ins().call(
+            func,
+            &[self.consts.fmem_ptr, dst_start, arg, size_value, ctr],
+        );
+        let count = self.bd.inst_results(inst)[0];
+
+        // advance the loop
+        let advanced = self.bd.ins().imul_imm(count, size as i64);
+        let dst_value = self.bd.ins().iadd(dst_reg, advanced);
+        self.set(dst, dst_value);
+        if let BulkIdiom::Copy { src, .. } = idiom {
+            let src_reg = self.get(src);
+            let src_value = self.bd.ins().iadd(src_reg, advanced);
+            self.set(src, src_value);
+        }
+
+        let ctr = self.bd.ins().isub(ctr, count);
+        self.set(SPR::CTR, ctr);
+
+        // account for the iterations performed
+        let instructions = self.bd.ins().imul_imm(count, idiom.len() as i64);
+        let cycles = self.bd.ins().imul_imm(count, cycles);
+        for (delta, offset) in [
+            (instructions, offset_of!(Info, instructions)),
+            (cycles, offset_of!(Info, cycles)),
+        ] {
+            let current = self.bd.ins().load(
+                ir::types::I32,
+                MEMFLAGS,
+                self.consts.info_ptr,
+                offset as i32,
+            );
+            let updated = self.bd.ins().iadd(current, delta);
+            self.bd
+                .ins()
+                .store(MEMFLAGS, updated, self.consts.info_ptr, offset as i32);
+        }
+    }
 }
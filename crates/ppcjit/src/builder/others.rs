@@ -49,6 +49,12 @@ const INV_ICACHE_INFO: InstructionInfo = InstructionInfo {
     action: Action::FlushAndPrologue,
 };
 
+const INV_TLB_INFO: InstructionInfo = InstructionInfo {
+    cycles: 2,
+    auto_pc: true,
+    action: Action::FlushAndPrologue,
+};
+
 fn generate_mask(control: u8) -> u32 {
     let mut mask = 0;
     for i in 0..8 {
@@ -88,6 +94,7 @@ impl BlockBuilder<'_> {
             SPR::WPAR => tracing::warn!("write to WPAR"),
             spr if spr.is_data_bat() => self.dbat_changed = true,
             spr if spr.is_instr_bat() => self.ibat_changed = true,
+            SPR::SDR1 => self.page_table_changed = true,
             _ => (),
         }
 
@@ -98,6 +105,7 @@ impl BlockBuilder<'_> {
         let value = self.get(ins.gpr_s());
         let sr = Reg::SR[ins.field_sr() as usize];
         self.set(sr, value);
+        self.page_table_changed = true;
 
         SR_INFO
     }
@@ -423,4 +431,12 @@ impl BlockBuilder<'_> {
 
         INV_ICACHE_INFO
     }
+    pub fn tlbie(&mut self, ins: Ins) -> InstructionInfo {
+        let addr = self.get(ins.gpr_b());
+        self.bd
+            .ins()
+            .call(self.hooks.inv_tlb, &[self.consts.ctx_ptr, addr]);
+
+        INV_TLB_INFO
+    }
 }
@@ -6,6 +6,26 @@ use gekko::{Reg, SPR};
 use super::{Action, BlockBuilder};
 use crate::builder::InstructionInfo;
 
+/// Bit indices of FPSCR fields (i.e. `31 - n`, where `n` is the bit number in the manual).
+mod fpscr {
+    pub const FX: u32 = 31;
+    pub const FEX: u32 = 30;
+    pub const VX: u32 = 29;
+    pub const OX: u32 = 28;
+    pub const ZX: u32 = 26;
+    pub const XX: u32 = 25;
+    pub const VXSNAN: u32 = 24;
+    pub const VXISI: u32 = 23;
+    pub const VXIDI: u32 = 22;
+    pub const VXZDZ: u32 = 21;
+    pub const VXIMZ: u32 = 20;
+    pub const FR: u32 = 18;
+    pub const FI: u32 = 17;
+
+    /// Mask of all invalid operation exception bits (VXSNAN..=VXVC, VXSOFT..=VXCVI).
+    pub const VX_ALL: u32 = (0b11_1111 << 19) | (0b111 << 8);
+}
+
 /// Trait for transforming values into an IR value in a function.
 pub trait IntoIrValue {
     fn into_value(self, bd: &mut FunctionBuilder<'_>) -> ir::Value;
@@ -82,15 +102,20 @@ impl BlockBuilder<'_> {
         }
     }
 
-    /// Stub instruction - does absolutely nothing as a temporary implementation.
-    #[allow(dead_code)]
+    /// Stub instruction - does nothing other than reporting itself through the `unimplemented`
+    /// hook, as a temporary implementation.
     pub fn stub(&mut self, ins: Ins) -> InstructionInfo {
         let mut parsed = ParsedIns::new();
         ins.parse_basic(&mut parsed);
 
-        tracing::warn!("emitting stubbed instruction ({parsed})");
+        tracing::debug!("emitting stubbed instruction ({parsed})");
+
+        let code = self.ir_value(ins.code);
+        let pc = self.get(Reg::PC);
+        self.bd
+            .ins()
+            .call(self.hooks.unimplemented, &[self.consts.ctx_ptr, code, pc]);
 
-        self.bd.ins().nop();
         InstructionInfo {
             cycles: 2,
             auto_pc: true,
@@ -270,8 +295,278 @@ impl BlockBuilder<'_> {
         self.update_fprf(lt, gt, eq, un);
     }
 
+    /// Updates the VX and FEX summary bits of FPSCR.
     pub fn update_fpscr(&mut self) {
-        tracing::warn!("update FEX and VX")
+        let fpscr = self.get(Reg::FPSCR);
+
+        // VX is the OR of all invalid operation exception bits
+        let invalid = self.bd.ins().band_imm(fpscr, fpscr::VX_ALL as i64);
+        let vx = self.bd.ins().icmp_imm(IntCC::NotEqual, invalid, 0);
+        let fpscr = self.set_bit(fpscr, fpscr::VX, vx);
+
+        // FEX is set if any exception (VX, OX, UX, ZX, XX) is enabled (VE, OE, UE, ZE, XE)
+        let exceptions = self.bd.ins().ushr_imm(fpscr, 22);
+        let enabled = self.bd.ins().band(exceptions, fpscr);
+        let enabled = self.bd.ins().band_imm(enabled, 0b11111 << 3);
+        let fex = self.bd.ins().icmp_imm(IntCC::NotEqual, enabled, 0);
+        let fpscr = self.set_bit(fpscr, fpscr::FEX, fex);
+
+        self.set(Reg::FPSCR, fpscr);
+    }
+
+    /// Sets the given sticky exception bits in FPSCR if `cond` (a boolean) is true, also setting
+    /// FX if any of them was previously unset.
+    fn raise_fpscr(&mut self, cond: ir::Value, bits: u32) {
+        let fpscr = self.get(Reg::FPSCR);
+
+        let zero = self.ir_value(0u32);
+        let bits = self.ir_value(bits);
+        let raised = self.bd.ins().select(cond, bits, zero);
+
+        let new = self.bd.ins().band_not(raised, fpscr);
+        let any_new = self.bd.ins().icmp_imm(IntCC::NotEqual, new, 0);
+        let fx = self.bd.ins().uextend(ir::types::I32, any_new);
+        let fx = self.bd.ins().ishl_imm(fx, fpscr::FX as i64);
+
+        let fpscr = self.bd.ins().bor(fpscr, raised);
+        let fpscr = self.bd.ins().bor(fpscr, fx);
+
+        self.set(Reg::FPSCR, fpscr);
+    }
+
+    /// Returns whether the given F64 value is a signaling NaN.
+    fn is_snan(&mut self, value: ir::Value) -> ir::Value {
+        let is_nan = self.bd.ins().fcmp(FloatCC::Unordered, value, value);
+        let bits = self
+            .bd
+            .ins()
+            .bitcast(ir::types::I64, ir::MemFlags::new(), value);
+        let quiet = self.bd.ins().band_imm(bits, 1 << 51);
+        let signaling = self.bd.ins().icmp_imm(IntCC::Equal, quiet, 0);
+
+        self.bd.ins().band(is_nan, signaling)
+    }
+
+    /// Returns whether the given F64 value is infinite.
+    fn is_inf(&mut self, value: ir::Value) -> ir::Value {
+        let abs = self.bd.ins().fabs(value);
+        let inf = self.ir_value(f64::INFINITY);
+        self.bd.ins().fcmp(FloatCC::Equal, abs, inf)
+    }
+
+    /// Updates the FPSCR exception bits of a floating point arithmetic operation.
+    ///
+    /// - `invalid` is whether the operation was invalid (i.e. produced a NaN out of non-NaN
+    ///   operands), along with the specific VX bit to raise in that case.
+    /// - `error` is the rounding error of the operation (exact - result), with the same sign as
+    ///   the exact error. It is only meaningful if the result is finite.
+    /// - `result` is the final (possibly rounded to single) result of the operation.
+    /// - `unrounded` is the result of the operation before rounding to single.
+    fn update_fpscr_exceptions(
+        &mut self,
+        operands: &[ir::Value],
+        (invalid, invalid_bit): (ir::Value, u32),
+        zero_divide: Option<ir::Value>,
+        error: ir::Value,
+        unrounded: ir::Value,
+        result: ir::Value,
+    ) {
+        // invalid operations
+        let mut snan = self.ir_value(false);
+        let mut any_inf = self.ir_value(false);
+        for &operand in operands {
+            let is_snan = self.is_snan(operand);
+            snan = self.bd.ins().bor(snan, is_snan);
+
+            let is_inf = self.is_inf(operand);
+            any_inf = self.bd.ins().bor(any_inf, is_inf);
+        }
+
+        self.raise_fpscr(snan, 1 << fpscr::VXSNAN);
+        self.raise_fpscr(invalid, 1 << invalid_bit);
+
+        if let Some(zero_divide) = zero_divide {
+            self.raise_fpscr(zero_divide, 1 << fpscr::ZX);
+        }
+
+        // overflow: infinite result out of finite operands
+        let result_inf = self.is_inf(result);
+        let finite_operands = self.bd.ins().bnot(any_inf);
+        let overflow = self.bd.ins().band(result_inf, finite_operands);
+        let overflow = match zero_divide {
+            Some(zero_divide) => {
+                let not_zero_divide = self.bd.ins().bnot(zero_divide);
+                self.bd.ins().band(overflow, not_zero_divide)
+            }
+            None => overflow,
+        };
+        self.raise_fpscr(overflow, (1 << fpscr::OX) | (1 << fpscr::XX));
+
+        // inexact: either the operation itself or the rounding to single lost precision
+        let zero = self.ir_value(0.0f64);
+        let result_finite = self.bd.ins().bnot(result_inf);
+        let result_ordered = self.bd.ins().fcmp(FloatCC::Ordered, result, result);
+        let result_finite = self.bd.ins().band(result_finite, result_ordered);
+
+        let op_inexact = self.bd.ins().fcmp(FloatCC::OrderedNotEqual, error, zero);
+        let single_inexact = self
+            .bd
+            .ins()
+            .fcmp(FloatCC::OrderedNotEqual, unrounded, result);
+        let inexact = self.bd.ins().bor(op_inexact, single_inexact);
+        let inexact = self.bd.ins().band(inexact, result_finite);
+        let inexact = self.bd.ins().bor(inexact, overflow);
+
+        // rounded: the magnitude of the result is greater than the magnitude of the exact value
+        let single_error = self.bd.ins().fsub(unrounded, result);
+        let total_error = self.bd.ins().fadd(error, single_error);
+        let error_negative = self.bd.ins().fcmp(FloatCC::LessThan, total_error, zero);
+        let result_negative = self.bd.ins().fcmp(FloatCC::LessThan, result, zero);
+        let rounded_up = self.bd.ins().bxor(error_negative, result_negative);
+        let rounded = self.bd.ins().band(inexact, rounded_up);
+
+        self.raise_fpscr(inexact, 1 << fpscr::XX);
+
+        let fpscr = self.get(Reg::FPSCR);
+        let fpscr = self.set_bit(fpscr, fpscr::FI, inexact);
+        let fpscr = self.set_bit(fpscr, fpscr::FR, rounded);
+        self.set(Reg::FPSCR, fpscr);
+
+        self.update_fpscr();
+    }
+
+    /// Updates the FPSCR exception bits of an addition `lhs + rhs`.
+    pub fn update_fpscr_add(
+        &mut self,
+        lhs: ir::Value,
+        rhs: ir::Value,
+        unrounded: ir::Value,
+        result: ir::Value,
+    ) {
+        if !self.compiler.settings.float_exceptions {
+            return;
+        }
+
+        // inf - inf
+        let lhs_inf = self.is_inf(lhs);
+        let rhs_inf = self.is_inf(rhs);
+        let both_inf = self.bd.ins().band(lhs_inf, rhs_inf);
+        let result_nan = self.bd.ins().fcmp(FloatCC::Unordered, unrounded, unrounded);
+        let invalid = self.bd.ins().band(both_inf, result_nan);
+
+        // the rounding error of an addition is exactly representable (2Sum)
+        let rhs_virtual = self.bd.ins().fsub(unrounded, lhs);
+        let lhs_virtual = self.bd.ins().fsub(unrounded, rhs_virtual);
+        let rhs_error = self.bd.ins().fsub(rhs, rhs_virtual);
+        let lhs_error = self.bd.ins().fsub(lhs, lhs_virtual);
+        let error = self.bd.ins().fadd(lhs_error, rhs_error);
+
+        self.update_fpscr_exceptions(
+            &[lhs, rhs],
+            (invalid, fpscr::VXISI),
+            None,
+            error,
+            unrounded,
+            result,
+        );
+    }
+
+    /// Updates the FPSCR exception bits of a subtraction `lhs - rhs`.
+    pub fn update_fpscr_sub(
+        &mut self,
+        lhs: ir::Value,
+        rhs: ir::Value,
+        unrounded: ir::Value,
+        result: ir::Value,
+    ) {
+        if !self.compiler.settings.float_exceptions {
+            return;
+        }
+
+        let rhs = self.bd.ins().fneg(rhs);
+        self.update_fpscr_add(lhs, rhs, unrounded, result);
+    }
+
+    /// Updates the FPSCR exception bits of a multiplication `lhs * rhs`.
+    pub fn update_fpscr_mul(
+        &mut self,
+        lhs: ir::Value,
+        rhs: ir::Value,
+        unrounded: ir::Value,
+        result: ir::Value,
+    ) {
+        if !self.compiler.settings.float_exceptions {
+            return;
+        }
+
+        // inf * 0
+        let zero = self.ir_value(0.0f64);
+        let lhs_inf = self.is_inf(lhs);
+        let rhs_inf = self.is_inf(rhs);
+        let lhs_zero = self.bd.ins().fcmp(FloatCC::Equal, lhs, zero);
+        let rhs_zero = self.bd.ins().fcmp(FloatCC::Equal, rhs, zero);
+        let inf_zero = self.bd.ins().band(lhs_inf, rhs_zero);
+        let zero_inf = self.bd.ins().band(lhs_zero, rhs_inf);
+        let invalid = self.bd.ins().bor(inf_zero, zero_inf);
+
+        // the rounding error of a multiplication is exactly computed by a fused multiply-add
+        let neg = self.bd.ins().fneg(unrounded);
+        let error = self.bd.ins().fma(lhs, rhs, neg);
+
+        self.update_fpscr_exceptions(
+            &[lhs, rhs],
+            (invalid, fpscr::VXIMZ),
+            None,
+            error,
+            unrounded,
+            result,
+        );
+    }
+
+    /// Updates the FPSCR exception bits of a division `lhs / rhs`.
+    pub fn update_fpscr_div(
+        &mut self,
+        lhs: ir::Value,
+        rhs: ir::Value,
+        unrounded: ir::Value,
+        result: ir::Value,
+    ) {
+        if !self.compiler.settings.float_exceptions {
+            return;
+        }
+
+        let zero = self.ir_value(0.0f64);
+        let lhs_inf = self.is_inf(lhs);
+        let rhs_inf = self.is_inf(rhs);
+        let lhs_zero = self.bd.ins().fcmp(FloatCC::Equal, lhs, zero);
+        let rhs_zero = self.bd.ins().fcmp(FloatCC::Equal, rhs, zero);
+
+        // inf / inf and 0 / 0
+        let inf_inf = self.bd.ins().band(lhs_inf, rhs_inf);
+        let zero_zero = self.bd.ins().band(lhs_zero, rhs_zero);
+        self.raise_fpscr(inf_inf, 1 << fpscr::VXIDI);
+
+        // x / 0, x finite and non-zero
+        let lhs_ordered = self.bd.ins().fcmp(FloatCC::Ordered, lhs, lhs);
+        let lhs_nonzero = self.bd.ins().bnot(lhs_zero);
+        let lhs_finite = self.bd.ins().bnot(lhs_inf);
+        let zero_divide = self.bd.ins().band(rhs_zero, lhs_nonzero);
+        let zero_divide = self.bd.ins().band(zero_divide, lhs_finite);
+        let zero_divide = self.bd.ins().band(zero_divide, lhs_ordered);
+
+        // the remainder lhs - q * rhs is exact, and error = remainder / rhs has the right sign
+        let neg = self.bd.ins().fneg(unrounded);
+        let remainder = self.bd.ins().fma(neg, rhs, lhs);
+        let error = self.bd.ins().fdiv(remainder, rhs);
+
+        self.update_fpscr_exceptions(
+            &[lhs, rhs],
+            (zero_zero, fpscr::VXZDZ),
+            Some(zero_divide),
+            error,
+            unrounded,
+            result,
+        );
     }
 
     /// Updates CR1 by copying bits 28..32 of FPSCR.
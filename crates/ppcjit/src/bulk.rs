@@ -0,0 +1,95 @@
+//! Host routines performing the iterations of [`BulkIdiom`](crate::sequence::BulkIdiom) loops in
+//! bulk, called by blocks starting with one.
+//!
+//! Both only touch memory reachable through fastmem, and never more than a single page of it, so
+//! that they can't fault. Whatever they don't do is left to the loop itself.
+use std::ptr::NonNull;
+
+use crate::{FastmemLut, FastmemLuts};
+
+/// Signature of the bulk routines: `(fastmem, dst, value or src, size, ctr) -> iterations`.
+pub type Routine = extern "sysv64-unwind" fn(&FastmemLuts, u32, u32, u32, u32) -> u32;
+
+const PAGE_LEN: u32 = 1 << 17;
+
+/// Maximum number of iterations performed at once, so that a single block doesn't run for too
+/// long.
+const MAX_ITERATIONS: u32 = 4096;
+
+/// Returns a pointer to `addr` along with how many elements of `size` bytes fit in its fastmem
+/// page from there.
+fn page_span(lut: &FastmemLut, addr: u32, size: u32) -> Option<(NonNull<u8>, u32)> {
+    let base = lut[(addr >> 17) as usize]?;
+    let offset = addr & (PAGE_LEN - 1);
+
+    // SAFETY: the offset is within the page
+    Some((
+        unsafe { base.add(offset as usize) },
+        (PAGE_LEN - offset) / size,
+    ))
+}
+
+/// How many iterations to perform, given the CTR and how many fit in the pages involved. The last
+/// one is always left to the loop, which then goes on as usual.
+fn iterations(ctr: u32, fit: u32) -> u32 {
+    ctr.saturating_sub(1).min(fit).min(MAX_ITERATIONS)
+}
+
+/// Stores the low `size` bytes of `value` over and over starting at `dst`, for up to `ctr - 1`
+/// iterations. Returns how many were performed.
+pub extern "sysv64-unwind" fn fill(
+    fastmem: &FastmemLuts,
+    dst: u32,
+    value: u32,
+    size: u32,
+    ctr: u32,
+) -> u32 {
+    let Some((ptr, fit)) = page_span(&fastmem.stores, dst, size) else {
+        return 0;
+    };
+
+    let count = iterations(ctr, fit);
+    let len = (count * size) as usize;
+
+    // SAFETY: the range is within a single fastmem page
+    let out = unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), len) };
+    let bytes = &value.to_be_bytes()[4 - size as usize..];
+    for element in out.chunks_exact_mut(size as usize) {
+        element.copy_from_slice(bytes);
+    }
+
+    count
+}
+
+/// Copies elements of `size` bytes one after the other from `src` to `dst`, for up to `ctr - 1`
+/// iterations. Returns how many were performed.
+pub extern "sysv64-unwind" fn copy(
+    fastmem: &FastmemLuts,
+    dst: u32,
+    src: u32,
+    size: u32,
+    ctr: u32,
+) -> u32 {
+    let Some((dst_ptr, dst_fit)) = page_span(&fastmem.stores, dst, size) else {
+        return 0;
+    };
+
+    let Some((src_ptr, src_fit)) = page_span(&fastmem.loads, src, size) else {
+        return 0;
+    };
+
+    let count = iterations(ctr, dst_fit.min(src_fit));
+    let len = (count * size) as usize;
+
+    // copying one element at a time only matches a memmove if the destination does not start
+    // inside the source, since the loop would then read back what it wrote
+    let (dst_addr, src_addr) = (dst_ptr.addr().get(), src_ptr.addr().get());
+    if dst_addr > src_addr && dst_addr < src_addr + len {
+        return 0;
+    }
+
+    // SAFETY: both ranges are within a single fastmem page
+    unsafe { std::ptr::copy(src_ptr.as_ptr(), dst_ptr.as_ptr(), len) };
+
+    count
+}
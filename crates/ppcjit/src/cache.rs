@@ -3,6 +3,7 @@ use std::io::Cursor;
 use std::path::Path;
 
 use cranelift_codegen::isa::TargetIsa;
+use easyerr::{Error, ResultExt};
 use fjall::{Database, KeyspaceCreateOptions};
 use zerocopy::IntoBytes;
 
@@ -21,12 +22,28 @@ impl Hasher for Hash128 {
     }
 }
 
+/// Version of the generated code. Must be bumped whenever the code generated for a sequence changes
+/// in a way not captured by the settings, so that stale blocks are not loaded from the cache.
+const CODEGEN_VERSION: u32 = 3;
+
+/// Name of the marker file which, if present in the cache directory, makes the cache be cleared the
+/// next time it's opened.
+const STALE_FILE: &str = "stale";
+
+/// Returns the name of the database directory for the given codegen version. Stale blocks are never
+/// loaded since the version is part of every key, but they'd pile up, so each version gets its own
+/// database and the ones of other versions are removed.
+fn db_dir(version: u32) -> String {
+    format!("v{version}")
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct CompiledKey(u128);
 
 impl CompiledKey {
     pub fn new(isa: &dyn TargetIsa, settings: &CompilerSettings, seq: &Sequence) -> Self {
         let mut hasher = Hash128(twox_hash::XxHash3_128::with_seed(0));
+        CODEGEN_VERSION.hash(&mut hasher);
         isa.name().hash(&mut hasher);
         isa.triple().hash(&mut hasher);
         isa.flags().hash(&mut hasher);
@@ -46,24 +63,92 @@ pub struct Cache {
     decompress_buffer: Vec<u8>,
 }
 
+#[derive(Debug, Error)]
+pub enum OpenError {
+    #[error(transparent)]
+    Io { source: std::io::Error },
+    #[error(transparent)]
+    Database { source: fjall::Error },
+}
+
 impl Cache {
-    pub fn new(path: impl AsRef<Path>) -> Self {
-        _ = std::fs::create_dir(&path);
+    /// Opens the cache in the given directory, creating it if needed. Caches of other versions
+    /// are removed, and the cache is cleared if it was invalidated or can't be opened (e.g.
+    /// because it's corrupted). Returns `None` if it can't be opened even then, in which case
+    /// blocks are always compiled.
+    ///
+    /// Only directories created by the cache are ever removed, so that pointing it at the wrong
+    /// directory can't lose data.
+    pub fn open(path: &Path) -> Option<Self> {
+        let db_path = path.join(db_dir(CODEGEN_VERSION));
+        let err = match Self::try_open(path, &db_path) {
+            Ok(cache) => return Some(cache),
+            Err(err) => err,
+        };
+
+        tracing::warn!("failed to open JIT block cache, clearing it: {err}");
+        _ = std::fs::remove_dir_all(&db_path);
+
+        match Self::try_open(path, &db_path) {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                tracing::warn!("failed to open JIT block cache, disabling it: {err}");
+                None
+            }
+        }
+    }
+
+    fn try_open(path: &Path, db_path: &Path) -> Result<Self, OpenError> {
+        std::fs::create_dir_all(path).context(OpenCtx::Io)?;
+
+        let stale = path.join(STALE_FILE);
+        if stale.exists() {
+            tracing::info!("clearing invalidated JIT block cache");
+            if db_path.exists() {
+                std::fs::remove_dir_all(db_path).context(OpenCtx::Io)?;
+            }
+
+            std::fs::remove_file(&stale).context(OpenCtx::Io)?;
+        }
+
+        for entry in std::fs::read_dir(path).context(OpenCtx::Io)? {
+            let entry = entry.context(OpenCtx::Io)?;
+            let name = entry.file_name();
+            let Some(version) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix('v'))
+                .and_then(|version| version.parse::<u32>().ok())
+            else {
+                continue;
+            };
+
+            if version != CODEGEN_VERSION && entry.path().is_dir() {
+                tracing::info!("removing JIT block cache of version {version}");
+                std::fs::remove_dir_all(entry.path()).context(OpenCtx::Io)?;
+            }
+        }
 
-        let db = Database::builder(&path)
+        let db = Database::builder(db_path)
             .journal_compression(fjall::CompressionType::None)
             .manual_journal_persist(true)
             .open()
-            .unwrap();
+            .context(OpenCtx::Database)?;
 
-        Self {
+        Ok(Self {
             db,
             pending: 0,
             compressor: zstd::bulk::Compressor::new(5).unwrap(),
             decompressor: zstd::bulk::Decompressor::new().unwrap(),
             deser_buffer: vec![0; 512 * 1024],
             decompress_buffer: vec![0; 4 * 1024 * 1024],
-        }
+        })
+    }
+
+    /// Marks the cache in the given directory as stale, so that it's cleared the next time it's
+    /// opened. Unlike removing the directory, this is fine to do while the cache is open.
+    pub fn invalidate(path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(path)?;
+        std::fs::write(path.join(STALE_FILE), [])
     }
 
     pub fn get(&mut self, key: CompiledKey) -> Option<Compiled> {
@@ -115,3 +200,31 @@ impl Drop for Cache {
         self.db.persist(fjall::PersistMode::SyncAll).unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stale_caches_are_cleared() {
+        let path = std::env::temp_dir().join(format!("ppcjit-cache-test-{}", std::process::id()));
+        let old = path.join(db_dir(CODEGEN_VERSION - 1));
+        let unrelated = path.join("unrelated");
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::create_dir_all(&unrelated).unwrap();
+
+        drop(Cache::open(&path).unwrap());
+        assert!(!old.exists());
+        assert!(unrelated.exists());
+        assert!(path.join(db_dir(CODEGEN_VERSION)).exists());
+
+        Cache::invalidate(&path).unwrap();
+        assert!(path.join(STALE_FILE).exists());
+
+        drop(Cache::open(&path).unwrap());
+        assert!(!path.join(STALE_FILE).exists());
+        assert!(unrelated.exists());
+
+        _ = std::fs::remove_dir_all(path);
+    }
+}
@@ -3,13 +3,13 @@ use cranelift::prelude::isa;
 use gekko::{Address, Cpu, QuantReg};
 use strum::FromRepr;
 
-use crate::FastmemLut;
+use crate::FastmemLuts;
 use crate::block::{Info, LinkData};
 
 pub type Context = std::ffi::c_void;
 
 pub type GetRegistersHook = extern "sysv64-unwind" fn(*mut Context) -> *mut Cpu;
-pub type GetFastmemHook = extern "sysv64-unwind" fn(*mut Context) -> *mut FastmemLut;
+pub type GetFastmemHook = extern "sysv64-unwind" fn(*mut Context) -> *mut FastmemLuts;
 
 pub type FollowLinkHook =
     extern "sysv64-unwind" fn(*const Info, *mut Context, *mut LinkData) -> bool;
@@ -22,6 +22,8 @@ pub type ReadQuantizedHook =
 pub type WriteQuantizedHook = extern "sysv64-unwind" fn(*mut Context, Address, QuantReg, f64) -> u8;
 
 pub type InvalidateICache = extern "sysv64-unwind" fn(*mut Context, Address);
+pub type InvalidateTlb = extern "sysv64-unwind" fn(*mut Context, Address);
+pub type UnimplementedHook = extern "sysv64-unwind" fn(*mut Context, u32, Address);
 
 pub type GenericHook = extern "sysv64-unwind" fn(*mut Context);
 
@@ -51,13 +53,16 @@ pub enum HookKind {
     TbChanged,
     DecRead,
     DecChanged,
+    InvTlb,
+    PageTableChanged,
+    Unimplemented,
 }
 
 /// External functions that JITed code calls.
 pub struct Hooks {
     /// Hook that returns a pointer to the CPU state struct given the context.
     pub get_registers: GetRegistersHook,
-    /// Hook that returns a pointer to the fastmem LUT given the context.
+    /// Hook that returns a pointer to the fastmem LUTs given the context.
     pub get_fastmem: GetFastmemHook,
 
     /// Hook that checks whether a linked block should be followed or the execution should return.
@@ -89,6 +94,13 @@ pub struct Hooks {
     pub ibat_changed: GenericHook,
     pub dbat_changed: GenericHook,
 
+    // page table
+    /// Hook called by `tlbie`, given the context and the effective address whose TLB congruence
+    /// class should be invalidated.
+    pub invalidate_tlb: InvalidateTlb,
+    /// Hook called when SDR1 or a segment register changes.
+    pub page_table_changed: GenericHook,
+
     // time base
     pub tb_read: GenericHook,
     pub tb_changed: GenericHook,
@@ -96,6 +108,11 @@ pub struct Hooks {
     // decrementer
     pub dec_read: GenericHook,
     pub dec_changed: GenericHook,
+
+    // others
+    /// Hook called when an unimplemented instruction is executed, given the context, the
+    /// instruction and its address. Only called if unimplemented instructions are ignored.
+    pub unimplemented: UnimplementedHook,
 }
 
 impl Hooks {
@@ -213,6 +230,19 @@ impl Hooks {
         }
     }
 
+    /// Returns the function signature for the `unimplemented` hook.
+    pub(crate) fn unimplemented_sig(ptr_type: ir::Type) -> ir::Signature {
+        ir::Signature {
+            params: vec![
+                ir::AbiParam::new(ptr_type),       // ctx
+                ir::AbiParam::new(ir::types::I32), // instruction
+                ir::AbiParam::new(ir::types::I32), // address
+            ],
+            returns: vec![],
+            call_conv: isa::CallConv::SystemV,
+        }
+    }
+
     /// Returns the function signature for a generic hook.
     pub(crate) fn generic_hook_sig(ptr_type: ir::Type) -> ir::Signature {
         ir::Signature {
@@ -1,6 +1,7 @@
 #![feature(debug_closure_helpers)]
 
 mod builder;
+mod bulk;
 mod cache;
 mod module;
 mod sequence;
@@ -10,7 +11,7 @@ pub mod block;
 pub mod hooks;
 
 use std::alloc::Layout;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
 use std::sync::Arc;
 
@@ -51,24 +52,46 @@ pub struct CompilerSettings {
     pub ignore_unimplemented: bool,
     /// Whether to perform round to single operations.
     pub round_to_single: bool,
+    /// Whether floating point arithmetic should update the FPSCR exception bits. Has a
+    /// performance cost.
+    pub float_exceptions: bool,
+    /// Whether blocks starting with a memset or memcpy-like loop should perform most of its
+    /// iterations at once in an optimized host routine.
+    pub bulk_memory_idioms: bool,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct Settings {
     /// Compiler settings
     pub compiler: CompilerSettings,
-    /// Path to the block cache directory
-    pub cache_path: PathBuf,
+    /// Path to the block cache directory, if compiled blocks should persist across runs
+    pub cache_path: Option<PathBuf>,
+}
+
+/// Marks the block cache in the given directory as stale, so that it's cleared the next time a
+/// [`Jit`] opens it. Fine to call while the cache is in use.
+pub fn invalidate_cache(path: &Path) -> std::io::Result<()> {
+    Cache::invalidate(path)
 }
 
 pub const FASTMEM_LUT_COUNT: usize = 1 << 15;
 pub type FastmemLut = [Option<NonNull<u8>>; FASTMEM_LUT_COUNT];
 
+/// The fastmem LUTs used by blocks: loads look pages up in `loads` and stores in `stores`. Accesses
+/// to pages without an entry go through the memory hooks instead.
+#[repr(C)]
+pub struct FastmemLuts {
+    pub loads: FastmemLut,
+    pub stores: FastmemLut,
+}
+
 const NAMESPACE_USER_HOOKS: u32 = 0;
 const NAMESPACE_INTERNALS: u32 = 1;
 const NAMESPACE_LINK_DATA: u32 = 2;
 
 const INTERNAL_RAISE_EXCEPTION: u32 = 0;
+const INTERNAL_BULK_FILL: u32 = 1;
+const INTERNAL_BULK_COPY: u32 = 2;
 
 struct Compiler {
     settings: CompilerSettings,
@@ -259,12 +282,14 @@ impl Compiler {
                         HookKind::TbChanged => self.hooks.tb_changed as usize,
                         HookKind::DecRead => self.hooks.dec_read as usize,
                         HookKind::DecChanged => self.hooks.dec_changed as usize,
+                        HookKind::InvTlb => self.hooks.invalidate_tlb as usize,
+                        HookKind::PageTableChanged => self.hooks.page_table_changed as usize,
+                        HookKind::Unimplemented => self.hooks.unimplemented as usize,
                     };
 
                     Self::write_relocation(code, reloc, addr);
                 }
                 NAMESPACE_INTERNALS => {
-                    assert_eq!(name.index, INTERNAL_RAISE_EXCEPTION);
                     extern "sysv64-unwind" fn raise_exception(
                         regs: &mut Cpu,
                         exception: Exception,
@@ -272,7 +297,15 @@ impl Compiler {
                         regs.raise_exception(exception);
                     }
 
-                    let addr = raise_exception as extern "sysv64-unwind" fn(_, _) as usize;
+                    let addr = match name.index {
+                        INTERNAL_RAISE_EXCEPTION => {
+                            raise_exception as extern "sysv64-unwind" fn(_, _) as usize
+                        }
+                        INTERNAL_BULK_FILL => bulk::fill as bulk::Routine as usize,
+                        INTERNAL_BULK_COPY => bulk::copy as bulk::Routine as usize,
+                        _ => unreachable!(),
+                    };
+
                     Self::write_relocation(code, reloc, addr);
                 }
                 NAMESPACE_LINK_DATA => {
@@ -297,7 +330,7 @@ pub struct Jit {
     compiler: Compiler,
     code_ctx: codegen::Context,
     func_ctx: frontend::FunctionBuilderContext,
-    cache: Cache,
+    cache: Option<Cache>,
     compiled_count: u64,
     trampoline: Trampoline,
 }
@@ -331,7 +364,7 @@ impl Jit {
         let mut compiler = Compiler::new(settings.compiler, hooks);
         let mut code_ctx = codegen::Context::new();
         let mut func_ctx = frontend::FunctionBuilderContext::new();
-        let cache = Cache::new(settings.cache_path);
+        let cache = settings.cache_path.as_deref().and_then(Cache::open);
         let trampoline = compiler.trampoline(&mut code_ctx, &mut func_ctx);
 
         Self {
@@ -403,14 +436,17 @@ impl Jit {
             &self.compiler.settings,
             &translated.sequence,
         );
-        let compiled = if let Some(compiled) = self.cache.get(key) {
+        let cached = self.cache.as_mut().and_then(|cache| cache.get(key));
+        let compiled = if let Some(compiled) = cached {
             compiled
         } else {
             self.code_ctx.clear();
             self.code_ctx.func = translated.func;
 
             let compiled = self.compile()?;
-            self.cache.insert(key, &compiled);
+            if let Some(cache) = &mut self.cache {
+                cache.insert(key, &compiled);
+            }
 
             compiled
         };
@@ -445,3 +481,518 @@ impl Jit {
         unsafe { self.trampoline.call(ctx, block) }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::mem::transmute;
+
+    use gekko::disasm::Extensions;
+    use gekko::{Address, Reg, SPR};
+
+    use super::*;
+    use crate::hooks::*;
+
+    struct TestContext {
+        cpu: Cpu,
+        fastmem: Box<FastmemLuts>,
+        skipped: Vec<(u32, Address)>,
+        /// Address whose accesses fault.
+        fault: Address,
+        writes: Vec<(Address, i32)>,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            Self {
+                cpu: Cpu::default(),
+                fastmem: Box::new(FastmemLuts {
+                    loads: [None; FASTMEM_LUT_COUNT],
+                    stores: [None; FASTMEM_LUT_COUNT],
+                }),
+                skipped: Vec::new(),
+                fault: Address(0x8000_1000),
+                writes: Vec::new(),
+            }
+        }
+    }
+
+    const PC: Address = Address(0x8000_3100);
+    const FAULT_DSISR: u32 = 0x4000_0000;
+    const READ_VALUE: i32 = 0x1234_5678;
+
+    /// Address of the vector of the given exception, with the default MSR.
+    fn vector(exception: Exception) -> Address {
+        Address(0xFFF0_0000 | exception as u32)
+    }
+
+    extern "sysv64-unwind" fn get_registers(ctx: &mut TestContext) -> &mut Cpu {
+        &mut ctx.cpu
+    }
+
+    extern "sysv64-unwind" fn get_fastmem(ctx: &mut TestContext) -> &FastmemLuts {
+        &ctx.fastmem
+    }
+
+    extern "sysv64-unwind" fn unimplemented(ctx: &mut TestContext, code: u32, pc: Address) {
+        ctx.skipped.push((code, pc));
+    }
+
+    extern "sysv64-unwind" fn follow_link(_: &Info, _: &mut TestContext, _: &mut LinkData) -> bool {
+        false
+    }
+
+    extern "sysv64-unwind" fn read_i32(
+        ctx: &mut TestContext,
+        addr: Address,
+        value: &mut i32,
+    ) -> bool {
+        if addr == ctx.fault {
+            ctx.cpu.supervisor.exception.dsisr = FAULT_DSISR;
+            return false;
+        }
+
+        *value = READ_VALUE;
+        true
+    }
+
+    extern "sysv64-unwind" fn write_i32(ctx: &mut TestContext, addr: Address, value: i32) -> bool {
+        if addr == ctx.fault {
+            ctx.cpu.supervisor.exception.dsisr = FAULT_DSISR;
+            return false;
+        }
+
+        ctx.writes.push((addr, value));
+        true
+    }
+
+    extern "sysv64-unwind" fn never() {
+        unreachable!("unexpected hook call");
+    }
+
+    #[expect(
+        clippy::missing_transmute_annotations,
+        reason = "unnecessary - the field types are the annotations"
+    )]
+    fn hooks() -> Hooks {
+        let never = never as extern "sysv64-unwind" fn();
+
+        // SAFETY: the hooks are only called with the argument types they are defined with, except
+        // for `never`, which is never called
+        unsafe {
+            Hooks {
+                get_registers: transmute(get_registers as extern "sysv64-unwind" fn(_) -> _),
+                get_fastmem: transmute(get_fastmem as extern "sysv64-unwind" fn(_) -> _),
+                follow_link: transmute(follow_link as extern "sysv64-unwind" fn(_, _, _) -> _),
+                try_link: transmute(never),
+                read_i8: transmute(never),
+                write_i8: transmute(never),
+                read_i16: transmute(never),
+                write_i16: transmute(never),
+                read_i32: transmute(read_i32 as extern "sysv64-unwind" fn(_, _, _) -> _),
+                write_i32: transmute(write_i32 as extern "sysv64-unwind" fn(_, _, _) -> _),
+                read_i64: transmute(never),
+                write_i64: transmute(never),
+                read_quantized: transmute(never),
+                write_quantized: transmute(never),
+                invalidate_icache: transmute(never),
+                dcache_dma: transmute(never),
+                msr_changed: transmute(never),
+                ibat_changed: transmute(never),
+                dbat_changed: transmute(never),
+                invalidate_tlb: transmute(never),
+                page_table_changed: transmute(never),
+                tb_read: transmute(never),
+                tb_changed: transmute(never),
+                dec_read: transmute(never),
+                dec_changed: transmute(never),
+                unimplemented: transmute(unimplemented as extern "sysv64-unwind" fn(_, _, _)),
+            }
+        }
+    }
+
+    #[test]
+    fn unimplemented_instructions_are_reported() {
+        let cache_path = std::env::temp_dir().join(format!("ppcjit-test-{}", std::process::id()));
+        let settings = Settings {
+            compiler: CompilerSettings {
+                ignore_unimplemented: true,
+                ..Default::default()
+            },
+            cache_path: Some(cache_path.clone()),
+        };
+
+        let mut jit = Jit::new(settings, hooks());
+
+        // eciwx r3, r0, r4
+        let code = 0x7C60_226C;
+        let ins = Ins::new(code, Extensions::gekko_broadway());
+        let block = jit.build(std::iter::once(ins)).unwrap();
+
+        let mut ctx = TestContext::new();
+        let pc = Address(0x8000_3100);
+        for _ in 0..2 {
+            ctx.cpu.pc = pc;
+            unsafe { jit.call((&raw mut ctx).cast(), block.as_ptr()) };
+            assert_eq!(ctx.cpu.pc, Address(0x8000_3104));
+        }
+
+        assert_eq!(ctx.skipped, [(code, pc); 2]);
+
+        drop(jit);
+        _ = std::fs::remove_dir_all(cache_path);
+    }
+
+    /// Compiles `code` into a single block and runs it once from `PC`.
+    fn run(name: &str, ctx: &mut TestContext, code: &[u32]) -> Info {
+        run_with(name, ctx, code, CompilerSettings::default())
+    }
+
+    /// Like [`run`], with the given compiler settings.
+    fn run_with(
+        name: &str,
+        ctx: &mut TestContext,
+        code: &[u32],
+        compiler: CompilerSettings,
+    ) -> Info {
+        let cache_path =
+            std::env::temp_dir().join(format!("ppcjit-test-{}-{name}", std::process::id()));
+        let settings = Settings {
+            compiler,
+            cache_path: Some(cache_path.clone()),
+        };
+
+        let mut jit = Jit::new(settings, hooks());
+        let instructions = code
+            .iter()
+            .map(|&code| Ins::new(code, Extensions::gekko_broadway()));
+        let block = jit.build(instructions).unwrap();
+
+        ctx.cpu.pc = PC;
+        let info = unsafe { jit.call(std::ptr::from_mut(ctx).cast(), block.as_ptr()) };
+
+        drop(jit);
+        _ = std::fs::remove_dir_all(cache_path);
+
+        info
+    }
+
+    #[test]
+    fn data_fault_mid_block_is_precise() {
+        let code = [
+            0x3860_0001, // li r3, 1
+            0x8085_0000, // lwz r4, 0(r5)
+            0x38C0_0002, // li r6, 2
+        ];
+
+        // no fault: the whole block runs
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[5] = 0x8000_2000;
+        let info = run("no-fault", &mut ctx, &code);
+
+        assert_eq!(ctx.cpu.pc, PC + 12u32);
+        assert_eq!(
+            ctx.cpu.user.gpr[3..=6],
+            [1, READ_VALUE as u32, 0x8000_2000, 2]
+        );
+        assert_eq!(info.instructions, 3);
+
+        // fault: only the instructions before the load run
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[5] = ctx.fault.value();
+        let info = run("fault", &mut ctx, &code);
+
+        let exception = &ctx.cpu.supervisor.exception;
+        assert_eq!(ctx.cpu.pc, vector(Exception::DSI));
+        assert_eq!(exception.srr[0], (PC + 4u32).value());
+        assert_eq!(exception.dar, ctx.fault.value());
+        assert_eq!(exception.dsisr, FAULT_DSISR);
+        assert_eq!(ctx.cpu.user.gpr[3..=6], [1, 0, ctx.fault.value(), 0]);
+        assert_eq!(info.instructions, 2);
+    }
+
+    #[test]
+    fn faulting_store_with_update_leaves_ra_untouched() {
+        let code = [
+            0x9465_0000, // stwu r3, 0(r5)
+            0x3860_0001, // li r3, 1
+        ];
+
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[5] = ctx.fault.value();
+        let info = run("stwu", &mut ctx, &code);
+
+        assert_eq!(ctx.cpu.pc, vector(Exception::DSI));
+        assert_eq!(ctx.cpu.supervisor.exception.srr[0], PC.value());
+        assert_eq!(ctx.cpu.user.gpr[3], 0);
+        assert_eq!(ctx.cpu.user.gpr[5], ctx.fault.value());
+        assert!(ctx.writes.is_empty());
+        assert_eq!(info.instructions, 1);
+    }
+
+    #[test]
+    fn unaligned_lwarx_raises_alignment() {
+        let code = [
+            0x3860_0001, // li r3, 1
+            0x7C64_2828, // lwarx r3, r4, r5
+            0x38C0_0002, // li r6, 2
+        ];
+
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[4] = 0x8000_2000;
+        ctx.cpu.user.gpr[5] = 2;
+        let info = run("lwarx", &mut ctx, &code);
+
+        let exception = &ctx.cpu.supervisor.exception;
+        assert_eq!(ctx.cpu.pc, vector(Exception::Alignment));
+        assert_eq!(exception.srr[0], (PC + 4u32).value());
+        assert_eq!(exception.dar, 0x8000_2002);
+        assert_eq!(exception.dsisr, (3 << 5) | 4);
+        assert_eq!(ctx.cpu.user.gpr[3], 1);
+        assert_eq!(ctx.cpu.user.gpr[6], 0);
+        assert_eq!(ctx.cpu.supervisor.misc.reservation, 0);
+        assert_eq!(info.instructions, 2);
+    }
+
+    #[test]
+    fn stwcx_needs_reservation() {
+        let code = [
+            0x7C60_2028, // lwarx r3, 0, r4
+            0x7CC0_292D, // stwcx. r6, 0, r5
+        ];
+
+        let cr0_eq = |ctx: &TestContext| ctx.cpu.user.cr.to_bits() & (1 << 29) != 0;
+
+        // same address: the store happens
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[4] = 0x8000_2000;
+        ctx.cpu.user.gpr[5] = 0x8000_2000;
+        ctx.cpu.user.gpr[6] = 0x0BAD_F00D;
+        run("stwcx-reserved", &mut ctx, &code);
+
+        assert_eq!(ctx.cpu.user.gpr[3], READ_VALUE as u32);
+        assert_eq!(ctx.writes, [(Address(0x8000_2000), 0x0BAD_F00D)]);
+        assert!(cr0_eq(&ctx));
+        assert_eq!(ctx.cpu.supervisor.misc.reservation, 0);
+
+        // another address: the store does not happen
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.gpr[4] = 0x8000_2000;
+        ctx.cpu.user.gpr[5] = 0x8000_2004;
+        run("stwcx-unreserved", &mut ctx, &code);
+
+        assert!(ctx.writes.is_empty());
+        assert!(!cr0_eq(&ctx));
+        assert_eq!(ctx.cpu.pc, PC + 8u32);
+    }
+
+    #[test]
+    fn system_register_reads_are_reused() {
+        let cache_path =
+            std::env::temp_dir().join(format!("ppcjit-test-{}-reads", std::process::id()));
+        let settings = Settings {
+            compiler: CompilerSettings::default(),
+            cache_path: Some(cache_path.clone()),
+        };
+
+        let code = [
+            0x7C60_00A6, // mfmsr r3
+            0xFC22_182A, // fadd f1, f2, f3
+            0xE025_0000, // psq_l f1, 0(r5), 0, 0
+            0xE045_0008, // psq_l f2, 8(r5), 0, 0
+            0x7C80_00A6, // mfmsr r4
+        ];
+
+        let mut jit = Jit::new(settings, hooks());
+        let instructions = code
+            .iter()
+            .map(|&code| Ins::new(code, Extensions::gekko_broadway()));
+        let block = jit.build(instructions).unwrap();
+        let clir = block.meta().clir.as_deref().unwrap();
+
+        // loads from the registers struct look like `vN = load.i32 notrap aligned vM+offset`
+        let loads = |reg: Reg| {
+            let offset = reg.offset().to_string();
+            clir.lines()
+                .filter(|line| line.contains(" = load."))
+                .filter(|line| line.rsplit_once('+').is_some_and(|(_, o)| o == offset))
+                .count()
+        };
+
+        assert_eq!(loads(Reg::MSR), 1, "{clir}");
+        assert_eq!(loads(Reg::SPR(SPR::GQR[0])), 1, "{clir}");
+
+        drop(jit);
+        _ = std::fs::remove_dir_all(cache_path);
+    }
+
+    /// Maps the fastmem page at `0x8000_0000` to `page`, for both loads and stores.
+    fn map_page(ctx: &mut TestContext, page: &mut [u8]) {
+        let ptr = NonNull::new(page.as_mut_ptr());
+        ctx.fastmem.loads[0x8000_0000 >> 17] = ptr;
+        ctx.fastmem.stores[0x8000_0000 >> 17] = ptr;
+    }
+
+    fn bulk() -> CompilerSettings {
+        CompilerSettings {
+            bulk_memory_idioms: true,
+            ..Default::default()
+        }
+    }
+
+    const FILL: [u32; 2] = [
+        0x9483_0004, // stwu r4, 4(r3)
+        0x4200_FFFC, // bdnz -4
+    ];
+
+    #[test]
+    fn fill_idiom_runs_in_bulk() {
+        let mut page = vec![0; 1 << 17];
+        let mut ctx = TestContext::new();
+        map_page(&mut ctx, &mut page);
+        ctx.cpu.user.gpr[3] = 0x8000_0FFC;
+        ctx.cpu.user.gpr[4] = 0xDEAD_BEEF;
+        ctx.cpu.user.ctr = 10;
+        let info = run_with("bulk-fill", &mut ctx, &FILL, bulk());
+
+        assert!(
+            page[0x1000..0x1028]
+                .chunks(4)
+                .all(|w| w == [0xDE, 0xAD, 0xBE, 0xEF])
+        );
+        assert_eq!(page[0x1028..0x102C], [0; 4]);
+        assert_eq!(ctx.cpu.user.gpr[3], 0x8000_1024);
+        assert_eq!(ctx.cpu.user.ctr, 0);
+        assert_eq!(ctx.cpu.pc, PC + 8u32);
+        assert_eq!(info.instructions, 20);
+    }
+
+    #[test]
+    fn fill_idiom_is_left_alone_when_disabled() {
+        let mut page = vec![0; 1 << 17];
+        let mut ctx = TestContext::new();
+        map_page(&mut ctx, &mut page);
+        ctx.cpu.user.gpr[3] = 0x8000_0FFC;
+        ctx.cpu.user.gpr[4] = 0xDEAD_BEEF;
+        ctx.cpu.user.ctr = 10;
+        let info = run("bulk-fill-disabled", &mut ctx, &FILL);
+
+        assert_eq!(page[0x1000..0x1008], [0xDE, 0xAD, 0xBE, 0xEF, 0, 0, 0, 0]);
+        assert_eq!(ctx.cpu.user.gpr[3], 0x8000_1000);
+        assert_eq!(ctx.cpu.user.ctr, 9);
+        assert_eq!(info.instructions, 2);
+    }
+
+    const COPY: [u32; 3] = [
+        0x84A4_0004, // lwzu r5, 4(r4)
+        0x94A3_0004, // stwu r5, 4(r3)
+        0x4200_FFF8, // bdnz -8
+    ];
+
+    #[test]
+    fn copy_idiom_runs_in_bulk() {
+        let mut page = vec![0; 1 << 17];
+        for (i, byte) in page[0x2000..0x2020].iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+
+        let mut ctx = TestContext::new();
+        map_page(&mut ctx, &mut page);
+        ctx.cpu.user.gpr[3] = 0x8000_0FFC;
+        ctx.cpu.user.gpr[4] = 0x8000_1FFC;
+        ctx.cpu.user.ctr = 8;
+        let info = run_with("bulk-copy", &mut ctx, &COPY, bulk());
+
+        assert_eq!(page[0x1000..0x1020], page[0x2000..0x2020]);
+        assert_eq!(
+            ctx.cpu.user.gpr[3..=5],
+            [0x8000_101C, 0x8000_201C, 0x1D1E_1F20]
+        );
+        assert_eq!(ctx.cpu.user.ctr, 0);
+        assert_eq!(info.instructions, 24);
+    }
+
+    #[test]
+    fn overlapping_copy_idiom_runs_normally() {
+        let mut page = vec![0; 1 << 17];
+        page[0x1000..0x1004].copy_from_slice(&[1, 2, 3, 4]);
+
+        // copying forwards onto itself, which repeats the first word
+        let mut ctx = TestContext::new();
+        map_page(&mut ctx, &mut page);
+        ctx.cpu.user.gpr[3] = 0x8000_1000;
+        ctx.cpu.user.gpr[4] = 0x8000_0FFC;
+        ctx.cpu.user.ctr = 8;
+        let info = run_with("bulk-copy-overlap", &mut ctx, &COPY, bulk());
+
+        assert_eq!(page[0x1000..0x100C], [1, 2, 3, 4, 1, 2, 3, 4, 0, 0, 0, 0]);
+        assert_eq!(ctx.cpu.user.ctr, 7);
+        assert_eq!(info.instructions, 3);
+    }
+
+    fn float_exceptions() -> CompilerSettings {
+        CompilerSettings {
+            force_fpu: true,
+            float_exceptions: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the given FPSCR bit (in manual numbering) is set.
+    fn fpscr_bit(ctx: &TestContext, bit: u32) -> bool {
+        ctx.cpu.user.fpscr.to_bits() & (1 << (31 - bit)) != 0
+    }
+
+    const FPSCR_FX: u32 = 0;
+    const FPSCR_FEX: u32 = 1;
+    const FPSCR_ZX: u32 = 5;
+    const FPSCR_XX: u32 = 6;
+    const FPSCR_FI: u32 = 14;
+
+    #[test]
+    fn divide_by_zero_sets_zx() {
+        let code = [
+            0xFC22_1824, // fdiv f1, f2, f3
+        ];
+
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.fpr[2][0] = 1.0;
+        ctx.cpu.user.fpr[3][0] = 0.0;
+        run_with("fdiv-zero", &mut ctx, &code, float_exceptions());
+
+        assert_eq!(ctx.cpu.user.fpr[1][0], f64::INFINITY);
+        assert!(fpscr_bit(&ctx, FPSCR_ZX));
+        assert!(fpscr_bit(&ctx, FPSCR_FX));
+        assert!(!fpscr_bit(&ctx, FPSCR_XX));
+        assert!(!fpscr_bit(&ctx, FPSCR_FEX));
+    }
+
+    #[test]
+    fn inexact_add_sets_xx() {
+        let code = [
+            0xFC22_182A, // fadd f1, f2, f3
+        ];
+
+        // exact
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.fpr[2][0] = 1.0;
+        ctx.cpu.user.fpr[3][0] = 0.5;
+        run_with("fadd-exact", &mut ctx, &code, float_exceptions());
+
+        assert_eq!(ctx.cpu.user.fpr[1][0], 1.5);
+        assert!(!fpscr_bit(&ctx, FPSCR_XX));
+        assert!(!fpscr_bit(&ctx, FPSCR_FI));
+        assert!(!fpscr_bit(&ctx, FPSCR_FX));
+
+        // inexact
+        let mut ctx = TestContext::new();
+        ctx.cpu.user.fpr[2][0] = 1.0;
+        ctx.cpu.user.fpr[3][0] = 2.0f64.powi(-60);
+        run_with("fadd-inexact", &mut ctx, &code, float_exceptions());
+
+        assert_eq!(ctx.cpu.user.fpr[1][0], 1.0);
+        assert!(fpscr_bit(&ctx, FPSCR_XX));
+        assert!(fpscr_bit(&ctx, FPSCR_FI));
+        assert!(fpscr_bit(&ctx, FPSCR_FX));
+        assert!(!fpscr_bit(&ctx, FPSCR_ZX));
+    }
+}
@@ -1,7 +1,7 @@
 use std::ops::Deref;
 
 use gekko::disasm::{Ins, Opcode, ParsedIns};
-use gekko::{Address, InsExt};
+use gekko::{Address, GPR, InsExt};
 
 use crate::block::Pattern;
 
@@ -130,6 +130,88 @@ impl Sequence {
     }
 }
 
+/// A loop at the start of a block which only fills or copies memory forwards, one element at a
+/// time, so that its iterations can be performed in bulk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkIdiom {
+    /// `stXu value, size(dst)` followed by a `bdnz` back to it.
+    Fill { size: u8, value: GPR, dst: GPR },
+    /// `lXzu tmp, size(src)` and `stXu tmp, size(dst)` followed by a `bdnz` back to them.
+    Copy { size: u8, src: GPR, dst: GPR },
+}
+
+impl BulkIdiom {
+    /// Size of the elements accessed by the given load or store with update, if it's one of the
+    /// idiom.
+    fn access_size(ins: &Ins) -> Option<u8> {
+        Some(match ins.op {
+            Opcode::Lbzu | Opcode::Stbu => 1,
+            Opcode::Lhzu | Opcode::Sthu => 2,
+            Opcode::Lwzu | Opcode::Stwu => 4,
+            _ => return None,
+        })
+    }
+
+    /// How many instructions an idiom starting with `first` would take, if any can start with it.
+    pub fn len_starting_with(first: &Ins) -> Option<usize> {
+        match first.op {
+            Opcode::Stbu | Opcode::Sthu | Opcode::Stwu => Some(2),
+            Opcode::Lbzu | Opcode::Lhzu | Opcode::Lwzu => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Whether `ins` is a `bdnz` to `offset` bytes from itself.
+    fn is_bdnz_back(ins: &Ins, offset: i32) -> bool {
+        matches!(ins.op, Opcode::Bc)
+            && ins.field_bo() == 16
+            && !ins.field_aa()
+            && !ins.field_lk()
+            && ins.field_bd() as i32 == offset
+    }
+
+    /// Detects an idiom made of exactly the given instructions.
+    pub fn detect(ins: &[Ins]) -> Option<Self> {
+        match ins {
+            [store, bdnz] => {
+                let size = Self::access_size(store)?;
+                let (value, dst) = (store.gpr_s(), store.gpr_a());
+
+                let is_store = Self::len_starting_with(store) == Some(2);
+                let contiguous = store.field_offset() as i32 == size as i32;
+                let valid = dst != GPR::R0 && value != dst;
+
+                (is_store && contiguous && valid && Self::is_bdnz_back(bdnz, -4))
+                    .then_some(Self::Fill { size, value, dst })
+            }
+            [load, store, bdnz] => {
+                let size = Self::access_size(load)?;
+                let (tmp, src, dst) = (load.gpr_d(), load.gpr_a(), store.gpr_a());
+
+                let is_copy = Self::len_starting_with(load) == Some(3)
+                    && Self::access_size(store) == Some(size)
+                    && Self::len_starting_with(store) == Some(2)
+                    && store.gpr_s() == tmp;
+                let contiguous = load.field_offset() as i32 == size as i32
+                    && store.field_offset() as i32 == size as i32;
+                let valid = ![GPR::R0, tmp, dst].contains(&src) && ![GPR::R0, tmp].contains(&dst);
+
+                (is_copy && contiguous && valid && Self::is_bdnz_back(bdnz, -8))
+                    .then_some(Self::Copy { size, src, dst })
+            }
+            _ => None,
+        }
+    }
+
+    /// How many instructions an iteration of the idiom executes.
+    pub fn len(&self) -> u32 {
+        match self {
+            Self::Fill { .. } => 2,
+            Self::Copy { .. } => 3,
+        }
+    }
+}
+
 impl Deref for Sequence {
     type Target = [Ins];
 
@@ -17,4 +17,8 @@ fn main() {
     wesl.build_artifact(&"package::color_blit".parse().unwrap(), "color_blit");
     wesl.build_artifact(&"package::depth_blit".parse().unwrap(), "depth_blit");
     wesl.build_artifact(&"package::depth_resolve".parse().unwrap(), "depth_resolve");
+    wesl.build_artifact(
+        &"package::depth_resolve_single".parse().unwrap(),
+        "depth_resolve_single",
+    );
 }
@@ -0,0 +1,64 @@
+//! Replays a captured action stream offscreen, writing every presented frame to a PNG file.
+
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use clap::Parser;
+use eyre_pretty::{Context, ContextCompat, Result};
+use lazuli::modules::render::capture;
+use renderer::replay::Replayer;
+use zerocopy::IntoBytes;
+
+/// Replays a render action capture, dumping presented frames as PNGs.
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+    /// Path to the action capture
+    input: PathBuf,
+    /// Directory to write the frames into
+    #[arg(short, long, default_value = "frames")]
+    output: PathBuf,
+}
+
+fn main() -> Result<()> {
+    eyre_pretty::install()?;
+    let args = Args::parse();
+
+    let input = std::fs::File::open(&args.input).context("opening input file")?;
+    let mut reader = capture::Reader::new(BufReader::new(input)).context("reading capture")?;
+    std::fs::create_dir_all(&args.output).context("creating output directory")?;
+
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::PRIMARY,
+        ..Default::default()
+    });
+
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        ..Default::default()
+    }))
+    .context("requesting adapter")?;
+
+    let (device, queue) =
+        pollster::block_on(adapter.request_device(&renderer::device_descriptor(&adapter)))
+            .context("requesting device")?;
+
+    let mut replayer = Replayer::new(device, queue);
+    let mut frames = 0;
+    while let Some(action) = reader.read().context("reading action")? {
+        let Some(frame) = replayer.exec(action) else {
+            continue;
+        };
+
+        let image =
+            image::RgbaImage::from_raw(frame.width, frame.height, frame.pixels.as_bytes().to_vec())
+                .context("frame data does not match its dimensions")?;
+
+        let path = args.output.join(format!("frame_{frames:05}.png"));
+        image.save(&path).context("writing frame")?;
+        frames += 1;
+    }
+
+    println!("replayed {frames} frames into {}", args.output.display());
+    Ok(())
+}
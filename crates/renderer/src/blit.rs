@@ -355,64 +355,28 @@ impl ColorBlitter {
     }
 }
 
-pub struct DepthBlitter {
-    resolve_group_layout: wgpu::BindGroupLayout,
-    resolve_pipeline: wgpu::RenderPipeline,
-    blit_group_layout: wgpu::BindGroupLayout,
-    blit_pipeline: wgpu::RenderPipeline,
-    sampler: wgpu::Sampler,
+struct DepthResolve {
+    group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
 }
 
-impl DepthBlitter {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let resolve_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: None,
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Depth,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: true,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-        let blit_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+impl DepthResolve {
+    fn new(device: &wgpu::Device, multisampled: bool) -> Self {
+        let group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        sample_type: wgpu::TextureSampleType::Depth,
                         view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
+                        multisampled,
                     },
                     count: None,
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
                     visibility: wgpu::ShaderStages::VERTEX,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
@@ -424,33 +388,26 @@ impl DepthBlitter {
             ],
         });
 
-        let resolve_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&resolve_group_layout],
+            bind_group_layouts: &[&group_layout],
             push_constant_ranges: &[],
         });
 
-        let blit_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&blit_group_layout],
-            push_constant_ranges: &[],
-        });
+        let shader = if multisampled {
+            include_wesl!("depth_resolve")
+        } else {
+            include_wesl!("depth_resolve_single")
+        };
 
-        let resolve_shader = include_wesl!("depth_resolve");
-        let resolve_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("depth resolve"),
-            source: wgpu::ShaderSource::Wgsl(resolve_shader.into()),
-        });
-
-        let blit_shader = include_wesl!("depth_blit");
-        let blit_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("depth blit"),
-            source: wgpu::ShaderSource::Wgsl(blit_shader.into()),
+            source: wgpu::ShaderSource::Wgsl(shader.into()),
         });
 
-        let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("depth resolve pipeline"),
-            layout: Some(&resolve_layout),
+            layout: Some(&layout),
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleStrip,
                 strip_index_format: None,
@@ -461,13 +418,13 @@ impl DepthBlitter {
                 conservative: false,
             },
             vertex: wgpu::VertexState {
-                module: &resolve_module,
+                module: &module,
                 entry_point: Some("vs_main"),
                 compilation_options: Default::default(),
                 buffers: &[],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &resolve_module,
+                module: &module,
                 entry_point: Some("fs_main"),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
@@ -482,6 +439,69 @@ impl DepthBlitter {
             cache: None,
         });
 
+        Self {
+            group_layout,
+            pipeline,
+        }
+    }
+}
+
+pub struct DepthBlitter {
+    /// Resolves multisampled depth textures.
+    resolve: DepthResolve,
+    /// Resolves single sampled depth textures.
+    single_resolve: DepthResolve,
+    blit_group_layout: wgpu::BindGroupLayout,
+    blit_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+}
+
+impl DepthBlitter {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let blit_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let blit_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&blit_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let blit_shader = include_wesl!("depth_blit");
+        let blit_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("depth blit"),
+            source: wgpu::ShaderSource::Wgsl(blit_shader.into()),
+        });
+
         let blit_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("depth blit pipeline"),
             layout: Some(&blit_layout),
@@ -528,8 +548,8 @@ impl DepthBlitter {
         });
 
         Self {
-            resolve_group_layout,
-            resolve_pipeline,
+            resolve: DepthResolve::new(device, true),
+            single_resolve: DepthResolve::new(device, false),
             blit_group_layout,
             blit_pipeline,
             sampler,
@@ -563,6 +583,12 @@ impl DepthBlitter {
             view_formats: &[],
         });
 
+        let resolve = if texture.texture().sample_count() > 1 {
+            &self.resolve
+        } else {
+            &self.single_resolve
+        };
+
         let resolved_view = resolved.create_view(&Default::default());
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("depth resolve pass"),
@@ -591,7 +617,7 @@ impl DepthBlitter {
 
         let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
-            layout: &self.resolve_group_layout,
+            layout: &resolve.group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -608,7 +634,7 @@ impl DepthBlitter {
             ],
         });
 
-        pass.set_pipeline(&self.resolve_pipeline);
+        pass.set_pipeline(&resolve.pipeline);
         pass.set_bind_group(0, &group, &[]);
         pass.draw(0..4, 0..1);
 
@@ -4,32 +4,88 @@ mod alloc;
 mod blit;
 mod render;
 
-use std::sync::Arc;
+pub mod replay;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use flume::{Receiver, Sender};
-use lazuli::modules::render::{Action, RenderModule};
+use lazuli::modules::render::{Action, RenderModule, capture};
 use lazuli::system::gx::{EFB_HEIGHT, EFB_WIDTH};
 
 use crate::blit::XfbBlitter;
 use crate::render::Renderer as RendererInner;
 
+/// Writer of the capture the renderer is currently teeing actions into, if any.
+type Capture = Arc<Mutex<Option<capture::Writer<BufWriter<File>>>>>;
+
 #[expect(clippy::needless_pass_by_value, reason = "makes it clearer")]
-fn worker(mut renderer: RendererInner, receiver: Receiver<Action>) {
+fn worker(mut renderer: RendererInner, receiver: Receiver<Action>, capture: Capture) {
+    profiling::register_thread!();
+
     while let Ok(action) = receiver.recv() {
+        let mut capture = capture.lock().unwrap();
+        // flush on frame boundaries so the capture holds whole frames even if never stopped
+        let frame_end = matches!(action, Action::XfbCopy { .. } | Action::PresentXfb { .. });
+        if let Some(writer) = capture.as_mut()
+            && let Err(e) = writer
+                .write(&action)
+                .and_then(|()| if frame_end { writer.flush() } else { Ok(()) })
+        {
+            tracing::error!("failed to capture action, stopping capture: {e}");
+            *capture = None;
+        }
+
+        std::mem::drop(capture);
         renderer.exec(action);
     }
 }
 
+/// Returns the descriptor of the device the renderer needs on the given adapter. Optional
+/// features, like timestamp queries for GPU timing, are requested only if the adapter has them.
+pub fn device_descriptor(adapter: &wgpu::Adapter) -> wgpu::DeviceDescriptor<'static> {
+    let mut required_features = wgpu::Features::empty();
+    required_features |= wgpu::Features::DUAL_SOURCE_BLENDING;
+    required_features |= wgpu::Features::FLOAT32_FILTERABLE;
+    required_features |= wgpu::Features::PUSH_CONSTANTS;
+    required_features |= adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+
+    let mut required_limits = wgpu::Limits::defaults();
+    required_limits.max_texture_dimension_2d = 8192;
+    required_limits.max_push_constant_size = 64 + 32;
+
+    wgpu::DeviceDescriptor {
+        label: Some("lazuli wgpu device"),
+        required_features,
+        required_limits,
+        ..Default::default()
+    }
+}
+
 pub struct Stats {
     pub counters: wgpu::InternalCounters,
     pub alloc: Option<wgpu::AllocatorReport>,
 }
 
+/// Statistics of the last frame the renderer finished, i.e. of the work up to an XFB copy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// Time the GPU spent in the render passes of the frame. Only available if the device supports
+    /// timestamp queries, and read back asynchronously - so it lags a few frames behind.
+    pub gpu_time: Option<Duration>,
+}
+
 struct Inner {
     device: wgpu::Device,
     shared: Arc<render::Shared>,
     blitter: XfbBlitter,
+    capture: Capture,
 }
 
 /// A WGPU based renderer implementation.
@@ -49,9 +105,11 @@ impl Renderer {
         const CAPACITY: usize = 1024 * 1024 / size_of::<Action>();
         let (sender, receiver) = flume::bounded(CAPACITY);
 
+        let capture = Capture::default();
+        let worker_capture = capture.clone();
         std::thread::Builder::new()
             .name("lazuli wgpu renderer".into())
-            .spawn(move || worker(renderer, receiver))
+            .spawn(move || worker(renderer, receiver, worker_capture))
             .unwrap();
 
         Self {
@@ -59,11 +117,29 @@ impl Renderer {
                 device,
                 shared,
                 blitter,
+                capture,
             }),
             sender,
         }
     }
 
+    /// Starts writing every action the renderer receives into a capture at the given path,
+    /// replacing the current capture, if any.
+    pub fn start_capture(&self, path: &Path) -> std::io::Result<()> {
+        let writer = capture::Writer::new(BufWriter::new(File::create(path)?))?;
+        *self.inner.capture.lock().unwrap() = Some(writer);
+
+        Ok(())
+    }
+
+    /// Stops the current capture, if any.
+    pub fn stop_capture(&self) -> std::io::Result<()> {
+        match self.inner.capture.lock().unwrap().take() {
+            Some(mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
     pub fn render(&self, pass: &mut wgpu::RenderPass<'_>) {
         let xfb = self.inner.shared.xfb.lock().unwrap();
         self.inner.blitter.blit_to_target(
@@ -79,6 +155,13 @@ impl Renderer {
         );
     }
 
+    /// Sets the amount of samples per pixel of the EFB. See [`Action::SetMsaa`].
+    pub fn set_msaa(&self, samples: u32) {
+        self.sender
+            .send(Action::SetMsaa(samples))
+            .expect("rendering thread is alive");
+    }
+
     pub fn rendered_anything(&self) -> bool {
         self.inner
             .shared
@@ -91,6 +174,11 @@ impl Renderer {
         let alloc = self.inner.device.generate_allocator_report();
         Box::new(Stats { counters, alloc })
     }
+
+    /// Returns the statistics of the last finished frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        *self.inner.shared.frame_stats.lock().unwrap()
+    }
 }
 
 impl RenderModule for Renderer {
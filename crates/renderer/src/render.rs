@@ -3,20 +3,19 @@ mod framebuffer;
 mod pipeline;
 mod sampler;
 mod texture;
+mod timing;
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
-use glam::{Mat4, Vec2};
+use glam::{Mat4, Vec2, Vec4Swizzles};
 use lazuli::modules::render::{
-    Action, Clut, ClutAddress, Sampler, Scaling, TexEnvConfig, TexGenConfig, Texture, TextureId,
-    Viewport, oneshot,
+    Action, BoundingBox, Clut, ClutAddress, FrameSkip, LinePoint, Sampler, Scaling, TexEnvConfig,
+    TexGenConfig, Texture, TextureId, Viewport, oneshot,
 };
 use lazuli::system::gx::color::{Rgba, Rgba8};
-use lazuli::system::gx::pix::{
-    self, BlendMode, CompareMode, ConstantAlpha, DepthMode, DstBlendFactor, SrcBlendFactor,
-};
-use lazuli::system::gx::tev::AlphaFunction;
+use lazuli::system::gx::pix::{self, BlendMode, CompareMode, ConstantAlpha, DepthMode};
+use lazuli::system::gx::tev::{AlphaFunction, Fog, FogProjection};
 use lazuli::system::gx::tex::ClutFormat;
 use lazuli::system::gx::xform::{ChannelControl, Light};
 use lazuli::system::gx::{
@@ -27,15 +26,23 @@ use schnellru::{ByLength, LruMap};
 use seq_macro::seq;
 use zerocopy::IntoBytes;
 
+use crate::FrameStats;
 use crate::alloc::Allocator;
 use crate::blit::{ColorBlitter, DepthBlitter};
 use crate::render::framebuffer::Framebuffer;
 use crate::render::pipeline::TexGenStageSettings;
 use crate::render::texture::TextureSettings;
+use crate::render::timing::FrameTimer;
+
+/// Indices of the two triangles of a quad expanded from a line or a point, relative to its first
+/// corner. Corners of lines are ordered as start and end, each with its negative side first, and
+/// corners of points as top left, top right, bottom left and bottom right.
+const EXPANDED_QUAD: [u32; 6] = [0, 1, 3, 0, 3, 2];
 
 pub struct Shared {
     pub xfb: Mutex<wgpu::TextureView>,
     pub rendered_anything: AtomicBool,
+    pub frame_stats: Mutex<FrameStats>,
 }
 
 struct Allocators {
@@ -83,6 +90,7 @@ pub struct Renderer {
     depth_blitter: DepthBlitter,
     color_copy_buffer: wgpu::Buffer,
     depth_copy_buffer: wgpu::Buffer,
+    timer: Option<FrameTimer>,
 
     // caches
     pipeline_cache: pipeline::Cache,
@@ -94,6 +102,8 @@ pub struct Renderer {
     viewport: Viewport,
     clear_color: wgpu::Color,
     clear_depth: f32,
+    bounding_box: BoundingBox,
+    frame_skip: FrameSkip,
     current_config: data::Config,
     current_config_dirty: bool,
 
@@ -102,6 +112,8 @@ pub struct Renderer {
     matrices: Vec<Mat4>,
     configs: Vec<data::Config>,
 
+    /// Statistics of the current frame.
+    frame: FrameStats,
     actions: u64,
 }
 
@@ -120,24 +132,24 @@ fn set_channel(channel: &mut data::Channel, control: ChannelControl) {
 
 impl Renderer {
     pub fn new(device: wgpu::Device, queue: wgpu::Queue) -> (Self, Arc<Shared>) {
-        let framebuffer = Framebuffer::new(&device);
+        let framebuffer = Framebuffer::new(&device, framebuffer::DEFAULT_SAMPLES);
         let allocators = Allocators {
             index: Allocator::new(wgpu::BufferUsages::INDEX),
             storage: Allocator::new(wgpu::BufferUsages::STORAGE),
         };
 
-        let pipeline_cache = pipeline::Cache::new(&device);
+        let pipeline_cache = pipeline::Cache::new(&device, framebuffer.samples());
         let texture_cache = texture::Cache::default();
         let sampler_cache = sampler::Cache::default();
 
-        let color = framebuffer.color();
-        let multisampled_color = framebuffer.multisampled_color();
+        let (color, resolve_color) = framebuffer.color_attachment();
         let depth = framebuffer.depth();
         let external = framebuffer.external();
 
         let shared = Arc::new(Shared {
             xfb: Mutex::new(external.clone()),
             rendered_anything: AtomicBool::new(false),
+            frame_stats: Mutex::new(FrameStats::default()),
         });
 
         let color_blitter = ColorBlitter::new(&device);
@@ -157,15 +169,16 @@ impl Renderer {
             mapped_at_creation: false,
         });
 
+        let mut timer = FrameTimer::new(&device, &queue);
         let transfer_encoder = device.create_command_encoder(&Default::default());
         let mut render_encoder = device.create_command_encoder(&Default::default());
         let pass = render_encoder
             .begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("lazuli render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: multisampled_color,
+                    view: color,
                     depth_slice: None,
-                    resolve_target: Some(color),
+                    resolve_target: resolve_color,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                         store: wgpu::StoreOp::Store,
@@ -179,7 +192,7 @@ impl Renderer {
                     }),
                     stencil_ops: None,
                 }),
-                timestamp_writes: None,
+                timestamp_writes: timer.as_mut().and_then(FrameTimer::pass_writes),
                 occlusion_query_set: None,
             })
             .forget_lifetime();
@@ -199,6 +212,7 @@ impl Renderer {
             tex_slots: Default::default(),
             color_copy_buffer,
             depth_copy_buffer,
+            timer,
 
             pipeline_cache,
             texture_cache,
@@ -211,6 +225,8 @@ impl Renderer {
             viewport: Default::default(),
             clear_color: wgpu::Color::BLACK,
             clear_depth: 1.0,
+            bounding_box: BoundingBox::default(),
+            frame_skip: FrameSkip::None,
             current_config: Default::default(),
             current_config_dirty: true,
 
@@ -219,6 +235,7 @@ impl Renderer {
             configs: Vec::new(),
             matrices: Vec::new(),
 
+            frame: FrameStats::default(),
             actions: 0,
         };
 
@@ -227,6 +244,7 @@ impl Renderer {
     }
 
     pub fn exec(&mut self, action: Action) {
+        profiling::scope!("render action");
         match action {
             Action::SetFramebufferFormat(fmt) => self.set_framebuffer_format(fmt),
             Action::SetViewport(viewport) => self.set_viewport(viewport),
@@ -236,6 +254,9 @@ impl Renderer {
             Action::SetBlendMode(mode) => self.set_blend_mode(mode),
             Action::SetDepthMode(mode) => self.set_depth_mode(mode),
             Action::SetAlphaFunction(func) => self.set_alpha_function(func),
+            Action::SetEarlyDepth(early) => self.set_early_depth(early),
+            Action::SetLinePoint(line_point) => self.set_line_point(line_point),
+            Action::SetFog(fog) => self.set_fog(fog),
             Action::SetConstantAlpha(mode) => self.set_constant_alpha_mode(mode),
             Action::SetProjectionMatrix(mat) => self.set_projection_mat(mat.value()),
             Action::SetTexEnvConfig(config) => self.set_texenv_config(config),
@@ -250,15 +271,19 @@ impl Renderer {
                 clut_addr,
                 clut_fmt,
             } => self.set_texture_slot(slot, texture_id, sampler, scaling, clut_addr, clut_fmt),
-            Action::Draw(topology, vertices) => match topology {
-                Topology::QuadList => self.draw_quad_list(&vertices),
-                Topology::TriangleList => self.draw_triangle_list(&vertices),
-                Topology::TriangleStrip => self.draw_triangle_strip(&vertices),
-                Topology::TriangleFan => self.draw_triangle_fan(&vertices),
-                Topology::LineList => tracing::warn!("ignored line list primitive"),
-                Topology::LineStrip => tracing::warn!("ignored line strip primitive"),
-                Topology::PointList => tracing::warn!("ignored point list primitive"),
-            },
+            Action::Draw(..) if self.frame_skip == FrameSkip::Draws => (),
+            Action::Draw(topology, vertices) => {
+                self.grow_bounding_box(&vertices);
+                match topology {
+                    Topology::QuadList => self.draw_quad_list(&vertices),
+                    Topology::TriangleList => self.draw_triangle_list(&vertices),
+                    Topology::TriangleStrip => self.draw_triangle_strip(&vertices),
+                    Topology::TriangleFan => self.draw_triangle_fan(&vertices),
+                    Topology::LineList => self.draw_line_list(&vertices),
+                    Topology::LineStrip => self.draw_line_strip(&vertices),
+                    Topology::PointList => self.draw_point_list(&vertices),
+                }
+            }
             Action::SetAmbient(idx, color) => self.set_ambient(idx, color.into()),
             Action::SetMaterial(idx, color) => self.set_material(idx, color.into()),
             Action::SetColorChannel(idx, control) => self.set_color_channel(idx, control),
@@ -284,10 +309,28 @@ impl Renderer {
             } => {
                 self.depth_copy(x, y, width, height, half, clear, response);
             }
+            Action::SetBoundingBox { vertical, min, max } => {
+                self.set_bounding_box(vertical, min, max)
+            }
+            Action::GetBoundingBox { response } => _ = response.send(self.bounding_box),
             Action::XfbCopy { clear } => {
                 self.debug("XFB copy requested");
                 self.next_pass(clear, true);
             }
+            Action::SetMsaa(samples) => self.set_msaa(samples),
+            Action::PresentXfb { .. } if self.frame_skip != FrameSkip::None => (),
+            Action::PresentXfb {
+                width,
+                height,
+                data,
+            } => {
+                self.present_xfb(width, height, &data);
+            }
+            Action::Reset => self.clear(),
+            Action::SetFrameSkip(skip) => {
+                self.debug(format!("frame skip: {skip:?}"));
+                self.frame_skip = skip;
+            }
         }
 
         self.actions += 1;
@@ -307,12 +350,16 @@ impl Renderer {
             position: vertex.position,
             config_idx: self.configs.len() as u32 - 1,
             normal: vertex.normal,
-            _pad0: 0,
+            expand: data::EXPAND_NONE,
+            binormal: vertex.binormal,
+            _pad1: 0,
+            tangent: vertex.tangent,
+            _pad2: 0,
 
             position_mat: get_matrix(vertex.pos_norm_matrix).unwrap(),
             normal_mat: get_matrix(vertex.pos_norm_matrix.normal()).unwrap(),
-            _pad1: 0,
-            _pad2: 0,
+            other: 0,
+            _pad0: 0,
 
             chan0: vertex.chan0,
             chan1: vertex.chan1,
@@ -343,6 +390,23 @@ impl Renderer {
         }
     }
 
+    pub fn set_msaa(&mut self, samples: u32) {
+        // only 1 and 4 samples are guaranteed to be supported by every adapter
+        let samples = if samples <= 1 { 1 } else { 4 };
+        if samples == self.framebuffer.samples() {
+            return;
+        }
+
+        self.debug(format!("MSAA changed to {samples} samples"));
+
+        // pending draws must be recorded with pipelines matching the targets of the current pass,
+        // which keeps the old targets alive until it is finished
+        self.flush(format_args!("MSAA changed to {samples} samples"));
+        self.framebuffer.set_samples(&self.device, samples);
+        self.pipeline_cache.set_samples(samples);
+        self.next_pass(false, false);
+    }
+
     pub fn set_viewport(&mut self, viewport: Viewport) {
         self.debug(format!("set viewport to {viewport:?}"));
         self.current_pass.set_viewport(
@@ -354,6 +418,14 @@ impl Renderer {
             viewport.far_depth.clamp(0.0, 1.0),
         );
 
+        // line widths, point sizes and fog range adjustment are given in pixels, so the shader
+        // needs the size of the viewport
+        let viewport_size = Vec2::new(viewport.width.abs(), viewport.height.abs());
+        if self.current_config.viewport_size != viewport_size {
+            self.current_config.viewport_size = viewport_size;
+            self.current_config_dirty = true;
+        }
+
         self.viewport = viewport;
     }
 
@@ -375,46 +447,14 @@ impl Renderer {
     }
 
     pub fn set_blend_mode(&mut self, mode: BlendMode) {
-        let src = match mode.src_factor() {
-            SrcBlendFactor::Zero => wgpu::BlendFactor::Zero,
-            SrcBlendFactor::One => wgpu::BlendFactor::One,
-            SrcBlendFactor::DstColor => wgpu::BlendFactor::Dst,
-            SrcBlendFactor::InverseDstColor => wgpu::BlendFactor::OneMinusDst,
-            SrcBlendFactor::SrcAlpha => wgpu::BlendFactor::Src1Alpha,
-            SrcBlendFactor::InverseSrcAlpha => wgpu::BlendFactor::OneMinusSrc1Alpha,
-            SrcBlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
-            SrcBlendFactor::InverseDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
-        };
-
-        let dst = match mode.dst_factor() {
-            DstBlendFactor::Zero => wgpu::BlendFactor::Zero,
-            DstBlendFactor::One => wgpu::BlendFactor::One,
-            DstBlendFactor::SrcColor => wgpu::BlendFactor::Src1,
-            DstBlendFactor::InverseSrcColor => wgpu::BlendFactor::OneMinusSrc1,
-            DstBlendFactor::SrcAlpha => wgpu::BlendFactor::Src1Alpha,
-            DstBlendFactor::InverseSrcAlpha => wgpu::BlendFactor::OneMinusSrc1Alpha,
-            DstBlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
-            DstBlendFactor::InverseDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
-        };
+        let (blend, source) = pipeline::BlendSettings::new(mode);
 
-        let op = if mode.blend_subtract() {
-            wgpu::BlendOperation::Subtract
-        } else {
-            wgpu::BlendOperation::Add
-        };
-
-        let blend = pipeline::BlendSettings {
-            enabled: mode.enable(),
-            src,
-            dst,
-            op,
-            color_write: mode.color_mask(),
-            alpha_write: mode.alpha_mask(),
-        };
-
-        if self.pipeline_settings.blend != blend {
-            self.flush(format_args!("set blend settings to {blend:?}"));
+        if self.pipeline_settings.blend != blend || self.pipeline_settings.shader.source != source {
+            self.flush(format_args!(
+                "set blend settings to {blend:?} with source transform {source:?}"
+            ));
             self.pipeline_settings.blend = blend;
+            self.pipeline_settings.shader.source = source;
         }
     }
 
@@ -457,6 +497,119 @@ impl Renderer {
         self.current_config_dirty = true;
     }
 
+    pub fn set_early_depth(&mut self, early: bool) {
+        if self.pipeline_settings.shader.texenv.early_depth != early {
+            self.flush(format_args!("set early depth to {early}"));
+            self.pipeline_settings.shader.texenv.early_depth = early;
+        }
+    }
+
+    pub fn set_bounding_box(&mut self, vertical: bool, min: u16, max: u16) {
+        self.debug(format!(
+            "set bounding box (vertical: {vertical}) to {min}..={max}"
+        ));
+        if vertical {
+            self.bounding_box.top = min;
+            self.bounding_box.bottom = max;
+        } else {
+            self.bounding_box.left = min;
+            self.bounding_box.right = max;
+        }
+    }
+
+    /// Grows the bounding box to contain the vertices of the given stream, in screen space.
+    ///
+    /// This is an approximation of what the hardware does, which is tracking the extents of the
+    /// pixels that are actually drawn: vertices behind the camera are ignored and the box is not
+    /// affected by scissoring, depth or alpha tests.
+    fn grow_bounding_box(&mut self, stream: &VertexStream) {
+        let viewport = self.viewport;
+        let projection = self.current_config.projection_mat;
+        let get_matrix = |id| {
+            stream
+                .matrices()
+                .iter()
+                .find_map(|(i, m)| (*i == id).then_some(*m))
+        };
+
+        let max_x = (viewport.top_left_x + viewport.width).clamp(0.0, EFB_WIDTH as f32 - 1.0);
+        let max_y = (viewport.top_left_y + viewport.height).clamp(0.0, EFB_HEIGHT as f32 - 1.0);
+        let min_x = viewport.top_left_x.clamp(0.0, max_x);
+        let min_y = viewport.top_left_y.clamp(0.0, max_y);
+
+        let bbox = &mut self.bounding_box;
+        for vertex in stream.vertices() {
+            let Some(matrix) = get_matrix(vertex.pos_norm_matrix) else {
+                continue;
+            };
+
+            let clip = projection * matrix * vertex.position.extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+
+            let ndc = clip.xy() / clip.w;
+            let x = viewport.top_left_x + (ndc.x + 1.0) / 2.0 * viewport.width;
+            let y = viewport.top_left_y + (1.0 - ndc.y) / 2.0 * viewport.height;
+            let x = x.clamp(min_x, max_x) as u16;
+            let y = y.clamp(min_y, max_y) as u16;
+
+            bbox.left = bbox.left.min(x);
+            bbox.right = bbox.right.max(x);
+            bbox.top = bbox.top.min(y);
+            bbox.bottom = bbox.bottom.max(y);
+        }
+    }
+
+    pub fn set_line_point(&mut self, line_point: LinePoint) {
+        let size = line_point.size;
+        let width = size.line_width_pixels();
+        let vertical_width = if size.half_aspect() {
+            width / 2.0
+        } else {
+            width
+        };
+
+        self.current_config.line_width = Vec2::new(width, vertical_width);
+        self.current_config.point_size = size.point_size_pixels();
+        self.current_config.line_tex_offset = size.line_tex_offset().value();
+        self.current_config.point_tex_offset = size.point_tex_offset().value();
+        self.current_config.tex_offset_mask =
+            line_point.line_tex_offsets as u32 | (line_point.point_tex_offsets as u32) << 8;
+        self.current_config_dirty = true;
+    }
+
+    pub fn set_fog(&mut self, fog: Fog) {
+        let kind = fog.mode.kind();
+        if self.pipeline_settings.shader.texenv.fog != kind {
+            self.flush(format_args!("set fog to {kind:?}"));
+            self.pipeline_settings.shader.texenv.fog = kind;
+        }
+
+        let config = &mut self.current_config;
+        config.fog_color = Rgba::new(
+            fog.color.r() as f32 / 255.0,
+            fog.color.g() as f32 / 255.0,
+            fog.color.b() as f32 / 255.0,
+            1.0,
+        );
+        config.fog_scale = fog.scale.value();
+        config.fog_offset = fog.mode.offset();
+        config.fog_b_magnitude = fog.b_magnitude;
+        config.fog_b_shift = fog.b_shift;
+        config.fog_range_center = fog.range_center();
+        config.fog_range_factors = fog.range_factors();
+        config.fog_flags = 0;
+        if fog.mode.projection() == FogProjection::Orthographic {
+            config.fog_flags |= data::FOG_ORTHOGRAPHIC;
+        }
+        if fog.range.enabled() {
+            config.fog_flags |= data::FOG_RANGE;
+        }
+
+        self.current_config_dirty = true;
+    }
+
     pub fn set_constant_alpha_mode(&mut self, mode: ConstantAlpha) {
         self.debug(format!("set constant alpha mode to {mode:?}"));
         self.current_config.constant_alpha = if mode.enabled() {
@@ -527,6 +680,7 @@ impl Renderer {
             .iter()
             .map(|s| TexGenStageSettings {
                 base: s.base.clone(),
+                dual: s.dual,
                 normalize: s.normalize,
             })
             .collect();
@@ -544,6 +698,7 @@ impl Renderer {
     }
 
     pub fn load_texture(&mut self, id: TextureId, texture: Texture) {
+        profiling::scope!("upload texture");
         if self.texture_cache.update_raw(id, texture) {
             // HACK: avoid keeping old textures alive with a dependent bind group
             self.textures_group_cache.clear();
@@ -686,6 +841,77 @@ impl Renderer {
         }
     }
 
+    /// Draws lines or points with `draw`. Lines and points are expanded into quads whose winding
+    /// depends on their direction, so culling is disabled for them - which matches the hardware,
+    /// as it never culls them.
+    fn draw_expanded(
+        &mut self,
+        stream: &VertexStream,
+        draw: impl FnOnce(&mut Self, &[(MatrixId, data::MatrixIdx)]),
+    ) {
+        if stream.vertices().is_empty() {
+            return;
+        }
+
+        let culling = self.pipeline_settings.culling;
+        self.set_culling_mode(CullingMode::None);
+
+        self.flush_config();
+        let matrices = self.create_matrix_indices(stream.matrices());
+        draw(self, &matrices);
+
+        self.set_culling_mode(culling);
+    }
+
+    /// Inserts the four corners of the line from `a` to `b`.
+    fn insert_line(&mut self, a: &Vertex, b: &Vertex, matrices: &[(MatrixId, data::MatrixIdx)]) {
+        let base = self.vertices.len() as u32;
+        let corners = [
+            (a, 0, base + 2),
+            (a, 1, base + 2),
+            (b, 0, base),
+            (b, 1, base),
+        ];
+        for (vertex, side, other) in corners {
+            let idx = self.insert_vertex(vertex, matrices);
+            let inserted = &mut self.vertices[idx as usize];
+            inserted.expand = data::EXPAND_LINE | side << 2;
+            inserted.other = other;
+        }
+
+        self.indices.extend(EXPANDED_QUAD.map(|i| base + i));
+    }
+
+    pub fn draw_line_list(&mut self, stream: &VertexStream) {
+        self.draw_expanded(stream, |this, matrices| {
+            for [a, b] in stream.vertices().iter().array_chunks::<2>() {
+                this.insert_line(a, b, matrices);
+            }
+        });
+    }
+
+    pub fn draw_line_strip(&mut self, stream: &VertexStream) {
+        self.draw_expanded(stream, |this, matrices| {
+            for pair in stream.vertices().windows(2) {
+                this.insert_line(&pair[0], &pair[1], matrices);
+            }
+        });
+    }
+
+    pub fn draw_point_list(&mut self, stream: &VertexStream) {
+        self.draw_expanded(stream, |this, matrices| {
+            for vertex in stream.vertices() {
+                let base = this.vertices.len() as u32;
+                for corner in 0..4 {
+                    let idx = this.insert_vertex(vertex, matrices);
+                    this.vertices[idx as usize].expand = data::EXPAND_POINT | corner << 2;
+                }
+
+                this.indices.extend(EXPANDED_QUAD.map(|i| base + i));
+            }
+        });
+    }
+
     fn reset(&mut self) {
         self.indices.clear();
         self.vertices.clear();
@@ -828,6 +1054,9 @@ impl Renderer {
         self.current_pass
             .draw_indexed(0..self.indices.len() as u32, 0, 0..1);
 
+        self.frame.draw_calls += 1;
+        self.frame.triangles += self.indices.len() as u64 / 3;
+
         self.reset();
     }
 
@@ -836,8 +1065,8 @@ impl Renderer {
         self.flush(format_args!("finishing pass"));
 
         let color = self.framebuffer.color();
+        let (color_target, resolve_color) = self.framebuffer.color_attachment();
         let depth = self.framebuffer.depth();
-        let multisampled_color = self.framebuffer.multisampled_color();
 
         let color_op = if clear && self.pipeline_settings.blend.color_write {
             if !self.pipeline_settings.blend.alpha_write {
@@ -866,15 +1095,21 @@ impl Renderer {
             wgpu::LoadOp::Load
         };
 
+        // passes begun after an XFB copy belong to the next frame
+        let timed_passes = match &mut self.timer {
+            Some(timer) if copy_to_xfb => timer.end_frame(),
+            _ => 0,
+        };
+
         let transfer_encoder = self.device.create_command_encoder(&Default::default());
         let mut render_encoder = self.device.create_command_encoder(&Default::default());
         let mut pass = render_encoder
             .begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("main render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: multisampled_color,
+                    view: color_target,
                     depth_slice: None,
-                    resolve_target: Some(color),
+                    resolve_target: resolve_color,
                     ops: wgpu::Operations {
                         load: color_op,
                         store: wgpu::StoreOp::Store,
@@ -888,7 +1123,7 @@ impl Renderer {
                     }),
                     stencil_ops: None,
                 }),
-                timestamp_writes: None,
+                timestamp_writes: self.timer.as_mut().and_then(FrameTimer::pass_writes),
                 occlusion_query_set: None,
             })
             .forget_lifetime();
@@ -910,7 +1145,7 @@ impl Renderer {
 
         std::mem::drop(previous_pass);
 
-        if copy_to_xfb {
+        if copy_to_xfb && self.frame_skip == FrameSkip::None {
             let external = self.framebuffer.external();
             prev_render_encoder.copy_texture_to_texture(
                 wgpu::TexelCopyTextureInfoBase {
@@ -929,6 +1164,20 @@ impl Renderer {
             );
         }
 
+        // skipped frames still end here, so that their statistics don't pile up on the next one
+        if copy_to_xfb {
+            let gpu_time = self
+                .timer
+                .as_mut()
+                .and_then(|timer| timer.resolve(&mut prev_render_encoder, timed_passes));
+
+            let stats = FrameStats {
+                gpu_time,
+                ..std::mem::take(&mut self.frame)
+            };
+            *self.shared.frame_stats.lock().unwrap() = stats;
+        }
+
         let transfer_cmds = prev_transfer_encoder.finish();
         let render_cmds = prev_render_encoder.finish();
 
@@ -1180,7 +1429,9 @@ impl Renderer {
 
         self.next_pass(clear, false);
         let data = self.get_color_data(x, y, width, height, half);
-        response.send(data).unwrap();
+
+        // the requester might not care about the response (e.g. when replaying a capture)
+        _ = response.send(data);
     }
 
     pub fn depth_copy(
@@ -1199,6 +1450,359 @@ impl Renderer {
 
         self.next_pass(clear, false);
         let data = self.get_depth_data(x, y, width, height, half);
-        response.send(data).unwrap();
+
+        // the requester might not care about the response (e.g. when replaying a capture)
+        _ = response.send(data);
+    }
+
+    /// Drops all emulated GPU state and caches, and clears both the EFB and the XFB.
+    pub fn clear(&mut self) {
+        self.debug("clearing renderer");
+
+        // pending draws belong to the system being reset, so discard them
+        self.reset();
+
+        self.pipeline_settings = Default::default();
+        self.tex_slots = Default::default();
+        self.texture_cache = texture::Cache::default();
+        self.textures_group_cache.clear();
+
+        self.viewport = Default::default();
+        self.clear_color = wgpu::Color::BLACK;
+        self.clear_depth = 1.0;
+        self.bounding_box = BoundingBox::default();
+        self.frame_skip = FrameSkip::None;
+        self.current_config = Default::default();
+        self.current_config_dirty = true;
+
+        self.next_pass(true, false);
+        let black = vec![Rgba8::default(); (EFB_WIDTH * EFB_HEIGHT) as usize];
+        self.present_xfb(EFB_WIDTH as u16, EFB_HEIGHT as u16, &black);
+    }
+
+    /// Uploads an XFB drawn by the CPU into the external framebuffer.
+    pub fn present_xfb(&mut self, width: u16, height: u16, data: &[Rgba8]) {
+        self.debug(format!("presenting CPU drawn XFB [{width}x{height}]"));
+
+        // the external framebuffer is EFB sized, so crop (or pad) the XFB to fit it
+        let mut pixels = vec![Rgba8::default(); (EFB_WIDTH * EFB_HEIGHT) as usize];
+        for (src, dst) in data
+            .chunks_exact(width as usize)
+            .zip(pixels.chunks_exact_mut(EFB_WIDTH as usize))
+        {
+            let len = src.len().min(dst.len());
+            dst[..len].copy_from_slice(&src[..len]);
+        }
+
+        let external = self.framebuffer.external();
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: external.texture(),
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels.as_bytes(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(EFB_WIDTH as u32 * 4),
+                rows_per_image: None,
+            },
+            external.texture().size(),
+        );
+
+        self.shared.rendered_anything.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+    use lazuli::system::gx::color::Rgba16;
+    use lazuli::system::gx::tev::{AlphaCompare, AlphaLogic};
+    use lazuli::system::gx::tex::{Format, MipmapData};
+
+    use super::*;
+
+    /// Creates a renderer on the default adapter, or returns `None` if there is no adapter to
+    /// run on, in which case the calling test does nothing.
+    ///
+    /// Validation errors are not captured, so they panic.
+    fn renderer() -> Option<Renderer> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&crate::device_descriptor(&adapter))).ok()?;
+
+        let (mut renderer, _) = Renderer::new(device, queue);
+
+        // every texture slot is bound when drawing, even if unused
+        renderer.load_texture(
+            TextureId::default(),
+            Texture {
+                width: 1,
+                height: 1,
+                format: Format::Rgba8,
+                data: MipmapData::Direct(vec![vec![Rgba8::default()]]),
+            },
+        );
+        renderer.set_projection_mat(Mat4::IDENTITY);
+        renderer.set_depth_mode(DepthMode::default());
+
+        // the default alpha function discards everything
+        renderer.set_alpha_function(
+            AlphaFunction::default().with_comparison([AlphaCompare::Always; 2]),
+        );
+
+        Some(renderer)
+    }
+
+    /// Draws a quad covering the whole EFB in the given color. The TEV has no stages, so it
+    /// outputs the initial value of R3 - the last constant.
+    fn fill(renderer: &mut Renderer, color: Rgba8) {
+        let mut constants = [Rgba16::default(); 4];
+        constants[3] = Rgba16 {
+            r: color.r as i16,
+            g: color.g as i16,
+            b: color.b as i16,
+            a: color.a as i16,
+        };
+
+        renderer.set_texenv_config(TexEnvConfig {
+            stages: vec![],
+            constants,
+            ..Default::default()
+        });
+
+        let matrix = MatrixId::from_position_idx(0);
+        let vertices = [(-1.0, 1.0), (1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)].map(|(x, y)| Vertex {
+            position: Vec3::new(x, y, -0.5),
+            pos_norm_matrix: matrix,
+            ..Default::default()
+        });
+
+        let stream = VertexStream::new(
+            vertices.into(),
+            vec![(matrix, Mat4::IDENTITY), (matrix.normal(), Mat4::IDENTITY)],
+        );
+        renderer.draw_quad_list(&stream);
+    }
+
+    /// Finishes the current pass and reads back the pixel at the center of the EFB.
+    fn center(renderer: &mut Renderer) -> Rgba8 {
+        renderer.next_pass(false, false);
+        renderer.get_color_data(EFB_WIDTH as u16 / 2, EFB_HEIGHT as u16 / 2, 1, 1, false)[0]
+    }
+
+    const RED: Rgba8 = Rgba8 {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+
+    const BLUE: Rgba8 = Rgba8 {
+        r: 0,
+        g: 0,
+        b: 255,
+        a: 255,
+    };
+
+    /// Color and alpha updates enabled, with blending and logic ops disabled.
+    const WRITE_MASK: u32 = (1 << 3) | (1 << 4);
+
+    fn gray(value: u8) -> Rgba8 {
+        Rgba8 {
+            r: value,
+            g: value,
+            b: value,
+            a: 255,
+        }
+    }
+
+    /// Fills the EFB with `dst`, then draws `src` over it with the given blend mode (with color
+    /// and alpha updates enabled) and reads back the result.
+    fn blend(renderer: &mut Renderer, mode: u32, src: Rgba8, dst: Rgba8) -> Rgba8 {
+        renderer.set_blend_mode(BlendMode::from_bits(WRITE_MASK));
+        fill(renderer, dst);
+        renderer.set_blend_mode(BlendMode::from_bits(mode | WRITE_MASK));
+        fill(renderer, src);
+        center(renderer)
+    }
+
+    fn assert_close(result: Rgba8, expected: Rgba8, context: &str) {
+        let close = |a: u8, b: u8| a.abs_diff(b) <= 1;
+        assert!(
+            close(result.r, expected.r)
+                && close(result.g, expected.g)
+                && close(result.b, expected.b),
+            "{context}: got {result:?}, expected {expected:?}"
+        );
+    }
+
+    fn logic_op(op: u32, s: u8, d: u8) -> u8 {
+        match op {
+            0x0 => 0,
+            0x1 => s & d,
+            0x2 => s & !d,
+            0x3 => s,
+            0x4 => !s & d,
+            0x5 => d,
+            0x6 => s ^ d,
+            0x7 => s | d,
+            0x8 => !(s | d),
+            0x9 => !(s ^ d),
+            0xA => !d,
+            0xB => s | !d,
+            0xC => !s,
+            0xD => !s | d,
+            0xE => !(s & d),
+            0xF => 0xFF,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn logic_ops_match_truth_tables() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+
+        for op in 0..16 {
+            let mode = (1 << 1) | (op << 12);
+            for s in [0x00, 0xFF] {
+                for d in [0x00, 0xFF] {
+                    let result = blend(&mut renderer, mode, gray(s), gray(d));
+                    assert_close(
+                        result,
+                        gray(logic_op(op, s, d)),
+                        &format!("logic op {op:X} with src {s:02X} and dst {d:02X}"),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn subtract_ignores_factors_and_overrides_logic_ops() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+
+        // enable, logic op enable, src factor zero, dst factor zero, subtract, logic op set
+        let mode = 0b11 | (1 << 11) | (0xF << 12);
+        let result = blend(&mut renderer, mode, gray(0x40), gray(0xC0));
+        assert_close(result, gray(0x80), "dst - src");
+        let result = blend(&mut renderer, mode, gray(0xC0), gray(0x40));
+        assert_close(result, gray(0x00), "dst - src, clamped");
+    }
+
+    #[test]
+    fn regular_blending() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+
+        // enable, src factor src alpha (4), dst factor inverse src alpha (5)
+        let mode = 1 | (5 << 5) | (4 << 8);
+        let src = Rgba8 { a: 0x80, ..RED };
+        let result = blend(&mut renderer, mode, src, BLUE);
+        let expected = Rgba8 {
+            r: 0x80,
+            g: 0,
+            b: 0x7F,
+            a: 255,
+        };
+        assert_close(result, expected, "src alpha blending");
+
+        // blending disabled, no logic op: the source is written as is
+        let result = blend(&mut renderer, 0, gray(0x40), gray(0xC0));
+        assert_close(result, gray(0x40), "no blending");
+    }
+
+    #[test]
+    fn alpha_test_discards_in_shader() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+
+        let function = |a, b, logic, refs| {
+            AlphaFunction::default()
+                .with_comparison([a, b])
+                .with_logic(logic)
+                .with_refs(refs)
+        };
+
+        let cases: [(AlphaFunction, fn(u8) -> bool); 4] = [
+            (
+                function(
+                    AlphaCompare::GreaterOrEqual,
+                    AlphaCompare::Never,
+                    AlphaLogic::Or,
+                    [0x80, 0x00],
+                ),
+                |a| a >= 0x80,
+            ),
+            (
+                function(
+                    AlphaCompare::Greater,
+                    AlphaCompare::Less,
+                    AlphaLogic::And,
+                    [0x40, 0xC0],
+                ),
+                |a| a > 0x40 && a < 0xC0,
+            ),
+            (
+                function(
+                    AlphaCompare::Equal,
+                    AlphaCompare::Equal,
+                    AlphaLogic::Xor,
+                    [0x40, 0xC0],
+                ),
+                |a| a == 0x40 || a == 0xC0,
+            ),
+            (
+                function(
+                    AlphaCompare::LessOrEqual,
+                    AlphaCompare::GreaterOrEqual,
+                    AlphaLogic::Xnor,
+                    [0x40, 0xC0],
+                ),
+                |a| a > 0x40 && a < 0xC0,
+            ),
+        ];
+
+        let always = AlphaFunction::default().with_comparison([AlphaCompare::Always; 2]);
+        for (func, expected) in cases {
+            for alpha in [0x00, 0x3F, 0x40, 0x41, 0x7F, 0x80, 0xBF, 0xC0, 0xC1, 0xFF] {
+                renderer.set_alpha_function(always);
+                fill(&mut renderer, BLUE);
+                renderer.set_alpha_function(func);
+                fill(&mut renderer, Rgba8 { a: alpha, ..RED });
+                renderer.set_alpha_function(always);
+
+                let result = center(&mut renderer);
+                let passed = result.r == RED.r && result.b == RED.b;
+                assert_eq!(passed, expected(alpha), "{func:?} with alpha {alpha:02X}");
+            }
+        }
+    }
+
+    #[test]
+    fn msaa_toggles_with_pending_draws() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+
+        for samples in [1, 4, 1] {
+            fill(&mut renderer, RED);
+            renderer.set_msaa(samples);
+            fill(&mut renderer, BLUE);
+            assert_eq!(center(&mut renderer), BLUE, "{samples} samples");
+        }
     }
 }
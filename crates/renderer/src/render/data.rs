@@ -5,18 +5,38 @@ use zerocopy::{Immutable, IntoBytes};
 
 pub type MatrixIdx = u32;
 
+/// The vertex is drawn as is.
+pub const EXPAND_NONE: u32 = 0;
+/// The vertex is a corner of a line, expanded perpendicularly to it. Bit 2 selects the side.
+pub const EXPAND_LINE: u32 = 1;
+/// The vertex is a corner of a point, expanded around it. Bit 2 selects the right side and bit 3
+/// the bottom side.
+pub const EXPAND_POINT: u32 = 2;
+
+/// Fog distance is computed for an orthographic projection rather than a perspective one.
+pub const FOG_ORTHOGRAPHIC: u32 = 1 << 0;
+/// Fog distance is range adjusted.
+pub const FOG_RANGE: u32 = 1 << 1;
+
 #[derive(Debug, Clone, Immutable, IntoBytes, Default)]
 #[repr(C)]
 pub struct Vertex {
     pub position: Vec3,
     pub config_idx: u32,
     pub normal: Vec3,
-    pub _pad0: u32,
+    /// How this vertex is expanded by the vertex shader, see [`EXPAND_LINE`] and
+    /// [`EXPAND_POINT`].
+    pub expand: u32,
+    pub binormal: Vec3,
+    pub _pad1: u32,
+    pub tangent: Vec3,
+    pub _pad2: u32,
 
     pub position_mat: MatrixIdx,
     pub normal_mat: MatrixIdx,
-    pub _pad1: u32,
-    pub _pad2: u32,
+    /// Index of the vertex at the other end of the line, for line corners.
+    pub other: u32,
+    pub _pad0: u32,
 
     pub chan0: Rgba,
     pub chan1: Rgba,
@@ -69,4 +89,27 @@ pub struct Config {
     pub constant_alpha: u32,
     pub alpha_refs: [u32; 2],
     pub _pad0: u32,
+
+    pub viewport_size: Vec2,
+    /// Width of lines when expanded horizontally and vertically, in pixels.
+    pub line_width: Vec2,
+    /// Size of points, in pixels.
+    pub point_size: f32,
+    pub line_tex_offset: f32,
+    pub point_tex_offset: f32,
+    /// Bitmask of the texture coordinates offset in lines (low byte) and points (second byte).
+    pub tex_offset_mask: u32,
+
+    pub fog_color: Rgba,
+    /// The `A` fog parameter.
+    pub fog_scale: f32,
+    /// The `C` fog parameter.
+    pub fog_offset: f32,
+    pub fog_b_magnitude: u32,
+    pub fog_b_shift: u32,
+    /// Horizontal center of the range adjustment, in pixels relative to the center of the EFB.
+    pub fog_range_center: f32,
+    pub fog_range_factors: [f32; 10],
+    /// Bitmask of fog options: [`FOG_ORTHOGRAPHIC`] and [`FOG_RANGE`].
+    pub fog_flags: u32,
 }
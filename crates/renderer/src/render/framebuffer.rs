@@ -1,30 +1,82 @@
 //! Framebuffer (EFB color, EFB depth, XFB).
+//!
+//! The EFB can be multisampled, in which case color is resolved into a single sampled texture at
+//! the end of every render pass. Everything that reads from the EFB (color copies, XFB copies)
+//! reads the resolved texture, while depth copies resolve the depth samples on demand.
+//!
+//! Samples are taken at EFB resolution: the memory cost of the multisampled targets is the sample
+//! count times the size of the EFB. If the EFB is ever rendered at a scaled internal resolution,
+//! the multisampled targets must be scaled along with it, since they have to match the resolved
+//! texture.
 
 use lazuli::system::gx::{EFB_HEIGHT, EFB_WIDTH};
 
+/// Default amount of samples per pixel of the EFB.
+pub const DEFAULT_SAMPLES: u32 = 4;
+
+const SIZE: wgpu::Extent3d = wgpu::Extent3d {
+    width: EFB_WIDTH as u32,
+    height: EFB_HEIGHT as u32,
+    depth_or_array_layers: 1,
+};
+
 pub struct Framebuffer {
+    /// Amount of samples per pixel.
+    samples: u32,
     /// Color component of the EFB.
     color: wgpu::TextureView,
-    /// Multisampled color component of the EFB.
-    multisampled_color: wgpu::TextureView,
+    /// Multisampled color component of the EFB. Only present if `samples > 1`.
+    multisampled_color: Option<wgpu::TextureView>,
     /// Depth component of the EFB.
     depth: wgpu::TextureView,
     /// Represents the external framebuffer.
     external: wgpu::TextureView,
 }
 
-impl Framebuffer {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let size = wgpu::Extent3d {
-            width: EFB_WIDTH as u32,
-            height: EFB_HEIGHT as u32,
-            depth_or_array_layers: 1,
-        };
+fn create_multisampled_color(device: &wgpu::Device, samples: u32) -> Option<wgpu::TextureView> {
+    if samples == 1 {
+        return None;
+    }
+
+    let multisampled_color = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("efb color multisampled"),
+        dimension: wgpu::TextureDimension::D2,
+        size: SIZE,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+        mip_level_count: 1,
+        sample_count: samples,
+    });
 
+    Some(multisampled_color.create_view(&Default::default()))
+}
+
+fn create_depth(device: &wgpu::Device, samples: u32) -> wgpu::TextureView {
+    let depth = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("efb depth"),
+        dimension: wgpu::TextureDimension::D2,
+        size: SIZE,
+        format: wgpu::TextureFormat::Depth32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+        mip_level_count: 1,
+        sample_count: samples,
+    });
+
+    depth.create_view(&Default::default())
+}
+
+impl Framebuffer {
+    pub fn new(device: &wgpu::Device, samples: u32) -> Self {
         let color = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("efb color resolved"),
             dimension: wgpu::TextureDimension::D2,
-            size,
+            size: SIZE,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::RENDER_ATTACHMENT
@@ -34,36 +86,10 @@ impl Framebuffer {
             sample_count: 1,
         });
 
-        let multisampled_color = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("efb color multisampled"),
-            dimension: wgpu::TextureDimension::D2,
-            size,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[],
-            mip_level_count: 1,
-            sample_count: 4,
-        });
-
-        let depth = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("efb depth"),
-            dimension: wgpu::TextureDimension::D2,
-            size,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[],
-            mip_level_count: 1,
-            sample_count: 4,
-        });
-
         let external = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("xfb"),
             dimension: wgpu::TextureDimension::D2,
-            size,
+            size: SIZE,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
@@ -72,11 +98,12 @@ impl Framebuffer {
         });
 
         let color = color.create_view(&Default::default());
-        let multisampled_color = multisampled_color.create_view(&Default::default());
-        let depth = depth.create_view(&Default::default());
+        let multisampled_color = create_multisampled_color(device, samples);
+        let depth = create_depth(device, samples);
         let external = external.create_view(&Default::default());
 
         Self {
+            samples,
             color,
             multisampled_color,
             depth,
@@ -84,6 +111,18 @@ impl Framebuffer {
         }
     }
 
+    /// Recreates the multisampled targets with the given amount of samples. The contents of the
+    /// EFB are discarded.
+    pub fn set_samples(&mut self, device: &wgpu::Device, samples: u32) {
+        self.samples = samples;
+        self.multisampled_color = create_multisampled_color(device, samples);
+        self.depth = create_depth(device, samples);
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
     pub fn external(&self) -> &wgpu::TextureView {
         &self.external
     }
@@ -92,8 +131,12 @@ impl Framebuffer {
         &self.color
     }
 
-    pub fn multisampled_color(&self) -> &wgpu::TextureView {
-        &self.multisampled_color
+    /// Returns the view to render color into and, if multisampled, the view to resolve it to.
+    pub fn color_attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.multisampled_color {
+            Some(multisampled) => (multisampled, Some(&self.color)),
+            None => (&self.color, None),
+        }
     }
 
     pub fn depth(&self) -> &wgpu::TextureView {
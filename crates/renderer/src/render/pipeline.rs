@@ -11,6 +11,7 @@ use rustc_hash::FxHashMap;
 pub use settings::*;
 
 pub struct Cache {
+    samples: u32,
     group0_layout: wgpu::BindGroupLayout,
     group1_layout: wgpu::BindGroupLayout,
     layout: wgpu::PipelineLayout,
@@ -48,6 +49,7 @@ impl Cache {
         device: &wgpu::Device,
         layout: &wgpu::PipelineLayout,
         settings: &Settings,
+        samples: u32,
         id: u32,
     ) -> wgpu::RenderPipeline {
         let depth_stencil = if settings.depth.enabled {
@@ -166,7 +168,7 @@ impl Cache {
                 })],
             }),
             multisample: wgpu::MultisampleState {
-                count: 4,
+                count: samples,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -176,7 +178,7 @@ impl Cache {
         })
     }
 
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, samples: u32) -> Self {
         let storage_buffer = |binding| wgpu::BindGroupLayoutEntry {
             binding,
             visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
@@ -240,6 +242,7 @@ impl Cache {
         });
 
         Self {
+            samples,
             group0_layout,
             group1_layout,
             layout,
@@ -256,6 +259,15 @@ impl Cache {
         &self.group1_layout
     }
 
+    /// Sets the amount of samples of the render target. Cached pipelines are discarded if it
+    /// changes.
+    pub fn set_samples(&mut self, samples: u32) {
+        if self.samples != samples {
+            self.samples = samples;
+            self.cached_pipelines.clear();
+        }
+    }
+
     pub fn get(&mut self, device: &wgpu::Device, settings: &Settings) -> &wgpu::RenderPipeline {
         let len = self.cached_pipelines.len() as u32;
         match self.cached_pipelines.entry(settings.clone()) {
@@ -265,6 +277,7 @@ impl Cache {
                 device,
                 &self.layout,
                 settings,
+                self.samples,
                 len,
             )),
         }
@@ -1,6 +1,7 @@
 use lazuli::modules::render::TexEnvStage;
 use lazuli::system::gx::CullingMode;
-use lazuli::system::gx::tev::{AlphaCompare, AlphaLogic, DepthTexture};
+use lazuli::system::gx::pix::{BlendLogicOp, BlendMode, DstBlendFactor, SrcBlendFactor};
+use lazuli::system::gx::tev::{AlphaCompare, AlphaLogic, DepthTexture, FogKind};
 use lazuli::system::gx::xform::BaseTexGen;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -28,6 +29,116 @@ impl Default for BlendSettings {
     }
 }
 
+/// How the fragment shader transforms the source color before it reaches the blender. Together
+/// with the blend factors, this allows logic ops (which wgpu does not support) to be emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SourceTransform {
+    /// The source color is left as is.
+    #[default]
+    None,
+    /// The source color is inverted, i.e. `1 - src`.
+    Invert,
+    /// The source color is replaced by ones.
+    Ones,
+}
+
+impl BlendSettings {
+    /// Maps a GX blend mode to blend settings and the source transform needed to realize it.
+    ///
+    /// Subtractive blending takes precedence over regular blending, which takes precedence over
+    /// logic ops. Logic ops are emulated with blending, which is exact when every channel is
+    /// either zero or one (e.g. masks or solid selection rectangles) and an approximation
+    /// otherwise.
+    ///
+    /// The blend factors refer to the second blend source (the TEV output) for the source color,
+    /// so that the constant alpha (which replaces the alpha of the first blend source) does not
+    /// affect blending.
+    pub fn new(mode: BlendMode) -> (Self, SourceTransform) {
+        use SourceTransform as T;
+        use wgpu::{BlendFactor as F, BlendOperation as O};
+
+        let (enabled, src, dst, op, transform) = if mode.enable() && mode.blend_subtract() {
+            // factors are ignored: dst - src
+            (true, F::One, F::One, O::ReverseSubtract, T::None)
+        } else if mode.enable() {
+            let src = match mode.src_factor() {
+                SrcBlendFactor::Zero => F::Zero,
+                SrcBlendFactor::One => F::One,
+                SrcBlendFactor::DstColor => F::Dst,
+                SrcBlendFactor::InverseDstColor => F::OneMinusDst,
+                SrcBlendFactor::SrcAlpha => F::Src1Alpha,
+                SrcBlendFactor::InverseSrcAlpha => F::OneMinusSrc1Alpha,
+                SrcBlendFactor::DstAlpha => F::DstAlpha,
+                SrcBlendFactor::InverseDstAlpha => F::OneMinusDstAlpha,
+            };
+
+            let dst = match mode.dst_factor() {
+                DstBlendFactor::Zero => F::Zero,
+                DstBlendFactor::One => F::One,
+                DstBlendFactor::SrcColor => F::Src1,
+                DstBlendFactor::InverseSrcColor => F::OneMinusSrc1,
+                DstBlendFactor::SrcAlpha => F::Src1Alpha,
+                DstBlendFactor::InverseSrcAlpha => F::OneMinusSrc1Alpha,
+                DstBlendFactor::DstAlpha => F::DstAlpha,
+                DstBlendFactor::InverseDstAlpha => F::OneMinusDstAlpha,
+            };
+
+            (true, src, dst, O::Add, T::None)
+        } else if mode.logic_op_enable() {
+            // s = src, d = dst
+            let (transform, src, dst, op) = match mode.logic_op() {
+                // 0
+                BlendLogicOp::Clear => (T::None, F::Zero, F::Zero, O::Add),
+                // s * d
+                BlendLogicOp::And => (T::None, F::Zero, F::Src1, O::Add),
+                // s * (1 - d)
+                BlendLogicOp::ReverseAnd => (T::None, F::OneMinusDst, F::Zero, O::Add),
+                // s
+                BlendLogicOp::Copy => (T::None, F::One, F::Zero, O::Add),
+                // d * (1 - s)
+                BlendLogicOp::InverseAnd => (T::None, F::Zero, F::OneMinusSrc1, O::Add),
+                // d
+                BlendLogicOp::Noop => (T::None, F::Zero, F::One, O::Add),
+                // s * (1 - d) + d * (1 - s)
+                BlendLogicOp::Xor => (T::None, F::OneMinusDst, F::OneMinusSrc1, O::Add),
+                // s * (1 - d) + d
+                BlendLogicOp::Or => (T::None, F::OneMinusDst, F::One, O::Add),
+                // (1 - s) * (1 - d)
+                BlendLogicOp::Nor => (T::Invert, F::OneMinusDst, F::Zero, O::Add),
+                // (1 - s) * (1 - d) + d * s
+                BlendLogicOp::Equiv => (T::Invert, F::OneMinusDst, F::Src1, O::Add),
+                // 1 - d
+                BlendLogicOp::Inverse => (T::Ones, F::OneMinusDst, F::Zero, O::Add),
+                // 1 - d * (1 - s)
+                BlendLogicOp::ReverseOr => (T::Ones, F::One, F::OneMinusSrc1, O::Subtract),
+                // 1 - s
+                BlendLogicOp::InverseCopy => (T::Invert, F::One, F::Zero, O::Add),
+                // (1 - s) * (1 - d) + d
+                BlendLogicOp::InverseOr => (T::Invert, F::OneMinusDst, F::One, O::Add),
+                // 1 - s * d
+                BlendLogicOp::Nand => (T::Ones, F::One, F::Src1, O::Subtract),
+                // 1
+                BlendLogicOp::Set => (T::Ones, F::One, F::Zero, O::Add),
+            };
+
+            (true, src, dst, op, transform)
+        } else {
+            (false, F::One, F::Zero, O::Add, T::None)
+        };
+
+        let settings = Self {
+            enabled,
+            src,
+            dst,
+            op,
+            color_write: mode.color_mask(),
+            alpha_write: mode.alpha_mask(),
+        };
+
+        (settings, transform)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DepthSettings {
     pub enabled: bool,
@@ -120,19 +231,27 @@ pub struct AlphaFunctionSettings {
 }
 
 impl AlphaFunctionSettings {
-    /// Returns whether this configuration is trivially passable (i.e. never discards).
-    pub fn is_noop(&self) -> bool {
+    /// Returns the result of this configuration if it doesn't depend on alpha.
+    fn constant(&self) -> AlphaCompareValue {
         let lhs = AlphaCompareValue::new(self.comparison[0]);
         let rhs = AlphaCompareValue::new(self.comparison[1]);
 
-        let result = match self.logic {
+        match self.logic {
             AlphaLogic::And => lhs & rhs,
             AlphaLogic::Or => lhs | rhs,
             AlphaLogic::Xor => lhs ^ rhs,
             AlphaLogic::Xnor => !(lhs ^ rhs),
-        };
+        }
+    }
 
-        result == AlphaCompareValue::True
+    /// Returns whether this configuration is trivially passable (i.e. never discards).
+    pub fn is_noop(&self) -> bool {
+        self.constant() == AlphaCompareValue::True
+    }
+
+    /// Returns whether this configuration always discards.
+    pub fn never_passes(&self) -> bool {
+        self.constant() == AlphaCompareValue::False
     }
 }
 
@@ -141,11 +260,18 @@ pub struct TexEnvSettings {
     pub stages: Vec<TexEnvStage>,
     pub alpha_func: AlphaFunctionSettings,
     pub depth_tex: DepthTexture,
+    /// Whether the depth test happens before texturing (i.e. the zcomploc bit). If so, depth is
+    /// written even for fragments which fail the alpha test.
+    pub early_depth: bool,
+    /// The fog kind. Everything else about fog, including the projection and range adjustment,
+    /// is part of the per-draw config so that it doesn't multiply the number of pipelines.
+    pub fog: FogKind,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct TexGenStageSettings {
     pub base: BaseTexGen,
+    pub dual: bool,
     pub normalize: bool,
 }
 
@@ -158,6 +284,7 @@ pub struct TexGenSettings {
 pub struct ShaderSettings {
     pub texenv: TexEnvSettings,
     pub texgen: TexGenSettings,
+    pub source: SourceTransform,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Default)]
@@ -168,3 +295,130 @@ pub struct Settings {
     pub depth: DepthSettings,
     pub shader: ShaderSettings,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const COMPARES: [AlphaCompare; 8] = [
+        AlphaCompare::Never,
+        AlphaCompare::Less,
+        AlphaCompare::Equal,
+        AlphaCompare::LessOrEqual,
+        AlphaCompare::Greater,
+        AlphaCompare::NotEqual,
+        AlphaCompare::GreaterOrEqual,
+        AlphaCompare::Always,
+    ];
+
+    const LOGICS: [AlphaLogic; 4] = [
+        AlphaLogic::And,
+        AlphaLogic::Or,
+        AlphaLogic::Xor,
+        AlphaLogic::Xnor,
+    ];
+
+    /// Evaluates the alpha test for the given alpha and reference values, as the hardware does.
+    fn passes(settings: &AlphaFunctionSettings, alpha: u8, refs: [u8; 2]) -> bool {
+        let compare = |compare, reference| match compare {
+            AlphaCompare::Never => false,
+            AlphaCompare::Less => alpha < reference,
+            AlphaCompare::Equal => alpha == reference,
+            AlphaCompare::LessOrEqual => alpha <= reference,
+            AlphaCompare::Greater => alpha > reference,
+            AlphaCompare::NotEqual => alpha != reference,
+            AlphaCompare::GreaterOrEqual => alpha >= reference,
+            AlphaCompare::Always => true,
+        };
+
+        let lhs = compare(settings.comparison[0], refs[0]);
+        let rhs = compare(settings.comparison[1], refs[1]);
+
+        match settings.logic {
+            AlphaLogic::And => lhs && rhs,
+            AlphaLogic::Or => lhs || rhs,
+            AlphaLogic::Xor => lhs != rhs,
+            AlphaLogic::Xnor => lhs == rhs,
+        }
+    }
+
+    /// Which alphas of a 0..=255 gradient pass the alpha test.
+    fn coverage(settings: &AlphaFunctionSettings, refs: [u8; 2]) -> Vec<bool> {
+        (0..=255)
+            .map(|alpha| passes(settings, alpha, refs))
+            .collect()
+    }
+
+    #[test]
+    fn alpha_test_fast_paths() {
+        for a in COMPARES {
+            for b in COMPARES {
+                for logic in LOGICS {
+                    let settings = AlphaFunctionSettings {
+                        comparison: [a, b],
+                        logic,
+                    };
+
+                    for refs in [[0x00, 0xFF], [0x40, 0xC0], [0x80, 0x80]] {
+                        let coverage = coverage(&settings, refs);
+                        if settings.is_noop() {
+                            assert!(coverage.iter().all(|&x| x), "{settings:?} {refs:?}");
+                        }
+
+                        if settings.never_passes() {
+                            assert!(coverage.iter().all(|&x| !x), "{settings:?} {refs:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn alpha_test_coverage() {
+        let settings = |a, b, logic| AlphaFunctionSettings {
+            comparison: [a, b],
+            logic,
+        };
+        let count = |settings, refs| coverage(&settings, refs).iter().filter(|&&x| x).count();
+
+        // typical foliage setup: alpha >= ref, or'd with never
+        let foliage = settings(
+            AlphaCompare::GreaterOrEqual,
+            AlphaCompare::Never,
+            AlphaLogic::Or,
+        );
+        assert_eq!(count(foliage.clone(), [0x80, 0]), 0x80);
+        assert!(!passes(&foliage, 0x7F, [0x80, 0]) && passes(&foliage, 0x80, [0x80, 0]));
+
+        // a band between both references
+        let band = settings(AlphaCompare::Greater, AlphaCompare::Less, AlphaLogic::And);
+        assert_eq!(count(band.clone(), [0x40, 0xC0]), 0xC0 - 0x40 - 1);
+
+        // outside of the band
+        let outside = settings(
+            AlphaCompare::LessOrEqual,
+            AlphaCompare::GreaterOrEqual,
+            AlphaLogic::Or,
+        );
+        assert_eq!(count(outside, [0x40, 0xC0]), 256 - (0xC0 - 0x40 - 1));
+
+        // xor and xnor of the same comparison
+        let xor = settings(AlphaCompare::Equal, AlphaCompare::Equal, AlphaLogic::Xor);
+        assert_eq!(count(xor.clone(), [0x10, 0x10]), 0);
+        assert_eq!(count(xor, [0x10, 0x20]), 2);
+        let xnor = settings(AlphaCompare::Equal, AlphaCompare::Equal, AlphaLogic::Xnor);
+        assert_eq!(count(xnor, [0x10, 0x20]), 254);
+    }
+
+    #[test]
+    fn regular_blending() {
+        // enable, src factor src alpha (4), dst factor inverse src alpha (5)
+        let mode = BlendMode::from_bits(1 | (5 << 5) | (4 << 8));
+        let (settings, transform) = BlendSettings::new(mode);
+        assert_eq!(transform, SourceTransform::None);
+        assert_eq!(settings.src, wgpu::BlendFactor::Src1Alpha);
+        assert_eq!(settings.dst, wgpu::BlendFactor::OneMinusSrc1Alpha);
+        assert_eq!(settings.op, wgpu::BlendOperation::Add);
+    }
+}
@@ -3,10 +3,27 @@ mod texgen;
 
 use lazuli::system::gx::tev::DepthTexOp;
 use wesl::{VirtualResolver, Wesl};
-use wesl_quote::quote_declaration;
+use wesl_quote::{quote_declaration, quote_expression, quote_statement};
 
 use crate::render::pipeline::ShaderSettings;
-use crate::render::pipeline::settings::{TexEnvSettings, TexGenSettings};
+use crate::render::pipeline::settings::{SourceTransform, TexEnvSettings, TexGenSettings};
+
+fn has_frag_depth(texenv: &TexEnvSettings) -> bool {
+    match texenv.depth_tex.mode.op() {
+        DepthTexOp::Disabled => false,
+        DepthTexOp::Add | DepthTexOp::Replace => true,
+        _ => panic!("reserved depth tex mode"),
+    }
+}
+
+/// Whether the depth test should happen before the fragment shader runs, so that fragments
+/// discarded by the alpha test still update depth.
+///
+/// This only makes a difference when the alpha test can discard. Shaders which write depth (i.e.
+/// use a depth texture) always test depth late.
+fn has_early_depth(texenv: &TexEnvSettings) -> bool {
+    texenv.early_depth && !texenv.alpha_func.is_noop() && !has_frag_depth(texenv)
+}
 
 fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
     use wesl::syntax::*;
@@ -24,13 +41,7 @@ fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
         }
     };
 
-    let has_frag_depth = match settings.texenv.depth_tex.mode.op() {
-        DepthTexOp::Disabled => false,
-        DepthTexOp::Add | DepthTexOp::Replace => true,
-        _ => panic!("reserved depth tex mode"),
-    };
-
-    let fragment_out_struct = if has_frag_depth {
+    let fragment_out_struct = if has_frag_depth(&settings.texenv) {
         quote_declaration! {
             struct FragmentOutput {
                 @location(0) @blend_src(0) color: vec4f,
@@ -54,6 +65,12 @@ fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
         const PLACEHOLDER_RGB: vec3f = vec3f(1.0, 0.0, 0.8627);
         const PLACEHOLDER_RGBA: vec4f = vec4f(1.0, 0.0, 0.8627, 0.5);
 
+        const EXPAND_LINE: u32 = 1;
+        const EXPAND_POINT: u32 = 2;
+
+        const FOG_ORTHOGRAPHIC: u32 = 1;
+        const FOG_RANGE: u32 = 2;
+
         struct Light {
             color: vec4f,
 
@@ -92,6 +109,22 @@ fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
             constant_alpha: u32,
             alpha_refs: array<u32, 2>,
             _pad0: u32,
+
+            viewport_size: vec2f,
+            line_width: vec2f,
+            point_size: f32,
+            line_tex_offset: f32,
+            point_tex_offset: f32,
+            tex_offset_mask: u32,
+
+            fog_color: vec4f,
+            fog_scale: f32,
+            fog_offset: f32,
+            fog_b_magnitude: u32,
+            fog_b_shift: u32,
+            fog_range_center: f32,
+            fog_range_factors: array<f32, 10>,
+            fog_flags: u32,
         }
 
         // An input vertex
@@ -99,12 +132,16 @@ fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
             position: vec3f,
             config_idx: u32,
             normal: vec3f,
-            _pad0: u32,
+            expand: u32,
+            binormal: vec3f,
+            _pad1: u32,
+            tangent: vec3f,
+            _pad2: u32,
 
             position_mat: MtxIdx,
             normal_mat: MtxIdx,
-            _pad1: u32,
-            _pad2: u32,
+            other: u32,
+            _pad0: u32,
 
             chan0: vec4f,
             chan1: vec4f,
@@ -178,6 +215,13 @@ fn base_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
             let t = int.g;
             return vec3f(f32(s) / 255, f32(t) / 255, 1.0);
         }
+
+        // the direction from a vertex to a light, projected onto the tangent and binormal of the
+        // vertex
+        fn emboss_offset(light_pos: vec3f, vertex_pos: vec3f, tangent: vec3f, binormal: vec3f) -> vec3f {
+            let light_dir = normalize(light_pos - vertex_pos);
+            return vec3f(dot(light_dir, tangent), dot(light_dir, binormal), 0.0);
+        }
     }
 }
 
@@ -365,12 +409,7 @@ fn vertex_stage(texgen: &TexGenSettings) -> wesl::syntax::GlobalDeclaration {
     for (index, stage) in texgen.stages.iter().enumerate() {
         let index = index as u32;
 
-        let source = texgen::get_source(stage.base.source(), stage.base.kind());
-        let input = texgen::get_input(stage.base.input_kind(), source);
-        let transformed = texgen::transform(stage.base.kind(), input);
-        let output = texgen::get_output(stage.base.output_kind(), transformed);
-        let normalized = texgen::normalize(stage.normalize, output);
-        let result = texgen::post_transform(index, normalized);
+        let result = texgen::generate(index, stage);
 
         stages.push(wesl_quote::quote_statement! {
             {
@@ -432,8 +471,48 @@ fn vertex_stage(texgen: &TexGenSettings) -> wesl::syntax::GlobalDeclaration {
             let vertex_world_pos = base::matrices[vertex.position_mat] * vertex_local_pos;
             var vertex_view_pos = config.projection_mat * vertex_world_pos;
 
+            // lines and points are drawn as quads, whose corners are pushed away from the
+            // original vertex in screen space
+            var tex_offset = vec2f(0.0);
+            var tex_offset_mask = 0u;
+            let expand_kind = vertex.expand & 3;
+            if expand_kind == base::EXPAND_LINE {
+                let other = base::vertices[vertex.other];
+                let other_world_pos = base::matrices[other.position_mat] * vec4f(other.position, 1.0);
+                let other_view_pos = config.projection_mat * other_world_pos;
+
+                let ndc = vertex_view_pos.xy / vertex_view_pos.w;
+                let other_ndc = other_view_pos.xy / other_view_pos.w;
+                let delta = abs(other_ndc - ndc) * config.viewport_size;
+                let side = select(-1.0, 1.0, (vertex.expand & 4) != 0);
+
+                // like the hardware, lines are widened either horizontally or vertically,
+                // whichever is closest to being perpendicular to them
+                var offset: vec2f;
+                if delta.y > delta.x {
+                    offset = vec2f(side * config.line_width.x / config.viewport_size.x, 0.0);
+                } else {
+                    offset = vec2f(0.0, -side * config.line_width.y / config.viewport_size.y);
+                }
+
+                vertex_view_pos = vec4f(vertex_view_pos.xy + offset * vertex_view_pos.w, vertex_view_pos.zw);
+                tex_offset = vec2f(select(0.0, config.line_tex_offset, side > 0.0), 0.0);
+                tex_offset_mask = config.tex_offset_mask & 0xFF;
+            } else if expand_kind == base::EXPAND_POINT {
+                let right = select(0.0, 1.0, (vertex.expand & 4) != 0);
+                let bottom = select(0.0, 1.0, (vertex.expand & 8) != 0);
+                let corner = vec2f(2.0 * right - 1.0, 1.0 - 2.0 * bottom);
+                let offset = corner * config.point_size / config.viewport_size;
+
+                vertex_view_pos = vec4f(vertex_view_pos.xy + offset * vertex_view_pos.w, vertex_view_pos.zw);
+                tex_offset = vec2f(right, bottom) * config.point_tex_offset;
+                tex_offset_mask = (config.tex_offset_mask >> 8) & 0xFF;
+            }
+
             let vertex_local_norm = vec4f(vertex.normal, 0.0);
             let vertex_world_norm = normalize((base::matrices[vertex.normal_mat] * vertex_local_norm).xyz);
+            let vertex_world_binormal = (base::matrices[vertex.normal_mat] * vec4f(vertex.binormal, 0.0)).xyz;
+            let vertex_world_tangent = (base::matrices[vertex.normal_mat] * vec4f(vertex.tangent, 0.0)).xyz;
 
             // GameCube's normalized device coordinates are -1.0..1.0 in x/y and -1.0..0.0 in z,
             // while wgpu's normalized device coordinates are -1.0..1.0 in x/y and 0.0..1.0 in z.
@@ -454,6 +533,12 @@ fn vertex_stage(texgen: &TexGenSettings) -> wesl::syntax::GlobalDeclaration {
             var tex_coords: array<vec3f, 8>;
             @#compute_stages {}
 
+            for (var i = 0u; i < 8; i += 1) {
+                if ((tex_offset_mask >> i) & 1) != 0 {
+                    tex_coords[i] += vec3f(tex_offset * tex_coords[i].z, 0.0);
+                }
+            }
+
             out.tex_coord0 = tex_coords[0];
             out.tex_coord1 = tex_coords[1];
             out.tex_coord2 = tex_coords[2];
@@ -468,7 +553,10 @@ fn vertex_stage(texgen: &TexGenSettings) -> wesl::syntax::GlobalDeclaration {
     }
 }
 
-fn fragment_stage(texenv: &TexEnvSettings) -> wesl::syntax::GlobalDeclaration {
+fn fragment_stage(
+    texenv: &TexEnvSettings,
+    source: SourceTransform,
+) -> wesl::syntax::GlobalDeclaration {
     use wesl::syntax::*;
 
     let mut stages = vec![];
@@ -523,10 +611,30 @@ fn fragment_stage(texenv: &TexEnvSettings) -> wesl::syntax::GlobalDeclaration {
         @#s15 {}
     });
 
-    let alpha_comparison = texenv::get_alpha_comparison(&texenv.alpha_func);
+    let alpha_test = texenv::get_alpha_test(&texenv.alpha_func);
     let depth_texture = texenv::get_depth_texture(&texenv);
 
-    wesl_quote::quote_declaration! {
+    // fog uses the depth after it has been replaced by the depth texture
+    let depth = if has_frag_depth(texenv) {
+        quote_expression!(out.depth)
+    } else {
+        quote_expression!(in.clip.z)
+    };
+    let fog = texenv::get_fog(texenv.fog, depth);
+
+    // only the first blend source is transformed, the blend factors still refer to the original
+    // color through the second one
+    let source_transform = match source {
+        SourceTransform::None => quote_statement!({}),
+        SourceTransform::Invert => quote_statement!({
+            out.color = vec4f(1.0) - out.color;
+        }),
+        SourceTransform::Ones => quote_statement!({
+            out.color = vec4f(1.0);
+        }),
+    };
+
+    let mut fs_main = wesl_quote::quote_declaration! {
         @fragment
         fn fs_main(in: base::VertexOutput) -> base::FragmentOutput {
             const R0: u32 = 1;
@@ -548,29 +656,40 @@ fn fragment_stage(texenv: &TexEnvSettings) -> wesl::syntax::GlobalDeclaration {
 
             @#compute_stages {}
 
-            let color = regs[last_color_output].rgb;
+            var color = regs[last_color_output].rgb;
             let alpha = regs[last_alpha_output].a;
 
-            let alpha_ref0 = f32(config.alpha_refs[0]) / 255.0;
-            let alpha_ref1 = f32(config.alpha_refs[1]) / 255.0;
-
-            if !(#alpha_comparison) {
-                discard;
-            }
+            @#alpha_test {}
 
             var out: base::FragmentOutput;
-            out.blend = vec4f(regs[last_color_output].rgb, regs[last_alpha_output].a);
+            @#depth_texture {}
+            @#fog {}
+
+            out.blend = vec4f(color, alpha);
             if config.constant_alpha < 256 {
-                out.color = vec4f(regs[last_color_output].rgb, f32(config.constant_alpha) / 255.0);
+                out.color = vec4f(color, f32(config.constant_alpha) / 255.0);
             } else {
                 out.color = out.blend;
             }
 
-            @#depth_texture {}
+            @#source_transform {}
 
             return out;
         }
+    };
+
+    if has_early_depth(texenv) {
+        // a naga extension, i.e. `@early_depth_test(force)`
+        let GlobalDeclaration::Function(function) = &mut fs_main else {
+            unreachable!()
+        };
+
+        function
+            .attributes
+            .push(Attribute::EarlyDepthTest(None).into());
     }
+
+    fs_main
 }
 
 fn main_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
@@ -579,7 +698,7 @@ fn main_module(settings: &ShaderSettings) -> wesl::syntax::TranslationUnit {
     let extensions = wesl_quote::quote_directive!(enable dual_source_blending;);
     let [color_chan, alpha_chan] = compute_channels();
     let vertex = vertex_stage(&settings.texgen);
-    let fragment = fragment_stage(&settings.texenv);
+    let fragment = fragment_stage(&settings.texenv, settings.source);
 
     let mut module = wesl_quote::quote_module! {
         import package::base;
@@ -621,3 +740,28 @@ pub fn compile(settings: &ShaderSettings) -> String {
 
     compiled.syntax.to_string()
 }
+
+#[cfg(test)]
+mod test {
+    use lazuli::system::gx::tev::{AlphaCompare, AlphaLogic};
+
+    use super::*;
+    use crate::render::pipeline::AlphaFunctionSettings;
+
+    #[test]
+    fn early_depth_only_with_alpha_test() {
+        let mut settings = ShaderSettings::default();
+        settings.texenv.early_depth = true;
+        settings.texenv.alpha_func = AlphaFunctionSettings {
+            comparison: [AlphaCompare::Always; 2],
+            logic: AlphaLogic::And,
+        };
+        assert!(!compile(&settings).contains("@early_depth_test(force)"));
+
+        settings.texenv.alpha_func.comparison[0] = AlphaCompare::Greater;
+        assert!(compile(&settings).contains("@early_depth_test(force)"));
+
+        settings.texenv.early_depth = false;
+        assert!(!compile(&settings).contains("@early_depth_test"));
+    }
+}
@@ -1,7 +1,7 @@
 use lazuli::modules::render::TexEnvStage;
 use lazuli::system::gx::tev::{
     AlphaCompare, AlphaInputSrc, AlphaLogic, ColorChannel, ColorInputSrc, CompareOp, CompareTarget,
-    Constant, DepthTexFormat, DepthTexOp,
+    Constant, DepthTexFormat, DepthTexOp, FogKind,
 };
 use wesl_quote::{quote_expression, quote_statement};
 
@@ -420,17 +420,17 @@ fn get_alpha_comparison_helper(compare: AlphaCompare, idx: usize) -> wesl::synta
     let alpha_ref = wesl::syntax::Ident::new(format!("alpha_ref{idx}"));
     match compare {
         AlphaCompare::Never => quote_expression! { false },
-        AlphaCompare::Less => quote_expression! { alpha < #alpha_ref },
-        AlphaCompare::Equal => quote_expression! { alpha == #alpha_ref },
-        AlphaCompare::LessOrEqual => quote_expression! { alpha <= #alpha_ref },
-        AlphaCompare::Greater => quote_expression! { alpha > #alpha_ref },
-        AlphaCompare::NotEqual => quote_expression! { alpha != #alpha_ref },
-        AlphaCompare::GreaterOrEqual => quote_expression! { alpha >= #alpha_ref },
+        AlphaCompare::Less => quote_expression! { alpha_u8 < #alpha_ref },
+        AlphaCompare::Equal => quote_expression! { alpha_u8 == #alpha_ref },
+        AlphaCompare::LessOrEqual => quote_expression! { alpha_u8 <= #alpha_ref },
+        AlphaCompare::Greater => quote_expression! { alpha_u8 > #alpha_ref },
+        AlphaCompare::NotEqual => quote_expression! { alpha_u8 != #alpha_ref },
+        AlphaCompare::GreaterOrEqual => quote_expression! { alpha_u8 >= #alpha_ref },
         AlphaCompare::Always => quote_expression! { true },
     }
 }
 
-pub fn get_alpha_comparison(settings: &AlphaFunctionSettings) -> wesl::syntax::Expression {
+fn get_alpha_comparison(settings: &AlphaFunctionSettings) -> wesl::syntax::Expression {
     use wesl::syntax::*;
     let a = get_alpha_comparison_helper(settings.comparison[0], 0);
     let b = get_alpha_comparison_helper(settings.comparison[1], 1);
@@ -443,6 +443,34 @@ pub fn get_alpha_comparison(settings: &AlphaFunctionSettings) -> wesl::syntax::E
     }
 }
 
+/// Discards the fragment if it fails the alpha test. Alpha is compared as an 8 bit value, like the
+/// hardware does. Configurations which never or always discard don't compare at all, so that
+/// shaders which can't discard don't contain a discard.
+pub fn get_alpha_test(settings: &AlphaFunctionSettings) -> wesl::syntax::Statement {
+    use wesl::syntax::*;
+
+    if settings.is_noop() {
+        return quote_statement!({});
+    }
+
+    if settings.never_passes() {
+        return quote_statement!({
+            discard;
+        });
+    }
+
+    let comparison = get_alpha_comparison(settings);
+    quote_statement!({
+        let alpha_u8 = u32(round(clamp(alpha, 0.0, 1.0) * 255.0));
+        let alpha_ref0 = config.alpha_refs[0];
+        let alpha_ref1 = config.alpha_refs[1];
+
+        if !(#comparison) {
+            discard;
+        }
+    })
+}
+
 pub fn get_depth_texture(settings: &TexEnvSettings) -> wesl::syntax::Statement {
     use wesl::syntax::*;
 
@@ -470,3 +498,74 @@ pub fn get_depth_texture(settings: &TexEnvSettings) -> wesl::syntax::Statement {
         }
     }
 }
+
+/// Blends `color` with the fog color according to the depth of the fragment, given by `depth`.
+///
+/// Every fog kind is supported (linear, exponential, exponential squared and the backward
+/// variants of both), with either perspective or orthographic projections and optional range
+/// adjustment. The reserved kinds behave as linear fog. Only the kind is baked into the shader:
+/// the projection and range adjustment are selected by the fog flags of the config. Follows
+/// [`Fog::density`](lazuli::system::gx::tev::Fog::density) and
+/// [`Fog::blend`](lazuli::system::gx::tev::Fog::blend).
+pub fn get_fog(kind: FogKind, depth: wesl::syntax::Expression) -> wesl::syntax::Statement {
+    use wesl::syntax::*;
+
+    if kind == FogKind::Off {
+        return Statement::Void;
+    }
+
+    let curve = match kind {
+        FogKind::Exp => quote_statement!({
+            fog = 1.0 - exp2(-8.0 * fog);
+        }),
+        FogKind::Exp2 => quote_statement!({
+            fog = 1.0 - exp2(-8.0 * fog * fog);
+        }),
+        FogKind::BackwardExp => quote_statement!({
+            fog = exp2(-8.0 * (1.0 - fog));
+        }),
+        FogKind::BackwardExp2 => quote_statement!({
+            fog = exp2(-8.0 * (1.0 - fog) * (1.0 - fog));
+        }),
+        _ => quote_statement!({}),
+    };
+
+    quote_statement! {
+        {
+            let fog_z = u32(round(clamp(#depth, 0.0, 1.0) * f32(base::DEPTH_MAX)));
+
+            // the distance to the camera, scaled by A
+            var fog_distance: f32;
+            if (config.fog_flags & base::FOG_ORTHOGRAPHIC) == 0u {
+                let fog_denom = i32(config.fog_b_magnitude) - i32(fog_z >> config.fog_b_shift);
+                fog_distance = config.fog_scale * f32(1 << 24) / f32(fog_denom);
+            } else {
+                fog_distance = config.fog_scale * f32(fog_z) / f32(1 << 24);
+            }
+
+            // approximates the distance from the center of the screen with linearly interpolated
+            // factors, as x_adjust = sqrt(offset² + k²) / k
+            if (config.fog_flags & base::FOG_RANGE) != 0u {
+                let fog_half_width = config.viewport_size.x / 2.0;
+                let fog_offset = (in.clip.x - config.fog_range_center) / fog_half_width - 1.0;
+                let fog_index = clamp(9.0 - abs(fog_offset) * 9.0, 0.0, 9.0);
+                let fog_lower = u32(fog_index);
+                let fog_upper = min(fog_lower + 1, 9u);
+                let fog_k_lower = config.fog_range_factors[fog_lower];
+                let fog_k_upper = config.fog_range_factors[fog_upper];
+                let fog_k = mix(fog_k_lower, fog_k_upper, fract(fog_index));
+                fog_distance *= sqrt(fog_offset * fog_offset + fog_k * fog_k) / fog_k;
+            }
+
+            var fog = clamp(fog_distance - config.fog_offset, 0.0, 1.0);
+            @#curve {}
+
+            // like the hardware, blend with 8 bit precision
+            let fog_amount = round(fog * 256.0);
+            let fog_color = round(config.fog_color.rgb * 255.0);
+            let fog_input = round(clamp(color, vec3f(0.0), vec3f(1.0)) * 255.0);
+            let fog_blended = fog_input * (256.0 - fog_amount) + fog_color * fog_amount;
+            color = floor(fog_blended / 256.0) / 255.0;
+        }
+    }
+}
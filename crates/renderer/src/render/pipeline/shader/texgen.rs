@@ -1,16 +1,14 @@
 use lazuli::system::gx::xform::{TexGenInputKind, TexGenKind, TexGenOutputKind, TexGenSource};
 use wesl_quote::quote_expression;
 
-pub fn get_source(source: TexGenSource, kind: TexGenKind) -> wesl::syntax::Expression {
+use crate::render::pipeline::settings::TexGenStageSettings;
+
+pub fn get_source(source: TexGenSource) -> wesl::syntax::Expression {
     use wesl::syntax::*;
     match source {
         TexGenSource::Position => quote_expression! { vertex.position },
         TexGenSource::Normal => quote_expression! { vertex.normal },
-        TexGenSource::Color => match kind {
-            TexGenKind::ColorDiffuse => quote_expression! { vertex.chan0 },
-            TexGenKind::ColorSpecular => quote_expression! { vertex.chan1 },
-            _ => panic!("invalid texgen config"),
-        },
+        TexGenSource::Color => quote_expression! { vertex.chan0.rgb },
         TexGenSource::TexCoord0 => quote_expression! { vec3f(vertex.tex_coord[0], 1.0) },
         TexGenSource::TexCoord1 => quote_expression! { vec3f(vertex.tex_coord[1], 1.0) },
         TexGenSource::TexCoord2 => quote_expression! { vec3f(vertex.tex_coord[2], 1.0) },
@@ -19,8 +17,8 @@ pub fn get_source(source: TexGenSource, kind: TexGenKind) -> wesl::syntax::Expre
         TexGenSource::TexCoord5 => quote_expression! { vec3f(vertex.tex_coord[5], 1.0) },
         TexGenSource::TexCoord6 => quote_expression! { vec3f(vertex.tex_coord[6], 1.0) },
         TexGenSource::TexCoord7 => quote_expression! { vec3f(vertex.tex_coord[7], 1.0) },
-        TexGenSource::BinormalT => todo!(),
-        TexGenSource::BinormalB => todo!(),
+        TexGenSource::BinormalT => quote_expression! { vertex.tangent },
+        TexGenSource::BinormalB => quote_expression! { vertex.binormal },
         _ => panic!("reserved texgen source"),
     }
 }
@@ -36,18 +34,6 @@ pub fn get_input(
     }
 }
 
-pub fn transform(kind: TexGenKind, input: wesl::syntax::Expression) -> wesl::syntax::Expression {
-    use wesl::syntax::*;
-    match kind {
-        TexGenKind::Transform => quote_expression! { (matrix * #input).xyz },
-        // TODO: terrible stub (emboss)
-        TexGenKind::Emboss => quote_expression! { (#input).xyz },
-        TexGenKind::ColorDiffuse | TexGenKind::ColorSpecular => quote_expression! {
-            base::concat_texgen_color(#input)
-        },
-    }
-}
-
 pub fn get_output(
     format: TexGenOutputKind,
     transformed: wesl::syntax::Expression,
@@ -75,3 +61,44 @@ pub fn post_transform(
     use wesl::syntax::*;
     quote_expression! { (config.post_transform_mat[#stage_index] * vec4f(#normalized, 1.0)).xyz }
 }
+
+/// Offsets the coordinates of an earlier texgen by the direction to a light, in the tangent space
+/// of the vertex.
+pub fn emboss(source: u32, light: u32) -> wesl::syntax::Expression {
+    use wesl::syntax::*;
+    quote_expression! {
+        tex_coords[#source] + base::emboss_offset(
+            config.lights[#light].position,
+            vertex_world_pos.xyz,
+            vertex_world_tangent,
+            vertex_world_binormal,
+        )
+    }
+}
+
+/// Generates the texture coordinates of a texgen stage.
+pub fn generate(stage_index: u32, stage: &TexGenStageSettings) -> wesl::syntax::Expression {
+    use wesl::syntax::*;
+    let base = &stage.base;
+    match base.kind() {
+        TexGenKind::Transform => {
+            let source = get_source(base.source());
+            let input = get_input(base.input_kind(), source);
+            let transformed = quote_expression! { (matrix * #input).xyz };
+            let output = get_output(base.output_kind(), transformed);
+
+            if stage.dual {
+                post_transform(stage_index, normalize(stage.normalize, output))
+            } else {
+                output
+            }
+        }
+        TexGenKind::Emboss => emboss(
+            base.emboss_source().value() as u32,
+            base.emboss_light().value() as u32,
+        ),
+        // color texgens use the lit colors
+        TexGenKind::ColorDiffuse => quote_expression! { base::concat_texgen_color(out.chan0) },
+        TexGenKind::ColorSpecular => quote_expression! { base::concat_texgen_color(out.chan1) },
+    }
+}
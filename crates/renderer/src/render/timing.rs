@@ -0,0 +1,153 @@
+//! GPU timing of frames through timestamp queries.
+//!
+//! Every render pass of a frame writes a timestamp when it begins and ends. When the frame ends,
+//! the timestamps are resolved and read back asynchronously, so the GPU time of a frame only
+//! becomes available a few frames later. While a readback is in flight, frames are not timed.
+use std::time::Duration;
+
+use lazuli::modules::render::oneshot;
+
+/// Maximum amount of timed render passes in a frame. Passes past this are not timed.
+const MAX_PASSES: u32 = 64;
+const QUERY_COUNT: u32 = 2 * MAX_PASSES;
+const BUFFER_LEN: u64 = QUERY_COUNT as u64 * size_of::<u64>() as u64;
+
+type Readback = oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>;
+
+pub struct FrameTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+    /// How many passes of the current frame have been timed.
+    passes: u32,
+    /// Readback in flight and how many passes it contains.
+    pending: Option<(Readback, u32)>,
+    /// GPU time of the last frame read back.
+    last: Option<Duration>,
+}
+
+impl FrameTimer {
+    /// Creates a frame timer, if the device supports timestamp queries.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("frame timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame timestamps resolve buffer"),
+            size: BUFFER_LEN,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame timestamps readback buffer"),
+            size: BUFFER_LEN,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period() as f64,
+            passes: 0,
+            pending: None,
+            last: None,
+        })
+    }
+
+    /// Returns the timestamp writes of the next pass of the current frame, if it can be timed.
+    pub fn pass_writes(&mut self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        if self.passes == MAX_PASSES {
+            return None;
+        }
+
+        let index = 2 * self.passes;
+        self.passes += 1;
+
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// Ends the current frame, returning how many of its passes were timed. Passes timed after
+    /// this belong to the next frame.
+    pub fn end_frame(&mut self) -> u32 {
+        std::mem::take(&mut self.passes)
+    }
+
+    /// Resolves the timestamps of a frame with the given amount of timed passes into `encoder`,
+    /// to be read back once it's executed. The encoder must come after the one containing the
+    /// passes of the frame.
+    ///
+    /// Returns the GPU time of the last frame read back, if any.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, passes: u32) -> Option<Duration> {
+        self.poll();
+        if passes == 0 || self.pending.is_some() {
+            return self.last;
+        }
+
+        let len = 2 * passes as u64 * size_of::<u64>() as u64;
+        encoder.resolve_query_set(&self.query_set, 0..2 * passes, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, len);
+
+        let (sender, receiver) = oneshot::channel();
+        encoder.map_buffer_on_submit(&self.readback_buffer, wgpu::MapMode::Read, ..len, |r| {
+            _ = sender.send(r);
+        });
+
+        self.pending = Some((receiver, passes));
+        self.last
+    }
+
+    /// Reads back the timestamps of the pending frame, if they have arrived.
+    fn poll(&mut self) {
+        let Some((receiver, passes)) = &self.pending else {
+            return;
+        };
+
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(oneshot::TryRecvError::Empty) => return,
+            Err(oneshot::TryRecvError::Disconnected) => {
+                self.pending = None;
+                return;
+            }
+        };
+
+        let len = 2 * *passes as u64 * size_of::<u64>() as u64;
+        self.pending = None;
+
+        if let Err(e) = result {
+            tracing::warn!("failed to read back frame timestamps: {e}");
+            return;
+        }
+
+        let mapped = self.readback_buffer.get_mapped_range(..len);
+        let ticks = mapped
+            .chunks_exact(2 * size_of::<u64>())
+            .map(|pass| {
+                let begin = u64::from_le_bytes(pass[..8].try_into().unwrap());
+                let end = u64::from_le_bytes(pass[8..].try_into().unwrap());
+                end.saturating_sub(begin)
+            })
+            .sum::<u64>();
+
+        std::mem::drop(mapped);
+        self.readback_buffer.unmap();
+
+        self.last = Some(Duration::from_nanos((ticks as f64 * self.period) as u64));
+    }
+}
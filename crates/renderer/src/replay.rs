@@ -0,0 +1,73 @@
+//! Offscreen replaying of captured action streams.
+//!
+//! See [`lazuli::modules::render::capture`] for how actions are captured.
+use lazuli::modules::render::Action;
+use lazuli::system::gx::color::Rgba8;
+use lazuli::system::gx::{EFB_HEIGHT, EFB_WIDTH};
+
+use crate::render::Renderer as RendererInner;
+
+/// A frame presented by a replayed action.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Rgba8>,
+}
+
+impl Frame {
+    fn opaque(width: u32, height: u32, mut pixels: Vec<Rgba8>) -> Self {
+        // the XFB has no alpha channel
+        for pixel in &mut pixels {
+            pixel.a = 255;
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// A renderer without a surface which executes actions synchronously, as needed for replaying
+/// captures.
+pub struct Replayer {
+    renderer: RendererInner,
+}
+
+impl Replayer {
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue) -> Self {
+        let (renderer, _) = RendererInner::new(device, queue);
+        Self { renderer }
+    }
+
+    /// Executes the given action. Returns the frame it presents, if any.
+    pub fn exec(&mut self, action: Action) -> Option<Frame> {
+        match action {
+            Action::XfbCopy { .. } => {
+                self.renderer.exec(action);
+
+                // the copy to the XFB has been submitted, but the clear (if any) only happens
+                // once the next pass is - so the EFB still holds the frame
+                let pixels =
+                    self.renderer
+                        .get_color_data(0, 0, EFB_WIDTH as u16, EFB_HEIGHT as u16, false);
+
+                Some(Frame::opaque(EFB_WIDTH as u32, EFB_HEIGHT as u32, pixels))
+            }
+            Action::PresentXfb {
+                width,
+                height,
+                ref data,
+            } => {
+                let frame = Frame::opaque(width as u32, height as u32, data.clone());
+                self.renderer.exec(action);
+                Some(frame)
+            }
+            _ => {
+                self.renderer.exec(action);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Compares parsing indexed attributes with and without index validation.
+use std::hint::black_box;
+use std::mem::MaybeUninit;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use lazuli::Address;
+use lazuli::modules::vertex::{Ctx, VertexModule};
+use lazuli::system::gx::cmd::attributes::{
+    AttributeMode, ColorDescriptor, ColorFormat, ColorKind, CoordsFormat, NormalDescriptor,
+    PositionDescriptor, PositionKind, VertexAttributeTable,
+};
+use lazuli::system::gx::cmd::{Arrays, VertexAttributeStream, VertexDescriptor};
+use lazuli::system::gx::xform::DefaultMatrices;
+use lazuli::system::gx::{MatrixSet, Vertex};
+use lazuli::system::mem::RAM_LEN;
+use vtxjit::{JitVertexModule, Settings};
+
+/// How many vertices are parsed per iteration.
+const VERTICES: u16 = 4096;
+/// How many elements each array has.
+const ELEMENTS: u32 = 0x4000;
+
+/// A pseudo-random sequence, so that indices don't walk the arrays in order.
+fn sequence(seed: u32) -> impl Iterator<Item = u32> {
+    std::iter::successors(Some(seed), |x| {
+        Some(x.wrapping_mul(1_664_525).wrapping_add(1_013_904_223))
+    })
+}
+
+/// Places the arrays in the middle of RAM, as games do. No element is close enough to the end of
+/// RAM to take the slow path of validated reads.
+fn arrays() -> Arrays {
+    let mut arrays = Arrays::default();
+    for (i, (array, stride)) in [
+        (&mut arrays.position, 12),
+        (&mut arrays.normal, 6),
+        (&mut arrays.chan0, 4),
+        (&mut arrays.chan1, 4),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        array.address = Address(0x0040_0000 + i as u32 * 0x0010_0000);
+        array.stride = stride;
+    }
+
+    arrays
+}
+
+/// A table with F32 positions, I16 normals and RGBA8888 colors, the formats indexed geometry
+/// usually comes in.
+fn table() -> VertexAttributeTable {
+    let mut vat = VertexAttributeTable::default();
+    vat.a.set_position(
+        PositionDescriptor::default()
+            .with_kind(PositionKind::Vec3)
+            .with_format(CoordsFormat::F32),
+    );
+    vat.a
+        .set_normal(NormalDescriptor::default().with_format(CoordsFormat::I16));
+
+    let color = ColorDescriptor::default()
+        .with_kind(ColorKind::Rgba)
+        .with_format(ColorFormat::Rgba8888);
+    vat.a.set_chan0(color.clone());
+    vat.a.set_chan1(color);
+
+    vat
+}
+
+/// A stream of `attributes` 16 bit indices per vertex.
+fn stream(attributes: usize) -> VertexAttributeStream {
+    let data = sequence(attributes as u32)
+        .take(VERTICES as usize * attributes)
+        .flat_map(|x| (((x >> 16) % ELEMENTS) as u16).to_be_bytes())
+        .collect();
+
+    VertexAttributeStream::new(0, VERTICES, data)
+}
+
+fn bench(c: &mut Criterion) {
+    let ram: Vec<u8> = sequence(0x5EED)
+        .take(RAM_LEN)
+        .map(|x| (x >> 24) as u8)
+        .collect();
+    let arrays = arrays();
+    let default_matrices = DefaultMatrices::default();
+    let vat = table();
+
+    let descriptors = [
+        (
+            "Position",
+            1,
+            VertexDescriptor::default().with_position(AttributeMode::Index16),
+        ),
+        (
+            "All",
+            4,
+            VertexDescriptor::default()
+                .with_position(AttributeMode::Index16)
+                .with_normal(AttributeMode::Index16)
+                .with_chan0(AttributeMode::Index16)
+                .with_chan1(AttributeMode::Index16),
+        ),
+    ];
+
+    let mut vertices: Vec<MaybeUninit<Vertex>> = std::iter::repeat_with(MaybeUninit::uninit)
+        .take(VERTICES as usize)
+        .collect();
+    let mut matrix_set = MatrixSet::default();
+
+    let mut unchecked = JitVertexModule::new(Settings {
+        validate_indices: false,
+    });
+    let mut validated = JitVertexModule::new(Settings {
+        validate_indices: true,
+    });
+
+    let mut group = c.benchmark_group("Indexed Attributes");
+    group.throughput(Throughput::Elements(VERTICES as u64));
+
+    for (name, attributes, vcd) in descriptors {
+        let stream = stream(attributes);
+        let ctx = Ctx {
+            ram: &ram,
+            arrays: &arrays,
+            default_matrices: &default_matrices,
+        };
+
+        for (id, module) in [("Unchecked", &mut unchecked), ("Validated", &mut validated)] {
+            group.bench_function(BenchmarkId::new(id, name), |b| {
+                b.iter(|| {
+                    module.parse(
+                        ctx,
+                        &vcd,
+                        &vat,
+                        black_box(&stream),
+                        &mut vertices,
+                        &mut matrix_set,
+                    )
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
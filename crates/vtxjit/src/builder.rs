@@ -5,7 +5,8 @@ use cranelift::frontend;
 use cranelift::prelude::InstBuilder;
 use lazuli::system::gx::Vertex;
 use lazuli::system::gx::cmd::ArrayDescriptor;
-use lazuli::system::gx::cmd::attributes::{self, AttributeMode};
+use lazuli::system::gx::cmd::attributes::{self, AttributeDescriptor, AttributeMode};
+use lazuli::system::mem::RAM_LEN;
 use rustc_hash::FxHashMap;
 use seq_macro::seq;
 use util::offset_of;
@@ -20,6 +21,13 @@ const MEMFLAGS_READONLY: ir::MemFlags = ir::MemFlags::new()
     .with_can_move()
     .with_readonly();
 
+/// Largest amount of bytes the attribute parsers read from an attribute pointer, since values are
+/// loaded as whole vectors. The tangents of F32 NBT normals are loaded 24 bytes in.
+const MAX_READ: usize = 40;
+
+/// Read instead of array elements which are out of bounds of RAM, when validating indices.
+static ZEROES: [u8; MAX_READ] = [0; MAX_READ];
+
 struct Array {
     base: ir::Value,
     stride: ir::Value,
@@ -36,6 +44,9 @@ struct Consts {
     vertices_ptr: ir::Value,
     mtx_set_ptr: ir::Value,
     count: ir::Value,
+    /// Where array elements close to the end of RAM are copied to when validating indices, so
+    /// that reading [`MAX_READ`] bytes of them stays in bounds.
+    tail_slot: Option<ir::StackSlot>,
 }
 
 struct Vars {
@@ -43,11 +54,13 @@ struct Vars {
     data_ptr: ir::Value,
     vertex_ptr: ir::Value,
     mtx_set_marked: ir::Value,
+    violations: ir::Value,
 }
 
 pub struct ParserBuilder<'ctx> {
     bd: frontend::FunctionBuilder<'ctx>,
     config: Config,
+    validate_indices: bool,
     consts: Consts,
     vars: Vars,
     current_bb: ir::Block,
@@ -89,6 +102,14 @@ impl<'ctx> ParserBuilder<'ctx> {
             offset_of!(UnpackedDefaultMatrices, tex) as i32,
         );
 
+        let tail_slot = compiler.settings.validate_indices.then(|| {
+            bd.create_sized_stack_slot(ir::StackSlotData::new(
+                ir::StackSlotKind::ExplicitSlot,
+                MAX_READ as u32,
+                4,
+            ))
+        });
+
         let consts = Consts {
             ptr_type,
 
@@ -100,10 +121,12 @@ impl<'ctx> ParserBuilder<'ctx> {
             vertices_ptr,
             mtx_set_ptr: mtx_map_ptr,
             count,
+            tail_slot,
         };
 
         let zero = bd.ins().iconst(ir::types::I64, 0);
         let mtx_set_marked = bd.ins().scalar_to_vector(ir::types::I64X2, zero);
+        let violations = bd.ins().iconst(ir::types::I32, 0);
 
         let arrays = FxHashMap::default();
         let vars = Vars {
@@ -111,11 +134,13 @@ impl<'ctx> ParserBuilder<'ctx> {
             data_ptr,
             vertex_ptr: vertices_ptr,
             mtx_set_marked,
+            violations,
         };
 
         Self {
             bd,
             config,
+            validate_indices: compiler.settings.validate_indices,
             consts,
             vars,
             current_bb: entry_bb,
@@ -178,13 +203,17 @@ impl<'ctx> ParserBuilder<'ctx> {
 
         let index = self.bd.ins().uextend(ir::types::I32, index);
 
-        // compute address
-        let offset = self.bd.ins().imul(index, array.stride);
-        let addr = self.bd.ins().iadd(array.base, offset);
+        let ptr = if self.validate_indices {
+            self.checked_element_ptr(A::ARRAY_OFFSET, descriptor.size(), index)
+        } else {
+            // compute address
+            let offset = self.bd.ins().imul(index, array.stride);
+            let addr = self.bd.ins().iadd(array.base, offset);
 
-        // compute ptr
-        let addr = self.bd.ins().uextend(self.consts.ptr_type, addr);
-        let ptr = self.bd.ins().iadd(self.consts.ram_ptr, addr);
+            // compute ptr
+            let addr = self.bd.ins().uextend(self.consts.ptr_type, addr);
+            self.bd.ins().iadd(self.consts.ram_ptr, addr)
+        };
 
         // parse
         A::parse(&descriptor, self, ptr);
@@ -194,6 +223,118 @@ impl<'ctx> ParserBuilder<'ctx> {
             .iadd_imm(self.vars.data_ptr, index_ty.bytes() as i64);
     }
 
+    /// Computes a pointer to the element at `index` of the array at `array_offset`, checking that
+    /// its `size` bytes are in bounds of RAM. Elements out of bounds are read from [`ZEROES`]
+    /// instead and counted as violations.
+    ///
+    /// Since attributes are parsed by loading [`MAX_READ`] bytes, elements in the last
+    /// [`MAX_READ`] bytes of RAM are copied to a zero padded stack slot first. Both of these cases
+    /// are handled in cold blocks, so the fast path is a compare and a branch which is never taken.
+    fn checked_element_ptr(
+        &mut self,
+        array_offset: usize,
+        size: u32,
+        index: ir::Value,
+    ) -> ir::Value {
+        let ptr_type = self.consts.ptr_type;
+        let array = &self.vars.arrays[&array_offset];
+
+        // compute address, without wrapping around
+        let index = self.bd.ins().uextend(ptr_type, index);
+        let offset = self.bd.ins().imul(index, array.stride);
+        let addr = self.bd.ins().iadd(array.base, offset);
+        let ptr = self.bd.ins().iadd(self.consts.ram_ptr, addr);
+
+        let slow_bb = self.bd.create_block();
+        self.bd.set_cold_block(slow_bb);
+
+        let tail_bb = self.bd.create_block();
+        self.bd.set_cold_block(tail_bb);
+
+        let violation_bb = self.bd.create_block();
+        self.bd.set_cold_block(violation_bb);
+
+        let checked_bb = self.bd.create_block();
+        self.bd.append_block_param(checked_bb, ptr_type); // ptr
+        self.bd.append_block_param(checked_bb, ir::types::I32); // violations
+
+        // fast path: the whole read is in bounds
+        let fast = self.bd.ins().icmp_imm(
+            ir::condcodes::IntCC::UnsignedLessThanOrEqual,
+            addr,
+            (RAM_LEN - MAX_READ) as i64,
+        );
+
+        self.bd.ins().brif(
+            fast,
+            checked_bb,
+            &[
+                ir::BlockArg::Value(ptr),
+                ir::BlockArg::Value(self.vars.violations),
+            ],
+            slow_bb,
+            &[],
+        );
+
+        // check the element itself
+        self.bd.seal_block(slow_bb);
+        self.switch_to_bb(slow_bb);
+        let in_bounds = self.bd.ins().icmp_imm(
+            ir::condcodes::IntCC::UnsignedLessThanOrEqual,
+            addr,
+            (RAM_LEN as i64) - size as i64,
+        );
+        self.bd
+            .ins()
+            .brif(in_bounds, tail_bb, &[], violation_bb, &[]);
+
+        // in bounds, but close to the end: copy it to the tail slot
+        self.bd.seal_block(tail_bb);
+        self.switch_to_bb(tail_bb);
+        let slot = self.consts.tail_slot.unwrap();
+        let zero = self.bd.ins().iconst(ir::types::I64, 0);
+        for offset in (0..MAX_READ).step_by(8) {
+            self.bd.ins().stack_store(zero, slot, offset as i32);
+        }
+
+        for offset in 0..size as i32 {
+            let byte = self
+                .bd
+                .ins()
+                .load(ir::types::I8, MEMFLAGS_READONLY, ptr, offset);
+            self.bd.ins().stack_store(byte, slot, offset);
+        }
+
+        let slot_ptr = self.bd.ins().stack_addr(ptr_type, slot, 0);
+        self.bd.ins().jump(
+            checked_bb,
+            &[
+                ir::BlockArg::Value(slot_ptr),
+                ir::BlockArg::Value(self.vars.violations),
+            ],
+        );
+
+        // out of bounds: read zeroes instead
+        self.bd.seal_block(violation_bb);
+        self.switch_to_bb(violation_bb);
+        let zeroes_ptr = self.bd.ins().iconst(ptr_type, ZEROES.as_ptr() as i64);
+        let violations = self.bd.ins().iadd_imm(self.vars.violations, 1);
+        self.bd.ins().jump(
+            checked_bb,
+            &[
+                ir::BlockArg::Value(zeroes_ptr),
+                ir::BlockArg::Value(violations),
+            ],
+        );
+
+        self.bd.seal_block(checked_bb);
+        self.switch_to_bb(checked_bb);
+        let params = self.bd.block_params(checked_bb);
+        self.vars.violations = params[1];
+
+        params[0]
+    }
+
     fn parse<A: AttributeExt>(&mut self) {
         let mode = A::get_mode(&self.config.vcd);
         match mode {
@@ -233,6 +374,16 @@ impl<'ctx> ParserBuilder<'ctx> {
             (A::ARRAY_OFFSET + offset_of!(ArrayDescriptor, stride)) as i32,
         );
 
+        // validated parsers compute addresses in the pointer type, so that they can't wrap around
+        let (base, stride) = if self.validate_indices {
+            (
+                self.bd.ins().uextend(self.consts.ptr_type, base),
+                self.bd.ins().uextend(self.consts.ptr_type, stride),
+            )
+        } else {
+            (base, stride)
+        };
+
         self.vars
             .arrays
             .insert(A::ARRAY_OFFSET, Array { base, stride });
@@ -297,12 +448,14 @@ impl<'ctx> ParserBuilder<'ctx> {
         self.bd.append_block_param(iter_bb, self.consts.ptr_type); // vertex ptr
         self.bd.append_block_param(iter_bb, ir::types::I64X2); // matrix set marked
         self.bd.append_block_param(iter_bb, ir::types::I32); // loop iter
+        self.bd.append_block_param(iter_bb, ir::types::I32); // violations
 
         let body_bb = self.bd.create_block();
 
         let exit_bb = self.bd.create_block();
         self.bd.set_cold_block(exit_bb);
         self.bd.append_block_param(exit_bb, ir::types::I64X2); // matrix set marked
+        self.bd.append_block_param(exit_bb, ir::types::I32); // violations
 
         let zero_32 = self.bd.ins().iconst(ir::types::I32, 0);
         self.bd.ins().jump(
@@ -312,6 +465,7 @@ impl<'ctx> ParserBuilder<'ctx> {
                 ir::BlockArg::Value(self.consts.vertices_ptr),
                 ir::BlockArg::Value(self.vars.mtx_set_marked),
                 ir::BlockArg::Value(zero_32),
+                ir::BlockArg::Value(self.vars.violations),
             ],
         );
 
@@ -322,6 +476,7 @@ impl<'ctx> ParserBuilder<'ctx> {
         self.vars.vertex_ptr = params[1];
         self.vars.mtx_set_marked = params[2];
         let loop_iter = params[3];
+        self.vars.violations = params[4];
 
         // first, check if loop iter < count, otherwise exit
         let loop_cond = self.bd.ins().icmp(
@@ -334,7 +489,10 @@ impl<'ctx> ParserBuilder<'ctx> {
             body_bb,
             &[],
             exit_bb,
-            &[ir::BlockArg::Value(self.vars.mtx_set_marked)],
+            &[
+                ir::BlockArg::Value(self.vars.mtx_set_marked),
+                ir::BlockArg::Value(self.vars.violations),
+            ],
         );
 
         self.bd.seal_block(body_bb);
@@ -357,6 +515,7 @@ impl<'ctx> ParserBuilder<'ctx> {
                 ir::BlockArg::Value(self.vars.vertex_ptr),
                 ir::BlockArg::Value(self.vars.mtx_set_marked),
                 ir::BlockArg::Value(loop_iter),
+                ir::BlockArg::Value(self.vars.violations),
             ],
         );
 
@@ -364,7 +523,9 @@ impl<'ctx> ParserBuilder<'ctx> {
 
         // exit
         self.switch_to_bb(exit_bb);
-        let mtx_set_marked = self.bd.block_params(exit_bb)[0];
+        let params = self.bd.block_params(exit_bb);
+        let mtx_set_marked = params[0];
+        let violations = params[1];
 
         // flush matrix set
         let curr = self
@@ -376,7 +537,7 @@ impl<'ctx> ParserBuilder<'ctx> {
             .ins()
             .store(MEMFLAGS, new, self.consts.mtx_set_ptr, 0);
 
-        self.bd.ins().return_(&[]);
+        self.bd.ins().return_(&[violations]);
         self.bd.finalize();
     }
 }
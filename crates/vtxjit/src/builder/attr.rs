@@ -2,8 +2,8 @@ use cranelift::codegen::ir;
 use cranelift::prelude::InstBuilder;
 use lazuli::system::gx::Vertex;
 use lazuli::system::gx::cmd::attributes::{
-    self, Attribute, AttributeDescriptor, ColorFormat, ColorKind, CoordsFormat, PositionKind,
-    TexCoordsKind,
+    self, Attribute, AttributeDescriptor, ColorFormat, ColorKind, CoordsFormat, NormalKind,
+    PositionKind, TexCoordsKind,
 };
 use lazuli::system::gx::cmd::{ArrayDescriptor, Arrays};
 use util::offset_of;
@@ -617,6 +617,18 @@ impl AttributeExt for attributes::Position {
     }
 }
 
+/// Stores a vector at the given offset of the vertex being parsed.
+fn store_vec3(parser: &mut ParserBuilder, [x, y, z]: [ir::Value; 3], offset: usize) {
+    for (i, value) in [x, y, z].into_iter().enumerate() {
+        parser.bd.ins().store(
+            MEMFLAGS,
+            value,
+            parser.vars.vertex_ptr,
+            (offset + i * size_of::<f32>()) as i32,
+        );
+    }
+}
+
 impl AttributeExt for attributes::Normal {
     const ARRAY_OFFSET: usize = offset_of!(Arrays, normal);
 
@@ -634,31 +646,30 @@ impl AttributeExt for attributes::Normal {
         let scale = 1.0 / 2.0f32.powi(exp);
         let scale = parser.bd.ins().f32const(scale);
 
-        let [x, y, z] = match ty {
-            ir::types::I8 | ir::types::I16 => vec_int(parser, ptr, ty, signed, true, scale),
-            _ => vec_float(parser, ptr, true),
-        };
+        let vec = |parser: &mut ParserBuilder, index: u32| {
+            let ptr = parser
+                .bd
+                .ins()
+                .iadd_imm(ptr, (index * 3 * ty.bytes()) as i64);
 
-        parser.bd.ins().store(
-            MEMFLAGS,
-            x,
-            parser.vars.vertex_ptr,
-            offset_of!(Vertex, normal.x) as i32,
-        );
+            match ty {
+                ir::types::I8 | ir::types::I16 => vec_int(parser, ptr, ty, signed, true, scale),
+                _ => vec_float(parser, ptr, true),
+            }
+        };
 
-        parser.bd.ins().store(
-            MEMFLAGS,
-            y,
-            parser.vars.vertex_ptr,
-            offset_of!(Vertex, normal.y) as i32,
-        );
+        let normal = vec(parser, 0);
+        let (binormal, tangent) = match desc.kind() {
+            NormalKind::N3 => {
+                let zero = parser.bd.ins().f32const(0.0);
+                ([zero; 3], [zero; 3])
+            }
+            NormalKind::N9 => (vec(parser, 1), vec(parser, 2)),
+        };
 
-        parser.bd.ins().store(
-            MEMFLAGS,
-            z,
-            parser.vars.vertex_ptr,
-            offset_of!(Vertex, normal.z) as i32,
-        );
+        store_vec3(parser, normal, offset_of!(Vertex, normal));
+        store_vec3(parser, binormal, offset_of!(Vertex, binormal));
+        store_vec3(parser, tangent, offset_of!(Vertex, tangent));
 
         desc.size()
     }
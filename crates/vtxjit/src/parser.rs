@@ -19,7 +19,7 @@ impl Config {
     }
 }
 
-// ram, arrays, default matrices, data, vertices, matrix map, count
+// ram, arrays, default matrices, data, vertices, matrix map, count -> violations
 pub type ParserFn = extern "sysv64" fn(
     *const u8,
     *const Arrays,
@@ -28,7 +28,7 @@ pub type ParserFn = extern "sysv64" fn(
     *mut Vertex,
     *mut MatrixSet,
     u32,
-);
+) -> u32;
 
 pub struct VertexParser {
     code: Allocation<Exec>,